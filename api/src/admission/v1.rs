@@ -0,0 +1,162 @@
+//! The requests and responses of admission webhooks.
+
+use crate::meta::v1::Status;
+use crate::meta::GroupVersion;
+use crate::{TypeMeta, TypeMetaImpl};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const API_GROUP: &str = "admission.k8s.io/v1";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "admission.k8s.io",
+    version: "v1",
+};
+
+/// What the apiserver sends a webhook (with `request`), and the webhook
+/// answers (with `response`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionReview {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<AdmissionReview>,
+    pub request: Option<AdmissionRequest>,
+    pub response: Option<AdmissionResponse>,
+}
+
+impl AdmissionReview {
+    /// A review answering a request.
+    pub fn new(response: AdmissionResponse) -> Self {
+        AdmissionReview {
+            typemeta: TypeMetaImpl::default(),
+            request: None,
+            response: Some(response),
+        }
+    }
+}
+
+impl TypeMeta for AdmissionReview {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "AdmissionReview"
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionRequest {
+    /// Copied to the response.
+    pub uid: String,
+    /// The kind of `object`, which may be a different version of the
+    /// `request_kind` that was sent to the apiserver.
+    pub kind: GroupVersionKind,
+    pub resource: GroupVersionResource,
+    pub sub_resource: Option<String>,
+    pub request_kind: Option<GroupVersionKind>,
+    pub request_resource: Option<GroupVersionResource>,
+    pub request_sub_resource: Option<String>,
+    /// Not set for creates with `generateName`.
+    pub name: Option<String>,
+    pub namespace: Option<String>,
+    pub operation: Operation,
+    pub user_info: UserInfo,
+    /// The new object; not set for deletes.
+    pub object: Option<Value>,
+    /// The existing object, for updates and deletes.
+    pub old_object: Option<Value>,
+    /// Whether changes will not be persisted, so the webhook must not
+    /// have other side effects.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// The options of the operation, eg: a `CreateOptions`.
+    pub options: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupVersionKind {
+    #[serde(default)]
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupVersionResource {
+    #[serde(default)]
+    pub group: String,
+    pub version: String,
+    pub resource: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Operation {
+    #[default]
+    Create,
+    Update,
+    Delete,
+    Connect,
+}
+
+/// Who made the request.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInfo {
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub uid: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub extra: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionResponse {
+    /// The request's `uid`.
+    pub uid: String,
+    pub allowed: bool,
+    /// Why the request was denied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    /// A base64 encoded JSON patch to apply to the object.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch: Option<String>,
+    /// "JSONPatch", if there is a `patch`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch_type: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub audit_annotations: BTreeMap<String, String>,
+    /// Shown to the user.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[test]
+fn deser_review() {
+    let review: AdmissionReview = serde_json::from_value(json!({
+        "apiVersion": "admission.k8s.io/v1",
+        "kind": "AdmissionReview",
+        "request": {
+            "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+            "kind": {"group": "", "version": "v1", "kind": "Pod"},
+            "resource": {"group": "", "version": "v1", "resource": "pods"},
+            "namespace": "default",
+            "operation": "CREATE",
+            "userInfo": {"username": "admin", "groups": ["system:authenticated"]},
+            "object": {"apiVersion": "v1", "kind": "Pod"},
+            "oldObject": null,
+            "dryRun": false,
+        },
+    }))
+    .unwrap();
+    let request = review.request.unwrap();
+    assert_eq!(request.operation, Operation::Create);
+    assert_eq!(request.kind.kind, "Pod");
+    assert_eq!(request.user_info.username, "admin");
+    assert_eq!(request.object.unwrap()["kind"], "Pod");
+    assert_eq!(request.old_object, None);
+}
@@ -0,0 +1,206 @@
+use crate::core::v1::{Resource, TypedResource};
+use crate::meta::v1::{ItemList, Metadata, MetadataMut, ObjectMeta, StatusCondition};
+use crate::meta::{GroupVersion, GroupVersionResource};
+use crate::{Time, TypeMeta, TypeMetaImpl};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+const API_GROUP: &str = "apiextensions.k8s.io/v1beta1";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "apiextensions.k8s.io",
+    version: "v1beta1",
+};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceDefinition {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<CustomResourceDefinition>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub spec: CustomResourceDefinitionSpec,
+    #[serde(default)]
+    pub status: Option<CustomResourceDefinitionStatus>,
+}
+
+pub type CustomResourceDefinitionList = ItemList<CustomResourceDefinition>;
+
+impl TypeMeta for CustomResourceDefinition {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "CustomResourceDefinition"
+    }
+}
+
+impl Metadata for CustomResourceDefinition {
+    fn api_version(&self) -> &str {
+        <CustomResourceDefinition as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <CustomResourceDefinition as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for CustomResourceDefinition {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl Resource for CustomResourceDefinition {
+    type List = CustomResourceDefinitionList;
+
+    fn gvr(&self) -> GroupVersionResource {
+        GROUP_VERSION.with_resource("customresourcedefinitions")
+    }
+}
+
+impl TypedResource for CustomResourceDefinition {
+    type List = CustomResourceDefinitionList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "customresourcedefinitions",
+    };
+    const NAMESPACED: bool = false;
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceDefinitionSpec {
+    pub group: String,
+    pub version: Option<String>,
+    pub names: CustomResourceDefinitionNames,
+    pub scope: ResourceScope,
+    pub validation: Option<CustomResourceValidation>,
+    pub subresources: Option<CustomResourceSubresources>,
+    #[serde(default)]
+    pub versions: Vec<CustomResourceDefinitionVersion>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceDefinitionNames {
+    pub plural: String,
+    pub singular: Option<String>,
+    #[serde(default)]
+    pub short_names: Vec<String>,
+    pub kind: String,
+    pub list_kind: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub enum ResourceScope {
+    Cluster,
+    #[default]
+    Namespaced,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceValidation {
+    #[serde(rename = "openAPIV3Schema")]
+    pub open_api_v3_schema: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceSubresources {
+    /// Enables the status subresource.  Always an empty object.
+    pub status: Option<Map<String, Value>>,
+    pub scale: Option<CustomResourceSubresourceScale>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceSubresourceScale {
+    pub spec_replicas_path: String,
+    pub status_replicas_path: String,
+    pub label_selector_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceDefinitionVersion {
+    pub name: String,
+    pub served: bool,
+    pub storage: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceDefinitionStatus {
+    #[serde(default)]
+    pub conditions: Vec<CustomResourceDefinitionCondition>,
+    pub accepted_names: CustomResourceDefinitionNames,
+    #[serde(default)]
+    pub stored_versions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResourceDefinitionCondition {
+    #[serde(rename = "type")]
+    pub typ: CustomResourceDefinitionConditionType,
+    pub status: crate::core::v1::ConditionStatus,
+    pub last_transition_time: Option<Time>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CustomResourceDefinitionConditionType {
+    Established,
+    NamesAccepted,
+    Terminating,
+}
+
+impl StatusCondition for CustomResourceDefinitionCondition {
+    type Type = CustomResourceDefinitionConditionType;
+    fn condition_type(&self) -> &CustomResourceDefinitionConditionType {
+        &self.typ
+    }
+    fn condition_status(&self) -> crate::core::v1::ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
+
+#[test]
+fn deser_crd() {
+    let yaml = r#"
+      apiVersion: apiextensions.k8s.io/v1beta1
+      kind: CustomResourceDefinition
+      metadata:
+        name: crontabs.stable.example.com
+      spec:
+        group: stable.example.com
+        versions:
+          - name: v1
+            served: true
+            storage: true
+        scope: Namespaced
+        names:
+          plural: crontabs
+          singular: crontab
+          kind: CronTab
+          shortNames:
+          - ct
+        "#;
+
+    let crd: CustomResourceDefinition = ::serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(crd.spec.names.kind, "CronTab");
+    assert_eq!(crd.spec.names.short_names, vec!["ct"]);
+    assert_eq!(crd.spec.scope, ResourceScope::Namespaced);
+    assert_eq!(crd.spec.versions[0].name, "v1");
+}
@@ -0,0 +1,175 @@
+use crate::core::v1::{ConditionStatus, Resource, TypedResource};
+use crate::meta::v1::{ItemList, Metadata, MetadataMut, ObjectMeta, StatusCondition};
+use crate::meta::{GroupVersion, GroupVersionResource};
+use crate::{Integer, Time, TypeMeta, TypeMetaImpl};
+use std::borrow::Cow;
+
+const API_GROUP: &str = "apiregistration.k8s.io/v1";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "apiregistration.k8s.io",
+    version: "v1",
+};
+
+/// Registers an aggregated API server for a group version.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct APIService {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<APIService>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub spec: APIServiceSpec,
+    #[serde(default)]
+    pub status: Option<APIServiceStatus>,
+}
+
+pub type APIServiceList = ItemList<APIService>;
+
+impl TypeMeta for APIService {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "APIService"
+    }
+}
+
+impl Metadata for APIService {
+    fn api_version(&self) -> &str {
+        <APIService as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <APIService as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for APIService {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl Resource for APIService {
+    type List = APIServiceList;
+
+    fn gvr(&self) -> GroupVersionResource {
+        GROUP_VERSION.with_resource("apiservices")
+    }
+}
+
+impl TypedResource for APIService {
+    type List = APIServiceList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "apiservices",
+    };
+    const NAMESPACED: bool = false;
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct APIServiceSpec {
+    /// The service backing this API.  None means the local apiserver
+    /// handles the group version itself.
+    pub service: Option<ServiceReference>,
+    pub group: String,
+    pub version: String,
+    #[serde(default, rename = "insecureSkipTLSVerify")]
+    pub insecure_skip_tls_verify: bool,
+    /// PEM CA bundle, base64 encoded.
+    pub ca_bundle: Option<String>,
+    pub group_priority_minimum: Integer,
+    pub version_priority: Integer,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceReference {
+    pub namespace: Option<String>,
+    pub name: Option<String>,
+    pub port: Option<Integer>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct APIServiceStatus {
+    #[serde(default)]
+    pub conditions: Vec<APIServiceCondition>,
+}
+
+impl APIServiceStatus {
+    /// Whether the aggregated API is reachable.
+    pub fn available(&self) -> bool {
+        self.conditions.iter().any(|c| {
+            c.typ == APIServiceConditionType::Available && c.status == ConditionStatus::True
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct APIServiceCondition {
+    #[serde(rename = "type")]
+    pub typ: APIServiceConditionType,
+    pub status: ConditionStatus,
+    pub last_transition_time: Option<Time>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum APIServiceConditionType {
+    Available,
+}
+
+impl StatusCondition for APIServiceCondition {
+    type Type = APIServiceConditionType;
+    fn condition_type(&self) -> &APIServiceConditionType {
+        &self.typ
+    }
+    fn condition_status(&self) -> ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
+
+#[test]
+fn deser_apiservice() {
+    let yaml = r#"
+      apiVersion: apiregistration.k8s.io/v1
+      kind: APIService
+      metadata:
+        name: v1beta1.metrics.k8s.io
+      spec:
+        service:
+          name: metrics-server
+          namespace: kube-system
+        group: metrics.k8s.io
+        version: v1beta1
+        insecureSkipTLSVerify: true
+        groupPriorityMinimum: 100
+        versionPriority: 100
+      status:
+        conditions:
+        - type: Available
+          status: "True"
+          reason: Passed
+          message: all checks passed
+        "#;
+
+    let svc: APIService = ::serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(svc.spec.group, "metrics.k8s.io");
+    assert!(svc.spec.insecure_skip_tls_verify);
+    assert_eq!(
+        svc.spec.service.as_ref().unwrap().name.as_ref().unwrap(),
+        "metrics-server"
+    );
+    assert!(svc.status.unwrap().available());
+}
@@ -0,0 +1,509 @@
+use super::v1beta2;
+use crate::core::v1::{ConditionStatus, PodTemplateSpec, RestartPolicy, TypedResource};
+use crate::meta::v1::{
+    ItemList, LabelSelector, Metadata, MetadataMut, ObjectMeta, StatusCondition,
+};
+use crate::meta::{GroupVersion, GroupVersionResource};
+use crate::validation::{check_non_negative, child, FieldError, FieldErrorType, Validate};
+use crate::{IntOrString, Integer, SetDefaults, Time, TypeMeta, TypeMetaImpl};
+use std::borrow::Cow;
+
+const API_GROUP: &str = "apps/v1";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "apps",
+    version: "v1",
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<Deployment>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub spec: DeploymentSpec,
+    pub status: DeploymentStatus,
+}
+
+pub type DeploymentList = ItemList<Deployment>;
+
+impl TypeMeta for Deployment {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "Deployment"
+    }
+}
+
+impl Metadata for Deployment {
+    fn api_version(&self) -> &str {
+        <Deployment as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <Deployment as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for Deployment {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl TypedResource for Deployment {
+    type List = DeploymentList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "deployments",
+    };
+    const NAMESPACED: bool = true;
+}
+
+impl Deployment {
+    /// Whether the deployment has its minimum number of replicas
+    /// available.
+    pub fn is_available(&self) -> bool {
+        self.status.conditions.iter().any(|c| {
+            c.typ == DeploymentConditionType::Available && c.status == ConditionStatus::True
+        })
+    }
+}
+
+impl SetDefaults for Deployment {
+    fn set_defaults(&mut self) {
+        self.spec.strategy.set_defaults();
+        self.spec.template.set_defaults();
+    }
+}
+
+impl Validate for Deployment {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        self.metadata.validate_at(&child(path, "metadata"), errors);
+        let spec = child(path, "spec");
+        check_non_negative(&child(&spec, "replicas"), self.spec.replicas.into(), errors);
+        check_selector(&spec, &self.spec.selector, &self.spec.template, errors);
+        let template = child(&spec, "template");
+        self.spec.template.validate_at(&template, errors);
+        if self.spec.template.spec.restart_policy != RestartPolicy::Always {
+            errors.push(FieldError::new(
+                &child(&template, "spec.restartPolicy"),
+                FieldErrorType::NotSupported,
+                format!(
+                    "{:?}: supported values: \"Always\"",
+                    self.spec.template.spec.restart_policy
+                ),
+            ));
+        }
+    }
+}
+
+/// The selector at `spec.selector` must be non-empty, and match the
+/// labels of `template`.
+fn check_selector(
+    spec: &str,
+    selector: &LabelSelector,
+    template: &PodTemplateSpec,
+    errors: &mut Vec<FieldError>,
+) {
+    if selector.match_labels.is_empty() && selector.match_expressions.is_empty() {
+        errors.push(FieldError::new(
+            &child(spec, "selector"),
+            FieldErrorType::Required,
+            "",
+        ));
+        return;
+    }
+    let labels = &template.metadata.labels;
+    let matches = selector
+        .match_labels
+        .iter()
+        .all(|(k, v)| labels.get(k).map(String::as_str) == v.as_str());
+    if !matches {
+        errors.push(FieldError::new(
+            &child(spec, "template.metadata.labels"),
+            FieldErrorType::Invalid,
+            "`selector` does not match template `labels`",
+        ));
+    }
+}
+
+fn int1() -> Integer {
+    1
+}
+fn int10() -> Integer {
+    10
+}
+fn int600() -> Integer {
+    600
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentSpec {
+    #[serde(default)]
+    pub min_ready_seconds: Integer,
+    pub paused: bool,
+    #[serde(default = "int600")]
+    pub progress_deadline_seconds: Integer,
+    #[serde(default = "int1")]
+    pub replicas: Integer,
+    #[serde(default = "int10")]
+    pub revision_history_limit: Integer,
+    pub selector: LabelSelector,
+    #[serde(default)]
+    pub strategy: DeploymentStrategy,
+    pub template: PodTemplateSpec,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentStrategy {
+    #[serde(rename = "type")]
+    pub typ: DeploymentStrategyType,
+    pub rolling_update: Option<RollingUpdateDeployment>,
+}
+
+impl Default for DeploymentStrategy {
+    fn default() -> Self {
+        DeploymentStrategy {
+            typ: DeploymentStrategyType::RollingUpdate,
+            rolling_update: None,
+        }
+    }
+}
+
+impl SetDefaults for DeploymentStrategy {
+    /// A rolling update surges, and may leave unavailable, up to 25% of
+    /// the replicas.
+    fn set_defaults(&mut self) {
+        if self.typ != DeploymentStrategyType::RollingUpdate {
+            return;
+        }
+        let rolling_update = self.rolling_update.get_or_insert(RollingUpdateDeployment {
+            max_surge: None,
+            max_unavailable: None,
+        });
+        let quarter = || IntOrString::String("25%".to_string());
+        rolling_update.max_surge.get_or_insert_with(quarter);
+        rolling_update.max_unavailable.get_or_insert_with(quarter);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DeploymentStrategyType {
+    Recreate,
+    RollingUpdate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RollingUpdateDeployment {
+    pub max_surge: Option<IntOrString>,
+    pub max_unavailable: Option<IntOrString>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentStatus {
+    pub available_replicas: Integer,
+    pub collision_count: Integer,
+    #[serde(default)]
+    pub conditions: Vec<DeploymentCondition>,
+    pub observed_generation: Integer,
+    pub ready_replicas: Integer,
+    pub replicas: Integer,
+    pub unavailable_replicas: Integer,
+    pub updated_replicas: Integer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentCondition {
+    #[serde(rename = "type")]
+    pub typ: DeploymentConditionType,
+    pub status: ConditionStatus,
+    pub last_update_time: Option<Time>,
+    pub last_transition_time: Option<Time>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DeploymentConditionType {
+    Available,
+    Progressing,
+    ReplicaFailure,
+}
+
+impl StatusCondition for DeploymentCondition {
+    type Type = DeploymentConditionType;
+    fn condition_type(&self) -> &DeploymentConditionType {
+        &self.typ
+    }
+    fn condition_status(&self) -> ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
+
+// Conversions from and to apps/v1beta2, which has the same schema.
+
+impl From<v1beta2::Deployment> for Deployment {
+    fn from(d: v1beta2::Deployment) -> Self {
+        Deployment {
+            typemeta: TypeMetaImpl::default(),
+            metadata: d.metadata,
+            spec: d.spec.into(),
+            status: d.status.into(),
+        }
+    }
+}
+
+impl From<Deployment> for v1beta2::Deployment {
+    fn from(d: Deployment) -> Self {
+        v1beta2::Deployment {
+            typemeta: TypeMetaImpl::default(),
+            metadata: d.metadata,
+            spec: d.spec.into(),
+            status: d.status.into(),
+        }
+    }
+}
+
+impl From<v1beta2::DeploymentSpec> for DeploymentSpec {
+    fn from(s: v1beta2::DeploymentSpec) -> Self {
+        DeploymentSpec {
+            min_ready_seconds: s.min_ready_seconds,
+            paused: s.paused,
+            progress_deadline_seconds: s.progress_deadline_seconds,
+            replicas: s.replicas,
+            revision_history_limit: s.revision_history_limit,
+            selector: s.selector,
+            strategy: DeploymentStrategy {
+                typ: match s.strategy.typ {
+                    v1beta2::DeploymentStrategyType::Recreate => DeploymentStrategyType::Recreate,
+                    v1beta2::DeploymentStrategyType::RollingUpdate => {
+                        DeploymentStrategyType::RollingUpdate
+                    }
+                },
+                rolling_update: s.strategy.rolling_update.map(|r| RollingUpdateDeployment {
+                    max_surge: r.max_surge,
+                    max_unavailable: r.max_unavailable,
+                }),
+            },
+            template: s.template,
+        }
+    }
+}
+
+impl From<DeploymentSpec> for v1beta2::DeploymentSpec {
+    fn from(s: DeploymentSpec) -> Self {
+        v1beta2::DeploymentSpec {
+            min_ready_seconds: s.min_ready_seconds,
+            paused: s.paused,
+            progress_deadline_seconds: s.progress_deadline_seconds,
+            replicas: s.replicas,
+            revision_history_limit: s.revision_history_limit,
+            selector: s.selector,
+            strategy: v1beta2::DeploymentStrategy {
+                typ: match s.strategy.typ {
+                    DeploymentStrategyType::Recreate => v1beta2::DeploymentStrategyType::Recreate,
+                    DeploymentStrategyType::RollingUpdate => {
+                        v1beta2::DeploymentStrategyType::RollingUpdate
+                    }
+                },
+                rolling_update: s.strategy.rolling_update.map(|r| {
+                    v1beta2::RollingUpdateDeployment {
+                        max_surge: r.max_surge,
+                        max_unavailable: r.max_unavailable,
+                    }
+                }),
+            },
+            template: s.template,
+        }
+    }
+}
+
+impl From<v1beta2::DeploymentStatus> for DeploymentStatus {
+    fn from(s: v1beta2::DeploymentStatus) -> Self {
+        DeploymentStatus {
+            available_replicas: s.available_replicas,
+            collision_count: s.collision_count,
+            conditions: s.conditions.into_iter().map(From::from).collect(),
+            observed_generation: s.observed_generation,
+            ready_replicas: s.ready_replicas,
+            replicas: s.replicas,
+            unavailable_replicas: s.unavailable_replicas,
+            updated_replicas: s.updated_replicas,
+        }
+    }
+}
+
+impl From<DeploymentStatus> for v1beta2::DeploymentStatus {
+    fn from(s: DeploymentStatus) -> Self {
+        v1beta2::DeploymentStatus {
+            available_replicas: s.available_replicas,
+            collision_count: s.collision_count,
+            conditions: s.conditions.into_iter().map(From::from).collect(),
+            observed_generation: s.observed_generation,
+            ready_replicas: s.ready_replicas,
+            replicas: s.replicas,
+            unavailable_replicas: s.unavailable_replicas,
+            updated_replicas: s.updated_replicas,
+        }
+    }
+}
+
+impl From<v1beta2::DeploymentCondition> for DeploymentCondition {
+    fn from(c: v1beta2::DeploymentCondition) -> Self {
+        DeploymentCondition {
+            typ: match c.typ {
+                v1beta2::DeploymentConditionType::Available => DeploymentConditionType::Available,
+                v1beta2::DeploymentConditionType::Progressing => {
+                    DeploymentConditionType::Progressing
+                }
+                v1beta2::DeploymentConditionType::ReplicaFailure => {
+                    DeploymentConditionType::ReplicaFailure
+                }
+            },
+            status: c.status,
+            last_update_time: c.last_update_time,
+            last_transition_time: c.last_transition_time,
+            reason: c.reason,
+            message: c.message,
+        }
+    }
+}
+
+impl From<DeploymentCondition> for v1beta2::DeploymentCondition {
+    fn from(c: DeploymentCondition) -> Self {
+        v1beta2::DeploymentCondition {
+            typ: match c.typ {
+                DeploymentConditionType::Available => v1beta2::DeploymentConditionType::Available,
+                DeploymentConditionType::Progressing => {
+                    v1beta2::DeploymentConditionType::Progressing
+                }
+                DeploymentConditionType::ReplicaFailure => {
+                    v1beta2::DeploymentConditionType::ReplicaFailure
+                }
+            },
+            status: c.status,
+            last_update_time: c.last_update_time,
+            last_transition_time: c.last_transition_time,
+            reason: c.reason,
+            message: c.message,
+        }
+    }
+}
+
+#[test]
+fn convert_deployment() {
+    let old: v1beta2::Deployment = ::serde_json::from_value(json!({
+        "metadata": {"name": "web", "namespace": "default"},
+        "spec": {
+            "paused": false,
+            "replicas": 3,
+            "selector": {"matchLabels": {"app": "web"}},
+            "strategy": {"type": "RollingUpdate", "rollingUpdate": {"maxSurge": "25%"}},
+            "template": {
+                "metadata": {"labels": {"app": "web"}},
+                "spec": {"containers": [{"name": "web", "image": "nginx"}]},
+            },
+        },
+        "status": {
+            "availableReplicas": 3, "collisionCount": 0, "observedGeneration": 1,
+            "readyReplicas": 3, "replicas": 3, "unavailableReplicas": 0, "updatedReplicas": 3,
+            "conditions": [{"type": "Available", "status": "True"}],
+        },
+    }))
+    .unwrap();
+    let new = Deployment::from(old.clone());
+    assert!(new.is_available());
+    let json = ::serde_json::to_value(&new).unwrap();
+    assert_eq!(json["apiVersion"], "apps/v1");
+    assert_eq!(json["kind"], "Deployment");
+    assert_eq!(json["spec"]["strategy"]["rollingUpdate"]["maxSurge"], "25%");
+    let json = ::serde_json::to_value(v1beta2::Deployment::from(new)).unwrap();
+    assert_eq!(json["apiVersion"], "apps/v1beta2");
+    assert_eq!(json, ::serde_json::to_value(old).unwrap());
+}
+
+#[test]
+fn deployment_set_defaults() {
+    let mut d: Deployment = ::serde_json::from_value(json!({
+        "metadata": {"name": "web"},
+        "spec": {
+            "paused": false,
+            "selector": {"matchLabels": {"app": "web"}},
+            "template": {
+                "metadata": {"labels": {"app": "web"}},
+                "spec": {"containers": [{"name": "web", "image": "nginx:1.19"}]},
+            },
+        },
+        "status": {
+            "availableReplicas": 0, "collisionCount": 0, "observedGeneration": 0,
+            "readyReplicas": 0, "replicas": 0, "unavailableReplicas": 0, "updatedReplicas": 0,
+        },
+    }))
+    .unwrap();
+    d.set_defaults();
+    let json = ::serde_json::to_value(&d).unwrap();
+    assert_eq!(
+        json["spec"]["strategy"],
+        json!({"type": "RollingUpdate", "rollingUpdate": {"maxSurge": "25%", "maxUnavailable": "25%"}})
+    );
+    let pod = &json["spec"]["template"]["spec"];
+    assert_eq!(pod["schedulerName"], "default-scheduler");
+    assert_eq!(pod["containers"][0]["imagePullPolicy"], "IfNotPresent");
+}
+
+#[test]
+fn deployment_validate() {
+    let mut d: Deployment = ::serde_json::from_value(json!({
+        "metadata": {"name": "web"},
+        "spec": {
+            "paused": false,
+            "selector": {"matchLabels": {"app": "web"}},
+            "template": {
+                "metadata": {"labels": {"app": "web"}},
+                "spec": {"containers": [{"name": "web", "image": "nginx", "ports": [{"containerPort": 80}]}]},
+            },
+        },
+        "status": {
+            "availableReplicas": 0, "collisionCount": 0, "observedGeneration": 0,
+            "readyReplicas": 0, "replicas": 0, "unavailableReplicas": 0, "updatedReplicas": 0,
+        },
+    }))
+    .unwrap();
+    assert_eq!(d.validate(), Ok(()));
+
+    d.spec.replicas = -1;
+    d.spec.template.metadata.labels.clear();
+    let c = &mut d.spec.template.spec.containers;
+    c[0].image = None;
+    c[0].ports[0].container_port = 0;
+    c.push(c[0].clone());
+    let err = d.validate().unwrap_err();
+    let fields: Vec<_> = err.errors.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        vec![
+            "spec.replicas",
+            "spec.template.metadata.labels",
+            "spec.template.spec.containers[0].image",
+            "spec.template.spec.containers[0].ports[0].containerPort",
+            "spec.template.spec.containers[1].image",
+            "spec.template.spec.containers[1].ports[0].containerPort",
+            "spec.template.spec.containers[1].name",
+        ]
+    );
+    assert_eq!(err.errors[6].typ, FieldErrorType::Duplicate);
+}
@@ -1,15 +1,97 @@
-use super::super::core::v1::{ConditionStatus, PodTemplateSpec};
-use super::super::meta::v1::{LabelSelector, ObjectMeta};
-use super::super::{IntOrString, Integer, Time};
+use super::super::core::v1::{ConditionStatus, PodTemplateSpec, TypedResource};
+use super::super::meta::v1::{
+    ItemList, LabelSelector, Metadata, MetadataMut, ObjectMeta, StatusCondition,
+};
+use super::super::meta::{GroupVersion, GroupVersionResource};
+use super::super::{IntOrString, Integer, SetDefaults, Time, TypeMeta, TypeMetaImpl};
+use std::borrow::Cow;
+
+const API_GROUP: &str = "apps/v1beta2";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "apps",
+    version: "v1beta2",
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Deployment {
+    #[serde(flatten)]
+    pub(super) typemeta: TypeMetaImpl<Deployment>,
     pub metadata: ObjectMeta,
     pub spec: DeploymentSpec,
     pub status: DeploymentStatus,
 }
 
+pub type DeploymentList = ItemList<Deployment>;
+
+impl TypeMeta for Deployment {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "Deployment"
+    }
+}
+
+impl Metadata for Deployment {
+    fn api_version(&self) -> &str {
+        <Deployment as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <Deployment as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for Deployment {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl TypedResource for Deployment {
+    type List = DeploymentList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "deployments",
+    };
+    const NAMESPACED: bool = true;
+}
+
+impl SetDefaults for Deployment {
+    /// A rolling update surges, and may leave unavailable, up to 25% of
+    /// the replicas.
+    fn set_defaults(&mut self) {
+        let strategy = &mut self.spec.strategy;
+        if strategy.typ == DeploymentStrategyType::RollingUpdate {
+            let rolling_update = strategy
+                .rolling_update
+                .get_or_insert(RollingUpdateDeployment {
+                    max_surge: None,
+                    max_unavailable: None,
+                });
+            let quarter = || IntOrString::String("25%".to_string());
+            rolling_update.max_surge.get_or_insert_with(quarter);
+            rolling_update.max_unavailable.get_or_insert_with(quarter);
+        }
+        self.spec.template.set_defaults();
+    }
+}
+
+impl Deployment {
+    /// Whether the deployment has its minimum number of replicas
+    /// available.
+    pub fn is_available(&self) -> bool {
+        self.status.conditions.iter().any(|c| {
+            c.typ == DeploymentConditionType::Available && c.status == ConditionStatus::True
+        })
+    }
+}
+
 fn int1() -> Integer {
     1
 }
@@ -91,3 +173,16 @@ pub enum DeploymentConditionType {
     Progressing,
     ReplicaFailure,
 }
+
+impl StatusCondition for DeploymentCondition {
+    type Type = DeploymentConditionType;
+    fn condition_type(&self) -> &DeploymentConditionType {
+        &self.typ
+    }
+    fn condition_status(&self) -> ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
@@ -0,0 +1,193 @@
+use crate::core::v1::TypedResource;
+use crate::meta::v1::{ItemList, Metadata, MetadataMut, ObjectMeta};
+use crate::meta::{GroupVersion, GroupVersionResource};
+use crate::{Integer, Time, TypeMeta, TypeMetaImpl};
+use std::borrow::Cow;
+
+const API_GROUP: &str = "autoscaling/v1";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "autoscaling",
+    version: "v1",
+};
+
+/// The `scale` subresource of a scalable resource, eg: Deployments,
+/// ReplicaSets and StatefulSets.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Scale {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<Scale>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    #[serde(default)]
+    pub spec: ScaleSpec,
+    #[serde(default)]
+    pub status: ScaleStatus,
+}
+
+impl TypeMeta for Scale {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "Scale"
+    }
+}
+
+impl Metadata for Scale {
+    fn api_version(&self) -> &str {
+        <Scale as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <Scale as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for Scale {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaleSpec {
+    /// Desired number of replicas.
+    #[serde(default)]
+    pub replicas: Integer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaleStatus {
+    /// Actual number of replicas.
+    #[serde(default)]
+    pub replicas: Integer,
+    /// Label selector (in string form) for the pods counted in
+    /// `replicas`.
+    pub selector: Option<String>,
+}
+
+/// Scales a resource's replicas to keep its pods' average CPU
+/// utilization near a target.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscaler {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<HorizontalPodAutoscaler>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub spec: HorizontalPodAutoscalerSpec,
+    #[serde(default)]
+    pub status: Option<HorizontalPodAutoscalerStatus>,
+}
+
+pub type HorizontalPodAutoscalerList = ItemList<HorizontalPodAutoscaler>;
+
+impl HorizontalPodAutoscaler {
+    pub fn new(metadata: ObjectMeta, spec: HorizontalPodAutoscalerSpec) -> Self {
+        HorizontalPodAutoscaler {
+            typemeta: TypeMetaImpl::default(),
+            metadata,
+            spec,
+            status: None,
+        }
+    }
+}
+
+impl TypeMeta for HorizontalPodAutoscaler {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "HorizontalPodAutoscaler"
+    }
+}
+
+impl Metadata for HorizontalPodAutoscaler {
+    fn api_version(&self) -> &str {
+        <HorizontalPodAutoscaler as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <HorizontalPodAutoscaler as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for HorizontalPodAutoscaler {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl TypedResource for HorizontalPodAutoscaler {
+    type List = HorizontalPodAutoscalerList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "horizontalpodautoscalers",
+    };
+    const NAMESPACED: bool = true;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscalerSpec {
+    pub scale_target_ref: CrossVersionObjectReference,
+    pub min_replicas: Option<Integer>,
+    pub max_replicas: Integer,
+    /// The average CPU utilization to aim for, as a percentage of the
+    /// pods' requests.  The apiserver defaults it to 80.
+    #[serde(rename = "targetCPUUtilizationPercentage")]
+    pub target_cpu_utilization_percentage: Option<Integer>,
+}
+
+/// The resource to scale, in any API version.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrossVersionObjectReference {
+    pub kind: String,
+    pub name: String,
+    pub api_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscalerStatus {
+    pub observed_generation: Option<i64>,
+    pub last_scale_time: Option<Time>,
+    #[serde(default)]
+    pub current_replicas: Integer,
+    #[serde(default)]
+    pub desired_replicas: Integer,
+    #[serde(rename = "currentCPUUtilizationPercentage")]
+    pub current_cpu_utilization_percentage: Option<Integer>,
+}
+
+#[test]
+fn deser_scale() {
+    let scale: Scale = ::serde_json::from_str(
+        r#"{
+          "kind": "Scale",
+          "apiVersion": "autoscaling/v1",
+          "metadata": {"name": "web", "namespace": "default", "resourceVersion": "42"},
+          "spec": {"replicas": 3},
+          "status": {"replicas": 2, "selector": "app=web"}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(scale.spec.replicas, 3);
+    assert_eq!(scale.status.replicas, 2);
+    assert_eq!(scale.status.selector.as_deref(), Some("app=web"));
+
+    assert!(
+        ::serde_json::from_str::<Scale>(r#"{"kind": "Deployment", "apiVersion": "apps/v1"}"#)
+            .is_err()
+    );
+}
@@ -0,0 +1,486 @@
+use super::v1;
+use crate::core::v1::{ConditionStatus, TypedResource};
+use crate::meta::v1::{
+    ItemList, LabelSelector, Metadata, MetadataMut, ObjectMeta, StatusCondition,
+};
+use crate::meta::{GroupVersion, GroupVersionResource};
+use crate::{ConversionError, Integer, Quantity, Time, TypeMeta, TypeMetaImpl};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+pub use super::v1::CrossVersionObjectReference;
+
+const API_GROUP: &str = "autoscaling/v2";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "autoscaling",
+    version: "v2",
+};
+
+/// Scales a resource's replicas to keep any number of metrics near
+/// their targets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscaler {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<HorizontalPodAutoscaler>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub spec: HorizontalPodAutoscalerSpec,
+    #[serde(default)]
+    pub status: Option<HorizontalPodAutoscalerStatus>,
+}
+
+pub type HorizontalPodAutoscalerList = ItemList<HorizontalPodAutoscaler>;
+
+impl HorizontalPodAutoscaler {
+    pub fn new(metadata: ObjectMeta, spec: HorizontalPodAutoscalerSpec) -> Self {
+        HorizontalPodAutoscaler {
+            typemeta: TypeMetaImpl::default(),
+            metadata,
+            spec,
+            status: None,
+        }
+    }
+}
+
+impl TypeMeta for HorizontalPodAutoscaler {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "HorizontalPodAutoscaler"
+    }
+}
+
+impl Metadata for HorizontalPodAutoscaler {
+    fn api_version(&self) -> &str {
+        <HorizontalPodAutoscaler as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <HorizontalPodAutoscaler as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for HorizontalPodAutoscaler {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl TypedResource for HorizontalPodAutoscaler {
+    type List = HorizontalPodAutoscalerList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "horizontalpodautoscalers",
+    };
+    const NAMESPACED: bool = true;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscalerSpec {
+    pub scale_target_ref: CrossVersionObjectReference,
+    pub min_replicas: Option<Integer>,
+    pub max_replicas: Integer,
+    /// The apiserver defaults these to 80% average CPU utilization.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metrics: Vec<MetricSpec>,
+    pub behavior: Option<HorizontalPodAutoscalerBehavior>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricSourceType {
+    Resource,
+    ContainerResource,
+    Pods,
+    Object,
+    External,
+}
+
+/// A metric to scale on.  The field matching `typ` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricSpec {
+    #[serde(rename = "type")]
+    pub typ: MetricSourceType,
+    pub resource: Option<ResourceMetricSource>,
+    pub container_resource: Option<ContainerResourceMetricSource>,
+    pub pods: Option<PodsMetricSource>,
+    pub object: Option<ObjectMetricSource>,
+    pub external: Option<ExternalMetricSource>,
+}
+
+impl MetricSpec {
+    /// Aim for the pods' average use of `resource` (eg: "cpu") to be
+    /// `percent` of what they request.
+    pub fn resource_utilization(resource: &str, percent: Integer) -> Self {
+        MetricSpec {
+            typ: MetricSourceType::Resource,
+            resource: Some(ResourceMetricSource {
+                name: resource.to_string(),
+                target: MetricTarget {
+                    typ: MetricTargetType::Utilization,
+                    value: None,
+                    average_value: None,
+                    average_utilization: Some(percent),
+                },
+            }),
+            container_resource: None,
+            pods: None,
+            object: None,
+            external: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceMetricSource {
+    pub name: String,
+    pub target: MetricTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerResourceMetricSource {
+    pub name: String,
+    pub container: String,
+    pub target: MetricTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PodsMetricSource {
+    pub metric: MetricIdentifier,
+    pub target: MetricTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectMetricSource {
+    pub described_object: CrossVersionObjectReference,
+    pub metric: MetricIdentifier,
+    pub target: MetricTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalMetricSource {
+    pub metric: MetricIdentifier,
+    pub target: MetricTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricIdentifier {
+    pub name: String,
+    pub selector: Option<LabelSelector>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricTargetType {
+    Utilization,
+    Value,
+    AverageValue,
+}
+
+/// The target value of a metric.  The field matching `typ` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricTarget {
+    #[serde(rename = "type")]
+    pub typ: MetricTargetType,
+    pub value: Option<Quantity>,
+    pub average_value: Option<Quantity>,
+    /// As a percentage of the pods' requests.
+    pub average_utilization: Option<Integer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscalerBehavior {
+    pub scale_up: Option<HPAScalingRules>,
+    pub scale_down: Option<HPAScalingRules>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HPAScalingRules {
+    pub stabilization_window_seconds: Option<Integer>,
+    /// "Max", "Min" or "Disabled".
+    pub select_policy: Option<String>,
+    #[serde(default)]
+    pub policies: Vec<HPAScalingPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HPAScalingPolicy {
+    /// "Pods" or "Percent".
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub value: Integer,
+    pub period_seconds: Integer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscalerStatus {
+    pub observed_generation: Option<i64>,
+    pub last_scale_time: Option<Time>,
+    #[serde(default)]
+    pub current_replicas: Integer,
+    #[serde(default)]
+    pub desired_replicas: Integer,
+    #[serde(default)]
+    pub current_metrics: Vec<MetricStatus>,
+    #[serde(default)]
+    pub conditions: Vec<HorizontalPodAutoscalerCondition>,
+}
+
+/// The current value of a metric.  The field matching `typ` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricStatus {
+    #[serde(rename = "type")]
+    pub typ: MetricSourceType,
+    pub resource: Option<ResourceMetricStatus>,
+    pub container_resource: Option<ContainerResourceMetricStatus>,
+    pub pods: Option<PodsMetricStatus>,
+    pub object: Option<ObjectMetricStatus>,
+    pub external: Option<ExternalMetricStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceMetricStatus {
+    pub name: String,
+    pub current: MetricValueStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerResourceMetricStatus {
+    pub name: String,
+    pub container: String,
+    pub current: MetricValueStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PodsMetricStatus {
+    pub metric: MetricIdentifier,
+    pub current: MetricValueStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectMetricStatus {
+    pub described_object: CrossVersionObjectReference,
+    pub metric: MetricIdentifier,
+    pub current: MetricValueStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalMetricStatus {
+    pub metric: MetricIdentifier,
+    pub current: MetricValueStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricValueStatus {
+    pub value: Option<Quantity>,
+    pub average_value: Option<Quantity>,
+    pub average_utilization: Option<Integer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HorizontalPodAutoscalerCondition {
+    #[serde(rename = "type")]
+    pub typ: HorizontalPodAutoscalerConditionType,
+    pub status: ConditionStatus,
+    pub last_transition_time: Option<Time>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HorizontalPodAutoscalerConditionType {
+    AbleToScale,
+    ScalingActive,
+    ScalingLimited,
+}
+
+impl StatusCondition for HorizontalPodAutoscalerCondition {
+    type Type = HorizontalPodAutoscalerConditionType;
+    fn condition_type(&self) -> &HorizontalPodAutoscalerConditionType {
+        &self.typ
+    }
+    fn condition_status(&self) -> ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
+
+// Conversions from and to autoscaling/v1, which only knows CPU
+// utilization.
+
+impl From<v1::HorizontalPodAutoscaler> for HorizontalPodAutoscaler {
+    fn from(hpa: v1::HorizontalPodAutoscaler) -> Self {
+        let spec = hpa.spec;
+        HorizontalPodAutoscaler {
+            typemeta: TypeMetaImpl::default(),
+            metadata: hpa.metadata,
+            spec: HorizontalPodAutoscalerSpec {
+                scale_target_ref: spec.scale_target_ref,
+                min_replicas: spec.min_replicas,
+                max_replicas: spec.max_replicas,
+                metrics: spec
+                    .target_cpu_utilization_percentage
+                    .map(|percent| MetricSpec::resource_utilization("cpu", percent))
+                    .into_iter()
+                    .collect(),
+                behavior: None,
+            },
+            status: hpa.status.map(|status| HorizontalPodAutoscalerStatus {
+                observed_generation: status.observed_generation,
+                last_scale_time: status.last_scale_time,
+                current_replicas: status.current_replicas,
+                desired_replicas: status.desired_replicas,
+                current_metrics: status
+                    .current_cpu_utilization_percentage
+                    .map(|percent| MetricStatus {
+                        typ: MetricSourceType::Resource,
+                        resource: Some(ResourceMetricStatus {
+                            name: "cpu".to_string(),
+                            current: MetricValueStatus {
+                                average_utilization: Some(percent),
+                                ..Default::default()
+                            },
+                        }),
+                        container_resource: None,
+                        pods: None,
+                        object: None,
+                        external: None,
+                    })
+                    .into_iter()
+                    .collect(),
+                conditions: Vec::new(),
+            }),
+        }
+    }
+}
+
+/// The CPU utilization target in `metric`, if that is what it is.
+fn cpu_utilization(metric: &MetricSpec) -> Option<Integer> {
+    let resource = metric.resource.as_ref()?;
+    if metric.typ != MetricSourceType::Resource
+        || resource.name != "cpu"
+        || resource.target.typ != MetricTargetType::Utilization
+    {
+        return None;
+    }
+    resource.target.average_utilization
+}
+
+/// Fails if `hpa` scales on anything but CPU utilization, or has
+/// `behavior`, which autoscaling/v1 can't express.  The status
+/// conditions are dropped.
+impl TryFrom<HorizontalPodAutoscaler> for v1::HorizontalPodAutoscaler {
+    type Error = ConversionError;
+
+    fn try_from(hpa: HorizontalPodAutoscaler) -> Result<Self, ConversionError> {
+        let error = |reason: &str| ConversionError {
+            api_version: <v1::HorizontalPodAutoscaler as TypeMeta>::api_version(),
+            reason: reason.to_string(),
+        };
+        let spec = hpa.spec;
+        let target = match spec.metrics.as_slice() {
+            [] => None,
+            [metric] => Some(
+                cpu_utilization(metric)
+                    .ok_or_else(|| error("only a CPU utilization metric is supported"))?,
+            ),
+            _ => return Err(error("only a single metric is supported")),
+        };
+        if spec.behavior.is_some() {
+            return Err(error("scaling behavior is not supported"));
+        }
+        let mut v1 = v1::HorizontalPodAutoscaler::new(
+            hpa.metadata,
+            v1::HorizontalPodAutoscalerSpec {
+                scale_target_ref: spec.scale_target_ref,
+                min_replicas: spec.min_replicas,
+                max_replicas: spec.max_replicas,
+                target_cpu_utilization_percentage: target,
+            },
+        );
+        v1.status = hpa.status.map(|status| v1::HorizontalPodAutoscalerStatus {
+            observed_generation: status.observed_generation,
+            last_scale_time: status.last_scale_time,
+            current_replicas: status.current_replicas,
+            desired_replicas: status.desired_replicas,
+            current_cpu_utilization_percentage: status
+                .current_metrics
+                .iter()
+                .filter(|m| m.typ == MetricSourceType::Resource)
+                .filter_map(|m| m.resource.as_ref())
+                .find(|r| r.name == "cpu")
+                .and_then(|r| r.current.average_utilization),
+        });
+        Ok(v1)
+    }
+}
+
+#[test]
+fn convert_hpa() {
+    let old: v1::HorizontalPodAutoscaler = ::serde_json::from_value(json!({
+        "apiVersion": "autoscaling/v1",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": {"name": "web", "namespace": "default"},
+        "spec": {
+            "scaleTargetRef": {"apiVersion": "apps/v1", "kind": "Deployment", "name": "web"},
+            "minReplicas": 2,
+            "maxReplicas": 10,
+            "targetCPUUtilizationPercentage": 60,
+        },
+        "status": {"currentReplicas": 3, "desiredReplicas": 4, "currentCPUUtilizationPercentage": 75},
+    }))
+    .unwrap();
+    let new = HorizontalPodAutoscaler::from(old.clone());
+    let json = ::serde_json::to_value(&new).unwrap();
+    assert_eq!(json["apiVersion"], "autoscaling/v2");
+    let metric = &json["spec"]["metrics"][0];
+    assert_eq!(metric["type"], "Resource");
+    assert_eq!(metric["resource"]["name"], "cpu");
+    assert_eq!(metric["resource"]["target"]["type"], "Utilization");
+    assert_eq!(metric["resource"]["target"]["averageUtilization"], 60);
+    assert_eq!(
+        json["status"]["currentMetrics"][0]["resource"]["current"]["averageUtilization"],
+        75
+    );
+    assert_eq!(v1::HorizontalPodAutoscaler::try_from(new.clone()), Ok(old));
+
+    let mut memory = new.clone();
+    memory.spec.metrics = vec![MetricSpec::resource_utilization("memory", 50)];
+    let err = v1::HorizontalPodAutoscaler::try_from(memory).unwrap_err();
+    assert_eq!(err.api_version, "autoscaling/v1");
+    let mut two = new.clone();
+    two.spec
+        .metrics
+        .push(MetricSpec::resource_utilization("cpu", 50));
+    assert!(v1::HorizontalPodAutoscaler::try_from(two).is_err());
+    let mut behavior = new;
+    behavior.spec.behavior = Some(Default::default());
+    assert!(v1::HorizontalPodAutoscaler::try_from(behavior).is_err());
+}
@@ -0,0 +1,210 @@
+use crate::core::v1::{ConditionStatus, PodTemplateSpec, RestartPolicy, TypedResource};
+use crate::meta::v1::{
+    is_condition_true, ItemList, LabelSelector, Metadata, MetadataMut, ObjectMeta, StatusCondition,
+};
+use crate::meta::{GroupVersion, GroupVersionResource};
+use crate::validation::{check_non_negative, child, FieldError, FieldErrorType, Validate};
+use crate::{Integer, SetDefaults, Time, TypeMeta, TypeMetaImpl};
+use std::borrow::Cow;
+
+const API_GROUP: &str = "batch/v1";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "batch",
+    version: "v1",
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<Job>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub spec: JobSpec,
+    #[serde(default)]
+    pub status: Option<JobStatus>,
+}
+
+pub type JobList = ItemList<Job>;
+
+impl TypeMeta for Job {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "Job"
+    }
+}
+
+impl Metadata for Job {
+    fn api_version(&self) -> &str {
+        <Job as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <Job as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for Job {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl TypedResource for Job {
+    type List = JobList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "jobs",
+    };
+    const NAMESPACED: bool = true;
+}
+
+impl SetDefaults for Job {
+    /// A job runs one pod to completion, retrying up to 6 times.
+    fn set_defaults(&mut self) {
+        let spec = &mut self.spec;
+        if spec.completions.is_none() && spec.parallelism.is_none() {
+            spec.completions = Some(1);
+        }
+        spec.parallelism.get_or_insert(1);
+        spec.backoff_limit.get_or_insert(6);
+        spec.template.set_defaults();
+    }
+}
+
+impl Validate for Job {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        self.metadata.validate_at(&child(path, "metadata"), errors);
+        let spec = child(path, "spec");
+        let counts = [
+            ("parallelism", self.spec.parallelism),
+            ("completions", self.spec.completions),
+            ("backoffLimit", self.spec.backoff_limit),
+        ];
+        for (field, count) in &counts {
+            if let Some(count) = *count {
+                check_non_negative(&child(&spec, field), count.into(), errors);
+            }
+        }
+        let template = child(&spec, "template");
+        self.spec.template.validate_at(&template, errors);
+        if self.spec.template.spec.restart_policy == RestartPolicy::Always {
+            errors.push(FieldError::new(
+                &child(&template, "spec.restartPolicy"),
+                FieldErrorType::NotSupported,
+                "\"Always\": supported values: \"OnFailure\", \"Never\"",
+            ));
+        }
+    }
+}
+
+impl Job {
+    fn condition(&self, typ: JobConditionType) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| is_condition_true(&s.conditions, &typ))
+    }
+
+    /// Whether the job has run to completion.
+    pub fn is_complete(&self) -> bool {
+        self.condition(JobConditionType::Complete)
+    }
+
+    /// Whether the job has failed, eg: by exceeding its backoff limit.
+    pub fn is_failed(&self) -> bool {
+        self.condition(JobConditionType::Failed)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSpec {
+    pub parallelism: Option<Integer>,
+    pub completions: Option<Integer>,
+    pub active_deadline_seconds: Option<i64>,
+    pub backoff_limit: Option<Integer>,
+    pub selector: Option<LabelSelector>,
+    pub manual_selector: Option<bool>,
+    pub template: PodTemplateSpec,
+    pub ttl_seconds_after_finished: Option<Integer>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatus {
+    #[serde(default)]
+    pub conditions: Vec<JobCondition>,
+    pub start_time: Option<Time>,
+    pub completion_time: Option<Time>,
+    pub active: Option<Integer>,
+    pub succeeded: Option<Integer>,
+    pub failed: Option<Integer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobCondition {
+    #[serde(rename = "type")]
+    pub typ: JobConditionType,
+    pub status: ConditionStatus,
+    pub last_probe_time: Option<Time>,
+    pub last_transition_time: Option<Time>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum JobConditionType {
+    Complete,
+    Failed,
+}
+
+impl StatusCondition for JobCondition {
+    type Type = JobConditionType;
+    fn condition_type(&self) -> &JobConditionType {
+        &self.typ
+    }
+    fn condition_status(&self) -> ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
+
+#[test]
+fn deser_job() {
+    let yaml = r#"
+      apiVersion: batch/v1
+      kind: Job
+      metadata:
+        name: pi
+        namespace: default
+      spec:
+        backoffLimit: 4
+        template:
+          metadata: {}
+          spec:
+            restartPolicy: Never
+            containers:
+            - name: pi
+              image: perl
+              command: ["perl", "-Mbignum=bpi", "-wle", "print bpi(2000)"]
+      status:
+        succeeded: 1
+        conditions:
+        - type: Complete
+          status: "True"
+        "#;
+
+    let job: Job = ::serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(job.spec.backoff_limit, Some(4));
+    assert!(job.is_complete());
+    assert!(!job.is_failed());
+}
@@ -0,0 +1,85 @@
+use base64;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::ops::Deref;
+
+/// Arbitrary bytes, as in a Secret's `data` or a ConfigMap's
+/// `binaryData`.  They are serialized base64 encoded.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteString(pub Vec<u8>);
+
+impl ByteString {
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl fmt::Debug for ByteString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+impl From<Vec<u8>> for ByteString {
+    fn from(bytes: Vec<u8>) -> Self {
+        ByteString(bytes)
+    }
+}
+
+impl<'a> From<&'a [u8]> for ByteString {
+    fn from(bytes: &'a [u8]) -> Self {
+        ByteString(bytes.to_vec())
+    }
+}
+
+impl<'a> From<&'a str> for ByteString {
+    fn from(s: &'a str) -> Self {
+        ByteString(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for ByteString {
+    fn from(s: String) -> Self {
+        ByteString(s.into_bytes())
+    }
+}
+
+impl Deref for ByteString {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for ByteString {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Serialize for ByteString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        base64::decode(&s)
+            .map(ByteString)
+            .map_err(|e| de::Error::custom(format!("invalid base64: {}", e)))
+    }
+}
+
+#[test]
+fn bytestring() {
+    let b = ByteString::from("hello");
+    assert_eq!(::serde_json::to_value(&b).unwrap(), "aGVsbG8=");
+    let b2: ByteString = ::serde_json::from_value("aGVsbG8=".into()).unwrap();
+    assert_eq!(b, b2);
+    assert_eq!(&b2[..], b"hello");
+    assert!(::serde_json::from_value::<ByteString>("!!".into()).is_err());
+}
@@ -0,0 +1,519 @@
+//! Checking the API types against the apiserver's OpenAPI (swagger
+//! 2.0) schemas, to catch fields that are missing, misnamed or of the
+//! wrong type.
+//!
+//! Each property of a kind's definition, down to the leaves, is set in
+//! an object holding only that and the required properties, which is
+//! deserialized into the Rust type and serialized again.  A property
+//! that doesn't survive the round trip is missing from the type, one
+//! that fails to deserialize has the wrong type, and any field the type
+//! writes that the schema doesn't have is misnamed (or gone upstream).
+//!
+//! Strings are given made up values, so a failure to deserialize one as
+//! an enum doesn't count, `apiVersion` and `kind` are left to
+//! `TypeMetaImpl`, and objects without properties (eg:
+//! `RawExtension`) are only checked to deserialize.
+//!
+//! The check of all the kinds here runs as a test with the
+//! `conformance` feature, against the `swagger.json` of Kubernetes
+//! `PINNED_VERSION`, which must be fetched first:
+//!
+//! ```text
+//! curl -o api/testdata/swagger.json \
+//!     https://raw.githubusercontent.com/kubernetes/kubernetes/v1.23.0/api/openapi-spec/swagger.json
+//! ```
+//!
+//! (or given by `$KUBERNETES_SWAGGER`); without it, the test is
+//! skipped with a message.  The differences found are
+//! compared with the known ones in `testdata/conformance.txt`, and the
+//! test fails with any that are new or gone; rerun it with
+//! `UPDATE_CONFORMANCE=1` to accept them.
+//!
+//! `conformance.txt` starts with the SHA-256 of the `swagger.json` it
+//! was recorded against, and the test fails if given any other, so
+//! that the findings are always from the same schemas.  Moving to
+//! another Kubernetes release means changing `PINNED_VERSION` and
+//! recording `conformance.txt` again.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use std::fmt;
+
+/// The Kubernetes release whose schemas the types are checked against.
+pub const PINNED_VERSION: &str = "v1.23.0";
+
+/// How the type of one kind differs from its schema.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Properties that are dropped by the type.
+    pub missing: Vec<String>,
+    /// Properties that fail to deserialize, with the error.
+    pub mismatched: Vec<(String, String)>,
+    /// Fields written by the type that aren't in the schema.
+    pub unknown: Vec<String>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty() && self.unknown.is_empty()
+    }
+}
+
+/// One line per difference: `-` for missing properties, `!` for
+/// mismatched ones and `+` for unknown fields.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for path in &self.missing {
+            writeln!(f, "- {}", path)?;
+        }
+        for (path, err) in &self.mismatched {
+            writeln!(f, "! {}: {}", path, err)?;
+        }
+        for path in &self.unknown {
+            writeln!(f, "+ {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! kinds {
+    ($($definition:expr => $t:ty,)*) => {
+        /// Check each of the kinds here against its definition in
+        /// `swagger`.
+        pub fn check_all(swagger: &Value) -> Vec<(&'static str, Report)> {
+            vec![$(($definition, check::<$t>(swagger, $definition)),)*]
+        }
+    };
+}
+
+kinds! {
+    "io.k8s.api.apps.v1.Deployment" => crate::apps::v1::Deployment,
+    "io.k8s.api.autoscaling.v1.HorizontalPodAutoscaler" => crate::autoscaling::v1::HorizontalPodAutoscaler,
+    "io.k8s.api.autoscaling.v1.Scale" => crate::autoscaling::v1::Scale,
+    "io.k8s.api.autoscaling.v2.HorizontalPodAutoscaler" => crate::autoscaling::v2::HorizontalPodAutoscaler,
+    "io.k8s.api.batch.v1.Job" => crate::batch::v1::Job,
+    "io.k8s.api.core.v1.ConfigMap" => crate::core::v1::ConfigMap,
+    "io.k8s.api.core.v1.Namespace" => crate::core::v1::Namespace,
+    "io.k8s.api.core.v1.Pod" => crate::core::v1::Pod,
+    "io.k8s.api.core.v1.Secret" => crate::core::v1::Secret,
+    "io.k8s.kube-aggregator.pkg.apis.apiregistration.v1.APIService" => crate::apiregistration::v1::APIService,
+}
+
+/// Check `T` against the definition called `definition` in `swagger`.
+pub fn check<T: Serialize + DeserializeOwned>(swagger: &Value, definition: &str) -> Report {
+    let schemas = Schemas {
+        definitions: &swagger["definitions"],
+    };
+    let root = match schemas.definitions.get(definition) {
+        Some(root) => root,
+        None => {
+            return Report {
+                missing: vec![format!("(no definition {})", definition)],
+                ..Default::default()
+            }
+        }
+    };
+    let mut leaves = Vec::new();
+    schemas.leaves(root, &mut Vec::new(), &mut vec![definition], &mut leaves);
+
+    let mut report = Report::default();
+    for leaf in leaves {
+        // Checked against the type itself, by `TypeMetaImpl`.
+        if let [(Step::Field("apiVersion" | "kind"), _)] = leaf.path[..] {
+            continue;
+        }
+        let path = display(&leaf.path);
+        let object = schemas.build(&leaf.path, leaf.value);
+        let value = match serde_json::from_value::<T>(object) {
+            Ok(value) => value,
+            Err(ref e) if e.to_string().starts_with("unknown variant") => continue,
+            Err(e) => {
+                report.mismatched.push((path, e.to_string()));
+                continue;
+            }
+        };
+        let out = match serde_json::to_value(&value) {
+            Ok(out) => out,
+            Err(e) => {
+                report.mismatched.push((path, e.to_string()));
+                continue;
+            }
+        };
+        let kept = leaf
+            .path
+            .iter()
+            .try_fold(&out, |v, (step, _)| step.get(v))
+            .is_some_and(|v| !v.is_null());
+        if leaf.checked && !kept {
+            report.missing.push(path);
+        }
+        schemas.unknown(&out, root, "", 0, &mut report.unknown);
+    }
+    report.unknown.sort();
+    report.unknown.dedup();
+    report
+}
+
+/// A step along the path to a property.
+#[derive(Debug, Clone)]
+enum Step<'a> {
+    Field(&'a str),
+    Item,
+    Entry,
+}
+
+impl<'a> Step<'a> {
+    fn get<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        match self {
+            Step::Field(name) => value.get(name),
+            Step::Item => value.get(0),
+            Step::Entry => value.get("key"),
+        }
+    }
+}
+
+fn display(path: &[(Step, &Value)]) -> String {
+    let mut s = String::new();
+    for (step, _) in path {
+        match step {
+            Step::Field(name) if s.is_empty() => s.push_str(name),
+            Step::Field(name) => {
+                s.push('.');
+                s.push_str(name);
+            }
+            Step::Item => s.push_str("[0]"),
+            Step::Entry => s.push_str("[key]"),
+        }
+    }
+    s
+}
+
+/// A value to set at `path`, each step of which is taken from the
+/// schema alongside it.
+struct Leaf<'a> {
+    path: Vec<(Step<'a>, &'a Value)>,
+    value: Value,
+    /// Whether the value should survive the round trip.
+    checked: bool,
+}
+
+struct Schemas<'a> {
+    definitions: &'a Value,
+}
+
+/// How deep to fill in required properties, and look for unknown
+/// fields.
+const MAX_DEPTH: usize = 32;
+
+impl<'a> Schemas<'a> {
+    /// `schema`, or the definition it refers to.
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        match schema["$ref"].as_str() {
+            Some(r) => &self.definitions[r.trim_start_matches("#/definitions/")],
+            None => schema,
+        }
+    }
+
+    /// The leaves of `schema`, found at `path`.  `stack` holds the
+    /// definitions being expanded, which are not expanded again.
+    fn leaves(
+        &self,
+        schema: &'a Value,
+        path: &mut Vec<(Step<'a>, &'a Value)>,
+        stack: &mut Vec<&'a str>,
+        leaves: &mut Vec<Leaf<'a>>,
+    ) {
+        let name = schema["$ref"]
+            .as_str()
+            .map(|r| r.trim_start_matches("#/definitions/"));
+        if let Some(name) = name {
+            if stack.contains(&name) {
+                return;
+            }
+            stack.push(name);
+        }
+        let schema = self.resolve(schema);
+        match (
+            schema["type"].as_str(),
+            schema["properties"].as_object(),
+            schema.get("additionalProperties"),
+        ) {
+            (_, Some(properties), _) => {
+                for (k, v) in properties {
+                    path.push((Step::Field(k), schema));
+                    self.leaves(v, path, stack, leaves);
+                    path.pop();
+                }
+            }
+            (Some("object"), None, Some(values)) if values.is_object() => {
+                path.push((Step::Entry, schema));
+                self.leaves(values, path, stack, leaves);
+                path.pop();
+            }
+            (Some("array"), _, _) => {
+                path.push((Step::Item, schema));
+                self.leaves(&schema["items"], path, stack, leaves);
+                path.pop();
+            }
+            (Some("object"), None, _) | (None, None, _) => leaves.push(Leaf {
+                path: path.clone(),
+                value: Value::Object(Map::new()),
+                checked: false,
+            }),
+            _ => leaves.push(Leaf {
+                path: path.clone(),
+                value: self.example(schema, 0),
+                checked: true,
+            }),
+        }
+        if name.is_some() {
+            stack.pop();
+        }
+    }
+
+    /// A value for `schema`, with only its required properties.
+    fn example(&self, schema: &'a Value, depth: usize) -> Value {
+        // Quantities are strings, but not just any string.
+        let reference = schema["$ref"].as_str().unwrap_or_default();
+        if reference.ends_with(".api.resource.Quantity") {
+            return "1".into();
+        }
+        let schema = self.resolve(schema);
+        if let Some(value) = schema["enum"].get(0) {
+            return value.clone();
+        }
+        match (schema["type"].as_str(), schema["format"].as_str()) {
+            (Some("string"), Some("date-time")) => "2006-01-02T15:04:05Z".into(),
+            (Some("string"), Some("byte")) => "AA==".into(),
+            (Some("string"), Some("int-or-string")) => 1.into(),
+            (Some("string"), _) => "x".into(),
+            (Some("integer"), _) => 1.into(),
+            (Some("number"), _) => (1.5).into(),
+            (Some("boolean"), _) => true.into(),
+            (Some("array"), _) => Value::Array(Vec::new()),
+            _ => Value::Object(self.required(schema, depth)),
+        }
+    }
+
+    /// Values for the required properties of `schema`.
+    fn required(&self, schema: &'a Value, depth: usize) -> Map<String, Value> {
+        let mut object = Map::new();
+        if depth > MAX_DEPTH {
+            return object;
+        }
+        let required = schema["required"].as_array().map_or(&[][..], Vec::as_slice);
+        for name in required.iter().filter_map(Value::as_str) {
+            let property = &schema["properties"][name];
+            object.insert(name.to_string(), self.example(property, depth + 1));
+        }
+        object
+    }
+
+    /// An object with `value` at `path`, and whatever else is required
+    /// along the way.
+    fn build(&self, path: &[(Step, &'a Value)], value: Value) -> Value {
+        path.iter()
+            .enumerate()
+            .rev()
+            .fold(value, |inner, (depth, (step, parent))| match step {
+                Step::Field(name) => {
+                    let mut object = self.required(parent, depth);
+                    object.insert(name.to_string(), inner);
+                    Value::Object(object)
+                }
+                Step::Item => Value::Array(vec![inner]),
+                Step::Entry => json_object("key", inner),
+            })
+    }
+
+    /// Add the fields of `value` (found at `path`) that `schema`
+    /// doesn't have to `unknown`.
+    fn unknown(
+        &self,
+        value: &Value,
+        schema: &'a Value,
+        path: &str,
+        depth: usize,
+        unknown: &mut Vec<String>,
+    ) {
+        let schema = self.resolve(schema);
+        if depth > MAX_DEPTH {
+            return;
+        }
+        match value {
+            Value::Object(object) => {
+                if let Some(properties) = schema["properties"].as_object() {
+                    for (k, v) in object {
+                        let field = if path.is_empty() {
+                            k.clone()
+                        } else {
+                            format!("{}.{}", path, k)
+                        };
+                        match properties.get(k) {
+                            Some(s) => self.unknown(v, s, &field, depth + 1, unknown),
+                            None => unknown.push(field),
+                        }
+                    }
+                } else if let Some(values) = schema.get("additionalProperties") {
+                    for (k, v) in object {
+                        let entry = format!("{}[{}]", path, k);
+                        self.unknown(v, values, &entry, depth + 1, unknown);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    let item_path = format!("{}[{}]", path, i);
+                    self.unknown(item, &schema["items"], &item_path, depth + 1, unknown);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn json_object(key: &str, value: Value) -> Value {
+    let mut object = Map::new();
+    object.insert(key.to_string(), value);
+    Value::Object(object)
+}
+
+#[test]
+fn check_namespace() {
+    use crate::core::v1::Namespace;
+
+    let swagger = json!({"definitions": {
+        "Namespace": {
+            "type": "object",
+            "properties": {
+                "apiVersion": {"type": "string"},
+                "kind": {"type": "string"},
+                "metadata": {"$ref": "#/definitions/ObjectMeta"},
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "finalizers": {"type": "array", "items": {"type": "string"}},
+                        "owner": {"type": "string"},
+                    },
+                },
+                "status": {
+                    "type": "object",
+                    "properties": {"phase": {"type": "integer"}},
+                },
+            },
+        },
+        "ObjectMeta": {
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "labels": {"type": "object", "additionalProperties": {"type": "string"}},
+                "ownerReferences": {
+                    "type": "array",
+                    "items": {"$ref": "#/definitions/OwnerReference"},
+                },
+            },
+        },
+        "OwnerReference": {
+            "type": "object",
+            "properties": {
+                "uid": {"type": "string"},
+                "name": {"type": "string"},
+                "kind": {"type": "string"},
+                "apiVersion": {"type": "string"},
+                "controller": {"type": "boolean"},
+            },
+            "required": ["uid", "name", "kind", "apiVersion"],
+        },
+    }});
+    let report = check::<Namespace>(&swagger, "Namespace");
+    assert_eq!(report.missing, vec!["spec.owner"]);
+    assert_eq!(report.mismatched.len(), 1);
+    assert_eq!(report.mismatched[0].0, "status.phase");
+    // Not in this cut down ObjectMeta.
+    assert!(report.unknown.contains(&"metadata.uid".to_string()));
+    assert!(!report.unknown.contains(&"metadata.name".to_string()));
+    assert!(report
+        .to_string()
+        .starts_with("- spec.owner\n! status.phase: "));
+
+    let report = check::<Namespace>(&swagger, "Pod");
+    assert_eq!(report.missing, vec!["(no definition Pod)"]);
+}
+
+#[cfg(feature = "conformance")]
+#[test]
+fn upstream() {
+    use sha2::{Digest, Sha256};
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    let testdata = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata");
+    let swagger = env::var_os("KUBERNETES_SWAGGER")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| testdata.join("swagger.json"));
+    let (swagger, sha256): (Value, _) = match fs::read(&swagger) {
+        Ok(json) => (
+            serde_json::from_slice(&json).unwrap(),
+            hex(&Sha256::digest(&json)),
+        ),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!(
+                "Skipping: there is no {}; fetch the swagger.json of Kubernetes {}, \
+                 as the conformance module documentation says",
+                swagger.display(),
+                PINNED_VERSION
+            );
+            return;
+        }
+        Err(e) => panic!("Unable to read {} ({})", swagger.display(), e),
+    };
+    let mut found = format!("# sha256 {} (Kubernetes {})\n", sha256, PINNED_VERSION);
+    for (definition, report) in check_all(&swagger) {
+        if !report.is_empty() {
+            found.push_str(&format!("== {}\n{}", definition, report));
+        }
+    }
+
+    let known = testdata.join("conformance.txt");
+    if env::var_os("UPDATE_CONFORMANCE").is_some() {
+        fs::write(&known, &found).unwrap();
+        return;
+    }
+    let known = match fs::read_to_string(&known) {
+        Ok(known) => known,
+        Err(e) => panic!(
+            "Unable to read {} ({}); record it with UPDATE_CONFORMANCE=1",
+            known.display(),
+            e
+        ),
+    };
+    let pinned = known.lines().next().unwrap_or("");
+    assert_eq!(
+        found.lines().next().unwrap(),
+        pinned,
+        "This swagger.json isn't the one conformance.txt was recorded against"
+    );
+    let (found, known): (Vec<_>, Vec<_>) = (found.lines().collect(), known.lines().collect());
+    let new: Vec<_> = found.iter().filter(|l| !known.contains(l)).collect();
+    let gone: Vec<_> = known.iter().filter(|l| !found.contains(l)).collect();
+    assert!(
+        new.is_empty() && gone.is_empty(),
+        "The types have drifted from the schemas.\nNew:\n{}\nGone:\n{}\n\
+         (rerun with UPDATE_CONFORMANCE=1 to accept)",
+        new.iter()
+            .map(|l| format!("  {}", l))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        gone.iter()
+            .map(|l| format!("  {}", l))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+}
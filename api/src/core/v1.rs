@@ -1,9 +1,27 @@
-use crate::meta::v1::{ItemList, LabelSelector, List, Metadata, ObjectMeta};
+use crate::apiextensions::v1beta1::ResourceScope;
+use crate::meta::v1::{
+    find_condition, is_condition_true, ItemList, LabelSelector, List, Metadata, MetadataMut,
+    ObjectMeta, StatusCondition,
+};
 use crate::meta::{GroupVersion, GroupVersionResource};
-use crate::{IntOrString, Integer, Quantity, Time, TypeMeta, TypeMetaImpl};
+use crate::validation::{
+    check, check_labels, check_non_negative, check_port, check_unique, child, index,
+    is_config_map_key, is_dns1123_label, is_port_name, FieldError, FieldErrorType, Validate,
+};
+use crate::{
+    ByteString, IntOrString, Integer, Quantity, Redacted, ResourceList, SetDefaults, Time,
+    TypeMeta, TypeMetaImpl,
+};
+use base64;
+use failure::Error;
 use serde_json::{self, Map, Value};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::default::Default;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str;
 
 // TODO(gus): Uses of serde_json::{Map,Value} below are probably incorrect.
 
@@ -28,6 +46,44 @@ pub trait Resource {
     fn gvr(&self) -> GroupVersionResource;
 }
 
+/// Statically maps an object type to the resource that serves it, so
+/// clients can derive the URL and return types from the Rust type.
+///
+/// Generic code can then be written without GVR tables, eg:
+///
+/// ```ignore
+/// fn describe<K: TypedResource>() -> String {
+///     format!("{} ({}.{})", K::kind(), K::plural(), K::group())
+/// }
+/// ```
+pub trait TypedResource: TypeMeta + Sized {
+    type List: List<Item = Self>;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static>;
+    const NAMESPACED: bool;
+
+    fn group() -> &'static str {
+        Self::GROUP_VERSION_RESOURCE.group
+    }
+
+    fn version() -> &'static str {
+        Self::GROUP_VERSION_RESOURCE.version
+    }
+
+    /// The resource name, eg: "pods".
+    fn plural() -> &'static str {
+        Self::GROUP_VERSION_RESOURCE.resource
+    }
+
+    fn scope() -> ResourceScope {
+        if Self::NAMESPACED {
+            ResourceScope::Namespaced
+        } else {
+            ResourceScope::Cluster
+        }
+    }
+}
+
 impl NamespacedResource for Pods {
     type List = PodList;
 
@@ -87,6 +143,27 @@ impl Resource for Namespace {
 
 pub type NamespaceList = ItemList<Namespace>;
 
+impl Validate for Namespace {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        let metadata = child(path, "metadata");
+        self.metadata.validate_at(&metadata, errors);
+        if let Some(ref name) = self.metadata.name {
+            check(&child(&metadata, "name"), name, is_dns1123_label, errors);
+        }
+    }
+}
+
+impl TypedResource for Namespace {
+    type List = NamespaceList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "namespaces",
+    };
+    const NAMESPACED: bool = false;
+}
+
 impl TypeMeta for Namespace {
     fn api_version() -> &'static str {
         API_GROUP
@@ -108,6 +185,12 @@ impl Metadata for Namespace {
     }
 }
 
+impl MetadataMut for Namespace {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct NamespaceSpec {
@@ -129,6 +212,383 @@ pub enum NamespacePhase {
     Terminating,
 }
 
+pub const SECRET_TYPE_OPAQUE: &str = "Opaque";
+pub const SECRET_TYPE_TLS: &str = "kubernetes.io/tls";
+pub const SECRET_TYPE_DOCKER_CONFIG_JSON: &str = "kubernetes.io/dockerconfigjson";
+
+/// The certificate chain in a TLS secret, PEM encoded.
+pub const TLS_CERT_KEY: &str = "tls.crt";
+/// The private key in a TLS secret, PEM encoded.
+pub const TLS_PRIVATE_KEY_KEY: &str = "tls.key";
+/// The registry credentials in a `kubernetes.io/dockerconfigjson` secret.
+pub const DOCKER_CONFIG_JSON_KEY: &str = ".dockerconfigjson";
+
+/// `Debug` shows the keys of `data` and `stringData`, but not their
+/// values.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Secret {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<Secret>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, ByteString>,
+    /// Write-only: the apiserver merges it into `data`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub string_data: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub immutable: Option<bool>,
+}
+
+pub type SecretList = ItemList<Secret>;
+
+impl Secret {
+    fn with_type(name: &str, typ: &str) -> Self {
+        let mut secret = Secret::default();
+        secret.metadata.name = Some(name.to_string());
+        secret.typ = Some(typ.to_string());
+        secret
+    }
+
+    /// An `Opaque` secret holding `data`.
+    pub fn opaque<I, K, V>(name: &str, data: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<ByteString>,
+    {
+        let mut secret = Secret::with_type(name, SECRET_TYPE_OPAQUE);
+        for (key, value) in data {
+            secret.insert(key, value);
+        }
+        secret
+    }
+
+    /// A `kubernetes.io/tls` secret, from a PEM encoded certificate
+    /// chain and private key.
+    pub fn tls<C: Into<ByteString>, K: Into<ByteString>>(name: &str, cert: C, key: K) -> Self {
+        let mut secret = Secret::with_type(name, SECRET_TYPE_TLS);
+        secret.insert(TLS_CERT_KEY, cert);
+        secret.insert(TLS_PRIVATE_KEY_KEY, key);
+        secret
+    }
+
+    /// A `kubernetes.io/dockerconfigjson` image pull secret, with
+    /// credentials for `registry` (eg: `ghcr.io`).
+    pub fn docker_config_json(name: &str, registry: &str, username: &str, password: &str) -> Self {
+        let mut config = DockerConfigJson::default();
+        config.auths.insert(
+            registry.to_string(),
+            DockerConfigEntry::new(username, password),
+        );
+        let mut secret = Secret::with_type(name, SECRET_TYPE_DOCKER_CONFIG_JSON);
+        // Serializing maps of strings can't fail.
+        secret.insert(DOCKER_CONFIG_JSON_KEY, serde_json::to_vec(&config).unwrap());
+        secret
+    }
+
+    /// Set `key` to `value`, replacing any `stringData` for it.
+    pub fn insert<K: Into<String>, V: Into<ByteString>>(&mut self, key: K, value: V) {
+        let key = key.into();
+        self.string_data.remove(&key);
+        self.data.insert(key, value.into());
+    }
+
+    /// The value of `key`, from `stringData` if it is there (as the
+    /// apiserver would), or else decoded from `data`.
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.string_data
+            .get(key)
+            .map(|s| s.as_bytes())
+            .or_else(|| self.data.get(key).map(|b| &b[..]))
+    }
+
+    /// The value of `key` as text, if it is there.  Fails if it isn't
+    /// UTF-8.
+    pub fn get_str(&self, key: &str) -> Result<Option<&str>, str::Utf8Error> {
+        self.get(key).map(str::from_utf8).transpose()
+    }
+
+    /// The certificate chain of a TLS secret.
+    pub fn tls_cert(&self) -> Option<&[u8]> {
+        self.get(TLS_CERT_KEY)
+    }
+
+    /// The private key of a TLS secret.
+    pub fn tls_key(&self) -> Option<&[u8]> {
+        self.get(TLS_PRIVATE_KEY_KEY)
+    }
+
+    /// The registry credentials of an image pull secret, if it has
+    /// any.
+    pub fn docker_config(&self) -> Result<Option<DockerConfigJson>, serde_json::Error> {
+        self.get(DOCKER_CONFIG_JSON_KEY)
+            .map(serde_json::from_slice)
+            .transpose()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Secret")
+            .field("metadata", &self.metadata)
+            .field("typ", &self.typ)
+            .field("data", &self.data.keys().collect::<Vec<_>>())
+            .field("string_data", &self.string_data.keys().collect::<Vec<_>>())
+            .field("immutable", &self.immutable)
+            .finish()
+    }
+}
+
+impl TypedResource for Secret {
+    type List = SecretList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "secrets",
+    };
+    const NAMESPACED: bool = true;
+}
+
+impl TypeMeta for Secret {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "Secret"
+    }
+}
+
+impl Metadata for Secret {
+    fn api_version(&self) -> &str {
+        <Secret as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <Secret as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for Secret {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+/// The contents of a `kubernetes.io/dockerconfigjson` secret.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct DockerConfigJson {
+    /// By registry.
+    #[serde(default)]
+    pub auths: BTreeMap<String, DockerConfigEntry>,
+}
+
+/// Credentials for a registry.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct DockerConfigEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<Redacted<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Base64 encoded `username:password`, which is what the kubelet
+    /// uses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<Redacted<String>>,
+}
+
+impl DockerConfigEntry {
+    pub fn new(username: &str, password: &str) -> Self {
+        DockerConfigEntry {
+            username: Some(username.to_string()),
+            password: Some(password.to_string().into()),
+            email: None,
+            auth: Some(base64::encode(&format!("{}:{}", username, password)).into()),
+        }
+    }
+
+    /// The username and password, from `auth` if it is there.
+    pub fn credentials(&self) -> Option<(String, String)> {
+        let from_auth = self
+            .auth
+            .as_ref()
+            .and_then(|auth| base64::decode(auth.as_str()).ok())
+            .and_then(|auth| String::from_utf8(auth).ok())
+            .and_then(|auth| {
+                let mut parts = auth.splitn(2, ':');
+                Some((parts.next()?.to_string(), parts.next()?.to_string()))
+            });
+        from_auth.or_else(|| Some((self.username.clone()?, self.password.as_ref()?.0.clone())))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigMap {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<ConfigMap>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, String>,
+    /// Values that aren't UTF-8.  Keys must not also be in `data`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub binary_data: BTreeMap<String, ByteString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub immutable: Option<bool>,
+}
+
+pub type ConfigMapList = ItemList<ConfigMap>;
+
+impl ConfigMap {
+    pub fn new(name: &str) -> Self {
+        let mut cm = ConfigMap::default();
+        cm.metadata.name = Some(name.to_string());
+        cm
+    }
+
+    /// A ConfigMap with each of `paths`, keyed by its file name, as
+    /// `kubectl create configmap --from-file` makes them.
+    pub fn from_files<I, P>(name: &str, paths: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut cm = ConfigMap::new(name);
+        for path in paths {
+            cm.insert_file(None, path.as_ref())?;
+        }
+        Ok(cm)
+    }
+
+    /// A ConfigMap with each regular file in `dir`, keyed by its file
+    /// name, as `kubectl create configmap --from-file=<dir>` makes
+    /// them.  Subdirectories (and anything else) are skipped.
+    pub fn from_dir<P: AsRef<Path>>(name: &str, dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        let mut cm = ConfigMap::new(name);
+        let entries = fs::read_dir(dir)
+            .map_err(|e| format_err!("Unable to read directory {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry?.path();
+            // Following symlinks, as kubectl does.
+            if fs::metadata(&path)?.is_file() {
+                cm.insert_file(None, &path)?;
+            }
+        }
+        Ok(cm)
+    }
+
+    /// Add the contents of the file at `path`, as `key` or else its
+    /// file name: to `data` if it is UTF-8, or else to `binaryData`.
+    /// Fails if the key is invalid or already there.
+    pub fn insert_file<P: AsRef<Path>>(&mut self, key: Option<&str>, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let key = match key {
+            Some(key) => key.to_string(),
+            None => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| format_err!("{} has no usable file name", path.display()))?
+                .to_string(),
+        };
+        is_config_map_key(&key)
+            .map_err(|e| format_err!("{} is not a valid key name: {}", key, e))?;
+        if self.data.contains_key(&key) || self.binary_data.contains_key(&key) {
+            bail!(
+                "Cannot add key {} from {}: it already exists",
+                key,
+                path.display()
+            );
+        }
+        let contents =
+            fs::read(path).map_err(|e| format_err!("Unable to read {}: {}", path.display(), e))?;
+        match String::from_utf8(contents) {
+            Ok(text) => {
+                self.data.insert(key, text);
+            }
+            Err(e) => {
+                self.binary_data.insert(key, e.into_bytes().into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The value of `key`, from `data` or `binaryData`.
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.data
+            .get(key)
+            .map(|s| s.as_bytes())
+            .or_else(|| self.binary_data.get(key).map(|b| &b[..]))
+    }
+}
+
+impl Validate for ConfigMap {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        self.metadata.validate_at(&child(path, "metadata"), errors);
+        let data = child(path, "data");
+        for k in self.data.keys() {
+            check(&data, k, is_config_map_key, errors);
+        }
+        let binary_data = child(path, "binaryData");
+        for k in self.binary_data.keys() {
+            check(&binary_data, k, is_config_map_key, errors);
+            if self.data.contains_key(k) {
+                errors.push(FieldError::new(
+                    &binary_data,
+                    FieldErrorType::Invalid,
+                    format!("{}: duplicate of key in data", k),
+                ));
+            }
+        }
+    }
+}
+
+impl TypedResource for ConfigMap {
+    type List = ConfigMapList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    const NAMESPACED: bool = true;
+}
+
+impl TypeMeta for ConfigMap {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "ConfigMap"
+    }
+}
+
+impl Metadata for ConfigMap {
+    fn api_version(&self) -> &str {
+        <ConfigMap as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <ConfigMap as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for ConfigMap {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Pod {
@@ -144,6 +604,81 @@ pub struct Pod {
 
 pub type PodList = ItemList<Pod>;
 
+impl TypedResource for Pod {
+    type List = PodList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "pods",
+    };
+    const NAMESPACED: bool = true;
+}
+
+impl Pod {
+    /// Whether the pod's Ready condition is true.
+    pub fn is_ready(&self) -> bool {
+        is_condition_true(&self.status.conditions, &PodConditionType::Ready)
+    }
+
+    /// The pod's phase, `Unknown` if it has none yet.
+    pub fn phase(&self) -> PodPhase {
+        self.status.phase.unwrap_or(PodPhase::Unknown)
+    }
+
+    /// Whether the pod is bound to a node and all its containers have
+    /// been started, though they might not be ready.
+    pub fn is_running(&self) -> bool {
+        self.phase() == PodPhase::Running
+    }
+
+    /// Whether all the pod's containers have terminated, and won't be
+    /// restarted.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.phase(), PodPhase::Succeeded | PodPhase::Failed)
+    }
+
+    /// The pod's condition of type `typ`, if it has one.
+    pub fn condition(&self, typ: PodConditionType) -> Option<&PodCondition> {
+        find_condition(&self.status.conditions, &typ)
+    }
+
+    /// The status of the container, init container or ephemeral
+    /// container called `name`, if it has one yet.
+    pub fn container_status(&self, name: &str) -> Option<&ContainerStatus> {
+        let status = &self.status;
+        status
+            .container_statuses
+            .iter()
+            .chain(&status.init_container_statuses)
+            .chain(&status.ephemeral_container_statuses)
+            .find(|c| c.name == name)
+    }
+
+    /// How many times the pod's containers have been restarted, in
+    /// all, as `kubectl get pods` shows.
+    pub fn restart_count(&self) -> Integer {
+        self.status
+            .container_statuses
+            .iter()
+            .map(|c| c.restart_count)
+            .sum()
+    }
+}
+
+impl SetDefaults for Pod {
+    fn set_defaults(&mut self) {
+        self.spec.set_defaults();
+    }
+}
+
+impl Validate for Pod {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        self.metadata.validate_at(&child(path, "metadata"), errors);
+        self.spec.validate_at(&child(path, "spec"), errors);
+    }
+}
+
 impl TypeMeta for Pod {
     fn api_version() -> &'static str {
         API_GROUP
@@ -165,6 +700,44 @@ impl Metadata for Pod {
     }
 }
 
+impl MetadataMut for Pod {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+/// Query parameters for reading a container's log, from the pod's
+/// `log` subresource.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PodLogOptions {
+    /// Needed if the pod has more than one container.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// Keep streaming the log as it is written.
+    #[serde(skip_serializing_if = "is_false")]
+    pub follow: bool,
+    /// The log of the previous instance of the container, if it was
+    /// restarted.
+    #[serde(skip_serializing_if = "is_false")]
+    pub previous: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_seconds: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_time: Option<Time>,
+    /// Prefix each line with an RFC 3339 timestamp.
+    #[serde(skip_serializing_if = "is_false")]
+    pub timestamps: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tail_lines: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_bytes: Option<Integer>,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PodTemplateSpec {
@@ -172,6 +745,12 @@ pub struct PodTemplateSpec {
     pub spec: PodSpec,
 }
 
+impl SetDefaults for PodTemplateSpec {
+    fn set_defaults(&mut self) {
+        self.spec.set_defaults();
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PodSpec {
@@ -182,6 +761,10 @@ pub struct PodSpec {
     pub containers: Vec<Container>,
     #[serde(default = "clusterfirst")]
     pub dns_policy: DNSPolicy,
+    /// Containers added to a running pod for debugging, through the
+    /// `ephemeralcontainers` subresource.
+    #[serde(default)]
+    pub ephemeral_containers: Vec<EphemeralContainer>,
     #[serde(default)]
     pub host_aliases: Vec<HostAlias>,
     #[serde(default)]
@@ -221,11 +804,164 @@ impl Default for PodSpec {
     }
 }
 
+impl Validate for PodTemplateSpec {
+    /// Pod templates needn't be named.
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        check_labels(&child(path, "metadata"), &self.metadata, errors);
+        self.spec.validate_at(&child(path, "spec"), errors);
+    }
+}
+
+impl Validate for PodSpec {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        let volumes = child(path, "volumes");
+        for (i, volume) in self.volumes.iter().enumerate() {
+            let name = child(&index(&volumes, i), "name");
+            check(&name, &volume.name, is_dns1123_label, errors);
+        }
+        check_unique(
+            self.volumes
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (child(&index(&volumes, i), "name"), v.name.as_str())),
+            errors,
+        );
+
+        let containers = child(path, "containers");
+        if self.containers.is_empty() {
+            errors.push(FieldError::new(&containers, FieldErrorType::Required, ""));
+        }
+        let init_containers = child(path, "initContainers");
+        let all = self
+            .init_containers
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (index(&init_containers, i), c))
+            .chain(
+                self.containers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| (index(&containers, i), c)),
+            )
+            .collect::<Vec<_>>();
+        for (field, container) in &all {
+            container.validate_at(field, errors);
+            let mounts = child(field, "volumeMounts");
+            for (i, mount) in container.volume_mounts.iter().enumerate() {
+                if !self.volumes.iter().any(|v| v.name == mount.name) {
+                    errors.push(FieldError::new(
+                        &child(&index(&mounts, i), "name"),
+                        FieldErrorType::NotFound,
+                        format!("{:?}", mount.name),
+                    ));
+                }
+            }
+        }
+        // Container names are shared between the two lists.
+        check_unique(
+            all.iter()
+                .map(|(field, c)| (child(field, "name"), c.name.as_str())),
+            errors,
+        );
+
+        if let Some(seconds) = self.active_deadline_seconds {
+            if seconds <= 0 {
+                errors.push(FieldError::new(
+                    &child(path, "activeDeadlineSeconds"),
+                    FieldErrorType::Invalid,
+                    format!("{}: must be greater than 0", seconds),
+                ));
+            }
+        }
+        check_non_negative(
+            &child(path, "terminationGracePeriodSeconds"),
+            self.termination_grace_period_seconds.into(),
+            errors,
+        );
+    }
+}
+
+impl SetDefaults for PodSpec {
+    fn set_defaults(&mut self) {
+        self.containers.iter_mut().for_each(Container::set_defaults);
+        self.init_containers
+            .iter_mut()
+            .for_each(Container::set_defaults);
+        self.ephemeral_containers
+            .iter_mut()
+            .for_each(|c| c.container.set_defaults());
+        if self.scheduler_name.is_none() {
+            self.scheduler_name = Some("default-scheduler".to_string());
+        }
+        if self.security_context.is_none() {
+            self.security_context = Some(Default::default());
+        }
+    }
+}
+
+impl PodSpec {
+    /// What the pod requests, as the scheduler counts it: the sum of
+    /// its containers' requests, or any more that an init container
+    /// (which runs alone) requests.
+    pub fn resource_requests(&self) -> ResourceList {
+        self.total(|r| &r.requests)
+    }
+
+    /// The pod's limits, counted as `resource_requests` counts
+    /// requests.  Resources that some container has no limit on are
+    /// still listed, with the limits of the others.
+    pub fn resource_limits(&self) -> ResourceList {
+        self.total(|r| &r.limits)
+    }
+
+    fn total(&self, list: impl Fn(&ResourceRequirements) -> &ResourceList) -> ResourceList {
+        let lists = |containers: &[Container]| {
+            containers
+                .iter()
+                .filter_map(|c| c.resources.as_ref().map(&list))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let mut total = lists(&self.containers).iter().sum::<ResourceList>();
+        for init in lists(&self.init_containers) {
+            total.max(&init);
+        }
+        total
+    }
+}
+
 #[test]
 fn podspec_default() {
     let _: PodSpec = Default::default();
 }
 
+#[test]
+fn podspec_resources() {
+    let spec: PodSpec = serde_json::from_value(json!({
+        "containers": [
+            {"name": "a", "resources": {"requests": {"cpu": "250m", "memory": "64Mi"}}},
+            {"name": "b", "resources": {
+                "requests": {"cpu": "500m"},
+                "limits": {"cpu": 1, "memory": "128Mi"},
+            }},
+            {"name": "c"},
+        ],
+        "initContainers": [
+            {"name": "migrate", "resources": {"requests": {"cpu": "100m", "memory": "1Gi"}}},
+        ],
+    }))
+    .unwrap();
+    assert_eq!(
+        serde_json::to_value(spec.resource_requests()).unwrap(),
+        json!({"cpu": "750m", "memory": "1Gi"})
+    );
+    assert_eq!(
+        serde_json::to_value(spec.resource_limits()).unwrap(),
+        json!({"cpu": "1", "memory": "128Mi"})
+    );
+    assert!(PodSpec::default().resource_requests().is_empty());
+}
+
 fn clusterfirst() -> DNSPolicy {
     DNSPolicy::ClusterFirst
 }
@@ -388,6 +1124,124 @@ fn container_default() {
     let _: Container = Default::default();
 }
 
+impl Validate for Container {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        let name = child(path, "name");
+        if self.name.is_empty() {
+            errors.push(FieldError::new(&name, FieldErrorType::Required, ""));
+        } else {
+            check(&name, &self.name, is_dns1123_label, errors);
+        }
+        if self.image.as_deref().is_none_or(str::is_empty) {
+            errors.push(FieldError::new(
+                &child(path, "image"),
+                FieldErrorType::Required,
+                "",
+            ));
+        }
+        let ports = child(path, "ports");
+        for (i, port) in self.ports.iter().enumerate() {
+            let field = index(&ports, i);
+            check_port(&child(&field, "containerPort"), port.container_port, errors);
+            if let Some(host_port) = port.host_port {
+                check_port(&child(&field, "hostPort"), host_port, errors);
+            }
+            if let Some(ref name) = port.name {
+                check(&child(&field, "name"), name, is_port_name, errors);
+            }
+        }
+        check_unique(
+            self.ports.iter().enumerate().filter_map(|(i, p)| {
+                let field = child(&index(&ports, i), "name");
+                p.name.as_deref().map(|name| (field, name))
+            }),
+            errors,
+        );
+    }
+}
+
+impl SetDefaults for Container {
+    /// Images without a tag, or tagged `latest`, are always pulled.
+    fn set_defaults(&mut self) {
+        if self.image_pull_policy.is_some() {
+            return;
+        }
+        let image = self.image.as_deref().unwrap_or("");
+        // The tag follows the last ':', unless that is part of a
+        // registry's host:port; a digest pins the image.
+        let name = image.split('@').next().unwrap_or("");
+        let tag = name
+            .rfind(':')
+            .filter(|&i| !name[i..].contains('/'))
+            .map(|i| &name[i + 1..]);
+        self.image_pull_policy = Some(if image.contains('@') {
+            PullPolicy::IfNotPresent
+        } else {
+            match tag {
+                None | Some("latest") => PullPolicy::Always,
+                Some(_) => PullPolicy::IfNotPresent,
+            }
+        });
+    }
+}
+
+#[test]
+fn container_set_defaults() {
+    let policy = |image: &str| {
+        let mut c = Container {
+            image: Some(image.to_string()),
+            ..Default::default()
+        };
+        c.set_defaults();
+        c.image_pull_policy.unwrap()
+    };
+    assert_eq!(policy("nginx"), PullPolicy::Always);
+    assert_eq!(policy("nginx:latest"), PullPolicy::Always);
+    assert_eq!(policy("nginx:1.19"), PullPolicy::IfNotPresent);
+    assert_eq!(policy("registry:5000/nginx"), PullPolicy::Always);
+    assert_eq!(policy("registry:5000/nginx:1.19"), PullPolicy::IfNotPresent);
+    assert_eq!(policy("nginx@sha256:abcd"), PullPolicy::IfNotPresent);
+
+    let mut c = Container {
+        image: Some("nginx".to_string()),
+        image_pull_policy: Some(PullPolicy::Never),
+        ..Default::default()
+    };
+    c.set_defaults();
+    assert_eq!(c.image_pull_policy, Some(PullPolicy::Never));
+}
+
+/// A container run in an existing pod, eg: for `kubectl debug`.
+///
+/// Ephemeral containers are never restarted, and may not have ports,
+/// probes, lifecycle hooks or resources.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EphemeralContainer {
+    #[serde(flatten)]
+    pub container: Container,
+    /// Share the process namespace of this container, rather than the
+    /// pod's.
+    pub target_container_name: Option<String>,
+}
+
+#[test]
+fn ephemeral_container() {
+    let c: EphemeralContainer = serde_json::from_value(json!({
+        "name": "debugger",
+        "image": "busybox",
+        "stdin": true,
+        "targetContainerName": "app",
+    }))
+    .unwrap();
+    assert_eq!(c.container.name, "debugger");
+    assert!(c.container.stdin);
+    assert_eq!(c.target_container_name.as_deref(), Some("app"));
+    let value = serde_json::to_value(&c).unwrap();
+    assert_eq!(value["image"], "busybox");
+    assert_eq!(value["targetContainerName"], "app");
+}
+
 fn devterminationlog() -> String {
     "/dev/termination-log".into()
 }
@@ -448,7 +1302,7 @@ pub struct ObjectFieldSelector {
 }
 
 fn quant1() -> Quantity {
-    "1".into()
+    Quantity::from(1)
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
@@ -598,9 +1452,9 @@ pub enum Protocol {
 #[serde(rename_all = "camelCase")]
 pub struct ResourceRequirements {
     #[serde(default)]
-    pub limits: Map<String, Value>,
+    pub limits: ResourceList,
     #[serde(default)]
-    pub requests: Map<String, Value>,
+    pub requests: ResourceList,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
@@ -663,6 +1517,43 @@ pub struct LocalObjectReference {
     pub name: String,
 }
 
+/// A reference to an object, eg: an event's `involvedObject`.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectReference {
+    pub api_version: Option<String>,
+    pub kind: Option<String>,
+    pub namespace: Option<String>,
+    pub name: Option<String>,
+    pub uid: Option<String>,
+    pub resource_version: Option<String>,
+    /// The part of the object referred to, eg:
+    /// `spec.containers{web}` for a container in a pod.
+    pub field_path: Option<String>,
+}
+
+impl ObjectReference {
+    /// A reference to `object`, as it is now.
+    pub fn from_object<T: Metadata>(object: &T) -> Self {
+        let meta = object.metadata();
+        let non_empty = |s: &str| Some(s.to_string()).filter(|s| !s.is_empty());
+        ObjectReference {
+            api_version: non_empty(object.api_version()),
+            kind: non_empty(object.kind()),
+            namespace: meta.namespace.clone(),
+            name: meta.name.clone(),
+            uid: meta.uid.clone(),
+            resource_version: meta.resource_version.clone(),
+            field_path: None,
+        }
+    }
+
+    pub fn field_path<P: Into<String>>(mut self, path: P) -> Self {
+        self.field_path = Some(path.into());
+        self
+    }
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PodSecurityContext {
@@ -863,6 +1754,8 @@ pub struct PodStatus {
     pub init_container_statuses: Vec<ContainerStatus>,
     #[serde(default)]
     pub container_statuses: Vec<ContainerStatus>,
+    #[serde(default)]
+    pub ephemeral_container_statuses: Vec<ContainerStatus>,
     pub qos_class: Option<PodQOSClass>,
 }
 
@@ -875,6 +1768,19 @@ pub enum PodConditionType {
     Unschedulable,
 }
 
+impl StatusCondition for PodCondition {
+    type Type = PodConditionType;
+    fn condition_type(&self) -> &PodConditionType {
+        &self.typ
+    }
+    fn condition_status(&self) -> ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PodCondition {
@@ -902,6 +1808,22 @@ pub struct ContainerStatus {
     pub container_id: Option<String>,
 }
 
+impl ContainerStatus {
+    /// Whether the container is running, though it might not be ready.
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, Some(ContainerState::Running(_)))
+    }
+
+    /// Why the container isn't running yet, if it is waiting, eg:
+    /// `ImagePullBackOff` or `CrashLoopBackOff`.
+    pub fn waiting_reason(&self) -> Option<&str> {
+        match self.state {
+            Some(ContainerState::Waiting(ref waiting)) => waiting.reason.as_deref(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum PodPhase {
     Pending,
@@ -1072,3 +1994,168 @@ fn deser_pod() {
     let pod2: Pod = ::serde_json::from_value(rt_json).unwrap();
     assert_eq!(pod, pod2);
 }
+
+#[test]
+fn typed_resource() {
+    fn describe<K: TypedResource>() -> String {
+        format!(
+            "{}/{} {} {:?}",
+            K::group(),
+            K::version(),
+            K::plural(),
+            K::scope()
+        )
+    }
+    assert_eq!(describe::<Pod>(), "/v1 pods Namespaced");
+    assert_eq!(describe::<Namespace>(), "/v1 namespaces Cluster");
+    assert_eq!(<Pod as TypeMeta>::kind(), "Pod");
+}
+
+#[test]
+fn object_reference() {
+    let pod: Pod = ::serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": "web-0",
+            "namespace": "default",
+            "uid": "1234",
+            "resourceVersion": "42",
+        },
+        "spec": {"containers": [{"name": "web"}]},
+    }))
+    .unwrap();
+    let r = ObjectReference::from_object(&pod).field_path("spec.containers{web}");
+    assert_eq!(
+        ::serde_json::to_value(&r).unwrap(),
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "namespace": "default",
+            "name": "web-0",
+            "uid": "1234",
+            "resourceVersion": "42",
+            "fieldPath": "spec.containers{web}",
+        })
+    );
+    let r = ObjectReference::from_object(&json!({"metadata": {"name": "x"}}));
+    assert_eq!(r.kind, None);
+    assert_eq!(r.name.as_deref(), Some("x"));
+}
+
+#[test]
+fn secret() {
+    let secret = Secret::tls("web-tls", "CERT", b"KEY".to_vec());
+    let json = ::serde_json::to_value(&secret).unwrap();
+    assert_eq!(json["kind"], "Secret");
+    assert_eq!(json["type"], "kubernetes.io/tls");
+    assert_eq!(
+        json["data"],
+        json!({"tls.crt": "Q0VSVA==", "tls.key": "S0VZ"})
+    );
+    assert!(json.get("stringData").is_none());
+    let secret: Secret = ::serde_json::from_value(json).unwrap();
+    assert_eq!(secret.tls_cert(), Some(&b"CERT"[..]));
+    assert_eq!(secret.tls_key(), Some(&b"KEY"[..]));
+    assert!(!format!("{:?}", secret).contains("CERT"));
+
+    let mut secret = Secret::opaque("db", vec![("user", "admin"), ("password", "s3cret")]);
+    secret
+        .string_data
+        .insert("password".to_string(), "hunter2".to_string());
+    assert_eq!(secret.get_str("password").unwrap(), Some("hunter2"));
+    secret.insert("password", vec![0xff]);
+    assert!(secret.string_data.is_empty());
+    assert!(secret.get_str("password").is_err());
+    assert_eq!(secret.get_str("missing").unwrap(), None);
+
+    let secret = Secret::docker_config_json("pull", "ghcr.io", "bot", "tok:en");
+    let config = secret.docker_config().unwrap().unwrap();
+    let entry = &config.auths["ghcr.io"];
+    assert_eq!(entry.auth.as_ref().unwrap().as_str(), "Ym90OnRvazplbg==");
+    assert_eq!(
+        entry.credentials(),
+        Some(("bot".to_string(), "tok:en".to_string()))
+    );
+    assert_eq!(Secret::default().docker_config().unwrap(), None);
+}
+
+#[test]
+fn config_map_from_files() {
+    use std::env;
+    use std::process;
+
+    let dir = env::temp_dir().join(format!("kubernetes-configmap-{}", process::id()));
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("app.conf"), "listen 80\n").unwrap();
+    fs::write(dir.join("logo.png"), [0x89, b'P', b'N', b'G', 0xff]).unwrap();
+    fs::write(dir.join("nested").join("ignored"), "x").unwrap();
+
+    let cm = ConfigMap::from_dir("web", &dir).unwrap();
+    assert_eq!(cm.metadata.name.as_deref(), Some("web"));
+    assert_eq!(cm.data.keys().collect::<Vec<_>>(), vec!["app.conf"]);
+    assert_eq!(cm.get("app.conf"), Some(&b"listen 80\n"[..]));
+    assert_eq!(
+        cm.get("logo.png"),
+        Some(&[0x89, b'P', b'N', b'G', 0xff][..])
+    );
+    assert!(cm.validate().is_ok());
+    let json = ::serde_json::to_value(&cm).unwrap();
+    assert_eq!(json["binaryData"]["logo.png"], "iVBOR/8=");
+
+    let mut cm = ConfigMap::from_files("web", &[dir.join("app.conf")]).unwrap();
+    assert!(cm.insert_file(None, dir.join("app.conf")).is_err());
+    cm.insert_file(Some("nginx.conf"), dir.join("app.conf"))
+        .unwrap();
+    assert!(cm.insert_file(Some("a/b"), dir.join("app.conf")).is_err());
+    assert!(ConfigMap::from_files("web", &[dir.join("missing")]).is_err());
+    assert_eq!(cm.data.len(), 2);
+
+    cm.binary_data.insert("app.conf".to_string(), "x".into());
+    assert!(cm.validate().is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn pod_status() {
+    let pod: Pod = ::serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {"name": "web-0"},
+        "status": {
+            "phase": "Running",
+            "conditions": [
+                {"type": "Ready", "status": "False", "reason": "ContainersNotReady"},
+                {"type": "PodScheduled", "status": "True"},
+            ],
+            "initContainerStatuses": [{
+                "name": "init", "ready": true, "restartCount": 1, "image": "busybox", "imageID": "",
+                "state": {"terminated": {"exitCode": 0}},
+            }],
+            "containerStatuses": [{
+                "name": "web", "ready": true, "restartCount": 2, "image": "nginx", "imageID": "",
+                "state": {"running": {"startedAt": "2024-01-01T00:00:00Z"}},
+            }, {
+                "name": "sidecar", "ready": false, "restartCount": 5, "image": "envoy", "imageID": "",
+                "state": {"waiting": {"reason": "CrashLoopBackOff"}},
+            }],
+        },
+    }))
+    .unwrap();
+    assert!(pod.is_running());
+    assert!(!pod.is_finished());
+    assert!(!pod.is_ready());
+    assert_eq!(
+        pod.condition(PodConditionType::Ready)
+            .and_then(|c| c.reason.as_deref()),
+        Some("ContainersNotReady")
+    );
+    assert!(pod.condition(PodConditionType::Initialized).is_none());
+    assert_eq!(pod.restart_count(), 7);
+    assert!(pod.container_status("web").unwrap().is_running());
+    let sidecar = pod.container_status("sidecar").unwrap();
+    assert_eq!(sidecar.waiting_reason(), Some("CrashLoopBackOff"));
+    assert_eq!(pod.container_status("init").unwrap().restart_count, 1);
+    assert!(pod.container_status("db").is_none());
+    assert_eq!(Pod::default().phase(), PodPhase::Unknown);
+}
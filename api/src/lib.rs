@@ -1,3 +1,4 @@
+extern crate base64;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -7,31 +8,97 @@ extern crate serde_json;
 extern crate failure;
 #[cfg(test)]
 extern crate serde_yaml;
+#[cfg(feature = "conformance")]
+extern crate sha2;
 
 use serde::de::{self, Deserialize, Deserializer, Unexpected};
 use serde::ser::{Serialize, Serializer};
 use std::borrow::Cow;
 use std::fmt;
 use std::marker::PhantomData;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+pub mod admission;
+pub mod apiextensions;
+pub mod apiregistration;
 pub mod apps;
+pub mod autoscaling;
+pub mod batch;
+mod bytestring;
+pub mod conformance;
 pub mod core;
 mod intstr;
+pub mod merge;
 pub mod meta;
+pub mod metrics;
+mod quantity;
+mod redacted;
+pub mod schema;
+pub mod stats;
 pub mod unstructured;
+pub mod validation;
+pub mod version;
 
 pub type Time = String;
 pub type Integer = i32;
+pub use self::bytestring::ByteString;
 pub use self::intstr::IntOrString;
+pub use self::quantity::{Format, ParseQuantityError, Quantity, ResourceList};
+pub use self::redacted::{redact_secret_data, Redacted};
 
-// A fixed-point integer, serialised as a particular string format.
-// See k8s.io/apimachinery/pkg/api/resource/quantity.go
-// TODO: implement this with some appropriate Rust type.
-pub type Quantity = String;
+/// The current time, as RFC 3339.
+pub fn now() -> Time {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // Civil from days, after Howard Hinnant.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
 
+pub const JSON: &str = "application/json";
 pub const JSON_PATCH: &'static str = "application/json-patch+json";
 pub const MERGE_PATCH: &'static str = "application/merge-patch+json";
 pub const STRATEGIC_MERGE_PATCH: &'static str = "application/strategic-merge-patch+json";
+/// Server-side apply.  JSON is YAML, so the body may be either.
+pub const APPLY_PATCH: &str = "application/apply-patch+yaml";
+
+/// Fill in unset fields with the defaults the apiserver would give
+/// them, so that a local object compares equal to the live one.
+///
+/// Most defaults are applied when deserializing; these are the ones
+/// that depend on other fields of the object.
+pub trait SetDefaults {
+    fn set_defaults(&mut self);
+}
+
+/// An object can't be converted to another version of its kind
+/// without losing information.
+#[derive(Debug, Clone, PartialEq, Fail)]
+#[fail(display = "Unable to convert to {}: {}", api_version, reason)]
+pub struct ConversionError {
+    /// The version being converted to.
+    pub api_version: &'static str,
+    pub reason: String,
+}
 
 pub trait TypeMeta {
     fn api_version() -> &'static str;
@@ -40,9 +107,16 @@ pub trait TypeMeta {
 
 /// Zero-sized struct that serializes to/from apiVersion/kind struct
 /// based on type parameter.
-#[derive(Default, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct TypeMetaImpl<T>(PhantomData<T>);
 
+// Not derived, since that would require `T: Default`
+impl<T> Default for TypeMetaImpl<T> {
+    fn default() -> Self {
+        TypeMetaImpl(PhantomData)
+    }
+}
+
 impl<T: TypeMeta> ::serde::de::Expected for TypeMetaImpl<T> {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         write!(formatter, "{}/{}", T::api_version(), T::kind())
@@ -236,4 +310,11 @@ mod tests {
             "invalid value: v1alpha1/NotTest, expected v1alpha1/Test",
         );
     }
+
+    #[test]
+    fn test_now() {
+        let now = now();
+        assert_eq!(now.len(), 20);
+        assert!(now.starts_with("20"));
+    }
 }
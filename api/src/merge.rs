@@ -0,0 +1,223 @@
+//! Deep merging of objects, eg: to layer a user's overrides on top of a
+//! generated manifest.
+//!
+//! Objects are merged field by field.  Lists are replaced, except those
+//! the apiserver's strategic merge patches merge by a key (its
+//! `patchMergeKey`), eg: containers by `name`: items of the overlay
+//! replace (or rather, are merged into) the base's items with the same
+//! key, and the rest are appended.
+//!
+//! Fields that are null or empty lists in the overlay are left as they
+//! are in the base, since that is how unset fields of typed objects
+//! serialize.  Other fields with default values are not: when merging
+//! typed objects, any field the overlay's type always serializes (eg:
+//! `Container::stdin`) overrides the base's, so partial overlays are
+//! better written as JSON, and merged with `merge_values`.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+
+/// The key fields of the lists that are merged item by item, by the name
+/// of the field holding the list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeKeys {
+    keys: HashMap<String, Vec<String>>,
+}
+
+impl MergeKeys {
+    /// No keys: all lists are replaced.
+    pub fn empty() -> Self {
+        MergeKeys {
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Merge the lists in fields called `field` by `key`.  A field may
+    /// have several keys, for lists in different types, eg: `ports` is
+    /// merged by `containerPort` in containers, and `port` in Services;
+    /// each item is matched by the first of them it has.
+    pub fn with(mut self, field: &str, key: &str) -> Self {
+        self.keys
+            .entry(field.to_string())
+            .or_default()
+            .push(key.to_string());
+        self
+    }
+
+    fn get(&self, field: &str) -> Option<&[String]> {
+        self.keys.get(field).map(Vec::as_slice)
+    }
+}
+
+/// The merge keys of the core and apps types' lists.
+impl Default for MergeKeys {
+    fn default() -> Self {
+        MergeKeys::empty()
+            .with("containers", "name")
+            .with("initContainers", "name")
+            .with("ephemeralContainers", "name")
+            .with("volumes", "name")
+            .with("volumeMounts", "mountPath")
+            .with("env", "name")
+            .with("ports", "containerPort")
+            .with("ports", "port")
+            .with("imagePullSecrets", "name")
+            .with("hostAliases", "ip")
+            .with("ownerReferences", "uid")
+            .with("conditions", "type")
+    }
+}
+
+/// Merge `overlay` into `base`, with the default `MergeKeys`.
+pub fn merge<T: Serialize + DeserializeOwned>(
+    base: &T,
+    overlay: &T,
+) -> Result<T, serde_json::Error> {
+    let mut merged = serde_json::to_value(base)?;
+    merge_values(
+        &mut merged,
+        &serde_json::to_value(overlay)?,
+        &MergeKeys::default(),
+    );
+    serde_json::from_value(merged)
+}
+
+/// Merge `overlay` into `base`.
+pub fn merge_values(base: &mut Value, overlay: &Value, keys: &MergeKeys) {
+    merge_field(base, overlay, None, keys)
+}
+
+fn merge_field(base: &mut Value, overlay: &Value, field: Option<&str>, keys: &MergeKeys) {
+    match (base, overlay) {
+        (_, Value::Null) => (),
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                match base.get_mut(k) {
+                    Some(b) => merge_field(b, v, Some(k), keys),
+                    None if v.is_null() => (),
+                    None => {
+                        base.insert(k.clone(), v.clone());
+                    }
+                }
+            }
+        }
+        (_, Value::Array(overlay)) if overlay.is_empty() => (),
+        (Value::Array(base), Value::Array(overlay)) => {
+            let merge_keys = match field.and_then(|f| keys.get(f)) {
+                Some(merge_keys) => merge_keys,
+                None => {
+                    *base = overlay.clone();
+                    return;
+                }
+            };
+            for item in overlay {
+                let key = merge_keys
+                    .iter()
+                    .find(|k| item.get(k.as_str()).is_some_and(|v| !v.is_null()));
+                let existing = key.and_then(|key| {
+                    base.iter_mut()
+                        .find(|b| b.get(key.as_str()) == item.get(key.as_str()))
+                });
+                match existing {
+                    Some(b) => merge_field(b, item, None, keys),
+                    None => base.push(item.clone()),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+#[test]
+fn merge_manifests() {
+    let mut base = json!({
+        "metadata": {"name": "web", "labels": {"app": "web"}},
+        "spec": {"template": {"spec": {
+            "containers": [{
+                "name": "web",
+                "image": "nginx:1.18",
+                "args": ["-g", "daemon off;"],
+                "env": [{"name": "A", "value": "1"}, {"name": "B", "value": "2"}],
+                "ports": [{"containerPort": 80, "name": "http"}],
+            }],
+            "volumes": [],
+        }}},
+    });
+    let overlay = json!({
+        "metadata": {"labels": {"tier": "frontend"}, "annotations": null},
+        "spec": {"template": {"spec": {
+            "containers": [
+                {
+                    "name": "web",
+                    "image": "nginx:1.19",
+                    "args": ["-c", "/etc/nginx.conf"],
+                    "env": [{"name": "B", "value": "3"}, {"name": "C", "value": "4"}],
+                    "ports": [],
+                },
+                {"name": "sidecar", "image": "envoy"},
+            ],
+        }}},
+    });
+    merge_values(&mut base, &overlay, &MergeKeys::default());
+    assert_eq!(
+        base,
+        json!({
+            "metadata": {"name": "web", "labels": {"app": "web", "tier": "frontend"}},
+            "spec": {"template": {"spec": {
+                "containers": [
+                    {
+                        "name": "web",
+                        "image": "nginx:1.19",
+                        "args": ["-c", "/etc/nginx.conf"],
+                        "env": [
+                            {"name": "A", "value": "1"},
+                            {"name": "B", "value": "3"},
+                            {"name": "C", "value": "4"},
+                        ],
+                        "ports": [{"containerPort": 80, "name": "http"}],
+                    },
+                    {"name": "sidecar", "image": "envoy"},
+                ],
+                "volumes": [],
+            }}},
+        })
+    );
+
+    let mut base = json!({"containers": [{"name": "a"}]});
+    merge_values(
+        &mut base,
+        &json!({"containers": [{"name": "b"}]}),
+        &MergeKeys::empty(),
+    );
+    assert_eq!(base, json!({"containers": [{"name": "b"}]}));
+}
+
+#[test]
+fn merge_typed() {
+    use crate::core::v1::{Container, PodSpec};
+
+    let base = PodSpec {
+        containers: vec![Container {
+            name: "web".to_string(),
+            image: Some("nginx".to_string()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let overlay = PodSpec {
+        containers: vec![Container {
+            name: "web".to_string(),
+            working_dir: Some("/srv".to_string()),
+            ..Default::default()
+        }],
+        node_name: Some("node-1".to_string()),
+        ..Default::default()
+    };
+    let merged = merge(&base, &overlay).unwrap();
+    assert_eq!(merged.containers.len(), 1);
+    assert_eq!(merged.containers[0].image.as_deref(), Some("nginx"));
+    assert_eq!(merged.containers[0].working_dir.as_deref(), Some("/srv"));
+    assert_eq!(merged.node_name.as_deref(), Some("node-1"));
+}
@@ -1,7 +1,10 @@
-use crate::{Integer, Time, TypeMeta, TypeMetaImpl};
+use crate::core::v1::ConditionStatus;
+use crate::{now, Integer, Time, TypeMeta, TypeMetaImpl};
+use serde::Deserialize;
+use serde_json::value::RawValue;
 use serde_json::{Map, Value};
-use std::borrow::Cow;
-use std::collections::HashMap;
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error as StdError;
 use std::fmt;
 use std::slice;
@@ -27,6 +30,10 @@ pub struct ObjectMeta {
     pub labels: HashMap<String, String>,
     #[serde(default)]
     pub owner_references: Vec<OwnerReference>,
+    /// Which field managers own which fields.  Large, so left out when
+    /// empty; see also `strip_managed_fields`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub managed_fields: Vec<ManagedFieldsEntry>,
     pub resource_version: Option<String>,
     pub self_link: Option<String>,
     pub uid: Option<String>,
@@ -37,6 +44,10 @@ pub struct ObjectMeta {
 pub struct ListMeta {
     #[serde(rename = "continue")]
     pub continu: Option<String>,
+    /// Roughly how many more items there are after this page, if the
+    /// apiserver knows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_item_count: Option<i64>,
     #[serde(default)]
     pub resource_version: String,
     #[serde(default)]
@@ -93,11 +104,7 @@ impl fmt::Display for Status {
         }
         if let Some(ref d) = self.details {
             for cause in &d.causes {
-                match (&cause.message, &cause.reason) {
-                    (&Some(ref msg), _) => write!(f, ", caused by {}", msg)?,
-                    (&None, &Some(ref reason)) => write!(f, ", caused by {:?}", reason)?,
-                    (&None, &None) => (),
-                }
+                write!(f, "{}", cause)?;
             }
         }
         Ok(())
@@ -127,9 +134,15 @@ pub enum StatusReason {
     InternalError,
     Expired,
     ServiceUnavailable,
+    NotAcceptable,
+    RequestEntityTooLarge,
+    UnsupportedMediaType,
+    /// A reason not known to this library.
+    #[serde(other)]
+    Unknown,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct StatusDetails {
     #[serde(default)]
@@ -144,11 +157,31 @@ pub struct StatusDetails {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct StatusCause {
+    /// The path of the field at fault, eg: `spec.containers[0].image`,
+    /// or empty if the cause isn't about one field.
+    #[serde(default)]
     pub field: String,
     pub message: Option<String>,
     pub reason: Option<CauseType>,
 }
 
+/// As `, caused by <field>: <message>`, for appending to the message of
+/// its `Status`.
+impl fmt::Display for StatusCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let field = if self.field.is_empty() {
+            String::new()
+        } else {
+            format!("{}: ", self.field)
+        };
+        match (&self.message, &self.reason) {
+            (&Some(ref msg), _) => write!(f, ", caused by {}{}", field, msg),
+            (&None, &Some(ref reason)) => write!(f, ", caused by {}{:?}", field, reason),
+            (&None, &None) => Ok(()),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum CauseType {
     FieldValueNotFound,
@@ -157,6 +190,86 @@ pub enum CauseType {
     FieldValueInvalid,
     FieldValueNotSupported,
     UnexpectedServerResponse,
+    /// Server-side apply would take a field owned by another field
+    /// manager.
+    FieldManagerConflict,
+    /// A cause not known to this library.
+    #[serde(other)]
+    Unknown,
+}
+
+/// How a field manager last set its fields.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagedFieldsOperation {
+    Apply,
+    Update,
+}
+
+/// The fields one field manager owns, from one kind of operation.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagedFieldsEntry {
+    pub manager: Option<String>,
+    pub operation: Option<ManagedFieldsOperation>,
+    pub api_version: Option<String>,
+    pub time: Option<Time>,
+    /// Always `FieldsV1`, so far.
+    pub fields_type: Option<String>,
+    /// The fields, as a trie.  See `fields`.
+    pub fields_v1: Option<Value>,
+    pub subresource: Option<String>,
+}
+
+impl ManagedFieldsEntry {
+    /// The owned fields, as paths like `.spec.replicas`, with list
+    /// items as `[name="web"]` (by key), `[="x"]` (by value) or `[0]`
+    /// (by index).  A path ending in a list item or map means the item
+    /// itself is owned, not just the fields under it.
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields = vec![];
+        if let Some(Value::Object(ref trie)) = self.fields_v1 {
+            fields_from_trie(trie, "", &mut fields);
+        }
+        fields
+    }
+}
+
+fn fields_from_trie(trie: &Map<String, Value>, prefix: &str, fields: &mut Vec<String>) {
+    for (key, child) in trie {
+        if key == "." {
+            fields.push(prefix.to_string());
+            continue;
+        }
+        let path = match key.split_at(key.find(':').map_or(0, |i| i + 1)) {
+            ("f:", name) => format!("{}.{}", prefix, name),
+            ("i:", index) => format!("{}[{}]", prefix, index),
+            ("v:", value) => format!("{}[={}]", prefix, value),
+            ("k:", keys) => match serde_json::from_str::<Map<String, Value>>(keys) {
+                Ok(keys) => {
+                    let keys: Vec<String> =
+                        keys.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                    format!("{}[{}]", prefix, keys.join(","))
+                }
+                Err(_) => format!("{}[{}]", prefix, keys),
+            },
+            _ => format!("{}.{}", prefix, key),
+        };
+        match child.as_object() {
+            Some(child) if !child.is_empty() => fields_from_trie(child, &path, fields),
+            _ => fields.push(path),
+        }
+    }
+}
+
+/// Remove `metadata.managedFields` from an object, or from each item of
+/// a list, eg: before printing or comparing objects.
+pub fn strip_managed_fields(object: &mut Value) {
+    if let Some(meta) = object.get_mut("metadata").and_then(Value::as_object_mut) {
+        meta.remove("managedFields");
+    }
+    if let Some(items) = object.get_mut("items").and_then(Value::as_array_mut) {
+        items.iter_mut().for_each(strip_managed_fields);
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -172,6 +285,235 @@ pub struct OwnerReference {
     pub controller: bool,
 }
 
+impl OwnerReference {
+    /// A reference to `owner`, for the `ownerReferences` of an object
+    /// that should be garbage collected when `owner` is deleted.
+    ///
+    /// `owner` needs its `apiVersion`, `kind`, name and uid, so must
+    /// have been read back from the apiserver, not just built locally.
+    pub fn new<T: Metadata>(owner: &T) -> Result<Self, IncompleteOwnerError> {
+        let meta = owner.metadata();
+        let missing = |field| IncompleteOwnerError { field };
+        if owner.api_version().is_empty() {
+            return Err(missing("apiVersion"));
+        }
+        if owner.kind().is_empty() {
+            return Err(missing("kind"));
+        }
+        Ok(OwnerReference {
+            api_version: owner.api_version().to_string(),
+            kind: owner.kind().to_string(),
+            name: meta.name.clone().ok_or_else(|| missing("name"))?,
+            uid: meta.uid.clone().ok_or_else(|| missing("uid"))?,
+            block_owner_deletion: false,
+            controller: false,
+        })
+    }
+
+    /// Set `blockOwnerDeletion`, so that a foreground deletion of the
+    /// owner waits for this object to be deleted first.
+    pub fn block_owner_deletion(mut self, block: bool) -> Self {
+        self.block_owner_deletion = block;
+        self
+    }
+
+    /// Whether this refers to `owner`.  Only the uid is compared, so a
+    /// new object with the same name is not the owner.
+    pub fn refers_to<T: Metadata>(&self, owner: &T) -> bool {
+        owner.metadata().uid.as_ref() == Some(&self.uid)
+    }
+}
+
+/// A reference to `owner` as the managing controller of an object,
+/// with `blockOwnerDeletion` set, as controllers set on the objects they
+/// create.  See `OwnerReference::new`.
+pub fn controller_ref<T: Metadata>(owner: &T) -> Result<OwnerReference, IncompleteOwnerError> {
+    Ok(OwnerReference {
+        controller: true,
+        ..OwnerReference::new(owner)?.block_owner_deletion(true)
+    })
+}
+
+/// An owner reference can't be made to an object missing `field`.
+#[derive(Debug, Clone, PartialEq, Fail)]
+#[fail(display = "owner has no {}, so can't be referenced", field)]
+pub struct IncompleteOwnerError {
+    pub field: &'static str,
+}
+
+impl ObjectMeta {
+    /// The managers owning each field, by path.  See
+    /// `ManagedFieldsEntry::fields`.
+    pub fn field_managers(&self) -> BTreeMap<String, Vec<&str>> {
+        let mut owners = BTreeMap::new();
+        for entry in &self.managed_fields {
+            let manager = entry.manager.as_deref().unwrap_or_default();
+            for field in entry.fields() {
+                owners.entry(field).or_insert_with(Vec::new).push(manager);
+            }
+        }
+        owners
+    }
+
+    /// The owner reference marked as the managing controller, if any.
+    pub fn controller(&self) -> Option<&OwnerReference> {
+        self.owner_references.iter().find(|r| r.controller)
+    }
+
+    /// Whether `owner` is one of this object's owners.
+    pub fn is_owned_by<T: Metadata>(&self, owner: &T) -> bool {
+        self.owner_references.iter().any(|r| r.refers_to(owner))
+    }
+
+    /// Whether `owner` is this object's managing controller.
+    pub fn is_controlled_by<T: Metadata>(&self, owner: &T) -> bool {
+        self.controller().is_some_and(|r| r.refers_to(owner))
+    }
+
+    /// Add `owner` to the owner references, replacing any existing
+    /// reference to the same object.
+    pub fn set_owner(&mut self, owner: OwnerReference) {
+        self.owner_references.retain(|r| r.uid != owner.uid);
+        self.owner_references.push(owner);
+    }
+}
+
+/// A condition in an object's status, in the form shared by newer
+/// built-in kinds and recommended for custom resources.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Condition {
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub status: ConditionStatus,
+    /// The `metadata.generation` the condition was set for.
+    pub observed_generation: Option<i64>,
+    pub last_transition_time: Option<Time>,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+impl Condition {
+    pub fn new<T: Into<String>>(typ: T, status: ConditionStatus) -> Self {
+        Condition {
+            typ: typ.into(),
+            status,
+            observed_generation: None,
+            last_transition_time: None,
+            reason: String::new(),
+            message: String::new(),
+        }
+    }
+
+    pub fn reason<R: Into<String>>(mut self, reason: R) -> Self {
+        self.reason = reason.into();
+        self
+    }
+
+    pub fn message<M: Into<String>>(mut self, message: M) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    pub fn observed_generation(mut self, generation: i64) -> Self {
+        self.observed_generation = Some(generation);
+        self
+    }
+}
+
+/// The parts of a status condition that the condition helpers need,
+/// so that they work with `Condition` and the kind specific condition
+/// types alike.
+pub trait StatusCondition {
+    type Type: PartialEq;
+    fn condition_type(&self) -> &Self::Type;
+    fn condition_status(&self) -> ConditionStatus;
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time>;
+}
+
+impl StatusCondition for Condition {
+    type Type = String;
+    fn condition_type(&self) -> &String {
+        &self.typ
+    }
+    fn condition_status(&self) -> ConditionStatus {
+        self.status
+    }
+    fn last_transition_time_mut(&mut self) -> &mut Option<Time> {
+        &mut self.last_transition_time
+    }
+}
+
+/// The condition of type `typ`, if there is one.
+pub fn find_condition<'a, C, Q>(conditions: &'a [C], typ: &Q) -> Option<&'a C>
+where
+    C: StatusCondition,
+    C::Type: Borrow<Q>,
+    Q: PartialEq + ?Sized,
+{
+    conditions
+        .iter()
+        .find(|c| c.condition_type().borrow() == typ)
+}
+
+/// Whether there is a condition of type `typ` with status `True`.
+pub fn is_condition_true<C, Q>(conditions: &[C], typ: &Q) -> bool
+where
+    C: StatusCondition,
+    C::Type: Borrow<Q>,
+    Q: PartialEq + ?Sized,
+{
+    find_condition(conditions, typ).is_some_and(|c| c.condition_status() == ConditionStatus::True)
+}
+
+/// Add `condition`, or replace the existing condition of its type.
+///
+/// `lastTransitionTime` is kept from the existing condition if the
+/// status hasn't changed, and otherwise set to now, unless `condition`
+/// sets it.  Returns whether the status changed (or the condition is
+/// new).
+pub fn set_condition<C: StatusCondition>(conditions: &mut Vec<C>, mut condition: C) -> bool {
+    let existing = conditions
+        .iter_mut()
+        .find(|c| c.condition_type() == condition.condition_type());
+    match existing {
+        Some(c) => {
+            let changed = c.condition_status() != condition.condition_status();
+            if condition.last_transition_time_mut().is_none() {
+                *condition.last_transition_time_mut() = if changed {
+                    Some(now())
+                } else {
+                    c.last_transition_time_mut().take()
+                };
+            }
+            *c = condition;
+            changed
+        }
+        None => {
+            if condition.last_transition_time_mut().is_none() {
+                *condition.last_transition_time_mut() = Some(now());
+            }
+            conditions.push(condition);
+            true
+        }
+    }
+}
+
+/// Remove the condition of type `typ`, returning it.
+pub fn remove_condition<C, Q>(conditions: &mut Vec<C>, typ: &Q) -> Option<C>
+where
+    C: StatusCondition,
+    C::Type: Borrow<Q>,
+    Q: PartialEq + ?Sized,
+{
+    let i = conditions
+        .iter()
+        .position(|c| c.condition_type().borrow() == typ)?;
+    Some(conditions.remove(i))
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct LabelSelector {
@@ -208,6 +550,7 @@ pub struct APIResourceList {
 #[serde(rename_all = "camelCase")]
 pub struct APIResource {
     pub name: String,
+    #[serde(default)]
     pub singular_name: String,
     pub namespaced: bool,
     pub group: Option<String>,
@@ -231,9 +574,18 @@ pub struct APIGroupList {
 #[serde(rename_all = "camelCase")]
 pub struct APIGroup {
     pub name: String,
-    pub versions: GroupVersionForDiscovery,
+    pub versions: Vec<GroupVersionForDiscovery>,
     pub preferred_version: Option<GroupVersionForDiscovery>,
-    #[serde(rename = "serverAddressByClientCIDRs")]
+    #[serde(default, rename = "serverAddressByClientCIDRs")]
+    pub server_address_by_client_cidrs: Vec<ServerAddressByClientCIDR>,
+}
+
+/// The versions served by the legacy (core) API at `/api`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct APIVersions {
+    pub versions: Vec<String>,
+    #[serde(default, rename = "serverAddressByClientCIDRs")]
     pub server_address_by_client_cidrs: Vec<ServerAddressByClientCIDR>,
 }
 
@@ -252,12 +604,82 @@ pub struct ServerAddressByClientCIDR {
     pub server_address: String,
 }
 
+/// A change to an object.  The object of an `ERROR` event is a
+/// `Status`, so typed watches turn those into errors.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
-pub struct WatchEvent {
+pub struct WatchEvent<T = Value> {
     #[serde(rename = "type")]
     pub typ: EventType,
-    pub object: Value,
+    pub object: T,
+}
+
+/// A `WatchEvent` borrowing from the line it was decoded from, with
+/// the object left undecoded.  For handling many events cheaply: the
+/// object need only be decoded if the event is interesting, and then
+/// straight into its own type, rather than via a `Value`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct WatchEventRef<'a> {
+    #[serde(rename = "type")]
+    pub typ: EventType,
+    #[serde(borrow)]
+    pub object: &'a RawValue,
+}
+
+impl<'a> WatchEventRef<'a> {
+    /// Decode the object.
+    pub fn object<T: Deserialize<'a>>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(self.object.get())
+    }
+
+    /// Decode just the identifying parts of the object's metadata.
+    pub fn metadata(&self) -> serde_json::Result<ObjectMetaRef<'a>> {
+        #[derive(Deserialize)]
+        struct Object<'a> {
+            #[serde(borrow, default)]
+            metadata: ObjectMetaRef<'a>,
+        }
+        Ok(self.object::<Object>()?.metadata)
+    }
+}
+
+/// The identifying parts of `ObjectMeta`, borrowed where possible
+/// (strings with escapes can't be).
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectMetaRef<'a> {
+    #[serde(borrow, default, deserialize_with = "borrow_opt")]
+    pub name: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrow_opt")]
+    pub namespace: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrow_opt")]
+    pub uid: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrow_opt")]
+    pub resource_version: Option<Cow<'a, str>>,
+}
+
+/// `ListMeta`, borrowed where possible.  Opt-in, for callers that
+/// handle list bodies themselves: the client's pager decodes each page
+/// into owned items anyway, so it uses `ListMeta`.
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListMetaRef<'a> {
+    #[serde(borrow, default, deserialize_with = "borrow_opt", rename = "continue")]
+    pub continu: Option<Cow<'a, str>>,
+    pub remaining_item_count: Option<i64>,
+    #[serde(borrow, default)]
+    pub resource_version: Cow<'a, str>,
+}
+
+/// serde only borrows a `Cow` directly in a field, not inside an
+/// `Option`.
+fn borrow_opt<'de: 'a, 'a, D>(d: D) -> Result<Option<Cow<'a, str>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+    Ok(Option::<Borrowed>::deserialize(d)?.map(|b| b.0))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -279,7 +701,8 @@ where
     #[serde(flatten)]
     typemeta: TypeMetaImpl<ItemList<T>>,
     pub metadata: ListMeta,
-    #[serde(default)]
+    // Not plain `default`, which would require `T: Default`
+    #[serde(default = "Vec::new")]
     pub items: Vec<T>,
 }
 
@@ -395,6 +818,61 @@ pub trait Metadata {
     fn api_version(&self) -> &str;
     fn kind(&self) -> &str;
     fn metadata(&self) -> Cow<ObjectMeta>;
+
+    /// The value of the label `key`, if set.
+    fn label(&self, key: &str) -> Option<String> {
+        self.metadata().labels.get(key).cloned()
+    }
+
+    fn has_label(&self, key: &str) -> bool {
+        self.metadata().labels.contains_key(key)
+    }
+
+    /// The value of the annotation `key`, if set.
+    fn annotation(&self, key: &str) -> Option<String> {
+        self.metadata().annotations.get(key).cloned()
+    }
+
+    fn has_annotation(&self, key: &str) -> bool {
+        self.metadata().annotations.contains_key(key)
+    }
+}
+
+/// Objects whose metadata can be changed in place.  Implemented by
+/// the typed objects and `DynamicObject`, but not `Value`, which has no
+/// `ObjectMeta` to borrow.
+pub trait MetadataMut: Metadata {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta;
+
+    fn labels_mut(&mut self) -> &mut HashMap<String, String> {
+        &mut self.metadata_mut().labels
+    }
+
+    fn annotations_mut(&mut self) -> &mut HashMap<String, String> {
+        &mut self.metadata_mut().annotations
+    }
+
+    /// Set the label `key`, returning its previous value.
+    fn set_label<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) -> Option<String> {
+        self.labels_mut().insert(key.into(), value.into())
+    }
+
+    fn remove_label(&mut self, key: &str) -> Option<String> {
+        self.labels_mut().remove(key)
+    }
+
+    /// Set the annotation `key`, returning its previous value.
+    fn set_annotation<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Option<String> {
+        self.annotations_mut().insert(key.into(), value.into())
+    }
+
+    fn remove_annotation(&mut self, key: &str) -> Option<String> {
+        self.annotations_mut().remove(key)
+    }
 }
 
 pub trait List {
@@ -455,6 +933,228 @@ mod tests {
         assert_eq!(j.metadata().name.as_ref().unwrap(), "pod-example");
     }
 
+    #[test]
+    fn discovery() {
+        use super::APIGroupList;
+        let groups: APIGroupList = serde_json::from_value(json!({
+            "kind": "APIGroupList",
+            "apiVersion": "v1",
+            "groups": [{
+                "name": "apps",
+                "versions": [
+                    {"groupVersion": "apps/v1", "version": "v1"},
+                    {"groupVersion": "apps/v1beta2", "version": "v1beta2"},
+                ],
+                "preferredVersion": {"groupVersion": "apps/v1", "version": "v1"},
+            }],
+        }))
+        .unwrap();
+        assert_eq!(groups.groups[0].versions.len(), 2);
+        assert_eq!(
+            groups.groups[0].preferred_version.as_ref().unwrap().version,
+            "v1"
+        );
+    }
+
+    #[test]
+    fn owner_references() {
+        use super::{controller_ref, IncompleteOwnerError, ObjectMeta, OwnerReference};
+
+        let mut owner = json!({
+            "apiVersion": "apps/v1",
+            "kind": "ReplicaSet",
+            "metadata": {"name": "web"},
+        });
+        assert_eq!(
+            OwnerReference::new(&owner),
+            Err(IncompleteOwnerError { field: "uid" })
+        );
+        owner["metadata"]["uid"] = "1234".into();
+        let r = controller_ref(&owner).unwrap();
+        assert_eq!(
+            serde_json::to_value(&r).unwrap(),
+            json!({
+                "apiVersion": "apps/v1",
+                "kind": "ReplicaSet",
+                "name": "web",
+                "uid": "1234",
+                "blockOwnerDeletion": true,
+                "controller": true,
+            })
+        );
+
+        let other = json!({
+            "apiVersion": "apps/v1",
+            "kind": "ReplicaSet",
+            "metadata": {"name": "web", "uid": "5678"},
+        });
+        let mut meta = ObjectMeta::default();
+        assert!(!meta.is_owned_by(&owner));
+        meta.set_owner(OwnerReference::new(&other).unwrap());
+        meta.set_owner(r.clone());
+        meta.set_owner(r);
+        assert_eq!(meta.owner_references.len(), 2);
+        assert!(meta.is_owned_by(&owner));
+        assert!(meta.is_owned_by(&other));
+        assert!(meta.is_controlled_by(&owner));
+        assert!(!meta.is_controlled_by(&other));
+        assert_eq!(meta.controller().unwrap().uid, "1234");
+    }
+
+    #[test]
+    fn labels() {
+        use super::MetadataMut;
+        use crate::core::v1::Pod;
+
+        let j = json!({"metadata": {"labels": {"app": "web"}}});
+        assert_eq!(j.label("app").as_deref(), Some("web"));
+        assert!(!j.has_label("tier"));
+        assert_eq!(j.annotation("app"), None);
+
+        let mut pod: Pod = serde_json::from_value(pod_json()).unwrap();
+        assert_eq!(pod.set_label("app", "web"), None);
+        assert_eq!(pod.set_label("app", "db").as_deref(), Some("web"));
+        pod.set_annotation("note", "hello");
+        assert!(pod.has_label("app"));
+        assert_eq!(pod.annotation("note").as_deref(), Some("hello"));
+        assert_eq!(pod.remove_label("app").as_deref(), Some("db"));
+        assert!(pod.labels_mut().is_empty());
+    }
+
+    #[test]
+    fn conditions() {
+        use super::{
+            find_condition, is_condition_true, remove_condition, set_condition, Condition,
+        };
+        use crate::core::v1::{ConditionStatus, PodCondition, PodConditionType};
+
+        let mut conditions = vec![];
+        let ready = |status| Condition::new("Ready", status).reason("Testing");
+        assert!(set_condition(
+            &mut conditions,
+            ready(ConditionStatus::False)
+        ));
+        let first = conditions[0].last_transition_time.clone();
+        assert!(first.is_some());
+        assert!(!is_condition_true(&conditions, "Ready"));
+
+        // Unchanged status keeps the transition time.
+        let mut same = ready(ConditionStatus::False).message("still not");
+        same.last_transition_time = None;
+        assert!(!set_condition(&mut conditions, same));
+        assert_eq!(conditions[0].last_transition_time, first);
+        assert_eq!(conditions[0].message, "still not");
+
+        let mut changed = ready(ConditionStatus::True);
+        changed.last_transition_time = Some("2020-01-01T00:00:00Z".into());
+        assert!(set_condition(&mut conditions, changed));
+        assert!(is_condition_true(&conditions, "Ready"));
+        assert_eq!(
+            find_condition(&conditions, "Ready")
+                .unwrap()
+                .last_transition_time
+                .as_deref(),
+            Some("2020-01-01T00:00:00Z")
+        );
+        assert_eq!(conditions.len(), 1);
+        assert!(remove_condition(&mut conditions, "Ready").is_some());
+        assert!(conditions.is_empty());
+
+        let mut pod = vec![PodCondition {
+            typ: PodConditionType::Ready,
+            status: ConditionStatus::True,
+            last_probe_time: None,
+            last_transition_time: None,
+            reason: None,
+            message: None,
+        }];
+        assert!(is_condition_true(&pod, &PodConditionType::Ready));
+        assert!(find_condition(&pod, &PodConditionType::Initialized).is_none());
+        let mut scheduled = pod[0].clone();
+        scheduled.typ = PodConditionType::PodScheduled;
+        assert!(set_condition(&mut pod, scheduled));
+        assert_eq!(pod.len(), 2);
+    }
+
+    #[test]
+    fn managed_fields() {
+        use super::{strip_managed_fields, ManagedFieldsOperation, ObjectMeta};
+
+        let mut object = json!({
+            "metadata": {
+                "name": "web",
+                "managedFields": [
+                    {
+                        "manager": "kubectl",
+                        "operation": "Apply",
+                        "apiVersion": "apps/v1",
+                        "fieldsType": "FieldsV1",
+                        "fieldsV1": {
+                            "f:metadata": {"f:labels": {"f:app": {}}},
+                            "f:spec": {
+                                "f:replicas": {},
+                                "f:template": {"f:spec": {"f:containers": {
+                                    "k:{\"name\":\"web\"}": {".": {}, "f:image": {}},
+                                }}},
+                            },
+                        },
+                    },
+                    {
+                        "manager": "hpa",
+                        "operation": "Update",
+                        "subresource": "scale",
+                        "fieldsType": "FieldsV1",
+                        "fieldsV1": {"f:spec": {"f:replicas": {}}},
+                    },
+                ],
+            },
+        });
+        let meta = object.metadata();
+        assert_eq!(
+            meta.managed_fields[1].operation,
+            Some(ManagedFieldsOperation::Update)
+        );
+        assert_eq!(
+            meta.managed_fields[0].fields(),
+            vec![
+                ".metadata.labels.app",
+                ".spec.replicas",
+                ".spec.template.spec.containers[name=\"web\"]",
+                ".spec.template.spec.containers[name=\"web\"].image",
+            ]
+        );
+        let owners = meta.field_managers();
+        assert_eq!(owners[".spec.replicas"], vec!["kubectl", "hpa"]);
+
+        strip_managed_fields(&mut object);
+        assert_eq!(object, json!({"metadata": {"name": "web"}}));
+        assert_eq!(
+            serde_json::to_value(ObjectMeta::default()).unwrap()["managedFields"],
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn borrowed() {
+        use super::{EventType, ListMetaRef, WatchEventRef};
+        use crate::core::v1::Pod;
+        use std::borrow::Cow;
+
+        let line = br#"{"type":"ADDED","object":{"apiVersion":"v1","kind":"Pod","metadata":{"name":"pod-example","resourceVersion":"12"},"spec":{"containers":[]}}}"#;
+        let event: WatchEventRef = serde_json::from_slice(line).unwrap();
+        assert_eq!(event.typ, EventType::Added);
+        let meta = event.metadata().unwrap();
+        assert_eq!(meta.resource_version.as_deref(), Some("12"));
+        assert!(matches!(meta.name, Some(Cow::Borrowed("pod-example"))));
+        let pod: Pod = event.object().unwrap();
+        assert_eq!(pod.metadata.name.as_deref(), Some("pod-example"));
+
+        let meta: ListMetaRef =
+            serde_json::from_str(r#"{"continue":"abc","resourceVersion":"7"}"#).unwrap();
+        assert!(matches!(meta.continu, Some(Cow::Borrowed("abc"))));
+        assert_eq!(meta.resource_version, "7");
+    }
+
     #[test]
     fn typed() {
         use crate::core::v1::Pod;
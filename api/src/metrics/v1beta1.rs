@@ -0,0 +1,148 @@
+//! Resource usage, as measured by the resource metrics pipeline
+//! (usually metrics-server) and served as metrics.k8s.io.
+//! See k8s.io/metrics/pkg/apis/metrics/v1beta1
+
+use crate::core::v1::TypedResource;
+use crate::meta::v1::{ItemList, Metadata, ObjectMeta};
+use crate::meta::{GroupVersion, GroupVersionResource};
+use crate::{ResourceList, Time, TypeMeta, TypeMetaImpl};
+use std::borrow::Cow;
+
+const API_GROUP: &str = "metrics.k8s.io/v1beta1";
+pub const GROUP_VERSION: GroupVersion = GroupVersion {
+    group: "metrics.k8s.io",
+    version: "v1beta1",
+};
+
+/// The usage of a node, named after it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeMetrics {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<NodeMetrics>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    /// The end of the window the usage was measured over.
+    pub timestamp: Time,
+    /// A duration, eg `20.05s`.
+    pub window: String,
+    #[serde(default)]
+    pub usage: ResourceList,
+}
+
+pub type NodeMetricsList = ItemList<NodeMetrics>;
+
+impl TypeMeta for NodeMetrics {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "NodeMetrics"
+    }
+}
+
+impl Metadata for NodeMetrics {
+    fn api_version(&self) -> &str {
+        <NodeMetrics as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <NodeMetrics as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl TypedResource for NodeMetrics {
+    type List = NodeMetricsList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "nodes",
+    };
+    const NAMESPACED: bool = false;
+}
+
+/// The usage of the containers of a pod, named after it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PodMetrics {
+    #[serde(flatten)]
+    typemeta: TypeMetaImpl<PodMetrics>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub timestamp: Time,
+    pub window: String,
+    #[serde(default)]
+    pub containers: Vec<ContainerMetrics>,
+}
+
+pub type PodMetricsList = ItemList<PodMetrics>;
+
+impl TypeMeta for PodMetrics {
+    fn api_version() -> &'static str {
+        API_GROUP
+    }
+    fn kind() -> &'static str {
+        "PodMetrics"
+    }
+}
+
+impl Metadata for PodMetrics {
+    fn api_version(&self) -> &str {
+        <PodMetrics as TypeMeta>::api_version()
+    }
+    fn kind(&self) -> &str {
+        <PodMetrics as TypeMeta>::kind()
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl TypedResource for PodMetrics {
+    type List = PodMetricsList;
+
+    const GROUP_VERSION_RESOURCE: GroupVersionResource<'static> = GroupVersionResource {
+        group: GROUP_VERSION.group,
+        version: GROUP_VERSION.version,
+        resource: "pods",
+    };
+    const NAMESPACED: bool = true;
+}
+
+impl PodMetrics {
+    /// The usage of all the pod's containers.
+    pub fn usage(&self) -> ResourceList {
+        self.containers.iter().map(|c| &c.usage).sum()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerMetrics {
+    pub name: String,
+    #[serde(default)]
+    pub usage: ResourceList,
+}
+
+#[test]
+fn pod_metrics() {
+    let metrics: PodMetrics = serde_json::from_value(json!({
+        "apiVersion": "metrics.k8s.io/v1beta1",
+        "kind": "PodMetrics",
+        "metadata": {"name": "web-0", "namespace": "default"},
+        "timestamp": "2024-05-01T10:00:00Z",
+        "window": "15.2s",
+        "containers": [
+            {"name": "web", "usage": {"cpu": "12345678n", "memory": "30Mi"}},
+            {"name": "proxy", "usage": {"cpu": "2m", "memory": "2048Ki"}},
+        ],
+    }))
+    .unwrap();
+    assert_eq!(
+        serde_json::to_value(metrics.usage()).unwrap(),
+        json!({"cpu": "14345678n", "memory": "32Mi"})
+    );
+}
@@ -0,0 +1,601 @@
+//! Amounts of resources, eg `100m` of CPU or `1Gi` of memory, as
+//! k8s.io/apimachinery's resource.Quantity, and lists of them.
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Deref, DerefMut, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+const NANO: i128 = 1_000_000_000;
+
+/// How a quantity is written, which the results of arithmetic on it
+/// follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// With a power of 1024 suffix: `Ki`, `Mi`, `Gi`, `Ti`, `Pi` or `Ei`.
+    BinarySI,
+    /// With a power of 1000 suffix: `n`, `u`, `m`, `k`, `M`, `G`, `T`,
+    /// `P` or `E`, or none.
+    DecimalSI,
+    /// With an exponent, eg `1e3`.
+    DecimalExponent,
+}
+
+/// A fixed-point amount, exact to a billionth: finer amounts are
+/// rounded away from zero, as the apiserver does.
+///
+/// Quantities are kept as written, but compare by amount:
+///
+/// ```
+/// # use kubernetes_api::Quantity;
+/// let memory: Quantity = "1Gi".parse().unwrap();
+/// assert_eq!(memory, "1024Mi".parse().unwrap());
+/// assert_eq!(memory.to_string(), "1Gi");
+/// let cpu: Quantity = "0.5".parse().unwrap();
+/// assert_eq!((cpu.clone() + cpu).to_string(), "1");
+/// ```
+///
+/// Amounts up to about 10^29 can be held; parsing larger ones fails,
+/// and arithmetic overflowing that panics.
+#[derive(Clone)]
+pub struct Quantity {
+    nanos: i128,
+    format: Format,
+    text: String,
+}
+
+/// A string isn't a quantity.
+#[derive(Debug, Clone, PartialEq, Fail)]
+#[fail(display = "Invalid quantity {:?}", _0)]
+pub struct ParseQuantityError(pub String);
+
+impl Quantity {
+    /// `millis` thousandths, eg `Quantity::from_milli(250)` is `250m`.
+    pub fn from_milli(millis: i64) -> Self {
+        Quantity::from_nanos(i128::from(millis) * 1_000_000, Format::DecimalSI)
+    }
+
+    fn from_nanos(nanos: i128, format: Format) -> Self {
+        Quantity {
+            nanos,
+            format,
+            text: canonical(nanos, format),
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.nanos == 0
+    }
+
+    /// The amount, rounded up to a whole number, eg the bytes of a
+    /// memory quantity.  Saturates at the bounds of `i64`.
+    pub fn value(&self) -> i64 {
+        saturate(div_ceil(self.nanos, NANO))
+    }
+
+    /// The amount in thousandths, rounded up, eg the millicores of a
+    /// CPU quantity.  Saturates at the bounds of `i64`.
+    pub fn milli_value(&self) -> i64 {
+        saturate(div_ceil(self.nanos, NANO / 1000))
+    }
+
+    fn checked(nanos: Option<i128>, format: Format) -> Self {
+        Quantity::from_nanos(nanos.expect("Quantity overflowed"), format)
+    }
+}
+
+fn div_ceil(n: i128, d: i128) -> i128 {
+    let q = n / d;
+    if n % d > 0 {
+        q + 1
+    } else {
+        q
+    }
+}
+
+fn saturate(n: i128) -> i64 {
+    n.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64
+}
+
+/// The suffix of a quantity: its format, power of 1024, and power of
+/// 10.
+fn suffix(s: &str) -> Option<(Format, u32, i32)> {
+    let (format, binary, decimal) = match s {
+        "" => (Format::DecimalSI, 0, 0),
+        "n" => (Format::DecimalSI, 0, -9),
+        "u" => (Format::DecimalSI, 0, -6),
+        "m" => (Format::DecimalSI, 0, -3),
+        "k" => (Format::DecimalSI, 0, 3),
+        "M" => (Format::DecimalSI, 0, 6),
+        "G" => (Format::DecimalSI, 0, 9),
+        "T" => (Format::DecimalSI, 0, 12),
+        "P" => (Format::DecimalSI, 0, 15),
+        "E" => (Format::DecimalSI, 0, 18),
+        "Ki" => (Format::BinarySI, 1, 0),
+        "Mi" => (Format::BinarySI, 2, 0),
+        "Gi" => (Format::BinarySI, 3, 0),
+        "Ti" => (Format::BinarySI, 4, 0),
+        "Pi" => (Format::BinarySI, 5, 0),
+        "Ei" => (Format::BinarySI, 6, 0),
+        _ => {
+            let exponent = s.strip_prefix(['e', 'E'])?;
+            (Format::DecimalExponent, 0, exponent.parse().ok()?)
+        }
+    };
+    Some((format, binary, decimal))
+}
+
+/// `nanos` written in `format`, as the apiserver would write it.
+fn canonical(nanos: i128, format: Format) -> String {
+    if nanos == 0 {
+        return "0".to_string();
+    }
+    // Small and fractional binary quantities are written as decimal.
+    if format == Format::BinarySI && nanos % NANO == 0 && (nanos / NANO).abs() >= 1024 {
+        let mut n = nanos / NANO;
+        let mut power = 0;
+        while power < 6 && n % 1024 == 0 {
+            n /= 1024;
+            power += 1;
+        }
+        return format!("{}{}", n, ["", "Ki", "Mi", "Gi", "Ti", "Pi", "Ei"][power]);
+    }
+    // The smallest mantissa, with an exponent that is a multiple of 3.
+    let (mut n, mut exponent) = (nanos, -9);
+    while exponent < 18 && n % 1000 == 0 {
+        n /= 1000;
+        exponent += 3;
+    }
+    if format == Format::DecimalExponent {
+        return match exponent {
+            0 => n.to_string(),
+            _ => format!("{}e{}", n, exponent),
+        };
+    }
+    let suffix = match exponent {
+        -9 => "n",
+        -6 => "u",
+        -3 => "m",
+        0 => "",
+        3 => "k",
+        6 => "M",
+        9 => "G",
+        12 => "T",
+        15 => "P",
+        _ => "E",
+    };
+    format!("{}{}", n, suffix)
+}
+
+impl FromStr for Quantity {
+    type Err = ParseQuantityError;
+
+    fn from_str(s: &str) -> Result<Self, ParseQuantityError> {
+        let error = || ParseQuantityError(s.to_string());
+        let (negative, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, suffix) = rest.split_at(end);
+        let (whole, fraction) = match number.find('.') {
+            Some(i) => (&number[..i], &number[i + 1..]),
+            None => (number, ""),
+        };
+        if (whole.is_empty() && fraction.is_empty()) || fraction.contains('.') {
+            return Err(error());
+        }
+        let (format, binary, decimal) = self::suffix(suffix).ok_or_else(error)?;
+        let digits = format!("{}{}", whole, fraction);
+        let digits = digits.trim_start_matches('0');
+        let mut nanos: i128 = if digits.is_empty() {
+            0
+        } else {
+            digits.parse().map_err(|_| error())?
+        };
+        nanos = 1024i128
+            .checked_pow(binary)
+            .and_then(|m| nanos.checked_mul(m))
+            .ok_or_else(error)?;
+        // The exponent and the number of digits are up to the input.
+        let scale = i32::try_from(fraction.len())
+            .ok()
+            .and_then(|len| decimal.checked_add(9)?.checked_sub(len))
+            .ok_or_else(error)?;
+        if scale >= 0 {
+            nanos = 10i128
+                .checked_pow(scale.unsigned_abs())
+                .and_then(|m| nanos.checked_mul(m))
+                .ok_or_else(error)?;
+        } else {
+            nanos = match 10i128.checked_pow(scale.unsigned_abs()) {
+                Some(d) => div_ceil(nanos, d),
+                // Finer than anything an i128 of nanos can hold.
+                None => nanos.signum(),
+            };
+        }
+        if negative {
+            nanos = -nanos;
+        }
+        Ok(Quantity {
+            nanos,
+            format,
+            text: s.to_string(),
+        })
+    }
+}
+
+impl From<i64> for Quantity {
+    fn from(n: i64) -> Self {
+        Quantity::from_nanos(i128::from(n) * NANO, Format::DecimalSI)
+    }
+}
+
+impl Default for Quantity {
+    fn default() -> Self {
+        Quantity::from(0)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl fmt::Debug for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Quantity").field(&self.text).finish()
+    }
+}
+
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.nanos == other.nanos
+    }
+}
+
+impl Eq for Quantity {}
+
+impl PartialOrd for Quantity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Quantity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.nanos.cmp(&other.nanos)
+    }
+}
+
+impl Hash for Quantity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nanos.hash(state)
+    }
+}
+
+impl Add<&Quantity> for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: &Quantity) -> Quantity {
+        Quantity::checked(self.nanos.checked_add(other.nanos), self.format)
+    }
+}
+
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: Quantity) -> Quantity {
+        self + &other
+    }
+}
+
+impl Sub<&Quantity> for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: &Quantity) -> Quantity {
+        Quantity::checked(self.nanos.checked_sub(other.nanos), self.format)
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: Quantity) -> Quantity {
+        self - &other
+    }
+}
+
+impl AddAssign<&Quantity> for Quantity {
+    fn add_assign(&mut self, other: &Quantity) {
+        *self = self.clone() + other;
+    }
+}
+
+impl SubAssign<&Quantity> for Quantity {
+    fn sub_assign(&mut self, other: &Quantity) {
+        *self = self.clone() - other;
+    }
+}
+
+impl Neg for Quantity {
+    type Output = Quantity;
+
+    fn neg(self) -> Quantity {
+        Quantity::checked(self.nanos.checked_neg(), self.format)
+    }
+}
+
+impl<'a> Sum<&'a Quantity> for Quantity {
+    fn sum<I: Iterator<Item = &'a Quantity>>(iter: I) -> Quantity {
+        let mut quantities = iter.peekable();
+        let mut sum = match quantities.peek() {
+            Some(q) => Quantity::from_nanos(0, q.format),
+            None => return Quantity::default(),
+        };
+        for q in quantities {
+            sum += q;
+        }
+        sum
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct QuantityVisitor;
+
+        impl<'de> Visitor<'de> for QuantityVisitor {
+            type Value = Quantity;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a quantity")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Quantity, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            // JSON numbers are quantities too, eg `cpu: 2`.
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Quantity, E> {
+                Ok(Quantity::from(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Quantity, E> {
+                self.visit_str(&v.to_string())
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Quantity, E> {
+                self.visit_str(&v.to_string())
+            }
+        }
+
+        deserializer.deserialize_any(QuantityVisitor)
+    }
+}
+
+/// Quantities of named resources, eg `cpu` and `memory`, as in a
+/// container's requests or a node's capacity.  Resources that aren't
+/// listed count as zero.
+///
+/// ```
+/// # use kubernetes_api::ResourceList;
+/// let mut requests = ResourceList::new();
+/// requests.insert("cpu".to_string(), "250m".parse().unwrap());
+/// let total = requests.clone() + &requests;
+/// assert_eq!(total["cpu"].to_string(), "500m");
+/// assert!(requests.fits_within(&total));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ResourceList(pub BTreeMap<String, Quantity>);
+
+impl ResourceList {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The quantity of `resource`, or zero.
+    pub fn quantity(&self, resource: &str) -> Quantity {
+        self.0.get(resource).cloned().unwrap_or_default()
+    }
+
+    /// Raise each resource to at least its quantity in `other`.
+    pub fn max(&mut self, other: &ResourceList) {
+        for (resource, q) in &other.0 {
+            match self.0.get_mut(resource) {
+                Some(mine) if *mine >= *q => (),
+                Some(mine) => *mine = q.clone(),
+                None if q.nanos <= 0 => (),
+                None => {
+                    self.0.insert(resource.clone(), q.clone());
+                }
+            }
+        }
+    }
+
+    /// The resources of which there is more than in `limit`.
+    pub fn exceeded(&self, limit: &ResourceList) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|&(resource, q)| *q > limit.quantity(resource))
+            .map(|(resource, _)| resource.as_str())
+            .collect()
+    }
+
+    /// Whether there is no more of any resource than in `limit`, eg
+    /// whether a pod's requests fit in what a node has left.
+    pub fn fits_within(&self, limit: &ResourceList) -> bool {
+        self.exceeded(limit).is_empty()
+    }
+}
+
+impl Deref for ResourceList {
+    type Target = BTreeMap<String, Quantity>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ResourceList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl AddAssign<&ResourceList> for ResourceList {
+    fn add_assign(&mut self, other: &ResourceList) {
+        for (resource, q) in &other.0 {
+            match self.0.get_mut(resource) {
+                Some(mine) => *mine += q,
+                None => {
+                    self.0.insert(resource.clone(), q.clone());
+                }
+            }
+        }
+    }
+}
+
+impl SubAssign<&ResourceList> for ResourceList {
+    fn sub_assign(&mut self, other: &ResourceList) {
+        for (resource, q) in &other.0 {
+            match self.0.get_mut(resource) {
+                Some(mine) => *mine -= q,
+                None => {
+                    self.0.insert(resource.clone(), -q.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Add<&ResourceList> for ResourceList {
+    type Output = ResourceList;
+
+    fn add(mut self, other: &ResourceList) -> ResourceList {
+        self += other;
+        self
+    }
+}
+
+impl Sub<&ResourceList> for ResourceList {
+    type Output = ResourceList;
+
+    fn sub(mut self, other: &ResourceList) -> ResourceList {
+        self -= other;
+        self
+    }
+}
+
+impl<'a> Sum<&'a ResourceList> for ResourceList {
+    fn sum<I: Iterator<Item = &'a ResourceList>>(iter: I) -> ResourceList {
+        iter.fold(ResourceList::new(), |sum, r| sum + r)
+    }
+}
+
+#[test]
+fn test_parse() {
+    let nanos = |s: &str| s.parse::<Quantity>().map(|q| q.nanos);
+    assert_eq!(nanos("1"), Ok(NANO));
+    assert_eq!(nanos("100m"), Ok(NANO / 10));
+    assert_eq!(nanos("1.5"), Ok(NANO * 3 / 2));
+    assert_eq!(nanos(".5"), Ok(NANO / 2));
+    assert_eq!(nanos("2."), Ok(NANO * 2));
+    assert_eq!(nanos("-1k"), Ok(-NANO * 1000));
+    assert_eq!(nanos("+1Ki"), Ok(NANO * 1024));
+    assert_eq!(nanos("1.5Gi"), Ok((NANO * 3) << 29));
+    assert_eq!(nanos("1e3"), Ok(NANO * 1000));
+    assert_eq!(nanos("1E-3"), Ok(NANO / 1000));
+    assert_eq!(nanos("1n"), Ok(1));
+    // Rounded away from zero.
+    assert_eq!(nanos("0.1n"), Ok(1));
+    assert_eq!(nanos("-1e-20"), Ok(-1));
+    assert_eq!(nanos("0.000"), Ok(0));
+    for bad in &[
+        "", ".", "1..2", "1.2.3", "1Z", "1kb", "Ki", "1e", "1 Gi", "1e100",
+    ] {
+        assert!(bad.parse::<Quantity>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_parse_extreme_exponents() {
+    let nanos = |s: &str| s.parse::<Quantity>().map(|q| q.nanos);
+    assert!(nanos("1e2147483647").is_err());
+    assert!(nanos("1e2147483640").is_err());
+    assert_eq!(nanos("1e-2147483648"), Ok(1));
+    assert_eq!(nanos("0.5e-2147483640"), Ok(1));
+}
+
+#[test]
+fn test_arithmetic() {
+    let q = |s: &str| s.parse::<Quantity>().unwrap();
+    assert_eq!(q("1Gi"), q("1024Mi"));
+    assert!(q("999m") < q("1"));
+    assert_eq!((q("1Gi") + q("512Mi")).to_string(), "1536Mi");
+    assert_eq!((q("1Gi") - q("1Gi")).to_string(), "0");
+    assert_eq!((q("1Ki") - q("1")).to_string(), "1023");
+    assert_eq!((q("100m") + q("1.9")).to_string(), "2");
+    assert_eq!((q("1") - q("1500m")).to_string(), "-500m");
+    assert_eq!((q("1e3") + q("1e3")).to_string(), "2e3");
+    assert_eq!((q("1k") + q("1M")).to_string(), "1001k");
+    assert_eq!(Quantity::from_milli(1500).to_string(), "1500m");
+    assert_eq!(q("1500m").value(), 2);
+    assert_eq!(q("0.1m").milli_value(), 1);
+    assert_eq!(q("1Ei").value(), 1 << 60);
+    let quantities = [q("1Mi"), q("1Mi")];
+    assert_eq!(quantities.iter().sum::<Quantity>().to_string(), "2Mi");
+
+    let json = serde_json::to_string(&q("0.5")).unwrap();
+    assert_eq!(json, r#""0.5""#);
+    assert_eq!(serde_json::from_str::<Quantity>("2").unwrap(), q("2"));
+    assert_eq!(serde_json::from_str::<Quantity>("0.25").unwrap(), q("250m"));
+    assert!(serde_json::from_str::<Quantity>(r#""lots""#).is_err());
+}
+
+#[test]
+fn test_resource_list() {
+    let list = |json: &str| serde_json::from_str::<ResourceList>(json).unwrap();
+    let requests = list(r#"{"cpu": "250m", "memory": "64Mi"}"#);
+    let node = list(r#"{"cpu": "2", "memory": "1Gi", "pods": "110"}"#);
+    assert!(requests.fits_within(&node));
+    assert!(!node.fits_within(&requests));
+    assert_eq!(node.exceeded(&requests), vec!["cpu", "memory", "pods"]);
+
+    let left = node.clone() - &requests - &requests;
+    assert_eq!(
+        serde_json::to_value(&left).unwrap(),
+        json!({"cpu": "1500m", "memory": "896Mi", "pods": "110"})
+    );
+    let mut more = requests.clone();
+    more.insert("nvidia.com/gpu".to_string(), Quantity::from(1));
+    assert_eq!(more.exceeded(&left), vec!["nvidia.com/gpu"]);
+    assert_eq!((ResourceList::new() - &more)["cpu"].to_string(), "-250m");
+
+    let mut max = requests.clone();
+    max.max(&list(r#"{"cpu": "100m", "memory": "1Gi"}"#));
+    assert_eq!(max, list(r#"{"cpu": "250m", "memory": "1Gi"}"#));
+    assert_eq!(
+        [requests.clone(), requests].iter().sum::<ResourceList>(),
+        list(r#"{"cpu": "500m", "memory": "128Mi"}"#)
+    );
+}
@@ -0,0 +1,99 @@
+use serde_json::Value;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// A secret value (token, password, key), which `Debug` doesn't show,
+/// so that it can't leak into logs.  It is serialized as the value
+/// itself.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Redacted<T>(pub T);
+
+impl<T> Redacted<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl<T> From<T> for Redacted<T> {
+    fn from(value: T) -> Self {
+        Redacted(value)
+    }
+}
+
+impl<T> Deref for Redacted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Redacted<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: AsRef<U>, U: ?Sized> AsRef<U> for Redacted<T> {
+    fn as_ref(&self) -> &U {
+        self.0.as_ref()
+    }
+}
+
+/// Replace the values in the `data` and `stringData` of `object` with
+/// `"<redacted>"`, if it is a Secret, or of each Secret in it, if it is
+/// a list or a watch event, for logging it.  The keys are kept.
+pub fn redact_secret_data(object: &mut Value) {
+    if let Some(items) = object.get_mut("items").and_then(Value::as_array_mut) {
+        items.iter_mut().for_each(redact_secret_data);
+    }
+    if let Some(object) = object.get_mut("object") {
+        redact_secret_data(object);
+    }
+    if object["kind"] != "Secret" {
+        return;
+    }
+    for field in &["data", "stringData"] {
+        let values = object
+            .get_mut(*field)
+            .and_then(Value::as_object_mut)
+            .into_iter()
+            .flat_map(|data| data.values_mut());
+        for value in values {
+            *value = Value::String("<redacted>".to_string());
+        }
+    }
+}
+
+#[test]
+fn redacted() {
+    let token = Redacted("s3cret".to_string());
+    assert_eq!(format!("{:?}", token), "<redacted>");
+    assert_eq!(token.len(), 6);
+    assert_eq!(serde_json::to_value(&token).unwrap(), "s3cret");
+    let token: Redacted<String> = serde_json::from_str("\"other\"").unwrap();
+    assert_eq!(token.into_inner(), "other");
+
+    let mut list = json!({
+        "kind": "List",
+        "items": [
+            {"kind": "Secret", "data": {"key": "aGk="}, "stringData": {"pw": "hi"}},
+            {"kind": "ConfigMap", "data": {"key": "hi"}},
+        ],
+    });
+    redact_secret_data(&mut list);
+    assert_eq!(list["items"][0]["data"]["key"], "<redacted>");
+    assert_eq!(list["items"][0]["stringData"]["pw"], "<redacted>");
+    assert_eq!(list["items"][1]["data"]["key"], "hi");
+
+    let mut event = json!({"type": "ADDED", "object": {"kind": "Secret", "data": {"k": "v"}}});
+    redact_secret_data(&mut event);
+    assert_eq!(event["object"]["data"]["k"], "<redacted>");
+}
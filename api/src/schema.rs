@@ -0,0 +1,229 @@
+//! Structural OpenAPI v3 schemas for Rust types, as used for
+//! CustomResourceDefinition validation.
+//!
+//! `#[derive(JsonSchema)]` (from `kubernetes_derive`) implements
+//! `JsonSchema` for structs and unit-only enums, following their serde
+//! attributes.
+
+pub use serde_json::Value;
+
+use serde_json::Map;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::intstr::IntOrString;
+
+pub trait JsonSchema {
+    /// The OpenAPI v3 schema for this type.
+    fn json_schema() -> Value;
+
+    /// Whether a field of this type may be omitted.
+    fn optional() -> bool {
+        false
+    }
+}
+
+fn typed(typ: &str) -> Value {
+    let mut m = Map::new();
+    m.insert("type".into(), typ.into());
+    Value::Object(m)
+}
+
+fn formatted(typ: &str, format: &str) -> Value {
+    let mut v = typed(typ);
+    v["format"] = format.into();
+    v
+}
+
+/// Schema for an object with the given (name, schema) properties.
+pub fn object(properties: Vec<(&str, Value)>, required: Vec<&str>) -> Value {
+    let mut v = typed("object");
+    v["properties"] = Value::Object(
+        properties
+            .into_iter()
+            .map(|(k, s)| (k.to_string(), s))
+            .collect(),
+    );
+    if !required.is_empty() {
+        v["required"] = required.into();
+    }
+    v
+}
+
+/// Schema for a string enumeration.
+pub fn string_enum(values: Vec<&str>) -> Value {
+    let mut v = typed("string");
+    v["enum"] = values.into();
+    v
+}
+
+/// Add a description to a schema.
+pub fn describe(mut schema: Value, description: &str) -> Value {
+    if !description.is_empty() {
+        schema["description"] = description.into();
+    }
+    schema
+}
+
+/// Merge the properties of a `#[serde(flatten)]` field into an object
+/// schema.
+pub fn flatten(schema: &mut Value, flattened: Value) {
+    if let Value::Object(mut inner) = flattened {
+        if let Some(Value::Object(props)) = inner.remove("properties") {
+            let outer = schema["properties"].as_object_mut().unwrap();
+            outer.extend(props);
+        }
+        if let Some(Value::Array(req)) = inner.remove("required") {
+            match schema["required"] {
+                Value::Array(ref mut outer) => outer.extend(req),
+                _ => schema["required"] = Value::Array(req),
+            }
+        }
+    }
+}
+
+/// The schema for a custom resource with the given spec and status.
+pub fn custom_resource(spec: Value, status: Option<Value>) -> Value {
+    let mut properties = vec![("spec", spec)];
+    if let Some(status) = status {
+        properties.push(("status", status));
+    }
+    object(properties, vec![])
+}
+
+macro_rules! impl_schema {
+    ($typ:expr, $format:expr; $($t:ty),*) => {
+        $(
+            impl JsonSchema for $t {
+                fn json_schema() -> Value {
+                    formatted($typ, $format)
+                }
+            }
+        )*
+    };
+    ($typ:expr; $($t:ty),*) => {
+        $(
+            impl JsonSchema for $t {
+                fn json_schema() -> Value {
+                    typed($typ)
+                }
+            }
+        )*
+    };
+}
+
+impl_schema!("integer", "int32"; i8, i16, i32, u8, u16);
+impl_schema!("integer", "int64"; i64, u32, u64, isize, usize);
+impl_schema!("number", "float"; f32);
+impl_schema!("number", "double"; f64);
+impl_schema!("boolean"; bool);
+impl_schema!("string"; String, str);
+
+impl<T: JsonSchema + ?Sized> JsonSchema for &T {
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Box<T> {
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+    fn optional() -> bool {
+        T::optional()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn json_schema() -> Value {
+        let mut v = typed("array");
+        v["items"] = T::json_schema();
+        v
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for HashMap<String, T> {
+    fn json_schema() -> Value {
+        let mut v = typed("object");
+        v["additionalProperties"] = T::json_schema();
+        v
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeMap<String, T> {
+    fn json_schema() -> Value {
+        HashMap::<String, T>::json_schema()
+    }
+}
+
+impl JsonSchema for IntOrString {
+    fn json_schema() -> Value {
+        let mut v = Value::Object(Map::new());
+        v["x-kubernetes-int-or-string"] = true.into();
+        v["anyOf"] = vec![typed("integer"), typed("string")].into();
+        v
+    }
+}
+
+impl JsonSchema for Value {
+    fn json_schema() -> Value {
+        let mut v = typed("object");
+        v["x-kubernetes-preserve-unknown-fields"] = true.into();
+        v
+    }
+}
+
+impl JsonSchema for Map<String, Value> {
+    fn json_schema() -> Value {
+        Value::json_schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives() {
+        assert_eq!(
+            Vec::<Option<i32>>::json_schema(),
+            json!({"type": "array", "items": {"type": "integer", "format": "int32"}})
+        );
+        assert_eq!(
+            HashMap::<String, String>::json_schema(),
+            json!({"type": "object", "additionalProperties": {"type": "string"}})
+        );
+        assert!(Option::<String>::optional());
+        assert!(!String::optional());
+    }
+
+    #[test]
+    fn objects() {
+        let s = object(
+            vec![
+                ("name", describe(String::json_schema(), "The name.")),
+                ("size", string_enum(vec!["Small", "Large"])),
+            ],
+            vec!["name"],
+        );
+        assert_eq!(
+            s,
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string", "description": "The name."},
+                    "size": {"type": "string", "enum": ["Small", "Large"]},
+                },
+                "required": ["name"],
+            })
+        );
+    }
+}
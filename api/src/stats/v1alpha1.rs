@@ -0,0 +1,219 @@
+//! Resource usage reported by the kubelet, served at `/stats/summary`
+//! (usually via the apiserver's node proxy).
+//! See k8s.io/kubelet/pkg/apis/stats/v1alpha1
+//!
+//! Accelerator and user-defined metrics are not included.
+
+use crate::Time;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub node: NodeStats,
+    #[serde(default)]
+    pub pods: Vec<PodStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStats {
+    pub node_name: String,
+    /// Stats of system daemons, eg: `kubelet` and `runtime`.
+    #[serde(default)]
+    pub system_containers: Vec<ContainerStats>,
+    pub start_time: Time,
+    pub cpu: Option<CPUStats>,
+    pub memory: Option<MemoryStats>,
+    pub network: Option<NetworkStats>,
+    /// The filesystem holding the kubelet's root directory.
+    pub fs: Option<FsStats>,
+    pub runtime: Option<RuntimeStats>,
+    pub rlimit: Option<RlimitStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RlimitStats {
+    pub time: Time,
+    pub maxpid: Option<i64>,
+    pub curproc: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeStats {
+    /// The filesystem holding container images.
+    pub image_fs: Option<FsStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PodStats {
+    pub pod_ref: PodReference,
+    pub start_time: Time,
+    #[serde(default)]
+    pub containers: Vec<ContainerStats>,
+    pub cpu: Option<CPUStats>,
+    pub memory: Option<MemoryStats>,
+    pub network: Option<NetworkStats>,
+    #[serde(default)]
+    pub volume: Vec<VolumeStats>,
+    /// Container rootfs and logs, plus emptyDir and other local
+    /// volumes.
+    #[serde(rename = "ephemeral-storage")]
+    pub ephemeral_storage: Option<FsStats>,
+    #[serde(rename = "process_stats")]
+    pub process_stats: Option<ProcessStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerStats {
+    pub name: String,
+    pub start_time: Time,
+    pub cpu: Option<CPUStats>,
+    pub memory: Option<MemoryStats>,
+    pub rootfs: Option<FsStats>,
+    pub logs: Option<FsStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PodReference {
+    pub name: String,
+    pub namespace: String,
+    pub uid: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceStats {
+    pub name: String,
+    pub rx_bytes: Option<u64>,
+    pub rx_errors: Option<u64>,
+    pub tx_bytes: Option<u64>,
+    pub tx_errors: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStats {
+    pub time: Time,
+    /// The default interface.
+    #[serde(flatten)]
+    pub default: InterfaceStats,
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CPUStats {
+    pub time: Time,
+    /// Average usage over the sampling window, in billionths of a core.
+    pub usage_nano_cores: Option<u64>,
+    /// Cumulative usage since the container started.
+    pub usage_core_nano_seconds: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    pub time: Time,
+    pub available_bytes: Option<u64>,
+    pub usage_bytes: Option<u64>,
+    /// What the OOM killer and eviction consider in use.
+    pub working_set_bytes: Option<u64>,
+    pub rss_bytes: Option<u64>,
+    pub page_faults: Option<u64>,
+    pub major_page_faults: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeStats {
+    #[serde(flatten)]
+    pub fs: FsStats,
+    pub name: String,
+    pub pvc_ref: Option<PVCReference>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PVCReference {
+    pub name: String,
+    pub namespace: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FsStats {
+    pub time: Time,
+    pub available_bytes: Option<u64>,
+    pub capacity_bytes: Option<u64>,
+    pub used_bytes: Option<u64>,
+    pub inodes_free: Option<u64>,
+    pub inodes: Option<u64>,
+    pub inodes_used: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessStats {
+    #[serde(rename = "process_count")]
+    pub process_count: Option<u64>,
+}
+
+#[test]
+fn deser_summary() {
+    let summary: Summary = ::serde_json::from_str(
+        r#"{
+          "node": {
+            "nodeName": "node-1",
+            "systemContainers": [
+              {"name": "kubelet", "startTime": "2020-01-01T00:00:00Z",
+               "cpu": {"time": "2020-01-02T00:00:00Z", "usageNanoCores": 25000000}}
+            ],
+            "startTime": "2020-01-01T00:00:00Z",
+            "memory": {"time": "2020-01-02T00:00:00Z", "workingSetBytes": 1073741824},
+            "network": {"time": "2020-01-02T00:00:00Z", "name": "eth0", "rxBytes": 100,
+                        "interfaces": [{"name": "eth0", "rxBytes": 100}]},
+            "runtime": {"imageFs": {"time": "2020-01-02T00:00:00Z", "usedBytes": 5}}
+          },
+          "pods": [
+            {
+              "podRef": {"name": "web-0", "namespace": "default", "uid": "abc"},
+              "startTime": "2020-01-01T00:00:00Z",
+              "containers": [{"name": "web", "startTime": "2020-01-01T00:00:00Z"}],
+              "volume": [{"time": "2020-01-02T00:00:00Z", "name": "data", "usedBytes": 42,
+                          "pvcRef": {"name": "data-web-0", "namespace": "default"}}],
+              "ephemeral-storage": {"time": "2020-01-02T00:00:00Z", "usedBytes": 7},
+              "process_stats": {"process_count": 3}
+            }
+          ]
+        }"#,
+    )
+    .unwrap();
+    let node = &summary.node;
+    assert_eq!(node.node_name, "node-1");
+    assert_eq!(
+        node.system_containers[0]
+            .cpu
+            .as_ref()
+            .unwrap()
+            .usage_nano_cores,
+        Some(25_000_000)
+    );
+    let network = node.network.as_ref().unwrap();
+    assert_eq!(network.default.name, "eth0");
+    assert_eq!(network.default.rx_bytes, Some(100));
+    assert_eq!(network.interfaces.len(), 1);
+    assert!(node.fs.is_none());
+
+    let pod = &summary.pods[0];
+    assert_eq!(pod.pod_ref.name, "web-0");
+    assert_eq!(pod.volume[0].fs.used_bytes, Some(42));
+    assert_eq!(pod.volume[0].pvc_ref.as_ref().unwrap().name, "data-web-0");
+    assert_eq!(pod.ephemeral_storage.as_ref().unwrap().used_bytes, Some(7));
+    assert_eq!(pod.process_stats.as_ref().unwrap().process_count, Some(3));
+}
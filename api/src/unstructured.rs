@@ -1,5 +1,5 @@
-use crate::meta::v1::{List, ListMeta, Metadata, ObjectMeta};
-use serde_json::{self, Value};
+use crate::meta::v1::{List, ListMeta, Metadata, MetadataMut, ObjectMeta};
+use serde_json::{self, Map, Value};
 use std::borrow::Cow;
 
 impl Metadata for Value {
@@ -49,3 +49,134 @@ impl List for Value {
         }
     }
 }
+
+/// An object of any kind, with apiVersion/kind/metadata parsed and
+/// all other fields kept as raw JSON.
+///
+/// Unlike `Value`, the metadata is always available without
+/// re-parsing, which makes this the preferred type for generic
+/// tooling that works with kinds only known at runtime.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamicObject {
+    #[serde(default)]
+    pub api_version: String,
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    #[serde(flatten)]
+    pub data: Map<String, Value>,
+}
+
+impl DynamicObject {
+    pub fn new(api_version: &str, kind: &str) -> Self {
+        DynamicObject {
+            api_version: api_version.into(),
+            kind: kind.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Metadata for DynamicObject {
+    fn api_version(&self) -> &str {
+        &self.api_version
+    }
+    fn kind(&self) -> &str {
+        &self.kind
+    }
+    fn metadata(&self) -> Cow<'_, ObjectMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl MetadataMut for DynamicObject {
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+/// A list of `DynamicObject`s, eg: the result of listing a resource
+/// that has no compiled-in type.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamicList {
+    #[serde(default)]
+    pub api_version: String,
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub metadata: ListMeta,
+    #[serde(default)]
+    pub items: Vec<DynamicObject>,
+}
+
+impl List for DynamicList {
+    type Item = DynamicObject;
+
+    fn listmeta(&self) -> Cow<'_, ListMeta> {
+        Cow::Borrowed(&self.metadata)
+    }
+    fn items(&self) -> &[DynamicObject] {
+        &self.items
+    }
+    fn items_mut(&mut self) -> &mut [DynamicObject] {
+        &mut self.items
+    }
+    fn into_items(self) -> Vec<DynamicObject> {
+        self.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicList, DynamicObject};
+    use crate::meta::v1::{List, Metadata};
+    use serde_json;
+
+    #[test]
+    fn dynamic_roundtrip() {
+        let j = json!({
+            "apiVersion": "example.com/v1",
+            "kind": "Widget",
+            "metadata": {
+                "name": "w1",
+                "namespace": "default",
+            },
+            "spec": {
+                "size": 3,
+            },
+        });
+        let o: DynamicObject = serde_json::from_value(j.clone()).unwrap();
+        assert_eq!(o.api_version(), "example.com/v1");
+        assert_eq!(o.kind(), "Widget");
+        assert_eq!(o.metadata().name.as_ref().unwrap(), "w1");
+        assert_eq!(o.data["spec"]["size"], 3);
+
+        let rt = serde_json::to_value(&o).unwrap();
+        assert_eq!(rt["spec"], j["spec"]);
+        assert_eq!(rt["kind"], "Widget");
+    }
+
+    #[test]
+    fn dynamic_list() {
+        let l: DynamicList = serde_json::from_value(json!({
+            "apiVersion": "example.com/v1",
+            "kind": "WidgetList",
+            "metadata": {"resourceVersion": "42"},
+            "items": [
+                {"metadata": {"name": "a"}},
+                {"metadata": {"name": "b"}},
+            ],
+        }))
+        .unwrap();
+        assert_eq!(l.listmeta().resource_version, "42");
+        let names: Vec<_> = l
+            .into_items()
+            .into_iter()
+            .map(|o| o.metadata.name.unwrap())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+}
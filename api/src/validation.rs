@@ -0,0 +1,417 @@
+//! Checking objects against (some of) the apiserver's validation rules
+//! before sending them.
+//!
+//! The errors are reported the way the apiserver reports them, with
+//! the path of the offending field, eg:
+//! `spec.containers[0].image: Required value`.
+
+use crate::meta::v1::{CauseType, ObjectMeta, StatusCause};
+use crate::Integer;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Check an object, and everything in it.
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationError> {
+        let mut errors = Vec::new();
+        self.validate_at("", &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { errors })
+        }
+    }
+
+    /// Add the problems with `self`, found at the field `path`, to
+    /// `errors`.
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>);
+}
+
+/// All the problems found with an object.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub struct ValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldErrorType {
+    Required,
+    Invalid,
+    Duplicate,
+    NotFound,
+    NotSupported,
+}
+
+impl fmt::Display for FieldErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FieldErrorType::Required => "Required value",
+            FieldErrorType::Invalid => "Invalid value",
+            FieldErrorType::Duplicate => "Duplicate value",
+            FieldErrorType::NotFound => "Not found",
+            FieldErrorType::NotSupported => "Unsupported value",
+        })
+    }
+}
+
+/// A problem with one field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The path to the field, eg: `metadata.labels`.
+    pub field: String,
+    pub typ: FieldErrorType,
+    /// What is wrong, if there is more to say than `typ`.
+    pub detail: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, typ: FieldErrorType, detail: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            typ,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl FieldError {
+    /// The error an apiserver reported for a field, or `None` if
+    /// `cause` isn't about a field.
+    pub fn from_cause(cause: &StatusCause) -> Option<Self> {
+        let typ = match cause.reason {
+            Some(CauseType::FieldValueRequired) => FieldErrorType::Required,
+            Some(CauseType::FieldValueInvalid) => FieldErrorType::Invalid,
+            Some(CauseType::FieldValueDuplicate) => FieldErrorType::Duplicate,
+            Some(CauseType::FieldValueNotFound) => FieldErrorType::NotFound,
+            Some(CauseType::FieldValueNotSupported) => FieldErrorType::NotSupported,
+            _ => return None,
+        };
+        if cause.field.is_empty() {
+            return None;
+        }
+        // The message starts with what `typ` says, eg: `Invalid
+        // value: "Web": ...`.
+        let message = cause.message.as_deref().unwrap_or("");
+        let detail = message
+            .strip_prefix(&typ.to_string())
+            .map_or(message, |rest| rest.trim_start_matches(':').trim_start());
+        Some(FieldError::new(&cause.field, typ, detail))
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.typ)?;
+        if !self.detail.is_empty() {
+            write!(f, ": {}", self.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// The path of the field `name` in the field at `path`.
+pub fn child(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+/// The path of the `i`th item in the list at `path`.
+pub fn index(path: &str, i: usize) -> String {
+    format!("{}[{}]", path, i)
+}
+
+/// The path of the `key` entry in the map at `path`.
+pub fn key(path: &str, key: &str) -> String {
+    format!("{}[{}]", path, key)
+}
+
+/// Check `value` with `check`, adding any problem as an `Invalid`
+/// error.
+pub fn check(
+    path: &str,
+    value: &str,
+    check: fn(&str) -> Result<(), String>,
+    errors: &mut Vec<FieldError>,
+) {
+    if let Err(detail) = check(value) {
+        errors.push(FieldError::new(
+            path,
+            FieldErrorType::Invalid,
+            format!("{:?}: {}", value, detail),
+        ));
+    }
+}
+
+/// Add a `Duplicate` error for each of `names`, given with the path
+/// of their field, seen before.
+pub fn check_unique<'a>(
+    names: impl IntoIterator<Item = (String, &'a str)>,
+    errors: &mut Vec<FieldError>,
+) {
+    let mut seen = HashSet::new();
+    for (field, name) in names {
+        if !seen.insert(name) {
+            errors.push(FieldError::new(
+                &field,
+                FieldErrorType::Duplicate,
+                format!("{:?}", name),
+            ));
+        }
+    }
+}
+
+fn is_alphanumeric(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit()
+}
+
+/// A DNS (RFC 1123) label, as namespace and container names must be:
+/// up to 63 lower case letters, digits and '-', starting and ending
+/// with a letter or digit.
+pub fn is_dns1123_label(value: &str) -> Result<(), String> {
+    if value.len() > 63 {
+        return Err("must be no more than 63 characters".to_string());
+    }
+    let ok = value.starts_with(is_alphanumeric)
+        && value.ends_with(is_alphanumeric)
+        && value.chars().all(|c| is_alphanumeric(c) || c == '-');
+    if !ok {
+        return Err(
+            "must consist of lower case alphanumeric characters or '-', \
+                    and must start and end with an alphanumeric character"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// A DNS (RFC 1123) subdomain, as most object names must be: up to 253
+/// characters of DNS labels separated by '.'.
+pub fn is_dns1123_subdomain(value: &str) -> Result<(), String> {
+    if value.len() > 253 {
+        return Err("must be no more than 253 characters".to_string());
+    }
+    if !value
+        .split('.')
+        .all(|label| is_dns1123_label(label).is_ok())
+    {
+        return Err(
+            "must consist of lower case alphanumeric characters, '-' or '.', \
+                    and must start and end with an alphanumeric character"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Up to 63 letters, digits, '-', '_' and '.', starting and ending with
+/// a letter or digit.
+fn is_name_part(value: &str) -> bool {
+    let edge = |c: char| c.is_ascii_alphanumeric();
+    value.len() <= 63
+        && value.starts_with(edge)
+        && value.ends_with(edge)
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// A label or annotation key: a name, optionally prefixed with a DNS
+/// subdomain and '/', eg: `app.kubernetes.io/name`.
+pub fn is_qualified_name(value: &str) -> Result<(), String> {
+    let (prefix, name) = match value.find('/') {
+        Some(i) => (Some(&value[..i]), &value[i + 1..]),
+        None => (None, value),
+    };
+    if let Some(prefix) = prefix {
+        if prefix.is_empty() {
+            return Err("prefix part must be non-empty".to_string());
+        }
+        is_dns1123_subdomain(prefix).map_err(|e| format!("prefix part {}", e))?;
+    }
+    if !is_name_part(name) {
+        return Err(
+            "name part must be no more than 63 alphanumeric characters, \
+                    '-', '_' or '.', and must start and end with an alphanumeric \
+                    character"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// A label value: empty, or as the name part of a qualified name.
+pub fn is_label_value(value: &str) -> Result<(), String> {
+    if !value.is_empty() && !is_name_part(value) {
+        return Err(
+            "must be no more than 63 alphanumeric characters, '-', '_' or \
+                    '.', and must start and end with an alphanumeric character"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// An IANA service name, as named ports must be: up to 15 lower case
+/// letters, digits and '-', with at least one letter, and no '-' at
+/// either end or next to another.
+pub fn is_port_name(value: &str) -> Result<(), String> {
+    if value.len() > 15 {
+        return Err("must be no more than 15 characters".to_string());
+    }
+    let ok = value.starts_with(is_alphanumeric)
+        && value.ends_with(is_alphanumeric)
+        && !value.contains("--")
+        && value.chars().all(|c| is_alphanumeric(c) || c == '-')
+        && value.chars().any(|c| c.is_ascii_lowercase());
+    if !ok {
+        return Err("must contain only lower case alphanumeric characters and \
+                    single '-', with at least one letter, and must start and end \
+                    with an alphanumeric character"
+            .to_string());
+    }
+    Ok(())
+}
+
+/// A key in a ConfigMap's or Secret's data: up to 253 letters, digits,
+/// '-', '_' and '.', and not `.` or `..`.
+pub fn is_config_map_key(value: &str) -> Result<(), String> {
+    if value.len() > 253 {
+        return Err("must be no more than 253 characters".to_string());
+    }
+    if value.is_empty()
+        || !value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err("must consist of alphanumeric characters, '-', '_' or '.'".to_string());
+    }
+    if value == "." || value == ".." {
+        return Err("must not be '.' or '..'".to_string());
+    }
+    Ok(())
+}
+
+/// Add an error if `port` isn't a valid TCP or UDP port.
+pub fn check_port(path: &str, port: Integer, errors: &mut Vec<FieldError>) {
+    if !(1..=65535).contains(&port) {
+        errors.push(FieldError::new(
+            path,
+            FieldErrorType::Invalid,
+            format!("{}: must be between 1 and 65535, inclusive", port),
+        ));
+    }
+}
+
+/// Add an error if `value` is negative.
+pub fn check_non_negative(path: &str, value: i64, errors: &mut Vec<FieldError>) {
+    if value < 0 {
+        errors.push(FieldError::new(
+            path,
+            FieldErrorType::Invalid,
+            format!("{}: must be greater than or equal to 0", value),
+        ));
+    }
+}
+
+/// The name (or generateName), labels and annotations, for an object
+/// whose name must be a DNS subdomain.
+impl Validate for ObjectMeta {
+    fn validate_at(&self, path: &str, errors: &mut Vec<FieldError>) {
+        match (&self.name, &self.generate_name) {
+            (Some(name), _) => check(&child(path, "name"), name, is_dns1123_subdomain, errors),
+            (None, Some(_)) => (),
+            (None, None) => errors.push(FieldError::new(
+                &child(path, "name"),
+                FieldErrorType::Required,
+                "name or generateName is required",
+            )),
+        }
+        if let Some(ref namespace) = self.namespace {
+            check(
+                &child(path, "namespace"),
+                namespace,
+                is_dns1123_label,
+                errors,
+            );
+        }
+        check_labels(path, self, errors);
+    }
+}
+
+/// Check the syntax of the labels and annotations in `metadata`, at
+/// `path`.
+pub fn check_labels(path: &str, metadata: &ObjectMeta, errors: &mut Vec<FieldError>) {
+    let labels = child(path, "labels");
+    for (k, v) in &metadata.labels {
+        check(&labels, k, is_qualified_name, errors);
+        check(&key(&labels, k), v, is_label_value, errors);
+    }
+    let annotations = child(path, "annotations");
+    for k in metadata.annotations.keys() {
+        check(&annotations, k, is_qualified_name, errors);
+    }
+}
+
+#[test]
+fn names() {
+    assert!(is_dns1123_label("web-1").is_ok());
+    assert!(is_dns1123_label("Web").is_err());
+    assert!(is_dns1123_label("-web").is_err());
+    assert!(is_dns1123_label(&"a".repeat(64)).is_err());
+    assert!(is_dns1123_subdomain("web.example.com").is_ok());
+    assert!(is_dns1123_subdomain("web..com").is_err());
+    assert!(is_qualified_name("app.kubernetes.io/name").is_ok());
+    assert!(is_qualified_name("App_Name").is_ok());
+    assert!(is_qualified_name("/name").is_err());
+    assert!(is_qualified_name("Example.com/name").is_err());
+    assert!(is_label_value("").is_ok());
+    assert!(is_label_value("v1.2_b").is_ok());
+    assert!(is_label_value("a b").is_err());
+    assert!(is_port_name("http").is_ok());
+    assert!(is_port_name("8080").is_err());
+    assert!(is_port_name("http--alt").is_err());
+    assert!(is_config_map_key(".dockerconfigjson").is_ok());
+    assert!(is_config_map_key("app.conf").is_ok());
+    assert!(is_config_map_key("..").is_err());
+    assert!(is_config_map_key("a/b").is_err());
+}
+
+#[test]
+fn object_meta() {
+    let meta = ObjectMeta {
+        name: Some("web_1".to_string()),
+        labels: vec![("app".to_string(), "web server".to_string())]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    let err = meta.validate().unwrap_err();
+    let fields: Vec<_> = err.errors.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(fields, vec!["name", "labels[app]"]);
+    assert!(err
+        .to_string()
+        .starts_with("name: Invalid value: \"web_1\": "));
+
+    let err = ObjectMeta::default().validate().unwrap_err();
+    assert_eq!(err.errors[0].typ, FieldErrorType::Required);
+    let meta = ObjectMeta {
+        generate_name: Some("web-".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(meta.validate(), Ok(()));
+}
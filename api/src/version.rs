@@ -0,0 +1,59 @@
+//! The apiserver's build information, served at `/version`.
+//! See k8s.io/apimachinery/pkg/version
+
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Info {
+    pub major: String,
+    pub minor: String,
+    pub git_version: String,
+    pub git_commit: String,
+    pub git_tree_state: String,
+    pub build_date: String,
+    pub go_version: String,
+    pub compiler: String,
+    pub platform: String,
+}
+
+impl Info {
+    /// Numeric (major, minor) version, for comparisons.
+    ///
+    /// Some providers append a suffix to the minor version (eg: "14+"
+    /// on GKE); this is ignored.
+    pub fn major_minor(&self) -> Option<(u32, u32)> {
+        fn num(s: &str) -> Option<u32> {
+            let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        }
+        Some((num(&self.major)?, num(&self.minor)?))
+    }
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.git_version)
+    }
+}
+
+#[test]
+fn deser_info() {
+    let info: Info = ::serde_json::from_str(
+        r#"{
+          "major": "1",
+          "minor": "14+",
+          "gitVersion": "v1.14.10-gke.17",
+          "gitCommit": "bdceba0734835c6cb1acbd1c447caf17d8613b44",
+          "gitTreeState": "clean",
+          "buildDate": "2020-01-17T23:10:13Z",
+          "goVersion": "go1.12.12b4",
+          "compiler": "gc",
+          "platform": "linux/amd64"
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(info.major_minor(), Some((1, 14)));
+    assert!(info.major_minor() >= Some((1, 11)));
+    assert_eq!(info.to_string(), "v1.14.10-gke.17");
+}
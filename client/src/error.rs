@@ -1,6 +1,12 @@
+use failure;
+use std::cmp;
+use std::fmt;
 use std::str;
+use std::time::Duration;
 
-use hyper;
+use api::meta::v1::{Status, StatusCause, StatusDetails, StatusReason};
+use api::validation::FieldError;
+use http;
 use serde_json;
 
 #[derive(Debug, Fail)]
@@ -22,11 +28,19 @@ pub enum ClientError {
         bytes: Vec<u8>,
     },
 
-    #[fail(display = "Unexpected HTTP response status: {}", status)]
-    HttpStatusError { status: hyper::StatusCode },
-
     #[fail(display = "Attribute {} required but not provided", attr)]
     RequiredAttributeError { attr: &'static str },
+
+    /// The response wasn't JSON, eg: it was an HTML error page from a
+    /// proxy.  `snippet` is the start of the body.
+    #[fail(
+        display = "expected a JSON response, but got {}: {}",
+        content_type, snippet
+    )]
+    UnexpectedContentType {
+        content_type: String,
+        snippet: String,
+    },
 }
 
 impl ClientError {
@@ -41,6 +55,245 @@ impl ClientError {
             bytes: bytes,
         }
     }
+
+    pub fn new_unexpected_content_type(content_type: &str, body: &[u8]) -> ClientError {
+        let body = String::from_utf8_lossy(&body[..body.len().min(200)]);
+        ClientError::UnexpectedContentType {
+            content_type: content_type.to_string(),
+            snippet: body.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+}
+
+/// An error response from the apiserver.
+///
+/// The reason is taken from the returned `Status` if there was one,
+/// otherwise inferred from the HTTP status code.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub struct ApiError {
+    pub code: u16,
+    pub reason: StatusReason,
+    pub message: String,
+    pub details: Option<StatusDetails>,
+    /// The apiserver's `Audit-Id` for the request, for finding it in
+    /// the audit log.
+    pub audit_id: Option<String>,
+    /// The request that failed, eg: `GET /api/v1/namespaces/default/pods/web`.
+    pub request: Option<String>,
+}
+
+impl ApiError {
+    /// An error with `reason`, and the HTTP status code the apiserver
+    /// uses for it.
+    pub fn new<S: Into<String>>(reason: StatusReason, message: S) -> Self {
+        ApiError {
+            code: code_for_reason(&reason),
+            reason,
+            message: message.into(),
+            details: None,
+            audit_id: None,
+            request: None,
+        }
+    }
+
+    pub fn from_status(status: Status) -> Self {
+        let code = status.code as u16;
+        ApiError {
+            code,
+            reason: status.reason.unwrap_or_else(|| reason_for_code(code)),
+            message: status.message,
+            details: status.details,
+            audit_id: None,
+            request: None,
+        }
+    }
+
+    /// For responses without a parseable `Status` body.
+    pub fn from_http(status: http::StatusCode, body: &[u8]) -> Self {
+        let code = status.as_u16();
+        let message = match str::from_utf8(body) {
+            Ok(s) if !s.trim().is_empty() => s.trim().to_string(),
+            _ => status.canonical_reason().unwrap_or("").to_string(),
+        };
+        ApiError {
+            code,
+            reason: reason_for_code(code),
+            message,
+            details: None,
+            audit_id: None,
+            request: None,
+        }
+    }
+
+    pub fn with_audit_id(mut self, audit_id: Option<String>) -> Self {
+        self.audit_id = audit_id;
+        self
+    }
+
+    /// Record which request failed, to name it in the message.
+    pub fn with_request<S: Into<String>>(mut self, request: S) -> Self {
+        self.request = Some(request.into());
+        self
+    }
+
+    /// Record how long the server asked for retries to wait, eg: from a
+    /// `Retry-After` header.  The Status `retryAfterSeconds` detail, if
+    /// there was one, is kept.
+    pub fn with_retry_after(mut self, after: Option<Duration>) -> Self {
+        if let Some(after) = after {
+            let details = self.details.get_or_insert_with(Default::default);
+            if details.retry_after_seconds.is_none() {
+                details.retry_after_seconds = Some(after.as_secs().min(i32::MAX as u64) as i32);
+            }
+        }
+        self
+    }
+
+    /// Find the `ApiError` in a `failure::Error`, if it is one.
+    pub fn from_error(e: &failure::Error) -> Option<&ApiError> {
+        e.downcast_ref()
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.reason == StatusReason::NotFound
+    }
+
+    pub fn is_already_exists(&self) -> bool {
+        self.reason == StatusReason::AlreadyExists
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        self.reason == StatusReason::Conflict
+    }
+
+    pub fn is_forbidden(&self) -> bool {
+        self.reason == StatusReason::Forbidden
+    }
+
+    pub fn is_invalid(&self) -> bool {
+        self.reason == StatusReason::Invalid
+    }
+
+    pub fn is_too_many_requests(&self) -> bool {
+        self.reason == StatusReason::TooManyRequests
+    }
+
+    /// The resourceVersion requested is no longer available.
+    pub fn is_expired(&self) -> bool {
+        self.reason == StatusReason::Expired || self.reason == StatusReason::Gone
+    }
+
+    /// The name of the object the error is about, if the apiserver said.
+    pub fn name(&self) -> Option<&str> {
+        self.details.as_ref().and_then(|d| d.name.as_deref())
+    }
+
+    /// The group of the object's kind, if the apiserver said.
+    pub fn group(&self) -> Option<&str> {
+        self.details.as_ref().and_then(|d| d.group.as_deref())
+    }
+
+    /// The kind (or, for some errors, the resource) of the object, if
+    /// the apiserver said.
+    pub fn kind(&self) -> Option<&str> {
+        self.details.as_ref().and_then(|d| d.kind.as_deref())
+    }
+
+    /// What went wrong in more detail, eg: one cause for each invalid
+    /// field of an object that failed validation.
+    pub fn causes(&self) -> &[StatusCause] {
+        self.details.as_ref().map_or(&[], |d| &d.causes)
+    }
+
+    /// The causes that are about fields, as client-side validation
+    /// reports them.
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        self.causes()
+            .iter()
+            .filter_map(FieldError::from_cause)
+            .collect()
+    }
+
+    /// How long the server suggested waiting before retrying, if at all.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.details
+            .as_ref()
+            .and_then(|d| d.retry_after_seconds)
+            // Negative values are nonsense, but come from the server.
+            .map(|s| Duration::from_secs(cmp::max(s, 0) as u64))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref request) = self.request {
+            write!(f, "{}: ", request)?;
+        }
+        write!(f, "{:?} ({})", self.reason, self.code)?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        for cause in self.causes() {
+            write!(f, "{}", cause)?;
+        }
+        if let Some(after) = self.retry_after() {
+            write!(f, " (retry after {}s)", after.as_secs())?;
+        }
+        if let Some(ref id) = self.audit_id {
+            write!(f, " (audit ID {})", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        ApiError::from_status(status)
+    }
+}
+
+/// As k8s.io/apimachinery/pkg/api/errors.reasonForError
+fn reason_for_code(code: u16) -> StatusReason {
+    match code {
+        400 => StatusReason::BadRequest,
+        401 => StatusReason::Unauthorized,
+        403 => StatusReason::Forbidden,
+        404 => StatusReason::NotFound,
+        405 => StatusReason::MethodNotAllowed,
+        406 => StatusReason::NotAcceptable,
+        409 => StatusReason::Conflict,
+        410 => StatusReason::Gone,
+        413 => StatusReason::RequestEntityTooLarge,
+        415 => StatusReason::UnsupportedMediaType,
+        422 => StatusReason::Invalid,
+        429 => StatusReason::TooManyRequests,
+        500 => StatusReason::InternalError,
+        503 => StatusReason::ServiceUnavailable,
+        504 => StatusReason::Timeout,
+        _ => StatusReason::Unknown,
+    }
+}
+
+/// The inverse of `reason_for_code`, as the constructors in
+/// k8s.io/apimachinery/pkg/api/errors.
+fn code_for_reason(reason: &StatusReason) -> u16 {
+    match *reason {
+        StatusReason::BadRequest => 400,
+        StatusReason::Unauthorized => 401,
+        StatusReason::Forbidden => 403,
+        StatusReason::NotFound => 404,
+        StatusReason::MethodNotAllowed => 405,
+        StatusReason::NotAcceptable => 406,
+        StatusReason::AlreadyExists | StatusReason::Conflict => 409,
+        StatusReason::Gone | StatusReason::Expired => 410,
+        StatusReason::RequestEntityTooLarge => 413,
+        StatusReason::UnsupportedMediaType => 415,
+        StatusReason::Invalid => 422,
+        StatusReason::TooManyRequests => 429,
+        StatusReason::ServiceUnavailable => 503,
+        StatusReason::Timeout => 504,
+        StatusReason::InternalError | StatusReason::ServerTimeout | StatusReason::Unknown => 500,
+    }
 }
 
 /// Pull out the 1K preceeding text from the failed document to aid diagnosis by users.
@@ -75,6 +328,11 @@ fn context(e: &serde_json::Error, body_ref: &[u8]) -> String {
 
 #[cfg(test)]
 mod tests {
+    use super::ApiError;
+    use api::meta::v1::{Status, StatusReason};
+    use http::StatusCode;
+    use std::time::Duration;
+
     #[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
     #[serde(rename_all = "camelCase")]
     struct SampleObject {
@@ -94,4 +352,113 @@ mod tests {
             String::from("unable to parse error Status: {\"doc\": 1} missing field `requiredField` at line 1 column 10"),
             format!("{}", err));
     }
+
+    #[test]
+    fn test_api_error() {
+        let status: Status = serde_json::from_value(json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "metadata": {},
+            "status": "Failure",
+            "message": "pods \"web-0\" not found",
+            "reason": "NotFound",
+            "details": {"name": "web-0", "kind": "pods"},
+            "code": 404
+        }))
+        .unwrap();
+        let err = ApiError::from(status);
+        assert!(err.is_not_found());
+        assert_eq!(err.code, 404);
+        assert_eq!(err.to_string(), "NotFound (404): pods \"web-0\" not found");
+
+        let err = ApiError::from_http(StatusCode::TOO_MANY_REQUESTS, b"");
+        assert!(err.is_too_many_requests());
+        assert_eq!(err.message, "Too Many Requests");
+        assert_eq!(err.retry_after(), None);
+        let err = err.with_retry_after(Some(Duration::from_secs(2)));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(2)));
+        assert_eq!(
+            err.to_string(),
+            "TooManyRequests (429): Too Many Requests (retry after 2s)"
+        );
+        // The Status detail wins.
+        let err = err.with_retry_after(Some(Duration::from_secs(9)));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(2)));
+        let mut err = err;
+        err.details.as_mut().unwrap().retry_after_seconds = Some(-1);
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(0)));
+
+        let err = ApiError::from_http(StatusCode::IM_A_TEAPOT, b"short and stout");
+        assert_eq!(err.reason, StatusReason::Unknown);
+        assert_eq!(err.message, "short and stout");
+
+        let e: ::failure::Error = err.into();
+        assert_eq!(ApiError::from_error(&e).unwrap().code, 418);
+
+        let err = ApiError::from_http(StatusCode::SERVICE_UNAVAILABLE, b"")
+            .with_audit_id(Some("4c3a8f5e".into()));
+        assert_eq!(
+            err.to_string(),
+            "ServiceUnavailable (503): Service Unavailable (audit ID 4c3a8f5e)"
+        );
+    }
+
+    #[test]
+    fn test_api_error_details() {
+        use api::validation::FieldErrorType;
+
+        let status: Status = serde_json::from_value(json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "metadata": {},
+            "status": "Failure",
+            "message": "Deployment.apps \"Web\" is invalid: [metadata.name: Invalid value: \"Web\": a lowercase RFC 1123 subdomain must consist of lower case alphanumeric characters, spec.template.spec.containers[0].image: Required value]",
+            "reason": "Invalid",
+            "details": {
+                "name": "Web",
+                "group": "apps",
+                "kind": "Deployment",
+                "causes": [
+                    {
+                        "reason": "FieldValueInvalid",
+                        "message": "Invalid value: \"Web\": a lowercase RFC 1123 subdomain must consist of lower case alphanumeric characters",
+                        "field": "metadata.name"
+                    },
+                    {
+                        "reason": "FieldValueRequired",
+                        "message": "Required value",
+                        "field": "spec.template.spec.containers[0].image"
+                    },
+                    {"message": "something else"}
+                ]
+            },
+            "code": 422
+        }))
+        .unwrap();
+        let err = ApiError::from(status);
+        assert!(err.is_invalid());
+        assert_eq!(err.name(), Some("Web"));
+        assert_eq!(err.group(), Some("apps"));
+        assert_eq!(err.kind(), Some("Deployment"));
+        assert_eq!(err.causes().len(), 3);
+        assert!(err
+            .to_string()
+            .ends_with(", caused by spec.template.spec.containers[0].image: Required value, caused by something else"));
+
+        let fields = err.field_errors();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].field, "metadata.name");
+        assert_eq!(fields[0].typ, FieldErrorType::Invalid);
+        assert_eq!(
+            fields[0].detail,
+            "\"Web\": a lowercase RFC 1123 subdomain must consist of lower case alphanumeric characters"
+        );
+        assert_eq!(
+            fields[1].to_string(),
+            "spec.template.spec.containers[0].image: Required value"
+        );
+        assert!(ApiError::from_http(StatusCode::NOT_FOUND, b"")
+            .field_errors()
+            .is_empty());
+    }
 }
@@ -1,10 +1,12 @@
 #[macro_use]
 extern crate failure;
+extern crate kubernetes_api as api;
 #[macro_use]
 extern crate log;
+#[cfg_attr(test, macro_use)]
 extern crate serde_json;
 
-extern crate hyper;
+extern crate http;
 #[cfg(test)]
 extern crate serde;
 #[cfg(test)]
@@ -12,3 +14,4 @@ extern crate serde;
 extern crate serde_derive;
 
 pub mod error;
+pub mod selector;
@@ -0,0 +1,811 @@
+//! Builders for label (and field) selectors, as used in
+//! `ListOptions`, and parsers for selector strings, for matching
+//! objects locally (eg: in a cache) just as the apiserver would.
+
+use api::meta::v1::{self, LabelSelectorOperator, ObjectMeta};
+use api::validation::{is_label_value, is_qualified_name};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::FromStr;
+
+/// A selector string that can't be parsed.
+#[derive(Debug, Clone, PartialEq, Fail)]
+#[fail(display = "Invalid selector {:?}: {}", selector, reason)]
+pub struct ParseError {
+    pub selector: String,
+    pub reason: String,
+}
+
+/// Labels to match a `LabelSelector` against.
+pub trait Labels {
+    fn label(&self, key: &str) -> Option<&str>;
+}
+
+impl Labels for HashMap<String, String> {
+    fn label(&self, key: &str) -> Option<&str> {
+        self.get(key).map(String::as_str)
+    }
+}
+
+impl Labels for BTreeMap<String, String> {
+    fn label(&self, key: &str) -> Option<&str> {
+        self.get(key).map(String::as_str)
+    }
+}
+
+impl Labels for ObjectMeta {
+    fn label(&self, key: &str) -> Option<&str> {
+        self.labels.label(key)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Requirement {
+    Eq(String, String),
+    NotEq(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    DoesNotExist(String),
+    Gt(String, i64),
+    Lt(String, i64),
+}
+
+impl Requirement {
+    fn matches<L: Labels + ?Sized>(&self, labels: &L) -> bool {
+        let number = |k: &str| labels.label(k).and_then(|v| v.parse::<i64>().ok());
+        match *self {
+            Requirement::Eq(ref k, ref v) => labels.label(k) == Some(v),
+            Requirement::NotEq(ref k, ref v) => labels.label(k) != Some(v),
+            Requirement::In(ref k, ref vs) => {
+                labels.label(k).is_some_and(|v| vs.iter().any(|s| s == v))
+            }
+            Requirement::NotIn(ref k, ref vs) => {
+                labels.label(k).is_none_or(|v| !vs.iter().any(|s| s == v))
+            }
+            Requirement::Exists(ref k) => labels.label(k).is_some(),
+            Requirement::DoesNotExist(ref k) => labels.label(k).is_none(),
+            Requirement::Gt(ref k, n) => number(k).is_some_and(|v| v > n),
+            Requirement::Lt(ref k, n) => number(k).is_some_and(|v| v < n),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Requirement::Eq(ref k, ref v) => write!(f, "{}={}", k, v),
+            Requirement::NotEq(ref k, ref v) => write!(f, "{}!={}", k, v),
+            Requirement::In(ref k, ref vs) => write!(f, "{} in ({})", k, vs.join(",")),
+            Requirement::NotIn(ref k, ref vs) => write!(f, "{} notin ({})", k, vs.join(",")),
+            Requirement::Exists(ref k) => write!(f, "{}", k),
+            Requirement::DoesNotExist(ref k) => write!(f, "!{}", k),
+            Requirement::Gt(ref k, n) => write!(f, "{}>{}", k, n),
+            Requirement::Lt(ref k, n) => write!(f, "{}<{}", k, n),
+        }
+    }
+}
+
+/// A label selector, built up from requirements that must all match.
+///
+/// ```
+/// # use kubernetes_client::selector::LabelSelector;
+/// let sel = LabelSelector::new()
+///     .eq("app", "web")
+///     .in_("tier", &["frontend", "canary"]);
+/// assert_eq!(sel.to_string(), "app=web,tier in (frontend,canary)");
+/// ```
+///
+/// Use with `ListOptions { label_selector: sel.into(), .. }`.
+///
+/// Selector strings parse as the apiserver parses them:
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use kubernetes_client::selector::LabelSelector;
+/// let sel: LabelSelector = "app=web, tier notin (db), !legacy".parse().unwrap();
+/// let mut labels = HashMap::new();
+/// labels.insert("app".to_string(), "web".to_string());
+/// assert!(sel.matches(&labels));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+impl LabelSelector {
+    /// An empty selector, which matches everything.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn with(mut self, r: Requirement) -> Self {
+        self.requirements.push(r);
+        self
+    }
+
+    pub fn eq<K: Into<String>, V: Into<String>>(self, key: K, value: V) -> Self {
+        self.with(Requirement::Eq(key.into(), value.into()))
+    }
+
+    pub fn ne<K: Into<String>, V: Into<String>>(self, key: K, value: V) -> Self {
+        self.with(Requirement::NotEq(key.into(), value.into()))
+    }
+
+    pub fn in_<K: Into<String>, V: AsRef<str>>(self, key: K, values: &[V]) -> Self {
+        let values = values.iter().map(|v| v.as_ref().to_string()).collect();
+        self.with(Requirement::In(key.into(), values))
+    }
+
+    pub fn not_in<K: Into<String>, V: AsRef<str>>(self, key: K, values: &[V]) -> Self {
+        let values = values.iter().map(|v| v.as_ref().to_string()).collect();
+        self.with(Requirement::NotIn(key.into(), values))
+    }
+
+    pub fn exists<K: Into<String>>(self, key: K) -> Self {
+        self.with(Requirement::Exists(key.into()))
+    }
+
+    pub fn does_not_exist<K: Into<String>>(self, key: K) -> Self {
+        self.with(Requirement::DoesNotExist(key.into()))
+    }
+
+    /// The label is an integer greater than `value`.
+    pub fn gt<K: Into<String>>(self, key: K, value: i64) -> Self {
+        self.with(Requirement::Gt(key.into(), value))
+    }
+
+    /// The label is an integer less than `value`.
+    pub fn lt<K: Into<String>>(self, key: K, value: i64) -> Self {
+        self.with(Requirement::Lt(key.into(), value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    /// Whether a set of labels (or an object's metadata) satisfies this
+    /// selector.
+    pub fn matches<L: Labels + ?Sized>(&self, labels: &L) -> bool {
+        self.requirements.iter().all(|r| r.matches(labels))
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = ParseError;
+
+    fn from_str(selector: &str) -> Result<Self, ParseError> {
+        let error = |reason: String| ParseError {
+            selector: selector.to_string(),
+            reason,
+        };
+        let mut tokens = tokenize(selector).into_iter().peekable();
+        let mut parsed = LabelSelector::new();
+        if tokens.peek().is_none() {
+            return Ok(parsed);
+        }
+        loop {
+            parsed = parsed.with(requirement(&mut tokens).map_err(error)?);
+            match tokens.next() {
+                None => return Ok(parsed),
+                Some(Token::Comma) => (),
+                Some(t) => return Err(error(format!("expected ',' but got {}", t))),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Not,
+    Eq,
+    DoubleEq,
+    NotEq,
+    In,
+    NotIn,
+    Gt,
+    Lt,
+    Open,
+    Close,
+    Comma,
+}
+
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Token::Ident(s) => return write!(f, "{:?}", s),
+            Token::Not => "'!'",
+            Token::Eq => "'='",
+            Token::DoubleEq => "'=='",
+            Token::NotEq => "'!='",
+            Token::In => "'in'",
+            Token::NotIn => "'notin'",
+            Token::Gt => "'>'",
+            Token::Lt => "'<'",
+            Token::Open => "'('",
+            Token::Close => "')'",
+            Token::Comma => "','",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Split a label selector into tokens, as the apiserver's lexer does.
+fn tokenize(selector: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = selector.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            ',' => (Token::Comma, 1),
+            '>' => (Token::Gt, 1),
+            '<' => (Token::Lt, 1),
+            '=' if rest.starts_with("==") => (Token::DoubleEq, 2),
+            '=' => (Token::Eq, 1),
+            '!' if rest.starts_with("!=") => (Token::NotEq, 2),
+            '!' => (Token::Not, 1),
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || "!=(),<>".contains(c))
+                    .unwrap_or(rest.len());
+                let token = match &rest[..len] {
+                    "in" => Token::In,
+                    "notin" => Token::NotIn,
+                    word => Token::Ident(word),
+                };
+                (token, len)
+            }
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    tokens
+}
+
+fn requirement<'a, I>(tokens: &mut Peekable<I>) -> Result<Requirement, String>
+where
+    I: Iterator<Item = Token<'a>>,
+{
+    if tokens.peek() == Some(&Token::Not) {
+        tokens.next();
+        return Ok(Requirement::DoesNotExist(key(tokens.next())?));
+    }
+    let key = key(tokens.next())?;
+    let op = match tokens.peek() {
+        None | Some(&Token::Comma) => return Ok(Requirement::Exists(key)),
+        Some(&op) => op,
+    };
+    tokens.next();
+    match op {
+        Token::Eq | Token::DoubleEq => Ok(Requirement::Eq(key, value(tokens)?)),
+        Token::NotEq => Ok(Requirement::NotEq(key, value(tokens)?)),
+        Token::In => Ok(Requirement::In(key, set(tokens)?)),
+        Token::NotIn => Ok(Requirement::NotIn(key, set(tokens)?)),
+        Token::Gt | Token::Lt => {
+            let n = match tokens.next() {
+                Some(Token::Ident(value)) => value
+                    .parse::<i64>()
+                    .map_err(|_| format!("{} needs an integer, not {:?}", op, value))?,
+                Some(t) => return Err(format!("{} needs an integer, but got {}", op, t)),
+                None => return Err(format!("{} needs an integer", op)),
+            };
+            Ok(if op == Token::Gt {
+                Requirement::Gt(key, n)
+            } else {
+                Requirement::Lt(key, n)
+            })
+        }
+        t => Err(format!(
+            "expected an operator after {:?}, but got {}",
+            key, t
+        )),
+    }
+}
+
+fn key(token: Option<Token>) -> Result<String, String> {
+    match token {
+        Some(Token::Ident(key)) => {
+            is_qualified_name(key).map_err(|e| format!("invalid label key {:?}: {}", key, e))?;
+            Ok(key.to_string())
+        }
+        Some(t) => Err(format!("expected a label key, but got {}", t)),
+        None => Err("expected a label key".to_string()),
+    }
+}
+
+/// A value, which is empty if there is none before the next ',' (or
+/// the end).
+fn value<'a, I>(tokens: &mut Peekable<I>) -> Result<String, String>
+where
+    I: Iterator<Item = Token<'a>>,
+{
+    match tokens.peek() {
+        None | Some(&Token::Comma) => Ok(String::new()),
+        Some(&Token::Ident(value)) => {
+            tokens.next();
+            is_label_value(value).map_err(|e| format!("invalid label value {:?}: {}", value, e))?;
+            Ok(value.to_string())
+        }
+        Some(t) => Err(format!("expected a label value, but got {}", t)),
+    }
+}
+
+/// A parenthesised, non-empty set of values, any of which may be empty.
+fn set<'a, I>(tokens: &mut Peekable<I>) -> Result<Vec<String>, String>
+where
+    I: Iterator<Item = Token<'a>>,
+{
+    match tokens.next() {
+        Some(Token::Open) => (),
+        Some(t) => return Err(format!("expected '(', but got {}", t)),
+        None => return Err("expected '('".to_string()),
+    }
+    if tokens.peek() == Some(&Token::Close) {
+        return Err("the set of values can't be empty".to_string());
+    }
+    let mut values = Vec::new();
+    loop {
+        values.push(match tokens.peek() {
+            Some(&Token::Comma) | Some(&Token::Close) => String::new(),
+            _ => value(tokens)?,
+        });
+        match tokens.next() {
+            Some(Token::Comma) => (),
+            Some(Token::Close) => return Ok(values),
+            Some(t) => return Err(format!("expected ',' or ')', but got {}", t)),
+            None => return Err("expected ')'".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, r) in self.requirements.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", r)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<LabelSelector> for String {
+    fn from(sel: LabelSelector) -> String {
+        sel.to_string()
+    }
+}
+
+impl<'a> From<&'a v1::LabelSelector> for LabelSelector {
+    fn from(sel: &'a v1::LabelSelector) -> Self {
+        let mut keys: Vec<_> = sel.match_labels.keys().collect();
+        // Map order is not significant; keep the output stable
+        keys.sort();
+        let labels = keys.into_iter().map(|k| {
+            let v = match sel.match_labels[k] {
+                ::serde_json::Value::String(ref s) => s.clone(),
+                ref other => other.to_string(),
+            };
+            Requirement::Eq(k.clone(), v)
+        });
+        let exprs = sel.match_expressions.iter().map(|e| {
+            let key = e.key.clone();
+            match e.operator {
+                LabelSelectorOperator::In => Requirement::In(key, e.values.clone()),
+                LabelSelectorOperator::NotIn => Requirement::NotIn(key, e.values.clone()),
+                LabelSelectorOperator::Exists => Requirement::Exists(key),
+                LabelSelectorOperator::DoesNotExist => Requirement::DoesNotExist(key),
+            }
+        });
+        LabelSelector {
+            requirements: labels.chain(exprs).collect(),
+        }
+    }
+}
+
+/// A field name usable in field selectors.  The apiserver only
+/// supports a handful of fields per resource, listed in `fields`, and
+/// the selectable fields of custom resources.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Field(Cow<'static, str>);
+
+impl Field {
+    /// A field not covered by the constants in `fields`.
+    pub const fn new(name: &'static str) -> Self {
+        Field(Cow::Borrowed(name))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A field only known at runtime, eg: a custom resource's selectable
+/// field read from its definition.
+impl From<String> for Field {
+    fn from(name: String) -> Self {
+        Field(Cow::Owned(name))
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Commonly supported selector fields.
+pub mod fields {
+    use super::Field;
+
+    /// Supported by all resources.
+    pub const METADATA_NAME: Field = Field::new("metadata.name");
+    /// Supported by all namespaced resources.
+    pub const METADATA_NAMESPACE: Field = Field::new("metadata.namespace");
+
+    // Pods
+    pub const SPEC_NODE_NAME: Field = Field::new("spec.nodeName");
+    pub const SPEC_RESTART_POLICY: Field = Field::new("spec.restartPolicy");
+    pub const SPEC_SCHEDULER_NAME: Field = Field::new("spec.schedulerName");
+    pub const SPEC_SERVICE_ACCOUNT_NAME: Field = Field::new("spec.serviceAccountName");
+    pub const STATUS_PHASE: Field = Field::new("status.phase");
+    pub const STATUS_POD_IP: Field = Field::new("status.podIP");
+    pub const STATUS_NOMINATED_NODE_NAME: Field = Field::new("status.nominatedNodeName");
+
+    // Nodes
+    pub const SPEC_UNSCHEDULABLE: Field = Field::new("spec.unschedulable");
+
+    // Secrets
+    pub const TYPE: Field = Field::new("type");
+
+    // Events
+    pub const INVOLVED_OBJECT_KIND: Field = Field::new("involvedObject.kind");
+    pub const INVOLVED_OBJECT_NAME: Field = Field::new("involvedObject.name");
+    pub const INVOLVED_OBJECT_NAMESPACE: Field = Field::new("involvedObject.namespace");
+    pub const INVOLVED_OBJECT_UID: Field = Field::new("involvedObject.uid");
+    pub const REASON: Field = Field::new("reason");
+    pub const SOURCE: Field = Field::new("source");
+}
+
+/// A field selector.  Only equality tests are supported by the
+/// apiserver.
+///
+/// ```
+/// # use kubernetes_client::selector::{fields, FieldSelector};
+/// let sel = FieldSelector::new()
+///     .eq(fields::SPEC_NODE_NAME, "node-1")
+///     .ne(fields::STATUS_PHASE, "Succeeded");
+/// assert_eq!(sel.to_string(), "spec.nodeName=node-1,status.phase!=Succeeded");
+/// ```
+///
+/// Selector strings parse as the apiserver parses them, and match
+/// objects by the values at the fields' paths:
+///
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate kubernetes_client;
+/// # use kubernetes_client::selector::FieldSelector;
+/// # fn main() {
+/// let sel: FieldSelector = "spec.nodeName=node-1".parse().unwrap();
+/// assert!(sel.matches(&json!({"spec": {"nodeName": "node-1"}})));
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldSelector {
+    /// (field, whether equal, value).
+    requirements: Vec<(Cow<'static, str>, bool, String)>,
+}
+
+impl FieldSelector {
+    /// An empty selector, which matches everything.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn eq<V: Into<String>>(mut self, field: Field, value: V) -> Self {
+        self.requirements.push((field.0, true, value.into()));
+        self
+    }
+
+    pub fn ne<V: Into<String>>(mut self, field: Field, value: V) -> Self {
+        self.requirements.push((field.0, false, value.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    /// Whether `object` satisfies this selector.  A field that is
+    /// missing (or null) has the value `""`, and one that isn't a
+    /// string is compared as JSON, eg `true`.  Which fields the
+    /// apiserver supports for each resource isn't checked.
+    pub fn matches(&self, object: &Value) -> bool {
+        self.requirements.iter().all(|&(ref field, eq, ref value)| {
+            let found = field
+                .split('.')
+                .try_fold(object, |v, k| v.get(k))
+                .unwrap_or(&Value::Null);
+            let found = match *found {
+                Value::Null => Cow::Borrowed(""),
+                Value::String(ref s) => Cow::Borrowed(s.as_str()),
+                ref v => Cow::Owned(v.to_string()),
+            };
+            (found == value.as_str()) == eq
+        })
+    }
+}
+
+impl FromStr for FieldSelector {
+    type Err = ParseError;
+
+    fn from_str(selector: &str) -> Result<Self, ParseError> {
+        let error = |reason: String| ParseError {
+            selector: selector.to_string(),
+            reason,
+        };
+        let mut parsed = FieldSelector::new();
+        for term in split_unescaped(selector) {
+            if term.is_empty() {
+                continue;
+            }
+            let (field, eq, value) =
+                split_term(term).ok_or_else(|| error(format!("{:?} has no operator", term)))?;
+            let value = unescape(value).map_err(error)?;
+            parsed
+                .requirements
+                .push((Cow::Owned(field.to_string()), eq, value));
+        }
+        Ok(parsed)
+    }
+}
+
+/// Split a field selector at the commas that aren't escaped.
+fn split_unescaped(selector: &str) -> Vec<&str> {
+    let mut terms = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in selector.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                terms.push(&selector[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    terms.push(&selector[start..]);
+    terms
+}
+
+/// Split a term at its first unescaped operator, into the field,
+/// whether it is an equality test, and the (still escaped) value.
+fn split_term(term: &str) -> Option<(&str, bool, &str)> {
+    let mut escaped = false;
+    for (i, c) in term.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        let rest = &term[i..];
+        if c == '\\' {
+            escaped = true;
+        } else if let Some(value) = rest.strip_prefix("!=") {
+            return Some((&term[..i], false, value));
+        } else if let Some(value) = rest.strip_prefix("==") {
+            return Some((&term[..i], true, value));
+        } else if let Some(value) = rest.strip_prefix('=') {
+            return Some((&term[..i], true, value));
+        }
+    }
+    None
+}
+
+/// Undo the escaping of `\\`, `,` and `=` in a field selector's value.
+fn unescape(value: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ '\\') | Some(c @ ',') | Some(c @ '=') => unescaped.push(c),
+                Some(c) => return Err(format!("invalid escape sequence \\{} in {:?}", c, value)),
+                None => return Err(format!("unterminated escape sequence in {:?}", value)),
+            },
+            ',' | '=' => return Err(format!("unescaped {:?} in {:?}", c, value)),
+            c => unescaped.push(c),
+        }
+    }
+    Ok(unescaped)
+}
+
+impl fmt::Display for FieldSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &(ref field, eq, ref value)) in self.requirements.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            let op = if eq { "=" } else { "!=" };
+            // Values may contain selector syntax; escape as the apiserver expects
+            let value = value
+                .replace('\\', "\\\\")
+                .replace(',', "\\,")
+                .replace('=', "\\=");
+            write!(f, "{}{}{}", field, op, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<FieldSelector> for String {
+    fn from(sel: FieldSelector) -> String {
+        sel.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fields, Field, FieldSelector, LabelSelector};
+    use api::meta::v1;
+    use serde_json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn label_selector() {
+        let sel = LabelSelector::new()
+            .eq("app", "web")
+            .ne("env", "prod")
+            .in_("tier", &["frontend", "canary"])
+            .not_in("zone", &["a"])
+            .exists("owner")
+            .does_not_exist("legacy");
+        assert_eq!(
+            sel.to_string(),
+            "app=web,env!=prod,tier in (frontend,canary),zone notin (a),owner,!legacy"
+        );
+        assert_eq!(String::from(LabelSelector::new()), "");
+
+        let mut labels = HashMap::new();
+        labels.insert("app".to_string(), "web".to_string());
+        labels.insert("tier".to_string(), "canary".to_string());
+        labels.insert("owner".to_string(), "me".to_string());
+        assert!(sel.matches(&labels));
+        labels.insert("legacy".to_string(), "true".to_string());
+        assert!(!sel.matches(&labels));
+    }
+
+    #[test]
+    fn from_api() {
+        let sel: v1::LabelSelector = serde_json::from_value(json!({
+            "matchLabels": {"tier": "frontend", "app": "web"},
+            "matchExpressions": [
+                {"key": "env", "operator": "NotIn", "values": ["dev", "test"]},
+                {"key": "owner", "operator": "Exists", "values": []},
+            ],
+        }))
+        .unwrap();
+        assert_eq!(
+            LabelSelector::from(&sel).to_string(),
+            "app=web,tier=frontend,env notin (dev,test),owner"
+        );
+    }
+
+    #[test]
+    fn parse_label_selector() {
+        let parse = |s: &str| s.parse::<LabelSelector>().map(|sel| sel.to_string());
+        assert_eq!(parse(""), Ok("".to_string()));
+        assert_eq!(parse("  "), Ok("".to_string()));
+        assert_eq!(
+            parse("app=web, env != prod,tier in (frontend, canary),zone notin (a,),owner,!legacy"),
+            Ok(
+                "app=web,env!=prod,tier in (frontend,canary),zone notin (a,),owner,!legacy"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse("app==web,x=,n>3,m<-1"),
+            Ok("app=web,x=,n>3,m<-1".to_string())
+        );
+        assert_eq!(
+            parse("app.kubernetes.io/name=web"),
+            Ok("app.kubernetes.io/name=web".to_string())
+        );
+        for bad in &[
+            "app=web,",
+            ",app",
+            "app in ()",
+            "app in (a",
+            "app in a",
+            "app=a=b",
+            "app web",
+            "n>x",
+            "!app=web",
+            "-app",
+            "app=-web",
+            "a/b/c",
+        ] {
+            let err = bad.parse::<LabelSelector>().unwrap_err();
+            assert_eq!(err.selector, *bad);
+        }
+
+        let mut meta = v1::ObjectMeta::default();
+        meta.labels.insert("app".to_string(), "web".to_string());
+        meta.labels.insert("replicas".to_string(), "3".to_string());
+        let matches = |s: &str| s.parse::<LabelSelector>().unwrap().matches(&meta);
+        for sel in &[
+            "",
+            "app",
+            "app=web",
+            "app in (web,db)",
+            "env notin (prod)",
+            "!env",
+            "replicas>2",
+            "replicas<4",
+            "env!=prod",
+        ] {
+            assert!(matches(sel), "{}", sel);
+        }
+        for sel in &[
+            "env",
+            "app=db",
+            "app notin (web)",
+            "env in (prod)",
+            "!app",
+            "replicas>3",
+            "app>1",
+            "app!=web",
+        ] {
+            assert!(!matches(sel), "{}", sel);
+        }
+    }
+
+    #[test]
+    fn parse_field_selector() {
+        let sel: FieldSelector = r"metadata.name=web-0,status.phase!=Failed,reason==a\=b\,c"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            sel.to_string(),
+            r"metadata.name=web-0,status.phase!=Failed,reason=a\=b\,c"
+        );
+        assert_eq!("".parse::<FieldSelector>(), Ok(FieldSelector::new()));
+        for bad in &["metadata.name", "reason=a=b", r"reason=a\b", "reason=a\\"] {
+            assert!(bad.parse::<FieldSelector>().is_err(), "{}", bad);
+        }
+
+        let pod = json!({
+            "metadata": {"name": "web-0"},
+            "spec": {"nodeName": "node-1", "unschedulable": true},
+            "reason": "a=b,c",
+        });
+        assert!(sel.matches(&pod));
+        let matches = |s: &str| s.parse::<FieldSelector>().unwrap().matches(&pod);
+        assert!(matches("spec.unschedulable=true"));
+        assert!(matches("spec.hostname="));
+        assert!(matches("spec.nodeName!=node-2"));
+        assert!(!matches("spec.nodeName=node-2"));
+        assert!(!matches("spec.hostname!="));
+    }
+
+    #[test]
+    fn field_selector() {
+        let sel = FieldSelector::new()
+            .eq(fields::METADATA_NAME, "web-0")
+            .ne(fields::STATUS_PHASE, "Failed");
+        assert_eq!(sel.to_string(), "metadata.name=web-0,status.phase!=Failed");
+
+        let sel = FieldSelector::new().eq(fields::REASON, "a=b,c");
+        assert_eq!(String::from(sel), r"reason=a\=b\,c");
+
+        let replicas = format!("spec.{}", "replicas");
+        let sel = FieldSelector::new().eq(Field::from(replicas), "3");
+        assert_eq!(sel.to_string(), "spec.replicas=3");
+    }
+}
@@ -0,0 +1,34 @@
+//! Print Rust types for a CustomResourceDefinition.
+//!
+//! Usage: crd2rust [CRD.yaml]
+//!
+//! Reads from stdin if no file is given.
+
+extern crate failure;
+extern crate kubernetes_codegen;
+
+use failure::Error;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+fn run() -> Result<(), Error> {
+    let yaml = match env::args().nth(1) {
+        Some(ref path) if path != "-" => fs::read_to_string(path)?,
+        _ => {
+            let mut s = String::new();
+            io::stdin().read_to_string(&mut s)?;
+            s
+        }
+    };
+    print!("{}", kubernetes_codegen::generate_from_yaml(&yaml)?);
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("crd2rust: {}", e);
+        process::exit(1);
+    }
+}
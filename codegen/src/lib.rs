@@ -0,0 +1,441 @@
+//! # Rust types from CustomResourceDefinitions
+//!
+//! Turns a CRD's OpenAPI v3 validation schema into Rust source for the
+//! spec (and status) types, annotated with `#[derive(CustomResource)]`
+//! so the resulting object gets the usual `TypeMeta`/`Metadata` impls.
+//!
+//! From a build script:
+//!
+//! ```ignore
+//! extern crate kubernetes_codegen;
+//!
+//! fn main() {
+//!     let out = std::env::var("OUT_DIR").unwrap();
+//!     kubernetes_codegen::generate_file("crds/crontab.yaml", format!("{}/crontab.rs", out))
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! and then `include!(concat!(env!("OUT_DIR"), "/crontab.rs"));`.
+//! The including module needs `Serialize`, `Deserialize` and
+//! `CustomResource` derives in scope.
+//!
+//! The `crd2rust` binary does the same from the command line.
+
+#[macro_use]
+extern crate failure;
+extern crate kubernetes_api as api;
+extern crate serde_json;
+extern crate serde_yaml;
+
+use api::apiextensions::v1beta1::{CustomResourceDefinition, ResourceScope};
+use failure::Error;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Fail)]
+#[fail(display = "unsupported schema at {}: {}", path, reason)]
+pub struct SchemaError {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Generate Rust source for a CRD.
+pub fn generate(crd: &CustomResourceDefinition) -> Result<String, Error> {
+    Generator::default().crd(crd)
+}
+
+/// Generate Rust source for a CRD, given as YAML (or JSON).
+pub fn generate_from_yaml(yaml: &str) -> Result<String, Error> {
+    let crd: CustomResourceDefinition = serde_yaml::from_str(yaml)?;
+    generate(&crd)
+}
+
+/// Read a CRD from `input` and write the generated source to
+/// `output`.  Intended for use from `build.rs`.
+pub fn generate_file<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<(), Error> {
+    let input = input.as_ref();
+    println!("cargo:rerun-if-changed={}", input.display());
+    let src = generate_from_yaml(&fs::read_to_string(input)?)?;
+    fs::write(output, src)?;
+    Ok(())
+}
+
+/// A Rust type for some schema, and whether it implements `Default`.
+struct Ty {
+    name: String,
+    default: bool,
+}
+
+impl Ty {
+    fn new(name: &str) -> Self {
+        Ty {
+            name: name.into(),
+            default: true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Generator {
+    // Emitted type definitions, in order.
+    out: String,
+    names: HashSet<String>,
+}
+
+impl Generator {
+    fn crd(mut self, crd: &CustomResourceDefinition) -> Result<String, Error> {
+        let spec = &crd.spec;
+        let names = &spec.names;
+        let version = spec
+            .version
+            .as_ref()
+            .or_else(|| spec.versions.iter().find(|v| v.storage).map(|v| &v.name))
+            .or_else(|| spec.versions.first().map(|v| &v.name))
+            .ok_or_else(|| SchemaError {
+                path: names.kind.clone(),
+                reason: "no version".into(),
+            })?;
+
+        let empty = Map::new();
+        let properties = spec
+            .validation
+            .as_ref()
+            .and_then(|v| v.open_api_v3_schema.as_ref())
+            .and_then(|s| s.get("properties"))
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+
+        let spec_name = format!("{}Spec", names.kind);
+        let status_name = format!("{}Status", names.kind);
+        self.names.insert(names.kind.clone());
+        self.names.insert(format!("{}List", names.kind));
+        self.names.insert(spec_name.clone());
+
+        let mut attrs = vec![
+            format!("group = {:?}", spec.group),
+            format!("version = {:?}", version),
+            format!("kind = {:?}", names.kind),
+            format!("plural = {:?}", names.plural),
+        ];
+        if let Some(ref singular) = names.singular {
+            attrs.push(format!("singular = {:?}", singular));
+        }
+        for s in &names.short_names {
+            attrs.push(format!("shortname = {:?}", s));
+        }
+        if spec.scope == ResourceScope::Namespaced {
+            attrs.push("namespaced".into());
+        }
+        let status = properties.get("status");
+        if status.is_some() {
+            self.names.insert(status_name.clone());
+            attrs.push(format!("status = {:?}", status_name));
+        }
+
+        let mut header = String::new();
+        writeln!(
+            header,
+            "// Generated by kubernetes_codegen from {}.  Do not edit.",
+            crd.metadata.name.as_ref().unwrap_or(&names.plural)
+        )?;
+        writeln!(header)?;
+        let spec_schema = properties.get("spec").cloned().unwrap_or_else(json_object);
+        let mut body = String::new();
+        self.object(
+            &mut body,
+            &spec_name,
+            &spec_schema,
+            &[
+                "#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, PartialEq)]".into(),
+                format!("#[kubernetes({})]", attrs.join(", ")),
+            ],
+        )?;
+        if let Some(status) = status {
+            self.object(&mut body, &status_name, status, &[])?;
+        }
+
+        Ok(header + &body + &self.out)
+    }
+
+    /// Emit a struct for an object schema into `out`.
+    fn object(
+        &mut self,
+        out: &mut String,
+        name: &str,
+        schema: &Value,
+        attrs: &[String],
+    ) -> Result<bool, Error> {
+        let empty = Map::new();
+        let properties = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+        let required: HashSet<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|r| r.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        let mut fields = String::new();
+        let mut default = true;
+        for (prop, prop_schema) in properties {
+            let ty = self.rust_type(&format!("{}{}", name, pascal_case(prop)), prop_schema)?;
+            let (field, renamed) = field_name(prop);
+            let container =
+                ty.name.starts_with("Vec<") || ty.name.starts_with("::std::collections::HashMap<");
+
+            doc(&mut fields, "    ", prop_schema)?;
+            let mut serde = vec![];
+            if renamed {
+                serde.push(format!("rename = {:?}", prop));
+            }
+            let field_ty = if required.contains(prop.as_str()) {
+                default = default && ty.default;
+                ty.name
+            } else if container {
+                serde.push("default".into());
+                ty.name
+            } else {
+                format!("Option<{}>", ty.name)
+            };
+            if !serde.is_empty() {
+                writeln!(fields, "    #[serde({})]", serde.join(", "))?;
+            }
+            writeln!(fields, "    pub {}: {},", field, field_ty)?;
+        }
+        if properties.is_empty() {
+            // Nothing known about the contents; keep whatever is there.
+            writeln!(fields, "    #[serde(flatten)]")?;
+            writeln!(
+                fields,
+                "    pub fields: ::serde_json::Map<String, ::serde_json::Value>,"
+            )?;
+        }
+
+        doc(out, "", schema)?;
+        if attrs.is_empty() {
+            let derives = if default {
+                "Serialize, Deserialize, Default, Debug, Clone, PartialEq"
+            } else {
+                "Serialize, Deserialize, Debug, Clone, PartialEq"
+            };
+            writeln!(out, "#[derive({})]", derives)?;
+        }
+        for a in attrs {
+            writeln!(out, "{}", a)?;
+        }
+        writeln!(out, "#[serde(rename_all = \"camelCase\")]")?;
+        writeln!(out, "pub struct {} {{", name)?;
+        out.push_str(&fields);
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        Ok(default)
+    }
+
+    /// Find (or emit) the Rust type for a schema.  `name` is used for
+    /// any nested struct.
+    fn rust_type(&mut self, name: &str, schema: &Value) -> Result<Ty, Error> {
+        let flag = |f| schema.get(f).and_then(Value::as_bool).unwrap_or(false);
+        if flag("x-kubernetes-int-or-string") {
+            return Ok(Ty {
+                name: "::kubernetes_api::IntOrString".into(),
+                default: false,
+            });
+        }
+        if flag("x-kubernetes-preserve-unknown-fields") && schema.get("properties").is_none() {
+            return Ok(Ty::new("::serde_json::Value"));
+        }
+
+        let typ = schema.get("type").and_then(Value::as_str);
+        let format = schema.get("format").and_then(Value::as_str);
+        let ty = match (typ, format) {
+            (Some("string"), Some("date-time")) => Ty::new("::kubernetes_api::Time"),
+            (Some("string"), _) => Ty::new("String"),
+            (Some("integer"), Some("int32")) => Ty::new("i32"),
+            (Some("integer"), _) => Ty::new("i64"),
+            (Some("number"), _) => Ty::new("f64"),
+            (Some("boolean"), _) => Ty::new("bool"),
+            (Some("array"), _) => {
+                let item = match schema.get("items") {
+                    Some(items) => self.rust_type(&singular(name), items)?,
+                    None => Ty::new("::serde_json::Value"),
+                };
+                Ty::new(&format!("Vec<{}>", item.name))
+            }
+            (Some("object"), _) | (None, _) if schema.get("properties").is_some() => {
+                let name = self.unique(name);
+                let mut out = String::new();
+                let default = self.object(&mut out, &name, schema, &[])?;
+                self.out.push_str(&out);
+                Ty { name, default }
+            }
+            (Some("object"), _) => match schema.get("additionalProperties") {
+                Some(v) if v.is_object() => {
+                    let value = self.rust_type(name, v)?;
+                    Ty::new(&format!(
+                        "::std::collections::HashMap<String, {}>",
+                        value.name
+                    ))
+                }
+                _ => Ty::new("::serde_json::Value"),
+            },
+            // anyOf/oneOf and friends
+            (None, _) => Ty::new("::serde_json::Value"),
+            (Some(t), _) => {
+                return Err(SchemaError {
+                    path: name.into(),
+                    reason: format!("unknown type {:?}", t),
+                }
+                .into())
+            }
+        };
+        Ok(ty)
+    }
+
+    fn unique(&mut self, name: &str) -> String {
+        let mut candidate = name.to_string();
+        let mut i = 2;
+        while self.names.contains(&candidate) {
+            candidate = format!("{}{}", name, i);
+            i += 1;
+        }
+        self.names.insert(candidate.clone());
+        candidate
+    }
+}
+
+fn json_object() -> Value {
+    Value::Object(Map::new())
+}
+
+/// Emit a schema's description as doc comments.
+fn doc(out: &mut String, indent: &str, schema: &Value) -> Result<(), Error> {
+    if let Some(desc) = schema.get("description").and_then(Value::as_str) {
+        for line in desc.trim().lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                writeln!(out, "{}///", indent)?;
+            } else {
+                writeln!(out, "{}/// {}", indent, line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Item type name for an array, eg: "FooPorts" -> "FooPort".
+fn singular(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if let (Some(stem), false) = (name.strip_suffix('s'), name.ends_with("ss")) {
+        stem.to_string()
+    } else {
+        format!("{}Item", name)
+    }
+}
+
+fn pascal_case(s: &str) -> String {
+    let mut out = String::new();
+    let mut upper = true;
+    for c in s.chars() {
+        if !c.is_alphanumeric() {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn snake_case(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).map(|n| n.is_lowercase()) == Some(true);
+            if (prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_lower))
+                && !out.ends_with('_')
+            {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_numeric()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// What serde's `rename_all = "camelCase"` makes of a field name.
+fn serde_camel_case(field: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for c in field.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The Rust field name for a property, and whether it needs an
+/// explicit `#[serde(rename)]`.
+fn field_name(prop: &str) -> (String, bool) {
+    let mut field = snake_case(prop);
+    if field == "type" {
+        // As elsewhere in kubernetes_api
+        field = "typ".into();
+    } else if KEYWORDS.contains(&field.as_str()) {
+        field.push('_');
+    }
+    let renamed = serde_camel_case(&field) != prop;
+    (field, renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{field_name, singular, snake_case};
+
+    #[test]
+    fn names() {
+        assert_eq!(snake_case("cronSpec"), "cron_spec");
+        assert_eq!(snake_case("openAPIV3Schema"), "open_apiv3_schema");
+        assert_eq!(snake_case("podIP"), "pod_ip");
+        assert_eq!(snake_case("x-foo"), "x_foo");
+        assert_eq!(field_name("replicas"), ("replicas".to_string(), false));
+        assert_eq!(field_name("podIP"), ("pod_ip".to_string(), true));
+        assert_eq!(field_name("type"), ("typ".to_string(), true));
+        assert_eq!(field_name("ref"), ("ref_".to_string(), false));
+        assert_eq!(singular("FooPorts"), "FooPort");
+        assert_eq!(singular("FooPolicies"), "FooPolicy");
+        assert_eq!(singular("FooData"), "FooDataItem");
+    }
+}
@@ -0,0 +1,36 @@
+// Generated by kubernetes_codegen from crontabs.stable.example.com.  Do not edit.
+
+/// Desired state of the CronTab.
+#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[kubernetes(group = "stable.example.com", version = "v1", kind = "CronTab", plural = "crontabs", singular = "crontab", shortname = "ct", namespaced, status = "CronTabStatus")]
+#[serde(rename_all = "camelCase")]
+pub struct CronTabSpec {
+    /// Schedule, in cron format.
+    pub cron_spec: String,
+    #[serde(default)]
+    pub env: ::std::collections::HashMap<String, String>,
+    pub image: Option<String>,
+    pub max_unavailable: Option<::kubernetes_api::IntOrString>,
+    #[serde(default)]
+    pub ports: Vec<CronTabSpecPort>,
+    pub replicas: Option<i32>,
+    pub template: Option<::serde_json::Value>,
+    #[serde(rename = "type")]
+    pub typ: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CronTabStatus {
+    #[serde(default)]
+    pub active: Vec<String>,
+    pub last_schedule_time: Option<::kubernetes_api::Time>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CronTabSpecPort {
+    pub container_port: i64,
+    pub protocol: Option<String>,
+}
+
@@ -0,0 +1,52 @@
+extern crate kubernetes_api;
+extern crate kubernetes_codegen;
+#[macro_use]
+extern crate kubernetes_derive;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use kubernetes_api::TypeMeta;
+
+mod crontab {
+    include!("../testdata/crontab.rs");
+}
+
+#[test]
+fn golden() {
+    let generated =
+        kubernetes_codegen::generate_from_yaml(include_str!("../testdata/crontab.yaml")).unwrap();
+    assert_eq!(generated, include_str!("../testdata/crontab.rs"));
+}
+
+#[test]
+fn generated_types() {
+    use crontab::*;
+
+    assert_eq!(
+        <CronTab as TypeMeta>::api_version(),
+        "stable.example.com/v1"
+    );
+    assert_eq!(CronTab::GROUP_VERSION_RESOURCE.resource, "crontabs");
+
+    let c: CronTab = serde_json::from_str(
+        r#"{
+          "apiVersion": "stable.example.com/v1",
+          "kind": "CronTab",
+          "metadata": {"name": "c1"},
+          "spec": {
+            "cronSpec": "* * * * */5",
+            "type": "batch",
+            "maxUnavailable": "25%",
+            "ports": [{"containerPort": 80}]
+          },
+          "status": {"active": ["job-1"]}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(c.spec.cron_spec, "* * * * */5");
+    assert_eq!(c.spec.typ.unwrap(), "batch");
+    assert_eq!(c.spec.ports[0].container_port, 80);
+    assert_eq!(c.status.unwrap().active, vec!["job-1"]);
+}
@@ -0,0 +1,348 @@
+//! # Derive macros for Kubernetes custom resources
+//!
+//! `#[derive(CustomResource)]` on a "spec" struct generates the full
+//! API object wrapping it:
+//!
+//! ```ignore
+//! #[derive(CustomResource, Serialize, Deserialize, Debug, Clone)]
+//! #[kubernetes(group = "example.com", version = "v1", kind = "Widget",
+//!              plural = "widgets", namespaced, status = "WidgetStatus")]
+//! pub struct WidgetSpec {
+//!     size: i32,
+//! }
+//! ```
+//!
+//! generates `Widget` (with `metadata`, `spec` and optional `status`
+//! fields), `WidgetList`, the `TypeMeta`/`Metadata`/`MetadataMut` impls, a
+//! `Widget::GROUP_VERSION_RESOURCE` constant and `Widget::crd()`
+//! returning the matching CustomResourceDefinition.
+//!
+//! The spec (and status) types must implement `Serialize`,
+//! `Deserialize`, `Debug` and `Clone`, and serde's derive macros must
+//! be in scope.
+//!
+//! With the `schema` flag, `crd()` also includes an OpenAPI v3
+//! validation schema, and the spec (and status) types must implement
+//! `kubernetes_api::schema::JsonSchema`, usually via
+//! `#[derive(JsonSchema)]`.
+
+// quote! needs a higher limit for the generated impls
+#![recursion_limit = "256"]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{Data, DeriveInput, Ident, Lit, Meta, NestedMeta};
+
+mod schema;
+
+#[proc_macro_derive(CustomResource, attributes(kubernetes))]
+pub fn derive_custom_resource(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match custom_resource(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(JsonSchema, attributes(serde))]
+pub fn derive_json_schema(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match schema::json_schema(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct Attrs {
+    group: Option<String>,
+    version: Option<String>,
+    kind: Option<String>,
+    plural: Option<String>,
+    singular: Option<String>,
+    shortnames: Vec<String>,
+    status: Option<String>,
+    namespaced: bool,
+    schema: bool,
+}
+
+fn lit_str(lit: &Lit, name: &Ident) -> syn::Result<String> {
+    match *lit {
+        Lit::Str(ref s) => Ok(s.value()),
+        _ => Err(syn::Error::new_spanned(
+            lit,
+            format!("expected string value for `{}`", name),
+        )),
+    }
+}
+
+fn parse_attrs(input: &DeriveInput) -> syn::Result<Attrs> {
+    let mut attrs = Attrs::default();
+    for attr in &input.attrs {
+        if !attr.path.is_ident("kubernetes") {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    "expected #[kubernetes(key = \"value\", ...)]",
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "namespaced" => {
+                    attrs.namespaced = true;
+                }
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "schema" => {
+                    attrs.schema = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(ref nv)) => {
+                    let value = lit_str(&nv.lit, &nv.ident)?;
+                    let key = nv.ident.to_string();
+                    match key.as_str() {
+                        "group" => attrs.group = Some(value),
+                        "version" => attrs.version = Some(value),
+                        "kind" => attrs.kind = Some(value),
+                        "plural" => attrs.plural = Some(value),
+                        "singular" => attrs.singular = Some(value),
+                        "shortname" => attrs.shortnames.push(value),
+                        "status" => attrs.status = Some(value),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                &nv.ident,
+                                format!("unknown kubernetes attribute `{}`", key),
+                            ))
+                        }
+                    }
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "unexpected kubernetes attribute",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(attrs)
+}
+
+fn required(value: Option<String>, name: &str) -> syn::Result<String> {
+    value.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            format!("missing #[kubernetes({} = \"...\")] attribute", name),
+        )
+    })
+}
+
+fn custom_resource(input: DeriveInput) -> syn::Result<TokenStream2> {
+    match input.data {
+        Data::Struct(_) => (),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "CustomResource can only be derived for structs",
+            ))
+        }
+    }
+    let attrs = parse_attrs(&input)?;
+    let group = required(attrs.group, "group")?;
+    let version = required(attrs.version, "version")?;
+    let kind = required(attrs.kind, "kind")?;
+    let plural = required(attrs.plural, "plural")?;
+    let singular = attrs.singular.unwrap_or_else(|| kind.to_lowercase());
+    let shortnames = attrs.shortnames;
+    let namespaced = attrs.namespaced;
+
+    let vis = &input.vis;
+    let spec_ty = &input.ident;
+    let ty = Ident::new(&kind, Span::call_site());
+    let list_ty = Ident::new(&format!("{}List", kind), Span::call_site());
+    let api_version = format!("{}/{}", group, version);
+    let list_kind = format!("{}List", kind);
+    let crd_name = format!("{}.{}", plural, group);
+
+    let status_ty = match attrs.status {
+        Some(ref s) => Some(syn::parse_str::<syn::Type>(s)?),
+        None => None,
+    };
+    let (status_field, status_init, subresources) = match status_ty {
+        Some(ref status_ty) => (
+            quote! {
+                #[serde(default, skip_serializing_if = "Option::is_none")]
+                pub status: Option<#status_ty>,
+            },
+            quote! { status: None, },
+            quote! {
+                Some(::kubernetes_api::apiextensions::v1beta1::CustomResourceSubresources {
+                    status: Some(Default::default()),
+                    scale: None,
+                })
+            },
+        ),
+        None => (quote! {}, quote! {}, quote! { None }),
+    };
+
+    let validation = if attrs.schema {
+        let status_schema = match status_ty {
+            Some(ref status_ty) => quote! {
+                Some(<#status_ty as ::kubernetes_api::schema::JsonSchema>::json_schema())
+            },
+            None => quote! { None },
+        };
+        quote! {
+            Some(CustomResourceValidation {
+                open_api_v3_schema: Some(::kubernetes_api::schema::custom_resource(
+                    <#spec_ty as ::kubernetes_api::schema::JsonSchema>::json_schema(),
+                    #status_schema,
+                )),
+            })
+        }
+    } else {
+        quote! { None }
+    };
+
+    let scope = if namespaced {
+        quote! { ::kubernetes_api::apiextensions::v1beta1::ResourceScope::Namespaced }
+    } else {
+        quote! { ::kubernetes_api::apiextensions::v1beta1::ResourceScope::Cluster }
+    };
+
+    let cluster_resource = if namespaced {
+        quote! {}
+    } else {
+        quote! {
+            impl ::kubernetes_api::core::v1::Resource for #ty {
+                type List = #list_ty;
+
+                fn gvr(&self) -> ::kubernetes_api::meta::GroupVersionResource {
+                    #ty::GROUP_VERSION_RESOURCE
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        #[serde(rename_all = "camelCase")]
+        #vis struct #ty {
+            #[serde(flatten)]
+            typemeta: ::kubernetes_api::TypeMetaImpl<#ty>,
+            #[serde(default)]
+            pub metadata: ::kubernetes_api::meta::v1::ObjectMeta,
+            pub spec: #spec_ty,
+            #status_field
+        }
+
+        #vis type #list_ty = ::kubernetes_api::meta::v1::ItemList<#ty>;
+
+        impl #ty {
+            pub const GROUP_VERSION_RESOURCE: ::kubernetes_api::meta::GroupVersionResource<'static> =
+                ::kubernetes_api::meta::GroupVersionResource {
+                    group: #group,
+                    version: #version,
+                    resource: #plural,
+                };
+
+            /// Construct a new object with the given name and spec.
+            pub fn new(name: &str, spec: #spec_ty) -> Self {
+                #ty {
+                    typemeta: Default::default(),
+                    metadata: ::kubernetes_api::meta::v1::ObjectMeta {
+                        name: Some(name.into()),
+                        ..Default::default()
+                    },
+                    spec,
+                    #status_init
+                }
+            }
+
+            /// The CustomResourceDefinition that registers this type.
+            pub fn crd() -> ::kubernetes_api::apiextensions::v1beta1::CustomResourceDefinition {
+                use ::kubernetes_api::apiextensions::v1beta1::*;
+                let mut crd = CustomResourceDefinition::default();
+                crd.metadata.name = Some(#crd_name.into());
+                crd.spec = CustomResourceDefinitionSpec {
+                    group: #group.into(),
+                    version: Some(#version.into()),
+                    names: CustomResourceDefinitionNames {
+                        plural: #plural.into(),
+                        singular: Some(#singular.into()),
+                        short_names: vec![#(#shortnames.into()),*],
+                        kind: #kind.into(),
+                        list_kind: Some(#list_kind.into()),
+                        categories: vec![],
+                    },
+                    scope: #scope,
+                    validation: #validation,
+                    subresources: #subresources,
+                    versions: vec![CustomResourceDefinitionVersion {
+                        name: #version.into(),
+                        served: true,
+                        storage: true,
+                    }],
+                };
+                crd
+            }
+        }
+
+        impl ::kubernetes_api::TypeMeta for #ty {
+            fn api_version() -> &'static str {
+                #api_version
+            }
+            fn kind() -> &'static str {
+                #kind
+            }
+        }
+
+        impl ::kubernetes_api::meta::v1::Metadata for #ty {
+            fn api_version(&self) -> &str {
+                <#ty as ::kubernetes_api::TypeMeta>::api_version()
+            }
+            fn kind(&self) -> &str {
+                <#ty as ::kubernetes_api::TypeMeta>::kind()
+            }
+            fn metadata(&self) -> ::std::borrow::Cow<'_, ::kubernetes_api::meta::v1::ObjectMeta> {
+                ::std::borrow::Cow::Borrowed(&self.metadata)
+            }
+        }
+
+        impl ::kubernetes_api::meta::v1::MetadataMut for #ty {
+            fn metadata_mut(&mut self) -> &mut ::kubernetes_api::meta::v1::ObjectMeta {
+                &mut self.metadata
+            }
+        }
+
+        impl ::kubernetes_api::core::v1::NamespacedResource for #ty {
+            type List = #list_ty;
+
+            fn namespaced(&self) -> bool {
+                #namespaced
+            }
+
+            fn gvr(&self) -> ::kubernetes_api::meta::GroupVersionResource {
+                #ty::GROUP_VERSION_RESOURCE
+            }
+        }
+
+        impl ::kubernetes_api::core::v1::TypedResource for #ty {
+            type List = #list_ty;
+
+            const GROUP_VERSION_RESOURCE: ::kubernetes_api::meta::GroupVersionResource<'static> =
+                #ty::GROUP_VERSION_RESOURCE;
+            const NAMESPACED: bool = #namespaced;
+        }
+
+        #cluster_resource
+    })
+}
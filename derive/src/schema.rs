@@ -0,0 +1,295 @@
+//! `#[derive(JsonSchema)]`
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Attribute, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+/// The serde attributes we care about.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    default: bool,
+    flatten: bool,
+    skip: bool,
+}
+
+fn serde_attrs(attrs: &[Attribute]) -> SerdeAttrs {
+    let mut out = SerdeAttrs::default();
+    for attr in attrs {
+        if !attr.path.is_ident("serde") {
+            continue;
+        }
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list,
+            // serde itself reports malformed attributes
+            _ => continue,
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Word(ref ident)) => match ident.to_string().as_str() {
+                    "default" => out.default = true,
+                    "flatten" => out.flatten = true,
+                    "skip" | "skip_deserializing" => out.skip = true,
+                    _ => (),
+                },
+                NestedMeta::Meta(Meta::NameValue(ref nv)) => {
+                    let value = match nv.lit {
+                        Lit::Str(ref s) => s.value(),
+                        _ => continue,
+                    };
+                    match nv.ident.to_string().as_str() {
+                        "rename" => out.rename = Some(value),
+                        "rename_all" => out.rename_all = Some(value),
+                        "default" => out.default = true,
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    out
+}
+
+/// Concatenated `///` doc comments.
+fn doc(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path.is_ident("doc"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::NameValue(nv)) => match nv.lit {
+                Lit::Str(s) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// Apply a serde `rename_all` rule to a field name, which is taken to
+/// be snake_case, exactly as serde's `RenameRule::apply_to_field` does.
+fn rename_field(field: &str, rule: Option<&str>) -> String {
+    match rule {
+        Some("UPPERCASE") | Some("SCREAMING_SNAKE_CASE") => field.to_ascii_uppercase(),
+        Some("PascalCase") => {
+            let mut pascal = String::new();
+            let mut capitalize = true;
+            for c in field.chars() {
+                if c == '_' {
+                    capitalize = true;
+                } else if capitalize {
+                    pascal.push(c.to_ascii_uppercase());
+                    capitalize = false;
+                } else {
+                    pascal.push(c);
+                }
+            }
+            pascal
+        }
+        Some("camelCase") => lower_first(&rename_field(field, Some("PascalCase"))),
+        Some("kebab-case") => field.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => field.to_ascii_uppercase().replace('_', "-"),
+        // Including "lowercase" and "snake_case", which fields already
+        // are.
+        _ => field.to_string(),
+    }
+}
+
+/// Apply a serde `rename_all` rule to a variant name, which is taken to
+/// be PascalCase, exactly as serde's `RenameRule::apply_to_variant`
+/// does.
+fn rename_variant(variant: &str, rule: Option<&str>) -> String {
+    match rule {
+        Some("lowercase") => variant.to_ascii_lowercase(),
+        Some("UPPERCASE") => variant.to_ascii_uppercase(),
+        Some("camelCase") => lower_first(variant),
+        Some("snake_case") => {
+            let mut snake = String::new();
+            for (i, c) in variant.char_indices() {
+                if i > 0 && c.is_uppercase() {
+                    snake.push('_');
+                }
+                snake.push(c.to_ascii_lowercase());
+            }
+            snake
+        }
+        Some("SCREAMING_SNAKE_CASE") => {
+            rename_variant(variant, Some("snake_case")).to_ascii_uppercase()
+        }
+        Some("kebab-case") => rename_variant(variant, Some("snake_case")).replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => {
+            rename_variant(variant, Some("SCREAMING_SNAKE_CASE")).replace('_', "-")
+        }
+        // Including "PascalCase", which variants already are.
+        _ => variant.to_string(),
+    }
+}
+
+fn lower_first(s: &str) -> String {
+    let mut c = s.chars();
+    match c.next() {
+        Some(first) => first.to_ascii_lowercase().to_string() + c.as_str(),
+        None => String::new(),
+    }
+}
+
+/// An identifier as serde names it, without any `r#`.
+fn unraw(ident: &syn::Ident) -> String {
+    let ident = ident.to_string();
+    match ident.strip_prefix("r#") {
+        Some(ident) => ident.to_string(),
+        None => ident,
+    }
+}
+
+pub fn json_schema(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let container = serde_attrs(&input.attrs);
+    let rule = container.rename_all.as_deref();
+    let description = doc(&input.attrs);
+
+    let body = match input.data {
+        Data::Struct(ref data) => {
+            let fields = match data.fields {
+                Fields::Named(ref f) => &f.named,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &input.ident,
+                        "JsonSchema can only be derived for structs with named fields",
+                    ))
+                }
+            };
+            let mut properties = vec![];
+            let mut flattened = vec![];
+            for field in fields {
+                let attrs = serde_attrs(&field.attrs);
+                let ty = &field.ty;
+                if attrs.skip {
+                    continue;
+                }
+                if attrs.flatten {
+                    flattened.push(quote! {
+                        ::kubernetes_api::schema::flatten(
+                            &mut schema,
+                            <#ty as ::kubernetes_api::schema::JsonSchema>::json_schema(),
+                        );
+                    });
+                    continue;
+                }
+                let ident = unraw(field.ident.as_ref().unwrap());
+                let name = attrs.rename.unwrap_or_else(|| rename_field(&ident, rule));
+                let desc = doc(&field.attrs);
+                let required = if attrs.default || container.default {
+                    quote! { false }
+                } else {
+                    quote! { !<#ty as ::kubernetes_api::schema::JsonSchema>::optional() }
+                };
+                properties.push(quote! {
+                    properties.push((
+                        #name,
+                        ::kubernetes_api::schema::describe(
+                            <#ty as ::kubernetes_api::schema::JsonSchema>::json_schema(),
+                            #desc,
+                        ),
+                    ));
+                    if #required {
+                        required.push(#name);
+                    }
+                });
+            }
+            quote! {
+                #[allow(unused_mut)]
+                let mut properties = vec![];
+                #[allow(unused_mut)]
+                let mut required = vec![];
+                #(#properties)*
+                #[allow(unused_mut)]
+                let mut schema = ::kubernetes_api::schema::object(properties, required);
+                #(#flattened)*
+                schema
+            }
+        }
+        Data::Enum(ref data) => {
+            let mut values = vec![];
+            for variant in &data.variants {
+                match variant.fields {
+                    Fields::Unit => (),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            variant,
+                            "JsonSchema can only be derived for enums with unit variants",
+                        ))
+                    }
+                }
+                let attrs = serde_attrs(&variant.attrs);
+                if attrs.skip {
+                    continue;
+                }
+                values.push(
+                    attrs
+                        .rename
+                        .unwrap_or_else(|| rename_variant(&unraw(&variant.ident), rule)),
+                );
+            }
+            quote! {
+                ::kubernetes_api::schema::string_enum(vec![#(#values),*])
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "JsonSchema cannot be derived for unions",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::kubernetes_api::schema::JsonSchema for #ident #ty_generics #where_clause {
+            fn json_schema() -> ::kubernetes_api::schema::Value {
+                let schema = { #body };
+                ::kubernetes_api::schema::describe(schema, #description)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rename_field, rename_variant};
+
+    #[test]
+    fn rename_rules() {
+        assert_eq!(rename_field("pod_ip", Some("camelCase")), "podIp");
+        assert_eq!(rename_field("pod_ip", Some("lowercase")), "pod_ip");
+        assert_eq!(rename_field("pod_ip", Some("UPPERCASE")), "POD_IP");
+        assert_eq!(rename_field("max_surge", Some("PascalCase")), "MaxSurge");
+        assert_eq!(rename_field("max_surge", Some("kebab-case")), "max-surge");
+        assert_eq!(
+            rename_field("max_surge", Some("SCREAMING-KEBAB-CASE")),
+            "MAX-SURGE"
+        );
+        assert_eq!(
+            rename_variant("IfNotPresent", Some("camelCase")),
+            "ifNotPresent"
+        );
+        assert_eq!(
+            rename_variant("IfNotPresent", Some("lowercase")),
+            "ifnotpresent"
+        );
+        assert_eq!(
+            rename_variant("IfNotPresent", Some("UPPERCASE")),
+            "IFNOTPRESENT"
+        );
+        assert_eq!(
+            rename_variant("IfNotPresent", Some("kebab-case")),
+            "if-not-present"
+        );
+        assert_eq!(
+            rename_variant("IfNotPresent", Some("SCREAMING_SNAKE_CASE")),
+            "IF_NOT_PRESENT"
+        );
+        assert_eq!(rename_variant("IfNotPresent", None), "IfNotPresent");
+    }
+}
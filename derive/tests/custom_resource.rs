@@ -0,0 +1,89 @@
+extern crate kubernetes_api;
+#[macro_use]
+extern crate kubernetes_derive;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+
+use kubernetes_api::apiextensions::v1beta1::ResourceScope;
+use kubernetes_api::meta::v1::Metadata;
+use kubernetes_api::TypeMeta;
+
+#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[kubernetes(
+    group = "example.com",
+    version = "v1",
+    kind = "Widget",
+    plural = "widgets",
+    shortname = "wd",
+    namespaced,
+    status = "WidgetStatus"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetSpec {
+    pub size: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetStatus {
+    pub ready_replicas: i32,
+}
+
+#[derive(CustomResource, Serialize, Deserialize, Debug, Clone)]
+#[kubernetes(
+    group = "example.com",
+    version = "v1alpha1",
+    kind = "Gadget",
+    plural = "gadgets"
+)]
+pub struct GadgetSpec {}
+
+#[test]
+fn typemeta() {
+    assert_eq!(<Widget as TypeMeta>::api_version(), "example.com/v1");
+    assert_eq!(<Widget as TypeMeta>::kind(), "Widget");
+    assert_eq!(<WidgetList as TypeMeta>::kind(), "WidgetList");
+    assert_eq!(Widget::GROUP_VERSION_RESOURCE.resource, "widgets");
+}
+
+#[test]
+fn serde_roundtrip() {
+    let w = Widget::new("w1", WidgetSpec { size: 3 });
+    assert_eq!(w.metadata().name.as_ref().unwrap(), "w1");
+
+    let j = serde_json::to_value(&w).unwrap();
+    assert_eq!(j["apiVersion"], "example.com/v1");
+    assert_eq!(j["kind"], "Widget");
+    assert_eq!(j["spec"], json!({"size": 3}));
+    assert!(j.get("status").is_none());
+
+    let w2: Widget = serde_json::from_value(json!({
+        "apiVersion": "example.com/v1",
+        "kind": "Widget",
+        "metadata": {"name": "w1"},
+        "spec": {"size": 3},
+        "status": {"readyReplicas": 2},
+    }))
+    .unwrap();
+    assert_eq!(w2.spec, w.spec);
+    assert_eq!(w2.status.unwrap().ready_replicas, 2);
+}
+
+#[test]
+fn crd() {
+    let crd = Widget::crd();
+    assert_eq!(crd.metadata.name.unwrap(), "widgets.example.com");
+    assert_eq!(crd.spec.group, "example.com");
+    assert_eq!(crd.spec.names.kind, "Widget");
+    assert_eq!(crd.spec.names.singular.unwrap(), "widget");
+    assert_eq!(crd.spec.names.short_names, vec!["wd"]);
+    assert_eq!(crd.spec.scope, ResourceScope::Namespaced);
+    assert!(crd.spec.subresources.unwrap().status.is_some());
+
+    let crd = Gadget::crd();
+    assert_eq!(crd.spec.scope, ResourceScope::Cluster);
+    assert_eq!(crd.spec.subresources, None);
+}
@@ -0,0 +1,134 @@
+extern crate kubernetes_api;
+#[macro_use]
+extern crate kubernetes_derive;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+
+use kubernetes_api::schema::JsonSchema;
+use kubernetes_api::IntOrString;
+
+/// A thing.
+#[derive(JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Thing {
+    /// How big.
+    max_size: i32,
+    #[serde(rename = "type")]
+    typ: Option<Kind>,
+    #[serde(default)]
+    tags: Vec<String>,
+    surge: IntOrString,
+    #[serde(flatten)]
+    extra: Extra,
+    #[serde(skip)]
+    #[allow(dead_code)]
+    ignored: bool,
+}
+
+#[derive(JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Extra {
+    pod_ip: String,
+}
+
+#[derive(JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Small,
+    #[serde(rename = "LARGE")]
+    Large,
+}
+
+#[derive(CustomResource, JsonSchema, Serialize, Deserialize, Debug, Clone)]
+#[kubernetes(
+    group = "example.com",
+    version = "v1",
+    kind = "Gizmo",
+    plural = "gizmos",
+    schema
+)]
+struct GizmoSpec {
+    size: i64,
+}
+
+#[test]
+fn derived_schema() {
+    assert_eq!(
+        Thing::json_schema(),
+        json!({
+            "type": "object",
+            "description": "A thing.",
+            "properties": {
+                "maxSize": {"type": "integer", "format": "int32", "description": "How big."},
+                "type": {"type": "string", "enum": ["small", "LARGE"]},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "surge": {
+                    "x-kubernetes-int-or-string": true,
+                    "anyOf": [{"type": "integer"}, {"type": "string"}],
+                },
+                "podIp": {"type": "string"},
+            },
+            "required": ["maxSize", "surge", "podIp"],
+        })
+    );
+}
+
+#[test]
+fn crd_validation() {
+    let crd = Gizmo::crd();
+    let schema = crd.spec.validation.unwrap().open_api_v3_schema.unwrap();
+    assert_eq!(
+        schema,
+        json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {"size": {"type": "integer", "format": "int64"}},
+                    "required": ["size"],
+                },
+            },
+        })
+    );
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Default)]
+#[serde(rename_all = "UPPERCASE")]
+struct Shouty {
+    pod_ip: String,
+    r#type: String,
+}
+
+#[derive(JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING-KEBAB-CASE")]
+enum Policy {
+    IfNotPresent,
+    r#Always,
+}
+
+#[test]
+fn names_match_serde() {
+    let schema = Shouty::json_schema();
+    let mut properties: Vec<_> = schema["properties"].as_object().unwrap().keys().collect();
+    properties.sort();
+    let value = serde_json::to_value(Shouty::default()).unwrap();
+    let mut fields: Vec<_> = value.as_object().unwrap().keys().collect();
+    fields.sort();
+    assert_eq!(properties, fields);
+    assert_eq!(fields, vec!["POD_IP", "TYPE"]);
+
+    assert_eq!(
+        Policy::json_schema()["enum"],
+        json!([
+            serde_json::to_value(Policy::IfNotPresent).unwrap(),
+            serde_json::to_value(Policy::r#Always).unwrap(),
+        ])
+    );
+    assert_eq!(
+        Policy::json_schema()["enum"],
+        json!(["IF-NOT-PRESENT", "ALWAYS"])
+    );
+}
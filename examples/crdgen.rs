@@ -0,0 +1,58 @@
+//! Print an installable CustomResourceDefinition for a Rust type.
+//!
+//!     cargo run --example crdgen | kubectl apply -f -
+//!
+//! This is an example, rather than a binary of this crate, so that the
+//! library doesn't depend on serde_yaml.  In an operator's own crate,
+//! the same `main` in `src/bin/crdgen.rs` beside its types is run with
+//! `cargo run --bin crdgen`.
+
+extern crate kubernetes_api;
+#[macro_use]
+extern crate kubernetes_derive;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_yaml;
+
+use std::collections::HashMap;
+
+/// A scheduled job.
+#[derive(CustomResource, JsonSchema, Serialize, Deserialize, Debug, Clone)]
+#[kubernetes(
+    group = "stable.example.com",
+    version = "v1",
+    kind = "CronTab",
+    plural = "crontabs",
+    shortname = "ct",
+    namespaced,
+    status = "CronTabStatus",
+    schema
+)]
+#[serde(rename_all = "camelCase")]
+pub struct CronTabSpec {
+    /// Schedule, in cron format.
+    pub cron_spec: String,
+    pub image: String,
+    pub replicas: Option<i32>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub concurrency_policy: Option<ConcurrencyPolicy>,
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub enum ConcurrencyPolicy {
+    Allow,
+    Forbid,
+    Replace,
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CronTabStatus {
+    pub last_schedule_time: Option<kubernetes_api::Time>,
+}
+
+fn main() {
+    print!("{}", serde_yaml::to_string(&CronTab::crd()).unwrap());
+}
@@ -0,0 +1,367 @@
+//! A small kubectl, as an example of building a command line tool on
+//! the client.
+//!
+//!     cargo run --example krs -- get pods -n kube-system
+//!     cargo run --example krs -- describe deploy coredns -n kube-system
+//!     cargo run --example krs -- watch nodes
+//!     cargo run --example krs -- logs web-0 -c nginx -f
+//!     cargo run --example krs -- delete configmap stale
+//!
+//! Resources can be named as kubectl accepts them ("po", "deploy",
+//! "deployments.apps", "Pod"), and are resolved using discovery.
+
+extern crate failure;
+extern crate futures;
+extern crate kubernetes_api;
+extern crate kubernetes_client;
+extern crate kubernetes_holding;
+extern crate pretty_env_logger;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate tokio;
+
+use std::env;
+use std::process;
+
+use failure::{format_err, Error};
+use futures::prelude::*;
+use serde_json::Value;
+use tokio::runtime::Runtime;
+
+use kubernetes_api::core::v1::PodLogOptions;
+use kubernetes_api::meta::v1::{
+    strip_managed_fields, DeleteOptions, EventType, GetOptions, ListOptions,
+};
+use kubernetes_api::meta::GroupVersionResource;
+use kubernetes_client::selector::{fields, FieldSelector};
+use kubernetes_holding::client::restmapper::{RESTMapper, ResourceMapping};
+use kubernetes_holding::client::Client;
+
+const USAGE: &str = "\
+usage: krs [-n NAMESPACE | -A] [-l SELECTOR] [-o json|yaml] COMMAND
+
+commands:
+  get RESOURCE [NAME]
+  describe RESOURCE NAME
+  watch RESOURCE [NAME]
+  delete RESOURCE NAME
+  logs POD [-c CONTAINER] [-f]";
+
+const HEADER: [&str; 4] = ["NAMESPACE", "NAME", "STATUS", "CREATED"];
+
+#[derive(Debug, Default)]
+struct Args {
+    namespace: Option<String>,
+    all_namespaces: bool,
+    selector: String,
+    output: Option<String>,
+    container: Option<String>,
+    follow: bool,
+    command: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let mut args = Args::default();
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || {
+            argv.next()
+                .ok_or_else(|| format_err!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "-n" | "--namespace" => args.namespace = Some(value()?),
+            "-A" | "--all-namespaces" => args.all_namespaces = true,
+            "-l" | "--selector" => args.selector = value()?,
+            "-o" | "--output" => args.output = Some(value()?),
+            "-c" | "--container" => args.container = Some(value()?),
+            "-f" | "--follow" => args.follow = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') => return Err(format_err!("unknown option {}", arg)),
+            _ => args.command.push(arg),
+        }
+    }
+    Ok(args)
+}
+
+struct Krs {
+    client: Client,
+    mapper: RESTMapper,
+    rt: Runtime,
+    args: Args,
+}
+
+impl Krs {
+    /// The namespace to use for a resource, if it is namespaced.
+    fn namespace(&self, mapping: &ResourceMapping) -> Option<String> {
+        if !mapping.namespaced || self.args.all_namespaces {
+            return None;
+        }
+        Some(
+            self.args
+                .namespace
+                .as_deref()
+                .or_else(|| self.client.default_namespace())
+                .unwrap_or("default")
+                .to_string(),
+        )
+    }
+
+    fn lookup(&mut self, resource: &str) -> Result<ResourceMapping, Error> {
+        self.rt.block_on(self.mapper.lookup(resource))
+    }
+
+    /// Print objects as the `-o` option asks, or else as a table.
+    fn print(&self, objects: &[Value], header: bool) -> Result<(), Error> {
+        // Hidden, as kubectl does.
+        let mut objects = objects.to_vec();
+        objects.iter_mut().for_each(strip_managed_fields);
+        match self.args.output.as_deref() {
+            Some("json") => {
+                for o in &objects {
+                    println!("{}", serde_json::to_string_pretty(o)?);
+                }
+            }
+            Some("yaml") => {
+                for o in &objects {
+                    println!("{}", serde_yaml::to_string(o)?);
+                }
+            }
+            Some(other) => return Err(format_err!("unknown output format {:?}", other)),
+            None => {
+                if header {
+                    if self.args.all_namespaces {
+                        print!("{:<21}", HEADER[0]);
+                    }
+                    println!("{:<40} {:<12} {}", HEADER[1], HEADER[2], HEADER[3]);
+                }
+                for o in &objects {
+                    let meta = &o["metadata"];
+                    if self.args.all_namespaces {
+                        print!("{:<20} ", meta["namespace"].as_str().unwrap_or(""));
+                    }
+                    println!(
+                        "{:<40} {:<12} {}",
+                        meta["name"].as_str().unwrap_or(""),
+                        status(o),
+                        meta["creationTimestamp"].as_str().unwrap_or(""),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, resource: &str, name: Option<&str>) -> Result<(), Error> {
+        let mapping = self.lookup(resource)?;
+        let ns = self.namespace(&mapping);
+        let objects = match name {
+            Some(name) => {
+                let get =
+                    self.client
+                        .get(&mapping.gvr(), ns.as_deref(), name, GetOptions::default());
+                vec![self.rt.block_on(get)?]
+            }
+            None => {
+                let opts = ListOptions {
+                    label_selector: self.args.selector.clone(),
+                    ..Default::default()
+                };
+                let list = self.client.list(&mapping.gvr(), ns.as_deref(), opts);
+                let list: Value = self.rt.block_on(list)?;
+                list["items"].as_array().cloned().unwrap_or_default()
+            }
+        };
+        self.print(&objects, true)
+    }
+
+    fn describe(&mut self, resource: &str, name: &str) -> Result<(), Error> {
+        let mapping = self.lookup(resource)?;
+        let ns = self.namespace(&mapping);
+        let get = self
+            .client
+            .get(&mapping.gvr(), ns.as_deref(), name, GetOptions::default());
+        let object: Value = self.rt.block_on(get)?;
+        let meta = &object["metadata"];
+
+        println!("Name:         {}", name);
+        if let Some(ns) = meta["namespace"].as_str() {
+            println!("Namespace:    {}", ns);
+        }
+        println!("Kind:         {}", mapping.kind);
+        println!(
+            "Created:      {}",
+            meta["creationTimestamp"].as_str().unwrap_or("")
+        );
+        for (title, field) in &[("Labels:", "labels"), ("Annotations:", "annotations")] {
+            print!("{:<14}", title);
+            match meta[field].as_object() {
+                Some(m) if !m.is_empty() => {
+                    let pairs: Vec<String> = m
+                        .iter()
+                        .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or("")))
+                        .collect();
+                    println!("{}", pairs.join("\n              "));
+                }
+                _ => println!("<none>"),
+            }
+        }
+        for owner in meta["ownerReferences"].as_array().into_iter().flatten() {
+            println!(
+                "Controlled By: {}/{}",
+                owner["kind"].as_str().unwrap_or(""),
+                owner["name"].as_str().unwrap_or("")
+            );
+        }
+        for section in &["spec", "status"] {
+            if !object[section].is_null() {
+                println!("{}:", section);
+                for line in serde_yaml::to_string(&object[section])?.lines().skip(1) {
+                    println!("  {}", line);
+                }
+            }
+        }
+
+        let events = GroupVersionResource {
+            group: "",
+            version: "v1",
+            resource: "events",
+        };
+        let opts = ListOptions {
+            field_selector: FieldSelector::new()
+                .eq(
+                    fields::INVOLVED_OBJECT_UID,
+                    meta["uid"].as_str().unwrap_or(""),
+                )
+                .to_string(),
+            ..Default::default()
+        };
+        let list = self.client.list(&events, ns.as_deref(), opts);
+        let list: Value = self.rt.block_on(list)?;
+        println!("Events:");
+        let items = list["items"].as_array().cloned().unwrap_or_default();
+        if items.is_empty() {
+            println!("  <none>");
+        }
+        for e in items {
+            println!(
+                "  {:<8} {:<20} {}",
+                e["type"].as_str().unwrap_or(""),
+                e["reason"].as_str().unwrap_or(""),
+                e["message"].as_str().unwrap_or("")
+            );
+        }
+        Ok(())
+    }
+
+    fn watch(&mut self, resource: &str, name: Option<&str>) -> Result<(), Error> {
+        let mapping = self.lookup(resource)?;
+        let ns = self.namespace(&mapping);
+        let opts = ListOptions {
+            label_selector: self.args.selector.clone(),
+            ..Default::default()
+        };
+        let events: Box<dyn Stream<Item = _, Error = Error> + Send> = match name {
+            Some(name) => Box::new(self.client.watch(&mapping.gvr(), ns.as_deref(), name, opts)),
+            None => Box::new(self.client.watch_list(&mapping.gvr(), ns.as_deref(), opts)),
+        };
+        for event in events.wait() {
+            let event = event?;
+            if event.typ == EventType::Error {
+                return Err(format_err!("watch failed: {}", event.object));
+            }
+            if self.args.output.is_none() {
+                print!("{:<10} ", format!("{:?}", event.typ).to_uppercase());
+            }
+            self.print(&[event.object], false)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, resource: &str, name: &str) -> Result<(), Error> {
+        let mapping = self.lookup(resource)?;
+        let ns = self.namespace(&mapping);
+        let delete = self.client.delete(
+            &mapping.gvr(),
+            ns.as_deref(),
+            name,
+            DeleteOptions::default(),
+        );
+        self.rt.block_on(delete)?;
+        println!("{} {:?} deleted", mapping.singular, name);
+        Ok(())
+    }
+
+    fn logs(&mut self, pod: &str) -> Result<(), Error> {
+        let mapping = self.lookup("pods")?;
+        let ns = self.namespace(&mapping).unwrap_or_default();
+        let opts = PodLogOptions {
+            container: self.args.container.clone(),
+            follow: self.args.follow,
+            ..Default::default()
+        };
+        let lines = self.client.logs(&ns, pod, opts).for_each(|line| {
+            println!("{}", line);
+            Ok(())
+        });
+        self.rt.block_on(lines)
+    }
+}
+
+/// A one word summary of an object's status.
+fn status(o: &Value) -> String {
+    let status = &o["status"];
+    if let Some(phase) = status["phase"].as_str() {
+        return phase.to_string();
+    }
+    let ready = status["conditions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|c| c["type"] == "Ready" || c["type"] == "Available");
+    match ready {
+        Some(c) if c["status"] == "True" => c["type"].as_str().unwrap_or("").to_string(),
+        Some(c) => format!("Not{}", c["type"].as_str().unwrap_or("")),
+        None => String::new(),
+    }
+}
+
+fn main_() -> Result<(), Error> {
+    let args = parse_args()?;
+    let client = Client::new()?;
+    let mut krs = Krs {
+        mapper: RESTMapper::new(client.clone()),
+        client,
+        rt: Runtime::new()?,
+        args,
+    };
+    let command = krs.args.command.clone();
+    let command: Vec<&str> = command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["get", resource] => krs.get(resource, None),
+        ["get", resource, name] => krs.get(resource, Some(name)),
+        ["describe", resource, name] => krs.describe(resource, name),
+        ["watch", resource] => krs.watch(resource, None),
+        ["watch", resource, name] => krs.watch(resource, Some(name)),
+        ["delete", resource, name] => krs.delete(resource, name),
+        ["logs", pod] => krs.logs(pod),
+        _ => Err(format_err!("{}", USAGE)),
+    }
+}
+
+fn main() {
+    pretty_env_logger::init();
+    let status = match main_() {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            for c in e.iter_chain().skip(1) {
+                eprintln!(" Caused by {}", c);
+            }
+            1
+        }
+    };
+    process::exit(status);
+}
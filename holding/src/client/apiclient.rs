@@ -0,0 +1,278 @@
+//! Client operations behind an object-safe trait.
+//!
+//! `Client` is generic over its connector, and its methods are generic
+//! over the object type, so code that takes a `Client` ends up generic
+//! too.  `ApiClient` instead works with `DynamicObject` and returns
+//! boxed futures, so it can be used as a trait object:
+//!
+//! ```
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # use std::sync::Arc;
+//! # use futures::Future;
+//! # use kubernetes_api::meta::v1::GetOptions;
+//! # use kubernetes_api::meta::GroupVersionResource;
+//! # use kubernetes_api::unstructured::DynamicObject;
+//! use kubernetes_holding::client::apiclient::ApiClient;
+//! use kubernetes_holding::client::fake::FakeClient;
+//!
+//! struct Controller {
+//!     client: Arc<dyn ApiClient>,
+//! }
+//!
+//! # fn main() {
+//! let fake = FakeClient::new();
+//! let controller = Controller { client: Arc::new(fake.client().clone()) };
+//! let gvr = GroupVersionResource { group: "", version: "v1", resource: "namespaces" };
+//! let found = controller.client.get_opt(&gvr, None, "default", GetOptions::default());
+//! assert_eq!(found.wait().unwrap(), None);
+//! # }
+//! ```
+
+use failure::Error;
+use futures::{Future, Stream};
+use serde_json::Value;
+
+use api::meta::v1::{DeleteOptions, GetOptions, ListOptions, WatchEvent};
+use api::meta::GroupVersionResource;
+use api::unstructured::{DynamicList, DynamicObject};
+
+use super::{status_code, Client};
+
+pub type BoxFuture<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
+pub type BoxStream<T> = Box<dyn Stream<Item = T, Error = Error> + Send>;
+
+/// The operations of `Client`, on `DynamicObject`s.
+///
+/// Create and update use the namespace from the object's metadata,
+/// like the equivalent `Client` methods.
+pub trait ApiClient: Send + Sync {
+    fn get(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: GetOptions,
+    ) -> BoxFuture<DynamicObject>;
+
+    /// Like `get`, but returns `None` if the object does not exist.
+    fn get_opt(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: GetOptions,
+    ) -> BoxFuture<Option<DynamicObject>> {
+        Box::new(self.get(gvr, namespace, name, opts).then(|r| match r {
+            Ok(o) => Ok(Some(o)),
+            Err(ref e) if status_code(e) == Some(404) => Ok(None),
+            Err(e) => Err(e),
+        }))
+    }
+
+    fn list(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> BoxFuture<DynamicList>;
+
+    fn watch(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: ListOptions,
+    ) -> BoxStream<WatchEvent>;
+
+    fn watch_list(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> BoxStream<WatchEvent>;
+
+    fn create(
+        &self,
+        gvr: &GroupVersionResource,
+        value: &DynamicObject,
+        opts: GetOptions,
+    ) -> BoxFuture<DynamicObject>;
+
+    fn update(&self, gvr: &GroupVersionResource, value: &DynamicObject)
+        -> BoxFuture<DynamicObject>;
+
+    fn patch(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        patch_type: &str,
+        value: &Value,
+    ) -> BoxFuture<DynamicObject>;
+
+    fn delete(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: DeleteOptions,
+    ) -> BoxFuture<()>;
+
+    fn delete_collection(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> BoxFuture<()>;
+}
+
+impl ApiClient for Client {
+    fn get(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: GetOptions,
+    ) -> BoxFuture<DynamicObject> {
+        Box::new(Client::get(self, gvr, namespace, name, opts))
+    }
+
+    fn list(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> BoxFuture<DynamicList> {
+        Box::new(Client::list(self, gvr, namespace, opts))
+    }
+
+    fn watch(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: ListOptions,
+    ) -> BoxStream<WatchEvent> {
+        Box::new(Client::watch(self, gvr, namespace, name, opts))
+    }
+
+    fn watch_list(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> BoxStream<WatchEvent> {
+        Box::new(Client::watch_list(self, gvr, namespace, opts))
+    }
+
+    fn create(
+        &self,
+        gvr: &GroupVersionResource,
+        value: &DynamicObject,
+        opts: GetOptions,
+    ) -> BoxFuture<DynamicObject> {
+        Box::new(Client::create(self, gvr, value, opts))
+    }
+
+    fn update(
+        &self,
+        gvr: &GroupVersionResource,
+        value: &DynamicObject,
+    ) -> BoxFuture<DynamicObject> {
+        Box::new(Client::update(self, gvr, value))
+    }
+
+    fn patch(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        patch_type: &str,
+        value: &Value,
+    ) -> BoxFuture<DynamicObject> {
+        Box::new(Client::patch(self, gvr, namespace, name, patch_type, value))
+    }
+
+    fn delete(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: DeleteOptions,
+    ) -> BoxFuture<()> {
+        Box::new(Client::delete(self, gvr, namespace, name, opts))
+    }
+
+    fn delete_collection(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> BoxFuture<()> {
+        Box::new(Client::delete_collection(self, gvr, namespace, opts))
+    }
+}
+
+#[test]
+fn test_api_client() {
+    use std::sync::Arc;
+
+    use api::meta::v1::{EventType, List};
+    use api::MERGE_PATCH;
+
+    use super::fake::FakeClient;
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let fake = FakeClient::new();
+    let client: Arc<dyn ApiClient> = Arc::new(fake.client().clone());
+
+    let mut cm = DynamicObject::new("v1", "ConfigMap");
+    cm.metadata.name = Some("a".to_string());
+    cm.metadata.namespace = Some("ns".to_string());
+    let created = client
+        .create(&gvr, &cm, GetOptions::default())
+        .wait()
+        .unwrap();
+    assert_eq!(created.metadata.resource_version, Some("1".to_string()));
+
+    let mut events = client
+        .watch_list(&gvr, Some("ns"), ListOptions::default())
+        .wait();
+    assert_eq!(events.next().unwrap().unwrap().typ, EventType::Added);
+
+    let patched = client
+        .patch(
+            &gvr,
+            Some("ns"),
+            "a",
+            MERGE_PATCH,
+            &json!({"data": {"k": "v"}}),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(patched.data["data"]["k"], "v");
+    assert_eq!(events.next().unwrap().unwrap().typ, EventType::Modified);
+
+    let list = client
+        .list(&gvr, Some("ns"), ListOptions::default())
+        .wait()
+        .unwrap();
+    assert_eq!(list.items().len(), 1);
+
+    client
+        .delete(&gvr, Some("ns"), "a", DeleteOptions::default())
+        .wait()
+        .unwrap();
+    assert_eq!(events.next().unwrap().unwrap().typ, EventType::Deleted);
+    let gone = client
+        .get_opt(&gvr, Some("ns"), "a", GetOptions::default())
+        .wait()
+        .unwrap();
+    assert_eq!(gone, None);
+}
@@ -0,0 +1,571 @@
+//! Create-or-update, server-side apply, and read-modify-write.
+
+use std::fmt;
+
+use failure::Error;
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+use std::sync::{Arc, Mutex};
+
+use api::meta::v1::{CauseType, ManagedFieldsOperation, Metadata};
+use api::meta::GroupVersionResource;
+use api::APPLY_PATCH;
+use k8sclient::error::ApiError;
+
+use super::{do_request, hyper_uri, require_name, status_code, Client, NoOptions};
+
+/// How many times to retry an update that lost a race with another
+/// writer.
+pub(crate) const MAX_CONFLICTS: usize = 5;
+
+impl Client {
+    /// Make `value` exist in exactly this shape: create it, or replace
+    /// the existing object if it already exists.
+    ///
+    /// If `value` carries a `resourceVersion`, the replace only succeeds
+    /// against that version.  Otherwise the current version is fetched
+    /// and the replace retried if it races with another writer.
+    pub fn apply<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        value: &T,
+    ) -> impl Future<Item = T, Error = Error> + Send
+    where
+        T: Metadata + Serialize + DeserializeOwned + Send + 'static,
+    {
+        let update = || -> Result<_, Error> {
+            let metadata = value.metadata();
+            let namespace = metadata.namespace.as_deref();
+            let name = require_name(&metadata)?;
+            let url = self.url(gvr, namespace, Some(name), NoOptions {})?;
+            let pinned = metadata.resource_version.is_some();
+            Ok((url, pinned, serde_json::to_value(value)?))
+        }();
+
+        let client = self.clone();
+        self.create(gvr, value, Default::default())
+            .or_else(move |e| {
+                if status_code(&e) != Some(409) {
+                    return Either::A(future::err(e));
+                }
+                Either::B(
+                    future::result(update)
+                        .and_then(move |(url, pinned, json)| replace(client, url, pinned, json)),
+                )
+            })
+    }
+}
+
+/// Options for `server_side_apply`.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyOptions {
+    /// Who is applying.  The fields applied are owned by this manager,
+    /// and fields it applied before but leaves out now are removed.
+    /// Required.
+    pub field_manager: String,
+    /// Take fields owned by other managers, rather than failing with
+    /// `ApplyConflict`.
+    #[serde(skip_serializing_if = "is_false")]
+    pub force: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+impl ApplyOptions {
+    pub fn new<S: Into<String>>(field_manager: S) -> Self {
+        ApplyOptions {
+            field_manager: field_manager.into(),
+            force: false,
+        }
+    }
+
+    /// Apply even if it takes fields from other managers.
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+}
+
+/// A server-side apply would have changed fields owned by other field
+/// managers.  Retry with `ApplyOptions::force` to take them, or leave
+/// them out of what is applied.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub struct ApplyConflict {
+    pub error: ApiError,
+    pub conflicts: Vec<FieldConflict>,
+}
+
+/// A field some other manager owns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldConflict {
+    /// The field, as a path like `.spec.replicas`.
+    pub field: String,
+    /// The manager that owns it.
+    pub manager: String,
+    /// How `manager` set it.
+    pub operation: ManagedFieldsOperation,
+    /// The subresource `manager` set it through, if any.
+    pub subresource: Option<String>,
+    /// The API version `manager` used, for updates.
+    pub api_version: Option<String>,
+}
+
+impl fmt::Display for ApplyConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Apply conflicted with other field managers: {}",
+            self.error
+        )
+    }
+}
+
+impl ApplyConflict {
+    /// The conflicts in `error`, if it is a server-side apply conflict.
+    pub fn from_api_error(error: &ApiError) -> Option<Self> {
+        let causes = &error.details.as_ref()?.causes;
+        let conflicts: Vec<FieldConflict> = causes
+            .iter()
+            .filter(|c| c.reason == Some(CauseType::FieldManagerConflict))
+            .map(|c| FieldConflict::parse(&c.field, c.message.as_deref().unwrap_or_default()))
+            .collect();
+        if error.code != 409 || conflicts.is_empty() {
+            return None;
+        }
+        Some(ApplyConflict {
+            error: error.clone(),
+            conflicts,
+        })
+    }
+}
+
+impl FieldConflict {
+    /// Parse a conflict cause's message, which the apiserver formats as
+    /// `conflict with "manager"`, then ` with subresource "status"` if
+    /// set through a subresource, then ` using apps/v1 at TIME` if set
+    /// by an update rather than an apply.  A message that doesn't parse
+    /// is kept whole as the manager.
+    fn parse(field: &str, message: &str) -> Self {
+        let mut conflict = FieldConflict {
+            field: field.to_string(),
+            manager: message.to_string(),
+            operation: ManagedFieldsOperation::Apply,
+            subresource: None,
+            api_version: None,
+        };
+        let rest = match message
+            .strip_prefix("conflict with ")
+            .and_then(|rest| quoted(rest))
+        {
+            Some((manager, rest)) => {
+                conflict.manager = manager;
+                rest
+            }
+            None => return conflict,
+        };
+        let rest = match rest
+            .strip_prefix(" with subresource ")
+            .and_then(|rest| quoted(rest))
+        {
+            Some((subresource, rest)) => {
+                conflict.subresource = Some(subresource);
+                rest
+            }
+            None => rest,
+        };
+        if let Some(using) = rest.strip_prefix(" using ") {
+            conflict.operation = ManagedFieldsOperation::Update;
+            let version = using.split(" at ").next().unwrap_or_default();
+            conflict.api_version = Some(version.to_string());
+        }
+        conflict
+    }
+}
+
+/// Split a leading quoted string (as Go's `%q` formats it) off `s`.
+fn quoted(s: &str) -> Option<(String, &str)> {
+    if !s.starts_with('"') {
+        return None;
+    }
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => {
+                let unquoted = serde_json::from_str(&s[..=i]).ok()?;
+                return Some((unquoted, &s[i + 1..]));
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn replace<T>(
+    client: Client,
+    url: ::url::Url,
+    pinned: bool,
+    json: Value,
+) -> impl Future<Item = T, Error = Error> + Send
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    future::loop_fn(0, move |attempt| {
+        let put_client = Arc::clone(&client.client);
+        let timeout = client.timeout;
+        let url2 = url.clone();
+        let mut json = json.clone();
+
+        let current = if pinned {
+            Either::A(future::ok(None))
+        } else {
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(hyper_uri(url.clone()))
+                .body(Body::empty())
+                .map_err(|e| e.into());
+            Either::B(
+                do_request(Arc::clone(&client.client), client.timeout, req)
+                    .map(|cur: Value| Some(cur["metadata"]["resourceVersion"].clone())),
+            )
+        };
+
+        current
+            .and_then(move |rv| {
+                if let Some(rv) = rv {
+                    json["metadata"]["resourceVersion"] = rv;
+                }
+                let req = serde_json::to_vec(&json)
+                    .map_err(Error::from)
+                    .and_then(|body| {
+                        Request::builder()
+                            .method(Method::PUT)
+                            .uri(hyper_uri(url2))
+                            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                            .body(Body::from(body))
+                            .map_err(|e| e.into())
+                    });
+                do_request(put_client, timeout, req)
+            })
+            .then(move |r| match r {
+                Ok(o) => Ok(Loop::Break(o)),
+                Err(ref e)
+                    if !pinned && status_code(e) == Some(409) && attempt + 1 < MAX_CONFLICTS =>
+                {
+                    debug!("Conflict replacing object, retrying");
+                    Ok(Loop::Continue(attempt + 1))
+                }
+                Err(e) => Err(e),
+            })
+    })
+}
+
+impl Client {
+    /// Server-side apply `value` to the named object, creating it if
+    /// need be.  `value` needs its `apiVersion` and `kind`, and should
+    /// only hold the fields the caller manages.
+    ///
+    /// Fails with `ApplyConflict` if the apply would change fields
+    /// another manager owns, unless `opts.force` is set.
+    pub fn server_side_apply<T, U>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        value: &T,
+        opts: ApplyOptions,
+    ) -> impl Future<Item = U, Error = Error> + Send
+    where
+        T: Serialize,
+        U: DeserializeOwned + Send + 'static,
+    {
+        let req = || -> Result<_, Error> {
+            if opts.field_manager.is_empty() {
+                return Err(format_err!("Server-side apply needs a field manager"));
+            }
+            Request::builder()
+                .method(Method::PATCH)
+                .uri(hyper_uri(self.url(gvr, namespace, Some(name), opts)?))
+                .header(CONTENT_TYPE, APPLY_PATCH)
+                .body(Body::from(serde_json::to_vec(value)?))
+                .map_err(|e| e.into())
+        }();
+        do_request(Arc::clone(&self.client), self.timeout, req).map_err(|e| {
+            match ApiError::from_error(&e).and_then(ApplyConflict::from_api_error) {
+                Some(conflict) => conflict.into(),
+                None => e,
+            }
+        })
+    }
+
+    /// Read-modify-write the named object: get it, change it with
+    /// `mutate`, and update it.  If the update conflicts with another
+    /// writer, start again from a fresh read, up to a few times.
+    ///
+    /// Returns the updated object.  `mutate` may be called more than
+    /// once, so should only depend on the object it is given.
+    pub fn retry_on_conflict<T, F>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        mutate: F,
+    ) -> impl Future<Item = T, Error = Error> + Send
+    where
+        T: Metadata + Serialize + DeserializeOwned + Send + 'static,
+        F: FnMut(&mut T) -> Result<(), Error> + Send + 'static,
+    {
+        let url = self.url(gvr, namespace, Some(name), NoOptions {});
+        let client = self.clone();
+        let mutate = Arc::new(Mutex::new(mutate));
+        future::result(url).and_then(move |url| {
+            future::loop_fn(0, move |attempt| {
+                let (put_client, timeout) = (Arc::clone(&client.client), client.timeout);
+                let put_url = url.clone();
+                let mutate = Arc::clone(&mutate);
+                let req = Request::builder()
+                    .method(Method::GET)
+                    .uri(hyper_uri(url.clone()))
+                    .body(Body::empty())
+                    .map_err(|e| e.into());
+                do_request(Arc::clone(&client.client), client.timeout, req)
+                    .and_then(move |mut object: T| {
+                        (mutate.lock().unwrap())(&mut object)?;
+                        let req = Request::builder()
+                            .method(Method::PUT)
+                            .uri(hyper_uri(put_url))
+                            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                            .body(Body::from(serde_json::to_vec(&object)?))
+                            .map_err(|e| e.into());
+                        Ok(do_request(put_client, timeout, req))
+                    })
+                    .flatten()
+                    .then(move |r| match r {
+                        Ok(o) => Ok(Loop::Break(o)),
+                        Err(ref e)
+                            if status_code(e) == Some(409) && attempt + 1 < MAX_CONFLICTS =>
+                        {
+                            debug!("Conflict updating object, retrying");
+                            Ok(Loop::Continue(attempt + 1))
+                        }
+                        Err(e) => Err(e),
+                    })
+            })
+        })
+    }
+}
+
+#[test]
+fn test_apply() {
+    use api::core::v1::{ConfigMap, TypedResource};
+    use api::meta::v1::StatusReason;
+
+    use super::fake::FakeClient;
+
+    let gvr = ConfigMap::GROUP_VERSION_RESOURCE;
+    let client = FakeClient::new();
+    let server = client.server();
+    let config_map = |data: &[(&str, &str)]| {
+        let mut cm = ConfigMap::new("settings");
+        cm.metadata.namespace = Some("ns".to_string());
+        for (k, v) in data {
+            cm.data.insert(k.to_string(), v.to_string());
+        }
+        cm
+    };
+    let verbs = || -> Vec<String> { server.actions().into_iter().map(|a| a.verb).collect() };
+
+    // Created.
+    let created = client
+        .apply(&gvr, &config_map(&[("a", "1")]))
+        .wait()
+        .unwrap();
+    assert_eq!(created.data["a"], "1");
+    assert_eq!(verbs(), vec!["create"]);
+
+    // Replaced as a whole once it exists.
+    let replaced = client
+        .apply(&gvr, &config_map(&[("b", "2")]))
+        .wait()
+        .unwrap();
+    assert_eq!(replaced.metadata.uid, created.metadata.uid);
+    assert_eq!(replaced.data.keys().collect::<Vec<_>>(), vec!["b"]);
+    assert_eq!(verbs()[1..], ["create", "get", "update"]);
+
+    // Another writer gets in between the get and the replace.
+    server.fail_next(
+        "update",
+        "configmaps",
+        ApiError::new(StatusReason::Conflict, "the object has been modified"),
+    );
+    let replaced = client
+        .apply(&gvr, &config_map(&[("b", "3")]))
+        .wait()
+        .unwrap();
+    assert_eq!(replaced.data["b"], "3");
+    assert_eq!(verbs()[4..], ["create", "get", "update", "get", "update"]);
+
+    // Unless pinned to the version it was read at.
+    let mut stale = config_map(&[("b", "4")]);
+    stale.metadata.resource_version = created.metadata.resource_version;
+    let err = client.apply(&gvr, &stale).wait().unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_conflict());
+    assert_eq!(verbs()[9..], ["create", "update"]);
+
+    // Other failures to create are just that.
+    server.fail_next(
+        "create",
+        "configmaps",
+        ApiError::new(StatusReason::Forbidden, "configmaps is forbidden"),
+    );
+    let err = client.apply(&gvr, &config_map(&[])).wait().unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_forbidden());
+    assert_eq!(verbs().len(), 12);
+    assert_eq!(
+        server.get(&gvr, Some("ns"), "settings").unwrap()["data"]["b"],
+        "3"
+    );
+}
+
+#[test]
+fn test_retry_on_conflict() {
+    use api::meta::v1::StatusReason;
+    use k8sclient::error::ApiError;
+
+    use super::fake::FakeClient;
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let client = FakeClient::new();
+    let server = client.server().clone();
+    server
+        .insert(
+            &gvr,
+            &json!({"metadata": {"name": "cm", "namespace": "ns"}, "data": {"n": "0"}}),
+        )
+        .unwrap();
+    let conflict = || ApiError::new(StatusReason::Conflict, "the object has been modified");
+
+    // Another writer gets in first.
+    let mut calls = 0;
+    let updated: Value = client
+        .retry_on_conflict(&gvr, Some("ns"), "cm", move |cm: &mut Value| {
+            calls += 1;
+            if calls == 1 {
+                server.fail_next("update", "configmaps", conflict());
+            }
+            cm["data"]["n"] = calls.to_string().into();
+            Ok(())
+        })
+        .wait()
+        .unwrap();
+    assert_eq!(updated["data"]["n"], "2");
+
+    // Other writers keep getting in first.
+    for _ in 0..MAX_CONFLICTS {
+        client
+            .server()
+            .fail_next("update", "configmaps", conflict());
+    }
+    let err = client
+        .retry_on_conflict(&gvr, Some("ns"), "cm", |_: &mut Value| Ok(()))
+        .wait()
+        .unwrap_err();
+    assert_eq!(status_code(&err), Some(409));
+
+    // Errors from `mutate` are returned as is.
+    let err = client
+        .retry_on_conflict(&gvr, Some("ns"), "cm", |_: &mut Value| {
+            Err(format_err!("nope"))
+        })
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "nope");
+}
+
+#[test]
+fn test_server_side_apply() {
+    use api::meta::v1::{StatusCause, StatusDetails, StatusReason};
+    use serde_urlencoded;
+
+    use super::fake::FakeClient;
+
+    let gvr = GroupVersionResource {
+        group: "apps",
+        version: "v1",
+        resource: "deployments",
+    };
+    let client = FakeClient::new();
+    let web = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": {"name": "web", "namespace": "ns"},
+        "spec": {"replicas": 3},
+    });
+    let opts = ApplyOptions::new("tester");
+    let created: Value = client
+        .server_side_apply(&gvr, Some("ns"), "web", &web, opts.clone())
+        .wait()
+        .unwrap();
+    assert_eq!(created["spec"]["replicas"], 3);
+
+    let cause = |message: &str| StatusCause {
+        field: ".spec.replicas".to_string(),
+        message: Some(message.to_string()),
+        reason: Some(CauseType::FieldManagerConflict),
+    };
+    let mut conflict = ApiError::new(StatusReason::Conflict, "Apply failed with 2 conflicts: ...");
+    conflict.details = Some(StatusDetails {
+        causes: vec![
+            cause(r#"conflict with "kubectl" using apps/v1 at 2020-01-01T00:00:00Z"#),
+            cause(r#"conflict with "hpa \"x\"" with subresource "scale""#),
+        ],
+        ..Default::default()
+    });
+    client.server().fail_next("patch", "deployments", conflict);
+    let err = client
+        .server_side_apply::<_, Value>(&gvr, Some("ns"), "web", &web, opts.clone())
+        .wait()
+        .unwrap_err();
+    let conflict = err.downcast_ref::<ApplyConflict>().unwrap();
+    assert_eq!(
+        conflict.conflicts,
+        vec![
+            FieldConflict {
+                field: ".spec.replicas".to_string(),
+                manager: "kubectl".to_string(),
+                operation: ManagedFieldsOperation::Update,
+                subresource: None,
+                api_version: Some("apps/v1".to_string()),
+            },
+            FieldConflict {
+                field: ".spec.replicas".to_string(),
+                manager: r#"hpa "x""#.to_string(),
+                operation: ManagedFieldsOperation::Apply,
+                subresource: Some("scale".to_string()),
+                api_version: None,
+            },
+        ]
+    );
+
+    assert_eq!(
+        serde_urlencoded::to_string(opts.force()).unwrap(),
+        "fieldManager=tester&force=true"
+    );
+    assert!(client
+        .server_side_apply::<_, Value>(&gvr, Some("ns"), "web", &web, ApplyOptions::default())
+        .wait()
+        .is_err());
+}
@@ -0,0 +1,77 @@
+//! What finally sends requests to the apiserver.
+//!
+//! `Client` talks to the apiserver through a `Backend`, which is by
+//! default a hyper client with the TLS configuration from the
+//! kubeconfig.  Another HTTP implementation can be used by implementing
+//! `Backend` for it, and passing it to `ClientBuilder::build_with_backend`;
+//! the client's middleware, retries, compression and timeouts still
+//! apply.
+//!
+//! ```
+//! # extern crate futures;
+//! # extern crate hyper;
+//! # extern crate kubernetes_holding;
+//! # use std::sync::Arc;
+//! use futures::{future, Future};
+//! use hyper::{Body, Request, Response, StatusCode};
+//! use kubernetes_holding::client::backend::Backend;
+//! use kubernetes_holding::client::middleware::ResponseFuture;
+//! use kubernetes_holding::client::Client;
+//!
+//! /// Answers everything with 503.
+//! #[derive(Debug)]
+//! struct Unavailable;
+//!
+//! impl Backend for Unavailable {
+//!     fn call(&self, _req: Request<Body>) -> ResponseFuture {
+//!         let mut res = Response::new(Body::empty());
+//!         *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+//!         Box::new(future::ok(res))
+//!     }
+//! }
+//!
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::builder()
+//!     .config(Default::default())
+//!     .build_with_backend(Arc::new(Unavailable))?;
+//! assert!(client.server_version().wait().is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use failure::Error;
+use futures::Future;
+use hyper::{self, Body, Request};
+
+use super::middleware::ResponseFuture;
+
+pub trait Backend: fmt::Debug + Send + Sync {
+    /// Send `req`, which has an absolute URI.
+    ///
+    /// The response body should be streamed as it arrives, rather than
+    /// buffered, as watches never finish.
+    fn call(&self, req: Request<Body>) -> ResponseFuture;
+
+    /// Whether `err` (returned by `call`) means the request was never
+    /// sent, so is safe to retry whatever it was.  By default, no errors
+    /// are.
+    fn is_connect_error(&self, _err: &Error) -> bool {
+        false
+    }
+}
+
+impl<C> Backend for hyper::Client<C>
+where
+    C: hyper::client::connect::Connect + 'static,
+{
+    fn call(&self, req: Request<Body>) -> ResponseFuture {
+        Box::new(self.request(req).from_err())
+    }
+
+    fn is_connect_error(&self, err: &Error) -> bool {
+        err.downcast_ref::<hyper::Error>()
+            .is_some_and(hyper::Error::is_connect)
+    }
+}
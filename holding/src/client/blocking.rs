@@ -0,0 +1,264 @@
+//! A synchronous client, for scripts and command line tools.
+//!
+//! `blocking::Client` runs an async `Client` on a runtime of its own,
+//! and waits for each result.  It must not be used from within another
+//! tokio runtime.
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # extern crate serde_json;
+//! use kubernetes_api::meta::v1::GetOptions;
+//! use kubernetes_api::meta::GroupVersionResource;
+//! use kubernetes_holding::client::blocking;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = blocking::Client::new()?;
+//! let gvr = GroupVersionResource { group: "", version: "v1", resource: "configmaps" };
+//! let cm: serde_json::Value = client.get(&gvr, Some("kube-system"), "kubeadm-config", GetOptions::default())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use failure::Error;
+use futures::sync::{mpsc, oneshot};
+use futures::{Future, Sink, Stream};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use tokio::runtime::{self, Runtime, TaskExecutor};
+
+use api::meta::v1::{DeleteOptions, GetOptions, ListOptions, Metadata, WatchEvent};
+use api::meta::GroupVersionResource;
+
+/// A synchronous `Client`.  Clones share the same runtime.
+#[derive(Clone)]
+pub struct Client {
+    client: super::Client,
+    executor: TaskExecutor,
+    // Stops the runtime once the last clone is dropped.
+    _runtime: Arc<Runtime>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+impl Client {
+    /// A client for the current context of the default kubeconfig.
+    pub fn new() -> Result<Self, Error> {
+        Self::from_async(super::Client::new()?)
+    }
+
+    /// Run `client` on a new runtime.
+    pub fn from_async(client: super::Client) -> Result<Self, Error> {
+        let runtime = runtime::Builder::new()
+            .core_threads(1)
+            .name_prefix("kubernetes-blocking-")
+            .build()?;
+        Ok(Client {
+            client,
+            executor: runtime.executor(),
+            _runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The underlying async client.
+    pub fn async_client(&self) -> &super::Client {
+        &self.client
+    }
+
+    /// Run `f` on the client's runtime, and wait for it.
+    pub fn block_on<F>(&self, f: F) -> Result<F::Item, F::Error>
+    where
+        F: Future + Send + 'static,
+        F::Item: Send + 'static,
+        F::Error: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.executor.spawn(f.then(move |r| {
+            let _ = tx.send(r);
+            Ok(())
+        }));
+        rx.wait()
+            .expect("blocking client runtime stopped while running a request")
+    }
+
+    pub fn get<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: GetOptions,
+    ) -> Result<T, Error>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.block_on(self.client.get(gvr, namespace, name, opts))
+    }
+
+    /// Like `get`, but returns `None` if the object does not exist.
+    pub fn get_opt<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: GetOptions,
+    ) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.block_on(self.client.get_opt(gvr, namespace, name, opts))
+    }
+
+    pub fn list<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> Result<T, Error>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.block_on(self.client.list(gvr, namespace, opts))
+    }
+
+    pub fn create<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        value: &T,
+        opts: GetOptions,
+    ) -> Result<T, Error>
+    where
+        T: Metadata + Serialize + DeserializeOwned + Send + 'static,
+    {
+        self.block_on(self.client.create(gvr, value, opts))
+    }
+
+    pub fn update<T>(&self, gvr: &GroupVersionResource, value: &T) -> Result<T, Error>
+    where
+        T: Metadata + Serialize + DeserializeOwned + Send + 'static,
+    {
+        self.block_on(self.client.update(gvr, value))
+    }
+
+    pub fn patch<T, U>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        patch_type: &str,
+        value: &T,
+    ) -> Result<U, Error>
+    where
+        T: Serialize,
+        U: DeserializeOwned + Send + 'static,
+    {
+        // The async client's future borrows `value`'s type.
+        let value = serde_json::to_value(value)?;
+        self.block_on(self.client.patch(gvr, namespace, name, patch_type, &value))
+    }
+
+    pub fn delete(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: DeleteOptions,
+    ) -> Result<(), Error> {
+        self.block_on(self.client.delete(gvr, namespace, name, opts))
+    }
+
+    pub fn delete_collection(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> Result<(), Error> {
+        self.block_on(self.client.delete_collection(gvr, namespace, opts))
+    }
+
+    /// Watch events, as they arrive.  The watch stops when the iterator
+    /// is dropped.
+    pub fn watch_list(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> impl Iterator<Item = Result<WatchEvent, Error>> {
+        self.iterate(self.client.watch_list(gvr, namespace, opts))
+    }
+
+    /// Watch a single object.  The watch stops when the iterator is
+    /// dropped.
+    pub fn watch(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: ListOptions,
+    ) -> impl Iterator<Item = Result<WatchEvent, Error>> {
+        self.iterate(self.client.watch(gvr, namespace, name, opts))
+    }
+
+    /// Run `stream` on the client's runtime, yielding its items.
+    fn iterate<S, T>(&self, stream: S) -> impl Iterator<Item = Result<T, Error>>
+    where
+        S: Stream<Item = T, Error = Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(0);
+        self.executor.spawn(
+            tx.sink_map_err(|_| ())
+                .send_all(stream.then(Ok::<_, ()>))
+                .map(|_| ()),
+        );
+        // Never fails: the error type is ().
+        rx.wait().filter_map(Result::ok)
+    }
+}
+
+#[test]
+fn test_blocking() {
+    use serde_json::Value;
+
+    use super::fake::FakeClient;
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let fake = FakeClient::new();
+    let client = Client::from_async(fake.client().clone()).unwrap();
+
+    let cm = json!({"metadata": {"name": "a", "namespace": "ns"}});
+    let _: Value = client.create(&gvr, &cm, GetOptions::default()).unwrap();
+    let mut events = client.watch_list(&gvr, Some("ns"), ListOptions::default());
+    let event = events.next().unwrap().unwrap();
+    assert_eq!(event.object["metadata"]["name"], "a");
+
+    let got: Value = client
+        .get(&gvr, Some("ns"), "a", GetOptions::default())
+        .unwrap();
+    assert_eq!(got["metadata"]["resourceVersion"], "1");
+    client
+        .clone()
+        .delete(&gvr, Some("ns"), "a", DeleteOptions::default())
+        .unwrap();
+    let gone: Option<Value> = client
+        .get_opt(&gvr, Some("ns"), "a", GetOptions::default())
+        .unwrap();
+    assert_eq!(gone, None);
+    assert!(events.next().unwrap().is_ok());
+
+    fake.server().close_watches();
+    assert!(events.next().is_none());
+}
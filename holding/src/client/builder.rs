@@ -0,0 +1,476 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use failure::Error;
+use hyper;
+use hyper::client::connect::dns::Resolve;
+use hyper::client::HttpConnector;
+use hyper::header::HeaderValue;
+
+use super::backend::Backend;
+use super::config::ConfigContext;
+use super::connector::ServerCertVerifier;
+use super::dns::{DnsResolver, IpPreference, Resolver, ThreadPoolResolver};
+use super::metrics::MetricsRecorder;
+use super::middleware::Middleware;
+use super::ratelimit::RateLimiter;
+use super::retry::RetryPolicy;
+use super::trace::{Instrument, Propagator};
+use super::transport::{Transport, UserAgent, DEFAULT_USER_AGENT};
+use super::warning::{LogWarnings, WarningHandler, Warnings};
+use super::Client;
+
+/// How many blocking DNS lookups `build` allows at once, by default.
+pub const DEFAULT_DNS_THREADS: usize = 4;
+
+/// Configures and creates a `Client`.
+///
+/// ```no_run
+/// # extern crate kubernetes_holding;
+/// # use kubernetes_holding::client::builder::ClientBuilder;
+/// # use kubernetes_holding::client::retry::RetryPolicy;
+/// # fn main() -> Result<(), failure::Error> {
+/// let client = ClientBuilder::new()
+///     .retry(RetryPolicy::default())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct ClientBuilder {
+    config: Option<ConfigContext>,
+    retry: Option<RetryPolicy>,
+    rate_limit: Option<(f32, u32)>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    disable_compression: bool,
+    max_line_length: Option<usize>,
+    max_body_size: Option<usize>,
+    #[cfg_attr(not(any(feature = "tls", feature = "rustls")), allow(dead_code))]
+    http1_only: bool,
+    #[cfg_attr(not(any(feature = "tls", feature = "rustls")), allow(dead_code))]
+    cert_verifier: Option<Arc<dyn ServerCertVerifier>>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Option<Duration>>,
+    tcp_keepalive: Option<Duration>,
+    dns_threads: Option<usize>,
+    resolver: Option<Arc<dyn Resolver>>,
+    resolve_timeout: Option<Duration>,
+    ip_preference: IpPreference,
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+    propagator: Option<Arc<dyn Propagator>>,
+    warning_handler: Option<Arc<dyn WarningHandler>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    /// HTTP/1.1-only, for exec, when `build` might use HTTP/2.
+    upgrade_backend: Option<Arc<dyn Backend>>,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Use this config, rather than the current context of the
+    /// default kubeconfig.
+    pub fn config(mut self, config: ConfigContext) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Retry failed idempotent requests. By default requests are not
+    /// retried.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Limit requests to `qps` per second on average, with bursts of up
+    /// to `burst`, as client-go's `QPS` and `Burst`. By default requests
+    /// are not rate limited.
+    ///
+    /// The limit is shared by all clones of the client.
+    pub fn rate_limit(mut self, qps: f32, burst: u32) -> Self {
+        self.rate_limit = Some((qps, burst));
+        self
+    }
+
+    /// Fail requests that take longer than `timeout`, with
+    /// `wait::TimeoutError`. This includes any retries, but not
+    /// watches. By default there is no timeout.
+    ///
+    /// `Client::with_timeout` overrides this.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Identify this application in the `User-Agent` header, ahead of
+    /// this library, eg `myoperator/1.2` gives
+    /// `myoperator/1.2 kubernetes-rs/0.2.0`.
+    pub fn user_agent<S: Into<String>>(mut self, product: S) -> Self {
+        self.user_agent = Some(product.into());
+        self
+    }
+
+    /// Don't ask for gzip-compressed responses. By default compression
+    /// is requested for everything except watches; it saves a lot of
+    /// time fetching large lists over slow links, at some cost in CPU.
+    pub fn disable_compression(mut self) -> Self {
+        self.disable_compression = true;
+        self
+    }
+
+    /// Fail watches and log streams with `watch::LineTooLong` when a
+    /// line is longer than `max` bytes, rather than buffering it
+    /// without limit.  The default is `watch::DEFAULT_MAX_LINE_LENGTH`.
+    pub fn max_line_length(mut self, max: usize) -> Self {
+        self.max_line_length = Some(max);
+        self
+    }
+
+    /// Fail requests with `BodyTooLarge` when the response body is
+    /// longer than `max` bytes (after decompression), rather than
+    /// reading it all into memory.  The default is
+    /// `DEFAULT_MAX_BODY_SIZE`; lists of many large objects may need
+    /// more, or paging.
+    pub fn max_body_size(mut self, max: usize) -> Self {
+        self.max_body_size = Some(max);
+        self
+    }
+
+    /// Don't offer HTTP/2 when connecting over TLS. By default HTTP/2 is
+    /// used when the server supports it, and multiplexes concurrent
+    /// requests and watches over a single connection.
+    ///
+    /// Only applies to `build` and `build_from_http`.  Exec doesn't
+    /// need it: those requests always get an HTTP/1.1 connection of
+    /// their own.
+    pub fn http1_only(mut self) -> Self {
+        self.http1_only = true;
+        self
+    }
+
+    /// Check the apiserver's certificate with `verifier`, as well as
+    /// (or, if it `replaces_default`, instead of) verifying it against
+    /// the cluster's CA.  See the `connector` module.
+    ///
+    /// Only applies to `build` and `build_from_http`, when they connect
+    /// over TLS (with the `tls` or `rustls` feature).
+    pub fn server_cert_verifier(mut self, verifier: Arc<dyn ServerCertVerifier>) -> Self {
+        self.cert_verifier = Some(verifier);
+        self
+    }
+
+    /// The most idle connections to keep open to the apiserver. By
+    /// default there is no limit.
+    ///
+    /// Like all the connection options, this only applies to `build`
+    /// and `build_from_http`.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long an idle connection is kept open for reuse, or `None` to
+    /// keep idle connections indefinitely. The default is 90 seconds.
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Enable TCP keepalive probes on connections, sent after they have
+    /// been idle for `interval`. Keeps connections (and in particular
+    /// quiet watches) alive through NATs and firewalls that drop idle
+    /// flows. By default keepalive is disabled.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Resolve the apiserver's name with up to `threads` blocking
+    /// `getaddrinfo` calls at once.  The default is
+    /// `DEFAULT_DNS_THREADS`.  Ignored if a `resolver` is set.
+    ///
+    /// Like all the connection options, this only applies to `build`.
+    pub fn dns_threads(mut self, threads: usize) -> Self {
+        self.dns_threads = Some(threads);
+        self
+    }
+
+    /// Resolve the apiserver's name with `resolver`, rather than
+    /// `getaddrinfo`.  See the `dns` module.
+    pub fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Fail connections whose name takes longer than `timeout` to
+    /// resolve.  By default there is no limit, beyond any the resolver
+    /// has.
+    pub fn resolve_timeout(mut self, timeout: Duration) -> Self {
+        self.resolve_timeout = Some(timeout);
+        self
+    }
+
+    /// Which of the apiserver's addresses to connect to, if it has both
+    /// IPv4 and IPv6 ones.  By default they are tried in the order the
+    /// resolver gives.
+    pub fn ip_preference(mut self, preference: IpPreference) -> Self {
+        self.ip_preference = preference;
+        self
+    }
+
+    /// Record request metrics with `recorder`.
+    pub fn metrics(mut self, recorder: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(recorder);
+        self
+    }
+
+    /// Add trace context headers to every request with `propagator`.
+    pub fn propagate_trace_context(mut self, propagator: Arc<dyn Propagator>) -> Self {
+        self.propagator = Some(propagator);
+        self
+    }
+
+    /// Pass the apiserver's warnings (eg: about deprecated APIs) to
+    /// `handler`, rather than logging each once.  See the `warning`
+    /// module.
+    pub fn warning_handler(mut self, handler: Arc<dyn WarningHandler>) -> Self {
+        self.warning_handler = Some(handler);
+        self
+    }
+
+    /// Add `middleware` to the request stack, inside the client's own
+    /// middleware and any added earlier. See the `middleware` module.
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Build a client connecting as the config says.  Without the `tls`
+    /// or `rustls` feature, this only supports apiservers on plain HTTP
+    /// (eg: `kubectl proxy`).
+    pub fn build(mut self) -> Result<Client, Error> {
+        let resolver = match self.resolver.take() {
+            Some(resolver) => resolver,
+            None => Arc::new(ThreadPoolResolver::new(
+                self.dns_threads.unwrap_or(DEFAULT_DNS_THREADS),
+            )),
+        };
+        let http = HttpConnector::new_with_resolver(DnsResolver {
+            resolver,
+            preference: self.ip_preference,
+        });
+        self.build_from_connector(http)
+    }
+
+    pub fn build_from_http(self, http: HttpConnector) -> Result<Client, Error> {
+        self.build_from_connector(http)
+    }
+
+    fn build_from_connector<R>(mut self, mut http: HttpConnector<R>) -> Result<Client, Error>
+    where
+        R: Resolve + Clone + Send + Sync + 'static,
+        R::Future: Send,
+    {
+        let config = match self.config.take() {
+            Some(config) => config,
+            None => super::default_config()?,
+        };
+        if self.tcp_keepalive.is_some() {
+            http.set_keepalive(self.tcp_keepalive);
+        }
+        if self.resolve_timeout.is_some() {
+            http.set_resolve_timeout(self.resolve_timeout);
+        }
+        #[cfg(feature = "tls")]
+        let connector = {
+            let verifier = self.cert_verifier.take();
+            if !self.http1_only {
+                // Upgrading a connection (for exec) needs HTTP/1.1, so
+                // such requests get connections of their own.
+                let http1 = super::https_connector(http.clone(), &config, true, verifier.clone())?;
+                let client: hyper::Client<_> = hyper::Client::builder().build(http1);
+                self.upgrade_backend = Some(Arc::new(client));
+            }
+            super::https_connector(http, &config, self.http1_only, verifier)?
+        };
+        #[cfg(all(feature = "rustls", not(feature = "tls")))]
+        let connector = {
+            let verifier = self.cert_verifier.take();
+            if !self.http1_only {
+                let http1 = super::rustls_connector(http.clone(), &config, true, verifier.clone())?;
+                let client: hyper::Client<_> = hyper::Client::builder().build(http1);
+                self.upgrade_backend = Some(Arc::new(client));
+            }
+            super::rustls_connector(http, &config, self.http1_only, verifier)?
+        };
+        #[cfg(not(any(feature = "tls", feature = "rustls")))]
+        let connector = super::http_connector(http, &config)?;
+        let mut builder = hyper::Client::builder();
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder.max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder.keep_alive_timeout(timeout);
+        }
+        let client = builder.build(connector);
+        self.config = Some(config);
+        self.build_with_client(client)
+    }
+
+    /// Build a client using an already configured hyper client.
+    pub fn build_with_client<C>(self, client: hyper::Client<C>) -> Result<Client, Error>
+    where
+        C: hyper::client::connect::Connect + 'static,
+    {
+        self.build_with_backend(Arc::new(client))
+    }
+
+    /// Build a client that sends its requests with `backend`.  See the
+    /// `backend` module.
+    pub fn build_with_backend(self, backend: Arc<dyn Backend>) -> Result<Client, Error> {
+        let config = match self.config {
+            Some(config) => config,
+            None => super::default_config()?,
+        };
+        if let Some((qps, _)) = self.rate_limit {
+            if qps.is_nan() || qps <= 0.0 {
+                return Err(format_err!("Rate limit must be positive, not {}", qps));
+            }
+        }
+        let user_agent = match self.user_agent {
+            Some(product) => HeaderValue::from_str(&format!("{} {}", product, DEFAULT_USER_AGENT))
+                .map_err(|_| format_err!("Invalid user agent: {:?}", product))?,
+            None => HeaderValue::from_static(DEFAULT_USER_AGENT),
+        };
+        let mut middleware: Vec<Arc<dyn Middleware>> = vec![Arc::new(UserAgent(user_agent))];
+        if let Some((qps, burst)) = self.rate_limit {
+            middleware.push(Arc::new(RateLimiter::new(qps, burst)));
+        }
+        middleware.push(Arc::new(Instrument {
+            metrics: self.metrics,
+            propagator: self.propagator,
+        }));
+        middleware.push(Arc::new(Warnings(
+            self.warning_handler
+                .unwrap_or_else(|| Arc::new(LogWarnings::new())),
+        )));
+        middleware.extend(self.middleware);
+
+        let mut transport = Transport::new(backend, middleware);
+        if let Some(backend) = self.upgrade_backend {
+            transport.set_upgrade_backend(backend);
+        }
+        transport.compression = !self.disable_compression;
+        if let Some(max) = self.max_line_length {
+            transport.max_line_length = max;
+        }
+        if let Some(max) = self.max_body_size {
+            transport.max_body_size = max;
+        }
+        transport.retry = self.retry;
+        Ok(Client::from_parts(transport, config, self.timeout))
+    }
+}
+
+#[test]
+fn test_user_agent() {
+    use futures::{future, Future, Stream};
+    use hyper::header::USER_AGENT;
+    use hyper::{Body, Request, Response};
+
+    use super::middleware::{Next, ResponseFuture};
+
+    /// Responds with the request's user agent.
+    #[derive(Debug)]
+    struct Echo;
+
+    impl Middleware for Echo {
+        fn call(&self, req: Request<Body>, _next: Next) -> ResponseFuture {
+            let ua = req.headers()[USER_AGENT].as_bytes().to_vec();
+            Box::new(future::ok(Response::new(ua.into())))
+        }
+    }
+
+    fn user_agent(builder: ClientBuilder) -> String {
+        let client = builder
+            .config(Default::default())
+            .middleware(Arc::new(Echo))
+            .build_with_client(hyper::Client::new())
+            .unwrap();
+        let req = Request::get("http://localhost/")
+            .body(Body::empty())
+            .unwrap();
+        let res = client.client.request(req).wait().unwrap();
+        let body = res.into_body().concat2().wait().unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    assert_eq!(user_agent(ClientBuilder::new()), DEFAULT_USER_AGENT);
+    assert_eq!(
+        user_agent(ClientBuilder::new().user_agent("myoperator/1.2")),
+        format!("myoperator/1.2 {}", DEFAULT_USER_AGENT)
+    );
+
+    assert!(ClientBuilder::new()
+        .config(Default::default())
+        .user_agent("bad\n")
+        .build_with_client(hyper::Client::new())
+        .is_err());
+}
+
+#[test]
+fn test_warning_handler() {
+    use futures::{future, Future};
+    use hyper::header::WARNING;
+    use hyper::{Body, Request, Response};
+
+    use super::middleware::{Next, ResponseFuture};
+    use super::warning::{Warning, WarningCollector};
+
+    /// Warns about everything.
+    #[derive(Debug)]
+    struct Deprecated;
+
+    impl Middleware for Deprecated {
+        fn call(&self, _req: Request<Body>, _next: Next) -> ResponseFuture {
+            let res = Response::builder()
+                .header(WARNING, r#"299 - "policy/v1beta1 is deprecated""#)
+                .header(WARNING, r#"299 - "so is this", 299 - "and this""#)
+                .body(Body::empty())
+                .unwrap();
+            Box::new(future::ok(res))
+        }
+    }
+
+    let warnings = Arc::new(WarningCollector::new());
+    let client = ClientBuilder::new()
+        .config(Default::default())
+        .warning_handler(warnings.clone())
+        .middleware(Arc::new(Deprecated))
+        .build_with_client(hyper::Client::new())
+        .unwrap();
+    let req = Request::get("http://localhost/apis/policy/v1beta1/poddisruptionbudgets")
+        .body(Body::empty())
+        .unwrap();
+    client.client.request(req).wait().unwrap();
+
+    let warnings = warnings.take();
+    let texts: Vec<_> = warnings.iter().map(|(_, w)| w.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        vec!["policy/v1beta1 is deprecated", "so is this", "and this"]
+    );
+    assert_eq!(
+        warnings[0],
+        (
+            "GET /apis/policy/v1beta1/poddisruptionbudgets (list policy/v1beta1/poddisruptionbudgets)"
+                .to_string(),
+            Warning {
+                agent: "-".to_string(),
+                text: "policy/v1beta1 is deprecated".to_string(),
+            }
+        )
+    );
+}
@@ -0,0 +1,401 @@
+//! Deleting an object and everything it owns from the client, for when
+//! the server-side garbage collector can't be relied on: it is
+//! disabled, or the owner references cross namespaces, which it
+//! ignores.
+//!
+//! Dependents are found by their `ownerReferences`, as the garbage
+//! collector finds them: an object is deleted once all of its owners
+//! are being deleted.  Dependents are deleted before their owners, and
+//! each is waited for until it is gone.
+//!
+//! ```no_run
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use kubernetes_api::meta::GroupVersionResource;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let deployments = GroupVersionResource {
+//!     group: "apps",
+//!     version: "v1",
+//!     resource: "deployments",
+//! };
+//! let mut rt = tokio::runtime::Runtime::new()?;
+//! let deleted = rt.block_on(client.delete_cascading(
+//!     &deployments,
+//!     Some("default"),
+//!     "web",
+//!     Default::default(),
+//! ))?;
+//! for object in deleted {
+//!     println!("deleted {:?} {:?}", object.kind, object.name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+
+use failure::Error;
+use futures::future::{self, Either, Loop};
+use futures::{stream, Future, Stream};
+use serde_json::Value;
+
+use api::core::v1::ObjectReference;
+use api::meta::v1::{DeleteOptions, ListOptions};
+use api::meta::GroupVersionResource;
+
+use super::restmapper::{RESTMapper, ResourceMapping};
+use super::{status_code, wait, Client};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CascadeOptions {
+    /// Where to look for dependents.  Empty (the default) means every
+    /// resource discovery finds that can be listed and deleted.
+    pub resources: Vec<ResourceMapping>,
+    /// Look for dependents in every namespace, not just the object's
+    /// own.
+    pub all_namespaces: bool,
+    /// Used for every deletion.
+    pub delete_options: DeleteOptions,
+}
+
+/// An object to delete.
+#[derive(Debug, Clone)]
+struct Node {
+    group: String,
+    version: String,
+    resource: String,
+    kind: String,
+    namespace: Option<String>,
+    name: String,
+    uid: String,
+    /// The UIDs of its owners.
+    owners: Vec<String>,
+}
+
+impl Node {
+    fn new(group: &str, version: &str, resource: &str, kind: &str, object: &Value) -> Self {
+        let meta = &object["metadata"];
+        let string = |v: &Value| v.as_str().unwrap_or_default().to_string();
+        let owners = meta["ownerReferences"]
+            .as_array()
+            .map(|refs| refs.iter().map(|r| string(&r["uid"])).collect())
+            .unwrap_or_default();
+        Node {
+            group: group.to_string(),
+            version: version.to_string(),
+            resource: resource.to_string(),
+            kind: kind.to_string(),
+            namespace: meta["namespace"].as_str().map(str::to_string),
+            name: string(&meta["name"]),
+            uid: string(&meta["uid"]),
+            owners,
+        }
+    }
+
+    fn gvr(&self) -> GroupVersionResource<'_> {
+        GroupVersionResource {
+            group: &self.group,
+            version: &self.version,
+            resource: &self.resource,
+        }
+    }
+
+    fn reference(&self) -> ObjectReference {
+        let api_version = if self.group.is_empty() {
+            self.version.clone()
+        } else {
+            format!("{}/{}", self.group, self.version)
+        };
+        ObjectReference {
+            api_version: Some(api_version),
+            kind: Some(self.kind.clone()),
+            namespace: self.namespace.clone(),
+            name: Some(self.name.clone()),
+            uid: Some(self.uid.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+fn deletable(r: &ResourceMapping) -> bool {
+    // Hand-written mappings may leave the verbs out.
+    r.verbs.is_empty()
+        || ["list", "delete"]
+            .iter()
+            .all(|v| r.verbs.iter().any(|r| r == v))
+}
+
+/// Every object of `resources` in `namespace`, or everywhere.  Cluster
+/// scoped resources are only looked at everywhere.  Resources that
+/// can't be listed are skipped.
+fn snapshot(
+    client: &Client,
+    resources: &[ResourceMapping],
+    namespace: Option<&str>,
+) -> impl Future<Item = Vec<Node>, Error = Error> + Send {
+    let lists = resources
+        .iter()
+        .filter(|r| r.namespaced || namespace.is_none())
+        .map(|r| {
+            let r = r.clone();
+            let ns = namespace.filter(|_| r.namespaced);
+            client.list(&r.gvr(), ns, ListOptions::default()).then(
+                move |list: Result<Value, Error>| match list {
+                    Ok(list) => Ok(list["items"]
+                        .as_array()
+                        .map(|items| {
+                            items
+                                .iter()
+                                .map(|o| Node::new(&r.group, &r.version, &r.resource, &r.kind, o))
+                                .collect()
+                        })
+                        .unwrap_or_default()),
+                    Err(ref e) if [403, 404, 405].contains(&status_code(e).unwrap_or_default()) => {
+                        debug!("Not looking for dependents in {}: {}", r.resource, e);
+                        Ok(Vec::new())
+                    }
+                    Err(e) => Err(e),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    future::join_all(lists).map(|lists| {
+        // The same object may be served by several resources.
+        let mut seen = HashSet::new();
+        lists
+            .into_iter()
+            .flatten()
+            .filter(|o| seen.insert(o.uid.clone()))
+            .collect()
+    })
+}
+
+/// The objects to delete once everything in `doomed` is: those all of
+/// whose owners are, transitively, in waves that can each be deleted
+/// once the following waves are gone.
+fn plan(objects: Vec<Node>, mut doomed: HashSet<String>) -> Vec<Vec<Node>> {
+    let mut rest: Vec<_> = objects
+        .into_iter()
+        .filter(|o| !doomed.contains(&o.uid))
+        .collect();
+    let mut waves = Vec::new();
+    loop {
+        let (wave, others): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|o| !o.owners.is_empty() && o.owners.iter().all(|uid| doomed.contains(uid)));
+        if wave.is_empty() {
+            break;
+        }
+        doomed.extend(wave.iter().map(|o| o.uid.clone()));
+        waves.push(wave);
+        rest = others;
+    }
+    waves.reverse();
+    waves
+}
+
+/// Delete `object`, and wait until it is gone.  A replacement of the
+/// same name doesn't count.
+fn delete_one(
+    client: Client,
+    object: Node,
+    opts: DeleteOptions,
+) -> impl Future<Item = ObjectReference, Error = Error> + Send {
+    debug!(
+        "Deleting {} {:?}/{}",
+        object.kind, object.namespace, object.name
+    );
+    let gvr = object.gvr();
+    let namespace = object.namespace.as_deref();
+    client
+        .delete(&gvr, namespace, &object.name, opts)
+        .or_else(|e| {
+            if status_code(&e) == Some(404) {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })
+        .and_then(move |()| {
+            let gvr = object.gvr();
+            let namespace = object.namespace.as_deref();
+            wait::await_deletion_of(&client, &gvr, namespace, &object.name, &object.uid)
+                .map(move |()| object.reference())
+        })
+}
+
+struct State {
+    root: Option<Node>,
+    /// The UIDs of everything deleted so far.
+    gone: HashSet<String>,
+    deleted: Vec<ObjectReference>,
+}
+
+impl Client {
+    /// Delete the named object and, first, everything it owns, waiting
+    /// until each is gone.  Returns what was deleted, in order.
+    ///
+    /// Once the object is gone, its dependents are looked for again
+    /// until there are none, in case any were made along the way.
+    /// Never times out by itself, see `wait::timeout`.  Requires a
+    /// tokio runtime with a timer.
+    pub fn delete_cascading(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: CascadeOptions,
+    ) -> impl Future<Item = Vec<ObjectReference>, Error = Error> + Send {
+        let client = self.clone();
+        let resources = if opts.resources.is_empty() {
+            Either::A(RESTMapper::new(self.clone()).resources())
+        } else {
+            Either::B(future::ok(opts.resources.clone()))
+        };
+        let (group, version, resource) = (
+            gvr.group.to_string(),
+            gvr.version.to_string(),
+            gvr.resource.to_string(),
+        );
+        let root = self.get(gvr, namespace, name, Default::default());
+        resources
+            .join(root)
+            .and_then(move |(resources, root): (_, Value)| {
+                let kind = root["kind"].as_str().unwrap_or_default().to_string();
+                let root = Node::new(&group, &version, &resource, &kind, &root);
+                let resources: Vec<_> = resources.into_iter().filter(deletable).collect();
+                // Cluster scoped objects may own namespaced ones anywhere.
+                let scope = root.namespace.clone().filter(|_| !opts.all_namespaces);
+                let state = State {
+                    root: Some(root),
+                    gone: HashSet::new(),
+                    deleted: Vec::new(),
+                };
+                future::loop_fn(state, move |mut state| {
+                    let client = client.clone();
+                    let delete_options = opts.delete_options.clone();
+                    snapshot(&client, &resources, scope.as_deref()).and_then(move |objects| {
+                        let mut doomed = state.gone.clone();
+                        doomed.extend(state.root.as_ref().map(|r| r.uid.clone()));
+                        let mut waves = plan(objects, doomed);
+                        waves.extend(state.root.take().map(|r| vec![r]));
+                        if waves.is_empty() {
+                            return Either::A(future::ok(Loop::Break(state.deleted)));
+                        }
+                        let deleted = stream::iter_ok(waves)
+                            .and_then(move |wave| {
+                                let (client, opts) = (client.clone(), delete_options.clone());
+                                future::join_all(
+                                    wave.into_iter()
+                                        .map(move |o| delete_one(client.clone(), o, opts.clone())),
+                                )
+                            })
+                            .concat2();
+                        Either::B(deleted.map(move |deleted| {
+                            for r in &deleted {
+                                state.gone.extend(r.uid.clone());
+                            }
+                            state.deleted.extend(deleted);
+                            Loop::Continue(state)
+                        }))
+                    })
+                })
+            })
+    }
+}
+
+#[test]
+fn test_delete_cascading() {
+    use tokio::runtime::Runtime;
+
+    use super::fake::FakeClient;
+
+    let mapping = |group: &str, resource: &str, kind: &str, namespaced: bool| ResourceMapping {
+        group: group.to_string(),
+        version: "v1".to_string(),
+        resource: resource.to_string(),
+        kind: kind.to_string(),
+        singular: kind.to_lowercase(),
+        short_names: Vec::new(),
+        namespaced,
+        verbs: vec!["list".to_string(), "delete".to_string()],
+    };
+    let deployments = mapping("apps", "deployments", "Deployment", true);
+    let replicasets = mapping("apps", "replicasets", "ReplicaSet", true);
+    let pods = mapping("", "pods", "Pod", true);
+    let mut events = mapping("", "events", "Event", true);
+    events.verbs = vec!["list".to_string()];
+    let opts = CascadeOptions {
+        resources: vec![
+            deployments.clone(),
+            replicasets.clone(),
+            pods.clone(),
+            events,
+        ],
+        ..Default::default()
+    };
+
+    let client = FakeClient::new();
+    let server = client.server();
+    let insert = |r: &ResourceMapping, namespace: &str, name: &str, owners: &[&Value]| {
+        let owners: Vec<_> = owners
+            .iter()
+            .map(|o| json!({"kind": o["kind"], "name": o["metadata"]["name"], "uid": o["metadata"]["uid"]}))
+            .collect();
+        let object = json!({
+            "kind": r.kind,
+            "metadata": {"name": name, "namespace": namespace, "ownerReferences": owners},
+        });
+        server.insert(&r.gvr(), &object).unwrap()
+    };
+    let deploy = insert(&deployments, "ns", "web", &[]);
+    let rs = insert(&replicasets, "ns", "web-1", &[&deploy]);
+    insert(&pods, "ns", "web-1-a", &[&rs]);
+    // Also owned by something that stays.
+    let other = insert(&deployments, "ns", "other", &[]);
+    insert(&pods, "ns", "shared", &[&rs, &other]);
+    // Not looked for outside the namespace by default.
+    insert(&pods, "elsewhere", "web-1-b", &[&rs]);
+
+    let mut rt = Runtime::new().unwrap();
+    let deleted = rt
+        .block_on(client.delete_cascading(&deployments.gvr(), Some("ns"), "web", opts.clone()))
+        .unwrap();
+    let names: Vec<_> = deleted
+        .iter()
+        .map(|r| format!("{}/{}", r.kind.as_ref().unwrap(), r.name.as_ref().unwrap()))
+        .collect();
+    assert_eq!(
+        names,
+        vec!["Pod/web-1-a", "ReplicaSet/web-1", "Deployment/web"]
+    );
+    assert_eq!(deleted[2].api_version.as_deref(), Some("apps/v1"));
+    assert!(server.get(&pods.gvr(), Some("ns"), "shared").is_some());
+    assert!(server
+        .get(&pods.gvr(), Some("elsewhere"), "web-1-b")
+        .is_some());
+
+    // Everywhere.
+    let deploy = insert(&deployments, "ns", "web", &[]);
+    let rs = insert(&replicasets, "ns", "web-1", &[&deploy]);
+    insert(&pods, "elsewhere", "web-1-c", &[&rs]);
+    let opts = CascadeOptions {
+        all_namespaces: true,
+        ..opts
+    };
+    let deleted = rt
+        .block_on(client.delete_cascading(&deployments.gvr(), Some("ns"), "web", opts.clone()))
+        .unwrap();
+    assert_eq!(deleted.len(), 3);
+    assert_eq!(deleted[0].namespace.as_deref(), Some("elsewhere"));
+    assert!(server
+        .get(&pods.gvr(), Some("elsewhere"), "web-1-c")
+        .is_none());
+
+    assert!(rt
+        .block_on(client.delete_cascading(&deployments.gvr(), Some("ns"), "web", opts))
+        .is_err());
+}
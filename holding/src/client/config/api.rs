@@ -1,3 +1,4 @@
+use api::Redacted;
 use serde_base64;
 use serde_json::{Map, Value};
 use std::collections::BTreeMap;
@@ -79,9 +80,9 @@ pub struct AuthInfo {
     #[serde(default)]
     pub client_key: PathBuf,
     #[serde(default, with = "serde_base64")]
-    pub client_key_data: Vec<u8>,
+    pub client_key_data: Redacted<Vec<u8>>,
     #[serde(default)]
-    pub token: String,
+    pub token: Redacted<String>,
     #[serde(default, rename = "tokenFile")]
     pub token_file: PathBuf,
     #[serde(default)]
@@ -93,7 +94,7 @@ pub struct AuthInfo {
     #[serde(default)]
     pub username: String,
     #[serde(default)]
-    pub password: String,
+    pub password: Redacted<String>,
     #[serde(default)]
     pub auth_provider: Option<AuthProviderConfig>,
     #[serde(default)]
@@ -114,6 +115,7 @@ pub struct Context {
 #[serde(rename_all = "kebab-case")]
 pub struct AuthProviderConfig {
     pub name: String,
+    /// Holds tokens, for some providers.
     #[serde(default)]
-    pub config: BTreeMap<String, String>,
+    pub config: Redacted<BTreeMap<String, String>>,
 }
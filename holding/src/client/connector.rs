@@ -0,0 +1,159 @@
+//! HTTPS connections that negotiate HTTP/2 via ALPN, and optionally
+//! check the server's certificate with a `ServerCertVerifier`.
+//!
+//! `HttpsConnector` needs the `tls` feature; with only `rustls`, the
+//! verifier is called by `rustls_connector` instead.
+//!
+//! For example, to only trust one particular certificate:
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate kubernetes_holding;
+//! # use std::sync::Arc;
+//! use failure::{format_err, Error};
+//! use kubernetes_holding::client::connector::ServerCertVerifier;
+//! use kubernetes_holding::client::Client;
+//!
+//! #[derive(Debug)]
+//! struct Pinned(Vec<u8>);
+//!
+//! impl ServerCertVerifier for Pinned {
+//!     fn verify(&self, host: &str, cert: &[u8]) -> Result<(), Error> {
+//!         if cert != &self.0[..] {
+//!             return Err(format_err!("{} is not the pinned certificate", host));
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn main() -> Result<(), Error> {
+//! let pinned = std::fs::read("apiserver.der")?;
+//! let client = Client::builder()
+//!     .server_cert_verifier(Arc::new(Pinned(pinned)))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+#[cfg(feature = "tls")]
+use std::io;
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+use failure::Error;
+#[cfg(feature = "tls")]
+use futures::Future;
+#[cfg(feature = "tls")]
+use hyper::client::connect::{Connect, Connected, Destination};
+#[cfg(feature = "tls")]
+use hyper_tls::{self, MaybeHttpsStream};
+
+/// Checks the certificate the apiserver presents, beyond (or instead
+/// of) the usual verification against the cluster's CA.
+pub trait ServerCertVerifier: fmt::Debug + Send + Sync {
+    /// Accept or reject the certificate `cert` (DER encoded) presented
+    /// by `host`.  Called once the TLS handshake is done, before any
+    /// request is sent on the connection.
+    fn verify(&self, host: &str, cert: &[u8]) -> Result<(), Error>;
+
+    /// Whether `verify` replaces the usual verification of the chain
+    /// and host name, rather than adding to it.  If so, only what
+    /// `verify` checks is checked.  By default it doesn't, which is
+    /// what pinning wants.
+    fn replaces_default(&self) -> bool {
+        false
+    }
+}
+
+/// The protocols we offer, in order of preference.
+#[cfg(feature = "tls")]
+pub(crate) const ALPN_PROTOCOLS: &[&str] = &["h2", "http/1.1"];
+
+/// As `hyper_tls::HttpsConnector`, but tells hyper when the server
+/// chose HTTP/2, so that requests are multiplexed over a single
+/// connection rather than each needing their own.
+///
+/// The TLS connector must have requested `ALPN_PROTOCOLS` for this to
+/// have any effect.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone)]
+pub struct HttpsConnector<T> {
+    inner: hyper_tls::HttpsConnector<T>,
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+}
+
+#[cfg(feature = "tls")]
+impl<T> HttpsConnector<T> {
+    /// Check servers' certificates with `verifier` too.  If it
+    /// `replaces_default`, the TLS connector should have been built to
+    /// accept invalid certificates and host names.
+    pub fn with_verifier(mut self, verifier: Arc<dyn ServerCertVerifier>) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<T> From<hyper_tls::HttpsConnector<T>> for HttpsConnector<T> {
+    fn from(inner: hyper_tls::HttpsConnector<T>) -> Self {
+        HttpsConnector {
+            inner,
+            verifier: None,
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<T> Connect for HttpsConnector<T>
+where
+    T: Connect<Error = io::Error>,
+    T::Transport: 'static,
+    T::Future: 'static,
+{
+    type Transport = MaybeHttpsStream<T::Transport>;
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = (Self::Transport, Connected), Error = io::Error> + Send>;
+
+    fn connect(&self, dst: Destination) -> Self::Future {
+        let verify = self.verifier.clone().map(|v| (v, dst.host().to_string()));
+        let connecting = self
+            .inner
+            .connect(dst)
+            .and_then(move |(stream, connected)| {
+                if let (Some((verifier, host)), MaybeHttpsStream::Https(ref tls)) =
+                    (verify, &stream)
+                {
+                    let cert = tls
+                        .get_ref()
+                        .peer_certificate()
+                        .and_then(|cert| cert.map(|c| c.to_der()).transpose())
+                        .map_err(io::Error::other)?
+                        .ok_or_else(|| {
+                            io::Error::new(io::ErrorKind::InvalidData, "Server sent no certificate")
+                        })?;
+                    verifier.verify(&host, &cert).map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Server certificate for {} rejected: {}", host, e),
+                        )
+                    })?;
+                }
+                Ok((stream, connected))
+            });
+        Box::new(connecting.map(|(stream, connected)| {
+            let h2 = match stream {
+                MaybeHttpsStream::Https(ref tls) => {
+                    tls.get_ref().negotiated_alpn().ok() == Some(Some(b"h2".to_vec()))
+                }
+                MaybeHttpsStream::Http(_) => false,
+            };
+            if h2 {
+                debug!("Negotiated HTTP/2");
+                (stream, connected.negotiated_h2())
+            } else {
+                (stream, connected)
+            }
+        }))
+    }
+}
@@ -0,0 +1,161 @@
+//! Copying files to and from containers, like `kubectl cp`.
+//!
+//! Files are sent as a tar archive through `exec`, so the container
+//! needs a `tar` command.
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use std::path::Path;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let mut rt = tokio::runtime::Runtime::new()?;
+//! rt.block_on(client.copy_to_pod(
+//!     "default",
+//!     "web-0",
+//!     Path::new("site"),
+//!     "/usr/share/nginx/html",
+//!     Default::default(),
+//! ))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use failure::Error;
+use futures::future::{self, Either};
+use futures::{stream, Future};
+
+use super::exec::{self, ExecOptions, Input};
+use super::tar::{Packer, Unpacker};
+use super::Client;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CopyOptions {
+    /// Needed if the pod has more than one container.
+    pub container: Option<String>,
+}
+
+impl CopyOptions {
+    fn exec_options(self) -> ExecOptions {
+        ExecOptions {
+            container: self.container,
+            tty: false,
+        }
+    }
+}
+
+/// Normalize a path in a container, and split it into the directory to
+/// run `tar` in, and the name of the file or directory in it.
+fn split_remote(path: &str) -> Result<(String, String), Error> {
+    let absolute = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|p| *p != "..") => {
+                parts.pop();
+            }
+            // The parent of / is /.
+            ".." if absolute => {}
+            part => parts.push(part),
+        }
+    }
+    let name = match parts.pop() {
+        Some(name) if name != ".." => name.to_string(),
+        _ => {
+            return Err(format_err!(
+                "Cannot copy {:?}: it does not name a file or directory",
+                path
+            ))
+        }
+    };
+    let dir = match (absolute, parts.join("/")) {
+        (true, dir) => format!("/{}", dir),
+        (false, ref dir) if dir.is_empty() => ".".to_string(),
+        (false, dir) => dir,
+    };
+    Ok((dir, name))
+}
+
+impl Client {
+    /// Copy the local file or directory `src` to `dest` in `pod`.  The
+    /// copy is named `dest`, and replaces any file already there;
+    /// `dest`'s parent directory must exist.
+    ///
+    /// Requires a tokio runtime.
+    pub fn copy_to_pod(
+        &self,
+        namespace: &str,
+        pod: &str,
+        src: &Path,
+        dest: &str,
+        opts: CopyOptions,
+    ) -> impl Future<Item = (), Error = Error> + Send {
+        let (dir, name) = match split_remote(dest) {
+            Ok(split) => split,
+            Err(e) => return Either::A(future::err(e)),
+        };
+        let packer = match Packer::new(src, &name) {
+            Ok(packer) => packer,
+            Err(e) => return Either::A(future::err(e)),
+        };
+        let stdin = Box::new(stream::iter_result(packer)) as Input;
+        let command = ["tar", "-xmf", "-", "-C", &dir];
+        let output = self.exec(namespace, pod, &command, Some(stdin), opts.exec_options());
+        Either::B(exec::read_output(output, (), |(), _| Ok(())).map(|_| ()))
+    }
+
+    /// Copy `src`, a file or directory in `pod`, to the local `dest`,
+    /// replacing any file already there.  `dest`'s parent directory
+    /// must exist.
+    ///
+    /// Symlinks and other special files in `src` are skipped.
+    ///
+    /// Requires a tokio runtime.
+    pub fn copy_from_pod(
+        &self,
+        namespace: &str,
+        pod: &str,
+        src: &str,
+        dest: &Path,
+        opts: CopyOptions,
+    ) -> impl Future<Item = (), Error = Error> + Send {
+        let (dir, name) = match split_remote(src) {
+            Ok(split) => split,
+            Err(e) => return Either::A(future::err(e)),
+        };
+        // In case the name starts with a "-".
+        let member = format!("./{}", name);
+        let command = ["tar", "-cf", "-", "-C", &dir, &member];
+        let output = self.exec(namespace, pod, &command, None, opts.exec_options());
+        let unpacker = Unpacker::new(dest, &name);
+        Either::B(
+            exec::read_output(output, unpacker, |mut unpacker, data| {
+                unpacker.feed(&data)?;
+                Ok(unpacker)
+            })
+            .and_then(|(unpacker, _)| unpacker.finish()),
+        )
+    }
+}
+
+#[test]
+fn test_split_remote() {
+    let split = |path| split_remote(path).ok();
+    let pair = |dir: &str, name: &str| Some((dir.to_string(), name.to_string()));
+    assert_eq!(split("/tmp/a"), pair("/tmp", "a"));
+    assert_eq!(split("/tmp//a/"), pair("/tmp", "a"));
+    assert_eq!(split("/a"), pair("/", "a"));
+    assert_eq!(split("/../a/./b/../c"), pair("/a", "c"));
+    assert_eq!(split("a"), pair(".", "a"));
+    assert_eq!(split("./a/b"), pair("a", "b"));
+    assert_eq!(split("../a"), pair("..", "a"));
+    assert_eq!(split("/"), None);
+    assert_eq!(split(""), None);
+    assert_eq!(split("a/.."), None);
+    assert_eq!(split(".."), None);
+}
@@ -0,0 +1,112 @@
+//! API discovery: which groups, versions and resources a cluster serves.
+
+use failure::Error;
+use futures::{future, Future};
+
+use serde_json::Value;
+
+use api::meta::v1::{APIGroupList, APIResourceList, APIVersions};
+use api::meta::GroupVersion;
+use api::version::Info;
+
+use super::diskcache::cached;
+use super::Client;
+
+impl Client {
+    /// The apiserver's version, served at `/version`.
+    pub fn server_version(&self) -> impl Future<Item = Info, Error = Error> + Send {
+        self.get_path(&["version"])
+    }
+
+    /// The versions of the legacy core group, served at `/api`.
+    pub fn core_api_versions(&self) -> impl Future<Item = APIVersions, Error = Error> + Send {
+        self.get_path(&["api"])
+    }
+
+    /// All named API groups, served at `/apis`.
+    pub fn api_groups(&self) -> impl Future<Item = APIGroupList, Error = Error> + Send {
+        self.get_path(&["apis"])
+    }
+
+    /// The resources served by a single group version, from
+    /// `/api/{version}` for the core group or `/apis/{group}/{version}`.
+    pub fn api_resources(
+        &self,
+        gv: &GroupVersion,
+    ) -> impl Future<Item = APIResourceList, Error = Error> + Send {
+        if gv.group.is_empty() {
+            self.get_path(&["api", gv.version])
+        } else {
+            self.get_path(&["apis", gv.group, gv.version])
+        }
+    }
+
+    /// Resource lists for every group version the server offers,
+    /// including the core group.
+    ///
+    /// Issues one request per group version, concurrently.
+    pub fn all_api_resources(
+        &self,
+    ) -> impl Future<Item = Vec<APIResourceList>, Error = Error> + Send {
+        fetch_discovery(self.clone(), true).map(|d| d.resources)
+    }
+
+    /// The OpenAPI v2 schema of everything the server offers, served
+    /// at `/openapi/v2`.  Several megabytes, so worth a disk cache.
+    pub fn openapi_v2(&self) -> impl Future<Item = Value, Error = Error> + Send {
+        let client = self.clone();
+        cached(self, "openapi-v2.json", true, move || {
+            client.get_path(&["openapi", "v2"])
+        })
+        .map(|(schema, _)| schema)
+    }
+}
+
+/// Everything returned by a full discovery pass.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Discovery {
+    pub groups: APIGroupList,
+    pub resources: Vec<APIResourceList>,
+    /// Whether this came from the client's disk cache.
+    #[serde(skip)]
+    pub cached: bool,
+}
+
+/// Discover everything, from the client's disk cache if it has a fresh
+/// copy and `reuse` is set.
+// Separate fn so the returned future doesn't borrow `&self`
+pub(crate) fn fetch_discovery(
+    client: Client,
+    reuse: bool,
+) -> impl Future<Item = Discovery, Error = Error> + Send {
+    let fetch = client.clone();
+    cached(&client, "discovery.json", reuse, move || discover(fetch))
+        .map(|(d, cached)| Discovery { cached, ..d })
+}
+
+fn discover(client: Client) -> impl Future<Item = Discovery, Error = Error> + Send {
+    client
+        .core_api_versions()
+        .join(client.api_groups())
+        .and_then(move |(core, groups)| {
+            let core_gvs = core.versions.into_iter();
+            let group_gvs = groups
+                .groups
+                .iter()
+                .flat_map(|g| g.versions.iter().map(|v| v.group_version.clone()));
+            let lists: Vec<_> = core_gvs
+                .chain(group_gvs)
+                .map(|gv| {
+                    GroupVersion::from_str(&gv)
+                        .map_err(Error::from)
+                        .map(|gv| client.api_resources(&gv))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(future::join_all(lists).map(move |resources| Discovery {
+                groups,
+                resources,
+                cached: false,
+            }))
+        })
+        .flatten()
+}
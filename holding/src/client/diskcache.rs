@@ -0,0 +1,284 @@
+//! Discovery results and the OpenAPI schema, kept on disk between
+//! runs as kubectl keeps them under `~/.kube/cache`, so that
+//! short-lived programs don't repeat discovery's many requests every
+//! time.
+//!
+//! ```no_run
+//! # extern crate kubernetes_holding;
+//! # use kubernetes_holding::client::diskcache::DiskCache;
+//! # use kubernetes_holding::client::restmapper::RESTMapper;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let dir = DiskCache::default_dir().ok_or(failure::err_msg("no home directory"))?;
+//! let client = Client::new()?.with_disk_cache(DiskCache::new(dir));
+//! let mapper = RESTMapper::new(client);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Entries are kept per apiserver URL and version: a client with a
+//! disk cache asks for the server's version, a single request, and
+//! uses what was cached for that if it is younger than the TTL.  A
+//! `RESTMapper` asks the server again when the cached discovery
+//! results have nothing matching, so new CRDs are still found.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime};
+
+use failure::Error;
+use futures::future::{self, Either};
+use futures::Future;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+
+use super::Client;
+
+/// Overrides `DiskCache::default_dir`, as for kubectl.
+pub const CACHE_DIR_ENV: &str = "KUBECACHEDIR";
+
+/// How long entries are used for, by default; kubectl's too.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// A directory of cached discovery results and schemas.  Best effort:
+/// failing to read or write it only means asking the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl DiskCache {
+    /// A cache in `dir`, which is made when first written to.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        DiskCache {
+            dir: dir.into(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Use entries for `ttl` after they are written, rather than
+    /// `DEFAULT_TTL`.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// `$KUBECACHEDIR`, or else `~/.kube/cache`.
+    pub fn default_dir() -> Option<PathBuf> {
+        env::var_os(CACHE_DIR_ENV)
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|h| h.join(".kube").join("cache")))
+    }
+
+    fn server_dir(&self, server: &str) -> PathBuf {
+        self.dir.join(escape(server))
+    }
+
+    fn path(&self, server: &str, version: &str, name: &str) -> PathBuf {
+        self.server_dir(server).join(escape(version)).join(name)
+    }
+
+    /// The entry `name` for `server` at `version`, if there is a fresh
+    /// one.
+    pub(crate) fn read<T: DeserializeOwned>(
+        &self,
+        server: &str,
+        version: &str,
+        name: &str,
+    ) -> Option<T> {
+        let path = self.path(server, version, name);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        // Entries from the future (eg: clock changes) are fresh.
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age >= self.ttl {
+            return None;
+        }
+        let read = fs::read(&path).map_err(Error::from);
+        match read.and_then(|bytes| serde_json::from_slice(&bytes).map_err(Error::from)) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                debug!("Ignoring cached {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Replace the entry `name` for `server` at `version`.
+    pub(crate) fn write<T: Serialize>(&self, server: &str, version: &str, name: &str, value: &T) {
+        let path = self.path(server, version, name);
+        // Written aside and renamed, so readers never see half of it.
+        let tmp = path.with_extension(format!("tmp.{}", process::id()));
+        let written = fs::create_dir_all(path.parent().unwrap())
+            .map_err(Error::from)
+            .and_then(|()| Ok(fs::write(&tmp, serde_json::to_vec(value)?)?))
+            .and_then(|()| Ok(fs::rename(&tmp, &path)?));
+        if let Err(e) = written {
+            debug!("Unable to cache {}: {}", path.display(), e);
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+
+    /// Forget everything cached for `server`.
+    pub fn invalidate(&self, server: &str) -> io::Result<()> {
+        match fs::remove_dir_all(self.server_dir(server)) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => r,
+        }
+    }
+}
+
+/// `s` as a single path component, as kubectl makes them: without any
+/// scheme, and with anything other than letters, digits, dots and
+/// dashes replaced by underscores.
+fn escape(s: &str) -> String {
+    let s = s
+        .strip_prefix("https://")
+        .or_else(|| s.strip_prefix("http://"))
+        .unwrap_or(s);
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// What `fetch` returns, from `client`'s disk cache if it has a fresh
+/// copy of `name` and `reuse` is set, or else saved there.  Also
+/// whether it came from the cache.
+pub(crate) fn cached<T, F, R>(
+    client: &Client,
+    name: &'static str,
+    reuse: bool,
+    fetch: F,
+) -> impl Future<Item = (T, bool), Error = Error> + Send
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+    R: Future<Item = T, Error = Error> + Send + 'static,
+{
+    let cache = match client.disk_cache.clone() {
+        Some(cache) => cache,
+        None => return Either::A(fetch().map(|value| (value, false))),
+    };
+    let server = client.config.cluster.server.clone();
+    Either::B(client.server_version().and_then(move |info| {
+        let version = info.git_version;
+        let hit = if reuse {
+            cache.read(&server, &version, name)
+        } else {
+            None
+        };
+        if let Some(value) = hit {
+            return Either::A(future::ok((value, true)));
+        }
+        Either::B(fetch().map(move |value| {
+            cache.write(&server, &version, name, &value);
+            (value, false)
+        }))
+    }))
+}
+
+#[test]
+fn test_disk_cache() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use hyper::{Body, Request, Response};
+
+    use api::version::Info;
+
+    use super::config::ConfigContext;
+    use super::middleware::{Middleware, Next, ResponseFuture};
+    use super::restmapper::RESTMapper;
+
+    /// A server with pods, and widgets once `crds` is set, counting
+    /// requests.
+    #[derive(Debug, Default)]
+    struct Server {
+        requests: AtomicUsize,
+        crds: AtomicUsize,
+    }
+
+    impl Middleware for Server {
+        fn call(&self, req: Request<Body>, _next: Next) -> ResponseFuture {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let body = match req.uri().path() {
+                "/version" => serde_json::to_value(Info {
+                    git_version: "v1.30.1".to_string(),
+                    ..Default::default()
+                })
+                .unwrap(),
+                "/api" => json!({"versions": ["v1"]}),
+                "/apis" if self.crds.load(Ordering::SeqCst) > 0 => json!({"groups": [{
+                    "name": "example.com",
+                    "versions": [{"groupVersion": "example.com/v1", "version": "v1"}],
+                }]}),
+                "/apis" => json!({"groups": []}),
+                "/api/v1" => json!({"groupVersion": "v1", "resources": [
+                    {"name": "pods", "singularName": "pod", "namespaced": true, "kind": "Pod"},
+                ]}),
+                "/apis/example.com/v1" => json!({"groupVersion": "example.com/v1", "resources": [
+                    {"name": "widgets", "singularName": "widget", "namespaced": true, "kind": "Widget"},
+                ]}),
+                "/openapi/v2" => json!({"swagger": "2.0"}),
+                path => panic!("unexpected request for {}", path),
+            };
+            let body = serde_json::to_vec(&body).unwrap();
+            Box::new(future::ok(Response::new(body.into())))
+        }
+    }
+
+    let dir = env::temp_dir().join(format!("kubernetes-diskcache-{}", process::id()));
+    let cache = DiskCache::new(&dir);
+    let server = Arc::new(Server::default());
+    let client = || {
+        let mut config: ConfigContext = Default::default();
+        config.cluster.server = "https://10.0.0.1:6443".to_string();
+        Client::builder()
+            .config(config)
+            .middleware(server.clone())
+            .build_with_client(hyper::Client::new())
+            .unwrap()
+            .with_disk_cache(cache.clone())
+    };
+    let requests = || server.requests.swap(0, Ordering::SeqCst);
+
+    let pods = RESTMapper::new(client()).lookup("pods").wait().unwrap();
+    assert_eq!(pods.kind, "Pod");
+    assert_eq!(requests(), 4);
+    assert!(dir.join("10.0.0.1_6443").join("v1.30.1").is_dir());
+    // Another run only asks for the version.
+    RESTMapper::new(client()).lookup("pod").wait().unwrap();
+    assert_eq!(requests(), 1);
+    // New resources are found, and cached.
+    server.crds.store(1, Ordering::SeqCst);
+    let mapper = RESTMapper::new(client());
+    assert_eq!(mapper.lookup("widget").wait().unwrap().group, "example.com");
+    assert_eq!(requests(), 1 + 5);
+    RESTMapper::new(client()).lookup("widget").wait().unwrap();
+    assert_eq!(requests(), 1);
+
+    client().openapi_v2().wait().unwrap();
+    assert_eq!(client().openapi_v2().wait().unwrap()["swagger"], "2.0");
+    assert_eq!(requests(), 3);
+
+    // Expired.
+    let expired = client().with_disk_cache(cache.clone().ttl(Duration::from_secs(0)));
+    expired.openapi_v2().wait().unwrap();
+    assert_eq!(requests(), 2);
+    cache.invalidate("https://10.0.0.1:6443").unwrap();
+    assert!(!dir.join("10.0.0.1_6443").exists());
+    cache.invalidate("https://10.0.0.1:6443").unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,169 @@
+//! Resolving the apiserver's name.
+//!
+//! By default names are resolved with the system's (blocking)
+//! `getaddrinfo`, on a small pool of threads so that a slow lookup
+//! doesn't hold up the client's other work.  An asynchronous resolver,
+//! eg: from trust-dns, can be used instead by implementing `Resolver`
+//! for it and passing it to `ClientBuilder::resolver`:
+//!
+//! ```
+//! # extern crate futures;
+//! # extern crate kubernetes_holding;
+//! # use std::io;
+//! # use std::net::{IpAddr, Ipv4Addr};
+//! # use std::sync::Arc;
+//! use futures::{future, Future};
+//! use kubernetes_holding::client::dns::{IpPreference, Resolver};
+//! use kubernetes_holding::client::Client;
+//!
+//! /// Everything is on this machine.
+//! #[derive(Debug)]
+//! struct Localhost;
+//!
+//! impl Resolver for Localhost {
+//!     fn resolve(
+//!         &self,
+//!         _host: &str,
+//!     ) -> Box<dyn Future<Item = Vec<IpAddr>, Error = io::Error> + Send> {
+//!         Box::new(future::ok(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]))
+//!     }
+//! }
+//!
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::builder()
+//!     .config(Default::default())
+//!     .resolver(Arc::new(Localhost))
+//!     .ip_preference(IpPreference::V4Only)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::vec;
+
+use futures::{future, Future};
+use hyper::client::connect::dns::{GaiResolver, Name, Resolve};
+
+/// Resolves host names to addresses.
+pub trait Resolver: fmt::Debug + Send + Sync {
+    /// The addresses of `host`, in the order to try them.
+    fn resolve(&self, host: &str) -> Box<dyn Future<Item = Vec<IpAddr>, Error = io::Error> + Send>;
+}
+
+/// `getaddrinfo`, on a pool of threads.
+#[derive(Debug, Clone)]
+pub struct ThreadPoolResolver {
+    inner: GaiResolver,
+}
+
+impl ThreadPoolResolver {
+    /// Allow up to `threads` lookups at once.
+    pub fn new(threads: usize) -> Self {
+        ThreadPoolResolver {
+            inner: GaiResolver::new(threads),
+        }
+    }
+}
+
+impl Resolver for ThreadPoolResolver {
+    fn resolve(&self, host: &str) -> Box<dyn Future<Item = Vec<IpAddr>, Error = io::Error> + Send> {
+        match host.parse::<Name>() {
+            Ok(name) => Box::new(self.inner.resolve(name).map(Iterator::collect)),
+            Err(e) => Box::new(future::err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                e.to_string(),
+            ))),
+        }
+    }
+}
+
+/// Which addresses to connect to, when a name has both IPv4 and IPv6
+/// addresses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpPreference {
+    /// In the order the resolver gives them.
+    #[default]
+    Any,
+    /// IPv4 first, then IPv6.
+    PreferV4,
+    /// IPv6 first, then IPv4.
+    PreferV6,
+    V4Only,
+    V6Only,
+}
+
+impl IpPreference {
+    /// Order (or filter) `addrs` by this preference.
+    fn apply(self, mut addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+        match self {
+            IpPreference::Any => (),
+            // Stable, so the resolver's order is kept otherwise.
+            IpPreference::PreferV4 => addrs.sort_by_key(IpAddr::is_ipv6),
+            IpPreference::PreferV6 => addrs.sort_by_key(IpAddr::is_ipv4),
+            IpPreference::V4Only => addrs.retain(IpAddr::is_ipv4),
+            IpPreference::V6Only => addrs.retain(IpAddr::is_ipv6),
+        }
+        addrs
+    }
+}
+
+/// The `Resolver` and `IpPreference` together, as hyper's connector
+/// wants them.
+#[derive(Debug, Clone)]
+pub(crate) struct DnsResolver {
+    pub resolver: Arc<dyn Resolver>,
+    pub preference: IpPreference,
+}
+
+impl Resolve for DnsResolver {
+    type Addrs = vec::IntoIter<IpAddr>;
+    type Future = Box<dyn Future<Item = Self::Addrs, Error = io::Error> + Send>;
+
+    fn resolve(&self, name: Name) -> Self::Future {
+        let preference = self.preference;
+        let host = name.as_str().to_string();
+        Box::new(self.resolver.resolve(&host).and_then(move |addrs| {
+            let addrs = preference.apply(addrs);
+            if addrs.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No {:?} addresses found for {}", preference, host),
+                ));
+            }
+            Ok(addrs.into_iter())
+        }))
+    }
+}
+
+#[test]
+fn test_ip_preference() {
+    let v4: IpAddr = "10.0.0.1".parse().unwrap();
+    let v6: IpAddr = "fd00::1".parse().unwrap();
+    let v4b: IpAddr = "10.0.0.2".parse().unwrap();
+    let addrs = vec![v6, v4, v4b];
+    assert_eq!(IpPreference::Any.apply(addrs.clone()), vec![v6, v4, v4b]);
+    assert_eq!(
+        IpPreference::PreferV4.apply(addrs.clone()),
+        vec![v4, v4b, v6]
+    );
+    assert_eq!(
+        IpPreference::PreferV6.apply(addrs.clone()),
+        vec![v6, v4, v4b]
+    );
+    assert_eq!(IpPreference::V4Only.apply(addrs.clone()), vec![v4, v4b]);
+    assert_eq!(IpPreference::V6Only.apply(addrs), vec![v6]);
+
+    let resolver = DnsResolver {
+        resolver: Arc::new(ThreadPoolResolver::new(1)),
+        preference: IpPreference::V6Only,
+    };
+    let err = resolver
+        .resolve("127.0.0.1".parse().unwrap())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
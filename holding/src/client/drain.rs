@@ -0,0 +1,438 @@
+//! Cordoning and draining nodes for maintenance, like `kubectl cordon`
+//! and `kubectl drain`.
+//!
+//! ```no_run
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use kubernetes_holding::client::drain::DrainOptions;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let opts = DrainOptions {
+//!     ignore_daemonsets: true,
+//!     ..Default::default()
+//! };
+//! let mut rt = tokio::runtime::Runtime::new()?;
+//! let drained = rt.block_on(client.drain("node-1", opts))?;
+//! println!("evicted {:?}", drained.evicted);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use failure::Error;
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request};
+use serde::de::IgnoredAny;
+use serde_json::{self, Value};
+use tokio::timer::Delay;
+
+use api::meta::v1::{DeleteOptions, ListOptions};
+use api::meta::GroupVersionResource;
+use api::MERGE_PATCH;
+use k8sclient::error::ApiError;
+use k8sclient::selector::{fields, FieldSelector};
+
+use super::apply::MAX_CONFLICTS;
+use super::{do_request_opt, hyper_uri, retry, status_code, wait, Client};
+
+const NODES: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "nodes",
+};
+
+const PODS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "pods",
+};
+
+/// Set by the kubelet on the apiserver's copies of static pods.
+const MIRROR_ANNOTATION: &str = "kubernetes.io/config.mirror";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrainOptions {
+    /// Leave pods managed by DaemonSets, which would only be recreated
+    /// on the node.  Otherwise they prevent the drain.
+    pub ignore_daemonsets: bool,
+    /// Evict pods with emptyDir volumes, losing their data.  Otherwise
+    /// they prevent the drain.
+    pub delete_emptydir_data: bool,
+    /// Evict pods not managed by a controller, which will not be
+    /// recreated.  Otherwise they prevent the drain.
+    pub force: bool,
+    /// Override the pods' own termination grace periods.
+    pub grace_period_seconds: Option<u64>,
+    /// Only evict pods matching this label selector.
+    pub pod_selector: String,
+    /// How long to wait before trying again to evict a pod when a
+    /// PodDisruptionBudget does not allow it yet, if the server doesn't
+    /// say.
+    pub eviction_retry_interval: Duration,
+}
+
+impl Default for DrainOptions {
+    fn default() -> Self {
+        DrainOptions {
+            ignore_daemonsets: false,
+            delete_emptydir_data: false,
+            force: false,
+            grace_period_seconds: None,
+            pod_selector: String::new(),
+            eviction_retry_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The pods on a drained node, as `namespace/name`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Drained {
+    /// Evicted, and now gone.
+    pub evicted: Vec<String>,
+    /// Left on the node: mirror pods, and DaemonSet pods if
+    /// `ignore_daemonsets`.
+    pub skipped: Vec<String>,
+}
+
+/// Some pods on the node can't be evicted with the `DrainOptions`
+/// given.  Nothing was evicted, but the node was cordoned.
+#[derive(Debug, Fail)]
+pub struct UndrainableError {
+    pub node: String,
+    /// `namespace/name (reason)`
+    pub pods: Vec<String>,
+}
+
+impl fmt::Display for UndrainableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot drain node {}: {}",
+            self.node,
+            self.pods.join(", ")
+        )
+    }
+}
+
+enum Action {
+    Evict,
+    Skip,
+    Refuse(&'static str),
+}
+
+fn classify(pod: &Value, opts: &DrainOptions) -> Action {
+    let meta = &pod["metadata"];
+    if !meta["annotations"][MIRROR_ANNOTATION].is_null() {
+        return Action::Skip;
+    }
+    let controller = meta["ownerReferences"]
+        .as_array()
+        .and_then(|refs| refs.iter().find(|r| r["controller"] == true));
+    if controller.is_some_and(|c| c["kind"] == "DaemonSet") {
+        return if opts.ignore_daemonsets {
+            Action::Skip
+        } else {
+            Action::Refuse("managed by a DaemonSet")
+        };
+    }
+    // Finished pods have nothing left to lose.
+    let phase = &pod["status"]["phase"];
+    if phase == "Succeeded" || phase == "Failed" {
+        return Action::Evict;
+    }
+    let empty_dir = pod["spec"]["volumes"]
+        .as_array()
+        .is_some_and(|vs| vs.iter().any(|v| !v["emptyDir"].is_null()));
+    if empty_dir && !opts.delete_emptydir_data {
+        return Action::Refuse("has emptyDir data");
+    }
+    if controller.is_none() && !opts.force {
+        return Action::Refuse("not managed by a controller");
+    }
+    Action::Evict
+}
+
+fn pod_name(pod: &Value) -> String {
+    format!(
+        "{}/{}",
+        pod["metadata"]["namespace"].as_str().unwrap_or_default(),
+        pod["metadata"]["name"].as_str().unwrap_or_default()
+    )
+}
+
+impl Client {
+    /// Mark `node` unschedulable, so that no new pods are placed on it.
+    /// Returns the node.
+    pub fn cordon(&self, node: &str) -> impl Future<Item = Value, Error = Error> + Send {
+        self.set_unschedulable(node, true)
+    }
+
+    /// Allow new pods to be placed on `node` again.  Returns the node.
+    pub fn uncordon(&self, node: &str) -> impl Future<Item = Value, Error = Error> + Send {
+        self.set_unschedulable(node, false)
+    }
+
+    fn set_unschedulable(
+        &self,
+        node: &str,
+        unschedulable: bool,
+    ) -> impl Future<Item = Value, Error = Error> + Send {
+        // Unset rather than false, like kubectl.
+        let patch =
+            json!({"spec": {"unschedulable": if unschedulable { Some(true) } else { None }}});
+        let client = self.clone();
+        let node = node.to_string();
+        // The patch only touches one field, but the apiserver can still
+        // give up with a conflict when the node is being updated often,
+        // as the kubelet does with its status.
+        future::loop_fn(0, move |attempt| {
+            client
+                .patch(&NODES, None, &node, MERGE_PATCH, &patch)
+                .then(move |r| match r {
+                    Ok(node) => Ok(Loop::Break(node)),
+                    Err(ref e) if status_code(e) == Some(409) && attempt + 1 < MAX_CONFLICTS => {
+                        debug!("Conflict patching node, retrying");
+                        Ok(Loop::Continue(attempt + 1))
+                    }
+                    Err(e) => Err(e),
+                })
+        })
+    }
+
+    /// Cordon `node`, then evict its pods and wait until they are gone.
+    ///
+    /// Evictions respect PodDisruptionBudgets, and are retried until
+    /// the budget allows them; this never times out by itself, see
+    /// `wait::timeout`.  Uses the `policy/v1` eviction API, so
+    /// requires Kubernetes 1.22 or later.  Requires a tokio runtime
+    /// with a timer.
+    pub fn drain(
+        &self,
+        node: &str,
+        opts: DrainOptions,
+    ) -> impl Future<Item = Drained, Error = Error> + Send {
+        let client = self.clone();
+        let node = node.to_string();
+        let list_opts = ListOptions {
+            field_selector: FieldSelector::new()
+                .eq(fields::SPEC_NODE_NAME, node.as_str())
+                .into(),
+            label_selector: opts.pod_selector.clone(),
+            ..Default::default()
+        };
+        self.cordon(&node)
+            .and_then(move |_| {
+                client
+                    .list(&PODS, None, list_opts)
+                    .map(move |list: Value| (client, node, opts, list))
+            })
+            .and_then(|(client, node, opts, list)| {
+                let mut evict = Vec::new();
+                let mut drained = Drained::default();
+                let mut refused = Vec::new();
+                let pods = list["items"].as_array().cloned().unwrap_or_default();
+                for pod in pods {
+                    match classify(&pod, &opts) {
+                        Action::Evict => evict.push(pod),
+                        Action::Skip => drained.skipped.push(pod_name(&pod)),
+                        Action::Refuse(why) => {
+                            refused.push(format!("{} ({})", pod_name(&pod), why))
+                        }
+                    }
+                }
+                if !refused.is_empty() {
+                    return Either::A(future::err(
+                        UndrainableError {
+                            node,
+                            pods: refused,
+                        }
+                        .into(),
+                    ));
+                }
+                let evictions = evict.into_iter().map(move |pod| {
+                    let name = pod_name(&pod);
+                    evict_and_wait(client.clone(), pod, opts.clone()).map(|()| name)
+                });
+                Either::B(
+                    future::join_all(evictions).map(move |evicted| Drained { evicted, ..drained }),
+                )
+            })
+    }
+}
+
+/// Evict `pod`, retrying while a PodDisruptionBudget prevents it, and
+/// wait until it is gone.
+fn evict_and_wait(
+    client: Client,
+    pod: Value,
+    opts: DrainOptions,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let meta = &pod["metadata"];
+    let namespace = meta["namespace"].as_str().unwrap_or_default().to_string();
+    let name = meta["name"].as_str().unwrap_or_default().to_string();
+    let uid = meta["uid"].as_str().unwrap_or_default().to_string();
+    let eviction = json!({
+        "apiVersion": "policy/v1",
+        "kind": "Eviction",
+        "metadata": {"name": name, "namespace": namespace},
+        "deleteOptions": DeleteOptions {
+            grace_period_seconds: opts.grace_period_seconds,
+            ..Default::default()
+        },
+    });
+    let client2 = client.clone();
+    let (namespace2, name2) = (namespace.clone(), name.clone());
+    future::loop_fn((), move |()| {
+        let req = client
+            .subresource_url(&PODS, Some(&namespace), &name, "eviction")
+            .and_then(|url| {
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(hyper_uri(url))
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(serde_json::to_vec(&eviction)?))
+                    .map_err(|e| e.into())
+            });
+        let interval = opts.eviction_retry_interval;
+        do_request_opt(Arc::clone(&client.client), client.timeout, req).then(
+            move |res: Result<Option<IgnoredAny>, Error>| match res {
+                Ok(_) => Either::A(future::ok(Loop::Break(()))),
+                Err(ref e) if status_code(e) == Some(404) => Either::A(future::ok(Loop::Break(()))),
+                Err(e) => match ApiError::from_error(&e) {
+                    Some(api) if api.is_too_many_requests() => {
+                        let delay = api
+                            .retry_after()
+                            .map_or(interval, |after| cmp::min(after, retry::MAX_RETRY_AFTER));
+                        debug!("Eviction blocked, retrying in {:?}: {}", delay, api);
+                        Either::B(
+                            Delay::new(Instant::now() + delay)
+                                .from_err()
+                                .map(|()| Loop::Continue(())),
+                        )
+                    }
+                    _ => Either::A(future::err(e)),
+                },
+            },
+        )
+    })
+    .and_then(move |()| wait::await_deletion_of(&client2, &PODS, Some(&namespace2), &name2, &uid))
+}
+
+#[test]
+fn test_cordon() {
+    use api::meta::v1::StatusReason;
+
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let server = client.server();
+    server
+        .insert(&NODES, &json!({"metadata": {"name": "node-1"}, "spec": {}}))
+        .unwrap();
+
+    server.fail_next(
+        "patch",
+        "nodes",
+        ApiError::new(StatusReason::Conflict, "the object has been modified"),
+    );
+    let node = client.cordon("node-1").wait().unwrap();
+    assert_eq!(node["spec"]["unschedulable"], true);
+    // Idempotent.
+    client.cordon("node-1").wait().unwrap();
+    assert_eq!(
+        server.get(&NODES, None, "node-1").unwrap()["spec"]["unschedulable"],
+        true
+    );
+
+    let node = client.uncordon("node-1").wait().unwrap();
+    assert_eq!(node["spec"], json!({}));
+    assert!(client.cordon("node-2").wait().is_err());
+}
+
+#[test]
+fn test_drain() {
+    use api::meta::v1::StatusReason;
+    use tokio::runtime::Runtime;
+
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let server = client.server();
+    server
+        .insert(&NODES, &json!({"metadata": {"name": "node-1"}, "spec": {}}))
+        .unwrap();
+    let pod = |name: &str, node: &str, extra: Value| {
+        let mut pod = json!({
+            "metadata": {
+                "name": name,
+                "namespace": "ns",
+                "ownerReferences": [{"kind": "ReplicaSet", "name": "rs", "controller": true}],
+            },
+            "spec": {"nodeName": node},
+        });
+        super::fake::merge_patch(&mut pod, &extra);
+        server.insert(&PODS, &pod).unwrap();
+    };
+    pod("web", "node-1", json!({}));
+    pod("elsewhere", "node-2", json!({}));
+    pod(
+        "agent",
+        "node-1",
+        json!({"metadata": {"ownerReferences": [{"kind": "DaemonSet", "name": "ds", "controller": true}]}}),
+    );
+    pod(
+        "static",
+        "node-1",
+        json!({"metadata": {"annotations": {MIRROR_ANNOTATION: "x"}, "ownerReferences": null}}),
+    );
+    pod(
+        "bare",
+        "node-1",
+        json!({"metadata": {"ownerReferences": null}}),
+    );
+
+    let mut rt = Runtime::new().unwrap();
+    let opts = DrainOptions {
+        eviction_retry_interval: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let err = rt
+        .block_on(client.drain("node-1", opts.clone()))
+        .unwrap_err();
+    let err = err.downcast::<UndrainableError>().unwrap();
+    assert_eq!(
+        err.pods,
+        vec![
+            "ns/agent (managed by a DaemonSet)",
+            "ns/bare (not managed by a controller)"
+        ]
+    );
+    let node = server.get(&NODES, None, "node-1").unwrap();
+    assert_eq!(node["spec"]["unschedulable"], true);
+
+    // One refusal from a PodDisruptionBudget.
+    server.fail_next(
+        "create",
+        "pods/eviction",
+        ApiError::new(StatusReason::TooManyRequests, "Cannot evict pod"),
+    );
+    let opts = DrainOptions {
+        ignore_daemonsets: true,
+        force: true,
+        ..opts
+    };
+    let mut drained = rt.block_on(client.drain("node-1", opts)).unwrap();
+    drained.evicted.sort();
+    drained.skipped.sort();
+    assert_eq!(drained.evicted, vec!["ns/bare", "ns/web"]);
+    assert_eq!(drained.skipped, vec!["ns/agent", "ns/static"]);
+    assert!(server.get(&PODS, Some("ns"), "web").is_none());
+    assert!(server.get(&PODS, Some("ns"), "elsewhere").is_some());
+}
@@ -0,0 +1,138 @@
+//! Client for resources whose type is only known at runtime.
+
+use failure::Error;
+use futures::{Future, Stream};
+use serde::Serialize;
+
+use api::meta::v1::{DeleteOptions, GetOptions, ListOptions, WatchEvent};
+use api::meta::GroupVersionResource;
+use api::unstructured::{DynamicList, DynamicObject};
+
+use super::pager::Page;
+use super::Client;
+
+/// Performs operations on a single `GroupVersionResource`, using
+/// `DynamicObject` in place of a compiled-in type.
+///
+/// Create/update use the namespace from the object's metadata, like
+/// the equivalent `Client` methods.
+#[derive(Debug, Clone)]
+pub struct DynamicClient<'a> {
+    client: &'a Client,
+    gvr: GroupVersionResource<'a>,
+    namespace: Option<&'a str>,
+}
+
+impl<'a> Client {
+    pub fn dynamic(&'a self, gvr: GroupVersionResource<'a>) -> DynamicClient<'a> {
+        DynamicClient {
+            client: self,
+            gvr,
+            namespace: None,
+        }
+    }
+}
+
+impl<'a> DynamicClient<'a> {
+    /// Restrict get/list/watch/patch/delete to the given namespace.
+    pub fn namespace(self, ns: &'a str) -> Self {
+        DynamicClient {
+            namespace: Some(ns),
+            ..self
+        }
+    }
+
+    pub fn gvr(&self) -> &GroupVersionResource<'a> {
+        &self.gvr
+    }
+
+    pub fn get(
+        &self,
+        name: &str,
+        opts: GetOptions,
+    ) -> impl Future<Item = DynamicObject, Error = Error> + Send {
+        self.client.get(&self.gvr, self.namespace, name, opts)
+    }
+
+    /// Like `get`, but returns `None` if the object does not exist.
+    pub fn get_opt(
+        &self,
+        name: &str,
+        opts: GetOptions,
+    ) -> impl Future<Item = Option<DynamicObject>, Error = Error> + Send {
+        self.client.get_opt(&self.gvr, self.namespace, name, opts)
+    }
+
+    pub fn list(&self, opts: ListOptions) -> impl Future<Item = DynamicList, Error = Error> + Send {
+        self.client.list(&self.gvr, self.namespace, opts)
+    }
+
+    /// Like `list`, but follows `continue` tokens and yields individual items.
+    pub fn iter(
+        &self,
+        opts: ListOptions,
+    ) -> impl Stream<Item = DynamicObject, Error = Error> + Send {
+        self.client
+            ._do_iter::<DynamicList>(self.gvr.clone(), self.namespace, opts)
+    }
+
+    /// Like `iter`, but yields each page, with the list's metadata.
+    pub fn pages(
+        &self,
+        opts: ListOptions,
+    ) -> impl Stream<Item = Page<DynamicObject>, Error = Error> + Send {
+        self.client
+            ._do_pages::<DynamicList>(self.gvr.clone(), self.namespace, opts)
+    }
+
+    pub fn watch(
+        &self,
+        name: &str,
+        opts: ListOptions,
+    ) -> impl Stream<Item = WatchEvent, Error = Error> + Send {
+        self.client.watch(&self.gvr, self.namespace, name, opts)
+    }
+
+    pub fn watch_list(
+        &self,
+        opts: ListOptions,
+    ) -> impl Stream<Item = WatchEvent, Error = Error> + Send {
+        self.client.watch_list(&self.gvr, self.namespace, opts)
+    }
+
+    pub fn create(
+        &self,
+        value: &DynamicObject,
+        opts: GetOptions,
+    ) -> impl Future<Item = DynamicObject, Error = Error> + Send {
+        self.client.create(&self.gvr, value, opts)
+    }
+
+    pub fn update(
+        &self,
+        value: &DynamicObject,
+    ) -> impl Future<Item = DynamicObject, Error = Error> + Send {
+        self.client.update(&self.gvr, value)
+    }
+
+    pub fn patch<T>(
+        &self,
+        name: &str,
+        patch_type: &str,
+        value: &T,
+    ) -> impl Future<Item = DynamicObject, Error = Error> + Send
+    where
+        T: Serialize,
+    {
+        self.client
+            .patch(&self.gvr, self.namespace, name, patch_type, value)
+    }
+
+    pub fn delete(
+        &self,
+        name: &str,
+        opts: DeleteOptions,
+    ) -> impl Future<Item = (), Error = Error> + Send {
+        self.client.delete(&self.gvr, self.namespace, name, opts)
+    }
+}
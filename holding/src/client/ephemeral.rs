@@ -0,0 +1,150 @@
+//! The `ephemeralcontainers` subresource of pods, for adding debugging
+//! containers to running pods, like `kubectl debug`.
+//!
+//! Requires Kubernetes 1.23 or later (or 1.22 with the
+//! `EphemeralContainers` feature gate); earlier versions served a
+//! different, alpha, API.
+
+use std::sync::Arc;
+
+use failure::Error;
+use futures::Future;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request};
+use serde_json;
+use url::Url;
+
+use api::core::v1::{EphemeralContainer, Pod, TypedResource};
+
+use super::{do_request, hyper_uri, require_name, Client};
+
+impl Client {
+    fn ephemeral_containers_url(&self, namespace: &str, name: &str) -> Result<Url, Error> {
+        self.subresource_url(
+            &Pod::GROUP_VERSION_RESOURCE,
+            Some(namespace),
+            name,
+            "ephemeralcontainers",
+        )
+    }
+
+    /// The pod, as read through the `ephemeralcontainers` subresource.
+    pub fn get_ephemeral_containers(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> impl Future<Item = Pod, Error = Error> + Send {
+        let req = self
+            .ephemeral_containers_url(namespace, name)
+            .and_then(|url| {
+                Request::builder()
+                    .method(Method::GET)
+                    .uri(hyper_uri(url))
+                    .body(Body::empty())
+                    .map_err(|e| e.into())
+            });
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
+    /// Replace `pod`'s ephemeral containers with those in
+    /// `pod.spec.ephemeral_containers`.  The rest of `pod` is ignored,
+    /// apart from its resourceVersion.
+    ///
+    /// Ephemeral containers can be added, but not changed or removed.
+    pub fn update_ephemeral_containers(
+        &self,
+        pod: &Pod,
+    ) -> impl Future<Item = Pod, Error = Error> + Send {
+        let req = || -> Result<_, Error> {
+            let namespace = pod
+                .metadata
+                .namespace
+                .as_ref()
+                .ok_or_else(|| format_err!("Pod has no namespace"))?;
+            let name = require_name(&pod.metadata)?;
+            let json = serde_json::to_vec(pod)?;
+
+            Request::builder()
+                .method(Method::PUT)
+                .uri(hyper_uri(self.ephemeral_containers_url(namespace, name)?))
+                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                .body(Body::from(json))
+                .map_err(|e| e.into())
+        }();
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
+    /// Add `container` to a running pod.
+    ///
+    /// Fails with a conflict if the pod changes while this is in
+    /// progress.  The container is started asynchronously; see
+    /// `status.ephemeral_container_statuses`.
+    pub fn add_ephemeral_container(
+        &self,
+        namespace: &str,
+        name: &str,
+        container: EphemeralContainer,
+    ) -> impl Future<Item = Pod, Error = Error> + Send {
+        let client = self.clone();
+        self.get_ephemeral_containers(namespace, name)
+            .and_then(move |mut pod| {
+                pod.spec.ephemeral_containers.push(container);
+                client.update_ephemeral_containers(&pod)
+            })
+    }
+}
+
+#[test]
+fn test_ephemeral_containers() {
+    use api::core::v1::Container;
+    use api::meta::v1::GetOptions;
+
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let mut pod = Pod::default();
+    pod.metadata.name = Some("web-0".to_string());
+    pod.metadata.namespace = Some("ns".to_string());
+    pod.spec.containers.push(Container {
+        name: "web".to_string(),
+        ..Default::default()
+    });
+    let _: Pod = client
+        .create(&Pod::GROUP_VERSION_RESOURCE, &pod, GetOptions::default())
+        .wait()
+        .unwrap();
+
+    let debugger = EphemeralContainer {
+        container: Container {
+            name: "debugger".to_string(),
+            image: Some("busybox".to_string()),
+            ..Default::default()
+        },
+        target_container_name: Some("web".to_string()),
+    };
+    let updated = client
+        .add_ephemeral_container("ns", "web-0", debugger.clone())
+        .wait()
+        .unwrap();
+    assert_eq!(updated.spec.ephemeral_containers, vec![debugger]);
+
+    let actions: Vec<_> = client
+        .server()
+        .actions()
+        .into_iter()
+        .map(|a| (a.verb, a.resource))
+        .collect();
+    assert_eq!(
+        &actions[1..],
+        &[
+            ("get".to_string(), "pods/ephemeralcontainers".to_string()),
+            ("update".to_string(), "pods/ephemeralcontainers".to_string()),
+        ]
+    );
+
+    // Only the ephemeral containers are changed.
+    let mut changed = updated.clone();
+    changed.spec.containers.clear();
+    let updated = client.update_ephemeral_containers(&changed).wait().unwrap();
+    assert_eq!(updated.spec.containers.len(), 1);
+}
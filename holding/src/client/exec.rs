@@ -0,0 +1,883 @@
+//! Running commands in containers, like `kubectl exec`, and attaching to
+//! them, like `kubectl attach`.
+//!
+//! The command's input and output are carried over a WebSocket or, if
+//! the apiserver (or a proxy in front of it) refuses one, SPDY.  Either
+//! needs an HTTP/1.1 connection, so a client built with `build` (which
+//! may otherwise use HTTP/2) opens one just for the command.  A custom
+//! backend must speak HTTP/1.1 itself.  Middleware that replaces
+//! response bodies (such as `record::Recorder`) breaks the upgrade.
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let mut rt = tokio::runtime::Runtime::new()?;
+//! let output = rt.block_on(client.exec_output(
+//!     "default",
+//!     "web-0",
+//!     &["cat", "/etc/hostname"],
+//!     None,
+//!     Default::default(),
+//! ))?;
+//! println!("{}", String::from_utf8_lossy(&output.stdout));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use failure::Error;
+use futures::future::{self, Either};
+use futures::sync::mpsc;
+use futures::{stream, Async, Future, Poll, Stream};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Method, Request, Response, StatusCode, Version};
+use serde_json::{self, Value};
+use tokio;
+use tokio::io::{AsyncRead, AsyncWrite};
+use url::Url;
+
+use api::meta::GroupVersionResource;
+
+use super::spdy::{self, Frame, Frames, FLAG_FIN};
+use super::transport::Transport;
+use super::websocket::{self, Message, Messages, BINARY, CLOSE, PING, PONG};
+use super::{error_response, hyper_uri, Client};
+
+const PODS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "pods",
+};
+
+/// Adds closing a channel (Kubernetes 1.29 and later).
+const V5: &str = "v5.channel.k8s.io";
+const V4: &str = "v4.channel.k8s.io";
+
+// The channels multiplexed over the WebSocket, each message starting
+// with the channel it belongs to.
+const STDIN: u8 = 0;
+const STDOUT: u8 = 1;
+const STDERR: u8 = 2;
+/// The command's exit status, as a `Status`.
+const ERROR: u8 = 3;
+/// v5 only: the rest of the message is the channel being closed.
+const CLOSE_CHANNEL: u8 = 255;
+
+/// What carries a session's channels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Protocol {
+    /// Speaking `v5.channel.k8s.io` if `v5`, else v4.
+    WebSocket { v5: bool },
+    /// With a SPDY stream for each channel.
+    Spdy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecOptions {
+    /// Needed if the pod has more than one container.
+    pub container: Option<String>,
+    /// Run the command in a terminal, which sends all its output as
+    /// stdout.
+    pub tty: bool,
+}
+
+/// Output from a command, as it arrives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+/// All the output of a command that succeeded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// A command failed, or could not be run.
+#[derive(Debug, Fail)]
+#[fail(display = "{}", message)]
+pub struct ExecError {
+    pub message: String,
+    /// `None` if the command didn't run.
+    pub exit_code: Option<i32>,
+    /// The command's standard error, when collected by `exec_output`.
+    pub stderr: String,
+}
+
+/// Data for a command's standard input.
+pub type Input = Box<dyn Stream<Item = Vec<u8>, Error = Error> + Send>;
+
+impl Client {
+    /// Run `command` in `pod`, yielding its output as it arrives.  The
+    /// stream ends when the command exits, failing with an `ExecError`
+    /// if it didn't succeed.
+    ///
+    /// `stdin`, if given, is sent as the command's standard input.
+    /// Over a WebSocket, only Kubernetes 1.29 and later can then close
+    /// it; before that the command never sees the end of its input.
+    ///
+    /// Requires a tokio runtime.
+    pub fn exec(
+        &self,
+        namespace: &str,
+        pod: &str,
+        command: &[&str],
+        stdin: Option<Input>,
+        opts: ExecOptions,
+    ) -> impl Stream<Item = Output, Error = Error> + Send {
+        let url = self
+            .subresource_url(&PODS, Some(namespace), pod, "exec")
+            .map(|mut url| {
+                {
+                    let mut query = url.query_pairs_mut();
+                    for arg in command {
+                        query.append_pair("command", arg);
+                    }
+                }
+                channels_query(url, stdin.is_some(), &opts)
+            });
+        stream(Arc::clone(&self.client), url, stdin, opts.tty)
+    }
+
+    /// Attach to the main process of `pod`'s container, like `kubectl
+    /// attach`, yielding its output from now on.  The stream ends when
+    /// the process exits.
+    ///
+    /// `stdin`, if given, is sent to the process, if its container has
+    /// `stdin: true`; as with `exec`, it can only be closed over a
+    /// WebSocket from Kubernetes 1.29.  `opts.tty` must match the
+    /// container's `tty`.
+    ///
+    /// Requires a tokio runtime.
+    pub fn attach(
+        &self,
+        namespace: &str,
+        pod: &str,
+        stdin: Option<Input>,
+        opts: ExecOptions,
+    ) -> impl Stream<Item = Output, Error = Error> + Send {
+        let url = self
+            .subresource_url(&PODS, Some(namespace), pod, "attach")
+            .map(|url| channels_query(url, stdin.is_some(), &opts));
+        stream(Arc::clone(&self.client), url, stdin, opts.tty)
+    }
+
+    /// Run `command` in `pod` to completion, collecting its output.
+    ///
+    /// Requires a tokio runtime.
+    pub fn exec_output(
+        &self,
+        namespace: &str,
+        pod: &str,
+        command: &[&str],
+        stdin: Option<Vec<u8>>,
+        opts: ExecOptions,
+    ) -> impl Future<Item = ExecOutput, Error = Error> + Send {
+        let stdin = stdin.map(|data| Box::new(stream::once(Ok(data))) as Input);
+        collect(self.exec(namespace, pod, command, stdin, opts))
+    }
+}
+
+/// Add the channels an exec or attach session wants to `url`.
+fn channels_query(mut url: Url, stdin: bool, opts: &ExecOptions) -> Url {
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some(ref container) = opts.container {
+            query.append_pair("container", container);
+        }
+        query
+            .append_pair("stdin", &stdin.to_string())
+            .append_pair("stdout", "true")
+            .append_pair("stderr", &(!opts.tty).to_string())
+            .append_pair("tty", &opts.tty.to_string());
+    }
+    url
+}
+
+/// Connect to the exec or attach `url`, and read the output.
+fn stream(
+    transport: Arc<Transport>,
+    url: Result<Url, Error>,
+    stdin: Option<Input>,
+    tty: bool,
+) -> impl Stream<Item = Output, Error = Error> + Send {
+    future::result(url)
+        .and_then(move |url| connect(transport, url, &[V5, V4], &[V4]))
+        .map(move |(io, protocol)| match protocol {
+            Protocol::WebSocket { v5 } => Either::A(session(io, v5, stdin)),
+            Protocol::Spdy => Either::B(spdy_session(io, stdin, tty)),
+        })
+        .flatten_stream()
+}
+
+/// Upgrade a connection for `url` to a WebSocket speaking one of
+/// `websocket` or, if the server refuses, SPDY speaking one of `spdy`.
+pub(crate) fn connect(
+    transport: Arc<Transport>,
+    url: Url,
+    websocket: &'static [&'static str],
+    spdy: &'static [&'static str],
+) -> impl Future<Item = (Upgraded, Protocol), Error = Error> + Send {
+    let request = move |use_spdy: bool| -> Result<Request<Body>, Error> {
+        let mut req = Request::builder()
+            .method(Method::GET)
+            .uri(hyper_uri(url.clone()))
+            .body(Body::empty())?;
+        if use_spdy {
+            spdy::upgrade_request(&mut req, spdy);
+        } else {
+            websocket::upgrade_request(&mut req, websocket);
+        }
+        Ok(req)
+    };
+    let retry = Arc::clone(&transport);
+    future::result(request(false))
+        .and_then(move |req| transport.upgrade(req))
+        .and_then(move |res| {
+            // HTTP/2 can't upgrade to SPDY either.
+            if res.status() == StatusCode::SWITCHING_PROTOCOLS || res.version() == Version::HTTP_2 {
+                return Either::A(upgrade(res, false));
+            }
+            debug!("WebSocket refused ({}), trying SPDY", res.status());
+            Either::B(
+                future::result(request(true))
+                    .and_then(move |req| retry.upgrade(req))
+                    .and_then(|res| upgrade(res, true)),
+            )
+        })
+}
+
+fn upgrade(
+    res: Response<Body>,
+    spdy: bool,
+) -> impl Future<Item = (Upgraded, Protocol), Error = Error> {
+    let status = res.status();
+    if status != StatusCode::SWITCHING_PROTOCOLS {
+        let version = res.version();
+        return Either::A(res.into_body().concat2().from_err().and_then(move |body| {
+            if version == Version::HTTP_2 {
+                Err(format_err!(
+                    "Streaming needs HTTP/1.1, but the server chose HTTP/2; \
+                     the client's backend must offer only HTTP/1.1"
+                ))
+            } else {
+                Err(error_response(status, body.as_ref()).into())
+            }
+        }));
+    }
+    let protocol = if spdy {
+        Protocol::Spdy
+    } else {
+        let v5 = res
+            .headers()
+            .get("sec-websocket-protocol")
+            .is_some_and(|p| p == V5);
+        Protocol::WebSocket { v5 }
+    };
+    Either::B(
+        res.into_body()
+            .on_upgrade()
+            .from_err()
+            .map(move |io| (io, protocol)),
+    )
+}
+
+/// Write `frames` to `write`, in the background.
+pub(crate) fn send<W, S>(write: W, frames: S)
+where
+    W: AsyncWrite + Send + 'static,
+    S: Stream<Item = Vec<u8>, Error = Error> + Send + 'static,
+{
+    tokio::spawn(
+        frames
+            .fold(write, |write, frame| {
+                tokio::io::write_all(write, frame)
+                    .and_then(|(write, _)| tokio::io::flush(write))
+                    .from_err::<Error>()
+            })
+            .map(|_| ())
+            .map_err(|e| warn!("Failed to send command input: {}", e)),
+    );
+}
+
+/// Send `stdin` over `io` (in the background), and read the output.
+fn session<T>(io: T, v5: bool, stdin: Option<Input>) -> Session<tokio::io::ReadHalf<T>>
+where
+    T: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read, write) = io.split();
+    let (pong, pongs) = mpsc::unbounded();
+    let input = match stdin {
+        Some(stdin) => {
+            let close = if v5 {
+                Some(websocket::encode(BINARY, &[CLOSE_CHANNEL, STDIN]))
+            } else {
+                None
+            };
+            Either::A(
+                stdin
+                    .map(|data| {
+                        let mut message = Vec::with_capacity(data.len() + 1);
+                        message.push(STDIN);
+                        message.extend(data);
+                        websocket::encode(BINARY, &message)
+                    })
+                    .chain(stream::iter_ok(close)),
+            )
+        }
+        None => Either::B(stream::empty()),
+    };
+    let frames = input.select(
+        pongs
+            .map(|data: Vec<u8>| websocket::encode(PONG, &data))
+            .map_err(|()| format_err!("Pong channel failed")),
+    );
+    send(write, frames);
+    Session {
+        messages: Messages::new(read),
+        pong,
+        status: Vec::new(),
+        done: false,
+    }
+}
+
+struct Session<R> {
+    messages: Messages<R>,
+    pong: mpsc::UnboundedSender<Vec<u8>>,
+    status: Vec<u8>,
+    done: bool,
+}
+
+impl<R: AsyncRead> Stream for Session<R> {
+    type Item = Output;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Output>, Error> {
+        while !self.done {
+            let message = match self.messages.poll() {
+                Ok(Async::Ready(message)) => message,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            };
+            match message {
+                Some(Message {
+                    opcode: BINARY,
+                    data,
+                }) => match data.split_first() {
+                    // Each channel starts with an empty message.
+                    Some((_, [])) => {}
+                    Some((&STDOUT, rest)) => {
+                        return Ok(Async::Ready(Some(Output::Stdout(rest.to_vec()))))
+                    }
+                    Some((&STDERR, rest)) => {
+                        return Ok(Async::Ready(Some(Output::Stderr(rest.to_vec()))))
+                    }
+                    Some((&ERROR, rest)) => self.status.extend_from_slice(rest),
+                    _ => {}
+                },
+                Some(Message { opcode: PING, data }) => {
+                    // Only fails once the writer has gone, and with it
+                    // the connection.
+                    let _ = self.pong.unbounded_send(data);
+                }
+                Some(Message { opcode: CLOSE, .. }) | None => {
+                    self.done = true;
+                    exit_status(&self.status)?;
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(Async::Ready(None))
+    }
+}
+
+/// Open the command's channels as streams over `io`, send `stdin` on
+/// its stream (in the background), and read the output from the
+/// others.
+fn spdy_session<T>(io: T, stdin: Option<Input>, tty: bool) -> SpdySession<tokio::io::ReadHalf<T>>
+where
+    T: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read, write) = io.split();
+    let (pong, pongs) = mpsc::unbounded();
+    // The apiserver expects the streams in this order, and only those
+    // the query asked for.
+    let mut streams = spdy::Streams::new();
+    let mut syns = Vec::new();
+    let mut open = |kind| {
+        let (stream, syn) = streams.open(&[("streamtype", kind)]);
+        syns.push(syn);
+        stream
+    };
+    let error = open("error");
+    let stdin = stdin.map(|stdin| (open("stdin"), stdin));
+    let stdout = open("stdout");
+    let stderr = if tty { None } else { Some(open("stderr")) };
+    let input = match stdin {
+        Some((stream, stdin)) => Either::A(
+            stdin
+                .map(move |data| spdy::data(stream, 0, &data))
+                .chain(stream::once(Ok(spdy::data(stream, FLAG_FIN, &[])))),
+        ),
+        None => Either::B(stream::empty()),
+    };
+    let frames = stream::iter_ok(syns).chain(input).select(
+        pongs
+            .map(|data: Vec<u8>| spdy::control(spdy::PING, 0, &data))
+            .map_err(|()| format_err!("Pong channel failed")),
+    );
+    send(write, frames);
+    SpdySession {
+        frames: Frames::new(read),
+        pong,
+        error,
+        stdout,
+        stderr,
+        open: vec![error, stdout].into_iter().chain(stderr).collect(),
+        status: Vec::new(),
+        done: false,
+    }
+}
+
+struct SpdySession<R> {
+    frames: Frames<R>,
+    pong: mpsc::UnboundedSender<Vec<u8>>,
+    error: u32,
+    stdout: u32,
+    stderr: Option<u32>,
+    /// The streams the server has yet to finish sending.
+    open: Vec<u32>,
+    status: Vec<u8>,
+    done: bool,
+}
+
+impl<R: AsyncRead> Stream for SpdySession<R> {
+    type Item = Output;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Output>, Error> {
+        while !self.done {
+            if self.open.is_empty() {
+                self.done = true;
+                exit_status(&self.status)?;
+                break;
+            }
+            let frame = match self.frames.poll() {
+                Ok(Async::Ready(frame)) => frame,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            };
+            match frame {
+                Some(Frame::Data {
+                    stream,
+                    flags,
+                    data,
+                }) => {
+                    if flags & FLAG_FIN != 0 {
+                        self.open.retain(|&s| s != stream);
+                    }
+                    match data {
+                        _ if stream == self.error => self.status.extend(data),
+                        ref data if data.is_empty() => {}
+                        _ if stream == self.stdout => {
+                            return Ok(Async::Ready(Some(Output::Stdout(data))))
+                        }
+                        _ if Some(stream) == self.stderr => {
+                            return Ok(Async::Ready(Some(Output::Stderr(data))))
+                        }
+                        _ => {}
+                    }
+                }
+                Some(Frame::Control {
+                    kind: spdy::PING,
+                    data,
+                    ..
+                }) => {
+                    // As for WebSocket pings.
+                    let _ = self.pong.unbounded_send(data);
+                }
+                Some(Frame::Control {
+                    kind: spdy::RST_STREAM,
+                    data,
+                    ..
+                }) => {
+                    if let Some(stream) = spdy::stream_id(&data) {
+                        self.open.retain(|&s| s != stream);
+                    }
+                }
+                Some(Frame::Control {
+                    kind: spdy::GOAWAY, ..
+                })
+                | None => self.open.clear(),
+                Some(_) => {}
+            }
+        }
+        Ok(Async::Ready(None))
+    }
+}
+
+/// Interpret the `Status` the server sends when the command exits.
+fn exit_status(status: &[u8]) -> Result<(), ExecError> {
+    if status.is_empty() {
+        return Ok(());
+    }
+    let status: Value = serde_json::from_slice(status)
+        .unwrap_or_else(|_| json!({"message": String::from_utf8_lossy(status)}));
+    if status["status"] == "Success" {
+        return Ok(());
+    }
+    let exit_code = status["details"]["causes"]
+        .as_array()
+        .and_then(|causes| causes.iter().find(|c| c["reason"] == "ExitCode"))
+        .and_then(|cause| cause["message"].as_str())
+        .and_then(|code| code.parse().ok());
+    Err(ExecError {
+        message: status["message"]
+            .as_str()
+            .unwrap_or("command failed")
+            .to_string(),
+        exit_code,
+        stderr: String::new(),
+    })
+}
+
+fn collect<S>(output: S) -> impl Future<Item = ExecOutput, Error = Error> + Send
+where
+    S: Stream<Item = Output, Error = Error> + Send,
+{
+    read_output(output, Vec::new(), |mut stdout, data| {
+        stdout.extend(data);
+        Ok(stdout)
+    })
+    .map(|(stdout, stderr)| ExecOutput { stdout, stderr })
+}
+
+/// Fold `f` over a command's stdout, collecting its stderr, which is
+/// added to any `ExecError`.
+pub(crate) fn read_output<S, A, F>(
+    output: S,
+    init: A,
+    mut f: F,
+) -> impl Future<Item = (A, Vec<u8>), Error = Error> + Send
+where
+    S: Stream<Item = Output, Error = Error> + Send,
+    A: Send,
+    F: FnMut(A, Vec<u8>) -> Result<A, Error> + Send,
+{
+    output
+        .then(Ok::<_, Error>)
+        .fold(
+            (init, Vec::new(), None),
+            move |(acc, mut stderr, failed), output| -> Result<_, Error> {
+                Ok(match output {
+                    Ok(Output::Stdout(data)) => (f(acc, data)?, stderr, failed),
+                    Ok(Output::Stderr(data)) => {
+                        stderr.extend(data);
+                        (acc, stderr, failed)
+                    }
+                    Err(e) => (acc, stderr, Some(e)),
+                })
+            },
+        )
+        .and_then(|(acc, stderr, failed)| match failed {
+            None => Ok((acc, stderr)),
+            Some(e) => Err(match e.downcast::<ExecError>() {
+                Ok(mut e) => {
+                    e.stderr = String::from_utf8_lossy(&stderr).into_owned();
+                    e.into()
+                }
+                Err(e) => e,
+            }),
+        })
+}
+
+#[test]
+fn test_session() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use tokio::net::TcpStream;
+    use tokio::runtime::Runtime;
+
+    // A server that says hello, reads the command's input, then fails
+    // it.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let frame = |data: &[u8]| {
+            let mut frame = vec![0x82, data.len() as u8];
+            frame.extend_from_slice(data);
+            frame
+        };
+        for data in &[&b"\x01"[..], b"\x02", b"\x01hello", b"\x02oops"] {
+            conn.write_all(&frame(data)).unwrap();
+        }
+        conn.write_all(&[0x89, 2, b'h', b'b']).unwrap();
+
+        let mut received = Vec::new();
+        let messages = loop {
+            let mut buf = [0; 1024];
+            let n = conn.read(&mut buf).unwrap();
+            received.extend_from_slice(&buf[..n]);
+            match Messages::new(&received[..]).collect().wait() {
+                Ok(ref messages) if messages.len() == 3 => break messages.clone(),
+                _ => {}
+            }
+        };
+
+        let status = json!({
+            "status": "Failure",
+            "reason": "NonZeroExitCode",
+            "message": "command terminated with non-zero exit code: exit status 2",
+            "details": {"causes": [{"reason": "ExitCode", "message": "2"}]},
+        });
+        let mut data = vec![ERROR];
+        data.extend(serde_json::to_vec(&status).unwrap());
+        conn.write_all(&[0x82, 126]).unwrap();
+        conn.write_all(&(data.len() as u16).to_be_bytes()).unwrap();
+        conn.write_all(&data).unwrap();
+        conn.write_all(&[0x88, 0]).unwrap();
+        messages
+    });
+
+    let mut rt = Runtime::new().unwrap();
+    let stdin = Box::new(stream::once(Ok(b"input".to_vec()))) as Input;
+    let err = rt
+        .block_on(
+            TcpStream::connect(&addr)
+                .from_err()
+                .and_then(move |conn| collect(session(conn, true, Some(stdin)))),
+        )
+        .unwrap_err()
+        .downcast::<ExecError>()
+        .unwrap();
+    assert_eq!(err.exit_code, Some(2));
+    assert_eq!(err.stderr, "oops");
+    assert!(err.message.contains("non-zero exit code"));
+
+    let mut messages = server.join().unwrap();
+    messages.sort_by_key(|m| m.data.clone());
+    assert_eq!(
+        messages,
+        vec![
+            Message {
+                opcode: BINARY,
+                data: b"\x00input".to_vec()
+            },
+            Message {
+                opcode: PONG,
+                data: b"hb".to_vec()
+            },
+            Message {
+                opcode: BINARY,
+                data: vec![CLOSE_CHANNEL, STDIN]
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_spdy_fallback() {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use tokio::runtime::Runtime;
+
+    use super::config::ConfigContext;
+
+    fn read_request(conn: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            conn.read_exact(&mut byte).unwrap();
+            request.push(byte[0]);
+        }
+        String::from_utf8(request).unwrap().to_lowercase()
+    }
+
+    // A proxy that refuses WebSockets, in front of an apiserver that
+    // fails the command.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        assert!(read_request(&mut conn).contains("upgrade: websocket"));
+        conn.write_all(
+            b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        )
+        .unwrap();
+        drop(conn);
+
+        let (mut conn, _) = listener.accept().unwrap();
+        let request = read_request(&mut conn);
+        assert!(request.contains("upgrade: spdy/3.1"));
+        assert!(request.contains("x-stream-protocol-version: v4.channel.k8s.io"));
+        conn.write_all(
+            b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\n\
+              Upgrade: SPDY/3.1\r\nX-Stream-Protocol-Version: v4.channel.k8s.io\r\n\r\n",
+        )
+        .unwrap();
+        // Streams 1, 3, 5 and 7 are error, stdin, stdout and stderr.
+        let status = json!({
+            "status": "Failure",
+            "message": "command terminated with non-zero exit code: exit status 2",
+            "details": {"causes": [{"reason": "ExitCode", "message": "2"}]},
+        });
+        for frame in &[
+            // A SYN_REPLY, whose header block isn't read.
+            spdy::control(2, 0, &[0, 0, 0, 1, 0x78]),
+            spdy::control(spdy::PING, 0, &[0, 0, 0, 2]),
+            spdy::data(5, 0, b"hello"),
+            spdy::data(7, 0, b"oops"),
+            spdy::data(1, FLAG_FIN, &serde_json::to_vec(&status).unwrap()),
+            spdy::data(5, FLAG_FIN, b""),
+            spdy::control(spdy::RST_STREAM, 0, &[0, 0, 0, 7, 0, 0, 0, 5]),
+        ] {
+            conn.write_all(frame).unwrap();
+        }
+
+        let mut received = Vec::new();
+        loop {
+            let mut buf = [0; 1024];
+            let n = conn.read(&mut buf).unwrap();
+            received.extend_from_slice(&buf[..n]);
+            let frames = Frames::new(&received[..]).collect().wait();
+            match frames {
+                Ok(ref frames) if frames.len() == 7 => break frames.clone(),
+                _ => {}
+            }
+        }
+    });
+
+    let mut context: ConfigContext = Default::default();
+    context.cluster.server = format!("http://{}", addr);
+    let client = Client::builder()
+        .config(context)
+        .build_with_client(hyper::Client::new())
+        .unwrap();
+    // Kept until the server has read the input sent in the background.
+    let mut rt = Runtime::new().unwrap();
+    let err = rt
+        .block_on(client.exec_output(
+            "default",
+            "web-0",
+            &["cat"],
+            Some(b"input".to_vec()),
+            Default::default(),
+        ))
+        .unwrap_err()
+        .downcast::<ExecError>()
+        .unwrap();
+    assert_eq!(err.exit_code, Some(2));
+    assert_eq!(err.stderr, "oops");
+
+    let frames = server.join().unwrap();
+    let opened = frames
+        .iter()
+        .filter_map(|frame| match frame {
+            Frame::Control {
+                kind: spdy::SYN_STREAM,
+                data,
+                ..
+            } => spdy::stream_id(data),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(opened, vec![1, 3, 5, 7]);
+    let data = |stream, flags, data: &[u8]| Frame::Data {
+        stream,
+        flags,
+        data: data.to_vec(),
+    };
+    assert!(frames.contains(&data(3, 0, b"input")));
+    assert!(frames.contains(&data(3, FLAG_FIN, b"")));
+    assert!(frames.contains(&Frame::Control {
+        kind: spdy::PING,
+        flags: 0,
+        data: vec![0, 0, 0, 2],
+    }));
+}
+
+#[test]
+fn test_attach() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use tokio::runtime::Runtime;
+
+    use super::config::ConfigContext;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            conn.read_exact(&mut byte).unwrap();
+            request.push(byte[0]);
+        }
+        conn.write_all(
+            b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\n\
+              Upgrade: websocket\r\nSec-WebSocket-Protocol: v5.channel.k8s.io\r\n\r\n",
+        )
+        .unwrap();
+        conn.write_all(&[0x82, 6, STDOUT, b'h', b'e', b'l', b'l', b'o'])
+            .unwrap();
+        conn.write_all(&[0x88, 0]).unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let mut context: ConfigContext = Default::default();
+    context.cluster.server = format!("http://{}", addr);
+    let client = Client::builder()
+        .config(context)
+        .build_with_client(hyper::Client::new())
+        .unwrap();
+    let opts = ExecOptions {
+        container: Some("app".to_string()),
+        tty: false,
+    };
+    let mut rt = Runtime::new().unwrap();
+    let output = rt
+        .block_on(client.attach("default", "web-0", None, opts).collect())
+        .unwrap();
+    assert_eq!(output, vec![Output::Stdout(b"hello".to_vec())]);
+
+    let request = server.join().unwrap();
+    assert!(
+        request.starts_with(
+            "GET /api/v1/namespaces/default/pods/web-0/attach?\
+             container=app&stdin=false&stdout=true&stderr=true&tty=false "
+        ),
+        "{}",
+        request
+    );
+}
+
+#[test]
+fn test_exit_status() {
+    assert!(exit_status(b"").is_ok());
+    assert!(exit_status(br#"{"metadata": {}, "status": "Success"}"#).is_ok());
+    let err =
+        exit_status(br#"{"status": "Failure", "message": "container not found"}"#).unwrap_err();
+    assert_eq!(err.exit_code, None);
+    assert_eq!(err.to_string(), "container not found");
+}
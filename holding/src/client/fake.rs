@@ -0,0 +1,1234 @@
+//! An in-memory apiserver, for unit testing code that uses `Client`.
+//!
+//! `FakeClient` is an ordinary `Client` whose requests are answered by
+//! a `FakeApiServer` rather than sent over the network, so the code
+//! under test uses exactly the same API as it would against a real
+//! cluster.  The fake supports get, list, create, update (including
+//! the `status`, `scale` and `ephemeralcontainers` subresources),
+//! merge patch, delete, deletecollection, eviction and watch, with
+//! label and field selectors, paginated lists, resource versions and
+//! finalizers, and serves pod logs set with `FakeApiServer::set_logs`.  It does no
+//! validation, defaulting or admission, and evictions ignore
+//! PodDisruptionBudgets.
+//!
+//! ```
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # extern crate kubernetes_client;
+//! # #[macro_use] extern crate serde_json;
+//! use futures::Future;
+//! use kubernetes_api::meta::v1::{GetOptions, StatusReason};
+//! use kubernetes_api::meta::GroupVersionResource;
+//! use kubernetes_client::error::ApiError;
+//! use kubernetes_holding::client::fake::FakeClient;
+//! # fn main() {
+//!
+//! let gvr = GroupVersionResource { group: "", version: "v1", resource: "configmaps" };
+//! let client = FakeClient::new();
+//! client
+//!     .server()
+//!     .insert(&gvr, &json!({"metadata": {"name": "cm", "namespace": "ns"}}))
+//!     .unwrap();
+//!
+//! let cm: serde_json::Value = client.get(&gvr, Some("ns"), "cm", GetOptions::default()).wait().unwrap();
+//! assert_eq!(cm["metadata"]["resourceVersion"], "1");
+//!
+//! client.server().fail_next("get", "configmaps", ApiError::new(StatusReason::InternalError, "boom"));
+//! assert!(client.get::<serde_json::Value>(&gvr, Some("ns"), "cm", GetOptions::default()).wait().is_err());
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use failure::Error;
+use futures::sync::mpsc::{self, UnboundedSender};
+use futures::{Future, Stream};
+use hyper::header::{HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use hyper::{Body, Method, Request, Response};
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use url::form_urlencoded;
+
+use api::meta::v1::{ListMeta, Status, StatusReason, StatusStatus};
+use api::meta::GroupVersionResource;
+use api::{now, APPLY_PATCH, MERGE_PATCH, STRATEGIC_MERGE_PATCH};
+use k8sclient::error::ApiError;
+
+use super::backend::Backend;
+use super::config::ConfigContext;
+use super::metrics::RequestLabels;
+use super::middleware::{Middleware, Next, ResponseFuture};
+use super::Client;
+
+/// A `Client` backed by a `FakeApiServer`.
+///
+/// Derefs to the `Client`; use `server` to seed objects, inject errors
+/// and inspect what was done.
+#[derive(Debug, Clone)]
+pub struct FakeClient {
+    client: Client,
+    server: FakeApiServer,
+}
+
+impl FakeClient {
+    /// A client for a new, empty, `FakeApiServer`.
+    pub fn new() -> Self {
+        Self::with_server(FakeApiServer::new())
+    }
+
+    /// A client for `server`, which may be shared with other clients.
+    pub fn with_server(server: FakeApiServer) -> Self {
+        let mut config: ConfigContext = Default::default();
+        // Never connected to.
+        config.cluster.server = "http://fake.invalid".to_string();
+        let client = Client::builder()
+            .config(config)
+            .build_with_backend(Arc::new(server.clone()))
+            .expect("static client config");
+        FakeClient { client, server }
+    }
+
+    pub fn server(&self) -> &FakeApiServer {
+        &self.server
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Default for FakeClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for FakeClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+/// An in-memory apiserver.  Clones share the same objects.
+///
+/// Objects are stored as JSON, keyed by group, resource, namespace and
+/// name; the version in the request is ignored.  It is a `Backend`,
+/// and also a `Middleware` that answers every request itself, never
+/// calling the rest of the stack.
+#[derive(Debug, Clone, Default)]
+pub struct FakeApiServer {
+    state: Arc<Mutex<State>>,
+}
+
+/// (group, resource, namespace, name).  The namespace is empty for
+/// cluster-scoped objects.
+type Key = (String, String, String, String);
+
+#[derive(Debug, Default)]
+struct State {
+    resource_version: u64,
+    objects: BTreeMap<Key, Value>,
+    /// Every change, as (resource version, key, watch event).
+    events: Vec<(u64, Key, Value)>,
+    watchers: Vec<Watcher>,
+    /// (verb, resource, error) for `fail_next`.
+    errors: Vec<(String, String, ApiError)>,
+    actions: Vec<RequestLabels>,
+    /// Container logs, by (namespace, pod, container).
+    logs: HashMap<(String, String, String), String>,
+    /// Bumped by `expire_continues`, and part of every continue token.
+    continue_generation: u64,
+}
+
+#[derive(Debug)]
+struct Watcher {
+    group: String,
+    resource: String,
+    filter: Filter,
+    tx: UnboundedSender<Vec<u8>>,
+}
+
+impl FakeApiServer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Store `object` as if it had been created, returning the stored
+    /// object.  The namespace is taken from the object's metadata.
+    pub fn insert<T: Serialize>(
+        &self,
+        gvr: &GroupVersionResource,
+        object: &T,
+    ) -> Result<Value, Error> {
+        let object = serde_json::to_value(object)?;
+        let namespace = object["metadata"]["namespace"].as_str().map(str::to_string);
+        let path = Path {
+            group: gvr.group.to_string(),
+            version: gvr.version.to_string(),
+            namespace,
+            resource: gvr.resource.to_string(),
+            name: None,
+            subresource: None,
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .create(&path, object)
+            .map_err(|e| (*e).into())
+    }
+
+    /// The stored object, if there is one.
+    pub fn get(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Option<Value> {
+        let key = (
+            gvr.group.to_string(),
+            gvr.resource.to_string(),
+            namespace.unwrap_or_default().to_string(),
+            name.to_string(),
+        );
+        self.state.lock().unwrap().objects.get(&key).cloned()
+    }
+
+    /// Fail the next request with this verb (`get`, `list`, `watch`,
+    /// `create`, `update`, `patch`, `delete` or `deletecollection`) and
+    /// resource (including any subresource, eg `pods/status`) with
+    /// `error`.  Either may be `*`, to match anything.  Errors queued
+    /// for the same request are returned in turn.
+    pub fn fail_next(&self, verb: &str, resource: &str, error: ApiError) {
+        self.state
+            .lock()
+            .unwrap()
+            .errors
+            .push((verb.to_string(), resource.to_string(), error));
+    }
+
+    /// Set the log of a container, as later read through the pod's
+    /// `log` subresource.  Follow requests get the log so far and end.
+    pub fn set_logs(&self, namespace: &str, pod: &str, container: &str, logs: &str) {
+        self.state.lock().unwrap().logs.insert(
+            (
+                namespace.to_string(),
+                pod.to_string(),
+                container.to_string(),
+            ),
+            logs.to_string(),
+        );
+    }
+
+    /// Every request received so far, in order.
+    pub fn actions(&self) -> Vec<RequestLabels> {
+        self.state.lock().unwrap().actions.clone()
+    }
+
+    /// Expire the continue tokens of every paginated list in progress,
+    /// as compaction does on a real apiserver.  Continuing such a list
+    /// fails with `410 Gone`, offering a token to continue it
+    /// inconsistently.
+    pub fn expire_continues(&self) {
+        self.state.lock().unwrap().continue_generation += 1;
+    }
+
+    /// End all open watches, as the apiserver does from time to time.
+    pub fn close_watches(&self) {
+        self.state.lock().unwrap().watchers.clear();
+    }
+}
+
+impl Middleware for FakeApiServer {
+    fn call(&self, req: Request<Body>, _next: Next) -> ResponseFuture {
+        Backend::call(self, req)
+    }
+}
+
+impl Backend for FakeApiServer {
+    fn call(&self, req: Request<Body>) -> ResponseFuture {
+        let state = Arc::clone(&self.state);
+        let (parts, body) = req.into_parts();
+        Box::new(body.concat2().from_err::<Error>().map(move |body| {
+            let labels = RequestLabels::new(&parts.method, &parts.uri);
+            let mut state = state.lock().unwrap();
+            state.actions.push(labels.clone());
+            let injected = state.errors.iter().position(|(verb, resource, _)| {
+                (verb == "*" || *verb == labels.verb)
+                    && (resource == "*" || *resource == labels.resource)
+            });
+            if let Some(i) = injected {
+                let (_, _, e) = state.errors.remove(i);
+                return error_response(e);
+            }
+
+            let path = match Path::parse(parts.uri.path()) {
+                Some(path) => path,
+                None => {
+                    return error_response(ApiError::new(
+                        StatusReason::NotFound,
+                        "the server could not find the requested resource",
+                    ))
+                }
+            };
+            let query: HashMap<String, String> =
+                form_urlencoded::parse(parts.uri.query().unwrap_or("").as_bytes())
+                    .into_owned()
+                    .collect();
+            let content_type = parts
+                .headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("");
+            let result = match (parts.method, &path.name) {
+                (Method::GET, _) if labels.verb == "watch" => {
+                    return state.watch(&path, &query);
+                }
+                (Method::GET, &Some(_)) if path.subresource.as_deref() == Some("log") => {
+                    return match state.logs(&path, &query) {
+                        Ok(logs) => Response::builder()
+                            .header(CONTENT_TYPE, "text/plain")
+                            .body(Body::from(logs))
+                            .unwrap(),
+                        Err(e) => error_response(*e),
+                    };
+                }
+                (Method::GET, &Some(_)) => state.read(&path),
+                (Method::GET, &None) => return state.list(&path, &query),
+                (Method::POST, &None) => {
+                    parse_body(&body).and_then(|object| state.create(&path, object))
+                }
+                (Method::POST, &Some(_)) if path.subresource.as_deref() == Some("eviction") => {
+                    state.delete(&path).map(|_| success())
+                }
+                (Method::PUT, &Some(_)) => {
+                    parse_body(&body).and_then(|object| state.update(&path, object))
+                }
+                (Method::PATCH, &Some(_)) => state.patch(&path, content_type, &body),
+                (Method::DELETE, &Some(_)) => state.delete(&path),
+                (Method::DELETE, &None) => Ok(state.delete_collection(&path, &query)),
+                (method, _) => Err(api_error(
+                    StatusReason::MethodNotAllowed,
+                    format!("{} is not supported on {}", method, parts.uri.path()),
+                )),
+            };
+            match result {
+                Ok(object) => {
+                    let status = if labels.verb == "create" { 201 } else { 200 };
+                    json_response(status, &object)
+                }
+                Err(e) => error_response(*e),
+            }
+        }))
+    }
+}
+
+/// A request path, split into its parts.
+#[derive(Debug)]
+struct Path {
+    group: String,
+    version: String,
+    namespace: Option<String>,
+    resource: String,
+    name: Option<String>,
+    subresource: Option<String>,
+}
+
+impl Path {
+    fn parse(path: &str) -> Option<Self> {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let (group, version, rest) = match segments.split_first() {
+            Some((&"api", rest)) if !rest.is_empty() => ("", rest[0], &rest[1..]),
+            Some((&"apis", rest)) if rest.len() >= 2 => (rest[0], rest[1], &rest[2..]),
+            _ => return None,
+        };
+        let (namespace, rest) = match rest {
+            ["namespaces", ns, rest @ ..] if !rest.is_empty() => (Some(ns.to_string()), rest),
+            rest => (None, rest),
+        };
+        let (resource, name, subresource) = match rest {
+            [resource] => (resource, None, None),
+            [resource, name] => (resource, Some(name.to_string()), None),
+            [resource, name, sub] => (resource, Some(name.to_string()), Some(sub.to_string())),
+            _ => return None,
+        };
+        Some(Path {
+            group: group.to_string(),
+            version: version.to_string(),
+            namespace,
+            resource: resource.to_string(),
+            name,
+            subresource,
+        })
+    }
+
+    fn key(&self, name: &str) -> Key {
+        (
+            self.group.clone(),
+            self.resource.clone(),
+            self.namespace.clone().unwrap_or_default(),
+            name.to_string(),
+        )
+    }
+
+    fn api_version(&self) -> String {
+        if self.group.is_empty() {
+            self.version.clone()
+        } else {
+            format!("{}/{}", self.group, self.version)
+        }
+    }
+
+    /// As the apiserver names objects in error messages, eg
+    /// `pods "foo"`.
+    fn describe(&self, name: &str) -> String {
+        if self.group.is_empty() {
+            format!("{} {:?}", self.resource, name)
+        } else {
+            format!("{}.{} {:?}", self.resource, self.group, name)
+        }
+    }
+
+    fn filter(&self, query: &HashMap<String, String>) -> Filter {
+        let param = |k: &str| query.get(k).cloned().unwrap_or_default();
+        Filter {
+            namespace: self.namespace.clone(),
+            name: self.name.clone(),
+            labels: param("labelSelector"),
+            fields: param("fieldSelector"),
+        }
+    }
+}
+
+/// Which objects a list or watch is for.
+#[derive(Debug)]
+struct Filter {
+    namespace: Option<String>,
+    name: Option<String>,
+    labels: String,
+    fields: String,
+}
+
+impl Filter {
+    fn matches(&self, key: &Key, object: &Value) -> bool {
+        let (_, _, ref namespace, ref name) = *key;
+        self.namespace.as_ref().is_none_or(|ns| ns == namespace)
+            && self.name.as_ref().is_none_or(|n| n == name)
+            && split_selector(&self.labels)
+                .iter()
+                .all(|req| label_matches(req, &object["metadata"]["labels"]))
+            && split_selector(&self.fields)
+                .iter()
+                .all(|req| field_matches(req, object))
+    }
+}
+
+/// Split a selector into its requirements, at commas outside of
+/// parentheses.
+fn split_selector(selector: &str) -> Vec<&str> {
+    let mut reqs = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                reqs.push(selector[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    reqs.push(selector[start..].trim());
+    reqs.retain(|r| !r.is_empty());
+    reqs
+}
+
+fn label_matches(req: &str, labels: &Value) -> bool {
+    let value = |k: &str| labels.get(k.trim()).and_then(Value::as_str);
+    let set = |s: &str| -> Vec<String> {
+        s.trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .collect()
+    };
+    if let Some(i) = req.find(" notin ") {
+        value(&req[..i]).is_none_or(|v| !set(&req[i + 7..]).iter().any(|s| s == v))
+    } else if let Some(i) = req.find(" in ") {
+        value(&req[..i]).is_some_and(|v| set(&req[i + 4..]).iter().any(|s| s == v))
+    } else if let Some(i) = req.find("!=") {
+        value(&req[..i]) != Some(req[i + 2..].trim())
+    } else if let Some(i) = req.find('=') {
+        value(&req[..i]) == Some(req[i + 1..].trim_start_matches('=').trim())
+    } else if let Some(key) = req.strip_prefix('!') {
+        value(key).is_none()
+    } else {
+        value(req).is_some()
+    }
+}
+
+fn field_matches(req: &str, object: &Value) -> bool {
+    let value = |path: &str| {
+        let field = path
+            .trim()
+            .split('.')
+            .fold(object, |v, k| v.get(k).unwrap_or(&Value::Null));
+        match *field {
+            Value::Null => String::new(),
+            Value::String(ref s) => s.clone(),
+            ref v => v.to_string(),
+        }
+    };
+    if let Some(i) = req.find("!=") {
+        value(&req[..i]) != req[i + 2..].trim()
+    } else if let Some(i) = req.find('=') {
+        value(&req[..i]) == req[i + 1..].trim_start_matches('=').trim()
+    } else {
+        false
+    }
+}
+
+impl State {
+    /// Record a change to an object, giving it a new resource version
+    /// and telling any interested watchers.
+    fn record(&mut self, typ: &str, key: &Key, mut object: Value) -> Value {
+        self.resource_version += 1;
+        let rv = self.resource_version;
+        object["metadata"]["resourceVersion"] = rv.to_string().into();
+        let event = json!({"type": typ, "object": object.clone()});
+        let line = watch_line(&event);
+        let (ref group, ref resource, _, _) = *key;
+        self.watchers.retain(|w| {
+            if w.group != *group || w.resource != *resource || !w.filter.matches(key, &object) {
+                return true;
+            }
+            // Forget watchers that have gone away.
+            w.tx.unbounded_send(line.clone()).is_ok()
+        });
+        self.events.push((rv, key.clone(), event));
+        if typ == "DELETED" {
+            self.objects.remove(key);
+        } else {
+            self.objects.insert(key.clone(), object.clone());
+        }
+        object
+    }
+
+    fn not_found(path: &Path, name: &str) -> Box<ApiError> {
+        api_error(
+            StatusReason::NotFound,
+            format!("{} not found", path.describe(name)),
+        )
+    }
+
+    fn get(&self, path: &Path) -> Result<Value, Box<ApiError>> {
+        let name = path.name.as_ref().unwrap();
+        self.objects
+            .get(&path.key(name))
+            .cloned()
+            .ok_or_else(|| Self::not_found(path, name))
+    }
+
+    /// The object, as seen through the subresource (if any).
+    fn read(&self, path: &Path) -> Result<Value, Box<ApiError>> {
+        let object = self.get(path)?;
+        match path.subresource.as_deref() {
+            Some("scale") => Ok(to_scale(&object)),
+            _ => Ok(object),
+        }
+    }
+
+    fn logs(&self, path: &Path, query: &HashMap<String, String>) -> Result<String, Box<ApiError>> {
+        let pod = self.get(path)?;
+        let container = match query.get("container") {
+            Some(container) => container.as_str(),
+            None => pod["spec"]["containers"][0]["name"]
+                .as_str()
+                .unwrap_or_default(),
+        };
+        let key = (
+            path.namespace.clone().unwrap_or_default(),
+            path.name.clone().unwrap_or_default(),
+            container.to_string(),
+        );
+        Ok(self.logs.get(&key).cloned().unwrap_or_default())
+    }
+
+    fn matching<'a>(
+        &'a self,
+        path: &'a Path,
+        filter: &'a Filter,
+    ) -> impl Iterator<Item = (&'a Key, &'a Value)> {
+        self.objects.iter().filter(move |&(key, object)| {
+            key.0 == path.group && key.1 == path.resource && filter.matches(key, object)
+        })
+    }
+
+    fn list(&self, path: &Path, query: &HashMap<String, String>) -> Response<Body> {
+        let filter = path.filter(query);
+        let limit = query
+            .get("limit")
+            .and_then(|l| l.parse::<usize>().ok())
+            .filter(|&l| l > 0);
+        // Continue tokens are "generation/namespace/name" of the last
+        // item returned.
+        let after = match query.get("continue").filter(|c| !c.is_empty()) {
+            Some(token) => {
+                let mut parts = token.splitn(3, '/');
+                match (
+                    parts.next().and_then(|g| g.parse::<u64>().ok()),
+                    parts.next(),
+                    parts.next(),
+                ) {
+                    (Some(g), Some(ns), Some(name)) if g == self.continue_generation => {
+                        Some((ns.to_string(), name.to_string()))
+                    }
+                    (Some(_), Some(ns), Some(name)) => {
+                        let metadata = ListMeta {
+                            continu: Some(format!("{}/{}/{}", self.continue_generation, ns, name)),
+                            ..Default::default()
+                        };
+                        return status_response(
+                            ApiError::new(
+                                StatusReason::Expired,
+                                "The provided continue parameter is too old to display a \
+                                 consistent list result.",
+                            ),
+                            metadata,
+                        );
+                    }
+                    _ => {
+                        return error_response(ApiError::new(
+                            StatusReason::BadRequest,
+                            "continue key is not valid",
+                        ))
+                    }
+                }
+            }
+            None => None,
+        };
+        let mut items: Vec<(&Key, &Value)> = self
+            .matching(path, &filter)
+            .filter(|&(key, _)| {
+                after
+                    .as_ref()
+                    .is_none_or(|(ns, name)| (&key.2, &key.3) > (ns, name))
+            })
+            .collect();
+        let mut metadata = json!({"resourceVersion": self.resource_version.to_string()});
+        if let Some(limit) = limit {
+            if items.len() > limit {
+                metadata["remainingItemCount"] = (items.len() - limit).into();
+                items.truncate(limit);
+                let (_, _, ns, name) = items[limit - 1].0;
+                metadata["continue"] =
+                    format!("{}/{}/{}", self.continue_generation, ns, name).into();
+            }
+        }
+        let items: Vec<Value> = items.into_iter().map(|(_, o)| o.clone()).collect();
+        let kind = items
+            .first()
+            .and_then(|o| o["kind"].as_str())
+            .map_or_else(|| "List".to_string(), |k| format!("{}List", k));
+        json_response(
+            200,
+            &json!({
+                "apiVersion": path.api_version(),
+                "kind": kind,
+                "metadata": metadata,
+                "items": items,
+            }),
+        )
+    }
+
+    fn watch(&mut self, path: &Path, query: &HashMap<String, String>) -> Response<Body> {
+        let filter = path.filter(query);
+        let (tx, rx) = mpsc::unbounded();
+        let since = query
+            .get("resourceVersion")
+            .and_then(|rv| rv.parse::<u64>().ok())
+            .filter(|&rv| rv != 0);
+        let initial: Vec<Value> = match since {
+            // Everything since then, ..
+            Some(rv) => self
+                .events
+                .iter()
+                .filter(|&&(erv, ref key, ref event)| {
+                    erv > rv
+                        && key.0 == path.group
+                        && key.1 == path.resource
+                        && filter.matches(key, &event["object"])
+                })
+                .map(|(_, _, event)| event.clone())
+                .collect(),
+            // .. or the current state.
+            None => self
+                .matching(path, &filter)
+                .map(|(_, o)| json!({"type": "ADDED", "object": o}))
+                .collect(),
+        };
+        for event in &initial {
+            let _ = tx.unbounded_send(watch_line(event));
+        }
+        self.watchers.push(Watcher {
+            group: path.group.clone(),
+            resource: path.resource.clone(),
+            filter,
+            tx,
+        });
+        let body = rx.map_err(|()| io::Error::other("watch closed"));
+        Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::wrap_stream(body))
+            .unwrap()
+    }
+
+    fn create(&mut self, path: &Path, mut object: Value) -> Result<Value, Box<ApiError>> {
+        if path.subresource.is_some() {
+            return Err(api_error(
+                StatusReason::MethodNotAllowed,
+                "creating subresources is not supported",
+            ));
+        }
+        let name = match object["metadata"]["name"].as_str() {
+            Some(name) => name.to_string(),
+            None => match object["metadata"]["generateName"].as_str() {
+                Some(prefix) => format!("{}{:05x}", prefix, self.resource_version + 1),
+                None => {
+                    return Err(api_error(
+                        StatusReason::Invalid,
+                        "name or generateName is required",
+                    ))
+                }
+            },
+        };
+        if let Some(ref ns) = path.namespace {
+            match object["metadata"]["namespace"].as_str() {
+                Some(objns) if objns != ns => {
+                    return Err(api_error(
+                        StatusReason::BadRequest,
+                        "the namespace of the provided object does not match the namespace sent on the request",
+                    ))
+                }
+                _ => object["metadata"]["namespace"] = ns.clone().into(),
+            }
+        }
+        let key = path.key(&name);
+        if self.objects.contains_key(&key) {
+            return Err(api_error(
+                StatusReason::AlreadyExists,
+                format!("{} already exists", path.describe(&name)),
+            ));
+        }
+        if object["apiVersion"].is_null() {
+            object["apiVersion"] = path.api_version().into();
+        }
+        let meta = &mut object["metadata"];
+        meta["name"] = name.into();
+        meta["uid"] = format!("fake-uid-{}", self.resource_version + 1).into();
+        meta["creationTimestamp"] = now().into();
+        Ok(self.record("ADDED", &key, object))
+    }
+
+    /// Replace the stored object (or just its status) with `object`.
+    fn update(&mut self, path: &Path, mut object: Value) -> Result<Value, Box<ApiError>> {
+        let name = path.name.as_ref().unwrap();
+        let key = path.key(name);
+        let stored = self.get(path)?;
+        if object["metadata"]["name"]
+            .as_str()
+            .is_some_and(|n| n != name)
+        {
+            return Err(api_error(
+                StatusReason::BadRequest,
+                "the name of the object does not match the name on the URL",
+            ));
+        }
+        match object["metadata"]["resourceVersion"].as_str() {
+            Some(rv) if !rv.is_empty() && stored["metadata"]["resourceVersion"] != rv => {
+                return Err(api_error(
+                    StatusReason::Conflict,
+                    format!(
+                        "Operation cannot be fulfilled on {}: the object has been modified; \
+                         please apply your changes to the latest version and try again",
+                        path.describe(name)
+                    ),
+                ))
+            }
+            _ => (),
+        }
+        match path.subresource.as_deref() {
+            None => {
+                // Immutable fields.
+                for field in &[
+                    "name",
+                    "namespace",
+                    "uid",
+                    "creationTimestamp",
+                    "deletionTimestamp",
+                ] {
+                    object["metadata"][*field] = stored["metadata"][*field].clone();
+                }
+                if object["apiVersion"].is_null() {
+                    object["apiVersion"] = stored["apiVersion"].clone();
+                }
+                remove_nulls(&mut object["metadata"]);
+            }
+            Some("status") => {
+                let status = object["status"].take();
+                object = stored;
+                object["status"] = status;
+            }
+            Some("ephemeralcontainers") => {
+                let containers = object["spec"]["ephemeralContainers"].take();
+                object = stored;
+                object["spec"]["ephemeralContainers"] = containers;
+            }
+            Some("scale") => {
+                let replicas = object["spec"]["replicas"].take();
+                object = stored;
+                object["spec"]["replicas"] = replicas;
+                return Ok(to_scale(&self.record("MODIFIED", &key, object)));
+            }
+            Some(sub) => {
+                return Err(api_error(
+                    StatusReason::MethodNotAllowed,
+                    format!("updating subresource {} is not supported", sub),
+                ))
+            }
+        }
+        let finalized = !object["metadata"]["deletionTimestamp"].is_null()
+            && object["metadata"]["finalizers"]
+                .as_array()
+                .is_none_or(Vec::is_empty);
+        if finalized {
+            Ok(self.record("DELETED", &key, object))
+        } else {
+            Ok(self.record("MODIFIED", &key, object))
+        }
+    }
+
+    fn patch(
+        &mut self,
+        path: &Path,
+        content_type: &str,
+        body: &[u8],
+    ) -> Result<Value, Box<ApiError>> {
+        if content_type == APPLY_PATCH {
+            // Field ownership isn't tracked, so applies are merges that
+            // never conflict.
+            let patch = parse_body(body)?;
+            return match self.read(path) {
+                Err(ref e) if e.is_not_found() && path.subresource.is_none() => {
+                    let mut object = json!({});
+                    merge_patch(&mut object, &patch);
+                    self.create(path, object)
+                }
+                Err(e) => Err(e),
+                Ok(mut object) => {
+                    merge_patch(&mut object, &patch);
+                    self.update(path, object)
+                }
+            };
+        }
+        if content_type != MERGE_PATCH && content_type != STRATEGIC_MERGE_PATCH {
+            return Err(api_error(
+                StatusReason::UnsupportedMediaType,
+                format!(
+                    "the fake apiserver does not support {:?} patches",
+                    content_type
+                ),
+            ));
+        }
+        let patch = parse_body(body)?;
+        let mut object = self.read(path)?;
+        merge_patch(&mut object, &patch);
+        self.update(path, object)
+    }
+
+    fn delete(&mut self, path: &Path) -> Result<Value, Box<ApiError>> {
+        let name = path.name.as_ref().unwrap();
+        let object = self.get(path)?;
+        Ok(self.remove(&path.key(name), object))
+    }
+
+    fn delete_collection(&mut self, path: &Path, query: &HashMap<String, String>) -> Value {
+        let filter = path.filter(query);
+        let doomed: Vec<(Key, Value)> = self
+            .matching(path, &filter)
+            .map(|(k, o)| (k.clone(), o.clone()))
+            .collect();
+        for (key, object) in doomed {
+            self.remove(&key, object);
+        }
+        success()
+    }
+
+    /// Delete an object, or if it has finalizers, mark it as being
+    /// deleted.
+    fn remove(&mut self, key: &Key, mut object: Value) -> Value {
+        let finalizers = object["metadata"]["finalizers"]
+            .as_array()
+            .is_some_and(|f| !f.is_empty());
+        if !finalizers {
+            return self.record("DELETED", key, object);
+        }
+        if object["metadata"]["deletionTimestamp"].is_null() {
+            object["metadata"]["deletionTimestamp"] = now().into();
+            return self.record("MODIFIED", key, object);
+        }
+        object
+    }
+}
+
+fn success() -> Value {
+    serde_json::to_value(Status {
+        metadata: ListMeta::default(),
+        code: 200,
+        details: None,
+        message: String::new(),
+        reason: None,
+        status: StatusStatus::Success,
+    })
+    .unwrap()
+}
+
+/// Our errors are boxed, as `ApiError` is large.
+fn api_error<S: Into<String>>(reason: StatusReason, message: S) -> Box<ApiError> {
+    Box::new(ApiError::new(reason, message))
+}
+
+fn parse_body(body: &[u8]) -> Result<Value, Box<ApiError>> {
+    match serde_json::from_slice(body) {
+        Ok(v @ Value::Object(_)) => Ok(v),
+        Ok(_) => Err(api_error(
+            StatusReason::BadRequest,
+            "request body must be a JSON object",
+        )),
+        Err(e) => Err(api_error(StatusReason::BadRequest, e.to_string())),
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7386).
+pub(crate) fn merge_patch(target: &mut Value, patch: &Value) {
+    match *patch {
+        Value::Object(ref patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            let target = target.as_object_mut().unwrap();
+            for (k, v) in patch {
+                if v.is_null() {
+                    target.remove(k);
+                } else {
+                    merge_patch(target.entry(k.clone()).or_insert(Value::Null), v);
+                }
+            }
+        }
+        ref v => *target = v.clone(),
+    }
+}
+
+/// The `scale` subresource of `object`.
+fn to_scale(object: &Value) -> Value {
+    let meta = &object["metadata"];
+    let selector = object["spec"]["selector"]["matchLabels"]
+        .as_object()
+        .map(|labels| {
+            labels
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or_default()))
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+    json!({
+        "apiVersion": "autoscaling/v1",
+        "kind": "Scale",
+        "metadata": {
+            "name": meta["name"],
+            "namespace": meta["namespace"],
+            "uid": meta["uid"],
+            "resourceVersion": meta["resourceVersion"],
+            "creationTimestamp": meta["creationTimestamp"],
+        },
+        "spec": {"replicas": object["spec"]["replicas"].as_i64().unwrap_or(1)},
+        "status": {
+            "replicas": object["status"]["replicas"].as_i64().unwrap_or(0),
+            "selector": selector,
+        },
+    })
+}
+
+fn remove_nulls(v: &mut Value) {
+    if let Some(o) = v.as_object_mut() {
+        o.retain(|_, v| !v.is_null());
+    }
+}
+
+fn watch_line(event: &Value) -> Vec<u8> {
+    let mut line = serde_json::to_vec(event).unwrap();
+    line.push(b'\n');
+    line
+}
+
+fn json_response(status: u16, body: &Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(body).unwrap()))
+        .unwrap()
+}
+
+fn error_response(e: ApiError) -> Response<Body> {
+    status_response(e, ListMeta::default())
+}
+
+fn status_response(e: ApiError, metadata: ListMeta) -> Response<Body> {
+    let retry_after = e.retry_after();
+    let status = Status {
+        metadata,
+        code: i32::from(e.code),
+        details: e.details,
+        message: e.message,
+        reason: Some(e.reason),
+        status: StatusStatus::Failure,
+    };
+    let mut body = serde_json::to_value(status).unwrap();
+    body["kind"] = "Status".into();
+    body["apiVersion"] = "v1".into();
+    let mut res = json_response(e.code, &body);
+    // As the apiserver also sends it.
+    if let Some(after) = retry_after {
+        res.headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(after.as_secs()));
+    }
+    res
+}
+
+#[cfg(test)]
+const CONFIGMAPS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "configmaps",
+};
+
+#[test]
+fn test_crud() {
+    use api::meta::v1::{DeleteOptions, GetOptions, ListOptions};
+
+    let client = FakeClient::new();
+    let cm = json!({"metadata": {"name": "a", "namespace": "ns", "labels": {"app": "x"}}});
+    let created = client
+        .create(&CONFIGMAPS, &cm, GetOptions::default())
+        .wait()
+        .unwrap();
+    assert_eq!(created["metadata"]["resourceVersion"], "1");
+    assert_eq!(created["apiVersion"], "v1");
+    let err = client
+        .create(&CONFIGMAPS, &cm, GetOptions::default())
+        .wait()
+        .unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_already_exists());
+    client
+        .server()
+        .insert(
+            &CONFIGMAPS,
+            &json!({"metadata": {"name": "b", "namespace": "ns"}}),
+        )
+        .unwrap();
+
+    // Stale updates conflict.
+    let mut stale = created.clone();
+    stale["data"] = json!({"k": "v"});
+    let updated: Value = client.update(&CONFIGMAPS, &stale).wait().unwrap();
+    assert_eq!(updated["metadata"]["resourceVersion"], "3");
+    let err = client.update(&CONFIGMAPS, &stale).wait().unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_conflict());
+
+    let patched: Value = client
+        .patch(
+            &CONFIGMAPS,
+            Some("ns"),
+            "a",
+            MERGE_PATCH,
+            &json!({"data": {"k": null, "k2": "v2"}}),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(patched["data"], json!({"k2": "v2"}));
+    assert_eq!(patched["metadata"]["labels"], json!({"app": "x"}));
+
+    let opts = ListOptions {
+        label_selector: "app in (x, y)".to_string(),
+        ..Default::default()
+    };
+    let list: Value = client.list(&CONFIGMAPS, Some("ns"), opts).wait().unwrap();
+    assert_eq!(list["items"].as_array().unwrap().len(), 1);
+    assert_eq!(list["metadata"]["resourceVersion"], "4");
+
+    client
+        .delete(&CONFIGMAPS, Some("ns"), "a", DeleteOptions::default())
+        .wait()
+        .unwrap();
+    let gone: Option<Value> = client
+        .get_opt(&CONFIGMAPS, Some("ns"), "a", GetOptions::default())
+        .wait()
+        .unwrap();
+    assert_eq!(gone, None);
+    assert!(client.server().get(&CONFIGMAPS, Some("ns"), "b").is_some());
+
+    let verbs: Vec<String> = client
+        .server()
+        .actions()
+        .into_iter()
+        .map(|a| a.verb)
+        .collect();
+    assert_eq!(
+        verbs,
+        vec!["create", "create", "update", "update", "patch", "list", "delete", "get"]
+    );
+}
+
+#[test]
+fn test_finalizers() {
+    use api::meta::v1::DeleteOptions;
+
+    let client = FakeClient::new();
+    let cm = json!({"metadata": {"name": "a", "namespace": "ns", "finalizers": ["f"]}});
+    client.server().insert(&CONFIGMAPS, &cm).unwrap();
+    client
+        .delete(&CONFIGMAPS, Some("ns"), "a", DeleteOptions::default())
+        .wait()
+        .unwrap();
+    let mut cm = client.server().get(&CONFIGMAPS, Some("ns"), "a").unwrap();
+    assert!(cm["metadata"]["deletionTimestamp"].is_string());
+
+    cm["metadata"]["finalizers"] = json!([]);
+    let _: Value = client.update(&CONFIGMAPS, &cm).wait().unwrap();
+    assert_eq!(client.server().get(&CONFIGMAPS, Some("ns"), "a"), None);
+}
+
+#[test]
+fn test_watch() {
+    use api::meta::v1::{EventType, ListOptions};
+
+    let client = FakeClient::new();
+    let server = client.server();
+    server
+        .insert(
+            &CONFIGMAPS,
+            &json!({"metadata": {"name": "a", "namespace": "ns"}}),
+        )
+        .unwrap();
+    let opts = ListOptions {
+        field_selector: "metadata.name=a".to_string(),
+        ..Default::default()
+    };
+    let mut events = client
+        .watch_list(&CONFIGMAPS, Some("ns"), opts.clone())
+        .wait();
+    let event = events.next().unwrap().unwrap();
+    assert_eq!(event.typ, EventType::Added);
+
+    let mut cm = server.get(&CONFIGMAPS, Some("ns"), "a").unwrap();
+    cm["data"] = json!({"k": "v"});
+    let _: Value = client.update(&CONFIGMAPS, &cm).wait().unwrap();
+    server
+        .insert(
+            &CONFIGMAPS,
+            &json!({"metadata": {"name": "b", "namespace": "ns"}}),
+        )
+        .unwrap();
+    let event = events.next().unwrap().unwrap();
+    assert_eq!(event.typ, EventType::Modified);
+    assert_eq!(event.object["data"]["k"], "v");
+
+    server.close_watches();
+    assert!(events.next().is_none());
+
+    // Resuming from a resource version replays what was missed.
+    let opts = ListOptions {
+        resource_version: "1".to_string(),
+        ..Default::default()
+    };
+    let events: Vec<_> = client
+        .watch_list(&CONFIGMAPS, None, opts)
+        .take(2)
+        .collect()
+        .wait()
+        .unwrap();
+    let names: Vec<_> = events
+        .iter()
+        .map(|e| e.object["metadata"]["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["a", "b"]);
+}
+
+#[test]
+fn test_fail_next() {
+    use api::meta::v1::GetOptions;
+
+    let client = FakeClient::new();
+    client.server().fail_next(
+        "get",
+        "*",
+        ApiError::new(StatusReason::TooManyRequests, "slow down"),
+    );
+    let err = client
+        .get::<Value>(&CONFIGMAPS, Some("ns"), "a", GetOptions::default())
+        .wait()
+        .unwrap_err();
+    let err = ApiError::from_error(&err).unwrap();
+    assert_eq!(err.code, 429);
+    assert_eq!(err.message, "slow down");
+
+    // Only once.
+    let err = client
+        .get::<Value>(&CONFIGMAPS, Some("ns"), "a", GetOptions::default())
+        .wait()
+        .unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_not_found());
+}
+
+#[test]
+fn test_selectors() {
+    let labels = json!({"app": "x", "tier": "web"});
+    assert_eq!(
+        split_selector("app in (x,y), tier!=db,!gone"),
+        vec!["app in (x,y)", "tier!=db", "!gone"]
+    );
+    for req in &[
+        "app=x",
+        "app==x",
+        "tier!=db",
+        "app in (x,y)",
+        "app notin (y)",
+        "tier",
+        "!gone",
+    ] {
+        assert!(label_matches(req, &labels), "{}", req);
+    }
+    for req in &[
+        "app=y",
+        "app!=x",
+        "app in (y)",
+        "gone",
+        "!app",
+        "gone in (x)",
+    ] {
+        assert!(!label_matches(req, &labels), "{}", req);
+    }
+    let object = json!({"metadata": {"name": "a"}, "spec": {"replicas": 3}});
+    assert!(field_matches("metadata.name=a", &object));
+    assert!(field_matches("spec.replicas==3", &object));
+    assert!(field_matches("metadata.namespace!=x", &object));
+    assert!(!field_matches("metadata.name!=a", &object));
+}
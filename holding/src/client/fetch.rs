@@ -0,0 +1,207 @@
+//! Talking to the apiserver with the browser's `fetch`, on wasm32.
+//!
+//! `FetchClient` has only what a dashboard needs: gets and lists
+//! decoded into the `api` types, and watches decoded as by the native
+//! `Client`.  The browser does TLS, and decides which certificates to
+//! trust, so there is no kubeconfig; the apiserver is usually reached
+//! through `kubectl proxy` or the dashboard's own server, which must
+//! allow the page's origin (CORS).
+//!
+//! These are futures 0.1 futures and streams, which aren't `Send`.  Run
+//! them with `wasm_bindgen_futures::spawn_local` or
+//! `future_to_promise`.
+
+use std::fmt;
+
+use failure::Error;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use http::StatusCode;
+use js_sys::{Reflect, Uint8Array};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use url::Url;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{self, Headers, ReadableStreamDefaultReader, Request, RequestInit, Response};
+
+use api::meta::v1::{GetOptions, ListOptions, WatchEvent};
+use api::meta::GroupVersionResource;
+use api::JSON;
+
+use super::json::{error_response, from_json};
+use super::urls;
+use super::watch;
+
+#[derive(Clone, Debug)]
+pub struct FetchClient {
+    server: String,
+    token: Option<String>,
+}
+
+impl FetchClient {
+    /// A client for the apiserver at `server`, eg:
+    /// `http://localhost:8001` for `kubectl proxy`.
+    pub fn new(server: &str) -> Self {
+        FetchClient {
+            server: server.to_string(),
+            token: None,
+        }
+    }
+
+    /// Authenticate with the bearer `token`.
+    pub fn bearer_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub fn get<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: GetOptions,
+    ) -> impl Future<Item = T, Error = Error>
+    where
+        T: DeserializeOwned,
+    {
+        let url = self.url(gvr, namespace, Some(name), opts);
+        self.fetch(url)
+            .and_then(|response| body(&response))
+            .and_then(|body| from_json("response body", &body))
+    }
+
+    pub fn list<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> impl Future<Item = T, Error = Error>
+    where
+        T: DeserializeOwned,
+    {
+        let url = self.url(gvr, namespace, None, opts);
+        self.fetch(url)
+            .and_then(|response| body(&response))
+            .and_then(|body| from_json("response body", &body))
+    }
+
+    /// Watch `gvr`, with the objects decoded into `T` (see
+    /// `watch::decode_event`), and `ERROR` events as errors.  The
+    /// stream ends when the apiserver ends the watch; start another
+    /// from the last resourceVersion seen.
+    pub fn watch_list<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        mut opts: ListOptions,
+    ) -> impl Stream<Item = WatchEvent<T>, Error = Error>
+    where
+        T: DeserializeOwned,
+    {
+        opts.watch = true;
+        let url = self.url(gvr, namespace, None, opts);
+        let body = self
+            .fetch(url)
+            .map(|response| chunks(&response))
+            .flatten_stream();
+        watch::lines(body).and_then(|line| watch::decode_event(&line))
+    }
+
+    fn url<O>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: Option<&str>,
+        opts: O,
+    ) -> Result<Url, Error>
+    where
+        O: Serialize + fmt::Debug,
+    {
+        urls::resource_url(
+            urls::path_url(&self.server, &[])?,
+            gvr,
+            namespace,
+            name,
+            opts,
+        )
+    }
+
+    fn request(&self, url: Result<Url, Error>) -> Result<Request, Error> {
+        let url = url?;
+        let headers = Headers::new().map_err(js_error)?;
+        headers.set("Accept", JSON).map_err(js_error)?;
+        if let Some(ref token) = self.token {
+            headers
+                .set("Authorization", &format!("Bearer {}", token))
+                .map_err(js_error)?;
+        }
+        let init = RequestInit::new();
+        init.set_method("GET");
+        init.set_headers(&headers);
+        Request::new_with_str_and_init(url.as_str(), &init).map_err(js_error)
+    }
+
+    /// GET `url`, resolving to the response once its headers arrive, or
+    /// to the error the apiserver returned.
+    fn fetch(&self, url: Result<Url, Error>) -> impl Future<Item = Response, Error = Error> {
+        future::result(self.request(url))
+            .and_then(|request| match web_sys::window() {
+                Some(window) => {
+                    debug!("Fetching {}", request.url());
+                    Ok(JsFuture::from(window.fetch_with_request(&request)).map_err(js_error))
+                }
+                None => Err(format_err!("No window to fetch with")),
+            })
+            .flatten()
+            .and_then(|response| {
+                let response: Response = response.unchecked_into();
+                if response.ok() {
+                    return Either::A(future::ok(response));
+                }
+                let status = StatusCode::from_u16(response.status()).map_err(Error::from);
+                Either::B(
+                    future::result(status)
+                        .join(body(&response))
+                        .and_then(|(status, body)| Err(error_response(status, &body).into())),
+                )
+            })
+    }
+}
+
+/// The whole body of `response`.
+fn body(response: &Response) -> impl Future<Item = Vec<u8>, Error = Error> {
+    future::result(response.array_buffer().map_err(js_error))
+        .and_then(|promise| JsFuture::from(promise).map_err(js_error))
+        .map(|buffer| Uint8Array::new(&buffer).to_vec())
+}
+
+/// The body of `response`, in chunks as they arrive.
+fn chunks(response: &Response) -> impl Stream<Item = Vec<u8>, Error = Error> {
+    let reader = response.body().map(|body| {
+        body.get_reader()
+            .unchecked_into::<ReadableStreamDefaultReader>()
+    });
+    stream::unfold(reader, |reader| {
+        let reader = reader?;
+        let read = JsFuture::from(reader.read())
+            .map_err(js_error)
+            .and_then(move |result| {
+                let done = Reflect::get(&result, &"done".into()).map_err(js_error)?;
+                if done.as_bool() == Some(true) {
+                    return Ok((Vec::new(), None));
+                }
+                let value = Reflect::get(&result, &"value".into()).map_err(js_error)?;
+                Ok((Uint8Array::new(&value).to_vec(), Some(reader)))
+            });
+        Some(read)
+    })
+}
+
+/// An exception thrown by the browser, as an `Error`.
+fn js_error(value: JsValue) -> Error {
+    match value.dyn_ref::<js_sys::Error>() {
+        Some(e) => format_err!("{}", String::from(e.message())),
+        None => format_err!("{:?}", value),
+    }
+}
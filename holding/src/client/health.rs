@@ -0,0 +1,142 @@
+//! Apiserver health probes: `/healthz`, `/readyz` and `/livez`.
+
+use failure::Error;
+use futures::{future, Future};
+use hyper::{Body, Method, Request};
+use std::str;
+use std::sync::Arc;
+
+use super::{hyper_uri, transport, wait, Client};
+
+/// The result of a single named check, from a `?verbose` probe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthCheck {
+    pub name: String,
+    pub ok: bool,
+    /// Failure reason, if any.
+    pub message: Option<String>,
+}
+
+/// The result of a health probe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub ok: bool,
+    /// Individual checks, if the server reported them.
+    pub checks: Vec<HealthCheck>,
+}
+
+impl Health {
+    /// Parse a verbose probe response, eg:
+    ///
+    /// ```text
+    /// [+]ping ok
+    /// [-]etcd failed: reason withheld
+    /// healthz check failed
+    /// ```
+    pub fn parse(ok: bool, body: &str) -> Self {
+        let checks = body
+            .lines()
+            .filter_map(|line| {
+                let (ok, rest) = if let Some(rest) = line.strip_prefix("[+]") {
+                    (true, rest)
+                } else if let Some(rest) = line.strip_prefix("[-]") {
+                    (false, rest)
+                } else {
+                    return None;
+                };
+                let mut parts = rest.splitn(2, ' ');
+                let name = parts.next().unwrap_or_default().to_string();
+                let status = parts.next().unwrap_or_default();
+                let message = status.strip_prefix("failed: ").map(String::from);
+                Some(HealthCheck { name, ok, message })
+            })
+            .collect();
+        Health { ok, checks }
+    }
+
+    /// Checks that did not pass.
+    pub fn failed(&self) -> impl Iterator<Item = &HealthCheck> {
+        self.checks.iter().filter(|c| !c.ok)
+    }
+}
+
+impl Client {
+    /// Overall apiserver health.  Deprecated upstream in favour of
+    /// `readyz` and `livez`, but served by all versions.
+    pub fn healthz(&self) -> impl Future<Item = Health, Error = Error> + Send {
+        self.probe("healthz", None)
+    }
+
+    /// Whether the apiserver is ready to serve requests.
+    pub fn readyz(&self) -> impl Future<Item = Health, Error = Error> + Send {
+        self.probe("readyz", None)
+    }
+
+    /// Whether the apiserver is alive, ie: should not be restarted.
+    pub fn livez(&self) -> impl Future<Item = Health, Error = Error> + Send {
+        self.probe("livez", None)
+    }
+
+    /// Probe a single named check, eg: `probe("readyz", Some("etcd"))`.
+    ///
+    /// An unhealthy server is reported as `Health { ok: false, .. }`,
+    /// not an error.
+    pub fn probe(
+        &self,
+        endpoint: &str,
+        check: Option<&str>,
+    ) -> impl Future<Item = Health, Error = Error> + Send {
+        let mut segments = vec![endpoint];
+        segments.extend(check);
+        let req = self.path_url(&segments).and_then(|mut url| {
+            url.set_query(Some("verbose"));
+            Request::builder()
+                .method(Method::GET)
+                .uri(hyper_uri(url))
+                .body(Body::empty())
+                .map_err(|e| e.into())
+        });
+        let client = Arc::clone(&self.client);
+        let max = client.max_body_size;
+        let f = future::result(req)
+            .and_then(move |req| client.request(req))
+            .and_then(move |res| {
+                let status = res.status();
+                transport::read_body(res.into_body(), max).and_then(move |body| {
+                    let body = str::from_utf8(&body)?;
+                    Ok(Health::parse(status.is_success(), body))
+                })
+            });
+        match self.timeout {
+            Some(after) => future::Either::A(wait::timeout(f, after)),
+            None => future::Either::B(f),
+        }
+    }
+}
+
+#[test]
+fn parse_verbose() {
+    let h = Health::parse(
+        false,
+        "[+]ping ok\n[+]log ok\n[-]etcd failed: reason withheld\n\
+         [+]poststarthook/generic-apiserver-start-informers ok\nhealthz check failed\n",
+    );
+    assert!(!h.ok);
+    assert_eq!(h.checks.len(), 4);
+    assert_eq!(
+        h.checks[1],
+        HealthCheck {
+            name: "log".into(),
+            ok: true,
+            message: None,
+        }
+    );
+    let failed: Vec<_> = h.failed().collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].name, "etcd");
+    assert_eq!(failed[0].message.as_ref().unwrap(), "reason withheld");
+
+    let h = Health::parse(true, "ok");
+    assert!(h.ok);
+    assert!(h.checks.is_empty());
+}
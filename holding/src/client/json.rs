@@ -0,0 +1,53 @@
+//! Decoding responses.
+
+use failure::{Error, ResultExt};
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json;
+#[cfg(feature = "simd-json")]
+use simd_json;
+
+use api::meta::v1::Status;
+use k8sclient::error::{ApiError, ClientError};
+
+/// Decode `body`, which is `what`, as JSON.
+#[cfg(not(feature = "simd-json"))]
+pub(crate) fn from_json<T: DeserializeOwned>(what: &str, body: &[u8]) -> Result<T, Error> {
+    let o = serde_json::from_slice(body)
+        .with_context(|e| ClientError::new_decode_error(what, e, body.to_vec()))?;
+    Ok(o)
+}
+
+/// Decode `body`, which is `what`, as JSON, using simd-json.  It needs
+/// a copy of `body` to decode in place, but is still much faster for
+/// large lists.
+#[cfg(feature = "simd-json")]
+pub(crate) fn from_json<T: DeserializeOwned>(what: &str, body: &[u8]) -> Result<T, Error> {
+    let mut buf = body.to_vec();
+    match simd_json::serde::from_slice(&mut buf) {
+        Ok(o) => Ok(o),
+        // serde_json's error (which `ClientError` wants) says where
+        // and what the problem is.  Or, if serde_json manages to
+        // decode it after all, so much the better.
+        Err(e) => {
+            debug!("simd-json failed to decode {}: {}", what, e);
+            let o = serde_json::from_slice(body)
+                .with_context(|e| ClientError::new_decode_error(what, e, body.to_vec()))?;
+            Ok(o)
+        }
+    }
+}
+
+/// Decode an unsuccessful response.
+pub(crate) fn error_response(httpstatus: StatusCode, body: &[u8]) -> ApiError {
+    match serde_json::from_slice::<Status>(body) {
+        Ok(status) => status.into(),
+        Err(e) => {
+            debug!(
+                "Failed to parse error Status ({}), falling back to HTTP status",
+                ClientError::new_decode_error("error Status", &e, body.to_vec())
+            );
+            ApiError::from_http(httpstatus, body)
+        }
+    }
+}
@@ -0,0 +1,693 @@
+//! Leader election, so that of several replicas of a controller only
+//! one acts at a time.
+//!
+//! The leader holds a lock object in the cluster, renewing it every
+//! few seconds.  Other candidates take it over once it hasn't been
+//! renewed for the lease duration.  Like client-go, candidates time the
+//! lease by their own clocks, from when they saw the lock change, so
+//! their clocks need not agree.  A leader that can't renew the lock
+//! stops leading after the renew deadline, a little before the others
+//! may take over.
+//!
+//! The lock is a `ResourceLock`: a `LeaseLock` (coordination.k8s.io/v1
+//! Lease), or, for older clusters and those without coordination.k8s.io,
+//! a `ConfigMapLock` or `EndpointsLock`, which keep the lock's record in
+//! an annotation.  All the candidates must use the same kind of lock.
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate futures;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use futures::Stream;
+//! # use kubernetes_holding::client::leaderelection::{LeaderElector, LeaseLock};
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let lock = LeaseLock::new(&client, "kube-system", "my-controller");
+//! let elector = LeaderElector::new(Arc::new(lock), "my-controller-0");
+//! let leading = elector.run(Duration::from_secs(2)).for_each(|leader| {
+//!     println!("leading: {}", leader);
+//!     Ok(())
+//! });
+//! tokio::runtime::Runtime::new()?.block_on(leading)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use failure::Error;
+use futures::{future, Future, Stream};
+use serde_json::{self, Value};
+use tokio::timer::Interval;
+
+use api::meta::v1::GetOptions;
+use api::meta::GroupVersionResource;
+use api::{self, Time};
+use k8sclient::error::ApiError;
+
+use super::Client;
+
+/// The annotation holding the record of a `ConfigMapLock` or
+/// `EndpointsLock`, as JSON.
+pub const LEADER_ANNOTATION: &str = "control-plane.alpha.kubernetes.io/leader";
+
+/// The lease duration used by default, as in client-go.
+pub const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(15);
+
+/// How long a leader keeps leading without renewing its lease, by
+/// default, as in client-go.
+pub const DEFAULT_RENEW_DEADLINE: Duration = Duration::from_secs(10);
+
+const LEASES: GroupVersionResource<'static> = GroupVersionResource {
+    group: "coordination.k8s.io",
+    version: "v1",
+    resource: "leases",
+};
+
+const CONFIG_MAPS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "configmaps",
+};
+
+const ENDPOINTS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "endpoints",
+};
+
+/// Who holds a lock, and since when.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderElectionRecord {
+    /// Empty if nobody does.
+    #[serde(default)]
+    pub holder_identity: String,
+    #[serde(default)]
+    pub lease_duration_seconds: i32,
+    #[serde(default)]
+    pub acquire_time: Time,
+    #[serde(default)]
+    pub renew_time: Time,
+    /// How many times the lock has changed hands.
+    #[serde(default)]
+    pub leader_transitions: i32,
+}
+
+pub type LockFuture<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
+
+/// An object in the cluster holding a `LeaderElectionRecord`.
+pub trait ResourceLock: fmt::Debug + Send + Sync {
+    /// The record, and the object holding it, or `None` if there is no
+    /// such object yet.
+    fn get(&self) -> LockFuture<Option<(Value, LeaderElectionRecord)>>;
+
+    /// Create the object, holding `record`.  Fails if it already
+    /// exists.
+    fn create(&self, record: &LeaderElectionRecord) -> LockFuture<Value>;
+
+    /// Replace the record of `object`, as returned by `get`.  Fails with
+    /// a conflict if the object has changed since.
+    fn update(&self, object: Value, record: &LeaderElectionRecord) -> LockFuture<Value>;
+
+    /// Names the lock in logs, eg `lease kube-system/my-controller`.
+    fn describe(&self) -> String;
+}
+
+/// A coordination.k8s.io/v1 Lease.
+#[derive(Debug, Clone)]
+pub struct LeaseLock {
+    client: Client,
+    namespace: String,
+    name: String,
+}
+
+impl LeaseLock {
+    pub fn new(client: &Client, namespace: &str, name: &str) -> Self {
+        LeaseLock {
+            client: client.clone(),
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+impl ResourceLock for LeaseLock {
+    fn get(&self) -> LockFuture<Option<(Value, LeaderElectionRecord)>> {
+        let lease = self.client.get_opt::<Value>(
+            &LEASES,
+            Some(&self.namespace),
+            &self.name,
+            GetOptions::default(),
+        );
+        Box::new(lease.map(|lease| {
+            lease.map(|lease| {
+                let spec = &lease["spec"];
+                let record = LeaderElectionRecord {
+                    holder_identity: spec["holderIdentity"].as_str().unwrap_or("").to_string(),
+                    lease_duration_seconds: spec["leaseDurationSeconds"].as_i64().unwrap_or(0)
+                        as i32,
+                    acquire_time: spec["acquireTime"].as_str().unwrap_or("").to_string(),
+                    renew_time: spec["renewTime"].as_str().unwrap_or("").to_string(),
+                    leader_transitions: spec["leaseTransitions"].as_i64().unwrap_or(0) as i32,
+                };
+                (lease, record)
+            })
+        }))
+    }
+
+    fn create(&self, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        let lease = json!({
+            "apiVersion": "coordination.k8s.io/v1",
+            "kind": "Lease",
+            "metadata": {"name": self.name, "namespace": self.namespace},
+            "spec": lease_spec(record),
+        });
+        Box::new(self.client.create(&LEASES, &lease, GetOptions::default()))
+    }
+
+    fn update(&self, mut object: Value, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        object["spec"] = lease_spec(record);
+        Box::new(self.client.update(&LEASES, &object))
+    }
+
+    fn describe(&self) -> String {
+        format!("lease {}/{}", self.namespace, self.name)
+    }
+}
+
+fn lease_spec(record: &LeaderElectionRecord) -> Value {
+    json!({
+        "holderIdentity": record.holder_identity,
+        "leaseDurationSeconds": record.lease_duration_seconds,
+        "acquireTime": micro_time(&record.acquire_time),
+        "renewTime": micro_time(&record.renew_time),
+        "leaseTransitions": record.leader_transitions,
+    })
+}
+
+/// `time`, with the microseconds a Lease's MicroTimes must have.
+fn micro_time(time: &str) -> String {
+    match time.strip_suffix('Z') {
+        Some(secs) if !secs.contains('.') => format!("{}.000000Z", secs),
+        _ => time.to_string(),
+    }
+}
+
+/// A ConfigMap, with the record in its `LEADER_ANNOTATION`.
+#[derive(Debug, Clone)]
+pub struct ConfigMapLock(AnnotationLock);
+
+impl ConfigMapLock {
+    pub fn new(client: &Client, namespace: &str, name: &str) -> Self {
+        ConfigMapLock(AnnotationLock::new(
+            client,
+            &CONFIG_MAPS,
+            "ConfigMap",
+            namespace,
+            name,
+        ))
+    }
+}
+
+impl ResourceLock for ConfigMapLock {
+    fn get(&self) -> LockFuture<Option<(Value, LeaderElectionRecord)>> {
+        self.0.get()
+    }
+
+    fn create(&self, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        self.0.create(record)
+    }
+
+    fn update(&self, object: Value, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        self.0.update(object, record)
+    }
+
+    fn describe(&self) -> String {
+        self.0.describe()
+    }
+}
+
+/// An Endpoints, with the record in its `LEADER_ANNOTATION`, as used
+/// by kube-controller-manager and kube-scheduler before Leases.
+#[derive(Debug, Clone)]
+pub struct EndpointsLock(AnnotationLock);
+
+impl EndpointsLock {
+    pub fn new(client: &Client, namespace: &str, name: &str) -> Self {
+        EndpointsLock(AnnotationLock::new(
+            client,
+            &ENDPOINTS,
+            "Endpoints",
+            namespace,
+            name,
+        ))
+    }
+}
+
+impl ResourceLock for EndpointsLock {
+    fn get(&self) -> LockFuture<Option<(Value, LeaderElectionRecord)>> {
+        self.0.get()
+    }
+
+    fn create(&self, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        self.0.create(record)
+    }
+
+    fn update(&self, object: Value, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        self.0.update(object, record)
+    }
+
+    fn describe(&self) -> String {
+        self.0.describe()
+    }
+}
+
+/// A core/v1 object with the record in an annotation.
+#[derive(Debug, Clone)]
+struct AnnotationLock {
+    client: Client,
+    resource: &'static str,
+    kind: &'static str,
+    namespace: String,
+    name: String,
+}
+
+impl AnnotationLock {
+    fn new(
+        client: &Client,
+        gvr: &GroupVersionResource<'static>,
+        kind: &'static str,
+        namespace: &str,
+        name: &str,
+    ) -> Self {
+        AnnotationLock {
+            client: client.clone(),
+            resource: gvr.resource,
+            kind,
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    fn gvr(&self) -> GroupVersionResource<'static> {
+        GroupVersionResource {
+            group: "",
+            version: "v1",
+            resource: self.resource,
+        }
+    }
+
+    fn get(&self) -> LockFuture<Option<(Value, LeaderElectionRecord)>> {
+        let object = self.client.get_opt::<Value>(
+            &self.gvr(),
+            Some(&self.namespace),
+            &self.name,
+            GetOptions::default(),
+        );
+        Box::new(object.and_then(|object| {
+            let object = match object {
+                Some(object) => object,
+                None => return Ok(None),
+            };
+            // An object without the annotation is an unheld lock.
+            let record = match object["metadata"]["annotations"][LEADER_ANNOTATION].as_str() {
+                Some(record) => serde_json::from_str(record)?,
+                None => Default::default(),
+            };
+            Ok(Some((object, record)))
+        }))
+    }
+
+    fn create(&self, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        let record = match serde_json::to_string(record) {
+            Ok(record) => record,
+            Err(e) => return Box::new(future::err(e.into())),
+        };
+        let object = json!({
+            "apiVersion": "v1",
+            "kind": self.kind,
+            "metadata": {
+                "name": self.name,
+                "namespace": self.namespace,
+                "annotations": {LEADER_ANNOTATION: record},
+            },
+        });
+        Box::new(
+            self.client
+                .create(&self.gvr(), &object, GetOptions::default()),
+        )
+    }
+
+    fn update(&self, mut object: Value, record: &LeaderElectionRecord) -> LockFuture<Value> {
+        let record = match serde_json::to_string(record) {
+            Ok(record) => record,
+            Err(e) => return Box::new(future::err(e.into())),
+        };
+        object["metadata"]["annotations"][LEADER_ANNOTATION] = Value::String(record);
+        Box::new(self.client.update(&self.gvr(), &object))
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} {}/{}",
+            self.kind.to_lowercase(),
+            self.namespace,
+            self.name
+        )
+    }
+}
+
+/// A candidate for leadership, under the name `identity`, which must
+/// be unique among the candidates (eg: the pod name).
+#[derive(Debug, Clone)]
+pub struct LeaderElector {
+    lock: Arc<dyn ResourceLock>,
+    identity: String,
+    lease_duration: Duration,
+    renew_deadline: Duration,
+    observed: Arc<Mutex<Observed>>,
+}
+
+/// The lock's record as last seen, and when it last changed.
+#[derive(Debug)]
+struct Observed {
+    record: Option<LeaderElectionRecord>,
+    at: Instant,
+    /// When this candidate last sent a write of the lock that
+    /// succeeded.
+    written: Option<Instant>,
+}
+
+impl LeaderElector {
+    pub fn new<S: Into<String>>(lock: Arc<dyn ResourceLock>, identity: S) -> Self {
+        LeaderElector {
+            lock,
+            identity: identity.into(),
+            lease_duration: DEFAULT_LEASE_DURATION,
+            renew_deadline: DEFAULT_RENEW_DEADLINE,
+            observed: Arc::new(Mutex::new(Observed {
+                record: None,
+                at: Instant::now(),
+                written: None,
+            })),
+        }
+    }
+
+    /// How long others wait, after the lock was last renewed, before
+    /// taking it over.  The default is 15s.
+    pub fn lease_duration(mut self, duration: Duration) -> Self {
+        self.lease_duration = duration;
+        self
+    }
+
+    /// How long the leader keeps leading when it can't renew the lock,
+    /// from when it sent its last successful renewal.  This must be
+    /// less than the lease duration, so that the leader stops before
+    /// anyone else can take over; it is cut to the lease duration if
+    /// not.  The default is 10s.
+    pub fn renew_deadline(mut self, deadline: Duration) -> Self {
+        self.renew_deadline = deadline;
+        self
+    }
+
+    /// Whether this candidate held the lock when last seen, and has
+    /// renewed it within the renew deadline.
+    pub fn is_leader(&self) -> bool {
+        let observed = self.observed.lock().unwrap();
+        let deadline = cmp::min(self.renew_deadline, self.lease_duration);
+        observed
+            .record
+            .as_ref()
+            .is_some_and(|r| r.holder_identity == self.identity && !expired(r, observed.at))
+            && observed
+                .written
+                .is_some_and(|written| written.elapsed() < deadline)
+    }
+
+    /// Who held the lock when last seen.
+    pub fn leader(&self) -> Option<String> {
+        let observed = self.observed.lock().unwrap();
+        observed
+            .record
+            .as_ref()
+            .map(|r| r.holder_identity.clone())
+            .filter(|holder| !holder.is_empty())
+    }
+
+    /// Take the lock if nobody holds it or its lease has run out, or
+    /// renew it if this candidate holds it.  Resolves to whether this
+    /// candidate now holds it.
+    pub fn try_acquire_or_renew(&self) -> impl Future<Item = bool, Error = Error> + Send {
+        let this = self.clone();
+        self.lock
+            .get()
+            .and_then(move |current| -> LockFuture<bool> {
+                let now = api::now();
+                let (object, old) = match current {
+                    Some(current) => current,
+                    None => {
+                        let record = this.record(None, now);
+                        let sent = Instant::now();
+                        let created = this.lock.create(&record);
+                        return Box::new(this.written(created, record, sent));
+                    }
+                };
+                if !this.observe(&old) {
+                    return Box::new(future::ok(false));
+                }
+                let record = this.record(Some(&old), now);
+                let sent = Instant::now();
+                let updated = this.lock.update(object, &record);
+                Box::new(this.written(updated, record, sent))
+            })
+    }
+
+    /// Try to acquire or renew the lock every `retry_period`, yielding
+    /// whether this candidate is the leader whenever that changes,
+    /// starting with the first try.  A leader should stop acting as
+    /// one as soon as this yields `false`.
+    ///
+    /// Failed tries are logged, and don't end the stream: a leader that
+    /// can't renew the lock stays the leader until its renew deadline.
+    pub fn run(&self, retry_period: Duration) -> impl Stream<Item = bool, Error = Error> + Send {
+        let this = self.clone();
+        let mut leading = None;
+        Interval::new(Instant::now(), retry_period)
+            .map_err(Error::from)
+            .and_then(move |_| {
+                let elector = this.clone();
+                this.try_acquire_or_renew().or_else(move |e| {
+                    warn!(
+                        "Unable to acquire or renew {}: {}",
+                        elector.lock.describe(),
+                        e
+                    );
+                    Ok(elector.is_leader())
+                })
+            })
+            .filter_map(move |leader| {
+                if leading == Some(leader) {
+                    return None;
+                }
+                leading = Some(leader);
+                Some(leader)
+            })
+    }
+
+    /// Note the record read from the lock, returning whether this
+    /// candidate may write it: whether it is unheld, or held by this
+    /// candidate, or its lease has run out.
+    fn observe(&self, record: &LeaderElectionRecord) -> bool {
+        let mut observed = self.observed.lock().unwrap();
+        if observed.record.as_ref() != Some(record) {
+            observed.record = Some(record.clone());
+            observed.at = Instant::now();
+        }
+        record.holder_identity.is_empty()
+            || record.holder_identity == self.identity
+            || expired(record, observed.at)
+    }
+
+    /// The record for this candidate to write, replacing `old`.
+    fn record(&self, old: Option<&LeaderElectionRecord>, now: Time) -> LeaderElectionRecord {
+        let (acquire_time, leader_transitions) = match old {
+            Some(old) if old.holder_identity == self.identity => {
+                (old.acquire_time.clone(), old.leader_transitions)
+            }
+            Some(old) => (now.clone(), old.leader_transitions + 1),
+            None => (now.clone(), 0),
+        };
+        LeaderElectionRecord {
+            holder_identity: self.identity.clone(),
+            lease_duration_seconds: self.lease_duration.as_secs() as i32,
+            acquire_time,
+            renew_time: now,
+            leader_transitions,
+        }
+    }
+
+    /// Whether writing `record`, sent at `sent`, succeeded, or lost a
+    /// race with another candidate.
+    ///
+    /// The lease is timed from when the write was sent, not when it
+    /// succeeded: the others may have seen it any time after that.
+    fn written<F>(
+        &self,
+        write: F,
+        record: LeaderElectionRecord,
+        sent: Instant,
+    ) -> impl Future<Item = bool, Error = Error>
+    where
+        F: Future<Item = Value, Error = Error>,
+    {
+        let this = self.clone();
+        write.then(move |r| match r {
+            Ok(_) => {
+                if this.leader().as_ref() != Some(&this.identity) {
+                    info!("{} acquired {}", this.identity, this.lock.describe());
+                }
+                let mut observed = this.observed.lock().unwrap();
+                observed.record = Some(record);
+                observed.at = sent;
+                observed.written = Some(sent);
+                Ok(true)
+            }
+            Err(ref e)
+                if ApiError::from_error(e)
+                    .is_some_and(|e| e.is_conflict() || e.is_already_exists()) =>
+            {
+                debug!(
+                    "{} lost the race for {}: {}",
+                    this.identity,
+                    this.lock.describe(),
+                    e
+                );
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        })
+    }
+}
+
+/// Whether the lease of `record`, seen at `at`, has run out.
+fn expired(record: &LeaderElectionRecord, at: Instant) -> bool {
+    at + Duration::from_secs(record.lease_duration_seconds.max(0) as u64) <= Instant::now()
+}
+
+#[test]
+fn test_leader_election() {
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let locks: Vec<Arc<dyn ResourceLock>> = vec![
+        Arc::new(LeaseLock::new(&client, "ns", "lock")),
+        Arc::new(ConfigMapLock::new(&client, "ns", "lock")),
+        Arc::new(EndpointsLock::new(&client, "ns", "lock")),
+    ];
+    for lock in locks {
+        let a = LeaderElector::new(Arc::clone(&lock), "a");
+        let b = LeaderElector::new(Arc::clone(&lock), "b");
+        assert!(a.try_acquire_or_renew().wait().unwrap(), "{:?}", lock);
+        assert!(a.is_leader());
+        assert!(!b.try_acquire_or_renew().wait().unwrap());
+        assert!(!b.is_leader());
+        assert_eq!(b.leader().as_deref(), Some("a"));
+        // Renewing.
+        assert!(a.try_acquire_or_renew().wait().unwrap());
+        let (_, record) = lock.get().wait().unwrap().unwrap();
+        assert_eq!(record.holder_identity, "a");
+        assert_eq!(record.lease_duration_seconds, 15);
+        assert_eq!(record.leader_transitions, 0);
+
+        // Once a's lease runs out, b takes over.
+        let a = a.lease_duration(Duration::from_secs(0));
+        assert!(a.try_acquire_or_renew().wait().unwrap());
+        assert!(b.try_acquire_or_renew().wait().unwrap());
+        assert!(b.is_leader());
+        assert!(!a.try_acquire_or_renew().wait().unwrap());
+        let (_, record) = lock.get().wait().unwrap().unwrap();
+        assert_eq!(record.holder_identity, "b");
+        assert_eq!(record.leader_transitions, 1);
+    }
+    assert_eq!(
+        micro_time("2020-01-02T03:04:05Z"),
+        "2020-01-02T03:04:05.000000Z"
+    );
+}
+
+#[test]
+fn test_failed_renewal() {
+    use std::thread;
+
+    use api::meta::v1::StatusReason;
+
+    use super::fake::FakeClient;
+
+    /// Takes `delay` to write the lock.
+    #[derive(Debug)]
+    struct Slow {
+        lock: LeaseLock,
+        delay: Duration,
+    }
+
+    impl ResourceLock for Slow {
+        fn get(&self) -> LockFuture<Option<(Value, LeaderElectionRecord)>> {
+            self.lock.get()
+        }
+
+        fn create(&self, record: &LeaderElectionRecord) -> LockFuture<Value> {
+            thread::sleep(self.delay);
+            self.lock.create(record)
+        }
+
+        fn update(&self, object: Value, record: &LeaderElectionRecord) -> LockFuture<Value> {
+            thread::sleep(self.delay);
+            self.lock.update(object, record)
+        }
+
+        fn describe(&self) -> String {
+            self.lock.describe()
+        }
+    }
+
+    let client = FakeClient::new();
+    let lock = Arc::new(Slow {
+        lock: LeaseLock::new(&client, "ns", "lock"),
+        delay: Duration::from_millis(150),
+    });
+    let a = LeaderElector::new(lock, "a").renew_deadline(Duration::from_millis(200));
+    assert!(a.try_acquire_or_renew().wait().unwrap());
+    assert!(a.is_leader());
+
+    // The deadline runs from when the write was sent, not when it
+    // succeeded.
+    thread::sleep(Duration::from_millis(100));
+    assert!(!a.is_leader());
+
+    // Failing to renew doesn't change the lock, which a still holds
+    // for others, but a stops leading until it renews again.
+    assert!(a.try_acquire_or_renew().wait().unwrap());
+    client.server().fail_next(
+        "update",
+        "leases",
+        ApiError::new(StatusReason::InternalError, "etcd is down"),
+    );
+    assert!(a.try_acquire_or_renew().wait().is_err());
+    assert_eq!(a.leader().as_deref(), Some("a"));
+    assert!(!a.is_leader());
+    assert!(a.try_acquire_or_renew().wait().unwrap());
+
+    // The deadline is cut to the lease duration.
+    let b = LeaderElector::new(Arc::new(LeaseLock::new(&client, "ns", "other")), "b")
+        .lease_duration(Duration::from_secs(0));
+    assert!(b.try_acquire_or_renew().wait().unwrap());
+    assert!(!b.is_leader());
+}
@@ -0,0 +1,743 @@
+//! Reading container logs, following one container's log across
+//! restarts, and following the logs of every pod matching a selector,
+//! like `stern`.
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate futures;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use futures::Stream;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let lines = client
+//!     .tail_logs(Some("default"), "app=web", Default::default())
+//!     .for_each(|l| {
+//!         println!("{} {}: {}", l.pod, l.container, l.line);
+//!         Ok(())
+//!     });
+//! tokio::runtime::Runtime::new()?.block_on(lines)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use failure::Error;
+use futures::{future, Async, Future, Poll, Stream};
+use hyper::{Body, Method, Request, StatusCode};
+use serde_json::Value;
+use serde_urlencoded;
+use tokio::timer::Delay;
+
+use api::core::v1::PodLogOptions;
+use api::meta::v1::{EventType, GetOptions, ListOptions, WatchEvent};
+use api::meta::GroupVersionResource;
+use k8sclient::error::ApiError;
+
+use super::retry::RetryPolicy;
+use super::shutdown::guard_with;
+use super::{do_stream, hyper_uri, watch, Client};
+
+const PODS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "pods",
+};
+
+/// A line from a container's log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    /// Without its newline.
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TailOptions {
+    /// Only follow containers with this name.
+    pub container: Option<String>,
+    /// For containers already running, start this far back in their
+    /// logs.  Containers that start later are followed from their first
+    /// line.
+    pub since_seconds: Option<i32>,
+    /// For containers already running, start this many lines from the
+    /// end of their logs.
+    pub tail_lines: Option<i32>,
+    /// Prefix each line with an RFC 3339 timestamp.
+    pub timestamps: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowOptions {
+    /// Needed if the pod has more than one container and doesn't name
+    /// a default one with the `kubectl.kubernetes.io/default-container`
+    /// annotation.
+    pub container: Option<String>,
+    /// For the container as it is when following starts, start this
+    /// far back in its log.  Later instances of it are followed from
+    /// their first line.
+    pub since_seconds: Option<i32>,
+    /// For the container as it is when following starts, start this
+    /// many lines from the end of its log.
+    pub tail_lines: Option<i32>,
+    /// Prefix each line with an RFC 3339 timestamp.
+    pub timestamps: bool,
+    /// When the log of a container that is still running is cut off,
+    /// drop the lines already passed on when resuming it.  Otherwise
+    /// those written in the second before it was cut off are repeated.
+    pub dedupe: bool,
+    /// How long to wait before looking at the pod again, once a log has
+    /// ended.
+    pub poll_interval: Duration,
+}
+
+impl Default for FollowOptions {
+    fn default() -> Self {
+        FollowOptions {
+            container: None,
+            since_seconds: None,
+            tail_lines: None,
+            timestamps: false,
+            dedupe: true,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Client {
+    /// The log of a container in `pod`, a line at a time.  With
+    /// `opts.follow`, the stream lasts until the container exits.
+    pub fn logs(
+        &self,
+        namespace: &str,
+        pod: &str,
+        opts: PodLogOptions,
+    ) -> impl Stream<Item = String, Error = Error> + Send {
+        let req = self
+            .subresource_url(&PODS, Some(namespace), pod, "log")
+            .and_then(|mut url| {
+                url.set_query(Some(&serde_urlencoded::to_string(opts)?));
+                Ok(Request::builder()
+                    .method(Method::GET)
+                    .uri(hyper_uri(url))
+                    .body(Body::empty())?)
+            });
+        let client = Arc::clone(&self.client);
+        let max = client.max_line_length;
+        let lines = future::result(req)
+            .and_then(move |req| do_stream(client, req, "Log"))
+            .map(move |body| {
+                watch::lines_limited(body, max).map(|mut line| {
+                    if line.last() == Some(&b'\n') {
+                        line.pop();
+                    }
+                    String::from_utf8_lossy(&line).into_owned()
+                })
+            })
+            .flatten_stream();
+        guard_with(self.shutdown(), lines)
+    }
+
+    /// Follow the log of a container in `pod` for as long as the pod
+    /// lasts.  Whenever the log ends (the container restarted, the log
+    /// was rotated, or the connection was lost), the pod is looked at
+    /// again: the log of a container that is still running is resumed
+    /// where it was cut off, and that of a new instance of it is
+    /// followed from the start.  The stream ends once the pod has
+    /// finished (or is deleted) and the log of its last container has
+    /// been read.
+    ///
+    /// Failing to look at the pod ends the stream with the error,
+    /// unless the failure is transient, when it is retried.
+    ///
+    /// Requires a tokio runtime.
+    pub fn follow_logs(
+        &self,
+        namespace: &str,
+        pod: &str,
+        opts: FollowOptions,
+    ) -> impl Stream<Item = String, Error = Error> + Send {
+        let mut follow = Follow {
+            client: self.clone(),
+            namespace: namespace.to_string(),
+            pod: pod.to_string(),
+            container: opts.container.clone(),
+            opts,
+            instance: None,
+            last: None,
+            skip: None,
+            state: FollowState::Done,
+        };
+        follow.state = FollowState::Lookup(follow.lookup());
+        guard_with(self.shutdown(), follow)
+    }
+
+    /// Follow the logs of all the running containers in pods matching
+    /// `label_selector`, merging their lines as they arrive.  Pods
+    /// are watched, so that containers that start (or restart) later
+    /// are followed too.
+    ///
+    /// A container whose log can't be read is skipped, with a warning.
+    /// The stream only ends if watching the pods fails, or the client's
+    /// `Shutdown` is triggered.
+    pub fn tail_logs(
+        &self,
+        namespace: Option<&str>,
+        label_selector: &str,
+        opts: TailOptions,
+    ) -> impl Stream<Item = LogLine, Error = Error> + Send {
+        let list_opts = ListOptions {
+            label_selector: label_selector.to_string(),
+            ..Default::default()
+        };
+        let mut tail = Tail {
+            client: self.clone(),
+            namespace: namespace.map(str::to_string),
+            list_opts: list_opts.clone(),
+            opts,
+            followed: HashSet::new(),
+            logs: Vec::new(),
+            events: Box::new(future::empty().into_stream()),
+        };
+        let lines = self
+            .list(&PODS, namespace, list_opts)
+            .map(move |list: Value| {
+                for pod in list["items"].as_array().into_iter().flatten() {
+                    tail.follow(pod, true);
+                }
+                tail.watch(list["metadata"]["resourceVersion"].as_str().unwrap_or(""));
+                tail
+            })
+            .flatten_stream();
+        // Otherwise the ended watch would be restarted.
+        guard_with(self.shutdown(), lines)
+    }
+}
+
+type BoxStream<T> = Box<dyn Stream<Item = T, Error = Error> + Send>;
+
+struct Tail {
+    client: Client,
+    namespace: Option<String>,
+    list_opts: ListOptions,
+    opts: TailOptions,
+    /// (namespace, pod, container ID) of each container followed, so
+    /// that a restarted container (which gets a new ID) is followed
+    /// again.
+    followed: HashSet<(String, String, String)>,
+    logs: Vec<BoxStream<LogLine>>,
+    events: BoxStream<WatchEvent>,
+}
+
+impl Tail {
+    fn watch(&mut self, resource_version: &str) {
+        let opts = ListOptions {
+            resource_version: resource_version.to_string(),
+            ..self.list_opts.clone()
+        };
+        self.events = Box::new(
+            self.client
+                .watch_list(&PODS, self.namespace.as_deref(), opts),
+        );
+    }
+
+    /// Follow any running containers in `pod` that aren't followed
+    /// yet.  `existing` if they were running before the tail started.
+    fn follow(&mut self, pod: &Value, existing: bool) {
+        let meta = &pod["metadata"];
+        let namespace = meta["namespace"].as_str().unwrap_or_default();
+        let name = meta["name"].as_str().unwrap_or_default();
+        let status = &pod["status"];
+        let statuses = status["initContainerStatuses"]
+            .as_array()
+            .into_iter()
+            .chain(status["containerStatuses"].as_array())
+            .flatten();
+        for c in statuses {
+            let container = c["name"].as_str().unwrap_or_default();
+            let id = match c["containerID"].as_str() {
+                Some(id) if c["state"]["running"].is_object() => id,
+                _ => continue,
+            };
+            if self
+                .opts
+                .container
+                .as_ref()
+                .is_some_and(|want| want.as_str() != container)
+            {
+                continue;
+            }
+            let key = (namespace.to_string(), name.to_string(), id.to_string());
+            if !self.followed.insert(key) {
+                continue;
+            }
+            debug!("Following {}/{} {}", namespace, name, container);
+            let opts = PodLogOptions {
+                container: Some(container.to_string()),
+                follow: true,
+                timestamps: self.opts.timestamps,
+                since_seconds: self.opts.since_seconds.filter(|_| existing),
+                tail_lines: self.opts.tail_lines.filter(|_| existing),
+                ..Default::default()
+            };
+            let (namespace, pod, container) = (
+                namespace.to_string(),
+                name.to_string(),
+                container.to_string(),
+            );
+            let describe = format!("{}/{} {}", namespace, pod, container);
+            let lines = self
+                .client
+                .logs(&namespace, &pod, opts)
+                .map(move |line| LogLine {
+                    namespace: namespace.clone(),
+                    pod: pod.clone(),
+                    container: container.clone(),
+                    line,
+                });
+            let lines = lines.map(Some).then(move |r| {
+                r.or_else(|e| {
+                    warn!("Unable to read the log of {}: {}", describe, e);
+                    Ok(None)
+                })
+            });
+            self.logs.push(Box::new(lines.filter_map(|line| line)));
+        }
+    }
+
+    fn forget(&mut self, pod: &Value) {
+        let meta = &pod["metadata"];
+        self.followed.retain(|(namespace, name, _)| {
+            meta["namespace"] != namespace.as_str() || meta["name"] != name.as_str()
+        });
+    }
+}
+
+impl Stream for Tail {
+    type Item = LogLine;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<LogLine>, Error> {
+        // Start following new containers first.
+        loop {
+            match self.events.poll()? {
+                Async::Ready(Some(event)) => {
+                    let rv = event.object["metadata"]["resourceVersion"]
+                        .as_str()
+                        .map(str::to_string);
+                    match event.typ {
+                        EventType::Added | EventType::Modified => self.follow(&event.object, false),
+                        EventType::Deleted => self.forget(&event.object),
+                        EventType::Error => {
+                            return Err(format_err!("Watching pods failed: {}", event.object))
+                        }
+                    }
+                    if let Some(rv) = rv {
+                        self.list_opts.resource_version = rv;
+                    }
+                }
+                // The apiserver ends watches from time to time.
+                Async::Ready(None) => {
+                    let rv = self.list_opts.resource_version.clone();
+                    self.watch(&rv);
+                }
+                Async::NotReady => break,
+            }
+        }
+        let mut i = 0;
+        while i < self.logs.len() {
+            match self.logs[i].poll()? {
+                Async::Ready(Some(line)) => {
+                    // Start with the next log next time, so that a busy
+                    // one can't starve the rest.
+                    self.logs.rotate_left(i + 1);
+                    return Ok(Async::Ready(Some(line)));
+                }
+                Async::Ready(None) => drop(self.logs.remove(i)),
+                Async::NotReady => i += 1,
+            }
+        }
+        Ok(Async::NotReady)
+    }
+}
+
+type BoxFuture<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
+
+const DEFAULT_CONTAINER: &str = "kubectl.kubernetes.io/default-container";
+
+enum FollowState {
+    /// Getting the pod, which is `None` if it is gone.
+    Lookup(BoxFuture<Option<Value>>),
+    /// Before looking at the pod again.
+    Waiting(Delay),
+    Reading(BoxStream<String>),
+    Done,
+}
+
+struct Follow {
+    client: Client,
+    namespace: String,
+    pod: String,
+    /// Once the pod has been seen, if it wasn't given.
+    container: Option<String>,
+    opts: FollowOptions,
+    /// The ID of the container instance being (or last) read.
+    instance: Option<String>,
+    /// The timestamp of the last line read, and how many lines read had
+    /// it.
+    last: Option<(Timestamp, usize)>,
+    /// While resuming: lines up to `last` are dropped.
+    skip: Option<(Timestamp, usize)>,
+    state: FollowState,
+}
+
+/// A log line's timestamp, comparable: the seconds, and the nanoseconds
+/// (which the kubelet writes without trailing zeros) padded to nine
+/// digits.
+type Timestamp = (String, String);
+
+/// The timestamp the kubelet put on `line`, and the rest of the line.
+fn split_timestamp(line: &str) -> Option<(Timestamp, &str)> {
+    let space = line.find(' ')?;
+    let time = line[..space].strip_suffix('Z')?;
+    let (secs, nanos) = match time.find('.') {
+        Some(dot) => (&time[..dot], &time[dot + 1..]),
+        None => (time, ""),
+    };
+    let timestamp = (secs.to_string(), format!("{:0<9}", nanos));
+    Some((timestamp, &line[space + 1..]))
+}
+
+impl Follow {
+    fn lookup(&self) -> BoxFuture<Option<Value>> {
+        let get = self.client.get::<Value>(
+            &PODS,
+            Some(&self.namespace),
+            &self.pod,
+            GetOptions::default(),
+        );
+        Box::new(get.then(|result| match result {
+            Ok(pod) => Ok(Some(pod)),
+            Err(ref e) if ApiError::from_error(e).is_some_and(ApiError::is_not_found) => Ok(None),
+            Err(e) => Err(e),
+        }))
+    }
+
+    fn pause(&self) -> FollowState {
+        FollowState::Waiting(Delay::new(Instant::now() + self.opts.poll_interval))
+    }
+
+    /// What to do next, now that the pod is as `pod` says.
+    fn looked_up(&mut self, pod: Option<Value>) -> FollowState {
+        let pod = match pod {
+            Some(pod) => pod,
+            None => {
+                debug!("{}/{} is gone", self.namespace, self.pod);
+                return FollowState::Done;
+            }
+        };
+        let container = self
+            .container
+            .get_or_insert_with(|| {
+                pod["metadata"]["annotations"][DEFAULT_CONTAINER]
+                    .as_str()
+                    .or_else(|| pod["spec"]["containers"][0]["name"].as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .clone();
+        let status = &pod["status"];
+        let container_status = status["initContainerStatuses"]
+            .as_array()
+            .into_iter()
+            .chain(status["containerStatuses"].as_array())
+            .flatten()
+            .find(|c| c["name"] == container.as_str());
+        let (id, running) = match container_status {
+            Some(c) => (c["containerID"].as_str(), c["state"]["running"].is_object()),
+            None => (None, false),
+        };
+        let mut opts = PodLogOptions {
+            container: Some(container.clone()),
+            follow: true,
+            timestamps: true,
+            ..Default::default()
+        };
+        match id {
+            Some(id) if Some(id) != self.instance.as_deref() => {
+                if self.instance.is_none() {
+                    opts.since_seconds = self.opts.since_seconds;
+                    opts.tail_lines = self.opts.tail_lines;
+                } else {
+                    info!(
+                        "Following new instance of {}/{} {}",
+                        self.namespace, self.pod, container
+                    );
+                }
+                self.instance = Some(id.to_string());
+                self.last = None;
+                self.skip = None;
+            }
+            Some(_) if running => {
+                info!(
+                    "Resuming log of {}/{} {}",
+                    self.namespace, self.pod, container
+                );
+                if let Some(((ref secs, _), _)) = self.last {
+                    opts.since_time = Some(format!("{}Z", secs));
+                }
+                self.skip = self.last.clone().filter(|_| self.opts.dedupe);
+            }
+            _ => {
+                return match status["phase"].as_str() {
+                    Some("Succeeded") | Some("Failed") => FollowState::Done,
+                    // Not started, or about to restart.
+                    _ => self.pause(),
+                };
+            }
+        }
+        FollowState::Reading(Box::new(self.client.logs(&self.namespace, &self.pod, opts)))
+    }
+
+    /// `line`, as passed on, or `None` if it was seen before.
+    fn read(&mut self, line: String) -> Option<String> {
+        let (timestamp, rest) = match split_timestamp(&line) {
+            Some(split) => split,
+            None => return Some(line),
+        };
+        if let Some((ref until, ref mut count)) = self.skip {
+            if timestamp < *until || (timestamp == *until && *count > 0) {
+                if timestamp == *until {
+                    *count -= 1;
+                }
+                return None;
+            }
+        }
+        self.skip = None;
+        match self.last {
+            Some((ref last, ref mut count)) if *last == timestamp => *count += 1,
+            _ => self.last = Some((timestamp, 1)),
+        }
+        if self.opts.timestamps {
+            Some(line)
+        } else {
+            Some(rest.to_string())
+        }
+    }
+}
+
+impl Stream for Follow {
+    type Item = String;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<String>, Error> {
+        loop {
+            match mem::replace(&mut self.state, FollowState::Done) {
+                FollowState::Lookup(mut get) => match get.poll() {
+                    Ok(Async::Ready(pod)) => self.state = self.looked_up(pod),
+                    Ok(Async::NotReady) => {
+                        self.state = FollowState::Lookup(get);
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => {
+                        let transient = ApiError::from_error(&e).is_none_or(|api| {
+                            StatusCode::from_u16(api.code).is_ok_and(RetryPolicy::is_retryable)
+                        });
+                        if !transient {
+                            return Err(e);
+                        }
+                        warn!("Unable to get {}/{}: {}", self.namespace, self.pod, e);
+                        self.state = self.pause();
+                    }
+                },
+                FollowState::Waiting(mut delay) => match delay.poll()? {
+                    Async::Ready(()) => self.state = FollowState::Lookup(self.lookup()),
+                    Async::NotReady => {
+                        self.state = FollowState::Waiting(delay);
+                        return Ok(Async::NotReady);
+                    }
+                },
+                FollowState::Reading(mut lines) => match lines.poll() {
+                    Ok(Async::Ready(Some(line))) => {
+                        self.state = FollowState::Reading(lines);
+                        if let Some(line) = self.read(line) {
+                            return Ok(Async::Ready(Some(line)));
+                        }
+                    }
+                    Ok(Async::NotReady) => {
+                        self.state = FollowState::Reading(lines);
+                        return Ok(Async::NotReady);
+                    }
+                    ended => {
+                        if let Err(e) = ended {
+                            warn!("Log of {}/{} failed: {}", self.namespace, self.pod, e);
+                        }
+                        self.state = self.pause();
+                    }
+                },
+                FollowState::Done => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_logs() {
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let server = client.server();
+    let pod = |name: &str, app: &str, running: bool| {
+        let state = if running {
+            json!({"running": {}})
+        } else {
+            json!({"waiting": {}})
+        };
+        json!({
+            "metadata": {"name": name, "namespace": "ns", "labels": {"app": app}},
+            "spec": {"containers": [{"name": "main"}, {"name": "sidecar"}]},
+            "status": {"containerStatuses": [
+                {"name": "main", "containerID": format!("id-{}-main", name), "state": state},
+                {"name": "sidecar", "containerID": format!("id-{}-sidecar", name), "state": state},
+            ]},
+        })
+    };
+    server.insert(&PODS, &pod("web-1", "web", true)).unwrap();
+    server.insert(&PODS, &pod("db-1", "db", true)).unwrap();
+    server.set_logs("ns", "web-1", "main", "one\ntwo\n");
+    server.set_logs("ns", "web-1", "sidecar", "side\n");
+    server.set_logs("ns", "web-2", "main", "three");
+
+    let lines: Vec<String> = client
+        .logs("ns", "web-1", PodLogOptions::default())
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(lines, vec!["one", "two"]);
+    assert!(client
+        .logs("ns", "nope", PodLogOptions::default())
+        .collect()
+        .wait()
+        .is_err());
+
+    let opts = TailOptions {
+        container: Some("main".to_string()),
+        ..Default::default()
+    };
+    let mut lines = client.tail_logs(Some("ns"), "app=web", opts).wait();
+    let mut next = || {
+        let l = lines.next().unwrap().unwrap();
+        format!("{} {}: {}", l.pod, l.container, l.line)
+    };
+    assert_eq!(next(), "web-1 main: one");
+    assert_eq!(next(), "web-1 main: two");
+
+    // A new pod is only followed once it is running.
+    let created = server.insert(&PODS, &pod("web-2", "web", false)).unwrap();
+    let mut running = pod("web-2", "web", true);
+    running["metadata"]["resourceVersion"] = created["metadata"]["resourceVersion"].clone();
+    client
+        .update(&PODS, &running)
+        .wait()
+        .map(|_: Value| ())
+        .unwrap();
+    assert_eq!(next(), "web-2 main: three");
+}
+
+#[test]
+fn test_follow_logs() {
+    use tokio::runtime::Runtime;
+
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let server = client.server();
+    let status = |id: &str, state: Value, phase: &str| {
+        json!({
+            "phase": phase,
+            "containerStatuses": [{"name": "main", "containerID": id, "state": state}],
+        })
+    };
+    server
+        .insert(
+            &PODS,
+            &json!({
+                "metadata": {"name": "web-0", "namespace": "ns"},
+                "spec": {"containers": [{"name": "main"}]},
+                "status": status("id-1", json!({"running": {}}), "Running"),
+            }),
+        )
+        .unwrap();
+    let set_status = |status: Value| {
+        let mut pod = server.get(&PODS, Some("ns"), "web-0").unwrap();
+        pod["status"] = status;
+        client
+            .update(&PODS, &pod)
+            .wait()
+            .map(|_: Value| ())
+            .unwrap();
+    };
+    let log = |lines: &[&str]| {
+        let log = lines.iter().map(|l| format!("{}\n", l)).collect::<String>();
+        server.set_logs("ns", "web-0", "main", &log);
+    };
+    log(&[
+        "2024-05-01T10:00:00.5Z one",
+        "2024-05-01T10:00:01Z two",
+        "2024-05-01T10:00:01Z two again",
+    ]);
+
+    let mut rt = Runtime::new().unwrap();
+    let opts = FollowOptions {
+        poll_interval: Duration::from_millis(1),
+        ..Default::default()
+    };
+    let mut lines: Option<BoxStream<String>> =
+        Some(Box::new(client.follow_logs("ns", "web-0", opts)));
+    let mut next = || {
+        let (line, rest) = rt
+            .block_on(lines.take().unwrap().into_future())
+            .map_err(|(e, _)| e)
+            .unwrap();
+        lines = Some(rest);
+        line
+    };
+    assert_eq!(next().unwrap(), "one");
+    assert_eq!(next().unwrap(), "two");
+    assert_eq!(next().unwrap(), "two again");
+
+    // The log is cut off, and resumed from the second of its last line
+    // (the fake ignores `sinceTime`), without repeating any.
+    log(&[
+        "2024-05-01T10:00:00.5Z one",
+        "2024-05-01T10:00:01Z two",
+        "2024-05-01T10:00:01Z two again",
+        "2024-05-01T10:00:01.25Z three",
+    ]);
+    assert_eq!(next().unwrap(), "three");
+
+    // The container restarts, and the new instance is read from the
+    // start.
+    set_status(status("id-2", json!({"running": {}}), "Running"));
+    log(&["2024-05-01T09:00:00Z fresh"]);
+    assert_eq!(next().unwrap(), "fresh");
+
+    set_status(status("id-2", json!({"terminated": {}}), "Succeeded"));
+    assert_eq!(next(), None);
+
+    assert_eq!(
+        split_timestamp("2024-05-01T10:00:01.25Z a b"),
+        Some((
+            ("2024-05-01T10:00:01".to_string(), "250000000".to_string()),
+            "a b"
+        ))
+    );
+    assert!(split_timestamp("no timestamp").is_none());
+}
@@ -0,0 +1,248 @@
+//! Client request metrics.
+//!
+//! Install a `MetricsRecorder` with `ClientBuilder::metrics` to be told
+//! about every HTTP request the client makes (including each retry,
+//! and the initial request of each watch).  With the `prometheus`
+//! feature, `PrometheusRecorder` records these in a Prometheus
+//! registry.
+
+use std::fmt;
+use std::time::Duration;
+
+use hyper::{Method, Uri};
+
+/// What a request was for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestLabels {
+    /// The Kubernetes verb (`get`, `list`, `watch`, `create`, ...).
+    pub verb: String,
+    pub group: String,
+    pub version: String,
+    /// The resource, including any subresource (eg `pods/status`).
+    /// Empty for non-resource paths such as `/healthz`.
+    pub resource: String,
+    /// Not used by `PrometheusRecorder`, to limit cardinality.
+    pub namespace: Option<String>,
+}
+
+impl RequestLabels {
+    pub fn new(method: &Method, uri: &Uri) -> Self {
+        let segments: Vec<&str> = uri.path().split('/').filter(|s| !s.is_empty()).collect();
+        let (group, version, rest) = match segments.split_first() {
+            Some((&"api", rest)) if !rest.is_empty() => ("", rest[0], &rest[1..]),
+            Some((&"apis", rest)) if rest.len() >= 2 => (rest[0], rest[1], &rest[2..]),
+            _ => ("", "", &[][..]),
+        };
+        let (namespace, rest) = match rest {
+            ["namespaces", ns, rest @ ..] if !rest.is_empty() => (Some(ns.to_string()), rest),
+            rest => (None, rest),
+        };
+        let (resource, name) = match rest {
+            [] => (String::new(), None),
+            [resource] => (resource.to_string(), None),
+            [resource, name] => (resource.to_string(), Some(name)),
+            [resource, name, sub, ..] => (format!("{}/{}", resource, sub), Some(name)),
+        };
+
+        let watch = uri
+            .query()
+            .map(|q| q.split('&').any(|kv| kv == "watch=true" || kv == "watch=1"))
+            == Some(true);
+        let verb = match *method {
+            Method::GET if watch => "watch",
+            Method::GET if name.is_none() && !resource.is_empty() => "list",
+            Method::GET => "get",
+            Method::POST => "create",
+            Method::PUT => "update",
+            Method::PATCH => "patch",
+            Method::DELETE if name.is_none() => "deletecollection",
+            Method::DELETE => "delete",
+            ref m => m.as_str(),
+        };
+        RequestLabels {
+            verb: verb.to_lowercase(),
+            group: group.to_string(),
+            version: version.to_string(),
+            resource,
+            namespace,
+        }
+    }
+}
+
+/// Receives client request metrics.
+pub trait MetricsRecorder: fmt::Debug + Send + Sync {
+    /// A request is being sent.
+    fn request_started(&self, request: &RequestLabels);
+
+    /// A response was received (`status` is its HTTP status code), or
+    /// the request failed without one (`status` is `None`).  `latency`
+    /// is the time until the response headers arrived.
+    fn request_finished(&self, request: &RequestLabels, status: Option<u16>, latency: Duration);
+}
+
+#[cfg(feature = "prometheus")]
+pub use self::prom::PrometheusRecorder;
+
+#[cfg(feature = "prometheus")]
+mod prom {
+    use prometheus::{
+        exponential_buckets, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts,
+        Registry,
+    };
+    use std::time::Duration;
+
+    use super::{MetricsRecorder, RequestLabels};
+
+    const LABELS: &[&str] = &["verb", "group", "version", "resource"];
+
+    /// Records `kubernetes_client_requests_total` (by code),
+    /// `kubernetes_client_request_duration_seconds` and
+    /// `kubernetes_client_requests_in_flight`, labelled by verb, group,
+    /// version and resource.
+    #[derive(Debug, Clone)]
+    pub struct PrometheusRecorder {
+        requests: IntCounterVec,
+        latency: HistogramVec,
+        in_flight: IntGaugeVec,
+    }
+
+    impl PrometheusRecorder {
+        /// Create the metrics, and register them with `registry`.
+        pub fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+            let mut labels = LABELS.to_vec();
+            labels.push("code");
+            let requests = IntCounterVec::new(
+                Opts::new(
+                    "kubernetes_client_requests_total",
+                    "Number of HTTP requests, partitioned by status code.",
+                ),
+                &labels,
+            )?;
+            let latency = HistogramVec::new(
+                HistogramOpts::new(
+                    "kubernetes_client_request_duration_seconds",
+                    "Request latency in seconds.",
+                )
+                .buckets(exponential_buckets(0.001, 2.0, 15)?),
+                LABELS,
+            )?;
+            let in_flight = IntGaugeVec::new(
+                Opts::new(
+                    "kubernetes_client_requests_in_flight",
+                    "Number of requests awaiting a response.",
+                ),
+                LABELS,
+            )?;
+            registry.register(Box::new(requests.clone()))?;
+            registry.register(Box::new(latency.clone()))?;
+            registry.register(Box::new(in_flight.clone()))?;
+            Ok(PrometheusRecorder {
+                requests,
+                latency,
+                in_flight,
+            })
+        }
+    }
+
+    fn values(r: &RequestLabels) -> [&str; 4] {
+        [&r.verb, &r.group, &r.version, &r.resource]
+    }
+
+    impl MetricsRecorder for PrometheusRecorder {
+        fn request_started(&self, request: &RequestLabels) {
+            self.in_flight.with_label_values(&values(request)).inc();
+        }
+
+        fn request_finished(
+            &self,
+            request: &RequestLabels,
+            status: Option<u16>,
+            latency: Duration,
+        ) {
+            let v = values(request);
+            self.in_flight.with_label_values(&v).dec();
+            self.latency
+                .with_label_values(&v)
+                .observe(latency.as_secs_f64());
+            let code = status.map_or_else(|| "<error>".to_string(), |s| s.to_string());
+            self.requests
+                .with_label_values(&[v[0], v[1], v[2], v[3], &code])
+                .inc();
+        }
+    }
+}
+
+#[test]
+fn test_request_labels() {
+    fn labels(method: Method, uri: &str) -> (String, String, String, String) {
+        let l = RequestLabels::new(&method, &uri.parse().unwrap());
+        (l.verb, l.group, l.version, l.resource)
+    }
+    fn expect(
+        verb: &str,
+        group: &str,
+        version: &str,
+        resource: &str,
+    ) -> (String, String, String, String) {
+        (verb.into(), group.into(), version.into(), resource.into())
+    }
+
+    assert_eq!(
+        labels(Method::GET, "https://k8s/api/v1/namespaces/ns/pods/p1"),
+        expect("get", "", "v1", "pods")
+    );
+    let l = RequestLabels::new(
+        &Method::GET,
+        &"https://k8s/api/v1/namespaces/ns/pods".parse().unwrap(),
+    );
+    assert_eq!(l.namespace, Some("ns".to_string()));
+    let l = RequestLabels::new(
+        &Method::GET,
+        &"https://k8s/api/v1/namespaces/ns".parse().unwrap(),
+    );
+    assert_eq!(l.namespace, None);
+    assert_eq!(
+        labels(
+            Method::GET,
+            "https://k8s/api/v1/namespaces/ns/pods?limit=500"
+        ),
+        expect("list", "", "v1", "pods")
+    );
+    assert_eq!(
+        labels(
+            Method::GET,
+            "https://k8s/apis/apps/v1/deployments?watch=true&resourceVersion=1"
+        ),
+        expect("watch", "apps", "v1", "deployments")
+    );
+    assert_eq!(
+        labels(
+            Method::PUT,
+            "https://k8s/api/v1/namespaces/ns/pods/p1/status"
+        ),
+        expect("update", "", "v1", "pods/status")
+    );
+    assert_eq!(
+        labels(Method::GET, "https://k8s/api/v1/namespaces/ns"),
+        expect("get", "", "v1", "namespaces")
+    );
+    assert_eq!(
+        labels(Method::POST, "https://k8s/api/v1/namespaces"),
+        expect("create", "", "v1", "namespaces")
+    );
+    assert_eq!(
+        labels(
+            Method::DELETE,
+            "https://k8s/apis/batch/v1/namespaces/ns/jobs"
+        ),
+        expect("deletecollection", "batch", "v1", "jobs")
+    );
+    assert_eq!(
+        labels(Method::GET, "https://k8s/healthz?verbose"),
+        expect("get", "", "", "")
+    );
+    assert_eq!(
+        labels(Method::GET, "https://k8s/apis/apps/v1"),
+        expect("get", "apps", "v1", "")
+    );
+}
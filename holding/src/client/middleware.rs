@@ -0,0 +1,141 @@
+//! Composable request middleware.
+//!
+//! Every HTTP request the client sends (including each retry, and the
+//! initial request of each watch) passes through a stack of
+//! `Middleware` before reaching the client's `Backend`.  Each
+//! middleware may change the request, pass it on down the stack with
+//! `Next::run` (or not), and inspect or replace the response.
+//!
+//! The client's own `User-Agent`, rate limiting, and tracing and
+//! metrics are the outermost middleware; those added with
+//! `ClientBuilder::middleware` run inside them, in the order they were
+//! added.  Retries, timeouts and response decompression apply to the
+//! stack as a whole.
+//!
+//! ```
+//! # extern crate failure;
+//! # extern crate hyper;
+//! # extern crate kubernetes_holding;
+//! use hyper::header::{HeaderValue, AUTHORIZATION};
+//! use hyper::{Body, Request};
+//! use kubernetes_holding::client::middleware::{Middleware, Next, ResponseFuture};
+//!
+//! #[derive(Debug)]
+//! struct StaticToken(HeaderValue);
+//!
+//! impl Middleware for StaticToken {
+//!     fn call(&self, mut req: Request<Body>, next: Next) -> ResponseFuture {
+//!         req.headers_mut().insert(AUTHORIZATION, self.0.clone());
+//!         next.run(req)
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use failure::Error;
+use futures::Future;
+use hyper::{Body, Request, Response};
+
+use super::backend::Backend;
+
+pub type ResponseFuture = Box<dyn Future<Item = Response<Body>, Error = Error> + Send>;
+
+pub trait Middleware: fmt::Debug + Send + Sync {
+    /// Handle `req`, usually by passing it on to `next`.
+    fn call(&self, req: Request<Body>, next: Next) -> ResponseFuture;
+}
+
+#[derive(Debug)]
+pub(crate) struct Stack {
+    middleware: Vec<Arc<dyn Middleware>>,
+    backend: Arc<dyn Backend>,
+}
+
+impl Stack {
+    pub(crate) fn new(middleware: Vec<Arc<dyn Middleware>>, backend: Arc<dyn Backend>) -> Self {
+        Stack {
+            middleware,
+            backend,
+        }
+    }
+
+    /// The same middleware, in front of another backend.
+    pub(crate) fn with_backend(&self, backend: Arc<dyn Backend>) -> Self {
+        Stack::new(self.middleware.clone(), backend)
+    }
+}
+
+/// The rest of the middleware stack.
+#[derive(Debug, Clone)]
+pub struct Next {
+    stack: Arc<Stack>,
+    index: usize,
+}
+
+impl Next {
+    pub(crate) fn new(stack: Arc<Stack>) -> Self {
+        Next { stack, index: 0 }
+    }
+
+    /// Pass the request on to the next middleware.
+    pub fn run(self, req: Request<Body>) -> ResponseFuture {
+        let middleware = self.stack.middleware.get(self.index).cloned();
+        match middleware {
+            Some(m) => m.call(
+                req,
+                Next {
+                    stack: self.stack,
+                    index: self.index + 1,
+                },
+            ),
+            None => self.stack.backend.call(req),
+        }
+    }
+}
+
+#[test]
+fn test_stack() {
+    use futures::{future, Stream};
+    use hyper::header::HeaderValue;
+
+    #[derive(Debug)]
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn call(&self, mut req: Request<Body>, next: Next) -> ResponseFuture {
+            req.headers_mut()
+                .append("x-tag", HeaderValue::from_static(self.0));
+            next.run(req)
+        }
+    }
+
+    /// Responds with the tags, in order.
+    #[derive(Debug)]
+    struct Echo;
+
+    impl Backend for Echo {
+        fn call(&self, req: Request<Body>) -> ResponseFuture {
+            let tags: Vec<_> = req
+                .headers()
+                .get_all("x-tag")
+                .iter()
+                .map(|v| v.to_str().unwrap())
+                .collect();
+            Box::new(future::ok(Response::new(tags.join(",").into())))
+        }
+    }
+
+    let stack = Stack::new(
+        vec![Arc::new(Tag("outer")), Arc::new(Tag("inner"))],
+        Arc::new(Echo),
+    );
+    let req = Request::get("http://example.com/")
+        .body(Body::empty())
+        .unwrap();
+    let res = Next::new(Arc::new(stack)).run(req).wait().unwrap();
+    let body = res.into_body().concat2().wait().unwrap();
+    assert_eq!(body.as_ref(), b"outer,inner");
+}
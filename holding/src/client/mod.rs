@@ -1,50 +1,121 @@
+use std::cmp;
 use std::default::Default;
 use std::env;
 use std::fmt;
 use std::path::PathBuf;
 use std::str;
 use std::sync::Arc;
+use std::time::Duration;
 
 use failure::{Error, ResultExt};
 use futures::{future, stream, Future, Stream};
-use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE};
 use hyper::{self, Body, Method, Request};
-use hyper_tls::HttpsConnector;
+#[cfg(feature = "tls")]
+use hyper_tls;
+#[cfg(feature = "tls")]
 use native_tls::{Certificate, Identity, TlsConnector};
-use openssl;
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, IgnoredAny};
 use serde::Serialize;
 use serde_json;
-use serde_urlencoded;
 use url::Url;
 
 use api::core::v1::{NamespacedResource, Resource};
 use api::meta::v1::{
-    DeleteOptions, GetOptions, List, ListOptions, Metadata, ObjectMeta, Status, WatchEvent,
+    DeleteOptions, GetOptions, List, ListOptions, Metadata, ObjectMeta, WatchEvent,
 };
 use api::meta::GroupVersionResource;
-use api::TypeMeta;
-use k8sclient::error::ClientError;
+use api::{TypeMeta, JSON, MERGE_PATCH};
+use k8sclient::error::{ApiError, ClientError};
 
+pub mod apiclient;
+pub mod apply;
+pub mod backend;
+pub mod blocking;
+pub mod builder;
+pub mod cascade;
 pub mod config;
+pub mod connector;
+pub mod cp;
+pub mod discovery;
+pub mod diskcache;
+pub mod dns;
+pub mod drain;
+pub mod dynamic;
+pub mod ephemeral;
+pub mod exec;
+pub mod fake;
+pub mod health;
+mod json;
+pub mod leaderelection;
+pub mod logs;
+pub mod metrics;
+pub mod middleware;
+pub mod multiwatch;
+pub mod namespace;
+pub mod node;
+pub mod pager;
+#[cfg(any(feature = "tls", feature = "rustls"))]
+mod pem;
+pub mod portforward;
+mod ratelimit;
+pub mod raw;
+pub mod record;
 mod resplit;
+pub mod restmapper;
+pub mod retry;
+#[cfg(feature = "rustls")]
+pub mod rustls_connector;
+pub mod scale;
+pub mod shutdown;
+mod spdy;
+pub mod subresource;
+mod tar;
+pub mod top;
+pub mod trace;
+mod transport;
+pub mod typed;
+mod urls;
+pub mod wait;
+pub mod warning;
+pub mod watch;
+pub mod watcher;
+#[cfg(feature = "tls")]
+pub mod webhook;
+mod websocket;
 
+use self::backend::Backend;
+use self::builder::ClientBuilder;
 use self::config::ConfigContext;
+#[cfg(any(feature = "tls", feature = "rustls"))]
+use self::connector::ServerCertVerifier;
+#[cfg(feature = "tls")]
+use self::connector::{HttpsConnector, ALPN_PROTOCOLS};
+use self::diskcache::DiskCache;
+use self::json::{error_response, from_json};
+use self::metrics::RequestLabels;
+use self::pager::{Page, PagerOptions};
+use self::shutdown::Shutdown;
+use self::transport::Transport;
 
 #[derive(Debug, Clone)]
-pub struct Client<C> {
-    client: Arc<hyper::Client<C>>,
+pub struct Client {
+    client: Arc<Transport>,
     config: ConfigContext,
+    timeout: Option<Duration>,
+    pager: PagerOptions,
+    shutdown: Option<Shutdown>,
+    disk_cache: Option<DiskCache>,
 }
 
 #[derive(Debug, Clone)]
-pub struct NamespacedClient<'a, C> {
+pub struct NamespacedClient<'a> {
     namespace: &'a str,
-    client: &'a Client<C>,
+    client: &'a Client,
 }
 
-impl<'a, C> Client<C> {
-    pub fn namespace(&'a self, ns: &'a str) -> NamespacedClient<'a, C> {
+impl<'a> Client {
+    pub fn namespace(&'a self, ns: &'a str) -> NamespacedClient<'a> {
         NamespacedClient {
             namespace: ns,
             client: self,
@@ -52,186 +123,418 @@ impl<'a, C> Client<C> {
     }
 }
 
-impl Client<HttpsConnector<hyper::client::HttpConnector>> {
+impl Client {
     pub fn new() -> Result<Self, Error> {
-        let dns_threads = 1; // Only need a single DNS lookup
-        let http = hyper::client::HttpConnector::new(dns_threads);
-        Client::new_from_http(http)
+        ClientBuilder::new().build()
     }
 
     pub fn new_from_http(http: hyper::client::HttpConnector) -> Result<Self, Error> {
-        let config_path = env::var_os(config::CONFIG_ENV)
-            .map(PathBuf::from)
-            .or_else(config::default_path)
-            .ok_or(format_err!("Unable to find config"))?;
-        debug!("Reading config from {}", config_path.display());
-        let config = config::load_from_file(&config_path)
-            .with_context(|e| format!("Unable to read {}: {}", config_path.display(), e))?;
-        let context = config.config_context(&config.current_context)?;
-        Client::new_from_context(http, context)
+        ClientBuilder::new().build_from_http(http)
     }
 
     pub fn new_from_context(
-        mut http: hyper::client::HttpConnector,
+        http: hyper::client::HttpConnector,
         config: ConfigContext,
     ) -> Result<Self, Error> {
-        http.enforce_http(false);
-        let mut tls = TlsConnector::builder();
-        if let (Some(certdata), Some(keydata)) = (
-            config.user.client_certificate_read(),
-            config.user.client_key_read(),
-        ) {
-            debug!("Setting user client cert");
-            let cert = openssl::x509::X509::from_pem(&certdata?)?;
-            let pkey = openssl::pkey::PKey::private_key_from_pem(&keydata?)?;
-            // openssl pkcs12 -export -clcerts -inkey kubecfg.key -in kubecfg.crt -out kubecfg.p12 -name "kubecfg"
-            let password = "";
-            let p12 =
-                openssl::pkcs12::Pkcs12::builder().build(password, "kubeconfig", &pkey, &cert)?;
-            tls.identity(Identity::from_pkcs12(&p12.to_der()?, password)?);
+        ClientBuilder::new().config(config).build_from_http(http)
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    pub fn new_with_client<C>(
+        client: hyper::Client<C>,
+        config: ConfigContext,
+    ) -> Result<Self, Error>
+    where
+        C: hyper::client::connect::Connect + 'static,
+    {
+        ClientBuilder::new()
+            .config(config)
+            .build_with_client(client)
+    }
+
+    fn from_parts(transport: Transport, config: ConfigContext, timeout: Option<Duration>) -> Self {
+        Client {
+            client: Arc::new(transport),
+            config,
+            timeout,
+            pager: Default::default(),
+            shutdown: None,
+            disk_cache: None,
         }
+    }
+}
+
+impl Client {
+    /// What sends this client's requests.
+    pub fn backend(&self) -> &dyn Backend {
+        &*self.client.backend
+    }
+
+    /// The namespace of the kubeconfig context, if it sets one.
+    pub fn default_namespace(&self) -> Option<&str> {
+        self.config.default_namespace.as_deref()
+    }
+
+    /// The timeout for each request (other than watches).
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
 
-        if let Some(data) = config.cluster.certificate_authority_read() {
-            debug!("Setting cluster CA cert");
-            let cert = Certificate::from_pem(&data?)?;
-            // FIXME: want to validate against _only_ this cert ..
-            tls.add_root_certificate(cert);
+    /// A client that shares this client's connections (and rate limit),
+    /// but with a different request timeout.
+    ///
+    /// ```no_run
+    /// # extern crate kubernetes_holding;
+    /// # use kubernetes_holding::client::Client;
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// let client = Client::new()?;
+    /// let patient = client.with_timeout(Some(Duration::from_secs(300)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_timeout(&self, timeout: Option<Duration>) -> Self {
+        Client {
+            timeout,
+            ..self.clone()
         }
+    }
+
+    /// How `iter` pages through lists.
+    pub fn pager(&self) -> &PagerOptions {
+        &self.pager
+    }
+
+    /// A client whose `iter`s page through lists as `pager` says.
+    pub fn with_pager(&self, pager: PagerOptions) -> Self {
+        Client {
+            pager,
+            ..self.clone()
+        }
+    }
+
+    /// What ends this client's watches and log streams, if anything.
+    pub fn shutdown(&self) -> Option<&Shutdown> {
+        self.shutdown.as_ref()
+    }
 
-        if config.cluster.insecure_skip_tls_verify {
-            debug!("Disabling CA verification");
-            // TODO: do this only for the endpoint in question, not globally.
-            tls.danger_accept_invalid_certs(true);
+    /// A client whose watches and log streams end when `shutdown` is
+    /// triggered; see the `shutdown` module.
+    pub fn with_shutdown(&self, shutdown: &Shutdown) -> Self {
+        Client {
+            shutdown: Some(shutdown.clone()),
+            ..self.clone()
         }
+    }
 
-        let hyper_client =
-            hyper::Client::builder().build(HttpsConnector::from((http, tls.build()?)));
+    /// Where discovery results and schemas are kept between runs, if
+    /// anywhere.
+    pub fn disk_cache(&self) -> Option<&DiskCache> {
+        self.disk_cache.as_ref()
+    }
 
-        Self::new_with_client(hyper_client, config)
+    /// A client that keeps discovery results and schemas in `cache`;
+    /// see the `diskcache` module.
+    pub fn with_disk_cache(&self, cache: DiskCache) -> Self {
+        Client {
+            disk_cache: Some(cache),
+            ..self.clone()
+        }
     }
 }
 
-impl<C> Client<C> {
-    pub fn new_with_client(client: hyper::Client<C>, config: ConfigContext) -> Result<Self, Error> {
-        Ok(Client {
-            client: Arc::new(client),
-            config: config,
-        })
+/// The current context of the default kubeconfig.
+fn default_config() -> Result<ConfigContext, Error> {
+    let config_path = env::var_os(config::CONFIG_ENV)
+        .map(PathBuf::from)
+        .or_else(config::default_path)
+        .ok_or(format_err!("Unable to find config"))?;
+    debug!("Reading config from {}", config_path.display());
+    let config = config::load_from_file(&config_path)
+        .with_context(|e| format!("Unable to read {}: {}", config_path.display(), e))?;
+    Ok(config.config_context(&config.current_context)?)
+}
+
+/// A connector that authenticates as `config` requires, and (unless
+/// `http1_only`) offers HTTP/2.
+#[cfg(feature = "tls")]
+fn https_connector<R>(
+    mut http: hyper::client::HttpConnector<R>,
+    config: &ConfigContext,
+    http1_only: bool,
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+) -> Result<HttpsConnector<hyper::client::HttpConnector<R>>, Error> {
+    http.enforce_http(false);
+    let mut tls = TlsConnector::builder();
+    if !http1_only {
+        tls.request_alpns(ALPN_PROTOCOLS);
+    }
+    if let (Some(certdata), Some(keydata)) = (
+        config.user.client_certificate_read(),
+        config.user.client_key_read(),
+    ) {
+        debug!("Setting user client cert");
+        let key = pem::pkcs8_key(&keydata?)?;
+        tls.identity(Identity::from_pkcs8(&certdata?, &key)?);
+    }
+
+    if let Some(data) = config.cluster.certificate_authority_read() {
+        debug!("Setting cluster CA cert");
+        let cert = Certificate::from_pem(&data?)?;
+        // FIXME: want to validate against _only_ this cert ..
+        tls.add_root_certificate(cert);
+    }
+
+    if config.cluster.insecure_skip_tls_verify {
+        debug!("Disabling CA verification");
+        // TODO: do this only for the endpoint in question, not globally.
+        tls.danger_accept_invalid_certs(true);
+    }
+
+    if verifier.as_ref().is_some_and(|v| v.replaces_default()) {
+        debug!("Replacing CA verification with a custom verifier");
+        tls.danger_accept_invalid_certs(true);
+        tls.danger_accept_invalid_hostnames(true);
     }
 
-    pub fn client(&self) -> &hyper::Client<C> {
-        &self.client
+    let https = HttpsConnector::from(hyper_tls::HttpsConnector::from((http, tls.build()?)));
+    Ok(match verifier {
+        Some(verifier) => https.with_verifier(verifier),
+        None => https,
+    })
+}
+
+/// A rustls connector that authenticates as `config` requires, and
+/// (unless `http1_only`) offers HTTP/2.
+#[cfg(all(feature = "rustls", not(feature = "tls")))]
+fn rustls_connector<R>(
+    mut http: hyper::client::HttpConnector<R>,
+    config: &ConfigContext,
+    http1_only: bool,
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+) -> Result<rustls_connector::RustlsConnector<hyper::client::HttpConnector<R>>, Error> {
+    http.enforce_http(false);
+    let tls = rustls_connector::client_config(config, http1_only, verifier)?;
+    Ok(rustls_connector::RustlsConnector::new(http, tls))
+}
+
+/// Without TLS support, only plain HTTP apiservers can be used.
+#[cfg(not(any(feature = "tls", feature = "rustls")))]
+fn http_connector<R>(
+    http: hyper::client::HttpConnector<R>,
+    config: &ConfigContext,
+) -> Result<hyper::client::HttpConnector<R>, Error> {
+    if config.cluster.server.starts_with("https:") {
+        return Err(format_err!(
+            "Unable to connect to {} without the `tls` or `rustls` feature; \
+             use `ClientBuilder::build_with_client` with a TLS connector",
+            config.cluster.server
+        ));
     }
+    Ok(http)
 }
 
+/// URL parameters for requests that take none.  (`()` can't be
+/// serialized as a query string.)
+#[derive(Debug, Serialize)]
+struct NoOptions {}
+
 fn hyper_uri(u: Url) -> hyper::Uri {
     u.to_string()
         .parse()
         .expect("attempted to convert invalid uri")
 }
 
-fn do_request<C, T>(
-    client: Arc<hyper::Client<C>>,
+/// The error for an unsuccessful response to `request` (as
+/// `transport::describe` names it).
+fn response_error(
+    request: String,
+    httpstatus: hyper::StatusCode,
+    audit_id: String,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> ApiError {
+    error_response(httpstatus, body)
+        .with_request(request)
+        .with_audit_id(Some(audit_id))
+        .with_retry_after(retry::retry_after_header(headers))
+}
+
+/// Send a request and decode the response, failing with
+/// `wait::TimeoutError` if that takes longer than `timeout`.
+fn do_request<T>(
+    client: Arc<Transport>,
+    timeout: Option<Duration>,
     req: Result<Request<hyper::Body>, Error>,
 ) -> impl Future<Item = T, Error = Error> + Send
 where
-    C: hyper::client::connect::Connect + 'static,
     T: DeserializeOwned + Send + 'static,
 {
-    future::result(req)
-        .inspect(|req|
-                 // Avoid body, since it may not be Debug
-                 debug!("Request: {} {}", req.method(), req.uri()))
-        .and_then(move |req|
-                  // TODO: add method/uri context to error
-                  client.request(req).from_err::<Error>())
-        .inspect(|res| debug!("Response: {} {:?}", res.status(), res.headers()))
-        // Verbose!
-        //.inspect(|res| debug!("Response: {:#?}", res))
-        .and_then(|res| {
-            let status = res.status();
-            res.into_body()
-                .concat2()
-                .map(move |body| (status, body))
-                .from_err()
-        })
-        // Verbose!
-        //.inspect(|(_, body)| debug!("Response body: {:?}", ::std::str::from_utf8(body.as_ref())))
-        .and_then(move |(httpstatus, body)| -> Result<T, Error> {
-            if !httpstatus.is_success() {
-                let status: Status = serde_json::from_slice(body.as_ref()).map_err(|e| {
-                    debug!(
-                        "Failed to parse error Status ({}), falling back to HTTP status",
-                        ClientError::new_decode_error("error Status", &e, body.to_vec())
-                    );
-                    ClientError::HttpStatusError { status: httpstatus }
-                })?;
-                Err(status.into())
-            } else {
-                let o = serde_json::from_slice(body.as_ref()).with_context(|e| {
-                    ClientError::new_decode_error("response body", e, body.to_vec())
-                })?;
-                Ok(o)
-            }
-        })
+    do_request_opt(client, timeout, req)
+        .and_then(|o| o.ok_or_else(|| format_err!("Unexpected empty response")))
 }
 
-fn do_watch<C, T>(
-    client: &Arc<hyper::Client<C>>,
-    req: Result<hyper::Request<hyper::Body>, Error>,
-) -> impl Stream<Item = T, Error = Error> + Send
+/// Like `do_request`, but `None` if the response is successful but
+/// empty, eg `204 No Content`.
+fn do_request_opt<T>(
+    client: Arc<Transport>,
+    timeout: Option<Duration>,
+    req: Result<Request<hyper::Body>, Error>,
+) -> impl Future<Item = Option<T>, Error = Error> + Send
 where
-    C: hyper::client::connect::Connect + 'static,
     T: DeserializeOwned + Send + 'static,
 {
-    let client = Arc::clone(client);
-    future::result(req)
-        .inspect(|req| debug!("Watch request: {} {}", req.method(), req.uri()))
-        .and_then(move |req|
-                  // TODO: add method/uri context to error
-                  client.request(req).from_err::<Error>())
-        .inspect(|res| debug!("Response: {:#?}", res))
-        .and_then(|res| {
+    let req = req.map(|mut req| {
+        accept_json(&mut req);
+        req
+    });
+    do_request_full(client, timeout, req).and_then(|(headers, body)| decode_body(&headers, &body))
+}
+
+/// Ask for a JSON response, unless the request already says what it
+/// accepts.
+fn accept_json<B>(req: &mut Request<B>) {
+    req.headers_mut()
+        .entry(ACCEPT)
+        .unwrap()
+        .or_insert_with(|| HeaderValue::from_static(JSON));
+}
+
+/// Decode a successful JSON response, or `None` if it is empty.
+fn decode_body<T: DeserializeOwned>(headers: &HeaderMap, body: &[u8]) -> Result<Option<T>, Error> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    if let Some(content_type) = headers.get(CONTENT_TYPE) {
+        let content_type = String::from_utf8_lossy(content_type.as_bytes());
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        if !mime.eq_ignore_ascii_case(JSON) && !mime.to_ascii_lowercase().ends_with("+json") {
+            return Err(ClientError::new_unexpected_content_type(&content_type, body).into());
+        }
+    }
+    from_json("response body", body).map(Some)
+}
+
+/// Like `do_request`, but returns the undecoded body.
+fn do_request_raw(
+    client: Arc<Transport>,
+    timeout: Option<Duration>,
+    req: Result<Request<hyper::Body>, Error>,
+) -> impl Future<Item = hyper::Chunk, Error = Error> + Send {
+    do_request_full(client, timeout, req).map(|(_, body)| body)
+}
+
+/// Like `do_request_raw`, but also returns the response headers.
+fn do_request_full(
+    client: Arc<Transport>,
+    timeout: Option<Duration>,
+    req: Result<Request<hyper::Body>, Error>,
+) -> impl Future<Item = (HeaderMap, hyper::Chunk), Error = Error> + Send {
+    let f = future::result(req).and_then(move |req| {
+        let request = transport::describe(req.method(), req.uri());
+        Transport::send(client, req)
+            // Verbose!
+            //.inspect(|(_, body)| debug!("Response body: {:?}", ::std::str::from_utf8(body.as_ref())))
+            .and_then(move |(httpstatus, audit_id, headers, body)| {
+                if !httpstatus.is_success() {
+                    Err(
+                        response_error(request, httpstatus, audit_id, &headers, body.as_ref())
+                            .into(),
+                    )
+                } else {
+                    Ok((headers, body))
+                }
+            })
+    });
+    match timeout {
+        Some(after) => future::Either::A(wait::timeout(f, after)),
+        None => future::Either::B(f),
+    }
+}
+
+/// Send a request whose response body is streamed, failing unless it
+/// succeeds.  `what` names the request in errors.
+fn do_stream(
+    client: Arc<Transport>,
+    mut req: hyper::Request<hyper::Body>,
+    what: &str,
+) -> impl Future<Item = Body, Error = Error> + Send {
+    let id = transport::request_id(req.headers_mut());
+    let request = transport::describe(req.method(), req.uri());
+    let failed = format!("{} {} failed (request {})", what, request, id);
+    let max = client.max_body_size;
+    client
+        .request(req)
+        .map_err(|e| e.context(failed).into())
+        .and_then(move |res| {
             let httpstatus = res.status();
+            let audit_id = transport::audit_id(res.headers()).unwrap_or(id);
+            let headers = res.headers().clone();
             let r = if httpstatus.is_success() {
-                Ok(res)
+                Ok(res.into_body())
             } else {
                 Err(res)
             };
-            future::result(r)
-                .or_else(move |res| {
-                    res.into_body()
-                        .concat2()
-                        .from_err::<Error>()
-                        .and_then(move |body| {
-                            let status: Status = serde_json::from_slice(body.as_ref()).map_err(
-                                |e| {
-                                    debug!("Failed to parse error Status ({}), falling back to HTTP status", 
-                                        ClientError::new_decode_error("error Status", &e, body.to_vec()));
-                                    ClientError::HttpStatusError { status: httpstatus }
-                                },
-                            )?;
-
-                            Err(status.into())
-                        })
-                })
-                .map(|res| {
-                    resplit::new(res.into_body(), |&c| c == b'\n')
-                        .from_err()
-                        .inspect(|line| {
-                            debug!(
-                                "Got line: {:#?}",
-                                ::std::str::from_utf8(line).unwrap_or("<invalid utf8>")
-                            )
-                        })
-                        .and_then(move |line| {
-                            let o: T = serde_json::from_slice(line.as_ref())
-                                .with_context(|e| ClientError::new_decode_error("watch line", e, line.to_vec()))?;
-                            Ok(o)
-                        })
+            future::result(r).or_else(move |res| {
+                transport::read_body(res.into_body(), max).and_then(move |body| {
+                    Err(
+                        response_error(request, httpstatus, audit_id, &headers, body.as_ref())
+                            .into(),
+                    )
                 })
+            })
         })
-        .flatten_stream()
+}
+
+/// Stream the lines of the watch response to `req`, decoded by `decode`.
+fn do_watch<T>(
+    client: &Arc<Transport>,
+    shutdown: Option<&Shutdown>,
+    req: Result<hyper::Request<hyper::Body>, Error>,
+    decode: fn(&[u8]) -> Result<T, Error>,
+) -> impl Stream<Item = T, Error = Error> + Send
+where
+    T: Send + 'static,
+{
+    let client = Arc::clone(client);
+    let events = future::result(req)
+        .and_then(move |mut req| {
+            accept_json(&mut req);
+            let span = trace::watch_span(&RequestLabels::new(req.method(), req.uri()));
+            let max = client.max_line_length;
+            do_stream(client, req, "Watch").map(move |body| {
+                // The span lives as long as the stream.
+                watch::lines_limited(body, max)
+                    .and_then(move |line| span.in_scope(|| decode(&line)))
+            })
+        })
+        .flatten_stream();
+    // Dropping the stream closes the connection.
+    shutdown::guard_with(shutdown, events)
+}
+
+/// The largest response body read into memory by default: 128MiB.
+/// Streamed bodies (watches, logs) are limited by line instead.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 128 << 20;
+
+/// A response body was larger than the client's limit; see
+/// `ClientBuilder::max_body_size`.
+#[derive(Debug, Clone, PartialEq, Fail)]
+#[fail(display = "Response body larger than the limit of {} bytes", max)]
+pub struct BodyTooLarge {
+    pub max: usize,
+}
+
+/// How many namespaces `list_multi` lists at once, unless
+/// `PagerOptions::list_concurrency` says otherwise.
+pub const LIST_CONCURRENCY: usize = 8;
+
+/// The HTTP status code of an API error, if `e` is one.
+pub(crate) fn status_code(e: &Error) -> Option<u16> {
+    ApiError::from_error(e).map(|e| e.code)
 }
 
 fn require_name(metadata: &ObjectMeta) -> Result<&String, ClientError> {
@@ -241,7 +544,7 @@ fn require_name(metadata: &ObjectMeta) -> Result<&String, ClientError> {
         .ok_or_else(|| ClientError::RequiredAttributeError { attr: "name" })
 }
 
-impl<'a, C: hyper::client::connect::Connect + 'static> NamespacedClient<'a, C> {
+impl<'a> NamespacedClient<'a> {
     pub fn iter<T>(
         &self,
         rsrc: T,
@@ -273,7 +576,7 @@ impl<'a, C: hyper::client::connect::Connect + 'static> NamespacedClient<'a, C> {
     }
 }
 
-impl<C: hyper::client::connect::Connect + 'static> Client<C> {
+impl Client {
     fn url<O>(
         &self,
         gvr: &GroupVersionResource,
@@ -284,44 +587,47 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
     where
         O: Serialize + fmt::Debug,
     {
-        let mut url: Url = self.config.cluster.server.parse()?;
-
-        {
-            let mut path = url
-                .path_segments_mut()
-                .map_err(|_| format_err!("URL scheme does not support paths"))?;
-            /* XXX: This looks like a k8s API rooted at (say) /kube on a
-             *      reverse proxy will break.
-             */
-            path.clear();
-            /* This knowledge should perhaps be pushed into the group itself */
-            path.push(if gvr.group == "" && gvr.version == "v1" {
-                "api"
-            } else {
-                "apis"
-            });
-            if gvr.group != "" {
-                path.push(&gvr.group);
-            }
-            path.push(&gvr.version);
-            namespace.map(|ns| path.extend(&["namespaces", ns]));
-            path.push(&gvr.resource);
-            name.map(|n| path.push(n));
-        }
+        urls::resource_url(self.path_url(&[])?, gvr, namespace, name, opts)
+    }
 
-        serde_urlencoded::to_string(&opts)
-            .map(|query| {
-                let q = if query != "" {
-                    Some(query.as_str())
-                } else {
-                    None
-                };
-                url.set_query(q)
-            })
-            .with_context(|e| format!("Unable to encode URL parameters {}", e))?;
+    /// The URL of `subresource` (eg: `scale`) of the named object.
+    fn subresource_url(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        subresource: &str,
+    ) -> Result<Url, Error> {
+        let mut url = self.url(gvr, namespace, Some(name), NoOptions {})?;
+        url.path_segments_mut()
+            .map_err(|_| format_err!("URL scheme does not support paths"))?
+            .push(subresource);
         Ok(url)
     }
 
+    /// URL for a non-resource path below the apiserver root,
+    /// eg: `["apis", "apps"]`.  The root is the server URL, path and
+    /// all, so that apiservers behind a reverse proxy at (say)
+    /// `https://gateway/clusters/foo` work.
+    fn path_url(&self, segments: &[&str]) -> Result<Url, Error> {
+        urls::path_url(&self.config.cluster.server, segments)
+    }
+
+    /// GET a non-resource path, eg: discovery or `/version`.
+    fn get_path<T>(&self, segments: &[&str]) -> impl Future<Item = T, Error = Error> + Send
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let req = self.path_url(segments).and_then(|url| {
+            Request::builder()
+                .method(Method::GET)
+                .uri(hyper_uri(url))
+                .body(Body::empty())
+                .map_err(|e| e.into())
+        });
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
     pub fn get<T>(
         &self,
         gvr: &GroupVersionResource,
@@ -339,7 +645,25 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
                 .body(Body::empty())
                 .map_err(|e| e.into())
         });
-        do_request(Arc::clone(&self.client), req)
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
+    /// Like `get`, but returns `None` if the object does not exist.
+    pub fn get_opt<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        opts: GetOptions,
+    ) -> impl Future<Item = Option<T>, Error = Error> + Send
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.get(gvr, namespace, name, opts).then(|r| match r {
+            Ok(o) => Ok(Some(o)),
+            Err(ref e) if status_code(e) == Some(404) => Ok(None),
+            Err(e) => Err(e),
+        })
     }
 
     pub fn create<T>(
@@ -355,23 +679,23 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
             let metadata = value.metadata();
             // NB: assumes input object is correctly qualified
             let namespace = &metadata.namespace;
-            let name = require_name(&metadata)?;
 
             let json = serde_json::to_vec(value)?;
 
+            // POST to the collection; the name comes from the body
             Request::builder()
                 .method(Method::POST)
                 .uri(hyper_uri(self.url(
                     gvr,
                     namespace.as_ref().map(|v| v.as_str()),
-                    Some(&name),
+                    None,
                     opts,
                 )?))
                 .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
                 .body(Body::from(json))
                 .map_err(|e| e.into())
         }();
-        do_request(Arc::clone(&self.client), req)
+        do_request(Arc::clone(&self.client), self.timeout, req)
     }
 
     pub fn update<T>(
@@ -396,13 +720,13 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
                     gvr,
                     namespace.as_ref().map(|v| v.as_str()),
                     Some(&name),
-                    (),
+                    NoOptions {},
                 )?))
                 .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
                 .body(Body::from(json))
                 .map_err(|e| e.into())
         }();
-        do_request(Arc::clone(&self.client), req)
+        do_request(Arc::clone(&self.client), self.timeout, req)
     }
 
     pub fn patch<T, U>(
@@ -422,12 +746,47 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
 
             Request::builder()
                 .method(Method::PATCH)
-                .uri(hyper_uri(self.url(gvr, namespace, Some(name), ())?))
+                .uri(hyper_uri(self.url(
+                    gvr,
+                    namespace,
+                    Some(name),
+                    NoOptions {},
+                )?))
                 .header(CONTENT_TYPE, patch_type)
                 .body(Body::from(json))
                 .map_err(|e| e.into())
         }();
-        do_request(Arc::clone(&self.client), req)
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
+    /// Merge-patch the `.status` of the named object, through the
+    /// status subresource, leaving the spec alone.
+    ///
+    /// Fields of `status` that serialize as `null` are removed, and
+    /// fields it leaves out are kept.
+    pub fn patch_status<S, U>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        status: &S,
+    ) -> impl Future<Item = U, Error = Error> + Send
+    where
+        S: Serialize,
+        U: DeserializeOwned + Send + 'static,
+    {
+        let req = self
+            .subresource_url(gvr, namespace, name, "status")
+            .and_then(|url| {
+                let patch = json!({ "status": status });
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(hyper_uri(url))
+                    .header(CONTENT_TYPE, MERGE_PATCH)
+                    .body(Body::from(serde_json::to_vec(&patch)?))
+                    .map_err(|e| e.into())
+            });
+        do_request(Arc::clone(&self.client), self.timeout, req)
     }
 
     pub fn delete(
@@ -444,7 +803,9 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
                 .body(Body::empty())
                 .map_err(|e| e.into())
         });
-        do_request(Arc::clone(&self.client), req)
+        // The response is the deleted object, or a Status, or (from
+        // some aggregated apiservers) nothing.
+        do_request_opt::<IgnoredAny>(Arc::clone(&self.client), self.timeout, req).map(|_| ())
     }
 
     pub fn delete_collection(
@@ -460,7 +821,9 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
                 .body(Body::empty())
                 .map_err(|e| e.into())
         });
-        do_request(Arc::clone(&self.client), req)
+        // The response is the deleted object, or a Status, or (from
+        // some aggregated apiservers) nothing.
+        do_request_opt::<IgnoredAny>(Arc::clone(&self.client), self.timeout, req).map(|_| ())
     }
 
     pub fn watch(
@@ -478,24 +841,56 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
                 .body(Body::empty())
                 .map_err(|e| e.into())
         });
-        do_watch(&self.client, req)
+        do_watch(&self.client, self.shutdown(), req, watch::decode_line_owned)
     }
 
+    /// Watch for changes to the objects of `gvr`, from
+    /// `opts.resource_version`.
+    ///
+    /// The apiserver ends watches from time to time, so callers restart
+    /// them.  If the old stream was read to its end, the restarted watch
+    /// reuses its pooled connection, without a new TCP (and TLS)
+    /// handshake or DNS lookup.  A stream dropped part way through takes
+    /// its connection with it.
     pub fn watch_list(
         &self,
         gvr: &GroupVersionResource,
         namespace: Option<&str>,
-        mut opts: ListOptions,
+        opts: ListOptions,
     ) -> impl Stream<Item = WatchEvent, Error = Error> + Send {
+        let req = self.watch_list_request(gvr, namespace, opts);
+        do_watch(&self.client, self.shutdown(), req, watch::decode_line_owned)
+    }
+
+    /// Like `watch_list`, with the objects decoded straight into `T`
+    /// (see `watch::decode_event`), and `ERROR` events as errors.
+    pub(crate) fn watch_list_decoded<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> impl Stream<Item = WatchEvent<T>, Error = Error> + Send
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let req = self.watch_list_request(gvr, namespace, opts);
+        do_watch(&self.client, self.shutdown(), req, watch::decode_event)
+    }
+
+    fn watch_list_request(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        mut opts: ListOptions,
+    ) -> Result<Request<Body>, Error> {
         opts.watch = true;
-        let req = self.url(gvr, namespace, None, opts).and_then(|url| {
+        self.url(gvr, namespace, None, opts).and_then(|url| {
             Request::builder()
                 .method(Method::GET)
                 .uri(hyper_uri(url))
                 .body(Body::empty())
                 .map_err(|e| e.into())
-        });
-        do_watch(&self.client, req)
+        })
     }
 
     pub fn list<T>(
@@ -514,7 +909,7 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
                 .body(Body::empty())
                 .map_err(|e| e.into())
         });
-        do_request(Arc::clone(&self.client), req)
+        do_request(Arc::clone(&self.client), self.timeout, req)
     }
 
     pub fn iter<T>(
@@ -542,6 +937,44 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
         self._do_iter::<T::List>(rsrc.gvr(), None, opts)
     }
 
+    /// List `gvr` in each of `namespaces`, as `iter` does, but with up
+    /// to `PagerOptions::list_concurrency` namespaces listed at once.
+    ///
+    /// Each namespace's items come together, but the namespaces come in
+    /// whatever order their lists finish.  Fails if any list fails.
+    pub fn list_multi<L, S>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespaces: &[S],
+        opts: ListOptions,
+    ) -> impl Stream<Item = L::Item, Error = Error> + Send
+    where
+        L: List + DeserializeOwned + Send + 'static,
+        L::Item: DeserializeOwned + Send + 'static,
+        S: AsRef<str>,
+    {
+        let urls: Result<Vec<Url>, Error> = namespaces
+            .iter()
+            .map(|ns| self.url(gvr, Some(ns.as_ref()), None, NoOptions {}))
+            .collect();
+        let client = self.clone();
+        let concurrency = cmp::max(self.pager.list_concurrency, 1);
+        future::result(urls)
+            .map(move |urls| {
+                stream::iter_ok(urls)
+                    .map(move |url| {
+                        pager::pages::<L>(client.clone(), url, opts.clone(), client.pager.clone())
+                            .map(|page| stream::iter_ok(page.items))
+                            .flatten()
+                            .collect()
+                    })
+                    .buffer_unordered(concurrency)
+            })
+            .flatten_stream()
+            .map(stream::iter_ok)
+            .flatten()
+    }
+
     fn _do_iter<L>(
         &self,
         gvr: GroupVersionResource,
@@ -550,41 +983,29 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
     ) -> impl Stream<Item = L::Item, Error = Error> + Send
     where
         L: List + DeserializeOwned + Send + 'static,
-        L::Item: TypeMeta + DeserializeOwned + Default + Send + 'static,
+        L::Item: DeserializeOwned + Send + 'static,
     {
-        let url = self.url(&gvr, namespace, None, opts.clone());
-
-        let client = Arc::clone(&self.client);
-        let fetch_pages = move |url: Url| {
-            stream::unfold(Some((url, opts)), move |context| {
-                context.and_then(|(mut url, mut opts)| {
-                    let req = Request::builder()
-                        .method(Method::GET)
-                        .uri(hyper_uri(url.clone()))
-                        .body(Body::empty())
-                        .map_err(|e| e.into());
-                    let res = do_request(Arc::clone(&client), req).and_then(move |list: L| {
-                        let next = match list.listmeta().continu {
-                            Some(ref continu) => {
-                                opts.continu = continu.clone();
-                                let query = serde_urlencoded::to_string(&opts)?;
-                                url.set_query(Some(&query));
-                                Some((url, opts))
-                            }
-                            None => None,
-                        };
-                        Ok((list, next))
-                    });
-                    Some(res)
-                })
-            })
-        };
+        self._do_pages::<L>(gvr, namespace, opts)
+            .map(|page| stream::iter_ok(page.items))
+            .flatten()
+    }
 
+    fn _do_pages<L>(
+        &self,
+        gvr: GroupVersionResource,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> impl Stream<Item = Page<L::Item>, Error = Error> + Send
+    where
+        L: List + DeserializeOwned + Send + 'static,
+        L::Item: DeserializeOwned + Send + 'static,
+    {
+        let url = self.url(&gvr, namespace, None, NoOptions {});
+        let pager = self.pager.clone();
+        let client = self.clone();
         future::result(url)
-            .and_then(move |url| future::ok(fetch_pages(url)))
+            .map(move |url| pager::pages::<L>(client, url, opts, pager))
             .flatten_stream()
-            .map(|page| stream::iter_ok(page.into_items().into_iter()))
-            .flatten()
     }
 }
 
@@ -592,8 +1013,7 @@ impl<C: hyper::client::connect::Connect + 'static> Client<C> {
 fn test_url() {
     let mut context: ConfigContext = Default::default();
     context.cluster.server = "https://192.168.42.147:8443".into();
-    let http = hyper::client::HttpConnector::new(1);
-    let client = Client::new_from_context(http, context).unwrap();
+    let client = Client::new_with_client(hyper::Client::new(), context).unwrap();
 
     let url = client
         .url(
@@ -650,3 +1070,359 @@ fn test_url() {
         "https://192.168.42.147:8443/api/v1/namespaces?resourceVersion=abcdef&limit=27"
     );
 }
+
+#[test]
+fn test_path_url() {
+    let mut context: ConfigContext = Default::default();
+    context.cluster.server = "https://192.168.42.147:8443".into();
+    let client = Client::new_with_client(hyper::Client::new(), context).unwrap();
+
+    let url = client.path_url(&["apis", "apps", "v1"]).unwrap();
+    assert_eq!(url.to_string(), "https://192.168.42.147:8443/apis/apps/v1");
+}
+
+#[test]
+fn test_url_prefix() {
+    let pods = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "pods",
+    };
+    for server in &[
+        "https://gateway/clusters/foo",
+        "https://gateway/clusters/foo/",
+    ] {
+        let mut context: ConfigContext = Default::default();
+        context.cluster.server = server.to_string();
+        let client = Client::new_with_client(hyper::Client::new(), context).unwrap();
+
+        let url = client
+            .url(&pods, Some("ns"), None, GetOptions::default())
+            .unwrap();
+        assert_eq!(
+            url.to_string(),
+            "https://gateway/clusters/foo/api/v1/namespaces/ns/pods"
+        );
+        let url = client.path_url(&["version"]).unwrap();
+        assert_eq!(url.to_string(), "https://gateway/clusters/foo/version");
+    }
+}
+
+#[test]
+fn test_timeout() {
+    use api::meta::v1::Status;
+    use std::net::TcpListener;
+    use tokio::runtime::Runtime;
+
+    // Accepts connections (into the backlog), but never responds.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut context: ConfigContext = Default::default();
+    context.cluster.server = format!("http://{}", listener.local_addr().unwrap());
+    let client = Client::builder()
+        .config(context)
+        .timeout(Duration::from_secs(600))
+        .build_with_client(hyper::Client::new())
+        .unwrap();
+    assert_eq!(client.timeout(), Some(Duration::from_secs(600)));
+    let client = client.with_timeout(Some(Duration::from_millis(50)));
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "namespaces",
+    };
+    let get = client.get::<Status>(&gvr, None, "default", GetOptions::default());
+    let err = Runtime::new().unwrap().block_on(get).unwrap_err();
+    assert!(err.downcast_ref::<wait::TimeoutError>().is_some());
+}
+
+#[test]
+fn test_empty_response() {
+    use self::fake::FakeApiServer;
+    use self::middleware::{Middleware, Next, ResponseFuture};
+    use hyper::{Response, StatusCode};
+
+    /// Answers everything with `204 No Content`, as some aggregated
+    /// apiservers do for deletes.
+    #[derive(Debug)]
+    struct NoContent;
+
+    impl Middleware for NoContent {
+        fn call(&self, _req: Request<Body>, _next: Next) -> ResponseFuture {
+            let res = Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .body(Body::empty())
+                .unwrap();
+            Box::new(future::ok(res))
+        }
+    }
+
+    let mut config: ConfigContext = Default::default();
+    config.cluster.server = "http://fake.invalid".to_string();
+    let client = Client::builder()
+        .config(config)
+        .middleware(Arc::new(NoContent))
+        .build_with_backend(Arc::new(FakeApiServer::new()))
+        .unwrap();
+    let gvr = GroupVersionResource {
+        group: "metrics.k8s.io",
+        version: "v1beta1",
+        resource: "pods",
+    };
+    client
+        .delete(&gvr, Some("ns"), "web-0", DeleteOptions::default())
+        .wait()
+        .unwrap();
+    client
+        .delete_collection(&gvr, Some("ns"), ListOptions::default())
+        .wait()
+        .unwrap();
+    let err = client
+        .get::<serde_json::Value>(&gvr, Some("ns"), "web-0", GetOptions::default())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected empty response");
+}
+
+#[test]
+fn test_content_type() {
+    use self::fake::FakeApiServer;
+    use self::middleware::{Middleware, Next, ResponseFuture};
+    use hyper::Response;
+
+    /// A misconfigured proxy's login page.
+    #[derive(Debug)]
+    struct Html;
+
+    impl Middleware for Html {
+        fn call(&self, req: Request<Body>, _next: Next) -> ResponseFuture {
+            assert_eq!(req.headers()[ACCEPT], JSON);
+            let res = Response::builder()
+                .header(CONTENT_TYPE, "text/html; charset=utf-8")
+                .body(Body::from("<html>\n  <body>Please log in</body>\n</html>"))
+                .unwrap();
+            Box::new(future::ok(res))
+        }
+    }
+
+    let mut config: ConfigContext = Default::default();
+    config.cluster.server = "http://fake.invalid".to_string();
+    let client = Client::builder()
+        .config(config)
+        .middleware(Arc::new(Html))
+        .build_with_backend(Arc::new(FakeApiServer::new()))
+        .unwrap();
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "pods",
+    };
+    let err = client
+        .get::<serde_json::Value>(&gvr, Some("ns"), "web-0", GetOptions::default())
+        .wait()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected a JSON response, but got text/html; charset=utf-8: \
+         <html> <body>Please log in</body> </html>"
+    );
+    assert!(client
+        .iter_opt(api::core::v1::Namespace::default(), ListOptions::default())
+        .collect()
+        .wait()
+        .is_err());
+}
+
+#[test]
+fn test_patch_status() {
+    use serde_json::Value;
+
+    use self::fake::FakeClient;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct WidgetStatus {
+        ready_replicas: i32,
+        message: Option<String>,
+    }
+
+    let gvr = GroupVersionResource {
+        group: "example.com",
+        version: "v1",
+        resource: "widgets",
+    };
+    let client = FakeClient::new();
+    client
+        .server()
+        .insert(
+            &gvr,
+            &json!({
+                "metadata": {"name": "w", "namespace": "ns"},
+                "spec": {"replicas": 3},
+                "status": {"readyReplicas": 1, "message": "starting", "phase": "Pending"},
+            }),
+        )
+        .unwrap();
+    let status = WidgetStatus {
+        ready_replicas: 3,
+        message: None,
+    };
+    let updated: Value = client
+        .patch_status(&gvr, Some("ns"), "w", &status)
+        .wait()
+        .unwrap();
+    assert_eq!(updated["spec"], json!({"replicas": 3}));
+    assert_eq!(
+        updated["status"],
+        json!({"readyReplicas": 3, "phase": "Pending"})
+    );
+}
+
+#[test]
+fn test_error_names_request() {
+    use serde_json::Value;
+
+    use self::fake::FakeClient;
+
+    let gvr = GroupVersionResource {
+        group: "apps",
+        version: "v1",
+        resource: "deployments",
+    };
+    let client = FakeClient::new();
+    let err = client
+        .get::<Value>(&gvr, Some("ns"), "web", GetOptions::default())
+        .wait()
+        .unwrap_err();
+    let api_error = ApiError::from_error(&err).unwrap();
+    assert!(api_error.is_not_found());
+    assert_eq!(
+        api_error.request.as_deref(),
+        Some("GET /apis/apps/v1/namespaces/ns/deployments/web (get apps/v1/deployments)")
+    );
+    assert!(err.to_string().starts_with(
+        "GET /apis/apps/v1/namespaces/ns/deployments/web (get apps/v1/deployments): NotFound (404)"
+    ));
+}
+
+#[test]
+fn test_list_multi() {
+    use serde_json::Value;
+
+    use self::fake::FakeClient;
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let client = FakeClient::new();
+    for i in 0..30 {
+        let object = json!({"metadata": {"name": format!("cm-{}", i), "namespace": format!("ns-{}", i % 10)}});
+        client.server().insert(&gvr, &object).unwrap();
+    }
+    let namespaces: Vec<String> = (0..9).map(|i| format!("ns-{}", i)).collect();
+    let opts = ListOptions {
+        limit: 2,
+        ..Default::default()
+    };
+    let mut names: Vec<String> = client
+        .list_multi::<Value, _>(&gvr, &namespaces, opts)
+        .map(|o| o["metadata"]["name"].as_str().unwrap().to_string())
+        .collect()
+        .wait()
+        .unwrap();
+    names.sort_by_key(|n| n[3..].parse::<u32>().unwrap());
+    let want: Vec<String> = (0..30)
+        .filter(|i| i % 10 != 9)
+        .map(|i| format!("cm-{}", i))
+        .collect();
+    assert_eq!(names, want);
+
+    // One at a time, the namespaces come in order.
+    let names: Vec<String> = client
+        .with_pager(PagerOptions {
+            list_concurrency: 1,
+            ..Default::default()
+        })
+        .list_multi::<Value, _>(&gvr, &namespaces[..2], Default::default())
+        .map(|o| o["metadata"]["name"].as_str().unwrap().to_string())
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(names, ["cm-0", "cm-10", "cm-20", "cm-1", "cm-11", "cm-21"]);
+}
+
+#[test]
+fn test_watch_reuses_connection() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use tokio::runtime::Runtime;
+
+    // Ends each watch after one event, as the apiserver does when a
+    // watch times out.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = Arc::clone(&connections);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                loop {
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let event = b"{\"type\":\"ADDED\",\"object\":{}}\n";
+                    let mut res = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                    Transfer-Encoding: chunked\r\n\r\n"
+                        .to_vec();
+                    res.extend(format!("{:x}\r\n", event.len()).as_bytes());
+                    res.extend(&event[..]);
+                    res.extend(b"\r\n0\r\n\r\n");
+                    stream.write_all(&res).unwrap();
+                }
+            });
+        }
+    });
+
+    let mut context: ConfigContext = Default::default();
+    context.cluster.server = format!("http://{}", addr);
+    let client = Client::builder()
+        .config(context)
+        .build_with_client(hyper::Client::new())
+        .unwrap();
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "pods",
+    };
+    // Restart straight away, as the watch helpers do.
+    let watches = future::loop_fn(0, move |n| {
+        client
+            .watch_list(&gvr, None, ListOptions::default())
+            .collect()
+            .map(move |events| {
+                assert_eq!(events.len(), 1);
+                if n < 4 {
+                    future::Loop::Continue(n + 1)
+                } else {
+                    future::Loop::Break(())
+                }
+            })
+    });
+    Runtime::new().unwrap().block_on(watches).unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,221 @@
+//! Watching a set of namespaces as one stream, for when a controller
+//! may not watch the whole cluster.
+//!
+//! Each namespace is watched separately, and its watch restarted from
+//! the last resource version seen when the apiserver ends it.  Failed
+//! watches are retried, backing off as `RetryPolicy` says; once a
+//! namespace has used up its retries, its error is passed on and it is
+//! not watched again, while the others carry on.
+//!
+//! If a namespace's resource version has expired, its `ERROR` event is
+//! passed on, and it is watched again from the current state, which
+//! starts with an `ADDED` event for each object.  Deletions in the gap
+//! are missed, so callers that keep a cache should relist.
+//!
+//! The stream ends once the client's `Shutdown` (if any) is triggered.
+
+use std::mem;
+use std::time::Instant;
+
+use failure::Error;
+use futures::{stream, Async, Future, Poll, Stream};
+use serde_json;
+use tokio::timer::Delay;
+
+use api::meta::v1::{EventType, ListOptions, Status, WatchEvent};
+use api::meta::GroupVersionResource;
+use k8sclient::error::ApiError;
+
+use super::retry::RetryPolicy;
+use super::shutdown::guard_with;
+use super::Client;
+
+type BoxStream<T> = Box<dyn Stream<Item = T, Error = Error> + Send>;
+
+/// Watch the objects of `gvr` in each of `namespaces`, from
+/// `opts.resource_version`, as one stream.
+pub fn watch_namespaces<S: AsRef<str>>(
+    client: &Client,
+    gvr: &GroupVersionResource,
+    namespaces: &[S],
+    opts: ListOptions,
+    retry: RetryPolicy,
+) -> impl Stream<Item = WatchEvent, Error = Error> + Send {
+    let empty: BoxStream<WatchEvent> = Box::new(stream::empty());
+    let merged = namespaces.iter().fold(empty, |merged, namespace| {
+        let watch = NamespaceWatch {
+            client: client.clone(),
+            gvr: (
+                gvr.group.to_string(),
+                gvr.version.to_string(),
+                gvr.resource.to_string(),
+            ),
+            namespace: namespace.as_ref().to_string(),
+            opts: opts.clone(),
+            retry: retry.clone(),
+            failures: 0,
+            state: State::Idle,
+        };
+        Box::new(merged.select(watch))
+    });
+    // Otherwise the ended watches would be restarted.
+    guard_with(client.shutdown(), merged)
+}
+
+enum State {
+    /// About to (re)start the watch.
+    Idle,
+    Watching(BoxStream<WatchEvent>),
+    /// Backing off after a failure.
+    Waiting(Delay),
+    Done,
+}
+
+/// The watch of one namespace, restarted as needed.
+struct NamespaceWatch {
+    client: Client,
+    gvr: (String, String, String),
+    namespace: String,
+    /// With the resource version to restart from.
+    opts: ListOptions,
+    retry: RetryPolicy,
+    /// Since the last event.
+    failures: u32,
+    state: State,
+}
+
+impl NamespaceWatch {
+    fn start(&self) -> BoxStream<WatchEvent> {
+        let gvr = GroupVersionResource {
+            group: &self.gvr.0,
+            version: &self.gvr.1,
+            resource: &self.gvr.2,
+        };
+        Box::new(
+            self.client
+                .watch_list(&gvr, Some(&self.namespace), self.opts.clone()),
+        )
+    }
+
+    /// Keep track of where to restart from.
+    fn seen(&mut self, event: &WatchEvent) {
+        if event.typ == EventType::Error {
+            let expired = serde_json::from_value::<Status>(event.object.clone())
+                .map(|status| ApiError::from(status).is_expired())
+                .unwrap_or(false);
+            if expired {
+                warn!(
+                    "Watch of {} in {} expired, restarting from the current state",
+                    self.gvr.2, self.namespace
+                );
+                self.opts.resource_version = String::new();
+            }
+            return;
+        }
+        if let Some(rv) = event.object["metadata"]["resourceVersion"].as_str() {
+            self.opts.resource_version = rv.to_string();
+        }
+    }
+}
+
+impl Stream for NamespaceWatch {
+    type Item = WatchEvent;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<WatchEvent>, Error> {
+        loop {
+            match mem::replace(&mut self.state, State::Done) {
+                State::Idle => self.state = State::Watching(self.start()),
+                State::Waiting(mut delay) => match delay.poll()? {
+                    Async::Ready(()) => self.state = State::Idle,
+                    Async::NotReady => {
+                        self.state = State::Waiting(delay);
+                        return Ok(Async::NotReady);
+                    }
+                },
+                State::Watching(mut events) => match events.poll() {
+                    Ok(Async::Ready(Some(event))) => {
+                        self.failures = 0;
+                        self.seen(&event);
+                        self.state = State::Watching(events);
+                        return Ok(Async::Ready(Some(event)));
+                    }
+                    Ok(Async::Ready(None)) => {
+                        debug!("Watch of {} in {} ended", self.gvr.2, self.namespace);
+                        self.state = State::Idle;
+                    }
+                    Ok(Async::NotReady) => {
+                        self.state = State::Watching(events);
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => {
+                        if self.failures >= self.retry.max_retries {
+                            return Err(e);
+                        }
+                        let backoff = self.retry.backoff(self.failures);
+                        warn!(
+                            "Watch of {} in {} failed, retrying in {:?}: {}",
+                            self.gvr.2, self.namespace, backoff, e
+                        );
+                        self.failures += 1;
+                        self.state = State::Waiting(Delay::new(Instant::now() + backoff));
+                    }
+                },
+                State::Done => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_watch_namespaces() {
+    use serde_json::Value;
+
+    use super::fake::FakeClient;
+
+    let configmaps = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let client = FakeClient::new();
+    let server = client.server();
+    for ns in &["a", "b", "c"] {
+        server
+            .insert(
+                &configmaps,
+                &json!({"metadata": {"name": "cm", "namespace": ns}}),
+            )
+            .unwrap();
+    }
+    let namespace = |event: &WatchEvent| -> String {
+        event.object["metadata"]["namespace"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let mut events = watch_namespaces(
+        &client,
+        &configmaps,
+        &["a", "b"],
+        ListOptions::default(),
+        RetryPolicy::default(),
+    )
+    .wait();
+    let mut seen = vec![
+        namespace(&events.next().unwrap().unwrap()),
+        namespace(&events.next().unwrap().unwrap()),
+    ];
+    seen.sort();
+    assert_eq!(seen, vec!["a", "b"]);
+
+    // The watches are restarted where they left off.
+    server.close_watches();
+    let mut cm = server.get(&configmaps, Some("b"), "cm").unwrap();
+    cm["data"] = json!({"k": "v"});
+    let _: Value = client.update(&configmaps, &cm).wait().unwrap();
+    let event = events.next().unwrap().unwrap();
+    assert_eq!(event.typ, EventType::Modified);
+    assert_eq!(namespace(&event), "b");
+}
@@ -0,0 +1,182 @@
+//! Making sure a namespace exists, and getting rid of it again, as
+//! test harnesses and provisioning tools do.
+//!
+//! ```no_run
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use std::collections::HashMap;
+//! # use std::time::Duration;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let mut labels = HashMap::new();
+//! labels.insert("owner".to_string(), "e2e".to_string());
+//! let mut rt = tokio::runtime::Runtime::new()?;
+//! rt.block_on(client.ensure_namespace("e2e-1", &labels))?;
+//! // ...
+//! rt.block_on(client.delete_namespace_and_wait("e2e-1", Duration::from_secs(300)))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use failure::Error;
+use futures::future::{self, Either, Loop};
+use futures::Future;
+
+use api::core::v1::{Namespace, NamespacePhase, TypedResource};
+use api::meta::GroupVersionResource;
+use api::MERGE_PATCH;
+use k8sclient::error::ApiError;
+
+use super::{wait, Client};
+
+const NAMESPACES: GroupVersionResource<'static> = Namespace::GROUP_VERSION_RESOURCE;
+
+fn is_terminating(ns: &Namespace) -> bool {
+    ns.metadata.deletion_timestamp.is_some() || ns.status.phase == Some(NamespacePhase::Terminating)
+}
+
+impl Client {
+    /// Make sure the namespace `name` exists and has `labels` (among
+    /// any others it has), creating it if need be, and return it.
+    ///
+    /// A namespace that is still being deleted is waited for, and then
+    /// created again.  Never times out by itself, see `wait::timeout`.
+    /// Requires a tokio runtime with a timer.
+    pub fn ensure_namespace(
+        &self,
+        name: &str,
+        labels: &HashMap<String, String>,
+    ) -> impl Future<Item = Namespace, Error = Error> + Send {
+        let client = self.clone();
+        let name = name.to_string();
+        let labels = labels.clone();
+        future::loop_fn((), move |()| {
+            let (client, name, labels) = (client.clone(), name.clone(), labels.clone());
+            client
+                .get_opt(&NAMESPACES, None, &name, Default::default())
+                .and_then(move |ns: Option<Namespace>| match ns {
+                    None => {
+                        let mut ns = Namespace::default();
+                        ns.metadata.name = Some(name);
+                        ns.metadata.labels = labels;
+                        Either::A(Either::A(
+                            client
+                                .create(&NAMESPACES, &ns, Default::default())
+                                .map(Loop::Break)
+                                .or_else(|e| match ApiError::from_error(&e) {
+                                    // Someone else made it first.
+                                    Some(api) if api.is_already_exists() => Ok(Loop::Continue(())),
+                                    _ => Err(e),
+                                }),
+                        ))
+                    }
+                    Some(ref ns) if is_terminating(ns) => {
+                        debug!("Waiting for namespace {} to finish terminating", name);
+                        let uid = ns.metadata.uid.clone().unwrap_or_default();
+                        Either::A(Either::B(
+                            wait::await_deletion_of(&client, &NAMESPACES, None, &name, &uid)
+                                .map(|()| Loop::Continue(())),
+                        ))
+                    }
+                    Some(ns) => {
+                        let missing: HashMap<_, _> = labels
+                            .into_iter()
+                            .filter(|(k, v)| ns.metadata.labels.get(k) != Some(v))
+                            .collect();
+                        if missing.is_empty() {
+                            return Either::B(Either::A(future::ok(Loop::Break(ns))));
+                        }
+                        let patch = json!({"metadata": {"labels": missing}});
+                        Either::B(Either::B(
+                            client
+                                .patch(&NAMESPACES, None, &name, MERGE_PATCH, &patch)
+                                .map(Loop::Break),
+                        ))
+                    }
+                })
+        })
+    }
+
+    /// Delete the namespace `name`, and wait until it and everything in
+    /// it is gone.  Succeeds if it doesn't exist.
+    ///
+    /// Fails with `wait::DeletionStalledError` if it remains after
+    /// `after`.  Requires a tokio runtime with a timer.
+    pub fn delete_namespace_and_wait(
+        &self,
+        name: &str,
+        after: Duration,
+    ) -> impl Future<Item = (), Error = Error> + Send {
+        wait::delete_and_wait(self, &NAMESPACES, None, name, Default::default(), after)
+    }
+}
+
+#[test]
+fn test_namespace() {
+    use serde_json::Value;
+    use tokio::runtime::Runtime;
+
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let server = client.server();
+    let mut rt = Runtime::new().unwrap();
+    let labels = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    let ns = rt
+        .block_on(client.ensure_namespace("e2e", &labels(&[("owner", "e2e")])))
+        .unwrap();
+    assert_eq!(ns.metadata.labels, labels(&[("owner", "e2e")]));
+    let uid = ns.metadata.uid;
+    // Labels are added to what is there.
+    let ns = rt
+        .block_on(client.ensure_namespace("e2e", &labels(&[("run", "1")])))
+        .unwrap();
+    assert_eq!(
+        ns.metadata.labels,
+        labels(&[("owner", "e2e"), ("run", "1")])
+    );
+    assert_eq!(ns.metadata.uid, uid);
+
+    // Terminating, until its finalizer is done.
+    let mut stored = server.get(&NAMESPACES, None, "e2e").unwrap();
+    stored["metadata"]["finalizers"] = json!(["example.com/cleanup"]);
+    let stored: Value = client.update(&NAMESPACES, &stored).wait().unwrap();
+    let err = rt
+        .block_on(client.delete_namespace_and_wait("e2e", Duration::from_millis(100)))
+        .unwrap_err();
+    let err = err.downcast::<wait::DeletionStalledError>().unwrap();
+    assert_eq!(err.finalizers, vec!["example.com/cleanup"]);
+
+    let ensure = client.ensure_namespace("e2e", &labels(&[]));
+    let finalize = {
+        let client = client.clone();
+        let mut stored = server.get(&NAMESPACES, None, "e2e").unwrap();
+        stored["metadata"]["finalizers"] = json!([]);
+        tokio::timer::Delay::new(std::time::Instant::now() + Duration::from_millis(50))
+            .from_err::<Error>()
+            .and_then(move |()| client.update(&NAMESPACES, &stored))
+            .map(|_: Value| ())
+    };
+    let (ns, ()) = rt.block_on(ensure.join(finalize)).unwrap();
+    assert_ne!(
+        ns.metadata.uid,
+        stored["metadata"]["uid"].as_str().map(String::from)
+    );
+    assert!(ns.metadata.labels.is_empty());
+
+    rt.block_on(client.delete_namespace_and_wait("e2e", Duration::from_secs(10)))
+        .unwrap();
+    assert!(server.get(&NAMESPACES, None, "e2e").is_none());
+    rt.block_on(client.delete_namespace_and_wait("e2e", Duration::from_secs(10)))
+        .unwrap();
+}
@@ -0,0 +1,129 @@
+//! Requests to a node's kubelet, via the apiserver's node proxy at
+//! `/api/v1/nodes/{name}/proxy/...`.
+//!
+//! The caller needs `get` on the `nodes/proxy` subresource, which grants
+//! full access to the kubelet API; see the Kubernetes docs before
+//! granting it.
+
+use std::str;
+use std::sync::Arc;
+
+use failure::Error;
+use futures::Future;
+use hyper::{Body, Method, Request};
+use serde::de::DeserializeOwned;
+
+use api::stats::v1alpha1::Summary;
+
+use super::{do_request, do_request_raw, hyper_uri, Client};
+
+impl Client {
+    fn node_proxy_request(&self, node: &str, path: &str) -> Result<Request<Body>, Error> {
+        let mut segments = vec!["api", "v1", "nodes", node, "proxy"];
+        segments.extend(path.split('/'));
+        let url = self.path_url(&segments)?;
+        Ok(Request::builder()
+            .method(Method::GET)
+            .uri(hyper_uri(url))
+            .body(Body::empty())?)
+    }
+
+    /// GET `path` (eg: `configz`) from `node`'s kubelet, decoding the
+    /// JSON response.
+    pub fn node_proxy_get<T>(
+        &self,
+        node: &str,
+        path: &str,
+    ) -> impl Future<Item = T, Error = Error> + Send
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let req = self.node_proxy_request(node, path);
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
+    /// GET `path` (eg: `metrics/cadvisor`) from `node`'s kubelet, as
+    /// bytes.
+    pub fn node_proxy_get_raw(
+        &self,
+        node: &str,
+        path: &str,
+    ) -> impl Future<Item = Vec<u8>, Error = Error> + Send {
+        let req = self.node_proxy_request(node, path);
+        do_request_raw(Arc::clone(&self.client), self.timeout, req).map(|body| body.to_vec())
+    }
+
+    /// Resource usage of `node`, and of the pods and containers on it.
+    pub fn node_stats_summary(
+        &self,
+        node: &str,
+    ) -> impl Future<Item = Summary, Error = Error> + Send {
+        self.node_proxy_get(node, "stats/summary")
+    }
+
+    /// A file under `/var/log` on `node`, eg: `kubelet.log` or
+    /// `pods/...`.  A directory (ending in `/`, or the empty string for
+    /// `/var/log` itself) is returned as the kubelet's HTML listing.
+    pub fn node_logs(
+        &self,
+        node: &str,
+        path: &str,
+    ) -> impl Future<Item = String, Error = Error> + Send {
+        self.node_proxy_get_raw(node, &format!("logs/{}", path))
+            .and_then(|body| Ok(String::from_utf8(body)?))
+    }
+}
+
+#[test]
+fn test_node_proxy() {
+    use futures::future;
+    use hyper::{Response, StatusCode};
+
+    use super::backend::Backend;
+    use super::middleware::ResponseFuture;
+
+    /// Answers with the request path, or 404 for unknown nodes.
+    #[derive(Debug)]
+    struct Kubelet;
+
+    impl Backend for Kubelet {
+        fn call(&self, req: Request<Body>) -> ResponseFuture {
+            let path = req.uri().path();
+            let mut res = if path == "/api/v1/nodes/node-1/proxy/stats/summary" {
+                Response::new(
+                    json!({"node": {"nodeName": "node-1", "startTime": "2020-01-01T00:00:00Z"}})
+                        .to_string()
+                        .into(),
+                )
+            } else if path.starts_with("/api/v1/nodes/node-1/proxy/") {
+                Response::new(path.to_string().into())
+            } else {
+                Response::new(Body::empty())
+            };
+            if !path.starts_with("/api/v1/nodes/node-1/") {
+                *res.status_mut() = StatusCode::NOT_FOUND;
+            }
+            Box::new(future::ok(res))
+        }
+    }
+
+    let mut config: super::config::ConfigContext = Default::default();
+    config.cluster.server = "https://apiserver.invalid".to_string();
+    let client = Client::builder()
+        .config(config)
+        .build_with_backend(Arc::new(Kubelet))
+        .unwrap();
+
+    let summary = client.node_stats_summary("node-1").wait().unwrap();
+    assert_eq!(summary.node.node_name, "node-1");
+    assert_eq!(
+        client.node_logs("node-1", "").wait().unwrap(),
+        "/api/v1/nodes/node-1/proxy/logs/"
+    );
+    assert_eq!(
+        client.node_logs("node-1", "pods/x.log").wait().unwrap(),
+        "/api/v1/nodes/node-1/proxy/logs/pods/x.log"
+    );
+    let err = client.node_stats_summary("node-2").wait().unwrap_err();
+    assert_eq!(super::status_code(&err), Some(404));
+}
@@ -0,0 +1,488 @@
+//! Paging through large lists with `limit` and `continue`, as `iter`
+//! does.
+//!
+//! The pages of a list are all from the same snapshot, at the
+//! `resourceVersion` of the first page, so together they are a
+//! consistent view of the collection.  But the apiserver only keeps
+//! the snapshot for a while (until compaction).  Continuing a list
+//! after that fails with `410 Gone`, and `iter` then fails with
+//! `ExpiredContinue`, unless `PagerOptions::on_expired` says to start
+//! the list again, or to carry on from a newer snapshot:
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # use futures::{Future, Stream};
+//! # use kubernetes_api::core::v1::Pod;
+//! # use kubernetes_holding::client::pager::{OnExpired, PagerOptions};
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?.with_pager(PagerOptions {
+//!     on_expired: OnExpired::Restart,
+//!     ..Default::default()
+//! });
+//! let names: Vec<String> = client
+//!     .resource::<Pod>()
+//!     .iter(Default::default())
+//!     .map(|pod| pod.metadata.name.unwrap_or_default())
+//!     .collect()
+//!     .wait()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! While the caller works through one page, the next is already being
+//! fetched (see `PagerOptions::prefetch`), so a large list isn't held
+//! up by the round trip for each page.
+//!
+//! `iter` yields just the items; `pages` yields each `Page`, with the
+//! list's metadata, eg: for showing progress:
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # use futures::Stream;
+//! # use kubernetes_api::core::v1::Pod;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let mut seen = 0;
+//! for page in client.resource::<Pod>().pages(Default::default()).wait() {
+//!     let page = page?;
+//!     if page.restarted {
+//!         seen = 0;
+//!     }
+//!     seen += page.items.len();
+//!     if let Some(remaining) = page.metadata.remaining_item_count {
+//!         println!("{}/~{}", seen, seen as i64 + remaining);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+use failure::{Error, ResultExt};
+use futures::{future, stream, Async, Future, Poll, Stream};
+use hyper::{Body, Method, Request, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json;
+use serde_urlencoded;
+use url::Url;
+
+use api::meta::v1::{List, ListMeta, ListOptions, Status};
+use k8sclient::error::ApiError;
+
+use super::transport::{self, Transport};
+use super::{accept_json, decode_body, hyper_uri, response_error, wait, Client, LIST_CONCURRENCY};
+
+/// How `iter` pages through lists, and how many `list_multi` makes at
+/// once.  Set with `Client::with_pager`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PagerOptions {
+    /// What to do when the continue token expires part way through a
+    /// list.
+    pub on_expired: OnExpired,
+    /// How many pages to fetch ahead of the caller, holding them in
+    /// memory until they are wanted.  0 fetches each page only once the
+    /// previous one has been taken.  The default is 1.
+    pub prefetch: usize,
+    /// How many namespaces `list_multi` lists at once (0 is taken as
+    /// 1).  The default is `LIST_CONCURRENCY`.
+    pub list_concurrency: usize,
+}
+
+impl Default for PagerOptions {
+    fn default() -> Self {
+        PagerOptions {
+            on_expired: Default::default(),
+            prefetch: 1,
+            list_concurrency: LIST_CONCURRENCY,
+        }
+    }
+}
+
+/// What to do when a list's snapshot expires before the list is
+/// finished.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnExpired {
+    /// Fail with `ExpiredContinue`.  What was listed is consistent, but
+    /// incomplete.
+    #[default]
+    Fail,
+    /// Start the list again from the beginning, from a new snapshot.
+    /// Items already seen are listed again.
+    Restart,
+    /// Carry on from where the list got to, using the token the
+    /// apiserver offers, but from a newer snapshot.  The list is then
+    /// inconsistent: objects created, changed or deleted in between may
+    /// be missing, or listed twice, or in different states.  If the
+    /// apiserver offers no token, this fails like `Fail`.
+    ContinueInconsistently,
+}
+
+/// The continue token for a list expired before the list was finished.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub struct ExpiredContinue {
+    pub error: ApiError,
+    /// The `resourceVersion` of the snapshot listed so far, if known.
+    pub resource_version: Option<String>,
+    /// A token the apiserver offered for continuing the list anyway,
+    /// if it did.  The rest of the list is from a newer snapshot than
+    /// what came before: objects created, changed or deleted since may
+    /// be missing, repeated or inconsistent.
+    pub inconsistent_continue: Option<String>,
+}
+
+impl fmt::Display for ExpiredContinue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "List continue token expired: {}", self.error)
+    }
+}
+
+/// One page of a list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    /// The list's `resourceVersion`, which is that of the snapshot
+    /// being listed, the `continue` token for the next page (`None` on
+    /// the last), and roughly how many items come after this page.
+    pub metadata: ListMeta,
+    pub items: Vec<T>,
+    /// Whether the list was started again, from a new snapshot, before
+    /// this page (see `OnExpired::Restart`), so that the items of the
+    /// earlier pages are listed again.
+    pub restarted: bool,
+}
+
+impl<T> Page<T> {
+    /// Whether this is the last page of the list.
+    pub fn is_last(&self) -> bool {
+        self.metadata
+            .continu
+            .as_deref()
+            .unwrap_or_default()
+            .is_empty()
+    }
+}
+
+/// Where a list has got to.
+struct Paging {
+    opts: ListOptions,
+    /// The `resourceVersion` of the pages so far.
+    snapshot: Option<String>,
+    /// Whether the list was restarted since the last page.
+    restarted: bool,
+}
+
+/// The pages of the list at `url`, from `opts.continu` on.
+pub(crate) fn pages<L>(
+    client: Client,
+    url: Url,
+    opts: ListOptions,
+    pager: PagerOptions,
+) -> impl Stream<Item = Page<L::Item>, Error = Error> + Send
+where
+    L: List + DeserializeOwned + Send + 'static,
+    L::Item: Send + 'static,
+{
+    let first = opts.continu.clone();
+    let paging = Paging {
+        opts,
+        snapshot: None,
+        restarted: false,
+    };
+    let prefetch = pager.prefetch;
+    let pages = stream::unfold(Some(paging), move |paging| {
+        let mut paging = paging?;
+        let first = first.clone();
+        let on_expired = pager.on_expired;
+        let page = fetch::<L>(&client, url.clone(), &paging.opts).then(move |r| match r {
+            Ok(list) => {
+                let meta = list.listmeta();
+                let restarted = paging.restarted;
+                match paging.snapshot {
+                    Some(ref rv) if *rv != meta.resource_version => warn!(
+                        "List continued inconsistently, at resourceVersion {} rather than {}",
+                        meta.resource_version, rv
+                    ),
+                    _ => (),
+                }
+                let next = meta.continu.clone().map(|continu| Paging {
+                    opts: ListOptions {
+                        continu,
+                        ..paging.opts
+                    },
+                    snapshot: Some(meta.resource_version.clone()),
+                    restarted: false,
+                });
+                let page = Page {
+                    metadata: meta.into_owned(),
+                    items: list.into_items(),
+                    restarted,
+                };
+                Ok((Some(page), next))
+            }
+            Err(e) => {
+                let mut expired = match e.downcast::<ExpiredContinue>() {
+                    Ok(expired) => expired,
+                    Err(e) => return Err(e),
+                };
+                expired.resource_version = paging.snapshot.take();
+                match (on_expired, expired.inconsistent_continue.take()) {
+                    (OnExpired::Restart, _) => {
+                        warn!("{}; restarting the list", expired);
+                        paging.opts.continu = first;
+                        paging.restarted = true;
+                    }
+                    (OnExpired::ContinueInconsistently, Some(continu)) => {
+                        warn!("{}; continuing from a newer snapshot", expired);
+                        paging.opts.continu = continu;
+                        paging.snapshot = expired.resource_version;
+                    }
+                    (_, continu) => {
+                        expired.inconsistent_continue = continu;
+                        return Err(expired.into());
+                    }
+                }
+                Ok((None, Some(paging)))
+            }
+        });
+        Some(page)
+    })
+    .filter_map(|page| page);
+    Prefetch {
+        stream: pages,
+        buffer: VecDeque::new(),
+        error: None,
+        done: false,
+        lookahead: prefetch,
+    }
+}
+
+/// `stream`, polled ahead of the caller until `lookahead` items are
+/// waiting (or on the way).
+struct Prefetch<S: Stream> {
+    stream: S,
+    buffer: VecDeque<S::Item>,
+    /// Passed on after the items before it.
+    error: Option<S::Error>,
+    done: bool,
+    lookahead: usize,
+}
+
+impl<S: Stream> Stream for Prefetch<S> {
+    type Item = S::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, S::Error> {
+        // Keeps the next item's fetch going, when the buffer is one
+        // short.
+        while !self.done && self.buffer.len() <= self.lookahead {
+            match self.stream.poll() {
+                Ok(Async::Ready(Some(item))) => self.buffer.push_back(item),
+                Ok(Async::Ready(None)) => self.done = true,
+                Ok(Async::NotReady) => break,
+                Err(e) => {
+                    self.error = Some(e);
+                    self.done = true;
+                }
+            }
+        }
+        if let Some(item) = self.buffer.pop_front() {
+            return Ok(Async::Ready(Some(item)));
+        }
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if self.done {
+            Ok(Async::Ready(None))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+/// Fetch a single page, failing with `ExpiredContinue` if it was a
+/// continuation that expired.
+fn fetch<L>(
+    client: &Client,
+    mut url: Url,
+    opts: &ListOptions,
+) -> impl Future<Item = L, Error = Error> + Send
+where
+    L: DeserializeOwned + Send + 'static,
+{
+    let continuing = !opts.continu.is_empty();
+    let req = serde_urlencoded::to_string(opts)
+        .with_context(|e| format!("Unable to encode URL parameters {}", e))
+        .map_err(Error::from)
+        .and_then(|query| {
+            url.set_query(Some(&query).filter(|q| !q.is_empty()).map(|q| q.as_str()));
+            let mut req = Request::builder()
+                .method(Method::GET)
+                .uri(hyper_uri(url))
+                .body(Body::empty())?;
+            accept_json(&mut req);
+            Ok(req)
+        });
+    let transport = Arc::clone(&client.client);
+    let f = future::result(req)
+        .and_then(move |req| {
+            let request = transport::describe(req.method(), req.uri());
+            Transport::send(transport, req).map(|response| (request, response))
+        })
+        .and_then(
+            move |(request, (httpstatus, audit_id, headers, body))| -> Result<L, Error> {
+                if httpstatus.is_success() {
+                    return decode_body(&headers, &body)?
+                        .ok_or_else(|| format_err!("Unexpected empty response"));
+                }
+                let error = response_error(request, httpstatus, audit_id, &headers, body.as_ref());
+                if continuing && httpstatus == StatusCode::GONE {
+                    let inconsistent_continue = serde_json::from_slice::<Status>(body.as_ref())
+                        .ok()
+                        .and_then(|s| s.metadata.continu)
+                        .filter(|c| !c.is_empty());
+                    return Err(ExpiredContinue {
+                        error,
+                        resource_version: None,
+                        inconsistent_continue,
+                    }
+                    .into());
+                }
+                Err(error.into())
+            },
+        );
+    match client.timeout {
+        Some(after) => future::Either::A(wait::timeout(f, after)),
+        None => future::Either::B(f),
+    }
+}
+
+#[test]
+fn test_pages() {
+    use serde_json::Value;
+
+    use api::meta::GroupVersionResource;
+
+    use super::fake::FakeClient;
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let client = FakeClient::new();
+    let server = client.server();
+    for name in &["a", "b", "c", "d", "e"] {
+        server
+            .insert(
+                &gvr,
+                &json!({"metadata": {"name": name, "namespace": "ns"}}),
+            )
+            .unwrap();
+    }
+    let opts = ListOptions {
+        limit: 2,
+        ..Default::default()
+    };
+    let names = |items: Vec<Value>| -> Vec<String> {
+        items
+            .iter()
+            .map(|o| o["metadata"]["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let url = client
+        .url(&gvr, Some("ns"), None, super::NoOptions {})
+        .unwrap();
+    let list = |c: &Client| pages::<Value>(c.clone(), url.clone(), opts.clone(), c.pager().clone());
+    let all = list(&client).collect().wait().unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[0].metadata.remaining_item_count, Some(3));
+    assert!(!all[1].is_last());
+    assert!(all[2].is_last());
+    assert!(all
+        .iter()
+        .all(|p| p.metadata.resource_version == "5" && !p.restarted));
+    let items = all.into_iter().flat_map(|p| p.items).collect();
+    assert_eq!(names(items), vec!["a", "b", "c", "d", "e"]);
+
+    // The second page was fetched with the first, so expiring the token
+    // then only fails the third.
+    let mut prefetched = list(&client).wait();
+    prefetched.next().unwrap().unwrap();
+    server.expire_continues();
+    assert_eq!(
+        names(prefetched.next().unwrap().unwrap().items),
+        vec!["c", "d"]
+    );
+    assert!(prefetched.next().unwrap().is_err());
+
+    // Expire the token after the first page.
+    let unprefetched = client.with_pager(PagerOptions {
+        prefetch: 0,
+        ..Default::default()
+    });
+    let mut first = list(&unprefetched).wait();
+    first.next().unwrap().unwrap();
+    server.expire_continues();
+    let err = first.next().unwrap().unwrap_err();
+    let expired = err.downcast_ref::<ExpiredContinue>().unwrap();
+    assert_eq!(expired.error.code, 410);
+    assert_eq!(expired.resource_version.as_deref(), Some("5"));
+    let token = expired.inconsistent_continue.clone().unwrap();
+    let rest: Value = client
+        .list(
+            &gvr,
+            Some("ns"),
+            ListOptions {
+                continu: token,
+                limit: 10,
+                ..Default::default()
+            },
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(rest["items"].as_array().unwrap().len(), 3);
+
+    // Or start again.
+    let restarting = client.with_pager(PagerOptions {
+        on_expired: OnExpired::Restart,
+        prefetch: 0,
+        ..Default::default()
+    });
+    let mut pages = list(&restarting).wait();
+    let mut items = pages.next().unwrap().unwrap().items;
+    server.expire_continues();
+    let restarted = pages.next().unwrap().unwrap();
+    assert!(restarted.restarted);
+    items.extend(restarted.items);
+    items.extend(pages.flat_map(|p| p.unwrap().items));
+    assert_eq!(names(items), vec!["a", "b", "a", "b", "c", "d", "e"]);
+
+    // Or carry on, seeing what changed since.
+    let inconsistent = client.with_pager(PagerOptions {
+        on_expired: OnExpired::ContinueInconsistently,
+        prefetch: 0,
+        ..Default::default()
+    });
+    let mut pages = list(&inconsistent).wait();
+    let mut items = pages.next().unwrap().unwrap().items;
+    server.expire_continues();
+    server
+        .insert(
+            &gvr,
+            &json!({"metadata": {"name": "bb", "namespace": "ns"}}),
+        )
+        .unwrap();
+    items.extend(pages.flat_map(|p| p.unwrap().items));
+    assert_eq!(names(items), vec!["a", "b", "bb", "c", "d", "e"]);
+}
@@ -0,0 +1,518 @@
+//! Forwarding connections to a pod's ports, like `kubectl port-forward`.
+//!
+//! Each connection is carried over an upgraded connection to the
+//! apiserver of its own: a WebSocket or, if the apiserver (or a proxy in
+//! front of it) refuses one, SPDY, with the same requirements as `exec`.
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate futures;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! # use futures::{Future, Stream};
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let listener = tokio::net::TcpListener::bind(&"127.0.0.1:8080".parse()?)?;
+//! let server = listener
+//!     .incoming()
+//!     .map_err(|e| eprintln!("Failed to accept: {}", e))
+//!     .for_each(move |conn| {
+//!         tokio::spawn(
+//!             client
+//!                 .port_forward_io("default", "web-0", 80, conn)
+//!                 .map_err(|e| eprintln!("Failed to forward: {}", e)),
+//!         );
+//!         Ok(())
+//!     });
+//! tokio::run(server);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use failure::Error;
+use futures::future::{self, Either};
+use futures::sync::mpsc;
+use futures::{stream, Async, Future, Poll, Stream};
+use tokio;
+use tokio::codec::{BytesCodec, FramedRead};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use api::meta::GroupVersionResource;
+
+use super::exec::{connect, send, Input, Protocol};
+use super::spdy::{self, Frame, Frames, FLAG_FIN};
+use super::websocket::{self, Message, Messages, BINARY, CLOSE, PING, PONG};
+use super::Client;
+
+const PODS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "pods",
+};
+
+const WEBSOCKET_PROTOCOL: &str = "v4.channel.k8s.io";
+const SPDY_PROTOCOL: &str = "portforward.k8s.io";
+
+// The WebSocket channels for the (one) port, each starting with the
+// port number.
+const DATA: u8 = 0;
+const ERROR: u8 = 1;
+
+/// The kubelet couldn't forward the connection, eg: because nothing
+/// was listening on the port.
+#[derive(Debug, Fail)]
+#[fail(display = "{}", message)]
+pub struct PortForwardError {
+    pub message: String,
+}
+
+impl Client {
+    /// Connect to `port` in `pod`, sending `input` and yielding what
+    /// comes back as it arrives.  The stream ends when the pod closes
+    /// the connection, failing with a `PortForwardError` if the kubelet
+    /// reported an error.
+    ///
+    /// Over a WebSocket, the end of `input` can't be sent on, so only
+    /// the pod can end the connection.
+    ///
+    /// Requires a tokio runtime.
+    pub fn port_forward(
+        &self,
+        namespace: &str,
+        pod: &str,
+        port: u16,
+        input: Input,
+    ) -> impl Stream<Item = Vec<u8>, Error = Error> + Send {
+        let url = self
+            .subresource_url(&PODS, Some(namespace), pod, "portforward")
+            .map(|mut url| {
+                url.query_pairs_mut()
+                    .append_pair("ports", &port.to_string());
+                url
+            });
+        let transport = Arc::clone(&self.client);
+        future::result(url)
+            .and_then(move |url| connect(transport, url, &[WEBSOCKET_PROTOCOL], &[SPDY_PROTOCOL]))
+            .map(move |(io, protocol)| match protocol {
+                Protocol::WebSocket { .. } => Either::A(session(io, input)),
+                Protocol::Spdy => Either::B(spdy_session(io, port, input)),
+            })
+            .flatten_stream()
+    }
+
+    /// Forward `io` (eg: an accepted `TcpStream`) to `port` in `pod`,
+    /// until the pod closes the connection.
+    ///
+    /// Requires a tokio runtime.
+    pub fn port_forward_io<T>(
+        &self,
+        namespace: &str,
+        pod: &str,
+        port: u16,
+        io: T,
+    ) -> impl Future<Item = (), Error = Error> + Send
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (read, write) = io.split();
+        let input = FramedRead::new(read, BytesCodec::new())
+            .map(|data| data.to_vec())
+            .from_err();
+        self.port_forward(namespace, pod, port, Box::new(input))
+            .fold(write, |write, data| {
+                tokio::io::write_all(write, data)
+                    .map(|(write, _)| write)
+                    .from_err::<Error>()
+            })
+            .and_then(|write| tokio::io::shutdown(write).from_err())
+            .map(|_| ())
+    }
+}
+
+/// Send `input` over `io` (in the background), and read what comes
+/// back.
+fn session<T>(io: T, input: Input) -> Session<tokio::io::ReadHalf<T>>
+where
+    T: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read, write) = io.split();
+    let (pong, pongs) = mpsc::unbounded();
+    let frames = input
+        .map(|data| {
+            let mut message = Vec::with_capacity(data.len() + 1);
+            message.push(DATA);
+            message.extend(data);
+            websocket::encode(BINARY, &message)
+        })
+        .select(
+            pongs
+                .map(|data: Vec<u8>| websocket::encode(PONG, &data))
+                .map_err(|()| format_err!("Pong channel failed")),
+        );
+    send(write, frames);
+    Session {
+        messages: Messages::new(read),
+        pong,
+        port_read: [false; 2],
+        error: Vec::new(),
+        done: false,
+    }
+}
+
+struct Session<R> {
+    messages: Messages<R>,
+    pong: mpsc::UnboundedSender<Vec<u8>>,
+    /// Whether each channel's port number has been read.
+    port_read: [bool; 2],
+    error: Vec<u8>,
+    done: bool,
+}
+
+impl<R: AsyncRead> Stream for Session<R> {
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, Error> {
+        while !self.done {
+            let message = match self.messages.poll() {
+                Ok(Async::Ready(message)) => message,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            };
+            match message {
+                Some(Message {
+                    opcode: BINARY,
+                    data,
+                }) => {
+                    let (channel, mut rest) = match data.split_first() {
+                        Some((&channel, rest)) if channel == DATA || channel == ERROR => {
+                            (channel, rest)
+                        }
+                        _ => continue,
+                    };
+                    let port_read = &mut self.port_read[channel as usize];
+                    if !*port_read && !rest.is_empty() {
+                        *port_read = true;
+                        rest = rest.get(2..).unwrap_or(&[]);
+                    }
+                    match channel {
+                        _ if rest.is_empty() => {}
+                        DATA => return Ok(Async::Ready(Some(rest.to_vec()))),
+                        _ => self.error.extend_from_slice(rest),
+                    }
+                }
+                Some(Message { opcode: PING, data }) => {
+                    // As for exec.
+                    let _ = self.pong.unbounded_send(data);
+                }
+                Some(Message { opcode: CLOSE, .. }) | None => {
+                    self.done = true;
+                    forward_error(&self.error)?;
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(Async::Ready(None))
+    }
+}
+
+/// Open a pair of streams for the connection over `io`, send `input` on
+/// the data stream (in the background), and read what comes back.
+fn spdy_session<T>(io: T, port: u16, input: Input) -> SpdySession<tokio::io::ReadHalf<T>>
+where
+    T: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read, write) = io.split();
+    let (pong, pongs) = mpsc::unbounded();
+    let port = port.to_string();
+    let mut streams = spdy::Streams::new();
+    let (error, error_syn) =
+        streams.open(&[("streamtype", "error"), ("port", &port), ("requestid", "0")]);
+    let (data, data_syn) =
+        streams.open(&[("streamtype", "data"), ("port", &port), ("requestid", "0")]);
+    // Nothing is sent on the error stream, so it's closed straight
+    // away, as kubectl does.
+    let open = vec![error_syn, spdy::data(error, FLAG_FIN, &[]), data_syn];
+    let frames = stream::iter_ok(open)
+        .chain(input.map(move |input| spdy::data(data, 0, &input)))
+        .chain(stream::once(Ok(spdy::data(data, FLAG_FIN, &[]))))
+        .select(
+            pongs
+                .map(|data: Vec<u8>| spdy::control(spdy::PING, 0, &data))
+                .map_err(|()| format_err!("Pong channel failed")),
+        );
+    send(write, frames);
+    SpdySession {
+        frames: Frames::new(read),
+        pong,
+        error,
+        data,
+        open: vec![error, data],
+        error_message: Vec::new(),
+        done: false,
+    }
+}
+
+struct SpdySession<R> {
+    frames: Frames<R>,
+    pong: mpsc::UnboundedSender<Vec<u8>>,
+    error: u32,
+    data: u32,
+    /// The streams the server has yet to finish sending.
+    open: Vec<u32>,
+    error_message: Vec<u8>,
+    done: bool,
+}
+
+impl<R: AsyncRead> Stream for SpdySession<R> {
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, Error> {
+        while !self.done {
+            if self.open.is_empty() {
+                self.done = true;
+                forward_error(&self.error_message)?;
+                break;
+            }
+            let frame = match self.frames.poll() {
+                Ok(Async::Ready(frame)) => frame,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            };
+            match frame {
+                Some(Frame::Data {
+                    stream,
+                    flags,
+                    data,
+                }) => {
+                    if flags & FLAG_FIN != 0 {
+                        self.open.retain(|&s| s != stream);
+                    }
+                    match data {
+                        ref data if data.is_empty() => {}
+                        _ if stream == self.error => self.error_message.extend(data),
+                        _ if stream == self.data => return Ok(Async::Ready(Some(data))),
+                        _ => {}
+                    }
+                }
+                Some(Frame::Control {
+                    kind: spdy::PING,
+                    data,
+                    ..
+                }) => {
+                    let _ = self.pong.unbounded_send(data);
+                }
+                Some(Frame::Control {
+                    kind: spdy::RST_STREAM,
+                    data,
+                    ..
+                }) => {
+                    if let Some(stream) = spdy::stream_id(&data) {
+                        self.open.retain(|&s| s != stream);
+                    }
+                }
+                Some(Frame::Control {
+                    kind: spdy::GOAWAY, ..
+                })
+                | None => self.open.clear(),
+                Some(_) => {}
+            }
+        }
+        Ok(Async::Ready(None))
+    }
+}
+
+/// The error the kubelet sent, if any.
+fn forward_error(message: &[u8]) -> Result<(), PortForwardError> {
+    if message.is_empty() {
+        return Ok(());
+    }
+    Err(PortForwardError {
+        message: String::from_utf8_lossy(message).trim().to_string(),
+    })
+}
+
+#[test]
+fn test_session() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use tokio::net::TcpStream;
+    use tokio::runtime::Runtime;
+
+    // A server that sends the ports, then some data, reads the input,
+    // then fails the connection.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let frame = |data: &[u8]| {
+            let mut frame = vec![0x82, data.len() as u8];
+            frame.extend_from_slice(data);
+            frame
+        };
+        for data in &[&b"\x00\x50\x00"[..], b"\x01\x50\x00", b"\x00hello"] {
+            conn.write_all(&frame(data)).unwrap();
+        }
+
+        let mut received = Vec::new();
+        let messages = loop {
+            let mut buf = [0; 1024];
+            let n = conn.read(&mut buf).unwrap();
+            received.extend_from_slice(&buf[..n]);
+            match Messages::new(&received[..]).collect().wait() {
+                Ok(ref messages) if !messages.is_empty() => break messages.clone(),
+                _ => {}
+            }
+        };
+
+        conn.write_all(&frame(b"\x01connection refused")).unwrap();
+        conn.write_all(&[0x88, 0]).unwrap();
+        messages
+    });
+
+    let mut rt = Runtime::new().unwrap();
+    let input = Box::new(stream::once(Ok(b"input".to_vec()))) as Input;
+    let output = rt
+        .block_on(TcpStream::connect(&addr).from_err().and_then(move |conn| {
+            session(conn, input)
+                .then(|r| Ok::<_, Error>(r.map_err(|e| e.to_string())))
+                .collect()
+        }))
+        .unwrap();
+    assert_eq!(
+        output,
+        vec![Ok(b"hello".to_vec()), Err("connection refused".to_string())]
+    );
+    assert_eq!(
+        server.join().unwrap(),
+        vec![Message {
+            opcode: BINARY,
+            data: b"\x00input".to_vec()
+        }]
+    );
+}
+
+#[test]
+fn test_spdy_fallback() {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use tokio::runtime::Runtime;
+
+    use super::config::ConfigContext;
+
+    fn read_request(conn: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            conn.read_exact(&mut byte).unwrap();
+            request.push(byte[0]);
+        }
+        String::from_utf8(request).unwrap().to_lowercase()
+    }
+
+    // A proxy that refuses WebSockets, in front of an apiserver that
+    // echoes the input.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let request = read_request(&mut conn);
+        assert!(
+            request.starts_with("get /api/v1/namespaces/default/pods/web-0/portforward?ports=80 ")
+        );
+        assert!(request.contains("sec-websocket-protocol: v4.channel.k8s.io"));
+        conn.write_all(
+            b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        )
+        .unwrap();
+        drop(conn);
+
+        let (mut conn, _) = listener.accept().unwrap();
+        let request = read_request(&mut conn);
+        assert!(request.contains("upgrade: spdy/3.1"));
+        assert!(request.contains("x-stream-protocol-version: portforward.k8s.io"));
+        conn.write_all(
+            b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\n\
+              Upgrade: SPDY/3.1\r\nX-Stream-Protocol-Version: portforward.k8s.io\r\n\r\n",
+        )
+        .unwrap();
+
+        // Streams 1 and 3 are error and data.
+        let mut received = Vec::new();
+        let frames = loop {
+            let mut buf = [0; 1024];
+            let n = conn.read(&mut buf).unwrap();
+            received.extend_from_slice(&buf[..n]);
+            let frames = Frames::new(&received[..]).collect().wait();
+            match frames {
+                Ok(ref frames) if frames.len() == 5 => break frames.clone(),
+                _ => {}
+            }
+        };
+        for frame in &[
+            spdy::control(spdy::PING, 0, &[0, 0, 0, 2]),
+            spdy::data(3, 0, b"echo: input"),
+            spdy::data(3, FLAG_FIN, b""),
+            spdy::data(1, FLAG_FIN, b""),
+        ] {
+            conn.write_all(frame).unwrap();
+        }
+        frames
+    });
+
+    let mut context: ConfigContext = Default::default();
+    context.cluster.server = format!("http://{}", addr);
+    let client = Client::builder()
+        .config(context)
+        .build_with_client(hyper::Client::new())
+        .unwrap();
+    let mut rt = Runtime::new().unwrap();
+    let input = Box::new(stream::once(Ok(b"input".to_vec()))) as Input;
+    let output = rt
+        .block_on(client.port_forward("default", "web-0", 80, input).collect())
+        .unwrap();
+    assert_eq!(output, vec![b"echo: input".to_vec()]);
+
+    let frames = server.join().unwrap();
+    let opened = frames
+        .iter()
+        .filter_map(|frame| match frame {
+            Frame::Control {
+                kind: spdy::SYN_STREAM,
+                data,
+                ..
+            } => spdy::stream_id(data),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(opened, vec![1, 3]);
+    let data = |stream, flags, data: &[u8]| Frame::Data {
+        stream,
+        flags,
+        data: data.to_vec(),
+    };
+    assert!(frames.contains(&data(1, FLAG_FIN, b"")));
+    assert!(frames.contains(&data(3, 0, b"input")));
+    assert!(frames.contains(&data(3, FLAG_FIN, b"")));
+}
+
+#[test]
+fn test_forward_error() {
+    assert!(forward_error(b"").is_ok());
+    let err = forward_error(b"error forwarding port 80: connection refused\n").unwrap_err();
+    assert_eq!(err.message, "error forwarding port 80: connection refused");
+}
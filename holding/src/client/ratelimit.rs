@@ -0,0 +1,89 @@
+//! Client-side rate limiting, as client-go's QPS/Burst.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use hyper::{Body, Request};
+use tokio::timer::Delay;
+
+use super::middleware::{Middleware, Next, ResponseFuture};
+
+/// A token bucket holding up to `burst` tokens, refilled at `qps`
+/// tokens per second. Every request (including the initial request of
+/// a watch) takes one token, waiting for it if the bucket is empty.
+#[derive(Debug)]
+pub struct RateLimiter {
+    qps: f64,
+    burst: f64,
+    // (tokens, when tokens was last updated).  Tokens go negative as
+    // requests queue up waiting for the bucket to refill.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// `qps` must be positive.
+    pub fn new(qps: f32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        RateLimiter {
+            qps: f64::from(qps),
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    /// Take a token, returning how long to wait before it may be used.
+    pub fn reserve(&self) -> Duration {
+        self.reserve_at(Instant::now())
+    }
+
+    fn reserve_at(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let (ref mut tokens, ref mut last) = *state;
+        if now > *last {
+            let elapsed = (now - *last).as_secs_f64();
+            *tokens = (*tokens + elapsed * self.qps).min(self.burst);
+            *last = now;
+        }
+        *tokens -= 1.0;
+        if *tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-*tokens / self.qps)
+        }
+    }
+}
+
+impl Middleware for RateLimiter {
+    fn call(&self, req: Request<Body>, next: Next) -> ResponseFuture {
+        let wait = self.reserve();
+        if wait == Duration::from_secs(0) {
+            return next.run(req);
+        }
+        debug!("Rate limited, waiting {:?}", wait);
+        // A timer error just means we don't wait.
+        Box::new(Delay::new(Instant::now() + wait).then(move |_| next.run(req)))
+    }
+}
+
+#[test]
+fn test_reserve() {
+    let limiter = RateLimiter::new(2.0, 3);
+    let start = Instant::now();
+    *limiter.state.lock().unwrap() = (3.0, start);
+
+    // The burst goes through immediately ..
+    for _ in 0..3 {
+        assert_eq!(limiter.reserve_at(start), Duration::from_secs(0));
+    }
+    // .. then requests are spaced 1/qps apart.
+    assert_eq!(limiter.reserve_at(start), Duration::from_millis(500));
+    assert_eq!(limiter.reserve_at(start), Duration::from_millis(1000));
+
+    // Refills, but never beyond the burst.
+    let later = start + Duration::from_secs(60);
+    for _ in 0..3 {
+        assert_eq!(limiter.reserve_at(later), Duration::from_secs(0));
+    }
+    assert_eq!(limiter.reserve_at(later), Duration::from_millis(500));
+}
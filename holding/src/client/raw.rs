@@ -0,0 +1,254 @@
+//! Requests for anything the typed calls don't cover yet, sent with the
+//! client's authentication, middleware and retries all the same.
+//!
+//! ```no_run
+//! # extern crate futures;
+//! # extern crate hyper;
+//! # extern crate kubernetes_holding;
+//! # use futures::Future;
+//! # use hyper::Method;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let res = client
+//!     .request_raw(Method::GET, "/readyz", &[("verbose", "true")], Vec::new())
+//!     .wait()?;
+//! println!("{}: {}", res.status, String::from_utf8_lossy(&res.body));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use failure::Error;
+use futures::{future, Future};
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use api::JSON;
+
+use super::transport::{self, Transport};
+use super::{from_json, hyper_uri, response_error, wait, Client};
+
+/// The response to `request_raw`, whatever its status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// Decompressed, if the server compressed it.
+    pub body: Vec<u8>,
+    /// The request's audit ID, as the apiserver logs it.
+    pub audit_id: String,
+    request: String,
+}
+
+impl RawResponse {
+    /// Fail with the `ApiError` the body describes, unless the status
+    /// is successful.
+    pub fn error_for_status(self) -> Result<Self, Error> {
+        if self.status.is_success() {
+            return Ok(self);
+        }
+        Err(response_error(
+            self.request,
+            self.status,
+            self.audit_id,
+            &self.headers,
+            &self.body,
+        )
+        .into())
+    }
+
+    /// Decode the body as JSON, whatever the status.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        from_json("response body", &self.body)
+    }
+}
+
+impl Client {
+    /// The URL of `path` (eg: `/apis/example.com/v1/widgets`) below the
+    /// apiserver root, with `query`.
+    fn raw_url(&self, path: &str, query: &[(&str, &str)]) -> Result<Url, Error> {
+        let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut url = self.path_url(&segments)?;
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        Ok(url)
+    }
+
+    fn raw_request(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Request<Body>, Error> {
+        let url = self.raw_url(path, query)?;
+        let mut req = Request::builder();
+        req.method(method).uri(hyper_uri(url));
+        if !body.is_empty() {
+            req.header(CONTENT_TYPE, HeaderValue::from_static(JSON));
+        }
+        Ok(req.body(Body::from(body))?)
+    }
+
+    /// Send a request for `path` below the apiserver root, with `query`
+    /// and a JSON `body` (if not empty), and read the whole response,
+    /// whatever its status.  See `RawResponse::error_for_status`.
+    ///
+    /// Path segments are escaped as needed, so a name with a `/` in it
+    /// can't be asked for.  Use `send_raw` for other content types or
+    /// headers.
+    pub fn request_raw(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> impl Future<Item = RawResponse, Error = Error> + Send {
+        let req = self.raw_request(method, path, query, body);
+        self.send_raw_request(req)
+    }
+
+    /// Send `req`, whose URI is a path (and query) below the apiserver
+    /// root, and read the whole response, whatever its status.
+    pub fn send_raw(
+        &self,
+        req: Request<Body>,
+    ) -> impl Future<Item = RawResponse, Error = Error> + Send {
+        let (mut parts, body) = req.into_parts();
+        let req = self.raw_url(parts.uri.path(), &[]).map(|mut url| {
+            url.set_query(parts.uri.query());
+            parts.uri = hyper_uri(url);
+            Request::from_parts(parts, body)
+        });
+        self.send_raw_request(req)
+    }
+
+    fn send_raw_request(
+        &self,
+        req: Result<Request<Body>, Error>,
+    ) -> impl Future<Item = RawResponse, Error = Error> + Send {
+        let client = Arc::clone(&self.client);
+        let f = future::result(req).and_then(move |req| {
+            let request = transport::describe(req.method(), req.uri());
+            Transport::send(client, req).map(move |(status, audit_id, headers, body)| RawResponse {
+                status,
+                headers,
+                body: body.to_vec(),
+                audit_id,
+                request,
+            })
+        });
+        match self.timeout {
+            Some(after) => future::Either::A(wait::timeout(f, after)),
+            None => future::Either::B(f),
+        }
+    }
+
+    /// Like `request_raw`, but the response body is streamed, and it
+    /// is not retried or timed out.
+    pub fn request_raw_stream(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> impl Future<Item = Response<Body>, Error = Error> + Send {
+        let client = Arc::clone(&self.client);
+        future::result(self.raw_request(method, path, query, body)).and_then(move |mut req| {
+            let id = transport::request_id(req.headers_mut());
+            let failed = format!(
+                "{} failed (request {})",
+                transport::describe(req.method(), req.uri()),
+                id
+            );
+            client.request(req).map_err(|e| e.context(failed).into())
+        })
+    }
+}
+
+#[test]
+fn test_request_raw() {
+    use futures::Stream;
+    use hyper::header::ACCEPT;
+    use serde_json::Value;
+
+    use api::meta::GroupVersionResource;
+    use k8sclient::error::ApiError;
+
+    use super::fake::FakeClient;
+
+    const PODS: GroupVersionResource<'static> = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "pods",
+    };
+
+    let client = FakeClient::new();
+    let pod = json!({"metadata": {"name": "web", "namespace": "ns"}});
+    let res = client
+        .request_raw(
+            Method::POST,
+            "/api/v1/namespaces/ns/pods",
+            &[("fieldManager", "test")],
+            serde_json::to_vec(&pod).unwrap(),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(res.status, StatusCode::CREATED);
+    assert!(client.server().get(&PODS, Some("ns"), "web").is_some());
+
+    let res = client
+        .request_raw(
+            Method::GET,
+            "api/v1/namespaces/ns/pods/web",
+            &[],
+            Vec::new(),
+        )
+        .wait()
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let got: Value = res.json().unwrap();
+    assert_eq!(got["metadata"]["name"], "web");
+
+    // Unsuccessful responses are still responses.
+    let res = client
+        .request_raw(
+            Method::GET,
+            "/api/v1/namespaces/ns/pods/db",
+            &[],
+            Vec::new(),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let err = res.error_for_status().unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_not_found());
+
+    let req = Request::get("/api/v1/namespaces/ns/pods?limit=1")
+        .header(ACCEPT, JSON)
+        .body(Body::empty())
+        .unwrap();
+    let res = client.send_raw(req).wait().unwrap();
+    let list: Value = res.error_for_status().unwrap().json().unwrap();
+    assert_eq!(list["items"].as_array().unwrap().len(), 1);
+
+    let res = client
+        .request_raw_stream(
+            Method::GET,
+            "/api/v1/namespaces/ns/pods/web",
+            &[],
+            Vec::new(),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.into_body().concat2().wait().unwrap();
+    let got: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(got["metadata"]["name"], "web");
+}
@@ -0,0 +1,312 @@
+//! Recording and replaying apiserver interactions.
+//!
+//! A `Recorder` saves each request the client sends, and the response
+//! it got, to a fixture file.  A `Replayer` later answers the same
+//! requests from that file, without a cluster, so that tests of code
+//! built on `Client` can check it still makes the same requests and
+//! still handles the responses.
+//!
+//! Both are `Middleware`, and go last in the stack:
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate kubernetes_holding;
+//! # use std::sync::Arc;
+//! # use kubernetes_holding::client::Client;
+//! # use kubernetes_holding::client::record::{Recorder, Replayer};
+//! # fn main() -> Result<(), failure::Error> {
+//! // Against a real cluster:
+//! let recorder = Arc::new(Recorder::new());
+//! let client = Client::builder().middleware(recorder.clone()).build()?;
+//! // ... exercise client ...
+//! recorder.save("tests/fixtures/scale.json")?;
+//!
+//! // Later, in CI:
+//! let replayer = Arc::new(Replayer::load("tests/fixtures/scale.json")?);
+//! let client = Client::builder().middleware(replayer.clone()).build()?;
+//! // ... exercise client ...
+//! assert_eq!(replayer.remaining(), 0);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Watch responses are recorded as they stream, so the fixture has
+//! whatever events had arrived when it was saved; on replay the watch
+//! ends after the last of them.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use failure::{Error, ResultExt};
+use futures::{Future, Stream};
+use hyper::header::{HeaderValue, ACCEPT_ENCODING, CONTENT_TYPE};
+use hyper::{Body, Request, Response};
+use serde_json::{self, Value};
+
+use super::middleware::{Middleware, Next, ResponseFuture};
+
+/// A request and its response, as saved in a fixture.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interaction {
+    pub method: String,
+    /// The path and query; the server is not recorded.
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub request_body: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub response_body: String,
+}
+
+impl Interaction {
+    /// Whether this is a recording of `req` (which has `body`).
+    /// JSON bodies are compared as JSON.
+    fn matches(&self, req: &Request<Body>, body: &[u8]) -> bool {
+        if self.method != req.method().as_str() || self.uri != path_and_query(req) {
+            return false;
+        }
+        match (
+            serde_json::from_str::<Value>(&self.request_body),
+            serde_json::from_slice::<Value>(body),
+        ) {
+            (Ok(recorded), Ok(sent)) => recorded == sent,
+            _ => self.request_body.as_bytes() == body,
+        }
+    }
+}
+
+fn path_and_query(req: &Request<Body>) -> String {
+    req.uri().path_and_query().map_or_else(
+        || req.uri().path().to_string(),
+        |pq| pq.as_str().to_string(),
+    )
+}
+
+/// Buffer the request body, so that it can be both saved and sent.
+fn read_request(req: Request<Body>) -> impl Future<Item = (Request<Body>, Vec<u8>), Error = Error> {
+    let (parts, body) = req.into_parts();
+    body.concat2().from_err().map(move |body| {
+        let body = body.to_vec();
+        (Request::from_parts(parts, Body::from(body.clone())), body)
+    })
+}
+
+/// Records interactions, for saving as a fixture.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    interactions: Arc<Mutex<Vec<Recording>>>,
+}
+
+#[derive(Debug)]
+struct Recording {
+    interaction: Interaction,
+    // Kept as bytes until it is saved, as it arrives in chunks that may
+    // split UTF-8 characters.
+    response_body: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Everything recorded so far, in the order the responses arrived.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| Interaction {
+                response_body: String::from_utf8_lossy(&r.response_body).into_owned(),
+                ..r.interaction.clone()
+            })
+            .collect()
+    }
+
+    /// Write everything recorded so far to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|e| format!("Unable to create {}: {}", path.display(), e))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &self.interactions())?;
+        Ok(())
+    }
+}
+
+impl Middleware for Recorder {
+    fn call(&self, mut req: Request<Body>, next: Next) -> ResponseFuture {
+        // Keep the fixture readable.
+        req.headers_mut()
+            .insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        let interactions = Arc::clone(&self.interactions);
+        Box::new(read_request(req).and_then(move |(req, request_body)| {
+            let method = req.method().to_string();
+            let uri = path_and_query(&req);
+            next.run(req).map(move |res| {
+                let (parts, body) = res.into_parts();
+                let interaction = Interaction {
+                    method,
+                    uri,
+                    request_body: String::from_utf8_lossy(&request_body).into_owned(),
+                    status: parts.status.as_u16(),
+                    content_type: parts
+                        .headers
+                        .get(CONTENT_TYPE)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string),
+                    response_body: String::new(),
+                };
+                let index = {
+                    let mut interactions = interactions.lock().unwrap();
+                    interactions.push(Recording {
+                        interaction,
+                        response_body: Vec::new(),
+                    });
+                    interactions.len() - 1
+                };
+                let body = body.inspect(move |chunk| {
+                    interactions.lock().unwrap()[index]
+                        .response_body
+                        .extend_from_slice(chunk);
+                });
+                Response::from_parts(parts, Body::wrap_stream(body))
+            })
+        }))
+    }
+}
+
+/// Answers requests from a fixture saved by a `Recorder`.
+///
+/// Each request is answered by the first recorded interaction with the
+/// same method, path, query and body that hasn't been used yet.
+/// Requests with no such recording fail.
+#[derive(Debug)]
+pub struct Replayer {
+    interactions: Arc<Mutex<Vec<(Interaction, bool)>>>,
+}
+
+impl Replayer {
+    pub fn new(interactions: Vec<Interaction>) -> Self {
+        Replayer {
+            interactions: Arc::new(Mutex::new(
+                interactions.into_iter().map(|i| (i, false)).collect(),
+            )),
+        }
+    }
+
+    /// Read a fixture written by `Recorder::save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|e| format!("Unable to open {}: {}", path.display(), e))?;
+        let interactions = serde_json::from_reader(BufReader::new(file))
+            .with_context(|e| format!("Unable to parse {}: {}", path.display(), e))?;
+        Ok(Self::new(interactions))
+    }
+
+    /// How many recorded interactions haven't been replayed.
+    pub fn remaining(&self) -> usize {
+        self.interactions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, used)| !used)
+            .count()
+    }
+}
+
+impl Middleware for Replayer {
+    fn call(&self, req: Request<Body>, _next: Next) -> ResponseFuture {
+        let interactions = Arc::clone(&self.interactions);
+        Box::new(read_request(req).and_then(move |(req, body)| {
+            let mut interactions = interactions.lock().unwrap();
+            let found = interactions
+                .iter_mut()
+                .find(|(i, used)| !used && i.matches(&req, &body));
+            let (interaction, used) = match found {
+                Some(found) => found,
+                None => {
+                    return Err(format_err!(
+                        "No recorded response for {} {}",
+                        req.method(),
+                        path_and_query(&req)
+                    ))
+                }
+            };
+            *used = true;
+            let mut res = Response::builder();
+            res.status(interaction.status);
+            if let Some(ref content_type) = interaction.content_type {
+                res.header(CONTENT_TYPE, content_type.as_str());
+            }
+            Ok(res.body(Body::from(interaction.response_body.clone()))?)
+        }))
+    }
+}
+
+#[test]
+fn test_record_replay() {
+    use std::env;
+    use std::process;
+
+    use api::meta::v1::{GetOptions, ListOptions, WatchEvent};
+    use api::meta::GroupVersionResource;
+
+    use super::config::ConfigContext;
+    use super::fake::FakeApiServer;
+    use super::Client;
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let client = |middleware: Vec<Arc<dyn Middleware>>| {
+        let mut config: ConfigContext = Default::default();
+        config.cluster.server = "http://fake.invalid".to_string();
+        let mut builder = Client::builder().config(config);
+        for m in middleware {
+            builder = builder.middleware(m);
+        }
+        builder.build_with_client(hyper::Client::new()).unwrap()
+    };
+    let exercise = |client: &Client| -> Result<(Value, Vec<WatchEvent>), Error> {
+        let cm = json!({"metadata": {"name": "a", "namespace": "ns"}, "data": {"k": "v"}});
+        let _: Value = client.create(&gvr, &cm, GetOptions::default()).wait()?;
+        let got = client
+            .get(&gvr, Some("ns"), "a", GetOptions::default())
+            .wait()?;
+        let events = client
+            .watch_list(&gvr, Some("ns"), ListOptions::default())
+            .take(1)
+            .collect()
+            .wait()?;
+        Ok((got, events))
+    };
+
+    let recorder = Arc::new(Recorder::new());
+    let server = FakeApiServer::new();
+    let recorded = exercise(&client(vec![recorder.clone(), Arc::new(server.clone())])).unwrap();
+    server.close_watches();
+    let interactions = recorder.interactions();
+    assert_eq!(interactions.len(), 3);
+    assert_eq!(interactions[0].method, "POST");
+    assert_eq!(interactions[0].uri, "/api/v1/namespaces/ns/configmaps");
+    assert_eq!(interactions[0].status, 201);
+    assert!(interactions[2].response_body.contains("ADDED"));
+
+    let path = env::temp_dir().join(format!("kubernetes-record-{}.json", process::id()));
+    recorder.save(&path).unwrap();
+    let replayer = Arc::new(Replayer::load(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    let replayed = exercise(&client(vec![replayer.clone()])).unwrap();
+    assert_eq!(replayed, recorded);
+    assert_eq!(replayer.remaining(), 0);
+
+    // Everything has been used up.
+    assert!(exercise(&client(vec![replayer])).is_err());
+}
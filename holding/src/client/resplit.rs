@@ -1,5 +1,13 @@
+use failure::Error;
 use futures::{stream, Async, Poll, Sink, Stream};
-use std::mem;
+
+/// A line (or other separated item) grew past the limit before its
+/// separator arrived.
+#[derive(Debug, Clone, PartialEq, Fail)]
+#[fail(display = "Line longer than the limit of {} bytes", max)]
+pub struct LineTooLong {
+    pub max: usize,
+}
 
 #[derive(Debug)]
 #[must_use = "streams do nothing unless polled"]
@@ -9,13 +17,22 @@ where
     S::Item: IntoIterator<Item = I>,
     P: FnMut(&I) -> bool,
 {
+    // Kept across items, so that its allocation is reused.
     buf: Vec<I>,
-    err: Option<S::Error>,
+    // The length of the prefix of `buf` known not to hold a separator.
+    scanned: usize,
+    max: usize,
+    // After `LineTooLong`.
+    failed: bool,
+    err: Option<Error>,
     stream: stream::Fuse<S>,
     predicate: P,
 }
 
-pub fn new<S, I, P>(s: S, predicate: P) -> ReSplit<S, I, P>
+/// Split `s` after each item matching `predicate`, failing with
+/// `LineTooLong` (and then ending) if more than `max` items arrive
+/// without one.
+pub fn new<S, I, P>(s: S, predicate: P, max: usize) -> ReSplit<S, I, P>
 where
     S: Stream,
     S::Item: IntoIterator<Item = I>,
@@ -23,6 +40,9 @@ where
 {
     ReSplit {
         buf: Vec::new(),
+        scanned: 0,
+        max,
+        failed: false,
         err: None,
         stream: s.fuse(),
         predicate: predicate,
@@ -56,25 +76,41 @@ where
     S: Stream,
     S::Item: IntoIterator<Item = I>,
     P: FnMut(&I) -> bool,
+    Error: From<S::Error>,
 {
     type Item = Vec<I>;
-    type Error = S::Error;
+    type Error = Error;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.failed {
+            return Ok(Async::Ready(None));
+        }
         loop {
-            if let Some(i) = self.buf.iter().position(&mut self.predicate) {
+            let found = self.buf[self.scanned..]
+                .iter()
+                .position(&mut self.predicate);
+            let end = found.map_or(self.buf.len(), |i| self.scanned + i + 1);
+            if found.is_some() && end <= self.max {
                 // found separator, so return prefix (including separator)
-                let tail = self.buf.split_off(i + 1);
-                let head = mem::replace(&mut self.buf, tail);
-                return Ok(Some(head).into());
+                self.scanned = 0;
+                return Ok(Some(self.buf.drain(..end).collect()).into());
+            }
+            self.scanned = self.buf.len();
+
+            if end > self.max {
+                // Give up on the rest of the stream, rather than
+                // returning the remainder of the line as if it was one.
+                self.buf = Vec::new();
+                self.failed = true;
+                return Err(LineTooLong { max: self.max }.into());
             }
 
             if let Some(err) = self.err.take() {
-                if self.buf.len() > 0 {
+                if !self.buf.is_empty() {
                     // flush any buffer first
                     self.err = Some(err);
-                    let buf = mem::replace(&mut self.buf, Vec::new());
-                    return Ok(Some(buf).into());
+                    self.scanned = 0;
+                    return Ok(Some(self.buf.drain(..).collect()).into());
                 }
                 return Err(err);
             }
@@ -89,18 +125,47 @@ where
 
                 Ok(Async::Ready(None)) => {
                     // Underlying stream ran out of values, so return what we have
-                    return if self.buf.len() > 0 {
-                        let buf = mem::replace(&mut self.buf, Vec::new());
-                        Ok(Some(buf).into())
+                    self.scanned = 0;
+                    return if !self.buf.is_empty() {
+                        Ok(Some(self.buf.drain(..).collect()).into())
                     } else {
                         Ok(Async::Ready(None))
                     };
                 }
 
                 Err(e) => {
-                    self.err = Some(e);
+                    self.err = Some(e.into());
                 }
             }
         }
     }
 }
+
+#[test]
+fn test_resplit() {
+    use futures::Future;
+
+    let chunks: Vec<Result<Vec<u8>, Error>> = vec![
+        Ok(b"one\ntw".to_vec()),
+        Ok(b"o\n".to_vec()),
+        Ok(b"three".to_vec()),
+    ];
+    let lines = new(stream::iter_result(chunks), |&c| c == b'\n', 8)
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(
+        lines,
+        vec![b"one\n".to_vec(), b"two\n".to_vec(), b"three".to_vec()]
+    );
+
+    let chunks: Vec<Result<Vec<u8>, Error>> = vec![
+        Ok(b"short\nmuch too".to_vec()),
+        Ok(b" long\nmore\n".to_vec()),
+    ];
+    let mut lines = new(stream::iter_result(chunks), |&c| c == b'\n', 8).wait();
+    assert_eq!(lines.next().unwrap().unwrap(), b"short\n");
+    let err = lines.next().unwrap().unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&LineTooLong { max: 8 }));
+    assert!(lines.next().is_none());
+}
@@ -0,0 +1,530 @@
+//! Resolve between kinds and resources using discovery information.
+
+use failure::Error;
+use futures::future::{self, Either};
+use futures::Future;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use api::meta::v1::APIResource;
+use api::meta::{GroupResource, GroupVersionKind, GroupVersionResource};
+
+use super::discovery::{fetch_discovery, Discovery};
+use super::Client;
+
+#[derive(Debug, Fail)]
+#[fail(display = "no resource matches {}", what)]
+pub struct NoMatchError {
+    pub what: String,
+}
+
+/// A single resource served by the apiserver, and the kind it serves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceMapping {
+    pub group: String,
+    pub version: String,
+    pub resource: String,
+    pub kind: String,
+    pub singular: String,
+    pub short_names: Vec<String>,
+    pub namespaced: bool,
+    pub verbs: Vec<String>,
+}
+
+impl ResourceMapping {
+    pub fn gvr(&self) -> GroupVersionResource<'_> {
+        GroupVersionResource {
+            group: &self.group,
+            version: &self.version,
+            resource: &self.resource,
+        }
+    }
+
+    pub fn gvk(&self) -> GroupVersionKind<'_> {
+        GroupVersionKind {
+            group: &self.group,
+            version: &self.version,
+            kind: &self.kind,
+        }
+    }
+}
+
+/// Indexed discovery results.
+#[derive(Debug)]
+struct Mappings {
+    // In discovery order, core group first.
+    resources: Vec<ResourceMapping>,
+    // group -> preferred version
+    preferred: HashMap<String, String>,
+    // From the client's disk cache, so perhaps out of date.
+    cached: bool,
+    fetched: Instant,
+}
+
+impl Mappings {
+    fn new(d: Discovery) -> Self {
+        let mut preferred: HashMap<_, _> = d
+            .groups
+            .groups
+            .iter()
+            .filter_map(|g| {
+                g.preferred_version
+                    .as_ref()
+                    .or_else(|| g.versions.first())
+                    .map(|v| (g.name.clone(), v.version.clone()))
+            })
+            .collect();
+        preferred.insert("".into(), "v1".into());
+
+        let resources = d
+            .resources
+            .into_iter()
+            .flat_map(|list| {
+                let gv = list.group_version;
+                list.resources
+                    .into_iter()
+                    // Skip subresources, eg: "pods/log"
+                    .filter(|r| !r.name.contains('/'))
+                    .map(move |r| mapping_for(&gv, r))
+            })
+            .collect();
+
+        Mappings {
+            resources,
+            preferred,
+            cached: d.cached,
+            fetched: Instant::now(),
+        }
+    }
+
+    fn is_preferred(&self, m: &ResourceMapping) -> bool {
+        self.preferred.get(&m.group) == Some(&m.version)
+    }
+
+    /// Pick the preferred version from a set of candidates, falling
+    /// back to the first (in discovery order).
+    fn best<'a, I>(&'a self, candidates: I) -> Option<&'a ResourceMapping>
+    where
+        I: Iterator<Item = &'a ResourceMapping>,
+    {
+        let candidates: Vec<_> = candidates.collect();
+        candidates
+            .iter()
+            .find(|m| self.is_preferred(m))
+            .or_else(|| candidates.first())
+            .cloned()
+    }
+
+    /// Every resource once, in its group's preferred version if that
+    /// serves it.
+    fn preferred(&self) -> Vec<ResourceMapping> {
+        let (preferred, rest): (Vec<_>, Vec<_>) =
+            self.resources.iter().partition(|m| self.is_preferred(m));
+        let mut seen = HashSet::new();
+        preferred
+            .into_iter()
+            .chain(rest)
+            .filter(|m| seen.insert((&m.group, &m.resource)))
+            .cloned()
+            .collect()
+    }
+
+    fn kind_to_resource(&self, gvk: &GroupVersionKind) -> Option<&ResourceMapping> {
+        self.best(self.resources.iter().filter(|m| {
+            m.group == gvk.group
+                && m.kind == gvk.kind
+                && (gvk.version.is_empty() || m.version == gvk.version)
+        }))
+    }
+
+    fn resource_to_kind(&self, gvr: &GroupVersionResource) -> Option<&ResourceMapping> {
+        self.best(self.resources.iter().filter(|m| {
+            m.group == gvr.group
+                && m.resource == gvr.resource
+                && (gvr.version.is_empty() || m.version == gvr.version)
+        }))
+    }
+
+    /// Resolve a kubectl-style resource argument: plural, singular,
+    /// short name or kind, optionally qualified by ".group".
+    fn lookup(&self, name: &str) -> Option<&ResourceMapping> {
+        let gr = GroupResource::from_str(name).ok()?;
+        let matches = |m: &ResourceMapping, r: &str| {
+            m.resource == r
+                || m.singular == r
+                || m.short_names.iter().any(|s| s == r)
+                || m.kind.to_lowercase() == r.to_lowercase()
+        };
+        let candidates = |r: &str, group: Option<&str>| -> Option<&ResourceMapping> {
+            // Core group wins any ambiguity, as in kubectl
+            let mut groups: Vec<&str> = match group {
+                Some(g) => vec![g],
+                None => self.resources.iter().map(|m| m.group.as_str()).collect(),
+            };
+            groups.dedup();
+            groups.into_iter().find_map(|g| {
+                self.best(
+                    self.resources
+                        .iter()
+                        .filter(|m| m.group == g && matches(m, r)),
+                )
+            })
+        };
+
+        // "deployments.apps" is ambiguous with resources containing
+        // dots, so also try the unqualified name.
+        candidates(gr.resource, Some(gr.group)).or_else(|| candidates(name, None))
+    }
+}
+
+fn mapping_for(group_version: &str, r: APIResource) -> ResourceMapping {
+    let (group, version) = match group_version.find('/') {
+        Some(i) => (&group_version[..i], &group_version[i + 1..]),
+        None => ("", group_version),
+    };
+    ResourceMapping {
+        group: r.group.unwrap_or_else(|| group.into()),
+        version: r.version.unwrap_or_else(|| version.into()),
+        singular: if r.singular_name.is_empty() {
+            r.kind.to_lowercase()
+        } else {
+            r.singular_name
+        },
+        resource: r.name,
+        kind: r.kind,
+        short_names: r.short_names,
+        namespaced: r.namespaced,
+        verbs: r.verbs,
+    }
+}
+
+/// Maps kinds to resources (and back) using discovery results,
+/// cached in memory.
+///
+/// The cache is populated on first use, from the client's disk cache
+/// if it has one.  A lookup that fails to match refreshes the cache
+/// once before giving up, so newly installed CRDs are found, unless
+/// it was refreshed less than `min_refresh_interval` ago: looking up
+/// kinds that don't exist doesn't repeat discovery every time.
+#[derive(Debug, Clone)]
+pub struct RESTMapper {
+    client: Client,
+    cache: Arc<RwLock<Option<Arc<Mappings>>>>,
+    min_refresh_interval: Duration,
+}
+
+/// How long after discovery a failed lookup must wait before it can
+/// cause discovery again, by default.
+pub const DEFAULT_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+impl RESTMapper {
+    pub fn new(client: Client) -> Self {
+        RESTMapper {
+            client,
+            cache: Arc::new(RwLock::new(None)),
+            min_refresh_interval: DEFAULT_MIN_REFRESH_INTERVAL,
+        }
+    }
+
+    /// Refresh for a failed lookup only this long after the last
+    /// discovery, rather than `DEFAULT_MIN_REFRESH_INTERVAL`.  Use
+    /// `invalidate` to refresh sooner, eg: after installing a CRD.
+    pub fn min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+
+    /// Discard cached discovery results, including any in the client's
+    /// disk cache.
+    pub fn invalidate(&self) {
+        *self.cache.write().unwrap() = None;
+        if let Some(disk) = self.client.disk_cache() {
+            let server = &self.client.config.cluster.server;
+            if let Err(e) = disk.invalidate(server) {
+                debug!(
+                    "Unable to invalidate cached discovery for {}: {}",
+                    server, e
+                );
+            }
+        }
+    }
+
+    /// Find the resource serving a kind.  An empty version selects
+    /// the group's preferred version.
+    pub fn kind_to_resource(
+        &self,
+        gvk: &GroupVersionKind,
+    ) -> impl Future<Item = ResourceMapping, Error = Error> + Send {
+        let gvk = (
+            gvk.group.to_string(),
+            gvk.version.to_string(),
+            gvk.kind.to_string(),
+        );
+        self.resolve(format!("{:?}", gvk), move |m| {
+            m.kind_to_resource(&GroupVersionKind {
+                group: &gvk.0,
+                version: &gvk.1,
+                kind: &gvk.2,
+            })
+            .cloned()
+        })
+    }
+
+    /// Find the kind served by a resource.  An empty version selects
+    /// the group's preferred version.
+    pub fn resource_to_kind(
+        &self,
+        gvr: &GroupVersionResource,
+    ) -> impl Future<Item = ResourceMapping, Error = Error> + Send {
+        let gvr = (
+            gvr.group.to_string(),
+            gvr.version.to_string(),
+            gvr.resource.to_string(),
+        );
+        self.resolve(format!("{:?}", gvr), move |m| {
+            m.resource_to_kind(&GroupVersionResource {
+                group: &gvr.0,
+                version: &gvr.1,
+                resource: &gvr.2,
+            })
+            .cloned()
+        })
+    }
+
+    /// Resolve a kubectl-style resource name, eg: "deploy",
+    /// "deployments.apps" or "Pod".
+    pub fn lookup(&self, name: &str) -> impl Future<Item = ResourceMapping, Error = Error> + Send {
+        let name = name.to_string();
+        self.resolve(name.clone(), move |m| m.lookup(&name).cloned())
+    }
+
+    /// Every resource the server offers, each in its group's preferred
+    /// version, or the first that serves it if that doesn't.
+    pub fn resources(&self) -> impl Future<Item = Vec<ResourceMapping>, Error = Error> + Send {
+        let cached = self.cache.read().unwrap().clone();
+        match cached {
+            Some(mappings) => Either::A(future::ok(mappings.preferred())),
+            None => Either::B(self.refresh(true).map(|m| m.preferred())),
+        }
+    }
+
+    /// Discover everything again, from the client's disk cache if
+    /// `reuse` is set and it has a fresh copy.
+    fn refresh(&self, reuse: bool) -> impl Future<Item = Arc<Mappings>, Error = Error> + Send {
+        let cache = Arc::clone(&self.cache);
+        fetch_discovery(self.client.clone(), reuse).map(move |d| {
+            let mappings = Arc::new(Mappings::new(d));
+            *cache.write().unwrap() = Some(Arc::clone(&mappings));
+            mappings
+        })
+    }
+
+    fn resolve<F>(
+        &self,
+        what: String,
+        f: F,
+    ) -> impl Future<Item = ResourceMapping, Error = Error> + Send
+    where
+        F: Fn(&Mappings) -> Option<ResourceMapping> + Send + 'static,
+    {
+        let cached = self.cache.read().unwrap().clone();
+        if let Some(found) = cached.as_ref().and_then(|m| f(m)) {
+            return Either::A(future::ok(found));
+        }
+        let recent = cached
+            .as_ref()
+            .is_some_and(|m| !m.cached && m.fetched.elapsed() < self.min_refresh_interval);
+        if recent {
+            return Either::A(future::err(NoMatchError { what }.into()));
+        }
+        // Only the first lookup may use the disk cache, which is asked
+        // again if it has nothing matching.
+        let mapper = self.clone();
+        Either::B(
+            self.refresh(cached.is_none())
+                .and_then(move |mappings| match f(&mappings) {
+                    Some(found) => Either::A(future::ok(found)),
+                    None if mappings.cached => {
+                        Either::B(mapper.refresh(false).and_then(move |mappings| {
+                            f(&mappings).ok_or_else(|| NoMatchError { what }.into())
+                        }))
+                    }
+                    None => Either::A(future::err(NoMatchError { what }.into())),
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mappings;
+    use api::meta::{GroupVersionKind, GroupVersionResource};
+    use client::discovery::Discovery;
+    use serde_json;
+
+    fn mappings() -> Mappings {
+        let d = Discovery {
+            groups: serde_json::from_value(json!({
+                "groups": [{
+                    "name": "apps",
+                    "versions": [
+                        {"groupVersion": "apps/v1", "version": "v1"},
+                        {"groupVersion": "apps/v1beta2", "version": "v1beta2"},
+                    ],
+                    "preferredVersion": {"groupVersion": "apps/v1", "version": "v1"},
+                }],
+            }))
+            .unwrap(),
+            resources: vec![
+                serde_json::from_value(json!({
+                    "groupVersion": "v1",
+                    "resources": [
+                        {"name": "pods", "singularName": "", "namespaced": true,
+                         "kind": "Pod", "shortNames": ["po"]},
+                        {"name": "pods/log", "singularName": "", "namespaced": true,
+                         "kind": "Pod"},
+                    ],
+                }))
+                .unwrap(),
+                serde_json::from_value(json!({
+                    "groupVersion": "apps/v1beta2",
+                    "resources": [
+                        {"name": "deployments", "singularName": "", "namespaced": true,
+                         "kind": "Deployment", "shortNames": ["deploy"]},
+                    ],
+                }))
+                .unwrap(),
+                serde_json::from_value(json!({
+                    "groupVersion": "apps/v1",
+                    "resources": [
+                        {"name": "deployments", "singularName": "", "namespaced": true,
+                         "kind": "Deployment", "shortNames": ["deploy"]},
+                    ],
+                }))
+                .unwrap(),
+            ],
+            cached: false,
+        };
+        Mappings::new(d)
+    }
+
+    #[test]
+    fn kind_to_resource() {
+        let m = mappings();
+        let gvk = GroupVersionKind {
+            group: "apps",
+            version: "",
+            kind: "Deployment",
+        };
+        let r = m.kind_to_resource(&gvk).unwrap();
+        assert_eq!(r.resource, "deployments");
+        assert_eq!(r.version, "v1");
+
+        let gvk = GroupVersionKind {
+            version: "v1beta2",
+            ..gvk
+        };
+        assert_eq!(m.kind_to_resource(&gvk).unwrap().version, "v1beta2");
+    }
+
+    #[test]
+    fn resource_to_kind() {
+        let m = mappings();
+        let gvr = GroupVersionResource {
+            group: "",
+            version: "v1",
+            resource: "pods",
+        };
+        let r = m.resource_to_kind(&gvr).unwrap();
+        assert_eq!(r.kind, "Pod");
+        assert!(r.namespaced);
+        assert_eq!(r.singular, "pod");
+    }
+
+    #[test]
+    fn lookup() {
+        let m = mappings();
+        assert_eq!(m.lookup("po").unwrap().resource, "pods");
+        assert_eq!(m.lookup("pod").unwrap().resource, "pods");
+        assert_eq!(m.lookup("Pod").unwrap().resource, "pods");
+        assert_eq!(m.lookup("deploy").unwrap().version, "v1");
+        assert_eq!(m.lookup("deployments.apps").unwrap().group, "apps");
+        assert!(m.lookup("deployments.extensions").is_none());
+        assert!(m.lookup("pods/log").is_none());
+    }
+
+    #[test]
+    fn preferred() {
+        let gvrs: Vec<_> = mappings()
+            .preferred()
+            .into_iter()
+            .map(|m| format!("{}/{}/{}", m.group, m.version, m.resource))
+            .collect();
+        assert_eq!(gvrs, vec!["/v1/pods", "apps/v1/deployments"]);
+    }
+
+    #[test]
+    fn refresh_interval() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use futures::{future, Future};
+        use hyper::{Body, Request, Response};
+
+        use client::config::ConfigContext;
+        use client::middleware::{Middleware, Next, ResponseFuture};
+        use client::Client;
+
+        use super::RESTMapper;
+
+        /// A server with only pods, counting discovery requests.
+        #[derive(Debug, Default)]
+        struct Server(AtomicUsize);
+
+        impl Middleware for Server {
+            fn call(&self, req: Request<Body>, _next: Next) -> ResponseFuture {
+                let body = match req.uri().path() {
+                    "/api" => {
+                        self.0.fetch_add(1, Ordering::SeqCst);
+                        json!({"versions": ["v1"]})
+                    }
+                    "/apis" => json!({"groups": []}),
+                    "/api/v1" => json!({"groupVersion": "v1", "resources": [
+                        {"name": "pods", "singularName": "pod", "namespaced": true, "kind": "Pod"},
+                    ]}),
+                    path => panic!("unexpected request for {}", path),
+                };
+                let body = serde_json::to_vec(&body).unwrap();
+                Box::new(future::ok(Response::new(body.into())))
+            }
+        }
+
+        let server = Arc::new(Server::default());
+        let mut config: ConfigContext = Default::default();
+        config.cluster.server = "http://fake.invalid".to_string();
+        let client = Client::builder()
+            .config(config)
+            .middleware(server.clone())
+            .build_with_client(hyper::Client::new())
+            .unwrap();
+        let discoveries = || server.0.swap(0, Ordering::SeqCst);
+
+        let mapper = RESTMapper::new(client);
+        assert_eq!(mapper.lookup("pod").wait().unwrap().kind, "Pod");
+        assert_eq!(discoveries(), 1);
+        // Unknown kinds don't each cause discovery.
+        for kind in &["widget", "gadget", "widget"] {
+            assert!(mapper.lookup(kind).wait().is_err());
+        }
+        assert_eq!(discoveries(), 0);
+        mapper.invalidate();
+        assert!(mapper.lookup("widget").wait().is_err());
+        assert_eq!(discoveries(), 1);
+
+        let mapper = mapper.min_refresh_interval(Duration::from_secs(0));
+        assert!(mapper.lookup("widget").wait().is_err());
+        assert_eq!(discoveries(), 1);
+    }
+}
@@ -0,0 +1,284 @@
+//! Automatic retries for transient failures.
+
+use std::cmp;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use hyper::header::{HeaderMap, RETRY_AFTER};
+use hyper::{Method, StatusCode};
+
+use super::error_response;
+
+/// When and how often to retry a request.
+///
+/// Idempotent requests (`GET`, `HEAD`, `PUT`, `DELETE`) are retried
+/// after a connection error, `429 Too Many Requests`, or a 5xx response
+/// other than `501 Not Implemented`.  Other requests are only retried
+/// after a `429`, which API Priority and Fairness sends before the
+/// request is handled at all.
+/// Retries back off exponentially with jitter, except when the server
+/// says how long to wait via `Retry-After` or the Status
+/// `retryAfterSeconds` detail, which is still capped at `max_backoff`.
+///
+/// A retried `PUT` or `DELETE` whose first attempt did reach the
+/// server may then fail with a conflict or not-found error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt.
+    pub max_retries: u32,
+    /// Backoff before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on the (pre-jitter) backoff, and on waits the
+    /// server asks for.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether requests with this method may be retried.
+    pub fn is_idempotent(method: &Method) -> bool {
+        *method == Method::GET
+            || *method == Method::HEAD
+            || *method == Method::PUT
+            || *method == Method::DELETE
+    }
+
+    /// Whether a response with this status is worth retrying.
+    pub fn is_retryable(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS
+            || (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+    }
+
+    /// The delay before retry number `attempt` (counting from 0): the
+    /// exponential backoff, capped at `max_backoff`, scaled by a random
+    /// factor in [0.5, 1).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |b| cmp::min(b, self.max_backoff));
+        backoff / 2 + jitter(backoff / 2)
+    }
+
+    /// The delay before retry number `attempt`: what the server asked
+    /// for, if anything, up to `max_backoff`, or else `backoff`.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        match retry_after {
+            Some(after) => cmp::min(after, self.max_backoff),
+            None => self.backoff(attempt),
+        }
+    }
+}
+
+/// The longest wait a server can ask for that is honoured where there
+/// is no `RetryPolicy`, eg: between evictions in a drain.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// A random duration in [0, max).
+fn jitter(max: Duration) -> Duration {
+    // Each RandomState is freshly (randomly) keyed, which is plenty
+    // for spreading out retries.
+    let r = RandomState::new().build_hasher().finish();
+    let nanos = max.as_secs() * 1_000_000_000 + u64::from(max.subsec_nanos());
+    if nanos == 0 {
+        return max;
+    }
+    let n = r % nanos;
+    Duration::new(n / 1_000_000_000, (n % 1_000_000_000) as u32)
+}
+
+/// How long the server asked us to wait, from the `Retry-After` header
+/// (in seconds) or the Status body.
+pub(crate) fn retry_after(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Option<Duration> {
+    retry_after_header(headers).or_else(|| error_response(status, body).retry_after())
+}
+
+/// The `Retry-After` header, in seconds.  (The apiserver never sends
+/// the HTTP-date form.)  This is whatever the server sent, so callers
+/// must cap it before waiting.
+pub(crate) fn retry_after_header(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+#[test]
+fn test_retryable() {
+    assert!(RetryPolicy::is_idempotent(&Method::GET));
+    assert!(RetryPolicy::is_idempotent(&Method::DELETE));
+    assert!(!RetryPolicy::is_idempotent(&Method::POST));
+    assert!(!RetryPolicy::is_idempotent(&Method::PATCH));
+
+    assert!(RetryPolicy::is_retryable(StatusCode::TOO_MANY_REQUESTS));
+    assert!(RetryPolicy::is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+    assert!(RetryPolicy::is_retryable(StatusCode::INTERNAL_SERVER_ERROR));
+    assert!(!RetryPolicy::is_retryable(StatusCode::NOT_IMPLEMENTED));
+    assert!(!RetryPolicy::is_retryable(StatusCode::CONFLICT));
+}
+
+#[test]
+fn test_backoff() {
+    let policy = RetryPolicy {
+        max_retries: 10,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+    };
+    for attempt in 0..40 {
+        let expected = cmp::min(
+            Duration::from_millis(100 << cmp::min(attempt, 10)),
+            Duration::from_secs(1),
+        );
+        let b = policy.backoff(attempt);
+        assert!(b >= expected / 2, "attempt {}: {:?}", attempt, b);
+        assert!(b <= expected, "attempt {}: {:?}", attempt, b);
+    }
+}
+
+#[test]
+fn test_retry_after() {
+    let mut headers = HeaderMap::new();
+    let body = br#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure",
+                   "message":"slow down","reason":"TooManyRequests","code":429,
+                   "details":{"retryAfterSeconds":3}}"#;
+    assert_eq!(
+        retry_after(StatusCode::TOO_MANY_REQUESTS, &headers, body),
+        Some(Duration::from_secs(3))
+    );
+    headers.insert(RETRY_AFTER, "7".parse().unwrap());
+    assert_eq!(
+        retry_after(StatusCode::TOO_MANY_REQUESTS, &headers, body),
+        Some(Duration::from_secs(7))
+    );
+    assert_eq!(
+        retry_after(StatusCode::SERVICE_UNAVAILABLE, &HeaderMap::new(), b"oops"),
+        None
+    );
+
+    // Whatever the server says, waits are bounded.
+    headers.insert(RETRY_AFTER, "18446744073709551615".parse().unwrap());
+    let huge = retry_after(StatusCode::TOO_MANY_REQUESTS, &headers, body);
+    assert_eq!(huge, Some(Duration::from_secs(u64::MAX)));
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.delay(0, huge), policy.max_backoff);
+    assert_eq!(
+        policy.delay(0, Some(Duration::from_secs(1))),
+        Duration::from_secs(1)
+    );
+    assert!(policy.delay(0, None) <= policy.initial_backoff);
+    let body = br#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure",
+                   "message":"slow down","reason":"TooManyRequests","code":429,
+                   "details":{"retryAfterSeconds":-5}}"#;
+    assert_eq!(
+        retry_after(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new(), body),
+        Some(Duration::from_secs(0))
+    );
+}
+
+#[test]
+fn test_retry_throttled() {
+    use std::sync::Arc;
+
+    use futures::Future;
+    use serde_json::Value;
+    use tokio::runtime::Runtime;
+
+    use api::meta::v1::{GetOptions, StatusReason};
+    use api::meta::GroupVersionResource;
+    use k8sclient::error::ApiError;
+
+    use super::config::ConfigContext;
+    use super::fake::{FakeApiServer, FakeClient};
+    use super::Client;
+
+    let gvr = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let throttled = |secs| {
+        ApiError::new(
+            StatusReason::TooManyRequests,
+            "too many requests, please try again later",
+        )
+        .with_retry_after(Some(Duration::from_secs(secs)))
+    };
+    let cm = json!({"metadata": {"name": "a", "namespace": "ns"}});
+    let mut rt = Runtime::new().unwrap();
+
+    // Without a retry policy, the delay is in the error.
+    let fake = FakeClient::new();
+    fake.server()
+        .fail_next("create", "configmaps", throttled(7));
+    let err = rt
+        .block_on(fake.create(&gvr, &cm, GetOptions::default()))
+        .unwrap_err();
+    let api = ApiError::from_error(&err).unwrap();
+    assert!(api.is_too_many_requests());
+    assert_eq!(api.retry_after(), Some(Duration::from_secs(7)));
+
+    // A create isn't idempotent, but is still retried after a 429.
+    let server = FakeApiServer::new();
+    let mut config: ConfigContext = Default::default();
+    config.cluster.server = "http://fake.invalid".to_string();
+    let client = Client::builder()
+        .config(config)
+        .retry(RetryPolicy::default())
+        .build_with_backend(Arc::new(server.clone()))
+        .unwrap();
+    server.fail_next("create", "configmaps", throttled(0));
+    let created: Value = rt
+        .block_on(client.create(&gvr, &cm, GetOptions::default()))
+        .unwrap();
+    assert_eq!(created["metadata"]["name"], "a");
+    assert_eq!(server.actions().len(), 2);
+
+    server.fail_next(
+        "create",
+        "configmaps",
+        ApiError::new(StatusReason::InternalError, "boom"),
+    );
+    let cm = json!({"metadata": {"name": "b", "namespace": "ns"}});
+    assert!(rt
+        .block_on(
+            client
+                .create(&gvr, &cm, GetOptions::default())
+                .map(|_: Value| ())
+        )
+        .is_err());
+    assert_eq!(server.actions().len(), 3);
+
+    // However long the server asks for, the wait is capped.
+    let mut config: ConfigContext = Default::default();
+    config.cluster.server = "http://fake.invalid".to_string();
+    let client = Client::builder()
+        .config(config)
+        .retry(RetryPolicy {
+            max_backoff: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .build_with_backend(Arc::new(server.clone()))
+        .unwrap();
+    server.fail_next("create", "configmaps", throttled(u64::MAX));
+    let cm = json!({"metadata": {"name": "c", "namespace": "ns"}});
+    let created: Value = rt
+        .block_on(client.create(&gvr, &cm, GetOptions::default()))
+        .unwrap();
+    assert_eq!(created["metadata"]["name"], "c");
+}
@@ -0,0 +1,393 @@
+//! HTTPS connections using rustls rather than native-tls (OpenSSL on
+//! Linux), with the `rustls` feature.
+//!
+//! Without the `tls` feature, `ClientBuilder::build` uses this, with the
+//! kubeconfig's CA and client certificate and key.  With both, `build`
+//! uses native-tls, but a `RustlsConnector` can still be given to
+//! `build_with_client`.
+//!
+//! rustls only checks certificates for DNS names, so an apiserver
+//! addressed by IP (as kubeadm and many local clusters write in the
+//! kubeconfig) needs `insecure-skip-tls-verify`, or native-tls.
+//!
+//! A `ServerCertVerifier` (eg: to pin the apiserver's certificate) is
+//! called by rustls during the handshake, as `build` arranges when
+//! given one with `ClientBuilder::server_cert_verifier`.
+
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+use std::sync::Arc;
+
+use failure::Error;
+use futures::{future, Async, Future, Poll};
+use hyper::client::connect::{Connect, Connected, Destination};
+use rustls::internal::pemfile;
+use rustls::{
+    Certificate, ClientConfig, ClientSession, RootCertStore, ServerCertVerified, Session, TLSError,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use webpki::DNSNameRef;
+use webpki_roots::TLS_SERVER_ROOTS;
+
+use super::config::ConfigContext;
+use super::connector::ServerCertVerifier;
+use super::pem;
+
+/// The protocols we offer, in order of preference.
+const ALPN_PROTOCOLS: &[&[u8]] = &[b"h2", b"http/1.1"];
+
+/// The rustls configuration `config` asks for: its cluster's CA (or, if
+/// it has none, the Mozilla root certificates), and its user's client
+/// certificate.  HTTP/2 is offered unless `http1_only`, and the
+/// server's certificate is checked with `verifier` too, if given.
+pub fn client_config(
+    config: &ConfigContext,
+    http1_only: bool,
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+) -> Result<ClientConfig, Error> {
+    let mut tls = ClientConfig::new();
+    if !http1_only {
+        tls.set_protocols(
+            &ALPN_PROTOCOLS
+                .iter()
+                .map(|p| p.to_vec())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    if let (Some(certdata), Some(keydata)) = (
+        config.user.client_certificate_read(),
+        config.user.client_key_read(),
+    ) {
+        debug!("Setting user client cert");
+        let certs = pemfile::certs(&mut BufReader::new(&certdata?[..]))
+            .map_err(|()| format_err!("Unable to parse the client certificate"))?;
+        let key = pem::pkcs8_key(&keydata?)?;
+        let key = pemfile::pkcs8_private_keys(&mut BufReader::new(&key[..]))
+            .ok()
+            .and_then(|keys| keys.into_iter().next())
+            .ok_or_else(|| format_err!("Unable to parse the client key as PKCS#1 or PKCS#8"))?;
+        tls.set_single_client_cert(certs, key);
+    }
+
+    match config.cluster.certificate_authority_read() {
+        Some(data) => {
+            debug!("Setting cluster CA cert");
+            let (valid, _) = tls
+                .root_store
+                .add_pem_file(&mut BufReader::new(&data?[..]))
+                .map_err(|()| format_err!("Unable to parse the cluster CA certificate"))?;
+            if valid == 0 {
+                return Err(format_err!("No usable cluster CA certificate"));
+            }
+        }
+        None => tls.root_store.add_server_trust_anchors(&TLS_SERVER_ROOTS),
+    }
+
+    if config.cluster.insecure_skip_tls_verify {
+        debug!("Disabling CA verification");
+        tls.dangerous()
+            .set_certificate_verifier(Arc::new(NoVerification));
+    }
+
+    if let Some(verifier) = verifier {
+        let default = if verifier.replaces_default() {
+            debug!("Replacing CA verification with a custom verifier");
+            None
+        } else {
+            // rustls doesn't export its verifier, so keep the config it's in.
+            Some(Arc::new(tls.clone()))
+        };
+        tls.dangerous()
+            .set_certificate_verifier(Arc::new(Verifier { verifier, default }));
+    }
+    Ok(tls)
+}
+
+/// Accepts any certificate, for `insecure-skip-tls-verify`.
+struct NoVerification;
+
+impl rustls::ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        _presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Checks certificates with a `ServerCertVerifier`, after (unless it
+/// `replaces_default`) the verification `default` is configured with.
+struct Verifier {
+    verifier: Arc<dyn ServerCertVerifier>,
+    default: Option<Arc<ClientConfig>>,
+}
+
+impl rustls::ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        if let Some(ref default) = self.default {
+            default.get_verifier().verify_server_cert(
+                roots,
+                presented_certs,
+                dns_name,
+                ocsp_response,
+            )?;
+        }
+        let host: &str = dns_name.into();
+        let cert = presented_certs
+            .first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+        self.verifier.verify(host, &cert.0).map_err(|e| {
+            TLSError::General(format!("Server certificate for {} rejected: {}", host, e))
+        })?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Connects with `http`, then for https URLs does a TLS handshake,
+/// telling hyper when the server chose HTTP/2.
+#[derive(Clone)]
+pub struct RustlsConnector<T> {
+    http: T,
+    tls: Arc<ClientConfig>,
+}
+
+impl<T> RustlsConnector<T> {
+    /// `http` must allow https URLs (eg `HttpConnector::enforce_http(false)`).
+    pub fn new(http: T, tls: ClientConfig) -> Self {
+        RustlsConnector {
+            http,
+            tls: Arc::new(tls),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RustlsConnector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RustlsConnector")
+            .field("http", &self.http)
+            .finish()
+    }
+}
+
+impl<T> Connect for RustlsConnector<T>
+where
+    T: Connect<Error = io::Error>,
+    T::Transport: 'static,
+    T::Future: 'static,
+{
+    type Transport = MaybeTlsStream<T::Transport>;
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = (Self::Transport, Connected), Error = io::Error> + Send>;
+
+    fn connect(&self, dst: Destination) -> Self::Future {
+        if dst.scheme() != "https" {
+            return Box::new(
+                self.http
+                    .connect(dst)
+                    .map(|(stream, connected)| (MaybeTlsStream::Plain(stream), connected)),
+            );
+        }
+        let session = match DNSNameRef::try_from_ascii_str(dst.host()) {
+            Ok(name) => ClientSession::new(&self.tls, name),
+            Err(_) => {
+                return Box::new(future::err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "rustls can't check a certificate for {}, which isn't a DNS name",
+                        dst.host()
+                    ),
+                )))
+            }
+        };
+        Box::new(self.http.connect(dst).and_then(move |(stream, connected)| {
+            let stream = TlsStream {
+                io: stream,
+                session,
+                eof: false,
+                closing: false,
+            };
+            Handshake(Some(stream)).map(move |stream| {
+                if stream.session.get_alpn_protocol() == Some(b"h2") {
+                    debug!("Negotiated HTTP/2");
+                    (
+                        MaybeTlsStream::Tls(Box::new(stream)),
+                        connected.negotiated_h2(),
+                    )
+                } else {
+                    (MaybeTlsStream::Tls(Box::new(stream)), connected)
+                }
+            })
+        }))
+    }
+}
+
+/// A connection made by `RustlsConnector`.
+#[derive(Debug)]
+pub enum MaybeTlsStream<S> {
+    Plain(S),
+    Tls(Box<TlsStream<S>>),
+}
+
+impl<S: Read + Write> Read for MaybeTlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            MaybeTlsStream::Plain(s) => s.read(buf),
+            MaybeTlsStream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl<S: Read + Write> Write for MaybeTlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            MaybeTlsStream::Plain(s) => s.write(buf),
+            MaybeTlsStream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            MaybeTlsStream::Plain(s) => s.flush(),
+            MaybeTlsStream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for MaybeTlsStream<S> {}
+
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for MaybeTlsStream<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self {
+            MaybeTlsStream::Plain(s) => s.shutdown(),
+            MaybeTlsStream::Tls(s) => s.shutdown(),
+        }
+    }
+}
+
+/// A TLS session over the non-blocking `io`.  Reads and writes fail
+/// with `WouldBlock` when `io` does, as tokio expects.
+pub struct TlsStream<S> {
+    io: S,
+    session: ClientSession,
+    /// Whether `io` has no more to read.
+    eof: bool,
+    /// Whether the close_notify alert has been queued.
+    closing: bool,
+}
+
+impl<S: fmt::Debug> fmt::Debug for TlsStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsStream").field("io", &self.io).finish()
+    }
+}
+
+impl<S: Read + Write> TlsStream<S> {
+    /// Write out all the TLS data queued.
+    fn write_tls(&mut self) -> io::Result<()> {
+        while self.session.wants_write() {
+            self.session.write_tls(&mut self.io)?;
+        }
+        Ok(())
+    }
+
+    /// Read and process some TLS data, noting the end of `io`.
+    fn read_tls(&mut self) -> io::Result<()> {
+        if self.session.read_tls(&mut self.io)? == 0 {
+            self.eof = true;
+        }
+        self.session.process_new_packets().map_err(|e| {
+            // Send the alert describing the error, if we can.
+            let _ = self.write_tls();
+            io::Error::new(io::ErrorKind::InvalidData, e)
+        })
+    }
+}
+
+impl<S: Read + Write> Read for TlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            self.write_tls()?;
+            let n = self.session.read(buf)?;
+            if n > 0 || self.eof || buf.is_empty() {
+                return Ok(n);
+            }
+            self.read_tls()?;
+        }
+    }
+}
+
+impl<S: Read + Write> Write for TlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Anything still queued must go first, or the queue grows
+        // without bound.
+        self.write_tls()?;
+        let n = self.session.write(buf)?;
+        match self.write_tls() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            r => r?,
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.session.flush()?;
+        self.write_tls()?;
+        self.io.flush()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for TlsStream<S> {}
+
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for TlsStream<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        if !self.closing {
+            self.session.send_close_notify();
+            self.closing = true;
+        }
+        match self.write_tls() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Async::NotReady),
+            r => r?,
+        }
+        self.io.shutdown()
+    }
+}
+
+/// Resolves to the stream once its handshake is done.
+struct Handshake<S>(Option<TlsStream<S>>);
+
+impl<S: Read + Write> Future for Handshake<S> {
+    type Item = TlsStream<S>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<TlsStream<S>, io::Error> {
+        let result = {
+            let stream = self.0.as_mut().expect("polled after completion");
+            (|| {
+                while stream.session.is_handshaking() {
+                    stream.write_tls()?;
+                    if stream.eof {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    stream.read_tls()?;
+                }
+                // Our last handshake message.
+                stream.write_tls()
+            })()
+        };
+        match result {
+            Ok(()) => Ok(Async::Ready(self.0.take().unwrap())),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+            Err(e) => Err(e),
+        }
+    }
+}
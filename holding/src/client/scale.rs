@@ -0,0 +1,197 @@
+//! The `scale` subresource, served by Deployments, ReplicaSets,
+//! StatefulSets and custom resources that declare one.
+//!
+//! ```no_run
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # use futures::Future;
+//! # use kubernetes_api::meta::GroupVersionResource;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let deployments = GroupVersionResource { group: "apps", version: "v1", resource: "deployments" };
+//! let scale = client.scale(&deployments, Some("default"), "web", 3).wait()?;
+//! assert_eq!(scale.spec.replicas, 3);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use failure::Error;
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request};
+use serde_json::{self, Value};
+
+use api::autoscaling::v1::Scale;
+use api::meta::GroupVersionResource;
+use api::{Integer, MERGE_PATCH};
+
+use super::{do_request, hyper_uri, wait, Client};
+
+impl Client {
+    /// The desired and actual replicas of the named object.
+    pub fn get_scale(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> impl Future<Item = Scale, Error = Error> + Send {
+        let req = self
+            .subresource_url(gvr, namespace, name, "scale")
+            .and_then(|url| {
+                Request::builder()
+                    .method(Method::GET)
+                    .uri(hyper_uri(url))
+                    .body(Body::empty())
+                    .map_err(|e| e.into())
+            });
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
+    /// Set the desired replicas of the named object.
+    ///
+    /// Unlike updating the object itself, this never conflicts with
+    /// other changes to it.
+    pub fn scale(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        replicas: Integer,
+    ) -> impl Future<Item = Scale, Error = Error> + Send {
+        let req = self
+            .subresource_url(gvr, namespace, name, "scale")
+            .and_then(|url| {
+                let patch = json!({"spec": {"replicas": replicas}});
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(hyper_uri(url))
+                    .header(CONTENT_TYPE, MERGE_PATCH)
+                    .body(Body::from(serde_json::to_vec(&patch)?))
+                    .map_err(|e| e.into())
+            });
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+
+    /// Like `scale`, but then wait until the object's controller has
+    /// seen the change, and `status.replicas` is `replicas`.
+    ///
+    /// Pods being scaled up need not be ready yet.  Never times out by
+    /// itself; see `wait::timeout`.
+    pub fn scale_and_wait(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        replicas: Integer,
+    ) -> impl Future<Item = Scale, Error = Error> + Send {
+        let client = self.clone();
+        let gvr = (
+            gvr.group.to_string(),
+            gvr.version.to_string(),
+            gvr.resource.to_string(),
+        );
+        let namespace = namespace.map(str::to_string);
+        let name = name.to_string();
+        self.scale(&borrow_gvr(&gvr), namespace.as_deref(), &name, replicas)
+            .and_then(move |_| {
+                wait::await_condition(
+                    &client,
+                    &borrow_gvr(&gvr),
+                    namespace.as_deref(),
+                    &name,
+                    move |o: &Value| scaled(o, replicas),
+                )
+                .and_then(move |_| client.get_scale(&borrow_gvr(&gvr), namespace.as_deref(), &name))
+            })
+    }
+}
+
+fn borrow_gvr(gvr: &(String, String, String)) -> GroupVersionResource<'_> {
+    GroupVersionResource {
+        group: &gvr.0,
+        version: &gvr.1,
+        resource: &gvr.2,
+    }
+}
+
+/// Whether the controller has caught up with the latest spec, and has
+/// `replicas` replicas.
+fn scaled(object: &Value, replicas: Integer) -> bool {
+    let generation = object["metadata"]["generation"].as_i64().unwrap_or(0);
+    let status = &object["status"];
+    status["observedGeneration"].as_i64().unwrap_or(0) >= generation
+        && object["spec"]["replicas"].as_i64() == Some(replicas.into())
+        && status["replicas"].as_i64().unwrap_or(0) == i64::from(replicas)
+}
+
+#[test]
+fn test_scale() {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::fake::FakeClient;
+
+    let deployments = GroupVersionResource {
+        group: "apps",
+        version: "v1",
+        resource: "deployments",
+    };
+    let client = FakeClient::new();
+    client
+        .server()
+        .insert(
+            &deployments,
+            &json!({
+                "metadata": {"name": "web", "namespace": "ns", "generation": 1},
+                "spec": {"replicas": 1, "selector": {"matchLabels": {"app": "web"}}},
+                "status": {"replicas": 1, "observedGeneration": 1},
+            }),
+        )
+        .unwrap();
+
+    let scale = client
+        .get_scale(&deployments, Some("ns"), "web")
+        .wait()
+        .unwrap();
+    assert_eq!(scale.spec.replicas, 1);
+    assert_eq!(scale.status.selector.as_deref(), Some("app=web"));
+
+    let scale = client
+        .scale(&deployments, Some("ns"), "web", 2)
+        .wait()
+        .unwrap();
+    assert_eq!(scale.spec.replicas, 2);
+    assert_eq!(scale.status.replicas, 1);
+
+    // Play the deployment controller.
+    let controller = client.clone();
+    let gvr = deployments.clone();
+    let t = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        let _: Value = controller
+            .patch(
+                &gvr,
+                Some("ns"),
+                "web",
+                MERGE_PATCH,
+                &json!({"metadata": {"generation": 3}, "status": {"replicas": 3, "observedGeneration": 3}}),
+            )
+            .wait()
+            .unwrap();
+    });
+    let scale = client
+        .scale_and_wait(&deployments, Some("ns"), "web", 3)
+        .wait()
+        .unwrap();
+    assert_eq!(scale.status.replicas, 3);
+    t.join().unwrap();
+
+    assert!(!scaled(
+        &json!({"spec": {"replicas": 3}, "status": {"replicas": 3}, "metadata": {"generation": 2}}),
+        3
+    ));
+}
@@ -0,0 +1,230 @@
+//! Stopping long running streams, eg: when a controller gets SIGTERM.
+//!
+//! Watches and log streams otherwise run until the apiserver ends them,
+//! which may be many minutes.  A `Shutdown` ends them as soon as it is
+//! triggered: a client made with `Client::with_shutdown` ends the
+//! streams of its watches (including those of `watch_namespaces`) and
+//! logs, closing their connections, and `Shutdown::guard` does the same
+//! for any other stream.
+//!
+//! Triggering is up to the caller, eg: from a signal handler:
+//!
+//! ```no_run
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # use futures::Stream;
+//! # use kubernetes_api::core::v1::{Pod, TypedResource};
+//! # use kubernetes_holding::client::shutdown::Shutdown;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let shutdown = Shutdown::new();
+//! let client = Client::new()?.with_shutdown(&shutdown);
+//! let trigger = shutdown.clone();
+//! // eg: with the ctrlc crate: ctrlc::set_handler(move || trigger.trigger())
+//! # drop(trigger);
+//! let pods = client.watch_list(&Pod::GROUP_VERSION_RESOURCE, None, Default::default());
+//! for event in pods.wait() {
+//!     // Ends once `trigger` is called.
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::future::Shared;
+use futures::sync::oneshot;
+use futures::{Async, Future, Poll, Stream};
+
+/// Ends streams when triggered.  Clones trigger (and are triggered)
+/// together.
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    triggered: AtomicBool,
+    sender: Mutex<Option<oneshot::Sender<()>>>,
+    receiver: Shared<oneshot::Receiver<()>>,
+}
+
+impl fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("triggered", &self.is_triggered())
+            .finish()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        Shutdown {
+            inner: Arc::new(Inner {
+                triggered: AtomicBool::new(false),
+                sender: Mutex::new(Some(sender)),
+                receiver: receiver.shared(),
+            }),
+        }
+    }
+
+    /// End the guarded streams.  Triggering again does nothing.
+    pub fn trigger(&self) {
+        self.inner.triggered.store(true, Ordering::SeqCst);
+        if let Some(sender) = self.inner.sender.lock().unwrap().take() {
+            let _ = sender.send(());
+        }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.inner.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Completes once triggered, eg: for selecting with a server's
+    /// future.
+    pub fn signal(&self) -> Signal {
+        Signal(Some(self.inner.receiver.clone()))
+    }
+
+    /// `stream`, ending (and dropped) once triggered.
+    pub fn guard<S: Stream>(&self, stream: S) -> Guarded<S> {
+        Guarded {
+            stream: Some(stream),
+            signal: Some(self.signal()),
+        }
+    }
+}
+
+/// `stream`, guarded by `shutdown` if there is one.
+pub(crate) fn guard_with<S: Stream>(shutdown: Option<&Shutdown>, stream: S) -> Guarded<S> {
+    Guarded {
+        stream: Some(stream),
+        signal: shutdown.map(Shutdown::signal),
+    }
+}
+
+/// See `Shutdown::signal`.
+#[must_use = "futures do nothing unless polled"]
+pub struct Signal(Option<Shared<oneshot::Receiver<()>>>);
+
+impl fmt::Debug for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Signal").finish()
+    }
+}
+
+impl Future for Signal {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let ready = match self.0 {
+            Some(ref mut receiver) => receiver.poll(),
+            None => return Ok(Async::NotReady),
+        };
+        match ready {
+            Ok(Async::Ready(_)) => Ok(Async::Ready(())),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            // Every `Shutdown` is gone without triggering, so it never
+            // will.
+            Err(_) => {
+                self.0 = None;
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+/// See `Shutdown::guard`.
+#[must_use = "streams do nothing unless polled"]
+pub struct Guarded<S> {
+    stream: Option<S>,
+    signal: Option<Signal>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for Guarded<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Guarded")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl<S: Stream> Stream for Guarded<S> {
+    type Item = S::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, S::Error> {
+        if let Some(ref mut signal) = self.signal {
+            if let Ok(Async::Ready(())) = signal.poll() {
+                debug!("Shutting down stream");
+                self.stream = None;
+                self.signal = None;
+            }
+        }
+        match self.stream {
+            Some(ref mut stream) => stream.poll(),
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+#[test]
+fn test_guard() {
+    use futures::sync::mpsc;
+
+    let shutdown = Shutdown::new();
+    let (tx, rx) = mpsc::unbounded::<u32>();
+    tx.unbounded_send(1).unwrap();
+    let mut guarded = shutdown.guard(rx).wait();
+    assert_eq!(guarded.next(), Some(Ok(1)));
+
+    shutdown.clone().trigger();
+    assert!(shutdown.is_triggered());
+    assert_eq!(guarded.next(), None);
+    // The stream was dropped.
+    assert!(tx.unbounded_send(2).is_err());
+
+    // Already triggered.
+    let (_tx, rx) = mpsc::unbounded::<u32>();
+    assert_eq!(shutdown.guard(rx).wait().next(), None);
+    assert_eq!(shutdown.signal().wait(), Ok(()));
+}
+
+#[test]
+fn test_client_shutdown() {
+    use api::meta::GroupVersionResource;
+
+    use super::fake::FakeClient;
+
+    let configmaps = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let fake = FakeClient::new();
+    fake.server()
+        .insert(
+            &configmaps,
+            &json!({"metadata": {"name": "cm", "namespace": "default"}}),
+        )
+        .unwrap();
+    let shutdown = Shutdown::new();
+    let client = fake.with_shutdown(&shutdown);
+    let mut events = client
+        .watch_list(&configmaps, Some("default"), Default::default())
+        .wait();
+    assert!(events.next().unwrap().is_ok());
+    shutdown.trigger();
+    assert!(events.next().is_none());
+}
@@ -0,0 +1,276 @@
+//! Just enough of SPDY/3.1 for the apiserver's streaming subresources,
+//! which older apiservers (and some proxies) offer instead of
+//! WebSockets: the upgrade headers, and framing.
+//!
+//! Header blocks are compressed without SPDY's preset dictionary, which
+//! zlib readers (Go's included) accept, so that any flate2 backend will
+//! do.  The server's header blocks say nothing the apiserver's streams
+//! need, so they aren't decompressed.
+
+use std::io::{self, Write};
+
+use failure::Error;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use futures::{Async, Poll, Stream};
+use hyper::header::{HeaderValue, CONNECTION, UPGRADE};
+use hyper::Request;
+use tokio::io::AsyncRead;
+
+pub(crate) const SYN_STREAM: u16 = 1;
+pub(crate) const RST_STREAM: u16 = 3;
+pub(crate) const PING: u16 = 6;
+pub(crate) const GOAWAY: u16 = 7;
+
+/// The last frame the sender will send on a stream.
+pub(crate) const FLAG_FIN: u8 = 0x01;
+
+const VERSION: u16 = 3;
+const MAX_LENGTH: usize = 0xff_ffff;
+
+/// A frame, as read from the connection.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Frame {
+    Data {
+        stream: u32,
+        flags: u8,
+        data: Vec<u8>,
+    },
+    /// Header blocks in `data` are left compressed.
+    Control { kind: u16, flags: u8, data: Vec<u8> },
+}
+
+/// Add the headers asking to upgrade `req` to SPDY, speaking one of
+/// the Kubernetes stream `protocols`, in order of preference.
+pub(crate) fn upgrade_request<B>(req: &mut Request<B>, protocols: &[&str]) {
+    let headers = req.headers_mut();
+    headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("SPDY/3.1"));
+    for protocol in protocols {
+        headers.append(
+            "x-stream-protocol-version",
+            HeaderValue::from_str(protocol).unwrap(),
+        );
+    }
+}
+
+/// The stream ID at the start of a control frame's `data`.
+pub(crate) fn stream_id(data: &[u8]) -> Option<u32> {
+    let id = data.get(..4)?;
+    Some(u32::from_be_bytes([id[0], id[1], id[2], id[3]]) & 0x7fff_ffff)
+}
+
+fn header(first: u32, flags: u8, len: usize) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(len + 8);
+    frame.extend_from_slice(&first.to_be_bytes());
+    frame.push(flags);
+    frame.extend_from_slice(&(len as u32).to_be_bytes()[1..]);
+    frame
+}
+
+pub(crate) fn control(kind: u16, flags: u8, data: &[u8]) -> Vec<u8> {
+    let first = 0x8000_0000 | u32::from(VERSION) << 16 | u32::from(kind);
+    let mut frame = header(first, flags, data.len());
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// Frames carrying `data` on `stream`, as many as frames' 24 bit
+/// lengths need, the last with `flags`.
+pub(crate) fn data(stream: u32, flags: u8, data: &[u8]) -> Vec<u8> {
+    let chunks = match data.len() {
+        0 => vec![data],
+        _ => data.chunks(MAX_LENGTH).collect(),
+    };
+    let mut frames = Vec::with_capacity(data.len() + 8 * chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        let flags = if i + 1 == chunks.len() { flags } else { 0 };
+        frames.extend(header(stream, flags, chunk.len()));
+        frames.extend_from_slice(chunk);
+    }
+    frames
+}
+
+/// Opens streams, each with a `SYN_STREAM` frame.  The header blocks
+/// of a connection are compressed together, so its frames must be sent
+/// in the order they were made.
+pub(crate) struct Streams {
+    headers: ZlibEncoder<Vec<u8>>,
+    next: u32,
+}
+
+impl Streams {
+    pub(crate) fn new() -> Self {
+        Streams {
+            headers: ZlibEncoder::new(Vec::new(), Compression::default()),
+            // Client streams are odd.
+            next: 1,
+        }
+    }
+
+    /// A frame opening a stream with `headers`, and the stream's ID.
+    pub(crate) fn open(&mut self, headers: &[(&str, &str)]) -> (u32, Vec<u8>) {
+        let stream = self.next;
+        self.next += 2;
+        let mut block = Vec::new();
+        block.extend_from_slice(&(headers.len() as u32).to_be_bytes());
+        for (name, value) in headers {
+            for s in &[name, value] {
+                block.extend_from_slice(&(s.len() as u32).to_be_bytes());
+                block.extend_from_slice(s.as_bytes());
+            }
+        }
+        // Compressing into a Vec can't fail.
+        self.headers.write_all(&block).unwrap();
+        self.headers.flush().unwrap();
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&stream.to_be_bytes());
+        // No associated stream, and the default priority and slot.
+        frame.extend_from_slice(&[0; 6]);
+        frame.append(self.headers.get_mut());
+        (stream, control(SYN_STREAM, 0, &frame))
+    }
+}
+
+/// Parse the frame at the start of `buf`, returning it and its length,
+/// or `None` if `buf` doesn't hold all of it yet.
+fn decode(buf: &[u8]) -> Option<(Frame, usize)> {
+    if buf.len() < 8 {
+        return None;
+    }
+    let first = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    let flags = buf[4];
+    let end = 8 + u32::from_be_bytes([0, buf[5], buf[6], buf[7]]) as usize;
+    if buf.len() < end {
+        return None;
+    }
+    let data = buf[8..end].to_vec();
+    let frame = if first & 0x8000_0000 != 0 {
+        Frame::Control {
+            kind: first as u16,
+            flags,
+            data,
+        }
+    } else {
+        Frame::Data {
+            stream: first,
+            flags,
+            data,
+        }
+    };
+    Some((frame, end))
+}
+
+/// The frames read from a SPDY connection.
+pub(crate) struct Frames<R> {
+    read: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: AsyncRead> Frames<R> {
+    pub(crate) fn new(read: R) -> Self {
+        Frames {
+            read,
+            buf: Vec::new(),
+            eof: false,
+        }
+    }
+}
+
+impl<R: AsyncRead> Stream for Frames<R> {
+    type Item = Frame;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Frame>, Error> {
+        loop {
+            if let Some((frame, len)) = decode(&self.buf) {
+                self.buf.drain(..len);
+                return Ok(Async::Ready(Some(frame)));
+            }
+            if self.eof {
+                if !self.buf.is_empty() {
+                    return Err(format_err!("SPDY connection closed mid-frame"));
+                }
+                return Ok(Async::Ready(None));
+            }
+            let mut chunk = [0; 8192];
+            match self.read.poll_read(&mut chunk) {
+                Ok(Async::Ready(0)) => self.eof = true,
+                Ok(Async::Ready(n)) => self.buf.extend_from_slice(&chunk[..n]),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_framing() {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+    use futures::Future;
+
+    let mut streams = Streams::new();
+    let (one, syn) = streams.open(&[("streamtype", "error")]);
+    let (three, syn2) = streams.open(&[("streamtype", "stdout")]);
+    assert_eq!((one, three), (1, 3));
+    assert_eq!(&syn[..4], &[0x80, 3, 0, 1]);
+    let frames = Frames::new(&[&syn[..], &syn2, &data(3, FLAG_FIN, b"hi")].concat()[..])
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(
+        frames[2],
+        Frame::Data {
+            stream: 3,
+            flags: FLAG_FIN,
+            data: b"hi".to_vec()
+        }
+    );
+
+    // The header blocks are one zlib stream.
+    let mut blocks = Vec::new();
+    for frame in &frames[..2] {
+        match frame {
+            Frame::Control {
+                kind: SYN_STREAM,
+                data,
+                ..
+            } => blocks.extend_from_slice(&data[10..]),
+            _ => panic!("not a SYN_STREAM: {:?}", frame),
+        }
+    }
+    let mut headers = Vec::new();
+    // Sync flushed, so the stream is never finished.
+    let _ = ZlibDecoder::new(&blocks[..]).read_to_end(&mut headers);
+    let block = |name: &str, value: &str| {
+        let mut block = vec![0, 0, 0, 1, 0, 0, 0, name.len() as u8];
+        block.extend_from_slice(name.as_bytes());
+        block.extend_from_slice(&[0, 0, 0, value.len() as u8]);
+        block.extend_from_slice(value.as_bytes());
+        block
+    };
+    assert_eq!(
+        headers,
+        [block("streamtype", "error"), block("streamtype", "stdout")].concat()
+    );
+
+    let big = data(5, FLAG_FIN, &vec![7; MAX_LENGTH + 1]);
+    let frames = Frames::new(&big[..]).collect().wait().unwrap();
+    assert_eq!(frames.len(), 2);
+    match &frames[1] {
+        Frame::Data { flags, data, .. } => assert_eq!((*flags, data.len()), (FLAG_FIN, 1)),
+        frame => panic!("not data: {:?}", frame),
+    }
+
+    assert_eq!(stream_id(&[0x80, 0, 0, 5, 0]), Some(5));
+    let ping = control(PING, 0, &[0, 0, 0, 2]);
+    assert_eq!(ping, [0x80, 3, 0, 6, 0, 0, 0, 4, 0, 0, 0, 2]);
+    assert!(Frames::new(&ping[..ping.len() - 1])
+        .collect()
+        .wait()
+        .is_err());
+}
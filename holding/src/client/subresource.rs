@@ -0,0 +1,218 @@
+//! Any subresource of an object, including those custom resources
+//! define, for when there isn't a call of its own (like `get_scale` or
+//! `patch_status`).
+//!
+//! ```no_run
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # extern crate serde_json;
+//! # use futures::Future;
+//! # use kubernetes_api::meta::GroupVersionResource;
+//! # use kubernetes_api::MERGE_PATCH;
+//! # use kubernetes_holding::client::subresource::Verb;
+//! # use kubernetes_holding::client::Client;
+//! # use serde_json::{json, Value};
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let widgets = GroupVersionResource { group: "example.com", version: "v1", resource: "widgets" };
+//! let status = json!({"status": {"phase": "Ready"}});
+//! let widget: Value = client
+//!     .subresource(&widgets, Some("default"), "w", "status", Verb::Patch(MERGE_PATCH), Some(&status))
+//!     .wait()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use failure::Error;
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+
+use api::meta::GroupVersionResource;
+use api::JSON;
+
+use super::{do_request, hyper_uri, Client};
+
+/// What to do with a subresource.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verb<'a> {
+    /// Read it, eg: `status` or `scale`.
+    Get,
+    /// POST to it, eg: `eviction`, `binding` or `token`.
+    Create,
+    /// Replace it, eg: `status`, `scale` or `finalize`.
+    Update,
+    /// Patch it, with a patch of this content type, eg: `MERGE_PATCH`.
+    Patch(&'a str),
+}
+
+impl Verb<'_> {
+    fn method(&self) -> Method {
+        match *self {
+            Verb::Get => Method::GET,
+            Verb::Create => Method::POST,
+            Verb::Update => Method::PUT,
+            Verb::Patch(_) => Method::PATCH,
+        }
+    }
+
+    fn content_type(&self) -> &str {
+        match *self {
+            Verb::Patch(patch_type) => patch_type,
+            _ => JSON,
+        }
+    }
+}
+
+impl Client {
+    /// Do `verb` to `subresource` of the named object, sending `body`
+    /// (as JSON, or as a patch) if there is one, and decode the
+    /// response.
+    pub fn subresource<B, T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        name: &str,
+        subresource: &str,
+        verb: Verb,
+        body: Option<&B>,
+    ) -> impl Future<Item = T, Error = Error> + Send
+    where
+        B: Serialize,
+        T: DeserializeOwned + Send + 'static,
+    {
+        let req = self
+            .subresource_url(gvr, namespace, name, subresource)
+            .and_then(|url| {
+                let mut req = Request::builder();
+                req.method(verb.method()).uri(hyper_uri(url));
+                let body = match body {
+                    Some(body) => {
+                        req.header(CONTENT_TYPE, verb.content_type());
+                        Body::from(serde_json::to_vec(body)?)
+                    }
+                    None => Body::empty(),
+                };
+                req.body(body).map_err(|e| e.into())
+            });
+        do_request(Arc::clone(&self.client), self.timeout, req)
+    }
+}
+
+#[test]
+fn test_subresource() {
+    use serde_json::Value;
+
+    use api::meta::v1::StatusReason;
+    use api::MERGE_PATCH;
+    use k8sclient::error::ApiError;
+
+    use super::fake::FakeClient;
+
+    let deployments = GroupVersionResource {
+        group: "apps",
+        version: "v1",
+        resource: "deployments",
+    };
+    let pods = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "pods",
+    };
+    let client = FakeClient::new();
+    let server = client.server();
+    server
+        .insert(
+            &deployments,
+            &json!({
+                "metadata": {"name": "web", "namespace": "ns"},
+                "spec": {"replicas": 1},
+                "status": {"replicas": 1},
+            }),
+        )
+        .unwrap();
+    server
+        .insert(
+            &pods,
+            &json!({"metadata": {"name": "web-0", "namespace": "ns"}}),
+        )
+        .unwrap();
+    let none = None::<&Value>;
+
+    let scale: Value = client
+        .subresource(&deployments, Some("ns"), "web", "scale", Verb::Get, none)
+        .wait()
+        .unwrap();
+    assert_eq!(scale["kind"], "Scale");
+
+    let patch = json!({"spec": {"replicas": 3}});
+    let scale: Value = client
+        .subresource(
+            &deployments,
+            Some("ns"),
+            "web",
+            "scale",
+            Verb::Patch(MERGE_PATCH),
+            Some(&patch),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(scale["spec"]["replicas"], 3);
+
+    let mut deploy = server.get(&deployments, Some("ns"), "web").unwrap();
+    deploy["spec"]["replicas"] = 5.into();
+    deploy["status"]["replicas"] = 3.into();
+    let updated: Value = client
+        .subresource(
+            &deployments,
+            Some("ns"),
+            "web",
+            "status",
+            Verb::Update,
+            Some(&deploy),
+        )
+        .wait()
+        .unwrap();
+    // Only the status changes.
+    assert_eq!(updated["spec"]["replicas"], 3);
+    assert_eq!(updated["status"]["replicas"], 3);
+
+    let eviction = json!({
+        "apiVersion": "policy/v1",
+        "kind": "Eviction",
+        "metadata": {"name": "web-0", "namespace": "ns"},
+    });
+    let _: Value = client
+        .subresource(
+            &pods,
+            Some("ns"),
+            "web-0",
+            "eviction",
+            Verb::Create,
+            Some(&eviction),
+        )
+        .wait()
+        .unwrap();
+    assert!(server.get(&pods, Some("ns"), "web-0").is_none());
+
+    // Whatever the server makes of it.
+    server.fail_next(
+        "get",
+        "deployments/rollback",
+        ApiError::new(
+            StatusReason::NotFound,
+            "the server could not find the requested resource",
+        ),
+    );
+    let err = client
+        .subresource::<Value, Value>(&deployments, Some("ns"), "web", "rollback", Verb::Get, none)
+        .wait()
+        .unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_not_found());
+}
@@ -0,0 +1,512 @@
+//! Just enough of the tar format to copy files to and from containers:
+//! ustar, plus the GNU and pax extensions for long names that the
+//! `tar` commands in container images use.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::mem;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use failure::{Error, ResultExt};
+
+const BLOCK: usize = 512;
+/// `tar` reads archives in records of this many bytes, and stops once
+/// it has read the one with the end-of-archive marker.  Padding to a
+/// whole record lets it finish even when it never sees the end of its
+/// input.
+const RECORD: usize = 20 * BLOCK;
+/// How much of a file to read at a time.
+const CHUNK: usize = 64 * 1024;
+
+const REGULAR: u8 = b'0';
+const SYMLINK: u8 = b'2';
+const DIRECTORY: u8 = b'5';
+const GNU_LONG_NAME: u8 = b'L';
+const GNU_LONG_LINK: u8 = b'K';
+const PAX: u8 = b'x';
+
+fn padding(len: u64) -> usize {
+    (BLOCK - (len % BLOCK as u64) as usize) % BLOCK
+}
+
+/// Write `n` as an octal number filling `field`, or in GNU base-256 if
+/// it doesn't fit.
+fn put_number(field: &mut [u8], n: u64) {
+    let digits = field.len() - 1;
+    let octal = format!("{:0width$o}", n, width = digits);
+    if octal.len() == digits {
+        field[..digits].copy_from_slice(octal.as_bytes());
+        field[digits] = 0;
+    } else {
+        for (i, b) in field.iter_mut().rev().enumerate() {
+            *b = n.checked_shr(8 * i as u32).unwrap_or(0) as u8;
+        }
+        field[0] = 0x80;
+    }
+}
+
+fn get_number(field: &[u8]) -> Result<u64, Error> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..].iter().fold(0, |n, b| (n << 8) | u64::from(*b)));
+    }
+    let digits = String::from_utf8_lossy(field);
+    let digits = digits.trim_matches(|c: char| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    Ok(u64::from_str_radix(digits, 8).context("Invalid number in tar header")?)
+}
+
+fn header(name: &[u8], typeflag: u8, mode: u32, size: u64, mtime: u64, link: &[u8]) -> Vec<u8> {
+    let mut h = vec![0; BLOCK];
+    h[..name.len()].copy_from_slice(name);
+    put_number(&mut h[100..108], u64::from(mode & 0o7777));
+    put_number(&mut h[108..116], 0);
+    put_number(&mut h[116..124], 0);
+    put_number(&mut h[124..136], size);
+    put_number(&mut h[136..148], mtime);
+    h[156] = typeflag;
+    h[157..157 + link.len()].copy_from_slice(link);
+    h[257..263].copy_from_slice(b"ustar\0");
+    h[263..265].copy_from_slice(b"00");
+    h[148..156].copy_from_slice(b"        ");
+    let sum: u32 = h.iter().map(|b| u32::from(*b)).sum();
+    h[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+    h
+}
+
+/// A GNU extension entry holding a name too long for the header.
+fn long_name(typeflag: u8, name: &[u8]) -> Vec<u8> {
+    let mut entry = header(b"././@LongLink", typeflag, 0, name.len() as u64 + 1, 0, b"");
+    entry.extend_from_slice(name);
+    entry.push(0);
+    let pad = padding(name.len() as u64 + 1);
+    entry.resize(entry.len() + pad, 0);
+    entry
+}
+
+/// The header blocks for an entry, with long names as needed.
+fn entry_header(name: &str, typeflag: u8, mode: u32, size: u64, mtime: u64, link: &str) -> Vec<u8> {
+    let mut blocks = Vec::new();
+    let mut name = name.as_bytes();
+    let mut link = link.as_bytes();
+    if link.len() > 100 {
+        blocks.extend(long_name(GNU_LONG_LINK, link));
+        link = &link[..100];
+    }
+    if name.len() > 100 {
+        blocks.extend(long_name(GNU_LONG_NAME, name));
+        name = &name[..100];
+    }
+    blocks.extend(header(name, typeflag, mode, size, mtime, link));
+    blocks
+}
+
+#[cfg(unix)]
+fn mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn mode(meta: &fs::Metadata) -> u32 {
+    match (meta.is_dir(), meta.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, false) => 0o644,
+        (false, true) => 0o444,
+    }
+}
+
+/// Archives a local file or directory, a chunk at a time, so that large
+/// files needn't be read into memory at once.
+pub(crate) struct Packer {
+    /// The files still to archive, and their names in the archive.
+    pending: VecDeque<(PathBuf, String)>,
+    /// The file being archived, and how much of it is left.
+    current: Option<(File, u64)>,
+    written: u64,
+    finished: bool,
+}
+
+impl Packer {
+    /// Archive `path`, naming it `name` in the archive.
+    pub(crate) fn new(path: &Path, name: &str) -> Result<Self, Error> {
+        // Follow `path` itself if it's a symlink, but not those in it.
+        let path = fs::canonicalize(path)
+            .with_context(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let mut pending = VecDeque::new();
+        walk(&path, name.to_string(), &mut pending)?;
+        Ok(Packer {
+            pending,
+            current: None,
+            written: 0,
+            finished: false,
+        })
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        if let Some((mut file, left)) = self.current.take() {
+            let mut chunk = vec![0; CHUNK.min(left as usize)];
+            file.read_exact(&mut chunk)
+                .context("File changed while being copied")?;
+            let left = left - chunk.len() as u64;
+            if left > 0 {
+                self.current = Some((file, left));
+            } else {
+                let pad = padding(self.written + chunk.len() as u64);
+                chunk.resize(chunk.len() + pad, 0);
+            }
+            return Ok(Some(chunk));
+        }
+        if let Some((path, name)) = self.pending.pop_front() {
+            let meta = fs::symlink_metadata(&path)
+                .with_context(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            let file_type = meta.file_type();
+            return Ok(Some(if file_type.is_dir() {
+                entry_header(&(name + "/"), DIRECTORY, mode(&meta), 0, mtime, "")
+            } else if file_type.is_symlink() {
+                let target = fs::read_link(&path)?;
+                let target = target.to_string_lossy();
+                entry_header(&name, SYMLINK, 0o777, 0, mtime, &target)
+            } else {
+                let file = File::open(&path)
+                    .with_context(|e| format!("Unable to open {}: {}", path.display(), e))?;
+                if meta.len() > 0 {
+                    self.current = Some((file, meta.len()));
+                }
+                entry_header(&name, REGULAR, mode(&meta), meta.len(), mtime, "")
+            }));
+        }
+        if self.finished {
+            return Ok(None);
+        }
+        self.finished = true;
+        let end = self.written as usize + 2 * BLOCK;
+        Ok(Some(vec![0; 2 * BLOCK + (RECORD - end % RECORD) % RECORD]))
+    }
+}
+
+fn walk(path: &Path, name: String, found: &mut VecDeque<(PathBuf, String)>) -> Result<(), Error> {
+    let meta = fs::symlink_metadata(path)
+        .with_context(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    found.push_back((path.to_path_buf(), name.clone()));
+    if meta.is_dir() {
+        let mut children = fs::read_dir(path)
+            .with_context(|e| format!("Unable to read {}: {}", path.display(), e))?
+            .collect::<Result<Vec<_>, _>>()?;
+        children.sort_by_key(|c| c.file_name());
+        for child in children {
+            let child_name = format!("{}/{}", name, child.file_name().to_string_lossy());
+            walk(&child.path(), child_name, found)?;
+        }
+    }
+    Ok(())
+}
+
+impl Iterator for Packer {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_chunk() {
+            Ok(Some(chunk)) => {
+                self.written += chunk.len() as u64;
+                Some(Ok(chunk))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                // Give up, rather than sending a corrupt archive.
+                self.pending.clear();
+                self.current = None;
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+enum State {
+    Header,
+    /// Extracting a file: the data left, then the padding after it.
+    File(File, u64, usize),
+    /// Reading the data of a long name or pax entry.
+    Extension(u8, Vec<u8>, u64, usize),
+    Skip(u64),
+    End,
+}
+
+/// After an entry's data, skip its padding (if any).
+fn skip(pad: u64) -> State {
+    if pad > 0 {
+        State::Skip(pad)
+    } else {
+        State::Header
+    }
+}
+
+/// Extracts an archive as it arrives, putting the entry named `name`
+/// (and those inside it) at `dest`.
+///
+/// Entries outside `name`, or with `..` in their names, are rejected.
+/// Symlinks and other special files are skipped.
+pub(crate) struct Unpacker {
+    dest: PathBuf,
+    name: String,
+    buf: Vec<u8>,
+    state: State,
+    long_name: Option<String>,
+    entries: usize,
+}
+
+impl Unpacker {
+    pub(crate) fn new(dest: &Path, name: &str) -> Self {
+        Unpacker {
+            dest: dest.to_path_buf(),
+            name: name.to_string(),
+            buf: Vec::new(),
+            state: State::Header,
+            long_name: None,
+            entries: 0,
+        }
+    }
+
+    /// Where to put the entry `name`.
+    fn target(&self, name: &str) -> Result<PathBuf, Error> {
+        let mut components = Path::new(name)
+            .components()
+            .filter(|c| *c != Component::CurDir);
+        if components.next() != Some(Component::Normal(self.name.as_ref())) {
+            return Err(format_err!("Unexpected entry {:?} in archive", name));
+        }
+        let mut target = self.dest.clone();
+        for c in components {
+            match c {
+                Component::Normal(c) => target.push(c),
+                _ => return Err(format_err!("Unsafe entry {:?} in archive", name)),
+            }
+        }
+        Ok(target)
+    }
+
+    pub(crate) fn feed(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        while !data.is_empty() {
+            let next = match self.state {
+                State::End => return Ok(()),
+                State::Header => {
+                    let n = data.len().min(BLOCK - self.buf.len());
+                    self.buf.extend_from_slice(&data[..n]);
+                    data = &data[n..];
+                    if self.buf.len() == BLOCK {
+                        let block = mem::replace(&mut self.buf, Vec::with_capacity(BLOCK));
+                        self.header(&block)?;
+                    }
+                    continue;
+                }
+                State::File(ref mut file, ref mut left, pad) => {
+                    let n = data.len().min(*left as usize);
+                    file.write_all(&data[..n])?;
+                    *left -= n as u64;
+                    data = &data[n..];
+                    if *left > 0 {
+                        continue;
+                    }
+                    skip(pad as u64)
+                }
+                State::Extension(typeflag, ref mut buf, ref mut left, pad) => {
+                    let n = data.len().min(*left as usize);
+                    buf.extend_from_slice(&data[..n]);
+                    *left -= n as u64;
+                    data = &data[n..];
+                    if *left > 0 {
+                        continue;
+                    }
+                    let buf = mem::take(buf);
+                    self.extension(typeflag, &buf);
+                    skip(pad as u64)
+                }
+                State::Skip(ref mut left) => {
+                    let n = data.len().min(*left as usize);
+                    *left -= n as u64;
+                    data = &data[n..];
+                    if *left > 0 {
+                        continue;
+                    }
+                    State::Header
+                }
+            };
+            self.state = next;
+        }
+        Ok(())
+    }
+
+    fn extension(&mut self, typeflag: u8, data: &[u8]) {
+        let text = String::from_utf8_lossy(data);
+        match typeflag {
+            GNU_LONG_NAME => self.long_name = Some(text.trim_end_matches('\0').to_string()),
+            PAX => {
+                // Records are "<length> <key>=<value>\n".
+                for record in text.split('\n') {
+                    if let Some(path) = record
+                        .split_once(' ')
+                        .and_then(|(_, kv)| kv.strip_prefix("path="))
+                    {
+                        self.long_name = Some(path.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn header(&mut self, block: &[u8]) -> Result<(), Error> {
+        if block.iter().all(|b| *b == 0) {
+            self.state = State::End;
+            return Ok(());
+        }
+        let stored = get_number(&block[148..156])?;
+        let sum: u64 = block
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                if (148..156).contains(&i) {
+                    32
+                } else {
+                    u64::from(*b)
+                }
+            })
+            .sum();
+        if stored != sum {
+            return Err(format_err!("Corrupt tar header"));
+        }
+        let size = get_number(&block[124..136])?;
+        let typeflag = block[156];
+        let field = |range: &[u8]| {
+            let end = range.iter().position(|b| *b == 0).unwrap_or(range.len());
+            String::from_utf8_lossy(&range[..end]).into_owned()
+        };
+        let name = match self.long_name.take() {
+            Some(name) => name,
+            None if &block[257..262] == b"ustar" && block[345] != 0 => {
+                format!("{}/{}", field(&block[345..500]), field(&block[..100]))
+            }
+            None => field(&block[..100]),
+        };
+        match typeflag {
+            GNU_LONG_NAME | GNU_LONG_LINK | PAX => {
+                self.state = State::Extension(typeflag, Vec::new(), size, padding(size));
+                return Ok(());
+            }
+            REGULAR | 0 | b'7' => {
+                let target = self.target(&name)?;
+                let file = File::create(&target)
+                    .with_context(|e| format!("Unable to create {}: {}", target.display(), e))?;
+                set_mode(&target, get_number(&block[100..108])?)?;
+                self.entries += 1;
+                if size > 0 {
+                    self.state = State::File(file, size, padding(size));
+                }
+                return Ok(());
+            }
+            DIRECTORY => {
+                let target = self.target(name.trim_end_matches('/'))?;
+                fs::create_dir_all(&target)
+                    .with_context(|e| format!("Unable to create {}: {}", target.display(), e))?;
+                self.entries += 1;
+            }
+            _ => warn!(
+                "Skipping {:?} in archive: not a regular file or directory",
+                name
+            ),
+        }
+        if size > 0 {
+            self.state = State::Skip(size + padding(size) as u64);
+        }
+        Ok(())
+    }
+
+    /// Check the archive was complete.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        match self.state {
+            State::End if self.entries > 0 => Ok(()),
+            _ if self.entries == 0 => Err(format_err!("No {} in archive", self.name)),
+            _ => Err(format_err!("Archive ended early")),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u64) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode as u32 & 0o777))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u64) -> Result<(), Error> {
+    Ok(())
+}
+
+#[test]
+fn test_roundtrip() {
+    use std::env;
+    use std::process;
+
+    let tmp = env::temp_dir().join(format!("kubernetes-tar-{}", process::id()));
+    let src = tmp.join("src");
+    let long = "d".repeat(120);
+    fs::create_dir_all(src.join(&long)).unwrap();
+    fs::write(src.join("small"), b"hello").unwrap();
+    fs::write(src.join(&long).join("big"), vec![42u8; 3 * CHUNK + 7]).unwrap();
+    fs::write(src.join("empty"), b"").unwrap();
+
+    let archive: Vec<u8> = Packer::new(&src, "copy")
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+        .concat();
+    assert_eq!(archive.len() % RECORD, 0);
+
+    // Fed in awkward pieces, as it arrives from the network.
+    let dest = tmp.join("dest");
+    let mut unpacker = Unpacker::new(&dest, "copy");
+    for piece in archive.chunks(700) {
+        unpacker.feed(piece).unwrap();
+    }
+    unpacker.finish().unwrap();
+    assert_eq!(fs::read(dest.join("small")).unwrap(), b"hello");
+    assert_eq!(fs::read(dest.join("empty")).unwrap(), b"");
+    assert_eq!(
+        fs::read(dest.join(&long).join("big")).unwrap(),
+        vec![42u8; 3 * CHUNK + 7]
+    );
+
+    let mut unpacker = Unpacker::new(&tmp.join("other"), "other");
+    assert!(unpacker.feed(&archive).is_err());
+    let mut unpacker = Unpacker::new(&tmp.join("short"), "copy");
+    unpacker.feed(&archive[..3 * BLOCK]).unwrap();
+    assert!(unpacker.finish().is_err());
+
+    let evil = entry_header("copy/../../evil", REGULAR, 0o644, 0, 0, "");
+    let mut unpacker = Unpacker::new(&tmp.join("evil"), "copy");
+    assert!(unpacker.feed(&evil).is_err());
+
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[test]
+fn test_numbers() {
+    let mut field = [0; 12];
+    put_number(&mut field, 0o644);
+    assert_eq!(&field, b"00000000644\0");
+    assert_eq!(get_number(&field).unwrap(), 0o644);
+    put_number(&mut field, 1 << 40);
+    assert_eq!(field[0], 0x80);
+    assert_eq!(get_number(&field).unwrap(), 1 << 40);
+    assert_eq!(get_number(b"     644 \0").unwrap(), 0o644);
+}
@@ -0,0 +1,405 @@
+//! Resource usage alongside what was asked for, like `kubectl top`.
+//!
+//! Usage comes from the resource metrics API (metrics.k8s.io, usually
+//! served by metrics-server), and is joined with nodes' allocatable
+//! resources and pods' requests and limits, so that it can be given as
+//! percentages:
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate futures;
+//! # extern crate kubernetes_holding;
+//! # use futures::Future;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! for node in client.top_nodes("").wait()? {
+//!     println!(
+//!         "{} cpu {:.0}% (requested {:.0}%)",
+//!         node.name,
+//!         node.usage_percent("cpu").unwrap_or_default(),
+//!         node.requests_percent("cpu").unwrap_or_default(),
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Nodes and pods the metrics API has no usage for yet (eg: pods that
+//! have just started) are left out.
+
+use std::collections::HashMap;
+
+use failure::Error;
+use futures::Future;
+use serde::Deserialize;
+use serde_json::{self, Value};
+
+use api::core::v1::{Pod, PodSpec, TypedResource};
+use api::meta::v1::ListOptions;
+use api::meta::GroupVersionResource;
+use api::metrics::v1beta1::{NodeMetrics, PodMetrics};
+use api::{Quantity, ResourceList, Time};
+
+use super::Client;
+
+const NODES: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "nodes",
+};
+
+/// Pods that may still be using resources.
+const UNFINISHED: &str = "status.phase!=Succeeded,status.phase!=Failed";
+
+/// The items of a list, whatever its kind.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+struct Items<T> {
+    #[serde(default)]
+    items: Vec<T>,
+}
+
+/// `used` as a percentage of `of`, or `None` if there is none of it.
+fn percent(used: &Quantity, of: &Quantity) -> Option<f64> {
+    if of.is_zero() {
+        return None;
+    }
+    Some(used.milli_value() as f64 * 100.0 / of.milli_value() as f64)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeUsage {
+    pub name: String,
+    /// The end of the window the usage was measured over.
+    pub timestamp: Time,
+    pub window: String,
+    pub usage: ResourceList,
+    /// What the node has for pods.
+    pub allocatable: ResourceList,
+    /// The requests of the unfinished pods on the node.
+    pub requests: ResourceList,
+    /// The limits of the unfinished pods on the node.
+    pub limits: ResourceList,
+}
+
+impl NodeUsage {
+    /// The usage of `resource`, as a percentage of what the node has
+    /// for pods, as `kubectl top node` shows.
+    pub fn usage_percent(&self, resource: &str) -> Option<f64> {
+        percent(
+            &self.usage.quantity(resource),
+            &self.allocatable.quantity(resource),
+        )
+    }
+
+    /// How much of `resource` is requested, as a percentage of what the
+    /// node has for pods, as `kubectl describe node` shows.
+    pub fn requests_percent(&self, resource: &str) -> Option<f64> {
+        percent(
+            &self.requests.quantity(resource),
+            &self.allocatable.quantity(resource),
+        )
+    }
+
+    /// Likewise for limits, which may add up to more than 100%.
+    pub fn limits_percent(&self, resource: &str) -> Option<f64> {
+        percent(
+            &self.limits.quantity(resource),
+            &self.allocatable.quantity(resource),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodUsage {
+    pub namespace: String,
+    pub name: String,
+    /// The node the pod runs on.
+    pub node: Option<String>,
+    pub timestamp: Time,
+    pub window: String,
+    /// The usage of all its containers.
+    pub usage: ResourceList,
+    /// As `PodSpec::resource_requests` counts them.
+    pub requests: ResourceList,
+    /// As `PodSpec::resource_limits` counts them.
+    pub limits: ResourceList,
+    pub containers: Vec<ContainerUsage>,
+}
+
+impl PodUsage {
+    /// The usage of `resource`, as a percentage of the pod's requests.
+    pub fn requests_percent(&self, resource: &str) -> Option<f64> {
+        percent(
+            &self.usage.quantity(resource),
+            &self.requests.quantity(resource),
+        )
+    }
+
+    /// The usage of `resource`, as a percentage of the pod's limits.
+    pub fn limits_percent(&self, resource: &str) -> Option<f64> {
+        percent(
+            &self.usage.quantity(resource),
+            &self.limits.quantity(resource),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerUsage {
+    pub name: String,
+    pub usage: ResourceList,
+    pub requests: ResourceList,
+    pub limits: ResourceList,
+}
+
+impl ContainerUsage {
+    /// The usage of `resource`, as a percentage of the container's
+    /// request.
+    pub fn requests_percent(&self, resource: &str) -> Option<f64> {
+        percent(
+            &self.usage.quantity(resource),
+            &self.requests.quantity(resource),
+        )
+    }
+
+    /// The usage of `resource`, as a percentage of the container's
+    /// limit.
+    pub fn limits_percent(&self, resource: &str) -> Option<f64> {
+        percent(
+            &self.usage.quantity(resource),
+            &self.limits.quantity(resource),
+        )
+    }
+}
+
+impl Client {
+    /// The usage of the nodes matching `label_selector`, by name, with
+    /// what they have and what their pods request.
+    pub fn top_nodes(
+        &self,
+        label_selector: &str,
+    ) -> impl Future<Item = Vec<NodeUsage>, Error = Error> + Send {
+        let selected = ListOptions {
+            label_selector: label_selector.to_string(),
+            ..Default::default()
+        };
+        let unfinished = ListOptions {
+            field_selector: UNFINISHED.to_string(),
+            ..Default::default()
+        };
+        let metrics = self.list::<Items<NodeMetrics>>(
+            &NodeMetrics::GROUP_VERSION_RESOURCE,
+            None,
+            selected.clone(),
+        );
+        let nodes = self.list::<Items<Value>>(&NODES, None, selected);
+        let pods = self.list::<Items<Pod>>(&Pod::GROUP_VERSION_RESOURCE, None, unfinished);
+        metrics
+            .join3(nodes, pods)
+            .and_then(|(metrics, nodes, pods)| {
+                let mut allocatable = HashMap::new();
+                for node in nodes.items {
+                    let name = node["metadata"]["name"].as_str().map(str::to_string);
+                    let resources = node["status"]["allocatable"].clone();
+                    if let (Some(name), false) = (name, resources.is_null()) {
+                        allocatable.insert(name, serde_json::from_value(resources)?);
+                    }
+                }
+                let mut requested: HashMap<String, (ResourceList, ResourceList)> = HashMap::new();
+                for pod in pods.items {
+                    if let Some(ref node) = pod.spec.node_name {
+                        let (requests, limits) = requested.entry(node.clone()).or_default();
+                        *requests += &pod.spec.resource_requests();
+                        *limits += &pod.spec.resource_limits();
+                    }
+                }
+                let mut usage = metrics
+                    .items
+                    .into_iter()
+                    .filter_map(|m| {
+                        let name = m.metadata.name.clone()?;
+                        let allocatable = allocatable.remove(&name)?;
+                        let (requests, limits) = requested.remove(&name).unwrap_or_default();
+                        Some(NodeUsage {
+                            name,
+                            timestamp: m.timestamp,
+                            window: m.window,
+                            usage: m.usage,
+                            allocatable,
+                            requests,
+                            limits,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                usage.sort_by(|a, b| a.name.cmp(&b.name));
+                Ok(usage)
+            })
+    }
+
+    /// The usage of the pods matching `label_selector` in `namespace`
+    /// (or all namespaces), by namespace and name, with their requests
+    /// and limits.
+    pub fn top_pods(
+        &self,
+        namespace: Option<&str>,
+        label_selector: &str,
+    ) -> impl Future<Item = Vec<PodUsage>, Error = Error> + Send {
+        let selected = ListOptions {
+            label_selector: label_selector.to_string(),
+            ..Default::default()
+        };
+        let metrics = self.list::<Items<PodMetrics>>(
+            &PodMetrics::GROUP_VERSION_RESOURCE,
+            namespace,
+            selected.clone(),
+        );
+        let pods = self.list::<Items<Pod>>(&Pod::GROUP_VERSION_RESOURCE, namespace, selected);
+        metrics.join(pods).map(|(metrics, pods)| {
+            let mut pods = pods
+                .items
+                .into_iter()
+                .map(|pod| {
+                    let key = (pod.metadata.namespace.clone(), pod.metadata.name.clone());
+                    (key, pod.spec)
+                })
+                .collect::<HashMap<_, _>>();
+            let mut usage = metrics
+                .items
+                .into_iter()
+                .filter_map(|m| {
+                    let key = (m.metadata.namespace.clone(), m.metadata.name.clone());
+                    let spec = pods.remove(&key)?;
+                    Some(pod_usage(m, spec))
+                })
+                .collect::<Vec<_>>();
+            usage.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+            usage
+        })
+    }
+}
+
+fn pod_usage(metrics: PodMetrics, spec: PodSpec) -> PodUsage {
+    let usage = metrics.usage();
+    let containers = metrics
+        .containers
+        .into_iter()
+        .map(|c| {
+            let resources = spec
+                .containers
+                .iter()
+                .chain(&spec.init_containers)
+                .find(|s| s.name == c.name)
+                .and_then(|s| s.resources.clone())
+                .unwrap_or_default();
+            ContainerUsage {
+                name: c.name,
+                usage: c.usage,
+                requests: resources.requests,
+                limits: resources.limits,
+            }
+        })
+        .collect();
+    PodUsage {
+        namespace: metrics.metadata.namespace.unwrap_or_default(),
+        name: metrics.metadata.name.unwrap_or_default(),
+        node: spec.node_name.clone(),
+        timestamp: metrics.timestamp,
+        window: metrics.window,
+        usage,
+        requests: spec.resource_requests(),
+        limits: spec.resource_limits(),
+        containers,
+    }
+}
+
+#[test]
+fn test_top() {
+    use super::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let server = client.server();
+    server
+        .insert(
+            &NODES,
+            &json!({
+                "kind": "Node",
+                "metadata": {"name": "node-1", "labels": {"pool": "a"}},
+                "status": {"allocatable": {"cpu": "4", "memory": "8Gi", "pods": "110"}},
+            }),
+        )
+        .unwrap();
+    server
+        .insert(
+            &NodeMetrics::GROUP_VERSION_RESOURCE,
+            &json!({
+                "kind": "NodeMetrics",
+                "metadata": {"name": "node-1", "labels": {"pool": "a"}},
+                "timestamp": "2024-05-01T10:00:00Z",
+                "window": "20s",
+                "usage": {"cpu": "1", "memory": "2Gi"},
+            }),
+        )
+        .unwrap();
+    let pod = |name: &str, phase: &str| {
+        json!({
+            "kind": "Pod",
+            "metadata": {"name": name, "namespace": "ns", "labels": {"app": "web"}},
+            "spec": {
+                "nodeName": "node-1",
+                "containers": [{
+                    "name": "web",
+                    "resources": {
+                        "requests": {"cpu": "500m", "memory": "1Gi"},
+                        "limits": {"cpu": "2", "memory": "1Gi"},
+                    },
+                }],
+            },
+            "status": {"phase": phase},
+        })
+    };
+    server.insert(&PODS_V1, &pod("web-0", "Running")).unwrap();
+    server.insert(&PODS_V1, &pod("web-1", "Running")).unwrap();
+    server.insert(&PODS_V1, &pod("done", "Succeeded")).unwrap();
+    server
+        .insert(
+            &PodMetrics::GROUP_VERSION_RESOURCE,
+            &json!({
+                "kind": "PodMetrics",
+                "metadata": {"name": "web-0", "namespace": "ns", "labels": {"app": "web"}},
+                "timestamp": "2024-05-01T10:00:00Z",
+                "window": "20s",
+                "containers": [{"name": "web", "usage": {"cpu": "250m", "memory": "512Mi"}}],
+            }),
+        )
+        .unwrap();
+
+    let nodes = client.top_nodes("pool=a").wait().unwrap();
+    assert_eq!(nodes.len(), 1);
+    let node = &nodes[0];
+    assert_eq!(node.name, "node-1");
+    assert_eq!(node.usage_percent("cpu"), Some(25.0));
+    assert_eq!(node.usage_percent("memory"), Some(25.0));
+    // The finished pod doesn't count.
+    assert_eq!(node.requests_percent("cpu"), Some(25.0));
+    assert_eq!(node.limits_percent("cpu"), Some(100.0));
+    assert_eq!(node.usage_percent("nvidia.com/gpu"), None);
+    assert!(client.top_nodes("pool=b").wait().unwrap().is_empty());
+
+    // web-1 has no metrics yet.
+    let pods = client.top_pods(Some("ns"), "app=web").wait().unwrap();
+    assert_eq!(pods.len(), 1);
+    let pod = &pods[0];
+    assert_eq!(
+        (pod.name.as_str(), pod.node.as_deref()),
+        ("web-0", Some("node-1"))
+    );
+    assert_eq!(pod.requests_percent("cpu"), Some(50.0));
+    assert_eq!(pod.limits_percent("cpu"), Some(12.5));
+    assert_eq!(pod.requests_percent("memory"), Some(50.0));
+    assert_eq!(pod.containers[0].limits_percent("memory"), Some(50.0));
+}
+
+#[cfg(test)]
+const PODS_V1: GroupVersionResource<'static> = Pod::GROUP_VERSION_RESOURCE;
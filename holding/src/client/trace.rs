@@ -0,0 +1,284 @@
+//! `tracing` spans for client requests.
+//!
+//! Each HTTP request gets a `request` span, recording its ID (as sent
+//! in the `Audit-ID` header), verb, group, version, resource and
+//! namespace, and (once the response headers arrive) the status and
+//! duration.  Each watch also gets a `watch`
+//! span, which lasts as long as the event stream.
+//!
+//! With no `tracing` subscriber installed, spans are logged through
+//! `log` instead.  Request and response headers are logged at trace
+//! level, with credentials redacted.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::{Future, Poll};
+use hyper::header::{
+    HeaderMap, HeaderName, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+};
+use hyper::{Body, Request};
+use tracing::field::Empty;
+use tracing::Span;
+
+use super::metrics::{MetricsRecorder, RequestLabels};
+use super::middleware::{Middleware, Next, ResponseFuture};
+use super::transport::request_id;
+
+/// Adds trace context headers (eg W3C `traceparent`) to outgoing
+/// requests, so that they can be joined to the caller's distributed
+/// trace.
+///
+/// `inject` is called with the request's span entered, so an
+/// OpenTelemetry propagator can find the trace context from the
+/// current span.
+pub trait Propagator: fmt::Debug + Send + Sync {
+    fn inject(&self, headers: &mut HeaderMap);
+}
+
+/// Shows headers for logging, with the values of those that carry
+/// credentials (`Authorization`, cookies, and any marked sensitive)
+/// replaced by `<redacted>`.
+pub struct RedactedHeaders<'a>(pub &'a HeaderMap);
+
+const CREDENTIALS: [HeaderName; 4] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+impl<'a> fmt::Debug for RedactedHeaders<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, value)| {
+                let value = if value.is_sensitive() || CREDENTIALS.contains(name) {
+                    "<redacted>"
+                } else {
+                    value.to_str().unwrap_or("<binary>")
+                };
+                (name.as_str(), value)
+            }))
+            .finish()
+    }
+}
+
+pub(crate) fn request_span(labels: &RequestLabels, id: &str) -> Span {
+    ::tracing::debug_span!(
+        "request",
+        id = id,
+        verb = labels.verb.as_str(),
+        group = labels.group.as_str(),
+        version = labels.version.as_str(),
+        resource = labels.resource.as_str(),
+        namespace = labels.namespace.as_deref().unwrap_or(""),
+        status = Empty,
+        duration_ms = Empty,
+    )
+}
+
+pub(crate) fn watch_span(labels: &RequestLabels) -> Span {
+    ::tracing::debug_span!(
+        "watch",
+        group = labels.group.as_str(),
+        version = labels.version.as_str(),
+        resource = labels.resource.as_str(),
+        namespace = labels.namespace.as_deref().unwrap_or(""),
+    )
+}
+
+/// A future polled with `span` entered, as `tracing-futures` does for
+/// `std` futures.
+struct InSpan<F> {
+    inner: F,
+    span: Span,
+}
+
+impl<F: Future> Future for InSpan<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        let _enter = self.span.enter();
+        self.inner.poll()
+    }
+}
+
+/// Runs the rest of the stack within a request span, and records
+/// metrics (if enabled).
+#[derive(Debug)]
+pub(crate) struct Instrument {
+    pub(crate) metrics: Option<Arc<dyn MetricsRecorder>>,
+    pub(crate) propagator: Option<Arc<dyn Propagator>>,
+}
+
+impl Middleware for Instrument {
+    fn call(&self, mut req: Request<Body>, next: Next) -> ResponseFuture {
+        let labels = RequestLabels::new(req.method(), req.uri());
+        let id = request_id(req.headers_mut());
+        debug!("Request {}: {} {}", id, req.method(), req.uri());
+        let span = request_span(&labels, &id);
+        if let Some(ref propagator) = self.propagator {
+            span.in_scope(|| propagator.inject(req.headers_mut()));
+        }
+        trace!(
+            "Request {} headers: {:?}",
+            id,
+            RedactedHeaders(req.headers())
+        );
+        let metrics = self.metrics.as_ref().map(|m| {
+            m.request_started(&labels);
+            Arc::clone(m)
+        });
+        let start = Instant::now();
+        let res = InSpan {
+            inner: span.in_scope(|| next.run(req)),
+            span: span.clone(),
+        };
+        Box::new(res.then(move |res| {
+            if let Ok(ref res) = res {
+                trace!(
+                    "Response {} {}: {:?}",
+                    id,
+                    res.status(),
+                    RedactedHeaders(res.headers())
+                );
+            }
+            let status = res.as_ref().ok().map(|r| r.status().as_u16());
+            let elapsed = start.elapsed();
+            match status {
+                Some(status) => span.record("status", status),
+                None => span.record("status", "error"),
+            };
+            span.record("duration_ms", elapsed.as_millis() as u64);
+            if let Some(m) = metrics {
+                m.request_finished(&labels, status, elapsed);
+            }
+            res
+        }))
+    }
+}
+
+#[test]
+fn test_redacted_headers() {
+    use hyper::header::{HeaderValue, ACCEPT};
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer s3cret"));
+    let mut key = HeaderValue::from_static("s3cret");
+    key.set_sensitive(true);
+    headers.insert("x-api-key", key);
+    let shown = format!("{:?}", RedactedHeaders(&headers));
+    assert!(!shown.contains("s3cret"), "{}", shown);
+    assert!(
+        shown.contains("\"accept\": \"application/json\""),
+        "{}",
+        shown
+    );
+    assert!(
+        shown.contains("\"authorization\": \"<redacted>\""),
+        "{}",
+        shown
+    );
+}
+
+#[test]
+fn test_request_span() {
+    use futures::future;
+    use hyper::header::HeaderValue;
+    use std::sync::Mutex;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+
+    use api::core::v1::{ConfigMap, TypedResource};
+    use api::meta::v1::GetOptions;
+    use client::config::ConfigContext;
+    use client::fake::FakeApiServer;
+    use client::Client;
+
+    /// Keeps track of which spans are entered.
+    #[derive(Debug, Default)]
+    struct Spans {
+        names: Mutex<Vec<&'static str>>,
+        entered: Mutex<Vec<u64>>,
+    }
+
+    impl Spans {
+        fn current(&self) -> Option<&'static str> {
+            let id = *self.entered.lock().unwrap().last()?;
+            Some(self.names.lock().unwrap()[id as usize - 1])
+        }
+    }
+
+    struct Capture(Arc<Spans>);
+
+    impl ::tracing::Subscriber for Capture {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut names = self.0.names.lock().unwrap();
+            names.push(span.metadata().name());
+            Id::from_u64(names.len() as u64)
+        }
+        fn record(&self, _: &Id, _: &Record) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event) {}
+        fn enter(&self, span: &Id) {
+            self.0.entered.lock().unwrap().push(span.into_u64());
+        }
+        fn exit(&self, span: &Id) {
+            assert_eq!(self.0.entered.lock().unwrap().pop(), Some(span.into_u64()));
+        }
+    }
+
+    /// Sets a header naming the current span.
+    #[derive(Debug)]
+    struct Stub(Arc<Spans>);
+
+    impl Propagator for Stub {
+        fn inject(&self, headers: &mut HeaderMap) {
+            let name = self.0.current().unwrap_or("none");
+            headers.insert("x-span", HeaderValue::from_static(name));
+        }
+    }
+
+    /// Notes the current span when first polled, rather than when
+    /// called, like most middleware that does any IO.
+    #[derive(Debug)]
+    struct Polled(Arc<Spans>, Arc<Mutex<Vec<Option<&'static str>>>>);
+
+    impl Middleware for Polled {
+        fn call(&self, req: Request<Body>, next: Next) -> ResponseFuture {
+            assert_eq!(req.headers()["x-span"], "request");
+            let spans = Arc::clone(&self.0);
+            let seen = Arc::clone(&self.1);
+            Box::new(future::lazy(move || {
+                seen.lock().unwrap().push(spans.current());
+                next.run(req)
+            }))
+        }
+    }
+
+    let spans = Arc::new(Spans::default());
+    let seen = Arc::new(Mutex::new(vec![]));
+    let mut config = ConfigContext::default();
+    config.cluster.server = "http://fake.invalid".to_string();
+    let client = Client::builder()
+        .config(config)
+        .propagate_trace_context(Arc::new(Stub(Arc::clone(&spans))))
+        .middleware(Arc::new(Polled(Arc::clone(&spans), Arc::clone(&seen))))
+        .build_with_backend(Arc::new(FakeApiServer::new()))
+        .unwrap();
+
+    ::tracing::subscriber::with_default(Capture(Arc::clone(&spans)), || {
+        let res = client
+            .get::<ConfigMap>(
+                &ConfigMap::GROUP_VERSION_RESOURCE,
+                Some("ns"),
+                "missing",
+                GetOptions::default(),
+            )
+            .wait();
+        assert!(res.is_err());
+    });
+    assert_eq!(*seen.lock().unwrap(), vec![Some("request")]);
+    assert!(spans.entered.lock().unwrap().is_empty());
+}
@@ -0,0 +1,431 @@
+//! The HTTP client shared by all clones of a `Client`, plus the
+//! per-request behaviour configured through `ClientBuilder`.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Instant;
+
+use failure::{Error, ResultExt};
+use flate2::read::GzDecoder;
+use futures::future::{self, Either, Loop};
+use futures::{Future, Stream};
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH,
+    USER_AGENT,
+};
+use hyper::{Body, Chunk, Method, Request, Response, StatusCode, Uri};
+use tokio::timer::Delay;
+
+use super::backend::Backend;
+use super::metrics::RequestLabels;
+use super::middleware::{Middleware, Next, ResponseFuture, Stack};
+use super::retry::{self, RetryPolicy};
+use super::watch;
+use super::{BodyTooLarge, DEFAULT_MAX_BODY_SIZE};
+
+#[derive(Debug)]
+pub(crate) struct Transport {
+    pub(crate) backend: Arc<dyn Backend>,
+    stack: Arc<Stack>,
+    /// For requests upgrading their connection (exec), if they need a
+    /// different backend from `backend`; see `set_upgrade_backend`.
+    upgrade_stack: Option<Arc<Stack>>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) compression: bool,
+    /// The longest line accepted in watch and log streams.
+    pub(crate) max_line_length: usize,
+    /// The largest response body read into memory.
+    pub(crate) max_body_size: usize,
+}
+
+/// The apiserver uses an `Audit-ID` request header as the ID of the
+/// request in its audit log, and returns it as a response header.
+const AUDIT_ID: &str = "audit-id";
+
+/// The ID of this request, generating one if it doesn't have one yet.
+pub(crate) fn request_id(headers: &mut HeaderMap) -> String {
+    let id = headers
+        .entry(HeaderName::from_static(AUDIT_ID))
+        .unwrap()
+        .or_insert_with(|| {
+            let h1 = RandomState::new().build_hasher().finish();
+            let h2 = RandomState::new().build_hasher().finish();
+            HeaderValue::from_str(&format!("{:016x}{:016x}", h1, h2)).unwrap()
+        });
+    String::from_utf8_lossy(id.as_bytes()).into_owned()
+}
+
+/// Names a request in errors, eg: `GET
+/// /apis/apps/v1/namespaces/default/deployments/web (get
+/// apps/v1/deployments)`.  The query is left out, as it may be long.
+pub(crate) fn describe(method: &Method, uri: &Uri) -> String {
+    let labels = RequestLabels::new(method, uri);
+    if labels.resource.is_empty() {
+        return format!("{} {}", method, uri.path());
+    }
+    let group = if labels.group.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", labels.group)
+    };
+    format!(
+        "{} {} ({} {}{}/{})",
+        method,
+        uri.path(),
+        labels.verb,
+        group,
+        labels.version,
+        labels.resource
+    )
+}
+
+/// The apiserver's audit ID for a response, if it sent one.
+pub(crate) fn audit_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(AUDIT_ID)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// The `User-Agent` sent when none is configured.
+pub(crate) const DEFAULT_USER_AGENT: &str = concat!("kubernetes-rs/", env!("CARGO_PKG_VERSION"));
+
+impl Transport {
+    pub(crate) fn new(backend: Arc<dyn Backend>, middleware: Vec<Arc<dyn Middleware>>) -> Self {
+        Transport {
+            stack: Arc::new(Stack::new(middleware, Arc::clone(&backend))),
+            backend,
+            upgrade_stack: None,
+            retry: None,
+            compression: true,
+            max_line_length: watch::DEFAULT_MAX_LINE_LENGTH,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Send a single request through the middleware stack.
+    pub(crate) fn request(&self, req: Request<Body>) -> ResponseFuture {
+        Next::new(Arc::clone(&self.stack)).run(req)
+    }
+
+    /// Send requests that upgrade their connection through the same
+    /// middleware to `backend`.  Upgrades need HTTP/1.1, so a client
+    /// that may be using HTTP/2 sends them over a connection of their
+    /// own.
+    pub(crate) fn set_upgrade_backend(&mut self, backend: Arc<dyn Backend>) {
+        self.upgrade_stack = Some(Arc::new(self.stack.with_backend(backend)));
+    }
+
+    /// Send a request that upgrades its connection, such as to a
+    /// WebSocket.
+    pub(crate) fn upgrade(&self, req: Request<Body>) -> ResponseFuture {
+        let stack = self.upgrade_stack.as_ref().unwrap_or(&self.stack);
+        Next::new(Arc::clone(stack)).run(req)
+    }
+
+    /// Send a request and read the whole (decompressed) response body,
+    /// retrying according to the retry policy (if any).
+    ///
+    /// Also returns the audit ID of the request, which is shared by all
+    /// retries, and the response headers.
+    pub(crate) fn send(
+        transport: Arc<Self>,
+        mut req: Request<Body>,
+    ) -> impl Future<Item = (StatusCode, String, HeaderMap, Chunk), Error = Error> + Send {
+        let id = request_id(req.headers_mut());
+        let max = transport.max_body_size;
+        let failed = format!(
+            "{} failed (request {})",
+            describe(req.method(), req.uri()),
+            id
+        );
+        if transport.compression {
+            req.headers_mut()
+                .entry(ACCEPT_ENCODING)
+                .unwrap()
+                .or_insert_with(|| HeaderValue::from_static("gzip"));
+        }
+
+        let policy = match transport.retry {
+            Some(ref p) => p.clone(),
+            None => {
+                return Either::A(
+                    transport
+                        .request(req)
+                        .and_then(move |res| read_response(res, max))
+                        .map_err(|e| e.context(failed).into())
+                        .and_then(move |res| decode_response(res, id, max)),
+                )
+            }
+        };
+
+        // The body has to be buffered so that it can be resent.
+        let (parts, body) = req.into_parts();
+        let idempotent = RetryPolicy::is_idempotent(&parts.method);
+        let f = body.concat2().from_err().and_then(move |body| {
+            let body = body.into_bytes();
+            future::loop_fn(0, move |attempt| {
+                let mut req = Request::builder();
+                req.method(parts.method.clone())
+                    .uri(parts.uri.clone())
+                    .version(parts.version);
+                for (k, v) in &parts.headers {
+                    req.header(k, v.clone());
+                }
+                let req = req.body(Body::from(body.clone())).unwrap();
+
+                let policy = policy.clone();
+                let failed = failed.clone();
+                let backend = Arc::clone(&transport.backend);
+                transport
+                    .request(req)
+                    .and_then(move |res| read_response(res, max))
+                    .then(move |res| {
+                        let delay = match res {
+                            Err(ref e) if idempotent && backend.is_connect_error(e) => {
+                                Some(policy.delay(attempt, None))
+                            }
+                            Ok((status, ref headers, ref body))
+                                if RetryPolicy::is_retryable(status)
+                                    && (idempotent || status == StatusCode::TOO_MANY_REQUESTS) =>
+                            {
+                                Some(
+                                    policy
+                                        .delay(attempt, retry::retry_after(status, headers, body)),
+                                )
+                            }
+                            _ => None,
+                        };
+                        let retry_at = delay
+                            .filter(|_| attempt < policy.max_retries)
+                            .and_then(|delay| Some((delay, Instant::now().checked_add(delay)?)));
+                        match retry_at {
+                            Some((delay, at)) => {
+                                debug!(
+                                    "Retrying in {:?} after {}",
+                                    delay,
+                                    match res {
+                                        Ok((status, _, _)) => status.to_string(),
+                                        Err(e) => e.to_string(),
+                                    }
+                                );
+                                Either::A(
+                                    Delay::new(at)
+                                        .from_err()
+                                        .map(move |_| Loop::Continue(attempt + 1)),
+                                )
+                            }
+                            _ => Either::B(future::result(
+                                res.map(Loop::Break).map_err(|e| e.context(failed).into()),
+                            )),
+                        }
+                    })
+            })
+        });
+        Either::B(f.and_then(move |res| decode_response(res, id, max)))
+    }
+}
+
+fn read_response(
+    res: Response<Body>,
+    max: usize,
+) -> impl Future<Item = (StatusCode, HeaderMap, Chunk), Error = Error> {
+    let status = res.status();
+    let headers = res.headers().clone();
+    // No need to read what is known to be too much.
+    let length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if length.is_some_and(|length| length > max as u64) {
+        return Either::A(future::err(BodyTooLarge { max }.into()));
+    }
+    Either::B(read_body(res.into_body(), max).map(move |body| (status, headers, body)))
+}
+
+/// Read all of `body`, failing with `BodyTooLarge` if it is longer
+/// than `max` bytes.
+pub(crate) fn read_body(body: Body, max: usize) -> impl Future<Item = Chunk, Error = Error> + Send {
+    body.from_err::<Error>()
+        .fold(Vec::new(), move |mut buf, chunk| {
+            if buf.len() + chunk.len() > max {
+                return Err(Error::from(BodyTooLarge { max }));
+            }
+            buf.extend_from_slice(&chunk);
+            Ok(buf)
+        })
+        .map(Chunk::from)
+}
+
+/// Sets the `User-Agent` header, if the request doesn't have one.
+#[derive(Debug)]
+pub(crate) struct UserAgent(pub(crate) HeaderValue);
+
+impl Middleware for UserAgent {
+    fn call(&self, mut req: Request<Body>, next: Next) -> ResponseFuture {
+        req.headers_mut()
+            .entry(USER_AGENT)
+            .unwrap()
+            .or_insert_with(|| self.0.clone());
+        next.run(req)
+    }
+}
+
+/// Undo any `Content-Encoding`, and find the audit ID (defaulting to
+/// the `id` we sent).  The decoded body is limited to `max` bytes too.
+fn decode_response(
+    (status, headers, body): (StatusCode, HeaderMap, Chunk),
+    id: String,
+    max: usize,
+) -> Result<(StatusCode, String, HeaderMap, Chunk), Error> {
+    let id = audit_id(&headers).unwrap_or(id);
+    let encoding = headers.get(CONTENT_ENCODING).map(HeaderValue::as_bytes);
+    match encoding {
+        None | Some(b"identity") => Ok((status, id, headers, body)),
+        Some(b"gzip") => {
+            let mut decoded = Vec::new();
+            GzDecoder::new(body.as_ref())
+                .take(max as u64 + 1)
+                .read_to_end(&mut decoded)
+                .context("Unable to decompress gzip response")?;
+            if decoded.len() > max {
+                return Err(BodyTooLarge { max }.into());
+            }
+            Ok((status, id, headers, decoded.into()))
+        }
+        Some(other) => Err(format_err!(
+            "Unsupported response Content-Encoding: {}",
+            String::from_utf8_lossy(other)
+        )),
+    }
+}
+
+#[test]
+fn test_decode_response() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let mut headers = HeaderMap::new();
+    let (_, id, _, body) = decode_response(
+        (StatusCode::OK, headers.clone(), "plain".into()),
+        "ours".into(),
+        100,
+    )
+    .unwrap();
+    assert_eq!(body.as_ref(), b"plain");
+    assert_eq!(id, "ours");
+
+    let mut e = GzEncoder::new(Vec::new(), Compression::default());
+    e.write_all(b"{\"kind\":\"PodList\"}").unwrap();
+    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    headers.insert(AUDIT_ID, HeaderValue::from_static("theirs"));
+    let body = e.finish().unwrap().into();
+    let (_, id, _, body) =
+        decode_response((StatusCode::OK, headers.clone(), body), "ours".into(), 100).unwrap();
+    assert_eq!(id, "theirs");
+    assert_eq!(body.as_ref(), b"{\"kind\":\"PodList\"}");
+
+    assert!(decode_response(
+        (StatusCode::OK, headers.clone(), "not gzip".into()),
+        "".into(),
+        100
+    )
+    .is_err());
+
+    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("br"));
+    assert!(decode_response((StatusCode::OK, headers, "".into()), "".into(), 100).is_err());
+}
+
+#[test]
+fn test_max_body_size() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let body = |chunks: Vec<&'static str>| {
+        Body::wrap_stream(futures::stream::iter_ok::<_, hyper::Error>(chunks))
+    };
+    let read = read_body(body(vec!["abc", "def"]), 6).wait().unwrap();
+    assert_eq!(read.as_ref(), b"abcdef");
+    let err = read_body(body(vec!["abc", "defg"]), 6).wait().unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&BodyTooLarge { max: 6 }));
+
+    // Refused up front, from Content-Length.
+    let res = Response::builder()
+        .header(CONTENT_LENGTH, "7")
+        .body(body(vec![]))
+        .unwrap();
+    let err = read_response(res, 6).wait().unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&BodyTooLarge { max: 6 }));
+
+    // And after decompression.
+    let mut e = GzEncoder::new(Vec::new(), Compression::default());
+    e.write_all(&[b'x'; 1000]).unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    let gzipped: Chunk = e.finish().unwrap().into();
+    assert!(gzipped.len() < 100);
+    let err = decode_response((StatusCode::OK, headers, gzipped), "".into(), 100).unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&BodyTooLarge { max: 100 }));
+}
+
+#[test]
+fn test_request_id() {
+    let mut headers = HeaderMap::new();
+    let id = request_id(&mut headers);
+    assert_eq!(id.len(), 32);
+    assert_eq!(request_id(&mut headers), id);
+    assert_ne!(request_id(&mut HeaderMap::new()), id);
+}
+
+#[test]
+fn test_upgrade_backend() {
+    use std::sync::Mutex;
+
+    /// Answers with its name.
+    #[derive(Debug)]
+    struct Named(&'static str);
+
+    impl Backend for Named {
+        fn call(&self, _req: Request<Body>) -> ResponseFuture {
+            Box::new(future::ok(Response::new(Body::from(self.0))))
+        }
+    }
+
+    /// Counts the requests through it.
+    #[derive(Debug, Default)]
+    struct Count(Mutex<usize>);
+
+    impl Middleware for Count {
+        fn call(&self, req: Request<Body>, next: Next) -> ResponseFuture {
+            *self.0.lock().unwrap() += 1;
+            next.run(req)
+        }
+    }
+
+    let body = |res: ResponseFuture| {
+        res.and_then(|res| res.into_body().concat2().from_err())
+            .wait()
+            .unwrap()
+    };
+    let count = Arc::new(Count::default());
+    let mut transport = Transport::new(Arc::new(Named("main")), vec![count.clone()]);
+    assert_eq!(
+        body(transport.upgrade(Request::new(Body::empty()))).as_ref(),
+        b"main"
+    );
+
+    transport.set_upgrade_backend(Arc::new(Named("http1")));
+    assert_eq!(
+        body(transport.upgrade(Request::new(Body::empty()))).as_ref(),
+        b"http1"
+    );
+    assert_eq!(
+        body(transport.request(Request::new(Body::empty()))).as_ref(),
+        b"main"
+    );
+    assert_eq!(*count.0.lock().unwrap(), 3);
+}
@@ -0,0 +1,363 @@
+//! Client handles for a single compiled-in resource type.
+
+use failure::Error;
+use futures::{Future, Stream};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::marker::PhantomData;
+
+use api::apps::v1::Deployment;
+use api::autoscaling::v1::Scale;
+use api::batch::v1::Job;
+use api::core::v1::{Pod, TypedResource};
+use api::meta::v1::{DeleteOptions, GetOptions, ListOptions, Metadata, WatchEvent};
+use api::meta::GroupVersionResource;
+use api::Integer;
+use k8sclient::selector::LabelSelector;
+
+use super::pager::Page;
+use super::watcher::{Event, WatcherOptions};
+use super::{Client, NamespacedClient};
+
+/// Performs operations on the resource serving `T`, eg:
+///
+/// ```ignore
+/// client.namespace("team-a").resource::<Pod>().get("web-0")
+/// ```
+///
+/// Create/update use the namespace from the object's metadata, like
+/// the equivalent `Client` methods.
+pub struct ResourceClient<'a, T> {
+    client: &'a Client,
+    namespace: Option<&'a str>,
+    // fn() -> T, so the handle is Send/Sync regardless of T
+    _type: PhantomData<fn() -> T>,
+}
+
+// Not derived, since that would require `T: Clone` and `T: Debug`
+impl<'a, T> Clone for ResourceClient<'a, T> {
+    fn clone(&self) -> Self {
+        ResourceClient {
+            client: self.client,
+            namespace: self.namespace,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: TypedResource> fmt::Debug for ResourceClient<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResourceClient")
+            .field("gvr", &T::GROUP_VERSION_RESOURCE)
+            .field("namespace", &self.namespace)
+            .finish()
+    }
+}
+
+impl<'a> Client {
+    /// Handle for the resource serving `T`.  For namespaced resources,
+    /// list and watch span all namespaces.
+    pub fn resource<T: TypedResource>(&'a self) -> ResourceClient<'a, T> {
+        ResourceClient {
+            client: self,
+            namespace: None,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl Client {
+    /// Get an object, deriving the resource from its type, eg:
+    /// `client.get_typed::<Pod>(Some("kube-system"), "etcd")`.
+    ///
+    /// `namespace` is ignored for cluster-scoped types.
+    ///
+    /// This can't be `Client::get::<Pod>(ns, name)`: that name is taken
+    /// by the dynamic `get`, which takes the resource as an argument and
+    /// which everything else is built on, and Rust has no overloading.
+    /// `client.resource::<Pod>().get(name)` is the other typed spelling.
+    pub fn get_typed<K>(
+        &self,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> impl Future<Item = K, Error = Error> + Send
+    where
+        K: TypedResource + DeserializeOwned + Send + 'static,
+    {
+        let ns = if K::NAMESPACED { namespace } else { None };
+        self.get(&K::GROUP_VERSION_RESOURCE, ns, name, Default::default())
+    }
+
+    /// List objects, deriving the resource from their type.  A `None`
+    /// namespace lists across all namespaces.
+    pub fn list_typed<K>(
+        &self,
+        namespace: Option<&str>,
+        opts: ListOptions,
+    ) -> impl Future<Item = K::List, Error = Error> + Send
+    where
+        K: TypedResource,
+        K::List: DeserializeOwned + Send + 'static,
+    {
+        let ns = if K::NAMESPACED { namespace } else { None };
+        self.list(&K::GROUP_VERSION_RESOURCE, ns, opts)
+    }
+
+    /// Watch the objects of type `K` matching `selector`, decoded.  A
+    /// `None` namespace watches across all namespaces.
+    pub fn watch_selected<K>(
+        &self,
+        namespace: Option<&str>,
+        selector: LabelSelector,
+    ) -> impl Stream<Item = WatchEvent<K>, Error = Error> + Send
+    where
+        K: TypedResource + DeserializeOwned + Send + 'static,
+    {
+        let ns = if K::NAMESPACED { namespace } else { None };
+        let opts = ListOptions {
+            label_selector: selector.into(),
+            ..Default::default()
+        };
+        self.watch_list_decoded(&K::GROUP_VERSION_RESOURCE, ns, opts)
+    }
+
+    /// `watch_selected` for Pods.
+    pub fn watch_pods(
+        &self,
+        namespace: Option<&str>,
+        selector: LabelSelector,
+    ) -> impl Stream<Item = WatchEvent<Pod>, Error = Error> + Send {
+        self.watch_selected(namespace, selector)
+    }
+
+    /// `watch_selected` for Deployments.
+    pub fn watch_deployments(
+        &self,
+        namespace: Option<&str>,
+        selector: LabelSelector,
+    ) -> impl Stream<Item = WatchEvent<Deployment>, Error = Error> + Send {
+        self.watch_selected(namespace, selector)
+    }
+
+    /// `watch_selected` for Jobs.
+    pub fn watch_jobs(
+        &self,
+        namespace: Option<&str>,
+        selector: LabelSelector,
+    ) -> impl Stream<Item = WatchEvent<Job>, Error = Error> + Send {
+        self.watch_selected(namespace, selector)
+    }
+}
+
+impl<'a> NamespacedClient<'a> {
+    /// Handle for the resource serving `T`, restricted to this
+    /// namespace if `T` is namespaced.
+    pub fn resource<T: TypedResource>(&self) -> ResourceClient<'a, T> {
+        ResourceClient {
+            client: self.client,
+            namespace: if T::NAMESPACED {
+                Some(self.namespace)
+            } else {
+                None
+            },
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> ResourceClient<'a, T>
+where
+    T: TypedResource + Metadata + Serialize + DeserializeOwned + Send + 'static,
+    T::List: DeserializeOwned + Send + 'static,
+{
+    fn gvr(&self) -> &'static GroupVersionResource<'static> {
+        &T::GROUP_VERSION_RESOURCE
+    }
+
+    pub fn get(&self, name: &str) -> impl Future<Item = T, Error = Error> + Send {
+        self.get_with_options(name, Default::default())
+    }
+
+    pub fn get_with_options(
+        &self,
+        name: &str,
+        opts: GetOptions,
+    ) -> impl Future<Item = T, Error = Error> + Send {
+        self.client.get(self.gvr(), self.namespace, name, opts)
+    }
+
+    /// Like `get`, but returns `None` if the object does not exist.
+    pub fn get_opt(&self, name: &str) -> impl Future<Item = Option<T>, Error = Error> + Send {
+        self.client
+            .get_opt(self.gvr(), self.namespace, name, Default::default())
+    }
+
+    pub fn list(&self, opts: ListOptions) -> impl Future<Item = T::List, Error = Error> + Send {
+        self.client.list(self.gvr(), self.namespace, opts)
+    }
+
+    /// Like `list`, but follows `continue` tokens and yields individual items.
+    pub fn iter(&self, opts: ListOptions) -> impl Stream<Item = T, Error = Error> + Send {
+        self.client
+            ._do_iter::<T::List>(T::GROUP_VERSION_RESOURCE, self.namespace, opts)
+    }
+
+    /// Like `iter`, but yields each page, with the list's metadata.
+    pub fn pages(&self, opts: ListOptions) -> impl Stream<Item = Page<T>, Error = Error> + Send {
+        self.client
+            ._do_pages::<T::List>(T::GROUP_VERSION_RESOURCE, self.namespace, opts)
+    }
+
+    pub fn watch(
+        &self,
+        name: &str,
+        opts: ListOptions,
+    ) -> impl Stream<Item = WatchEvent, Error = Error> + Send {
+        self.client.watch(self.gvr(), self.namespace, name, opts)
+    }
+
+    pub fn watch_list(
+        &self,
+        opts: ListOptions,
+    ) -> impl Stream<Item = WatchEvent, Error = Error> + Send {
+        self.client.watch_list(self.gvr(), self.namespace, opts)
+    }
+
+    /// Like `watch_list`, with the objects decoded.
+    pub fn watch_typed(
+        &self,
+        opts: ListOptions,
+    ) -> impl Stream<Item = WatchEvent<T>, Error = Error> + Send {
+        self.client
+            .watch_list_decoded(self.gvr(), self.namespace, opts)
+    }
+
+    /// The objects, as they are and then as they change; see the
+    /// `watcher` module.
+    pub fn watcher(
+        &self,
+        opts: WatcherOptions,
+    ) -> impl Stream<Item = Event<T>, Error = Error> + Send {
+        self.client.watcher(self.gvr(), self.namespace, opts)
+    }
+
+    pub fn create(
+        &self,
+        value: &T,
+        opts: GetOptions,
+    ) -> impl Future<Item = T, Error = Error> + Send {
+        self.client.create(self.gvr(), value, opts)
+    }
+
+    pub fn update(&self, value: &T) -> impl Future<Item = T, Error = Error> + Send {
+        self.client.update(self.gvr(), value)
+    }
+
+    pub fn patch<P>(
+        &self,
+        name: &str,
+        patch_type: &str,
+        value: &P,
+    ) -> impl Future<Item = T, Error = Error> + Send
+    where
+        P: Serialize,
+    {
+        self.client
+            .patch(self.gvr(), self.namespace, name, patch_type, value)
+    }
+
+    pub fn delete(
+        &self,
+        name: &str,
+        opts: DeleteOptions,
+    ) -> impl Future<Item = (), Error = Error> + Send {
+        self.client.delete(self.gvr(), self.namespace, name, opts)
+    }
+
+    /// See `Client::get_scale`.
+    pub fn get_scale(&self, name: &str) -> impl Future<Item = Scale, Error = Error> + Send {
+        self.client.get_scale(self.gvr(), self.namespace, name)
+    }
+
+    /// See `Client::scale`.
+    pub fn scale(
+        &self,
+        name: &str,
+        replicas: Integer,
+    ) -> impl Future<Item = Scale, Error = Error> + Send {
+        self.client
+            .scale(self.gvr(), self.namespace, name, replicas)
+    }
+
+    /// See `Client::scale_and_wait`.
+    pub fn scale_and_wait(
+        &self,
+        name: &str,
+        replicas: Integer,
+    ) -> impl Future<Item = Scale, Error = Error> + Send {
+        self.client
+            .scale_and_wait(self.gvr(), self.namespace, name, replicas)
+    }
+}
+
+#[test]
+fn resource_namespace() {
+    use api::core::v1::{Namespace, Pod};
+    use client::config::ConfigContext;
+    use hyper::client::HttpConnector;
+
+    let http = HttpConnector::new(1);
+    let client = Client::new_from_context(http, ConfigContext::default()).unwrap();
+
+    let pods = client.namespace("team-a").resource::<Pod>();
+    assert_eq!(pods.namespace, Some("team-a"));
+    assert_eq!(pods.gvr().resource, "pods");
+
+    // Cluster-scoped resources ignore the namespace
+    let namespaces = client.namespace("team-a").resource::<Namespace>();
+    assert_eq!(namespaces.namespace, None);
+
+    assert_eq!(client.resource::<Pod>().namespace, None);
+
+    // Older versions of a type are served from their own group version
+    let deployments = client.resource::<::api::apps::v1beta2::Deployment>();
+    assert_eq!(deployments.gvr().group, "apps");
+    assert_eq!(deployments.gvr().version, "v1beta2");
+}
+
+#[test]
+fn watch_pods() {
+    use api::meta::v1::EventType;
+    use client::fake::FakeClient;
+
+    let client = FakeClient::new();
+    let server = client.server();
+    let pods = Pod::GROUP_VERSION_RESOURCE;
+    for (ns, name, app) in &[("a", "web", "web"), ("a", "db", "db"), ("b", "web", "web")] {
+        server
+            .insert(
+                &pods,
+                &json!({
+                    "apiVersion": "v1",
+                    "kind": "Pod",
+                    "metadata": {"name": name, "namespace": ns, "labels": {"app": app}},
+                }),
+            )
+            .unwrap();
+    }
+
+    let mut events = client
+        .watch_pods(Some("a"), LabelSelector::new().eq("app", "web"))
+        .wait();
+    let event = events.next().unwrap().unwrap();
+    assert_eq!(event.typ, EventType::Added);
+    assert_eq!(event.object.metadata.name.as_deref(), Some("web"));
+    assert_eq!(event.object.metadata.namespace.as_deref(), Some("a"));
+
+    let mut events = client.watch_pods(None, LabelSelector::new()).wait();
+    for _ in 0..3 {
+        let event = events.next().unwrap().unwrap();
+        assert_eq!(event.typ, EventType::Added);
+    }
+}
@@ -0,0 +1,66 @@
+//! Apiserver URLs.
+
+use std::fmt;
+
+use failure::{Error, ResultExt};
+use serde::Serialize;
+use serde_urlencoded;
+use url::Url;
+
+use api::meta::GroupVersionResource;
+
+/// The URL of `segments` on the apiserver at `server`.
+pub(crate) fn path_url(server: &str, segments: &[&str]) -> Result<Url, Error> {
+    let mut url: Url = server.parse()?;
+    url.set_query(None);
+    url.path_segments_mut()
+        .map_err(|_| format_err!("URL scheme does not support paths"))?
+        .pop_if_empty()
+        .extend(segments);
+    Ok(url)
+}
+
+/// The URL, under the apiserver's `url`, of the resource `gvr` (in
+/// `namespace`, if it is namespaced), or the object `name`, with `opts`
+/// as its query.
+pub(crate) fn resource_url<O>(
+    mut url: Url,
+    gvr: &GroupVersionResource,
+    namespace: Option<&str>,
+    name: Option<&str>,
+    opts: O,
+) -> Result<Url, Error>
+where
+    O: Serialize + fmt::Debug,
+{
+    {
+        let mut path = url
+            .path_segments_mut()
+            .map_err(|_| format_err!("URL scheme does not support paths"))?;
+        /* This knowledge should perhaps be pushed into the group itself */
+        path.push(if gvr.group.is_empty() && gvr.version == "v1" {
+            "api"
+        } else {
+            "apis"
+        });
+        if !gvr.group.is_empty() {
+            path.push(gvr.group);
+        }
+        path.push(gvr.version);
+        namespace.map(|ns| path.extend(&["namespaces", ns]));
+        path.push(gvr.resource);
+        name.map(|n| path.push(n));
+    }
+
+    serde_urlencoded::to_string(&opts)
+        .map(|query| {
+            let q = if !query.is_empty() {
+                Some(query.as_str())
+            } else {
+                None
+            };
+            url.set_query(q)
+        })
+        .with_context(|e| format!("Unable to encode URL parameters {}", e))?;
+    Ok(url)
+}
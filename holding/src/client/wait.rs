@@ -0,0 +1,424 @@
+//! Waiting for objects to reach some state.
+
+use failure::Error;
+use futures::future::{self, Loop};
+use futures::{Future, Stream};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::timer::Timeout;
+
+use api::meta::v1::{DeleteOptions, EventType, ListOptions, ObjectMeta, Status};
+use api::meta::GroupVersionResource;
+use k8sclient::error::ApiError;
+use k8sclient::selector::{fields, FieldSelector};
+
+use super::{status_code, Client};
+
+#[derive(Debug, Fail)]
+#[fail(display = "{} was deleted while waiting", name)]
+pub struct DeletedError {
+    pub name: String,
+}
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "{} not deleted after {:?}, pending finalizers: {:?}",
+    name, after, finalizers
+)]
+pub struct DeletionStalledError {
+    pub name: String,
+    pub after: Duration,
+    pub finalizers: Vec<String>,
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "timed out after {:?}", after)]
+pub struct TimeoutError {
+    pub after: Duration,
+}
+
+fn resource_version(object: &Value) -> String {
+    object["metadata"]["resourceVersion"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// An owned GroupVersionResource, for use in `'static` futures.
+type OwnedGvr = (String, String, String);
+
+fn owned_gvr(gvr: &GroupVersionResource) -> OwnedGvr {
+    (
+        gvr.group.to_string(),
+        gvr.version.to_string(),
+        gvr.resource.to_string(),
+    )
+}
+
+/// Watch a single named object, starting from resourceVersion `rv`,
+/// until `handle` returns something.  Watches are restarted when the
+/// server closes them.
+fn watch_object<T, F>(
+    client: Client,
+    gvr: OwnedGvr,
+    namespace: Option<String>,
+    name: String,
+    rv: String,
+    handle: F,
+) -> impl Future<Item = T, Error = Error> + Send
+where
+    T: Send + 'static,
+    F: Fn(EventType, Value) -> Result<Option<T>, Error> + Send + Sync + 'static,
+{
+    let handle = Arc::new(handle);
+
+    future::loop_fn(rv, move |rv| {
+        let opts = ListOptions {
+            field_selector: FieldSelector::new()
+                .eq(fields::METADATA_NAME, name.as_str())
+                .into(),
+            resource_version: rv,
+            ..Default::default()
+        };
+        let gvr = GroupVersionResource {
+            group: &gvr.0,
+            version: &gvr.1,
+            resource: &gvr.2,
+        };
+        let last_rv = Arc::new(Mutex::new(opts.resource_version.clone()));
+        let last_rv2 = Arc::clone(&last_rv);
+        let handle = Arc::clone(&handle);
+
+        client
+            .watch_list(&gvr, namespace.as_deref(), opts)
+            .and_then(move |ev| -> Result<Option<T>, Error> {
+                if ev.typ == EventType::Error {
+                    let status: Status = serde_json::from_value(ev.object)?;
+                    let err = ApiError::from(status);
+                    if err.is_expired() {
+                        // resourceVersion too old; start again
+                        *last_rv.lock().unwrap() = String::new();
+                        return Ok(None);
+                    }
+                    return Err(err.into());
+                }
+                *last_rv.lock().unwrap() = resource_version(&ev.object);
+                handle(ev.typ, ev.object)
+            })
+            .filter_map(|o| o)
+            .into_future()
+            .map_err(|(e, _)| e)
+            .map(move |(found, _)| match found {
+                Some(t) => Loop::Break(t),
+                None => Loop::Continue(last_rv2.lock().unwrap().clone()),
+            })
+    })
+}
+
+/// Wait until the named object satisfies `cond`, and return it.
+///
+/// Canned conditions include `Pod::is_ready`, `Deployment::is_available`
+/// and `Job::is_complete`, eg:
+///
+/// ```ignore
+/// await_condition(&client, &pods, Some("default"), "web-0", Pod::is_ready)
+/// ```
+///
+/// Fails with `DeletedError` if the object is deleted.  The object need
+/// not exist yet.  Never times out by itself; see `timeout`.
+pub fn await_condition<T, F>(
+    client: &Client,
+    gvr: &GroupVersionResource,
+    namespace: Option<&str>,
+    name: &str,
+    cond: F,
+) -> impl Future<Item = T, Error = Error> + Send
+where
+    T: DeserializeOwned + Send + 'static,
+    F: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    let owned_name = name.to_string();
+    watch_object(
+        client.clone(),
+        owned_gvr(gvr),
+        namespace.map(String::from),
+        name.to_string(),
+        String::new(),
+        move |typ, object| match typ {
+            EventType::Deleted => Err(DeletedError {
+                name: owned_name.clone(),
+            }
+            .into()),
+            _ => {
+                let obj: T = serde_json::from_value(object)?;
+                Ok(if cond(&obj) { Some(obj) } else { None })
+            }
+        },
+    )
+}
+
+fn is_not_found(e: &Error) -> bool {
+    status_code(e) == Some(404)
+}
+
+/// Wait until the named object no longer exists.
+pub fn await_deletion(
+    client: &Client,
+    gvr: &GroupVersionResource,
+    namespace: Option<&str>,
+    name: &str,
+) -> impl Future<Item = (), Error = Error> + Send {
+    await_deletion_uid(client, gvr, namespace, name, None)
+}
+
+/// Like `await_deletion`, but only for the object with `uid`: succeeds
+/// if it has already been replaced by another of the same name, eg: a
+/// StatefulSet pod.
+pub fn await_deletion_of(
+    client: &Client,
+    gvr: &GroupVersionResource,
+    namespace: Option<&str>,
+    name: &str,
+    uid: &str,
+) -> impl Future<Item = (), Error = Error> + Send {
+    await_deletion_uid(client, gvr, namespace, name, Some(uid.to_string()))
+}
+
+fn await_deletion_uid(
+    client: &Client,
+    gvr: &GroupVersionResource,
+    namespace: Option<&str>,
+    name: &str,
+    uid: Option<String>,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let client2 = client.clone();
+    let gvr2 = owned_gvr(gvr);
+    let namespace2 = namespace.map(String::from);
+    let name2 = name.to_string();
+
+    // Watching alone would never finish if the object is already gone
+    client
+        .get_opt(gvr, namespace, name, Default::default())
+        .map(move |obj: Option<Value>| {
+            obj.filter(|o| uid.as_ref().is_none_or(|uid| o["metadata"]["uid"] == **uid))
+                .map(|o| resource_version(&o))
+        })
+        .and_then(move |rv| match rv {
+            None => future::Either::A(future::ok(())),
+            Some(rv) => future::Either::B(watch_object(
+                client2,
+                gvr2,
+                namespace2,
+                name2,
+                rv,
+                |typ, _| {
+                    Ok(if typ == EventType::Deleted {
+                        Some(())
+                    } else {
+                        None
+                    })
+                },
+            )),
+        })
+}
+
+/// Delete an object and wait until it is fully gone, including any
+/// finalizer processing.  Succeeds if the object does not exist.
+///
+/// Fails with `DeletionStalledError`, listing the finalizers still
+/// pending, if the object remains after `after`.  Requires a tokio
+/// runtime with a timer.
+pub fn delete_and_wait(
+    client: &Client,
+    gvr: &GroupVersionResource,
+    namespace: Option<&str>,
+    name: &str,
+    opts: DeleteOptions,
+    after: Duration,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let client2 = client.clone();
+    let gvr2 = owned_gvr(gvr);
+    let namespace2 = namespace.map(String::from);
+    let name2 = name.to_string();
+
+    client
+        .delete(gvr, namespace, name, opts)
+        .or_else(|e| if is_not_found(&e) { Ok(()) } else { Err(e) })
+        .and_then(move |()| {
+            let gvr = GroupVersionResource {
+                group: &gvr2.0,
+                version: &gvr2.1,
+                resource: &gvr2.2,
+            };
+            let namespace = namespace2.as_deref();
+            let wait = timeout(await_deletion(&client2, &gvr, namespace, &name2), after);
+            // Fetch the object again to explain the stall, unless it
+            // went just as time ran out.
+            let get = client2.get_opt(&gvr, namespace, &name2, Default::default());
+            wait.or_else(move |e| {
+                if e.downcast_ref::<TimeoutError>().is_none() {
+                    return future::Either::A(future::err(e));
+                }
+                future::Either::B(get.and_then(move |obj: Option<ObjectMetaOnly>| {
+                    match obj {
+                        None => Ok(()),
+                        Some(obj) => Err(DeletionStalledError {
+                            name: name2,
+                            after,
+                            finalizers: obj.metadata.finalizers,
+                        }
+                        .into()),
+                    }
+                }))
+            })
+        })
+}
+
+/// Just enough of any object to read its metadata.
+#[derive(Deserialize)]
+struct ObjectMetaOnly {
+    #[serde(default)]
+    metadata: ObjectMeta,
+}
+
+/// Fail with `TimeoutError` if `f` does not complete within `after`.
+///
+/// Requires a tokio runtime with a timer.
+pub fn timeout<F>(f: F, after: Duration) -> impl Future<Item = F::Item, Error = Error> + Send
+where
+    F: Future<Error = Error> + Send,
+{
+    Timeout::new(f, after).map_err(move |e| {
+        if e.is_elapsed() {
+            TimeoutError { after }.into()
+        } else if e.is_inner() {
+            e.into_inner().unwrap()
+        } else {
+            e.into_timer().unwrap().into()
+        }
+    })
+}
+
+#[test]
+fn test_timeout() {
+    use futures::future::empty;
+    use tokio::runtime::Runtime;
+
+    let mut rt = Runtime::new().unwrap();
+    let f = timeout(empty::<(), Error>(), Duration::from_millis(10));
+    let err = rt.block_on(f).unwrap_err();
+    assert!(err.downcast_ref::<TimeoutError>().is_some());
+
+    let f = timeout(future::ok::<_, Error>(42), Duration::from_secs(10));
+    assert_eq!(rt.block_on(f).unwrap(), 42);
+}
+
+#[test]
+fn test_delete_and_wait() {
+    use hyper::body::Sender;
+    use hyper::{Body, Request, Response};
+    use tokio::runtime::Runtime;
+
+    use super::config::ConfigContext;
+    use super::fake::FakeClient;
+    use super::middleware::{Middleware, Next, ResponseFuture};
+
+    /// Holds watches open without sending anything, as if their events
+    /// were lost.
+    #[derive(Debug, Default)]
+    struct StallWatches(Mutex<Vec<Sender>>);
+
+    impl Middleware for StallWatches {
+        fn call(&self, req: Request<Body>, next: Next) -> ResponseFuture {
+            if !req.uri().query().unwrap_or_default().contains("watch=") {
+                return next.run(req);
+            }
+            let (tx, body) = Body::channel();
+            self.0.lock().unwrap().push(tx);
+            Box::new(future::ok(Response::new(body)))
+        }
+    }
+
+    const CONFIGMAPS: GroupVersionResource<'static> = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let gvr = CONFIGMAPS;
+    let with_finalizer = |name: &str| {
+        json!({
+            "metadata": {"name": name, "namespace": "ns", "finalizers": ["example.com/hold"]},
+        })
+    };
+    let mut rt = Runtime::new().unwrap();
+    let client = FakeClient::new();
+    let server = client.server().clone();
+    let short = Duration::from_millis(100);
+
+    // Already gone.
+    rt.block_on(delete_and_wait(
+        &client,
+        &gvr,
+        Some("ns"),
+        "a",
+        Default::default(),
+        short,
+    ))
+    .unwrap();
+
+    server
+        .insert(&gvr, &json!({"metadata": {"name": "a", "namespace": "ns"}}))
+        .unwrap();
+    rt.block_on(delete_and_wait(
+        &client,
+        &gvr,
+        Some("ns"),
+        "a",
+        Default::default(),
+        short,
+    ))
+    .unwrap();
+    assert!(server.get(&gvr, Some("ns"), "a").is_none());
+
+    // Held up by a finalizer.
+    server.insert(&gvr, &with_finalizer("b")).unwrap();
+    let err = rt
+        .block_on(delete_and_wait(
+            &client,
+            &gvr,
+            Some("ns"),
+            "b",
+            Default::default(),
+            short,
+        ))
+        .unwrap_err();
+    let err = err.downcast::<DeletionStalledError>().unwrap();
+    assert_eq!(err.name, "b");
+    assert_eq!(err.finalizers, vec!["example.com/hold"]);
+
+    // Gone just as time runs out, unseen by the watch.
+    let mut config: ConfigContext = Default::default();
+    config.cluster.server = "http://fake.invalid".to_string();
+    let stalled = Client::builder()
+        .config(config)
+        .middleware(Arc::new(StallWatches::default()))
+        .build_with_backend(Arc::new(server.clone()))
+        .unwrap();
+    server.insert(&gvr, &with_finalizer("c")).unwrap();
+    let finalize = {
+        let (client, server) = (client.clone(), server.clone());
+        tokio::timer::Delay::new(std::time::Instant::now() + short / 2)
+            .from_err::<Error>()
+            .and_then(move |()| {
+                let mut stored = server.get(&CONFIGMAPS, Some("ns"), "c").unwrap();
+                stored["metadata"]["finalizers"] = json!([]);
+                client.update(&CONFIGMAPS, &stored)
+            })
+            .map(|_: Value| ())
+    };
+    let wait = delete_and_wait(&stalled, &gvr, Some("ns"), "c", Default::default(), short);
+    rt.block_on(wait.join(finalize)).unwrap();
+    assert!(server.get(&gvr, Some("ns"), "c").is_none());
+}
@@ -0,0 +1,221 @@
+//! Warnings from the apiserver.
+//!
+//! The apiserver warns about deprecated APIs (and admission webhooks
+//! about anything they like) with `Warning: 299 - "..."` response
+//! headers.  Each warning of each response is passed to the client's
+//! `WarningHandler`, which by default logs it once.
+//!
+//! ```no_run
+//! # extern crate kubernetes_holding;
+//! # use std::sync::Arc;
+//! # use kubernetes_holding::client::Client;
+//! # use kubernetes_holding::client::warning::WarningCollector;
+//! # fn main() -> Result<(), failure::Error> {
+//! let warnings = Arc::new(WarningCollector::new());
+//! let client = Client::builder().warning_handler(warnings.clone()).build()?;
+//! // ...
+//! for (request, warning) in warnings.take() {
+//!     println!("{}: {}", request, warning.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use futures::Future;
+use hyper::header::WARNING;
+use hyper::{Body, Request};
+
+use super::middleware::{Middleware, Next, ResponseFuture};
+use super::transport::describe;
+
+/// One warning from a `Warning` header.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Warning {
+    /// Who sent it, usually `-` for the apiserver itself.
+    pub agent: String,
+    pub text: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Receives the warnings sent in response to the client's requests.
+pub trait WarningHandler: fmt::Debug + Send + Sync {
+    /// `request` (named as in errors, eg: `GET /apis/policy/v1beta1/...
+    /// (list policy/v1beta1/poddisruptionbudgets)`) got `warning`.
+    fn handle(&self, request: &str, warning: &Warning);
+}
+
+/// Logs each distinct warning (at `warn` level) the first time it is
+/// sent, so that a controller's periodic resync doesn't repeat it.
+/// The default.
+#[derive(Debug, Default)]
+pub struct LogWarnings {
+    seen: Mutex<HashSet<Warning>>,
+}
+
+impl LogWarnings {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl WarningHandler for LogWarnings {
+    fn handle(&self, request: &str, warning: &Warning) {
+        if self.seen.lock().unwrap().insert(warning.clone()) {
+            warn!("{}: {}", request, warning);
+        }
+    }
+}
+
+/// Ignores warnings.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IgnoreWarnings;
+
+impl WarningHandler for IgnoreWarnings {
+    fn handle(&self, _request: &str, _warning: &Warning) {}
+}
+
+/// Keeps the warnings, with the requests that got them, eg: for a test
+/// to check that nothing uses a deprecated API.
+#[derive(Debug, Default)]
+pub struct WarningCollector {
+    warnings: Mutex<Vec<(String, Warning)>>,
+}
+
+impl WarningCollector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The warnings received since the last `take`.
+    pub fn take(&self) -> Vec<(String, Warning)> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+}
+
+impl WarningHandler for WarningCollector {
+    fn handle(&self, request: &str, warning: &Warning) {
+        self.warnings
+            .lock()
+            .unwrap()
+            .push((request.to_string(), warning.clone()));
+    }
+}
+
+/// Passes the warnings of each response to the handler.
+#[derive(Debug)]
+pub(crate) struct Warnings(pub(crate) Arc<dyn WarningHandler>);
+
+impl Middleware for Warnings {
+    fn call(&self, req: Request<Body>, next: Next) -> ResponseFuture {
+        let request = describe(req.method(), req.uri());
+        let handler = Arc::clone(&self.0);
+        Box::new(next.run(req).inspect(move |res| {
+            for value in res.headers().get_all(WARNING) {
+                for warning in parse(&String::from_utf8_lossy(value.as_bytes())) {
+                    handler.handle(&request, &warning);
+                }
+            }
+        }))
+    }
+}
+
+/// The `299` warnings in a `Warning` header value, which holds one or
+/// more `code agent "text" ["date"]`, separated by commas.  Parsing
+/// stops at anything malformed; other codes are ignored, as client-go
+/// does.
+pub fn parse(value: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut rest = value.trim_start();
+    while !rest.is_empty() {
+        let (code, after) = match split_token(rest) {
+            Some(split) => split,
+            None => break,
+        };
+        let (agent, after) = match split_token(after) {
+            Some(split) => split,
+            None => break,
+        };
+        let (text, mut after) = match quoted(after) {
+            Some(split) => split,
+            None => break,
+        };
+        // An optional date, which isn't kept.
+        if after.trim_start().starts_with('"') {
+            after = match quoted(after.trim_start()) {
+                Some((_, after)) => after,
+                None => break,
+            };
+        }
+        if code == "299" {
+            warnings.push(Warning {
+                agent: agent.to_string(),
+                text,
+            });
+        }
+        rest = after.trim_start();
+        match rest.strip_prefix(',') {
+            Some(next) => rest = next.trim_start(),
+            None => break,
+        }
+    }
+    warnings
+}
+
+/// The token at the start of `s`, and what follows the space after it.
+fn split_token(s: &str) -> Option<(&str, &str)> {
+    let end = s.find(' ')?;
+    Some((&s[..end], &s[end + 1..]))
+}
+
+/// The quoted string at the start of `s`, unescaped, and what follows
+/// it.
+fn quoted(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut text = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((text, &s[i + 2..])),
+            '\\' => text.push(chars.next()?.1),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+#[test]
+fn test_parse() {
+    let warning = |text: &str| Warning {
+        agent: "-".to_string(),
+        text: text.to_string(),
+    };
+    assert_eq!(
+        parse(r#"299 - "policy/v1beta1 PodDisruptionBudget is deprecated in v1.21+""#),
+        vec![warning(
+            "policy/v1beta1 PodDisruptionBudget is deprecated in v1.21+"
+        )]
+    );
+    assert_eq!(
+        parse(
+            r#"299 - "say \"hi\"" "Wed, 21 Oct 2015 07:28:00 GMT", 199 - "old", 299 webhook "two""#
+        ),
+        vec![
+            warning(r#"say "hi""#),
+            Warning {
+                agent: "webhook".to_string(),
+                text: "two".to_string(),
+            },
+        ]
+    );
+    assert_eq!(parse(r#"299 - "first", garbage"#), vec![warning("first")]);
+    assert!(parse("299 - unquoted").is_empty());
+    assert!(parse("").is_empty());
+}
@@ -0,0 +1,11 @@
+//! The client on wasm32, where hyper and tokio don't build: `fetch`,
+//! which talks to the apiserver with the browser's `fetch`, and the
+//! `watch` decoding it shares with the native client.
+
+pub mod fetch;
+mod json;
+mod resplit;
+mod urls;
+pub mod watch;
+
+use self::json::from_json;
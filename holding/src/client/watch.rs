@@ -0,0 +1,145 @@
+//! Decoding watch responses, independently of how they are received.
+//!
+//! A watch response body is a stream of JSON objects (usually
+//! `WatchEvent`s), one per line, arriving in arbitrarily split chunks.
+//! Nothing here does any IO, so it can be used with HTTP clients other
+//! than hyper, such as the browser's `fetch` on wasm32.
+
+use failure::{Error, ResultExt};
+use futures::Stream;
+use serde::de::{Deserialize, DeserializeOwned};
+use serde_json::{self, Value};
+
+use api::meta::v1::{EventType, Status, WatchEvent, WatchEventRef};
+use api::redact_secret_data;
+use k8sclient::error::{ApiError, ClientError};
+
+use super::resplit;
+pub use super::resplit::LineTooLong;
+
+/// The longest line `lines` accepts by default.  Far more than any
+/// object the apiserver can store.
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 16 << 20;
+
+/// Split a body into lines (each including its newline, except perhaps
+/// the last).  Lines longer than `DEFAULT_MAX_LINE_LENGTH` fail with
+/// `LineTooLong`.
+pub fn lines<S>(body: S) -> impl Stream<Item = Vec<u8>, Error = Error>
+where
+    S: Stream,
+    S::Item: IntoIterator<Item = u8>,
+    Error: From<S::Error>,
+{
+    lines_limited(body, DEFAULT_MAX_LINE_LENGTH)
+}
+
+/// Like `lines`, but failing with `LineTooLong` for lines longer than
+/// `max` bytes (including the newline), and ending after that.
+pub fn lines_limited<S>(body: S, max: usize) -> impl Stream<Item = Vec<u8>, Error = Error>
+where
+    S: Stream,
+    S::Item: IntoIterator<Item = u8>,
+    Error: From<S::Error>,
+{
+    resplit::new(body, |&c| c == b'\n', max)
+}
+
+/// Log `line`, without the data of any Secrets in it.
+fn log_line(line: &[u8]) {
+    if !log_enabled!(::log::Level::Debug) {
+        return;
+    }
+    match serde_json::from_slice::<Value>(line) {
+        Ok(mut event) => {
+            redact_secret_data(&mut event);
+            debug!("Got line: {}", event);
+        }
+        Err(_) => debug!("Got line: <{} bytes of invalid JSON>", line.len()),
+    }
+}
+
+/// Decode one line of a watch response.  `T` may borrow from `line`,
+/// eg: `WatchEventRef`.
+pub fn decode_line<'a, T: Deserialize<'a>>(line: &'a [u8]) -> Result<T, Error> {
+    log_line(line);
+    let o = serde_json::from_slice(line)
+        .with_context(|e| ClientError::new_decode_error("watch line", e, line.to_vec()))?;
+    Ok(o)
+}
+
+/// Like `decode_line`, for types that don't borrow from `line`, which
+/// are decoded with simd-json if the `simd-json` feature is enabled.
+pub(crate) fn decode_line_owned<T: DeserializeOwned>(line: &[u8]) -> Result<T, Error> {
+    log_line(line);
+    super::from_json("watch line", line)
+}
+
+/// Decode one line of a watch response into a `WatchEvent<T>`, or the
+/// error an `ERROR` event reports.  The envelope is decoded borrowing
+/// from `line`, and the object straight into `T`, without an
+/// intermediate `Value`.
+pub fn decode_event<T: DeserializeOwned>(line: &[u8]) -> Result<WatchEvent<T>, Error> {
+    let event: WatchEventRef = decode_line(line)?;
+    if event.typ == EventType::Error {
+        let status: Status = event
+            .object()
+            .with_context(|e| ClientError::new_decode_error("watch error", e, line.to_vec()))?;
+        return Err(ApiError::from(status).into());
+    }
+    let object = serde_json::from_str(event.object.get())
+        .with_context(|e| ClientError::new_decode_error("watch object", e, line.to_vec()))?;
+    Ok(WatchEvent {
+        typ: event.typ,
+        object,
+    })
+}
+
+/// Decode a watch response body.
+pub fn decode<S, T>(body: S) -> impl Stream<Item = T, Error = Error>
+where
+    S: Stream,
+    S::Item: IntoIterator<Item = u8>,
+    Error: From<S::Error>,
+    T: DeserializeOwned,
+{
+    lines(body).and_then(|line| decode_line_owned(&line))
+}
+
+#[test]
+fn test_decode() {
+    use api::meta::v1::ObjectMeta;
+    use futures::{stream, Future};
+
+    let chunks: Vec<Result<Vec<u8>, Error>> = vec![
+        Ok(b"{\"type\":\"ADDED\",\"object\":{\"a\":".to_vec()),
+        Ok(b"1}}\n{\"type\":\"DELETED\",".to_vec()),
+        Ok(b"\"object\":{}}".to_vec()),
+    ];
+    let events: Vec<WatchEvent> = decode(stream::iter_result(chunks))
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].typ, EventType::Added);
+    assert_eq!(events[0].object["a"], 1);
+    assert_eq!(events[1].typ, EventType::Deleted);
+
+    let line = b"{\"type\":\"MODIFIED\",\"object\":{\"metadata\":{\"name\":\"x\"}}}\n";
+    let event: WatchEventRef = decode_line(line).unwrap();
+    assert_eq!(event.typ, EventType::Modified);
+    assert_eq!(event.metadata().unwrap().name.as_deref(), Some("x"));
+
+    let event: WatchEvent<ObjectMeta> =
+        decode_event(b"{\"type\":\"ADDED\",\"object\":{\"name\":\"x\"}}").unwrap();
+    assert_eq!(event.typ, EventType::Added);
+    assert_eq!(event.object.name.as_deref(), Some("x"));
+    let line = br#"{"type":"ERROR","object":{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"too old resource version","reason":"Expired","code":410}}"#;
+    let err = decode_event::<ObjectMeta>(line).unwrap_err();
+    assert_eq!(ApiError::from_error(&err).unwrap().code, 410);
+
+    let bad = vec![Ok::<_, Error>(b"not json\n".to_vec())];
+    assert!(decode::<_, WatchEvent>(stream::iter_result(bad))
+        .collect()
+        .wait()
+        .is_err());
+}
@@ -0,0 +1,457 @@
+//! The objects of a resource as they are, and then as they change, for
+//! controllers and caches.
+//!
+//! A watcher lists the objects, then watches them from the list's
+//! `resourceVersion`, restarting the watch where it left off whenever
+//! the apiserver ends it (after a backoff, as for failures, if the
+//! watch ended without any events).  Failures are classified: transient ones
+//! (connection errors, `429`, and 5xx other than `501`) are retried,
+//! backing off as `WatcherOptions::retry` says, while others (eg
+//! `403 Forbidden`) are passed on straight away.  Either way, the
+//! stream ends after passing on an error.
+//!
+//! If the watcher falls so far behind that its resource version has
+//! expired, it lists the objects again, after a `Restarted` event.
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # use std::collections::HashMap;
+//! # use futures::Stream;
+//! # use kubernetes_api::core::v1::Pod;
+//! # use kubernetes_holding::client::watcher::Event;
+//! # use kubernetes_holding::client::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = Client::new()?;
+//! let mut pods = HashMap::new();
+//! for event in client.resource::<Pod>().watcher(Default::default()).wait() {
+//!     match event? {
+//!         Event::InitApply(pod) | Event::Apply(pod) => {
+//!             pods.insert(pod.metadata.name.clone(), pod);
+//!         }
+//!         Event::Delete(pod) => {
+//!             pods.remove(&pod.metadata.name);
+//!         }
+//!         Event::Restarted => pods.clear(),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::mem;
+use std::time::Instant;
+
+use failure::Error;
+use futures::{Async, Future, Poll, Stream};
+use hyper::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use tokio::timer::Delay;
+
+use api::meta::v1::{EventType, ListOptions, Status, WatchEvent};
+use api::meta::GroupVersionResource;
+use k8sclient::error::ApiError;
+
+use super::pager::{ExpiredContinue, Page};
+use super::retry::RetryPolicy;
+use super::shutdown::guard_with;
+use super::Client;
+
+type BoxStream<T> = Box<dyn Stream<Item = T, Error = Error> + Send>;
+
+/// A change to the watched objects.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<T> {
+    /// An object as listed, when the watcher starts or restarts.
+    InitApply(T),
+    /// An object was created or changed.
+    Apply(T),
+    /// An object was deleted, as it was last.
+    Delete(T),
+    /// The objects are being listed again, so whatever is known of
+    /// them should be discarded: the `InitApply` events that follow are
+    /// the current state.  Deletions since the last event are not
+    /// reported.
+    Restarted,
+}
+
+/// Which objects a watcher watches, and how it retries.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatcherOptions {
+    pub label_selector: String,
+    pub field_selector: String,
+    /// After `max_retries` consecutive transient failures, the last is
+    /// passed on.
+    pub retry: RetryPolicy,
+}
+
+impl Client {
+    /// The objects of `gvr` (in `namespace`, or in all namespaces), as
+    /// they are and then as they change; see the `watcher` module.
+    ///
+    /// An object that can't be decoded as a `T` is passed on as an
+    /// error, without ending the stream.  The stream also ends when the
+    /// client's `Shutdown` (if any) is triggered.
+    pub fn watcher<T>(
+        &self,
+        gvr: &GroupVersionResource,
+        namespace: Option<&str>,
+        opts: WatcherOptions,
+    ) -> impl Stream<Item = Event<T>, Error = Error> + Send
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let watcher = Watcher {
+            client: self.clone(),
+            gvr: (
+                gvr.group.to_string(),
+                gvr.version.to_string(),
+                gvr.resource.to_string(),
+            ),
+            namespace: namespace.map(str::to_string),
+            opts: ListOptions {
+                label_selector: opts.label_selector,
+                field_selector: opts.field_selector,
+                ..Default::default()
+            },
+            retry: opts.retry,
+            listed: false,
+            failures: 0,
+            quiet_watches: 0,
+            state: State::List,
+        };
+        // Otherwise the ended watch would be restarted.
+        guard_with(self.shutdown(), watcher.and_then(decode))
+    }
+}
+
+fn decode<T: DeserializeOwned>(event: Event<Value>) -> Result<Event<T>, Error> {
+    Ok(match event {
+        Event::InitApply(object) => Event::InitApply(serde_json::from_value(object)?),
+        Event::Apply(object) => Event::Apply(serde_json::from_value(object)?),
+        Event::Delete(object) => Event::Delete(serde_json::from_value(object)?),
+        Event::Restarted => Event::Restarted,
+    })
+}
+
+enum State {
+    /// About to (re)start the list.
+    List,
+    /// With the items of the current page still to pass on.
+    Listing(BoxStream<Page<Value>>, VecDeque<Value>),
+    /// About to (re)start the watch.
+    Watch,
+    /// With whether it has passed on an event.
+    Watching(BoxStream<WatchEvent>, bool),
+    /// Backing off after a failure, before retrying.
+    Waiting(Delay, Box<State>),
+    Done,
+}
+
+struct Watcher {
+    client: Client,
+    gvr: (String, String, String),
+    namespace: Option<String>,
+    /// With the resource version to watch from.
+    opts: ListOptions,
+    retry: RetryPolicy,
+    /// Whether a list was started before, so that another is a restart.
+    listed: bool,
+    /// Since the last page or event.
+    failures: u32,
+    /// Watches in a row that ended without passing on an event.
+    quiet_watches: u32,
+    state: State,
+}
+
+impl Watcher {
+    fn gvr(&self) -> GroupVersionResource<'_> {
+        GroupVersionResource {
+            group: &self.gvr.0,
+            version: &self.gvr.1,
+            resource: &self.gvr.2,
+        }
+    }
+
+    fn list(&self) -> BoxStream<Page<Value>> {
+        let opts = ListOptions {
+            resource_version: String::new(),
+            ..self.opts.clone()
+        };
+        Box::new(
+            self.client
+                ._do_pages::<Value>(self.gvr(), self.namespace.as_deref(), opts),
+        )
+    }
+
+    fn watch(&self) -> BoxStream<WatchEvent> {
+        Box::new(
+            self.client
+                .watch_list(&self.gvr(), self.namespace.as_deref(), self.opts.clone()),
+        )
+    }
+
+    /// List again if `e` says the resource version expired, retry
+    /// `retry` after a backoff if `e` is transient, or give up.
+    fn failed(&mut self, e: Error, retry: State) -> Result<(), Error> {
+        let api = ApiError::from_error(&e);
+        if api.is_some_and(ApiError::is_expired) || e.downcast_ref::<ExpiredContinue>().is_some() {
+            warn!("Watch of {} expired, listing again: {}", self.gvr.2, e);
+            self.opts.resource_version = String::new();
+            self.state = State::List;
+            return Ok(());
+        }
+        let transient = api
+            .is_none_or(|api| StatusCode::from_u16(api.code).is_ok_and(RetryPolicy::is_retryable));
+        if !transient || self.failures >= self.retry.max_retries {
+            return Err(e);
+        }
+        let backoff = self
+            .retry
+            .delay(self.failures, api.and_then(ApiError::retry_after));
+        warn!(
+            "Watch of {} failed, retrying in {:?}: {}",
+            self.gvr.2, backoff, e
+        );
+        self.failures += 1;
+        self.state = State::Waiting(Delay::new(Instant::now() + backoff), Box::new(retry));
+        Ok(())
+    }
+}
+
+impl Stream for Watcher {
+    type Item = Event<Value>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Event<Value>>, Error> {
+        loop {
+            match mem::replace(&mut self.state, State::Done) {
+                State::List => {
+                    let restarted = mem::replace(&mut self.listed, true);
+                    self.state = State::Listing(self.list(), VecDeque::new());
+                    if restarted {
+                        return Ok(Async::Ready(Some(Event::Restarted)));
+                    }
+                }
+                State::Listing(mut pages, mut items) => {
+                    if let Some(item) = items.pop_front() {
+                        self.state = State::Listing(pages, items);
+                        return Ok(Async::Ready(Some(Event::InitApply(item))));
+                    }
+                    match pages.poll() {
+                        Ok(Async::Ready(Some(page))) => {
+                            self.failures = 0;
+                            self.opts.resource_version = page.metadata.resource_version;
+                            self.state = State::Listing(pages, page.items.into());
+                            if page.restarted {
+                                return Ok(Async::Ready(Some(Event::Restarted)));
+                            }
+                        }
+                        Ok(Async::Ready(None)) => self.state = State::Watch,
+                        Ok(Async::NotReady) => {
+                            self.state = State::Listing(pages, items);
+                            return Ok(Async::NotReady);
+                        }
+                        Err(e) => self.failed(e, State::List)?,
+                    }
+                }
+                State::Watch => self.state = State::Watching(self.watch(), false),
+                State::Watching(mut events, evented) => match events.poll() {
+                    Ok(Async::Ready(Some(event))) => {
+                        let event = match event.typ {
+                            EventType::Added | EventType::Modified => Event::Apply(event.object),
+                            EventType::Deleted => Event::Delete(event.object),
+                            EventType::Error => {
+                                let status: Status = serde_json::from_value(event.object)?;
+                                self.failed(ApiError::from(status).into(), State::Watch)?;
+                                continue;
+                            }
+                        };
+                        self.failures = 0;
+                        self.quiet_watches = 0;
+                        if let Event::Apply(ref object) | Event::Delete(ref object) = event {
+                            if let Some(rv) = object["metadata"]["resourceVersion"].as_str() {
+                                self.opts.resource_version = rv.to_string();
+                            }
+                        }
+                        self.state = State::Watching(events, true);
+                        return Ok(Async::Ready(Some(event)));
+                    }
+                    Ok(Async::Ready(None)) if evented => {
+                        debug!("Watch of {} ended", self.gvr.2);
+                        self.state = State::Watch;
+                    }
+                    Ok(Async::Ready(None)) => {
+                        // Eg: closed straight away by a proxy, which
+                        // mustn't have us reconnecting in a tight loop.
+                        let backoff = self.retry.backoff(self.quiet_watches);
+                        debug!(
+                            "Watch of {} ended without any events, restarting in {:?}",
+                            self.gvr.2, backoff
+                        );
+                        self.quiet_watches = self.quiet_watches.saturating_add(1);
+                        self.state = State::Waiting(
+                            Delay::new(Instant::now() + backoff),
+                            Box::new(State::Watch),
+                        );
+                    }
+                    Ok(Async::NotReady) => {
+                        self.state = State::Watching(events, evented);
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => self.failed(e, State::Watch)?,
+                },
+                State::Waiting(mut delay, retry) => match delay.poll()? {
+                    Async::Ready(()) => self.state = *retry,
+                    Async::NotReady => {
+                        self.state = State::Waiting(delay, retry);
+                        return Ok(Async::NotReady);
+                    }
+                },
+                State::Done => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_watcher() {
+    use api::meta::v1::StatusReason;
+
+    use super::fake::FakeClient;
+
+    let configmaps = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let client = FakeClient::new();
+    let server = client.server();
+    for name in &["a", "b"] {
+        server
+            .insert(
+                &configmaps,
+                &json!({"metadata": {"name": name, "namespace": "ns"}}),
+            )
+            .unwrap();
+    }
+    let name = |event: Event<Value>| -> Event<String> {
+        let name = |o: Value| o["metadata"]["name"].as_str().unwrap().to_string();
+        match event {
+            Event::InitApply(o) => Event::InitApply(name(o)),
+            Event::Apply(o) => Event::Apply(name(o)),
+            Event::Delete(o) => Event::Delete(name(o)),
+            Event::Restarted => Event::Restarted,
+        }
+    };
+    let mut events = client
+        .watcher(&configmaps, Some("ns"), Default::default())
+        .map(name)
+        .wait();
+    let mut next = || events.next().unwrap().unwrap();
+    assert_eq!(next(), Event::InitApply("a".to_string()));
+    assert_eq!(next(), Event::InitApply("b".to_string()));
+
+    let update = |data: Value| {
+        let mut cm = server.get(&configmaps, Some("ns"), "a").unwrap();
+        cm["data"] = data;
+        let _: Value = client.update(&configmaps, &cm).wait().unwrap();
+    };
+    update(json!({"n": "1"}));
+    assert_eq!(next(), Event::Apply("a".to_string()));
+
+    // The watch carries on where it left off.
+    server.close_watches();
+    update(json!({"n": "2"}));
+    assert_eq!(next(), Event::Apply("a".to_string()));
+
+    // Or starts again.
+    server.close_watches();
+    server.fail_next(
+        "watch",
+        "configmaps",
+        ApiError::new(StatusReason::Expired, "too old resource version"),
+    );
+    assert_eq!(next(), Event::Restarted);
+    assert_eq!(next(), Event::InitApply("a".to_string()));
+    assert_eq!(next(), Event::InitApply("b".to_string()));
+    client
+        .delete(&configmaps, Some("ns"), "b", Default::default())
+        .wait()
+        .unwrap();
+    assert_eq!(next(), Event::Delete("b".to_string()));
+
+    // Other errors end it.
+    server.close_watches();
+    server.fail_next(
+        "watch",
+        "configmaps",
+        ApiError::new(StatusReason::Forbidden, "no"),
+    );
+    let err = events.next().unwrap().unwrap_err();
+    assert!(ApiError::from_error(&err).unwrap().is_forbidden());
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn test_quiet_watches() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::future;
+    use hyper::{Body, Request, Response};
+    use tokio::runtime::Runtime;
+
+    use super::backend::Backend;
+    use super::middleware::ResponseFuture;
+
+    /// Lists nothing, and ends every watch straight away.
+    #[derive(Debug, Default)]
+    struct Closing(AtomicUsize);
+
+    impl Backend for Closing {
+        fn call(&self, req: Request<Body>) -> ResponseFuture {
+            let watch = req.uri().query().unwrap_or("").contains("watch=true");
+            let body = if watch {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Body::empty()
+            } else {
+                json!({"metadata": {"resourceVersion": "1"}, "items": []})
+                    .to_string()
+                    .into()
+            };
+            Box::new(future::ok(Response::new(body)))
+        }
+    }
+
+    let backend = Arc::new(Closing::default());
+    let mut config: super::config::ConfigContext = Default::default();
+    config.cluster.server = "https://apiserver.invalid".to_string();
+    let client = Client::builder()
+        .config(config)
+        .build_with_backend(backend.clone())
+        .unwrap();
+    let configmaps = GroupVersionResource {
+        group: "",
+        version: "v1",
+        resource: "configmaps",
+    };
+    let mut rt = Runtime::new().unwrap();
+    rt.spawn(
+        client
+            .watcher::<Value>(&configmaps, None, Default::default())
+            .for_each(|_| Ok(()))
+            .map_err(|e| panic!("{}", e)),
+    );
+    // Backing off from 100ms, there's time for only a few.
+    thread::sleep(Duration::from_millis(350));
+    let watches = backend.0.load(Ordering::SeqCst);
+    assert!((2..=5).contains(&watches), "{} watches", watches);
+    rt.shutdown_now().wait().unwrap();
+}
@@ -0,0 +1,728 @@
+//! Admission webhooks: an HTTPS server that the apiserver sends
+//! `AdmissionReview`s to, which are passed to a `Handler` by path.
+//!
+//! `validate` and `mutate` make handlers of functions over typed (or,
+//! with `serde_json::Value`, dynamic) objects; mutations are sent back
+//! to the apiserver as a JSON patch.
+//!
+//! ```no_run
+//! # extern crate futures;
+//! # extern crate kubernetes_api;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! use futures::Future;
+//! use kubernetes_api::core::v1::Pod;
+//! use kubernetes_holding::client::webhook::{mutate, validate, WebhookServer};
+//!
+//! # fn main() -> Result<(), failure::Error> {
+//! let server = WebhookServer::from_pem(
+//!     &std::fs::read("/certs/tls.crt")?,
+//!     &std::fs::read("/certs/tls.key")?,
+//! )?
+//! .handler(
+//!     "/validate",
+//!     validate(|_req, pod: Pod| match pod.spec.host_network {
+//!         true => Err("host networking is not allowed".to_string()),
+//!         false => Ok(()),
+//!     }),
+//! )
+//! .handler(
+//!     "/mutate",
+//!     mutate(|_req, pod: &mut Pod| {
+//!         pod.metadata.labels.insert("mutated".into(), "true".into());
+//!         Ok(())
+//!     }),
+//! );
+//! tokio::run(
+//!     server
+//!         .serve(&"0.0.0.0:8443".parse()?)?
+//!         .map_err(|e| eprintln!("{}", e)),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use api::admission::v1::{AdmissionRequest, AdmissionResponse, AdmissionReview, Operation};
+use api::meta::v1::{ListMeta, Status, StatusReason, StatusStatus};
+use base64;
+use failure::Error;
+use futures::{future, Async, Future, Poll, Stream};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use native_tls::{self, Identity};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+use tokio;
+use tokio::net::TcpListener;
+use tokio::timer::{Delay, Interval};
+use tokio_tls::TlsAcceptor;
+
+use super::pem;
+use super::transport::read_body;
+use super::DEFAULT_MAX_BODY_SIZE;
+
+/// A handler's decision on a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Admission {
+    pub allowed: bool,
+    /// Why the request was denied.
+    pub message: Option<String>,
+    /// The HTTP status code given to the user, if the request was denied.
+    pub code: u16,
+    /// JSON patch operations to apply to the object.
+    pub patch: Vec<Value>,
+    /// Shown to the user.
+    pub warnings: Vec<String>,
+}
+
+impl Admission {
+    pub fn allow() -> Self {
+        Admission {
+            allowed: true,
+            message: None,
+            code: 200,
+            patch: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Deny the request as forbidden.
+    pub fn deny<S: Into<String>>(message: S) -> Self {
+        Admission {
+            allowed: false,
+            message: Some(message.into()),
+            code: 403,
+            ..Admission::allow()
+        }
+    }
+
+    pub fn with_patch(mut self, patch: Vec<Value>) -> Self {
+        self.patch = patch;
+        self
+    }
+
+    pub fn with_warning<S: Into<String>>(mut self, warning: S) -> Self {
+        self.warnings.push(warning.into());
+        self
+    }
+
+    fn into_response(self, uid: String) -> AdmissionResponse {
+        let code = self.code;
+        let status = self.message.map(|message| Status {
+            metadata: ListMeta::default(),
+            code: code.into(),
+            details: None,
+            message,
+            reason: match code {
+                403 => Some(StatusReason::Forbidden),
+                500 => Some(StatusReason::InternalError),
+                _ => None,
+            },
+            status: StatusStatus::Failure,
+        });
+        let patch = if self.patch.is_empty() {
+            None
+        } else {
+            Some(base64::encode(&Value::Array(self.patch).to_string()))
+        };
+        AdmissionResponse {
+            uid,
+            allowed: self.allowed,
+            status,
+            patch_type: patch.as_ref().map(|_| "JSONPatch".to_string()),
+            patch,
+            warnings: self.warnings,
+            ..Default::default()
+        }
+    }
+}
+
+pub type AdmissionFuture = Box<dyn Future<Item = Admission, Error = Error> + Send>;
+
+/// Decides on admission requests.  An error denies the request, as an
+/// internal error.
+pub trait Handler: Send + Sync {
+    fn admit(&self, request: &AdmissionRequest) -> AdmissionFuture;
+}
+
+impl<F> Handler for F
+where
+    F: Fn(&AdmissionRequest) -> AdmissionFuture + Send + Sync,
+{
+    fn admit(&self, request: &AdmissionRequest) -> AdmissionFuture {
+        self(request)
+    }
+}
+
+/// The object being admitted, or the one being deleted.
+fn object(request: &AdmissionRequest) -> Result<&Value, Error> {
+    let object = match request.operation {
+        Operation::Delete => request.old_object.as_ref(),
+        _ => request.object.as_ref(),
+    };
+    object.ok_or_else(|| format_err!("No object in {:?} request", request.operation))
+}
+
+/// A handler that allows the request if `f` accepts the object, and
+/// denies it with `f`'s message if not.
+pub fn validate<T, F>(f: F) -> impl Handler
+where
+    T: DeserializeOwned,
+    F: Fn(&AdmissionRequest, T) -> Result<(), String> + Send + Sync,
+{
+    move |request: &AdmissionRequest| -> AdmissionFuture {
+        let result = object(request)
+            .and_then(|object| Ok(T::deserialize(object)?))
+            .map(|object| match f(request, object) {
+                Ok(()) => Admission::allow(),
+                Err(message) => Admission::deny(message),
+            });
+        Box::new(future::result(result))
+    }
+}
+
+/// A handler that lets `f` modify the object, or deny the request with a
+/// message.
+pub fn mutate<T, F>(f: F) -> impl Handler
+where
+    T: Serialize + DeserializeOwned,
+    F: Fn(&AdmissionRequest, &mut T) -> Result<(), String> + Send + Sync,
+{
+    move |request: &AdmissionRequest| -> AdmissionFuture {
+        let result = object(request).and_then(|raw| {
+            let mut object = T::deserialize(raw)?;
+            let before = serde_json::to_value(&object)?;
+            if let Err(message) = f(request, &mut object) {
+                return Ok(Admission::deny(message));
+            }
+            let after = serde_json::to_value(&object)?;
+            let mut patch = Vec::new();
+            diff(Some(raw), &before, &after, "", &mut patch);
+            Ok(Admission::allow().with_patch(patch))
+        });
+        Box::new(future::result(result))
+    }
+}
+
+/// The JSON patch that turns `original` into `modified`.
+pub fn json_patch(original: &Value, modified: &Value) -> Vec<Value> {
+    let mut patch = Vec::new();
+    diff(Some(original), original, modified, "", &mut patch);
+    patch
+}
+
+/// Add operations to `patch` that make the changes from `before` to
+/// `after` at `path` in `raw`.  `before` is `raw` as the handler's type
+/// sees it, which may have more (defaulted) fields, so only what `raw`
+/// has is patched field by field; anything else is added whole.
+fn diff(raw: Option<&Value>, before: &Value, after: &Value, path: &str, patch: &mut Vec<Value>) {
+    if before == after {
+        return;
+    }
+    match (raw, before, after) {
+        (Some(Value::Object(raw)), Value::Object(before), Value::Object(after)) => {
+            for k in before.keys() {
+                if !after.contains_key(k) && raw.contains_key(k) {
+                    patch.push(json!({"op": "remove", "path": pointer(path, k)}));
+                }
+            }
+            for (k, v) in after {
+                match before.get(k) {
+                    Some(b) => diff(raw.get(k), b, v, &pointer(path, k), patch),
+                    None => patch.push(json!({"op": "add", "path": pointer(path, k), "value": v})),
+                }
+            }
+        }
+        (raw, _, after) => patch.push(json!({
+            "op": if raw.is_some() { "replace" } else { "add" },
+            "path": path,
+            "value": after,
+        })),
+    }
+}
+
+/// The JSON pointer to the field `key` of the object at `path`.
+fn pointer(path: &str, key: &str) -> String {
+    format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// How often `WebhookServer::from_files` checks for new certificates,
+/// by default.
+pub const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The certificate a `WebhookServer` presents, which can be replaced
+/// while it runs, eg: when cert-manager renews it.  New connections use
+/// the new certificate; open ones carry on with the old.
+#[derive(Clone)]
+pub struct ServingCert {
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl fmt::Debug for ServingCert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServingCert").finish()
+    }
+}
+
+impl ServingCert {
+    fn new(identity: Identity) -> Result<Self, Error> {
+        let acceptor = native_tls::TlsAcceptor::new(identity)?.into();
+        Ok(ServingCert {
+            acceptor: Arc::new(RwLock::new(acceptor)),
+        })
+    }
+
+    /// Present `identity` from now on.
+    pub fn set(&self, identity: Identity) -> Result<(), Error> {
+        let acceptor = native_tls::TlsAcceptor::new(identity)?.into();
+        *self.acceptor.write().unwrap() = acceptor;
+        Ok(())
+    }
+
+    /// Present the PEM encoded certificate (chain) `cert`, with the key
+    /// `key`, from now on, eg: after reading them from a Secret.
+    pub fn set_pem(&self, cert: &[u8], key: &[u8]) -> Result<(), Error> {
+        self.set(identity_from_pem(cert, key)?)
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().unwrap().clone()
+    }
+}
+
+fn identity_from_pem(cert: &[u8], key: &[u8]) -> Result<Identity, Error> {
+    Ok(Identity::from_pkcs8(cert, &pem::pkcs8_key(key)?)?)
+}
+
+/// The files a certificate and key are read from, and what they held
+/// when last read.
+#[derive(Debug)]
+struct CertFiles {
+    cert: PathBuf,
+    key: PathBuf,
+    interval: Duration,
+    loaded: (Vec<u8>, Vec<u8>),
+}
+
+impl CertFiles {
+    fn read(&self) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        Ok((fs::read(&self.cert)?, fs::read(&self.key)?))
+    }
+
+    /// Switch `cert` to what the files hold, if that has changed.  The
+    /// files are compared rather than their modification times, since a
+    /// mounted Secret is updated by swapping a symlink.
+    fn reload(&mut self, cert: &ServingCert) -> Result<(), Error> {
+        let contents = self.read()?;
+        if contents != self.loaded {
+            cert.set_pem(&contents.0, &contents.1)?;
+            info!("Reloaded webhook certificate from {}", self.cert.display());
+            self.loaded = contents;
+        }
+        Ok(())
+    }
+}
+
+/// An HTTPS server for admission webhooks.
+pub struct WebhookServer {
+    cert: ServingCert,
+    files: Option<CertFiles>,
+    handlers: HashMap<String, Arc<dyn Handler>>,
+    max_body_size: usize,
+}
+
+impl fmt::Debug for WebhookServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WebhookServer")
+            .field("files", &self.files.as_ref().map(|f| (&f.cert, &f.key)))
+            .field("paths", &self.handlers.keys().collect::<Vec<_>>())
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
+}
+
+impl WebhookServer {
+    /// A server presenting the certificate (and key) `identity`.
+    pub fn new(identity: Identity) -> Result<Self, Error> {
+        Ok(WebhookServer {
+            cert: ServingCert::new(identity)?,
+            files: None,
+            handlers: HashMap::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        })
+    }
+
+    /// A server presenting the PEM encoded certificate (chain) `cert`,
+    /// with the key `key`.
+    pub fn from_pem(cert: &[u8], key: &[u8]) -> Result<Self, Error> {
+        WebhookServer::new(identity_from_pem(cert, key)?)
+    }
+
+    /// A server presenting the PEM encoded certificate (chain) and key
+    /// in the files `cert` and `key`, eg: `tls.crt` and `tls.key` of a
+    /// mounted Secret.  The files are read again every
+    /// `DEFAULT_RELOAD_INTERVAL` (see `reload_interval`), and the new
+    /// certificate used if they have changed.
+    pub fn from_files<P: AsRef<Path>, Q: AsRef<Path>>(cert: P, key: Q) -> Result<Self, Error> {
+        let files = CertFiles {
+            cert: cert.as_ref().to_path_buf(),
+            key: key.as_ref().to_path_buf(),
+            interval: DEFAULT_RELOAD_INTERVAL,
+            loaded: Default::default(),
+        };
+        let loaded = files.read()?;
+        let mut server = WebhookServer::from_pem(&loaded.0, &loaded.1)?;
+        server.files = Some(CertFiles { loaded, ..files });
+        Ok(server)
+    }
+
+    /// How often to check the files given to `from_files` for a new
+    /// certificate.
+    pub fn reload_interval(mut self, interval: Duration) -> Self {
+        if let Some(ref mut files) = self.files {
+            files.interval = interval;
+        }
+        self
+    }
+
+    /// The certificate being presented, for replacing it.
+    pub fn serving_cert(&self) -> ServingCert {
+        self.cert.clone()
+    }
+
+    /// Send the reviews posted to `path` to `handler`.
+    pub fn handler<H: Handler + 'static>(mut self, path: &str, handler: H) -> Self {
+        self.handlers.insert(path.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Reject request bodies larger than `max` bytes.
+    pub fn max_body_size(mut self, max: usize) -> Self {
+        self.max_body_size = max;
+        self
+    }
+
+    /// Listen on `addr`.  The returned future serves connections until
+    /// it is dropped, on the tokio runtime it runs on.
+    pub fn serve(self, addr: &SocketAddr) -> Result<Serving, Error> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let cert = self.cert;
+        let handlers = Arc::new(self.handlers);
+        let max_body_size = self.max_body_size;
+        let http = Http::new();
+        let reload = match self.files {
+            Some(files) => future::Either::A(reload(files, cert.clone())),
+            None => future::Either::B(future::empty()),
+        };
+        let incoming = Accept::new(listener.incoming(), ACCEPT_BACKOFF);
+        let future = incoming.for_each(move |tcp| {
+            let handlers = handlers.clone();
+            let http = http.clone();
+            let connection = cert
+                .acceptor()
+                .accept(tcp)
+                .map_err(|e| debug!("TLS handshake with webhook client failed: {}", e))
+                .and_then(move |tls| {
+                    let service = service_fn(move |req| review(&handlers, max_body_size, req));
+                    http.serve_connection(tls, service)
+                        .map_err(|e| debug!("Webhook connection failed: {}", e))
+                });
+            tokio::spawn(connection);
+            Ok(())
+        });
+        Ok(Serving {
+            local_addr,
+            future: Box::new(future.select(reload).map(|_| ()).map_err(|(e, _)| e)),
+        })
+    }
+}
+
+/// How long to stop accepting connections for after running out of
+/// file descriptors (or similar), as hyper's own server does.
+const ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Connections from `incoming`, carrying on past failures to accept
+/// them, which would otherwise end the server.
+struct Accept<S> {
+    incoming: S,
+    backoff: Duration,
+    waiting: Option<Delay>,
+}
+
+impl<S> Accept<S> {
+    fn new(incoming: S, backoff: Duration) -> Self {
+        Accept {
+            incoming,
+            backoff,
+            waiting: None,
+        }
+    }
+}
+
+impl<S: Stream<Error = io::Error>> Stream for Accept<S> {
+    type Item = S::Item;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, Error> {
+        loop {
+            if let Some(ref mut waiting) = self.waiting {
+                if waiting.poll()?.is_not_ready() {
+                    return Ok(Async::NotReady);
+                }
+            }
+            self.waiting = None;
+            match self.incoming.poll() {
+                Ok(ready) => return Ok(ready),
+                // Only that connection failed.
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::ConnectionAborted
+                        || e.kind() == io::ErrorKind::ConnectionReset
+                        || e.kind() == io::ErrorKind::ConnectionRefused
+                        || e.kind() == io::ErrorKind::Interrupted =>
+                {
+                    debug!("Failed to accept webhook connection: {}", e);
+                }
+                // Most likely EMFILE or ENFILE: accepting again straight
+                // away would only spin until connections are closed.
+                Err(e) => {
+                    warn!(
+                        "Failed to accept webhook connection, pausing for {:?}: {}",
+                        self.backoff, e
+                    );
+                    self.waiting = Some(Delay::new(Instant::now() + self.backoff));
+                }
+            }
+        }
+    }
+}
+
+/// Reload `cert` from `files` whenever they change, forever.
+fn reload(mut files: CertFiles, cert: ServingCert) -> impl Future<Item = (), Error = Error> + Send {
+    Interval::new(Instant::now() + files.interval, files.interval)
+        .from_err()
+        .for_each(move |_| {
+            // Keep the old certificate if the new one is unreadable,
+            // eg: while only one of the files has been written.
+            if let Err(e) = files.reload(&cert) {
+                warn!("Unable to reload webhook certificate: {}", e);
+            }
+            Ok(())
+        })
+}
+
+/// A running `WebhookServer`.
+pub struct Serving {
+    local_addr: SocketAddr,
+    future: Box<dyn Future<Item = (), Error = Error> + Send>,
+}
+
+impl Serving {
+    /// The address being listened on, eg: to find the port picked for
+    /// port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl fmt::Debug for Serving {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Serving")
+            .field("local_addr", &self.local_addr)
+            .finish()
+    }
+}
+
+impl Future for Serving {
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<(), Error> {
+        self.future.poll()
+    }
+}
+
+fn plain(status: StatusCode, message: String) -> Response<Body> {
+    let mut res = Response::new(Body::from(message));
+    *res.status_mut() = status;
+    res
+}
+
+/// Answer the review posted in `req`.
+fn review(
+    handlers: &HashMap<String, Arc<dyn Handler>>,
+    max_body_size: usize,
+    req: Request<Body>,
+) -> Box<dyn Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+    let handler = match handlers.get(req.uri().path()) {
+        Some(handler) => handler.clone(),
+        None => {
+            let message = format!("No webhook at {}", req.uri().path());
+            return Box::new(future::ok(plain(StatusCode::NOT_FOUND, message)));
+        }
+    };
+    if req.method() != Method::POST {
+        let message = format!("{} is not supported", req.method());
+        return Box::new(future::ok(plain(StatusCode::METHOD_NOT_ALLOWED, message)));
+    }
+    let response = read_body(req.into_body(), max_body_size)
+        .and_then(|body| {
+            let review: AdmissionReview = serde_json::from_slice(&body)?;
+            review
+                .request
+                .ok_or_else(|| format_err!("AdmissionReview has no request"))
+        })
+        .then(move |request| {
+            let request = match request {
+                Ok(request) => request,
+                Err(e) => {
+                    let res = plain(StatusCode::BAD_REQUEST, e.to_string());
+                    return future::Either::A(future::ok(res));
+                }
+            };
+            let uid = request.uid.clone();
+            let admission = handler.admit(&request).or_else(|e| {
+                Ok(Admission {
+                    code: 500,
+                    ..Admission::deny(e.to_string())
+                })
+            });
+            future::Either::B(admission.map(move |admission| {
+                let review = AdmissionReview::new(admission.into_response(uid));
+                let mut res = Response::new(Body::from(json!(review).to_string()));
+                res.headers_mut()
+                    .insert(CONTENT_TYPE, HeaderValue::from_static(api::JSON));
+                res
+            }))
+        });
+    Box::new(response)
+}
+
+#[test]
+fn test_accept_errors() {
+    use futures::stream;
+    use tokio::runtime::Runtime;
+
+    let errors = vec![
+        Err(io::Error::from_raw_os_error(24)), // EMFILE
+        Ok(1),
+        Err(io::Error::from(io::ErrorKind::ConnectionAborted)),
+        Ok(2),
+    ];
+    let backoff = Duration::from_millis(50);
+    let accept = Accept::new(stream::iter_result(errors), backoff);
+    let mut rt = Runtime::new().unwrap();
+    let start = Instant::now();
+    assert_eq!(rt.block_on(accept.collect()).unwrap(), vec![1, 2]);
+    assert!(start.elapsed() >= backoff);
+}
+
+#[test]
+fn test_json_patch() {
+    let original = json!({"a": 1, "b": {"c": [1, 2], "d/e": "x"}, "f": true});
+    let modified = json!({"a": 2, "b": {"c": [1], "d/e": "x", "g": null}});
+    assert_eq!(
+        json_patch(&original, &modified),
+        vec![
+            json!({"op": "remove", "path": "/f"}),
+            json!({"op": "replace", "path": "/a", "value": 2}),
+            json!({"op": "replace", "path": "/b/c", "value": [1]}),
+            json!({"op": "add", "path": "/b/g", "value": null}),
+        ]
+    );
+    assert!(json_patch(&original, &original).is_empty());
+}
+
+#[test]
+fn test_review() {
+    use api::core::v1::Pod;
+
+    let mut handlers: HashMap<String, Arc<dyn Handler>> = HashMap::new();
+    handlers.insert(
+        "/validate".to_string(),
+        Arc::new(validate(|_: &AdmissionRequest, pod: Pod| {
+            if pod.spec.host_network {
+                return Err("no host networking".to_string());
+            }
+            Ok(())
+        })),
+    );
+    handlers.insert(
+        "/mutate".to_string(),
+        Arc::new(mutate(|_: &AdmissionRequest, pod: &mut Pod| {
+            pod.metadata
+                .labels
+                .insert("team".to_string(), "web".to_string());
+            Ok(())
+        })),
+    );
+    let post = |path: &str, object: Value| {
+        let body = json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "1234",
+                "kind": {"group": "", "version": "v1", "kind": "Pod"},
+                "resource": {"group": "", "version": "v1", "resource": "pods"},
+                "operation": "CREATE",
+                "userInfo": {},
+                "object": object,
+            },
+        });
+        let req = Request::post(path)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = review(&handlers, 1 << 20, req).wait().unwrap();
+        let status = res.status();
+        let body = res.into_body().concat2().wait().unwrap();
+        (status, serde_json::from_slice::<Value>(&body).ok())
+    };
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {"name": "web"},
+        "spec": {"containers": [{"name": "web", "image": "nginx"}]},
+    });
+
+    let (status, res) = post("/validate", pod.clone());
+    assert_eq!(status, StatusCode::OK);
+    let res = res.unwrap();
+    assert_eq!(res["apiVersion"], "admission.k8s.io/v1");
+    assert_eq!(res["response"]["uid"], "1234");
+    assert_eq!(res["response"]["allowed"], true);
+
+    let mut host = pod.clone();
+    host["spec"]["hostNetwork"] = json!(true);
+    let (_, res) = post("/validate", host);
+    let res = res.unwrap();
+    assert_eq!(res["response"]["allowed"], false);
+    assert_eq!(res["response"]["status"]["code"], 403);
+    assert_eq!(res["response"]["status"]["message"], "no host networking");
+
+    let (_, res) = post("/mutate", pod);
+    let res = res.unwrap();
+    assert_eq!(res["response"]["patchType"], "JSONPatch");
+    let patch = base64::decode(res["response"]["patch"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&patch).unwrap(),
+        json!([{"op": "add", "path": "/metadata/labels", "value": {"team": "web"}}])
+    );
+
+    let (status, _) = post("/other", json!({}));
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, res) = post("/validate", Value::Null);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res.unwrap()["response"]["status"]["code"], 500);
+}
@@ -0,0 +1,247 @@
+//! Just enough of WebSockets (RFC 6455) for the apiserver's streaming
+//! subresources: the handshake headers, and framing.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+
+use base64;
+use failure::Error;
+use futures::{Async, Poll, Stream};
+use hyper::header::{HeaderValue, CONNECTION, UPGRADE};
+use hyper::Request;
+use tokio::io::AsyncRead;
+
+pub(crate) const CONTINUATION: u8 = 0x0;
+pub(crate) const BINARY: u8 = 0x2;
+pub(crate) const CLOSE: u8 = 0x8;
+pub(crate) const PING: u8 = 0x9;
+pub(crate) const PONG: u8 = 0xa;
+
+/// A complete message, reassembled from its frames.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Message {
+    pub(crate) opcode: u8,
+    pub(crate) data: Vec<u8>,
+}
+
+fn random_bytes(n: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(n + 8);
+    while bytes.len() < n {
+        let h = RandomState::new().build_hasher().finish();
+        bytes.extend_from_slice(&h.to_be_bytes());
+    }
+    bytes.truncate(n);
+    bytes
+}
+
+/// Add the headers asking to upgrade `req` to a WebSocket speaking one
+/// of `protocols`, in order of preference.
+pub(crate) fn upgrade_request<B>(req: &mut Request<B>, protocols: &[&str]) {
+    let headers = req.headers_mut();
+    headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert("sec-websocket-version", HeaderValue::from_static("13"));
+    let key = base64::encode(&random_bytes(16));
+    headers.insert("sec-websocket-key", HeaderValue::from_str(&key).unwrap());
+    headers.insert(
+        "sec-websocket-protocol",
+        HeaderValue::from_str(&protocols.join(", ")).unwrap(),
+    );
+}
+
+/// A single, final, masked frame, as clients must send.
+pub(crate) fn encode(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = random_bytes(4);
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        n if n < 126 => frame.push(0x80 | n as u8),
+        n if n <= 0xffff => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+    frame
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Parse the frame at the start of `buf`, returning it and its length,
+/// or `None` if `buf` doesn't hold all of it yet.
+fn decode(buf: &[u8]) -> Option<(Frame, usize)> {
+    if buf.len() < 2 {
+        return None;
+    }
+    let (fin, opcode) = (buf[0] & 0x80 != 0, buf[0] & 0x0f);
+    let masked = buf[1] & 0x80 != 0;
+    let (len, mut offset) = match buf[1] & 0x7f {
+        126 if buf.len() >= 4 => (u64::from(u16::from_be_bytes([buf[2], buf[3]])), 4),
+        127 if buf.len() >= 10 => {
+            let mut len = [0; 8];
+            len.copy_from_slice(&buf[2..10]);
+            (u64::from_be_bytes(len), 10)
+        }
+        126 | 127 => return None,
+        n => (u64::from(n), 2),
+    };
+    let mask = if masked {
+        if buf.len() < offset + 4 {
+            return None;
+        }
+        offset += 4;
+        Some(&buf[offset - 4..offset])
+    } else {
+        None
+    };
+    if ((buf.len() - offset) as u64) < len {
+        return None;
+    }
+    let end = offset + len as usize;
+    let mut payload = buf[offset..end].to_vec();
+    if let Some(mask) = mask {
+        for (b, m) in payload.iter_mut().zip(mask.iter().cycle()) {
+            *b ^= m;
+        }
+    }
+    Some((
+        Frame {
+            fin,
+            opcode,
+            payload,
+        },
+        end,
+    ))
+}
+
+/// The messages read from a WebSocket connection.
+pub(crate) struct Messages<R> {
+    read: R,
+    buf: Vec<u8>,
+    // The start of a fragmented message.
+    partial: Option<Message>,
+    eof: bool,
+}
+
+impl<R: AsyncRead> Messages<R> {
+    pub(crate) fn new(read: R) -> Self {
+        Messages {
+            read,
+            buf: Vec::new(),
+            partial: None,
+            eof: false,
+        }
+    }
+}
+
+impl<R: AsyncRead> Stream for Messages<R> {
+    type Item = Message;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Message>, Error> {
+        loop {
+            while let Some((frame, len)) = decode(&self.buf) {
+                self.buf.drain(..len);
+                let message = match (frame.opcode, self.partial.take()) {
+                    // Control frames can arrive between fragments.
+                    (opcode, partial) if opcode & 0x8 != 0 => {
+                        self.partial = partial;
+                        Message {
+                            opcode,
+                            data: frame.payload,
+                        }
+                    }
+                    (CONTINUATION, Some(mut message)) => {
+                        message.data.extend(frame.payload);
+                        message
+                    }
+                    (CONTINUATION, None) => {
+                        return Err(format_err!("WebSocket continuation frame with no message"))
+                    }
+                    (opcode, _) => Message {
+                        opcode,
+                        data: frame.payload,
+                    },
+                };
+                if frame.fin || message.opcode & 0x8 != 0 {
+                    return Ok(Async::Ready(Some(message)));
+                }
+                self.partial = Some(message);
+            }
+            if self.eof {
+                if !self.buf.is_empty() || self.partial.is_some() {
+                    return Err(format_err!("WebSocket connection closed mid-message"));
+                }
+                return Ok(Async::Ready(None));
+            }
+            let mut chunk = [0; 8192];
+            match self.read.poll_read(&mut chunk) {
+                Ok(Async::Ready(0)) => self.eof = true,
+                Ok(Async::Ready(n)) => self.buf.extend_from_slice(&chunk[..n]),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_framing() {
+    use futures::Future;
+
+    let frame = encode(BINARY, b"\x01hello");
+    assert_eq!(frame[0], 0x82);
+    assert_eq!(frame[1], 0x80 | 6);
+    let (decoded, len) = decode(&frame).unwrap();
+    assert_eq!(len, frame.len());
+    assert_eq!(decoded.payload, b"\x01hello");
+    assert!(decode(&frame[..frame.len() - 1]).is_none());
+
+    let big = vec![7; 70000];
+    let frame = encode(BINARY, &big);
+    assert_eq!(frame[1], 0x80 | 127);
+    assert_eq!(decode(&frame).unwrap().0.payload, big);
+    let frame = encode(BINARY, &big[..300]);
+    assert_eq!(frame[1], 0x80 | 126);
+    assert_eq!(decode(&frame).unwrap().0.payload.len(), 300);
+
+    // As a server sends: unmasked, and here fragmented around a ping.
+    let stream: Vec<u8> = [
+        &[0x02, 3, 1, b'a', b'b'][..],
+        &[0x89, 1, b'p'],
+        &[0x80, 1, b'c'],
+        &[0x88, 0],
+    ]
+    .concat();
+    let messages = Messages::new(&stream[..]).collect().wait().unwrap();
+    assert_eq!(
+        messages,
+        vec![
+            Message {
+                opcode: PING,
+                data: b"p".to_vec()
+            },
+            Message {
+                opcode: BINARY,
+                data: b"\x01abc".to_vec()
+            },
+            Message {
+                opcode: CLOSE,
+                data: vec![]
+            },
+        ]
+    );
+
+    assert!(Messages::new(&stream[..4]).collect().wait().is_err());
+}
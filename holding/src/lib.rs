@@ -1,27 +1,68 @@
 //! # Kubernetes client
+//!
+//! On wasm32, for the browser, `client` has only the watch decoding and
+//! a `fetch` based client, as hyper and tokio don't build there.
 
 extern crate serde;
-#[cfg_attr(test, macro_use)]
-extern crate serde_json;
-extern crate url;
-#[macro_use]
+#[cfg_attr(not(target_arch = "wasm32"), macro_use)]
 extern crate serde_derive;
+#[cfg_attr(not(target_arch = "wasm32"), macro_use)]
+extern crate serde_json;
 extern crate serde_urlencoded;
 extern crate serde_yaml;
+extern crate url;
 #[macro_use]
 extern crate failure;
 extern crate base64;
+extern crate flate2;
 extern crate futures;
+extern crate http;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate hyper;
+#[cfg(all(feature = "tls", not(target_arch = "wasm32")))]
 extern crate hyper_tls;
+#[cfg(all(feature = "tls", not(target_arch = "wasm32")))]
 extern crate native_tls;
+#[cfg(all(feature = "testing", not(target_arch = "wasm32")))]
 extern crate openssl;
+#[cfg(feature = "prometheus")]
+extern crate prometheus;
+#[cfg(all(feature = "rustls", not(target_arch = "wasm32")))]
+extern crate rustls;
+#[cfg(feature = "simd-json")]
+extern crate simd_json;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate tokio;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate tokio_core;
+#[cfg(all(feature = "tls", not(target_arch = "wasm32")))]
+extern crate tokio_tls;
+extern crate tracing;
+#[cfg(all(feature = "rustls", not(target_arch = "wasm32")))]
+extern crate webpki;
+#[cfg(all(feature = "rustls", not(target_arch = "wasm32")))]
+extern crate webpki_roots;
 #[macro_use]
 extern crate log;
 
+#[cfg(target_arch = "wasm32")]
+extern crate js_sys;
+#[cfg(target_arch = "wasm32")]
+extern crate wasm_bindgen;
+#[cfg(target_arch = "wasm32")]
+extern crate wasm_bindgen_futures;
+#[cfg(target_arch = "wasm32")]
+extern crate web_sys;
+
 extern crate kubernetes_api as api;
 extern crate kubernetes_client as k8sclient;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod client;
+#[cfg(target_arch = "wasm32")]
+#[path = "client/wasm.rs"]
 pub mod client;
+#[cfg(not(target_arch = "wasm32"))]
 mod serde_base64;
+#[cfg(all(feature = "testing", not(target_arch = "wasm32")))]
+pub mod testing;
@@ -3,19 +3,21 @@
 use base64;
 use serde::{de, Deserialize, Deserializer, Serializer};
 
-pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+pub fn serialize<B, S>(bytes: &B, serializer: S) -> Result<S::Ok, S::Error>
 where
+    B: AsRef<[u8]>,
     S: Serializer,
 {
-    serializer.collect_str(&base64::display::Base64Display::standard(bytes))
+    serializer.collect_str(&base64::display::Base64Display::standard(bytes.as_ref()))
 }
 
-pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+pub fn deserialize<'de, B, D>(deserializer: D) -> Result<B, D::Error>
 where
+    B: From<Vec<u8>>,
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    base64::decode(&s).map_err(de::Error::custom)
+    base64::decode(&s).map(B::from).map_err(de::Error::custom)
 }
 
 #[cfg(test)]
@@ -0,0 +1,510 @@
+//! End-to-end testing against a real cluster: a kind cluster, or
+//! whatever a kubeconfig context points at.
+//!
+//! Which cluster `TestCluster::from_env` uses is given by
+//! `$KUBERNETES_TEST_CLUSTER`:
+//!
+//! - unset, or `kind`: a kind cluster called `kubernetes-rs-test`,
+//! - `kind:NAME`: a kind cluster called `NAME`,
+//! - `kubeconfig`: the current context of the default kubeconfig,
+//! - `kubeconfig:CONTEXT`: the context `CONTEXT` of the default
+//!   kubeconfig, eg: `kubeconfig:minikube`.
+//!
+//! An existing kind cluster is reused, and left running.  One that is
+//! created is deleted when the `TestCluster` is dropped, unless it is
+//! kept; since creating a cluster takes a minute or so, tests should
+//! share one, eg: with `TestCluster::shared`, which keeps it for the
+//! next run too (`kind delete cluster --name kubernetes-rs-test` to
+//! clean up).  The `kind` binary is found at `$KIND`, or on the `PATH`.
+//!
+//! Each test then works in its own `Sandbox` namespace, deleted when
+//! the sandbox is dropped:
+//!
+//! ```no_run
+//! # extern crate failure;
+//! # extern crate kubernetes_holding;
+//! # extern crate tokio;
+//! use kubernetes_holding::testing::cluster::TestCluster;
+//! # fn main() -> Result<(), failure::Error> {
+//! let cluster = TestCluster::shared()?;
+//! let sandbox = cluster.sandbox("my-test")?;
+//! let pods = sandbox.client().namespace(sandbox.namespace());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use api::meta::v1::{DeleteOptions, DeletionPropagation, GetOptions};
+use api::meta::GroupVersionResource;
+use failure::{Error, ResultExt};
+use k8sclient::error::ApiError;
+use serde_json::Value;
+use serde_yaml;
+use tokio::runtime::Runtime;
+
+use client::config::api::Config;
+use client::config::{self, ConfigContext};
+use client::Client;
+
+/// Names which cluster `TestCluster::from_env` uses.
+pub const CLUSTER_ENV: &str = "KUBERNETES_TEST_CLUSTER";
+
+/// The kind cluster used by default.
+pub const DEFAULT_KIND_CLUSTER: &str = "kubernetes-rs-test";
+
+/// How long to wait for the cluster to become ready, by default.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a sandbox waits for its default service account.
+const SANDBOX_TIMEOUT: Duration = Duration::from_secs(30);
+
+const NAMESPACES: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "namespaces",
+};
+
+const SERVICE_ACCOUNTS: GroupVersionResource<'static> = GroupVersionResource {
+    group: "",
+    version: "v1",
+    resource: "serviceaccounts",
+};
+
+/// Where a `TestCluster` comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClusterSource {
+    /// The kind cluster with this name.
+    Kind(String),
+    /// This context of the default kubeconfig, or its current context.
+    Kubeconfig(Option<String>),
+}
+
+impl ClusterSource {
+    /// As given by `$KUBERNETES_TEST_CLUSTER`.
+    pub fn from_env() -> Result<Self, Error> {
+        match env::var(CLUSTER_ENV) {
+            Ok(value) => Self::parse(&value),
+            Err(env::VarError::NotPresent) => Ok(ClusterSource::Kind(DEFAULT_KIND_CLUSTER.into())),
+            Err(e) => Err(format_err!("Invalid ${}: {}", CLUSTER_ENV, e)),
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self, Error> {
+        let (kind, name) = match value.find(':') {
+            Some(i) => (&value[..i], Some(&value[i + 1..])),
+            None => (value, None),
+        };
+        match (kind, name) {
+            ("" | "kind", None) => Ok(ClusterSource::Kind(DEFAULT_KIND_CLUSTER.into())),
+            ("kind", Some(name)) if !name.is_empty() => Ok(ClusterSource::Kind(name.into())),
+            ("kubeconfig", None) => Ok(ClusterSource::Kubeconfig(None)),
+            ("kubeconfig", Some(context)) if !context.is_empty() => {
+                Ok(ClusterSource::Kubeconfig(Some(context.into())))
+            }
+            _ => Err(format_err!(
+                "Invalid ${} {:?}: expected kind[:NAME] or kubeconfig[:CONTEXT]",
+                CLUSTER_ENV,
+                value
+            )),
+        }
+    }
+}
+
+/// Configures and starts (or finds) a kind `TestCluster`.
+#[derive(Debug)]
+pub struct KindClusterBuilder {
+    name: String,
+    image: Option<String>,
+    kind: Option<PathBuf>,
+    keep: bool,
+    startup_timeout: Option<Duration>,
+}
+
+impl KindClusterBuilder {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        KindClusterBuilder {
+            name: name.into(),
+            image: None,
+            kind: None,
+            keep: false,
+            startup_timeout: None,
+        }
+    }
+
+    /// The node image to create the cluster with, eg
+    /// `kindest/node:v1.20.0`, rather than kind's default.  Not checked
+    /// when reusing a cluster.
+    pub fn image<S: Into<String>>(mut self, image: S) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    /// The kind binary to run.
+    pub fn kind<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.kind = Some(path.into());
+        self
+    }
+
+    /// Leave a cluster that was created running when the `TestCluster`
+    /// is dropped, for later runs to reuse.
+    pub fn keep(mut self, keep: bool) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// How long to wait for the cluster to become ready.  The default
+    /// is 5 minutes.
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = Some(timeout);
+        self
+    }
+
+    pub fn start(self) -> Result<TestCluster, Error> {
+        let kind = Kind {
+            binary: self
+                .kind
+                .or_else(|| env::var_os("KIND").map(PathBuf::from))
+                .unwrap_or_else(|| PathBuf::from("kind")),
+            name: self.name,
+        };
+        let timeout = self.startup_timeout.unwrap_or(STARTUP_TIMEOUT);
+        let created = if kind.exists()? {
+            debug!("Reusing kind cluster {}", kind.name);
+            false
+        } else {
+            info!("Creating kind cluster {}", kind.name);
+            kind.create(self.image.as_deref(), timeout)?;
+            true
+        };
+        // Deletes the cluster if it doesn't become ready.
+        let mut cluster = TestCluster {
+            config: Default::default(),
+            kind: Some(kind),
+            delete: created && !self.keep,
+        };
+        let kubeconfig = cluster.kind.as_ref().unwrap().kubeconfig()?;
+        cluster.config = kubeconfig.config_context(&kubeconfig.current_context)?;
+        cluster.wait_ready(timeout)?;
+        Ok(cluster)
+    }
+}
+
+/// A running cluster to test against.
+#[derive(Debug)]
+pub struct TestCluster {
+    config: ConfigContext,
+    kind: Option<Kind>,
+    /// Whether to delete the kind cluster when dropped.
+    delete: bool,
+}
+
+impl TestCluster {
+    /// The cluster named by `$KUBERNETES_TEST_CLUSTER`, as described in
+    /// the module documentation.
+    pub fn from_env() -> Result<Self, Error> {
+        match ClusterSource::from_env()? {
+            ClusterSource::Kind(name) => Self::kind(name).start(),
+            ClusterSource::Kubeconfig(context) => Self::kubeconfig(context.as_deref()),
+        }
+    }
+
+    /// The cluster named by `$KUBERNETES_TEST_CLUSTER`, started once
+    /// for the whole test binary, and kept when it exits.
+    pub fn shared() -> Result<&'static Self, Error> {
+        static SHARED: OnceLock<Result<TestCluster, String>> = OnceLock::new();
+        let cluster = SHARED.get_or_init(|| {
+            let cluster = match ClusterSource::from_env() {
+                Ok(ClusterSource::Kind(name)) => Self::kind(name).keep(true).start(),
+                Ok(ClusterSource::Kubeconfig(context)) => Self::kubeconfig(context.as_deref()),
+                Err(e) => Err(e),
+            };
+            cluster.map_err(|e| e.to_string())
+        });
+        cluster
+            .as_ref()
+            .map_err(|e| format_err!("Unable to start the test cluster: {}", e))
+    }
+
+    /// A kind cluster called `name`.
+    pub fn kind<S: Into<String>>(name: S) -> KindClusterBuilder {
+        KindClusterBuilder::new(name)
+    }
+
+    /// The cluster of the context `context` (or the current context) of
+    /// the default kubeconfig.
+    pub fn kubeconfig(context: Option<&str>) -> Result<Self, Error> {
+        let path = env::var_os(config::CONFIG_ENV)
+            .map(PathBuf::from)
+            .or_else(config::default_path)
+            .ok_or(format_err!("Unable to find config"))?;
+        let kubeconfig = config::load_from_file(&path)
+            .with_context(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let context = context.unwrap_or(&kubeconfig.current_context);
+        let cluster = TestCluster {
+            config: kubeconfig.config_context(context)?,
+            kind: None,
+            delete: false,
+        };
+        cluster.wait_ready(STARTUP_TIMEOUT)?;
+        Ok(cluster)
+    }
+
+    /// Config for a client of the cluster.
+    pub fn config(&self) -> &ConfigContext {
+        &self.config
+    }
+
+    /// A new client of the cluster.
+    pub fn client(&self) -> Result<Client, Error> {
+        Client::builder().config(self.config.clone()).build()
+    }
+
+    /// The name of the kind cluster, if it is one.
+    pub fn kind_name(&self) -> Option<&str> {
+        self.kind.as_ref().map(|kind| kind.name.as_str())
+    }
+
+    /// A new namespace, called `prefix` with a random suffix, once its
+    /// default service account (which pods need) exists.
+    pub fn sandbox(&self, prefix: &str) -> Result<Sandbox, Error> {
+        let sandbox = Sandbox::create(self.client()?, prefix)?;
+        sandbox.wait_for_service_account(SANDBOX_TIMEOUT)?;
+        Ok(sandbox)
+    }
+
+    fn wait_ready(&self, timeout: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        let client = self.client()?;
+        let mut rt = Runtime::new()?;
+        loop {
+            let err = match rt.block_on(client.readyz()) {
+                Ok(ref health) if health.ok => return Ok(()),
+                Ok(health) => format!(
+                    "failed checks: {}",
+                    health
+                        .failed()
+                        .map(|c| c.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Err(e) => e.to_string(),
+            };
+            if Instant::now() > deadline {
+                return Err(format_err!(
+                    "{} not ready after {:?} ({})",
+                    self.config.cluster.server,
+                    timeout,
+                    err
+                ));
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        if let (true, Some(kind)) = (self.delete, &self.kind) {
+            info!("Deleting kind cluster {}", kind.name);
+            if let Err(e) = kind.delete() {
+                warn!("Unable to delete kind cluster {}: {}", kind.name, e);
+            }
+        }
+    }
+}
+
+/// The `kind` command, for one cluster.
+#[derive(Debug)]
+struct Kind {
+    binary: PathBuf,
+    name: String,
+}
+
+impl Kind {
+    /// Run kind with `args`, returning its output.
+    fn run(&self, args: &[OsString]) -> Result<Vec<u8>, Error> {
+        let output = Command::new(&self.binary)
+            .args(args)
+            .output()
+            .with_context(|e| format!("Unable to run {}: {}", self.binary.display(), e))?;
+        if !output.status.success() {
+            return Err(format_err!(
+                "{} {} failed ({}): {}",
+                self.binary.display(),
+                args.iter()
+                    .map(|a| a.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output.stdout)
+    }
+
+    fn exists(&self) -> Result<bool, Error> {
+        let clusters = self.run(&["get".into(), "clusters".into()])?;
+        Ok(String::from_utf8_lossy(&clusters)
+            .lines()
+            .any(|line| line.trim() == self.name))
+    }
+
+    fn create(&self, image: Option<&str>, timeout: Duration) -> Result<(), Error> {
+        let mut args: Vec<OsString> = vec![
+            "create".into(),
+            "cluster".into(),
+            format!("--name={}", self.name).into(),
+            format!("--wait={}s", timeout.as_secs()).into(),
+        ];
+        if let Some(image) = image {
+            args.push(format!("--image={}", image).into());
+        }
+        self.run(&args)?;
+        Ok(())
+    }
+
+    fn kubeconfig(&self) -> Result<Config, Error> {
+        let kubeconfig = self.run(&[
+            "get".into(),
+            "kubeconfig".into(),
+            format!("--name={}", self.name).into(),
+        ])?;
+        Ok(serde_yaml::from_slice(&kubeconfig).context("Unable to parse kind's kubeconfig")?)
+    }
+
+    fn delete(&self) -> Result<(), Error> {
+        self.run(&[
+            "delete".into(),
+            "cluster".into(),
+            format!("--name={}", self.name).into(),
+        ])?;
+        Ok(())
+    }
+}
+
+/// A namespace for one test, deleted (along with everything in it)
+/// when dropped.
+#[derive(Debug)]
+pub struct Sandbox {
+    client: Client,
+    namespace: String,
+}
+
+impl Sandbox {
+    /// Create a namespace called `prefix` with a random suffix.  The
+    /// namespace is labelled with the prefix, as
+    /// `kubernetes-rs.io/test`.
+    pub fn create(client: Client, prefix: &str) -> Result<Self, Error> {
+        let namespace = json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": {
+                "generateName": format!("{}-", prefix),
+                "labels": {"kubernetes-rs.io/test": prefix},
+            },
+        });
+        let namespace: Value = Runtime::new()?
+            .block_on(client.create(&NAMESPACES, &namespace, GetOptions::default()))
+            .with_context(|e| format!("Unable to create a namespace for {}: {}", prefix, e))?;
+        let namespace = namespace["metadata"]["name"]
+            .as_str()
+            .ok_or(format_err!("Created namespace has no name"))?
+            .to_string();
+        debug!("Created sandbox namespace {}", namespace);
+        Ok(Sandbox { client, namespace })
+    }
+
+    /// A client of the cluster.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The namespace's name.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    fn wait_for_service_account(&self, timeout: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        let mut rt = Runtime::new()?;
+        loop {
+            let get = self.client.get::<Value>(
+                &SERVICE_ACCOUNTS,
+                Some(&self.namespace),
+                "default",
+                GetOptions::default(),
+            );
+            match rt.block_on(get) {
+                Ok(_) => return Ok(()),
+                Err(ref e) if ApiError::from_error(e).is_some_and(|e| e.is_not_found()) => (),
+                Err(e) => return Err(e),
+            }
+            if Instant::now() > deadline {
+                return Err(format_err!(
+                    "No default service account in {} after {:?}",
+                    self.namespace,
+                    timeout
+                ));
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let opts = DeleteOptions {
+            propagation_policy: Some(DeletionPropagation::Background),
+            ..Default::default()
+        };
+        let deleted = Runtime::new().map_err(Error::from).and_then(|mut rt| {
+            rt.block_on(self.client.delete(&NAMESPACES, None, &self.namespace, opts))
+        });
+        if let Err(e) = deleted {
+            warn!(
+                "Unable to delete sandbox namespace {}: {}",
+                self.namespace, e
+            );
+        }
+    }
+}
+
+#[test]
+fn test_cluster_source() {
+    let kind = |name: &str| Ok(ClusterSource::Kind(name.to_string()));
+    let parse = |value: &str| ClusterSource::parse(value).map_err(|e| e.to_string());
+    assert_eq!(parse("kind"), kind(DEFAULT_KIND_CLUSTER));
+    assert_eq!(parse(""), kind(DEFAULT_KIND_CLUSTER));
+    assert_eq!(parse("kind:e2e"), kind("e2e"));
+    assert_eq!(parse("kubeconfig"), Ok(ClusterSource::Kubeconfig(None)));
+    assert_eq!(
+        parse("kubeconfig:staging"),
+        Ok(ClusterSource::Kubeconfig(Some("staging".to_string())))
+    );
+    assert!(parse("kind:").is_err());
+    assert!(parse("minikube").is_err());
+}
+
+#[test]
+#[ignore] // Needs kind, and docker.
+fn test_sandbox() {
+    let cluster = TestCluster::shared().unwrap();
+    let client = cluster.client().unwrap();
+    let namespace = {
+        let sandbox = cluster.sandbox("sandbox-test").unwrap();
+        assert!(sandbox.namespace().starts_with("sandbox-test-"));
+        sandbox.namespace().to_string()
+    };
+    let ns: Value = Runtime::new()
+        .unwrap()
+        .block_on(client.get(&NAMESPACES, None, &namespace, GetOptions::default()))
+        .unwrap();
+    assert!(ns["metadata"]["deletionTimestamp"].is_string());
+}